@@ -0,0 +1,443 @@
+//! Full-screen entry browser for boxes where the Electron UI never runs.
+//!
+//! Entries are listed grouped by service. Values stay in the keystore until one is revealed or
+//! edited, and revealing or deleting asks for confirmation first.
+
+use keystore_core::{KeystoreEntry, KeystoreError, KeystoreKey, KeystoreOperations};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use zeroize::Zeroizing;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Reveal,
+    Delete,
+}
+
+enum Mode {
+    Normal,
+    Search,
+    Confirm(Action),
+    Edit(Zeroizing<String>),
+}
+
+struct App<'a> {
+    keystore: &'a dyn KeystoreOperations,
+    backend: String,
+    keys: Vec<KeystoreKey>,
+    filter: String,
+    /// Index into `visible()`
+    selected: usize,
+    mode: Mode,
+    revealed: Option<Zeroizing<String>>,
+    status: String,
+    quit: bool,
+}
+
+enum Row<'k> {
+    Service(&'k str, usize),
+    Entry(usize, &'k KeystoreKey),
+}
+
+impl<'a> App<'a> {
+    fn new(keystore: &'a dyn KeystoreOperations) -> Result<Self, KeystoreError> {
+        let mut app = Self {
+            keystore,
+            backend: keystore.backend_info().name,
+            keys: Vec::new(),
+            filter: String::new(),
+            selected: 0,
+            mode: Mode::Normal,
+            revealed: None,
+            status: String::new(),
+            quit: false,
+        };
+        app.reload()?;
+        Ok(app)
+    }
+
+    fn reload(&mut self) -> Result<(), KeystoreError> {
+        self.keys = match self.keystore.list_keys() {
+            Err(KeystoreError::PlatformNotSupported) => {
+                return Err(KeystoreError::Platform(format!(
+                    "The {} backend cannot list its entries",
+                    self.backend
+                )))
+            }
+            result => result?,
+        };
+        self.keys.sort();
+        self.select(self.selected);
+        Ok(())
+    }
+
+    /// Entries matching the search, in service order
+    fn visible(&self) -> Vec<&KeystoreKey> {
+        let filter = self.filter.to_lowercase();
+        self.keys
+            .iter()
+            .filter(|key| {
+                filter.is_empty()
+                    || key.service.to_lowercase().contains(&filter)
+                    || key.account.to_lowercase().contains(&filter)
+            })
+            .collect()
+    }
+
+    /// Visible entries with a header row before each service
+    fn rows(&self) -> Vec<Row<'_>> {
+        let visible = self.visible();
+        let mut rows = Vec::new();
+        for (i, key) in visible.iter().enumerate() {
+            if i == 0 || visible[i - 1].service != key.service {
+                let count = visible[i..]
+                    .iter()
+                    .take_while(|k| k.service == key.service)
+                    .count();
+                rows.push(Row::Service(&key.service, count));
+            }
+            rows.push(Row::Entry(i, key));
+        }
+        rows
+    }
+
+    fn current(&self) -> Option<KeystoreKey> {
+        self.visible().get(self.selected).map(|&key| key.clone())
+    }
+
+    fn select(&mut self, index: usize) {
+        let len = self.visible().len();
+        let index = index.min(len.saturating_sub(1));
+        if index != self.selected {
+            self.revealed = None;
+        }
+        self.selected = index;
+    }
+
+    fn run_action(&mut self, action: Action) -> Result<(), KeystoreError> {
+        let Some(key) = self.current() else {
+            return Ok(());
+        };
+        match action {
+            Action::Reveal => {
+                self.revealed = Some(Zeroizing::new(
+                    self.keystore.get_password(&key.service, &key.account)?,
+                ));
+            }
+            Action::Delete => {
+                self.keystore.delete_password(&key.service, &key.account)?;
+                self.status = format!("Deleted {}/{}", key.service, key.account);
+                self.revealed = None;
+                self.reload()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&mut self, value: &str) -> Result<(), KeystoreError> {
+        let Some(key) = self.current() else {
+            return Ok(());
+        };
+        self.keystore.set_password(&KeystoreEntry {
+            service: key.service.clone(),
+            account: key.account.clone(),
+            value: value.to_string(),
+            ..Default::default()
+        })?;
+        self.revealed = None;
+        self.status = format!("Saved {}/{}", key.service, key.account);
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        let result = match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Normal => {
+                self.handle_normal(key.code);
+                Ok(())
+            }
+            Mode::Search => {
+                match key.code {
+                    KeyCode::Char(c) => self.filter.push(c),
+                    KeyCode::Backspace => {
+                        self.filter.pop();
+                    }
+                    KeyCode::Esc => {
+                        self.filter.clear();
+                        self.select(0);
+                        return;
+                    }
+                    KeyCode::Enter => return,
+                    _ => {}
+                }
+                self.mode = Mode::Search;
+                self.select(0);
+                Ok(())
+            }
+            Mode::Confirm(action) => match key.code {
+                KeyCode::Char('y') => self.run_action(action),
+                _ => Ok(()),
+            },
+            Mode::Edit(mut value) => match key.code {
+                KeyCode::Enter => self.save(&value),
+                KeyCode::Esc => Ok(()),
+                code => {
+                    match code {
+                        KeyCode::Char(c) => value.push(c),
+                        KeyCode::Backspace => {
+                            value.pop();
+                        }
+                        _ => {}
+                    }
+                    self.mode = Mode::Edit(value);
+                    Ok(())
+                }
+            },
+        };
+        if let Err(e) = result {
+            self.status = format!("error: {} ({})", e, e.code());
+        }
+    }
+
+    fn handle_normal(&mut self, code: KeyCode) {
+        self.status.clear();
+        let has_entry = self.current().is_some();
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Down | KeyCode::Char('j') => self.select(self.selected + 1),
+            KeyCode::Up | KeyCode::Char('k') => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Char('h') => self.revealed = None,
+            KeyCode::Char('r') if has_entry => self.mode = Mode::Confirm(Action::Reveal),
+            KeyCode::Char('d') if has_entry => self.mode = Mode::Confirm(Action::Delete),
+            KeyCode::Char('e') if has_entry => self.mode = Mode::Edit(Zeroizing::default()),
+            _ => {}
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).areas(frame.area());
+        let [list_area, details_area] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)])
+                .areas(main);
+
+        let rows = self.rows();
+        let mut state = ListState::default();
+        let items: Vec<ListItem> = rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| match row {
+                Row::Service(service, count) => ListItem::new(Line::from(vec![
+                    Span::from(*service).bold(),
+                    Span::from(format!(" ({})", count)).dim(),
+                ])),
+                Row::Entry(index, key) => {
+                    if *index == self.selected {
+                        state.select(Some(i));
+                    }
+                    ListItem::new(format!("  {}", key.account))
+                }
+            })
+            .collect();
+        let title = if self.filter.is_empty() {
+            format!(" {} entries ", self.keys.len())
+        } else {
+            format!(" {} of {} entries ", self.visible().len(), self.keys.len())
+        };
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::bordered().title(title))
+                .highlight_style(Style::new().reversed()),
+            list_area,
+            &mut state,
+        );
+
+        let mut details = vec![Line::from(format!("Backend: {}", self.backend))];
+        if let Some(key) = self.current() {
+            let siblings = self
+                .keys
+                .iter()
+                .filter(|k| k.service == key.service)
+                .count();
+            details.push(Line::from(format!("Service: {}", key.service)));
+            details.push(Line::from(format!("Account: {}", key.account)));
+            details.push(Line::from(format!("Accounts in service: {}", siblings)));
+            details.push(Line::from(""));
+            details.push(match &self.revealed {
+                Some(value) => Line::from(format!("Value: {}", value.as_str())),
+                None => Line::from("Value: hidden (r to reveal)").dim(),
+            });
+        }
+        frame.render_widget(
+            Paragraph::new(details)
+                .block(Block::bordered().title(" Details "))
+                .wrap(Wrap { trim: false }),
+            details_area,
+        );
+
+        let prompt = match &self.mode {
+            Mode::Normal if !self.status.is_empty() => self.status.clone(),
+            Mode::Normal => {
+                "↑/↓ move  / search  r reveal  h hide  e edit  d delete  q quit".to_string()
+            }
+            Mode::Search => format!("Search: {}", self.filter),
+            Mode::Confirm(Action::Reveal) => "Show this value on screen? (y/N)".to_string(),
+            Mode::Confirm(Action::Delete) => "Delete this entry? (y/N)".to_string(),
+            Mode::Edit(value) => format!(
+                "New value: {}  (Enter to save, Esc to cancel)",
+                "•".repeat(value.chars().count())
+            ),
+        };
+        frame.render_widget(Paragraph::new(prompt).block(Block::bordered()), footer);
+    }
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<(), KeystoreError> {
+    while !app.quit {
+        terminal.draw(|frame| app.render(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                app.handle_key(key);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs the browser until the user quits
+pub fn browse(keystore: &dyn KeystoreOperations) -> Result<(), KeystoreError> {
+    let mut app = App::new(keystore)?;
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keystore_core::BackendInfo;
+    use ratatui::crossterm::event::KeyModifiers;
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Store(Mutex<BTreeMap<(String, String), String>>);
+
+    impl KeystoreOperations for Store {
+        fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+            self.0.lock().unwrap().insert(
+                (entry.service.clone(), entry.account.clone()),
+                entry.value.clone(),
+            );
+            Ok(())
+        }
+
+        fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+            self.0
+                .lock()
+                .unwrap()
+                .get(&(service.to_string(), account.to_string()))
+                .cloned()
+                .ok_or_else(|| KeystoreError::KeyNotFound(account.to_string()))
+        }
+
+        fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+            self.0
+                .lock()
+                .unwrap()
+                .remove(&(service.to_string(), account.to_string()))
+                .map(|_| ())
+                .ok_or_else(|| KeystoreError::KeyNotFound(account.to_string()))
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn backend_info(&self) -> BackendInfo {
+            BackendInfo::new("test")
+        }
+
+        fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .keys()
+                .map(|(service, account)| KeystoreKey {
+                    service: service.clone(),
+                    account: account.clone(),
+                })
+                .collect())
+        }
+    }
+
+    fn store() -> Store {
+        let store = Store::default();
+        for (service, account) in [
+            ("twitch", "bot-oauth"),
+            ("twitch", "broadcaster"),
+            ("obs", "websocket"),
+        ] {
+            store
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    value: format!("{}-value", account),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        store
+    }
+
+    fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(c),
+            };
+            app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn test_grouping_and_search() {
+        let store = store();
+        let mut app = App::new(&store).unwrap();
+
+        let rows = app.rows();
+        assert!(matches!(rows[0], Row::Service("obs", 1)));
+        assert!(matches!(rows[2], Row::Service("twitch", 2)));
+        assert_eq!(rows.len(), 5);
+
+        press(&mut app, "/bro\n");
+        assert_eq!(app.visible().len(), 1);
+        assert_eq!(app.current().unwrap().account, "broadcaster");
+    }
+
+    #[test]
+    fn test_reveal_edit_and_delete_need_confirmation() {
+        let store = store();
+        let mut app = App::new(&store).unwrap();
+
+        press(&mut app, "rn");
+        assert!(app.revealed.is_none());
+        press(&mut app, "ry");
+        assert_eq!(app.revealed.as_deref().unwrap().as_str(), "websocket-value");
+
+        press(&mut app, "enew\n");
+        assert_eq!(store.get_password("obs", "websocket").unwrap(), "new");
+        assert!(app.revealed.is_none());
+
+        press(&mut app, "dy");
+        assert!(store.get_password("obs", "websocket").is_err());
+        assert_eq!(app.keys.len(), 2);
+    }
+}
@@ -0,0 +1,92 @@
+//! Clipboard copy that clears itself, so secrets never land in terminal scrollback.
+//!
+//! The secret stays on the clipboard for a fixed time. After that the previous text contents are
+//! put back, or the clipboard is emptied if it held no text. If the user has copied something
+//! else in the meantime the clipboard is left alone.
+
+use arboard::Clipboard;
+use keystore_core::KeystoreError;
+use std::thread;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Seconds a copied secret stays on the clipboard unless `--clear-after` says otherwise
+pub const DEFAULT_CLEAR_AFTER: u64 = 30;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Restore<'a> {
+    /// Someone else owns the clipboard now
+    Leave,
+    Clear,
+    Set(&'a str),
+}
+
+fn restore_action<'a>(
+    current: Option<&str>,
+    secret: &str,
+    previous: Option<&'a str>,
+) -> Restore<'a> {
+    if current != Some(secret) {
+        return Restore::Leave;
+    }
+    match previous {
+        // Restoring a previous copy of the same secret would defeat the clearing
+        Some(previous) if previous != secret => Restore::Set(previous),
+        _ => Restore::Clear,
+    }
+}
+
+fn clipboard_error(e: arboard::Error) -> KeystoreError {
+    KeystoreError::Platform(format!("Clipboard error: {}", e))
+}
+
+/// Puts `secret` on the clipboard and blocks for `clear_after` before restoring it. The process
+/// must stay alive for that time; on X11 and Wayland it also owns the clipboard contents.
+pub fn copy_and_clear(secret: &str, clear_after: Duration) -> Result<(), KeystoreError> {
+    let mut clipboard = Clipboard::new().map_err(clipboard_error)?;
+    let previous = clipboard.get_text().ok().map(Zeroizing::new);
+
+    clipboard.set_text(secret).map_err(clipboard_error)?;
+    eprintln!(
+        "Copied to the clipboard; clearing in {} s",
+        clear_after.as_secs()
+    );
+    thread::sleep(clear_after);
+
+    let current = clipboard.get_text().ok().map(Zeroizing::new);
+    match restore_action(
+        current.as_deref().map(String::as_str),
+        secret,
+        previous.as_deref().map(String::as_str),
+    ) {
+        Restore::Leave => Ok(()),
+        Restore::Clear => clipboard.clear().map_err(clipboard_error),
+        Restore::Set(previous) => clipboard.set_text(previous).map_err(clipboard_error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_action() {
+        assert_eq!(
+            restore_action(Some("s3cret"), "s3cret", Some("notes")),
+            Restore::Set("notes")
+        );
+        assert_eq!(
+            restore_action(Some("s3cret"), "s3cret", None),
+            Restore::Clear
+        );
+        assert_eq!(
+            restore_action(Some("s3cret"), "s3cret", Some("s3cret")),
+            Restore::Clear
+        );
+        assert_eq!(
+            restore_action(Some("copied later"), "s3cret", Some("notes")),
+            Restore::Leave
+        );
+        assert_eq!(restore_action(None, "s3cret", None), Restore::Leave);
+    }
+}
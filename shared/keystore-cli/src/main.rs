@@ -0,0 +1,375 @@
+//! Command-line access to the platform keystore for headless boxes and scripts.
+
+mod browse;
+mod clipboard;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use clap::{Parser, Subcommand, ValueEnum};
+use keystore_core::backup::{diff_snapshots, recover_backup};
+use keystore_core::dotenv::{read_env_file, shred_file, EnvMappingRule};
+use keystore_core::escrow::EscrowKey;
+use keystore_core::health::{doctor_report, DEFAULT_CERT_WARNING_DAYS};
+use keystore_core::import::{apply_import, plan_import, ImportDiff};
+use keystore_core::integrity::repair as repair_integrity;
+use keystore_core::inventory::inventory;
+use keystore_core::orphans::{find_orphans, remove_orphans};
+use keystore_core::platform::backend_for;
+use keystore_core::policy::Policy;
+use keystore_core::provenance::{set_provenance, Origin, ProvenanceContext, ProvenanceKeystore};
+use keystore_core::search::search;
+use keystore_core::{KeystoreEntry, KeystoreError, KeystoreOperations};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+#[derive(Parser)]
+#[command(
+    name = "keystore-cli",
+    version,
+    about = "Read and manage keystore entries"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a secret, or copy it to the clipboard with --copy
+    Get {
+        service: String,
+        account: String,
+        /// Copy to the clipboard instead of printing, then clear it again
+        #[arg(long)]
+        copy: bool,
+        /// Seconds before the clipboard is cleared
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = clipboard::DEFAULT_CLEAR_AFTER,
+            requires = "copy"
+        )]
+        clear_after: u64,
+    },
+    /// Store a secret read from a prompt, or from stdin when piped
+    Set {
+        service: String,
+        account: String,
+    },
+    Delete {
+        service: String,
+        account: String,
+    },
+    /// List entries whose service and account match glob patterns (`*`, `?`)
+    Search {
+        service: String,
+        #[arg(default_value = "*")]
+        account: String,
+    },
+    /// Browse, search and edit entries interactively
+    Browse,
+    /// Report every stored entry without its value
+    Inventory {
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        /// Only entries last written with this origin, e.g. oauth-flow or import-keytar
+        #[arg(long)]
+        origin: Option<String>,
+    },
+    /// Generate an organization escrow keypair; prints the secret, then the public key (base64)
+    EscrowKeygen {
+        /// Add an ML-KEM-768 key so backups also resist quantum attacks; needs a build that
+        /// reads version 3 backups to restore them
+        #[arg(long)]
+        hybrid: bool,
+    },
+    /// Restore a backup with the escrow secret key; only previews the changes without --apply
+    Recover {
+        backup: PathBuf,
+        /// File whose first line is the base64 escrow secret key, e.g. escrow-keygen's output
+        #[arg(long, value_name = "FILE")]
+        escrow_key: PathBuf,
+        #[arg(long)]
+        apply: bool,
+        /// Also replace entries stored with a different value
+        #[arg(long, requires = "apply")]
+        overwrite: bool,
+    },
+    /// Show which entries were added, removed or changed between two backups, without values
+    DiffBackups {
+        older: PathBuf,
+        newer: PathBuf,
+    },
+    /// Import tokens from a .env file; only previews the changes without --apply
+    ImportEnv {
+        path: PathBuf,
+        /// VARIABLE=service/account, e.g. 'TWITCH_*=twitch/{match}'; the first match wins
+        #[arg(long = "map", value_name = "RULE", required = true)]
+        rules: Vec<String>,
+        #[arg(long)]
+        apply: bool,
+        /// Also replace entries stored with a different value
+        #[arg(long, requires = "apply")]
+        overwrite: bool,
+        /// Overwrite and delete the file afterwards without asking
+        #[arg(long, requires = "apply")]
+        shred: bool,
+    },
+    /// Check the keystore's health and the integrity of every entry
+    Doctor {
+        /// Remove the orphaned records the audit found
+        #[arg(long)]
+        repair: bool,
+    },
+    /// List records left without their entry, such as stray chunks; removes them with --apply
+    Cleanup {
+        #[arg(long)]
+        apply: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Csv,
+}
+
+fn read_value() -> Result<Zeroizing<String>, KeystoreError> {
+    if io::stdin().is_terminal() {
+        return Ok(Zeroizing::new(rpassword::prompt_password("Value: ")?));
+    }
+    let mut line = Zeroizing::new(String::new());
+    io::stdin().lock().read_line(&mut line)?;
+    let trimmed = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(trimmed);
+    Ok(line)
+}
+
+fn confirm(question: &str) -> Result<bool, KeystoreError> {
+    if !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn print_diff(diff: &ImportDiff) {
+    let sections = [
+        ("+", &diff.added),
+        ("~", &diff.changed),
+        ("=", &diff.unchanged),
+    ];
+    for (marker, keys) in sections {
+        for key in keys {
+            println!("{} {}\t{}", marker, key.service, key.account);
+        }
+    }
+    for conflict in &diff.conflicts {
+        println!(
+            "! {}\t{}\t{}",
+            conflict.key.service, conflict.key.account, conflict.reason
+        );
+    }
+}
+
+fn run(command: Command, keystore: &dyn KeystoreOperations) -> Result<(), KeystoreError> {
+    match command {
+        Command::Get {
+            service,
+            account,
+            copy,
+            clear_after,
+        } => {
+            let value = Zeroizing::new(keystore.get_password(&service, &account)?);
+            if copy {
+                clipboard::copy_and_clear(&value, Duration::from_secs(clear_after))
+            } else {
+                println!("{}", value.as_str());
+                Ok(())
+            }
+        }
+        Command::Set { service, account } => {
+            let value = read_value()?;
+            keystore.set_password(&KeystoreEntry {
+                service,
+                account,
+                value: value.to_string(),
+                ..Default::default()
+            })
+        }
+        Command::Delete { service, account } => keystore.delete_password(&service, &account),
+        Command::Search { service, account } => {
+            for key in search(keystore, &service, &account)? {
+                println!("{}\t{}", key.service, key.account);
+            }
+            Ok(())
+        }
+        Command::Browse => browse::browse(keystore),
+        Command::Inventory { format, origin } => {
+            let mut report = inventory(keystore)?;
+            if let Some(origin) = origin {
+                report = report.filter_origin(Origin::parse(&origin)?);
+            }
+            match format {
+                Format::Json => println!("{}", report.to_json()?),
+                Format::Csv => print!("{}", report.to_csv()),
+            }
+            Ok(())
+        }
+        Command::EscrowKeygen { hybrid } => {
+            let key = if hybrid {
+                EscrowKey::generate_hybrid()?
+            } else {
+                EscrowKey::generate()?
+            };
+            println!("{}", STANDARD.encode(key.secret_key().as_slice()));
+            println!("{}", STANDARD.encode(key.public_key().to_bytes()));
+            Ok(())
+        }
+        Command::Recover {
+            backup,
+            escrow_key,
+            apply,
+            overwrite,
+        } => {
+            let encoded = Zeroizing::new(fs::read_to_string(escrow_key)?);
+            let secret = Zeroizing::new(
+                STANDARD
+                    .decode(encoded.lines().next().unwrap_or_default().trim())
+                    .map_err(|e| {
+                        KeystoreError::InvalidInput(format!("Invalid escrow key: {}", e))
+                    })?,
+            );
+            let entries =
+                recover_backup(&fs::read(backup)?, &EscrowKey::from_secret_bytes(&secret)?)?;
+            let diff = if apply {
+                apply_import(keystore, &entries, overwrite)?
+            } else {
+                plan_import(keystore, &entries)?
+            };
+            print_diff(&diff);
+            Ok(())
+        }
+        Command::DiffBackups { older, newer } => {
+            let older_passphrase = Zeroizing::new(rpassword::prompt_password(format!(
+                "Passphrase for {}: ",
+                older.display()
+            ))?);
+            let mut newer_passphrase = Zeroizing::new(rpassword::prompt_password(format!(
+                "Passphrase for {} (empty if the same): ",
+                newer.display()
+            ))?);
+            if newer_passphrase.is_empty() {
+                newer_passphrase = older_passphrase.clone();
+            }
+            let diff = diff_snapshots(
+                &fs::read(older)?,
+                &older_passphrase,
+                &fs::read(newer)?,
+                &newer_passphrase,
+            )?;
+            let sections = [
+                ("+", &diff.added),
+                ("-", &diff.removed),
+                ("~", &diff.changed),
+                ("=", &diff.unchanged),
+            ];
+            for (marker, keys) in sections {
+                for key in keys {
+                    println!("{} {}\t{}", marker, key.service, key.account);
+                }
+            }
+            Ok(())
+        }
+        Command::ImportEnv {
+            path,
+            rules,
+            apply,
+            overwrite,
+            shred,
+        } => {
+            let rules = rules
+                .iter()
+                .map(|rule| EnvMappingRule::parse(rule))
+                .collect::<Result<Vec<_>, _>>()?;
+            let import = read_env_file(&path, &rules)?;
+            let diff = if apply {
+                apply_import(keystore, &import.entries, overwrite)?
+            } else {
+                plan_import(keystore, &import.entries)?
+            };
+            print_diff(&diff);
+            for name in &import.unmapped {
+                println!("? {}\tno mapping rule", name);
+            }
+            if !apply {
+                return Ok(());
+            }
+            if !import.is_fully_imported(&diff, overwrite) {
+                eprintln!(
+                    "Not every variable was imported; keeping {}",
+                    path.display()
+                );
+            } else if shred || confirm(&format!("Shred {}?", path.display()))? {
+                shred_file(&path)?;
+                eprintln!("Shredded {}", path.display());
+            }
+            Ok(())
+        }
+        Command::Doctor { repair } => {
+            let report = doctor_report(keystore, DEFAULT_CERT_WARNING_DAYS);
+            println!("{}", report.status.as_str());
+            for warning in &report.warnings {
+                println!("! {}", warning);
+            }
+            let plan = report
+                .integrity
+                .map(|integrity| integrity.repair_plan())
+                .unwrap_or_default();
+            if repair {
+                let applied = repair_integrity(keystore, &plan)?;
+                println!("Repaired {} issues", applied);
+            } else if !plan.is_empty() {
+                eprintln!("{} issues can be repaired with --repair", plan.len());
+            }
+            Ok(())
+        }
+        Command::Cleanup { apply } => {
+            let orphans = find_orphans(keystore)?;
+            for orphan in &orphans {
+                println!("- {}\t{}", orphan.kind.as_str(), orphan.describe());
+            }
+            if apply {
+                let removed = remove_orphans(keystore, &orphans)?;
+                eprintln!("Removed {} orphaned records", removed);
+            } else if !orphans.is_empty() {
+                eprintln!("Dry run; remove them with --apply");
+            }
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    set_provenance(ProvenanceContext {
+        origin: Origin::Cli,
+        component: Some("keystore-cli".to_string()),
+    });
+    let keystore = Policy::load()
+        .and_then(|policy| backend_for(policy.backend))
+        .map(ProvenanceKeystore::new);
+    match keystore.and_then(|keystore| run(cli.command, &keystore)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {} ({})", e, e.code());
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Per-entry counts of secret reads, with the caller and purpose behind each.
+//!
+//! [`AccessCountingKeystore`] counts every value it hands out, per service and account, in a
+//! shared [`AccessCounters`], and passes each read to the listeners as a [`SecretAccess`]. Misses
+//! and failures aren't counted; the audit trail has those. Who is reading, and why, is declared by
+//! the calling thread with [`set_access_context`] or [`with_access_context`], the way
+//! [`crate::queue::set_thread_priority`] declares priority: a bot's chat handler might say caller
+//! `chat-bot`, purpose `send-message`. Counts live in memory and start over with the process.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Who reads secrets on this thread, and what for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessContext {
+    pub caller: Option<String>,
+    pub purpose: Option<String>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<AccessContext> = RefCell::new(AccessContext::default());
+}
+
+/// Context of reads made on this thread
+pub fn current_access_context() -> AccessContext {
+    CONTEXT.with(|context| context.borrow().clone())
+}
+
+/// Sets the context of reads made on this thread from now on
+pub fn set_access_context(context: AccessContext) {
+    CONTEXT.with(|current| *current.borrow_mut() = context);
+}
+
+/// Runs `f` with this thread's reads in `context`, restoring the previous context afterwards
+pub fn with_access_context<T>(context: AccessContext, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<AccessContext>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            if let Some(context) = self.0.take() {
+                set_access_context(context);
+            }
+        }
+    }
+
+    let _restore = Restore(Some(current_access_context()));
+    set_access_context(context);
+    f()
+}
+
+/// One value handed out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretAccess {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub service: String,
+    pub account: String,
+    pub caller: Option<String>,
+    pub purpose: Option<String>,
+    /// Reads of this entry since the process started, this one included
+    pub count: u64,
+}
+
+type Listener = Arc<dyn Fn(&SecretAccess) + Send + Sync>;
+
+/// Read counts and listeners, shared between the keystore and its owner
+#[derive(Default)]
+pub struct AccessCounters {
+    counts: Mutex<BTreeMap<KeystoreKey, u64>>,
+    listeners: RwLock<Vec<Listener>>,
+}
+
+impl AccessCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `listener` runs inline with the read, so it must not block for long
+    pub fn on_access(&self, listener: impl Fn(&SecretAccess) + Send + Sync + 'static) {
+        self.listeners.write().unwrap().push(Arc::new(listener));
+    }
+
+    /// Reads of each entry read at least once, in key order
+    pub fn counts(&self) -> Vec<(KeystoreKey, u64)> {
+        self.counts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, count)| (key.clone(), *count))
+            .collect()
+    }
+
+    fn record(&self, service: &str, account: &str) {
+        let key = KeystoreKey {
+            service: service.to_string(),
+            account: account.to_string(),
+        };
+        let count = {
+            let mut counts = self.counts.lock().unwrap();
+            let count = counts.entry(key).or_insert(0);
+            *count += 1;
+            *count
+        };
+        let context = current_access_context();
+        let access = SecretAccess {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            service: service.to_string(),
+            account: account.to_string(),
+            caller: context.caller,
+            purpose: context.purpose,
+            count,
+        };
+        for listener in self.listeners.read().unwrap().iter() {
+            listener(&access);
+        }
+    }
+}
+
+pub struct AccessCountingKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    counters: Arc<AccessCounters>,
+}
+
+impl AccessCountingKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>, counters: Arc<AccessCounters>) -> Self {
+        Self { inner, counters }
+    }
+}
+
+impl KeystoreOperations for AccessCountingKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.inner.set_password(entry)
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        let value = self.inner.get_password(service, account)?;
+        self.counters.record(service, account);
+        Ok(value)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.inner.delete_password(service, account)
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.list_credentials(service)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        let values = self.inner.get_many(keys)?;
+        for (key, value) in keys.iter().zip(&values) {
+            if value.is_some() {
+                self.counters.record(&key.service, &key.account);
+            }
+        }
+        Ok(values)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.inner.set_many(entries)
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.delete_many(keys)
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.delete_service(service)
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.wipe_all()
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.inner.swap(a, b)
+    }
+
+    /// Not counted as an access, since no value is read
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        let value = self.inner.get_or_create(service, account, length)?;
+        self.counters.record(service, account);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    #[test]
+    fn test_reads_are_counted_with_the_thread_context() {
+        let counters = Arc::new(AccessCounters::new());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let listener_seen = Arc::clone(&seen);
+        counters.on_access(move |access| listener_seen.lock().unwrap().push(access.clone()));
+        let keystore =
+            AccessCountingKeystore::new(Box::new(MemoryKeystore::default()), Arc::clone(&counters));
+        keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "bot".to_string(),
+                value: "token".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+
+        keystore.get_password("twitch", "bot").unwrap();
+        with_access_context(
+            AccessContext {
+                caller: Some("chat-bot".to_string()),
+                purpose: Some("send-message".to_string()),
+            },
+            || keystore.get_password("twitch", "bot").unwrap(),
+        );
+        assert!(keystore.get_password("twitch", "missing").is_err());
+        assert_eq!(current_access_context(), AccessContext::default());
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].caller, None);
+        assert_eq!(seen[1].caller.as_deref(), Some("chat-bot"));
+        assert_eq!(seen[1].purpose.as_deref(), Some("send-message"));
+        assert_eq!(seen[1].count, 2);
+        assert_eq!(counters.counts().len(), 1);
+    }
+}
@@ -0,0 +1,528 @@
+//! Alternate names for stored entries.
+//!
+//! When a channel changes its login, the token saved under the old name should stay reachable
+//! under both. [`AliasKeystore`] keeps a table of alias keys that resolve to a target entry: reads,
+//! writes and deletes through an alias act on the target. Aliases never chain; an alias of an alias
+//! points at the final target. Deleting or renaming a target updates every alias of it, so the
+//! table never points at a missing entry.
+
+use crate::blob::BlobStore;
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+/// Service holding the alias table
+pub const ALIAS_SERVICE: &str = "streaming-enhancement:aliases";
+pub(crate) const ALIAS_ACCOUNT: &str = "index";
+
+/// alias -> target
+type AliasTable = BTreeMap<KeystoreKey, KeystoreKey>;
+
+fn key(service: &str, account: &str) -> KeystoreKey {
+    KeystoreKey {
+        service: service.to_string(),
+        account: account.to_string(),
+    }
+}
+
+pub struct AliasKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    aliases: Mutex<Option<AliasTable>>,
+}
+
+impl AliasKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self {
+            inner,
+            aliases: Mutex::new(None),
+        }
+    }
+
+    /// Runs `f` on the alias table, loading it on first use. The lock is held throughout so table
+    /// updates don't interleave.
+    fn with_aliases<T>(
+        &self,
+        f: impl FnOnce(&mut AliasTable) -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        let mut guard = self
+            .aliases
+            .lock()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))?;
+        if guard.is_none() {
+            // Stored as pairs, since JSON object keys must be strings
+            let pairs: Vec<(KeystoreKey, KeystoreKey)> =
+                match self.blobs().get_structured(ALIAS_SERVICE, ALIAS_ACCOUNT) {
+                    Ok(pairs) => pairs,
+                    Err(KeystoreError::KeyNotFound(_)) => Vec::new(),
+                    Err(e) => return Err(e),
+                };
+            *guard = Some(pairs.into_iter().collect());
+        }
+        f(guard.as_mut().unwrap())
+    }
+
+    fn blobs(&self) -> BlobStore<'_> {
+        BlobStore::new(self.inner.as_ref())
+    }
+
+    fn save(&self, aliases: &AliasTable) -> Result<(), KeystoreError> {
+        let pairs: Vec<(&KeystoreKey, &KeystoreKey)> = aliases.iter().collect();
+        self.blobs()
+            .put_structured(ALIAS_SERVICE, ALIAS_ACCOUNT, &pairs)
+    }
+
+    fn resolve(&self, service: &str, account: &str) -> Result<KeystoreKey, KeystoreError> {
+        let requested = key(service, account);
+        self.with_aliases(|aliases| Ok(aliases.get(&requested).cloned().unwrap_or(requested)))
+    }
+
+    fn exists(&self, key: &KeystoreKey) -> Result<bool, KeystoreError> {
+        self.inner.has_password(&key.service, &key.account)
+    }
+
+    /// Makes `alias` resolve to `target`, which must exist. `alias` must not name a stored entry.
+    pub fn add_alias(
+        &self,
+        alias: &KeystoreKey,
+        target: &KeystoreKey,
+    ) -> Result<(), KeystoreError> {
+        self.with_aliases(|aliases| {
+            if self.exists(alias)? {
+                return Err(KeystoreError::InvalidInput(format!(
+                    "{}:{} is a stored entry and cannot be an alias",
+                    alias.service, alias.account
+                )));
+            }
+            let target = aliases
+                .get(target)
+                .cloned()
+                .unwrap_or_else(|| target.clone());
+            if &target == alias {
+                return Err(KeystoreError::InvalidInput(format!(
+                    "{}:{} cannot be an alias of itself",
+                    alias.service, alias.account
+                )));
+            }
+            if !self.exists(&target)? {
+                return Err(KeystoreError::KeyNotFound(format!(
+                    "{}:{}",
+                    target.service, target.account
+                )));
+            }
+            aliases.insert(alias.clone(), target);
+            self.save(aliases)
+        })
+    }
+
+    pub fn remove_alias(&self, alias: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.with_aliases(|aliases| match aliases.remove(alias) {
+            Some(_) => self.save(aliases),
+            None => Err(KeystoreError::KeyNotFound(format!(
+                "Alias {}:{}",
+                alias.service, alias.account
+            ))),
+        })
+    }
+
+    /// Drops the cached table, so the next call reads the one stored, e.g. after
+    /// [`crate::orphans::remove_orphans`] edited it below this layer
+    pub fn reload(&self) {
+        if let Ok(mut aliases) = self.aliases.lock() {
+            *aliases = None;
+        }
+    }
+
+    /// Every alias with its target, sorted by alias
+    pub fn aliases(&self) -> Result<Vec<(KeystoreKey, KeystoreKey)>, KeystoreError> {
+        self.with_aliases(|aliases| Ok(aliases.clone().into_iter().collect()))
+    }
+
+    /// Moves the entry at `from` (or the entry `from` is an alias of) to `to`, re-pointing its
+    /// aliases. `to` must not be in use. Returns the key the entry was moved from.
+    pub fn rename(
+        &self,
+        from: &KeystoreKey,
+        to: &KeystoreKey,
+    ) -> Result<KeystoreKey, KeystoreError> {
+        self.with_aliases(|aliases| {
+            if aliases.contains_key(to) || self.exists(to)? {
+                return Err(KeystoreError::InvalidInput(format!(
+                    "{}:{} already exists",
+                    to.service, to.account
+                )));
+            }
+            let from = aliases.get(from).cloned().unwrap_or_else(|| from.clone());
+            let value = Zeroizing::new(self.inner.get_password(&from.service, &from.account)?);
+            // Metadata is a courtesy; a backend that keeps none still moves the value
+            let metadata = self
+                .inner
+                .get_metadata(&from.service, &from.account)
+                .unwrap_or_default();
+            self.inner.set_password(&KeystoreEntry {
+                service: to.service.clone(),
+                account: to.account.clone(),
+                value: value.to_string(),
+                label: metadata.label,
+                comment: metadata.comment,
+                expires_at: metadata.expires_at,
+            })?;
+            // A protected or write-once entry, or a failing backend, may refuse the delete; drop
+            // the copy so the value doesn't live on under both names
+            if let Err(e) = self.inner.delete_password(&from.service, &from.account) {
+                let _ = self.inner.delete_password(&to.service, &to.account);
+                return Err(e);
+            }
+
+            let mut changed = false;
+            for target in aliases.values_mut().filter(|target| **target == from) {
+                *target = to.clone();
+                changed = true;
+            }
+            if changed {
+                self.save(aliases)?;
+            }
+            Ok(from)
+        })
+    }
+}
+
+impl KeystoreOperations for AliasKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        let target = self.resolve(&entry.service, &entry.account)?;
+        self.inner.set_password(&KeystoreEntry {
+            service: target.service,
+            account: target.account,
+            ..entry.clone()
+        })
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        let target = self.resolve(service, account)?;
+        self.inner.get_password(&target.service, &target.account)
+    }
+
+    /// Deletes the entry `service`/`account` resolves to, together with all of its aliases
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.with_aliases(|aliases| {
+            let requested = key(service, account);
+            let target = aliases.get(&requested).cloned().unwrap_or(requested);
+            self.inner
+                .delete_password(&target.service, &target.account)?;
+
+            let before = aliases.len();
+            aliases.retain(|_, aliased| *aliased != target);
+            if aliases.len() != before {
+                self.save(aliases)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.list_credentials(service)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        let targets = keys
+            .iter()
+            .map(|key| self.resolve(&key.service, &key.account))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.inner.get_many(&targets)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        let targets = entries
+            .iter()
+            .map(|entry| {
+                let target = self.resolve(&entry.service, &entry.account)?;
+                Ok(KeystoreEntry {
+                    service: target.service,
+                    account: target.account,
+                    ..entry.clone()
+                })
+            })
+            .collect::<Result<Vec<_>, KeystoreError>>()?;
+        self.inner.set_many(&targets)
+    }
+
+    /// Deletes the entries the keys resolve to, with all of their aliases. Returns the keys asked
+    /// for whose entry was removed.
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.with_aliases(|aliases| {
+            let targets: Vec<KeystoreKey> = keys
+                .iter()
+                .map(|key| aliases.get(key).cloned().unwrap_or_else(|| key.clone()))
+                .collect();
+            let deleted = self.inner.delete_many(&targets)?;
+
+            let before = aliases.len();
+            aliases.retain(|_, aliased| deleted.binary_search(aliased).is_err());
+            if aliases.len() != before {
+                self.save(aliases)?;
+            }
+            let mut removed: Vec<KeystoreKey> = keys
+                .iter()
+                .zip(&targets)
+                .filter(|(_, target)| deleted.binary_search(target).is_ok())
+                .map(|(key, _)| key.clone())
+                .collect();
+            removed.sort();
+            removed.dedup();
+            Ok(removed)
+        })
+    }
+
+    /// Also drops aliases under `service` and aliases of the entries removed
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.with_aliases(|aliases| {
+            let removed = self.inner.delete_service(service)?;
+            let before = aliases.len();
+            aliases.retain(|alias, target| alias.service != service && target.service != service);
+            if aliases.len() != before {
+                self.save(aliases)?;
+            }
+            Ok(removed)
+        })
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.with_aliases(|aliases| {
+            let removed = self.inner.wipe_all()?;
+            // The stored table was one of the entries removed
+            aliases.clear();
+            Ok(removed)
+        })
+    }
+
+    /// Swaps the entries `a` and `b` resolve to; aliases keep pointing at the same keys
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        let a = self.resolve(&a.service, &a.account)?;
+        let b = self.resolve(&b.service, &b.account)?;
+        self.inner.swap(&a, &b)
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        let key = self.resolve(service, account)?;
+        self.inner.get_metadata(&key.service, &key.account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        let key = self.resolve(service, account)?;
+        self.inner.has_password(&key.service, &key.account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        let target = self.resolve(service, account)?;
+        self.inner
+            .get_or_create(&target.service, &target.account, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    fn keystore() -> AliasKeystore {
+        let keystore = AliasKeystore::new(Box::new(MemoryKeystore::default()));
+        keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "new-login".to_string(),
+                value: "token".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        keystore
+    }
+
+    #[test]
+    fn test_alias_resolves_and_follows_rename() {
+        let keystore = keystore();
+        let old = key("twitch", "old-login");
+        let new = key("twitch", "new-login");
+        keystore.add_alias(&old, &new).unwrap();
+        // Aliases of aliases point at the final target
+        keystore
+            .add_alias(&key("twitch", "older-login"), &old)
+            .unwrap();
+        assert_eq!(
+            keystore.get_password("twitch", "old-login").unwrap(),
+            "token"
+        );
+
+        keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "old-login".to_string(),
+                value: "refreshed".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            keystore.get_password("twitch", "new-login").unwrap(),
+            "refreshed"
+        );
+
+        let renamed = key("twitch", "newest-login");
+        keystore.rename(&old, &renamed).unwrap();
+        assert!(keystore.inner.get_password("twitch", "new-login").is_err());
+        assert_eq!(
+            keystore.get_password("twitch", "older-login").unwrap(),
+            "refreshed"
+        );
+        assert!(keystore
+            .aliases()
+            .unwrap()
+            .iter()
+            .all(|(_, target)| *target == renamed));
+    }
+
+    #[test]
+    fn test_delete_removes_aliases_and_invalid_aliases_are_rejected() {
+        let keystore = keystore();
+        let target = key("twitch", "new-login");
+        assert!(keystore.add_alias(&target, &target).is_err());
+        assert!(matches!(
+            keystore.add_alias(&key("twitch", "x"), &key("twitch", "missing")),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+
+        keystore
+            .add_alias(&key("twitch", "old-login"), &target)
+            .unwrap();
+        keystore.delete_password("twitch", "old-login").unwrap();
+        assert!(keystore.get_password("twitch", "new-login").is_err());
+        assert!(keystore.aliases().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_renaming_a_protected_entry_leaves_it_in_place() {
+        use crate::protect::ProtectedKeystore;
+
+        let protected = ProtectedKeystore::new(Box::new(MemoryKeystore::default()));
+        protected
+            .set_password(&KeystoreEntry {
+                service: "obs".to_string(),
+                account: "stream-key".to_string(),
+                value: "live_123".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        protected.protect(&key("obs", "stream-key")).unwrap();
+        let keystore = AliasKeystore::new(Box::new(protected));
+
+        assert!(matches!(
+            keystore.rename(&key("obs", "stream-key"), &key("obs", "primary")),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        assert_eq!(
+            keystore.get_password("obs", "stream-key").unwrap(),
+            "live_123"
+        );
+        assert!(!keystore.has_password("obs", "primary").unwrap());
+    }
+
+    #[cfg(feature = "backend-file")]
+    #[test]
+    fn test_metadata_is_written_through_aliases_and_kept_on_rename() {
+        use crate::crypto::AesGcmKey;
+        use crate::platform::fallback::FallbackKeystore;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let backend = FallbackKeystore::open(
+            dir.path().join("keystore.fallback"),
+            AesGcmKey::generate().unwrap(),
+        )
+        .unwrap();
+        let keystore = AliasKeystore::new(Box::new(backend));
+        keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "bot".to_string(),
+                value: "token".to_string(),
+                label: Some("Chat bot".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        keystore
+            .add_alias(&key("twitch", "old-bot"), &key("twitch", "bot"))
+            .unwrap();
+        keystore
+            .set_many(&[KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "old-bot".to_string(),
+                value: "refreshed".to_string(),
+                comment: Some("Rotated weekly".to_string()),
+                ..Default::default()
+            }])
+            .unwrap();
+
+        keystore
+            .rename(&key("twitch", "bot"), &key("twitch", "chat-bot"))
+            .unwrap();
+        let metadata = keystore.get_metadata("twitch", "old-bot").unwrap();
+        assert_eq!(metadata.label.as_deref(), Some("Chat bot"));
+        assert_eq!(metadata.comment.as_deref(), Some("Rotated weekly"));
+    }
+
+    #[test]
+    fn test_expiry_reaches_the_layer_below() {
+        use crate::expiry::ExpiringKeystore;
+
+        let keystore = AliasKeystore::new(Box::new(ExpiringKeystore::new(Box::new(
+            MemoryKeystore::default(),
+        ))));
+        for (account, expires_at) in [("device-code", 1), ("webhook", u64::MAX)] {
+            keystore
+                .set_password(&KeystoreEntry {
+                    service: "twitch".to_string(),
+                    account: account.to_string(),
+                    value: "code".to_string(),
+                    expires_at: Some(expires_at),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        assert!(matches!(
+            keystore.get_password("twitch", "device-code"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+
+        keystore
+            .rename(&key("twitch", "webhook"), &key("twitch", "eventsub"))
+            .unwrap();
+        assert_eq!(
+            keystore
+                .get_metadata("twitch", "eventsub")
+                .unwrap()
+                .expires_at,
+            Some(u64::MAX)
+        );
+    }
+}
@@ -0,0 +1,633 @@
+//! Audit trail of keystore operations.
+//!
+//! [`AuditingKeystore`] wraps a backend and hands an [`AuditRecord`] for every read, write, delete
+//! and listing to the sinks registered on a shared [`AuditLog`]. Records name the service and
+//! account and the outcome, never the value. [`FileSink`] appends JSON lines to a file; any
+//! `Fn(&AuditRecord)` closure is a sink too, which is how the napi layer forwards records to the
+//! JS event bus.
+//!
+//! Lines written by [`FileSink`] form a hash chain: each carries the SHA-256 of the previous line's
+//! hash and its own record, so editing, removing or reordering a line breaks every hash after it.
+//! A chain alone can be recomputed by whoever rewrites the file, so a sink made
+//! [`FileSink::signed`] also puts an HMAC-SHA256 of the current hash on a record now and then, under
+//! a key derived from the `audit` master key. [`verify_audit_log`] walks the chain and checks the
+//! signatures. Removing lines from the end after the last signature can't be detected, and the
+//! chain assumes one process writes each file.
+
+use crate::access::current_access_context;
+use crate::error::KeystoreError;
+use crate::keys::MasterKeys;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// Master key the audit signing key is derived from
+pub const AUDIT_KEY: &str = "audit";
+
+const SIGNING_KEY_INFO: &[u8] = b"streaming-enhancement:audit-log:v1";
+
+/// A signed sink signs at least once per this many records...
+pub const SIGN_EVERY_RECORDS: usize = 64;
+
+/// ...and once a minute while records keep coming
+pub const SIGN_EVERY: Duration = Duration::from_secs(60);
+
+/// `prev_hash` of the first record of a chain
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    /// `get`, `set`, `delete`, `delete_service`, `wipe_all`, `swap`, `list`, `rename`,
+    /// `add_alias` or `remove_alias`
+    pub operation: String,
+    pub service: Option<String>,
+    pub account: Option<String>,
+    /// `ok` or the error code
+    pub outcome: String,
+    pub backend: String,
+    /// Why a `get` was made, as declared by the caller (see [`crate::access::AccessContext`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<String>,
+    /// The scoped keystore the call was made through (see [`crate::scope`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+pub trait AuditSink: Send + Sync {
+    /// Must not fail or block for long; it runs inline with the keystore operation
+    fn record(&self, record: &AuditRecord);
+}
+
+impl<F: Fn(&AuditRecord) + Send + Sync> AuditSink for F {
+    fn record(&self, record: &AuditRecord) {
+        self(record)
+    }
+}
+
+/// A line of a chained log file: the record's fields plus the chain
+#[derive(Serialize, Deserialize)]
+struct ChainedRecord {
+    #[serde(flatten)]
+    record: AuditRecord,
+    prev_hash: Option<String>,
+    hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+fn chain_hash(prev_hash: &str, record: &AuditRecord) -> Result<String, KeystoreError> {
+    let json =
+        serde_json::to_vec(record).map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+    Ok(hex::encode(
+        Sha256::new()
+            .chain_update(prev_hash.as_bytes())
+            .chain_update(&json)
+            .finalize(),
+    ))
+}
+
+fn signer(key: &[u8]) -> Result<Hmac<Sha256>, KeystoreError> {
+    Hmac::<Sha256>::new_from_slice(key)
+        .map_err(|e| KeystoreError::Platform(format!("Invalid signing key: {}", e)))
+}
+
+/// The key audit signatures are made with, creating the `audit` master key on first use
+pub fn audit_signing_key(
+    backend: &dyn KeystoreOperations,
+) -> Result<Zeroizing<Vec<u8>>, KeystoreError> {
+    let keys = MasterKeys::new(backend);
+    keys.ensure(AUDIT_KEY)?;
+    keys.derive(AUDIT_KEY, SIGNING_KEY_INFO, 32)
+}
+
+/// Fetches the signing key when a signature is due. It runs inline with an audited operation, so
+/// it must read from below the [`AuditingKeystore`] or it would audit itself.
+pub type AuditKeySource = Box<dyn Fn() -> Result<Zeroizing<Vec<u8>>, KeystoreError> + Send + Sync>;
+
+struct Chain {
+    file: File,
+    prev_hash: String,
+    unsigned: usize,
+    signed_at: Option<Instant>,
+}
+
+/// Appends one hash-chained JSON object per line. Write errors are dropped so auditing never fails
+/// an operation.
+pub struct FileSink {
+    chain: Mutex<Chain>,
+    key: Option<AuditKeySource>,
+}
+
+impl FileSink {
+    /// Opens `path` for appending, continuing the chain of the records already in it
+    pub fn open(path: &Path) -> Result<Self, KeystoreError> {
+        // A file written before chaining, or empty, starts a new chain
+        let prev_hash = match fs::read_to_string(path) {
+            Ok(existing) => existing
+                .lines()
+                .rev()
+                .find(|line| !line.trim().is_empty())
+                .and_then(|line| serde_json::from_str::<ChainedRecord>(line).ok())
+                .and_then(|last| last.hash),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        Ok(Self {
+            chain: Mutex::new(Chain {
+                file: options.open(path)?,
+                prev_hash: prev_hash.unwrap_or_else(|| GENESIS_HASH.to_string()),
+                unsigned: 0,
+                signed_at: None,
+            }),
+            key: None,
+        })
+    }
+
+    /// Signs the chain with the key from `key`: on the first record written, then every
+    /// [`SIGN_EVERY_RECORDS`] records or [`SIGN_EVERY`]. While the key can't be read, as when the
+    /// keystore is locked, signing waits for a later record.
+    pub fn signed(mut self, key: AuditKeySource) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    fn signature(&self, chain: &Chain, hash: &str) -> Option<String> {
+        let key = self.key.as_ref()?;
+        let due = match chain.signed_at {
+            None => true,
+            Some(at) => chain.unsigned + 1 >= SIGN_EVERY_RECORDS || at.elapsed() >= SIGN_EVERY,
+        };
+        if !due {
+            return None;
+        }
+        let mut mac = signer(&key().ok()?).ok()?;
+        mac.update(hash.as_bytes());
+        Some(hex::encode(mac.finalize().into_bytes()))
+    }
+}
+
+impl AuditSink for FileSink {
+    fn record(&self, record: &AuditRecord) {
+        let mut chain = self.chain.lock().unwrap();
+        let Ok(hash) = chain_hash(&chain.prev_hash, record) else {
+            return;
+        };
+        let signature = self.signature(&chain, &hash);
+        let signed = signature.is_some();
+        let line = ChainedRecord {
+            record: record.clone(),
+            prev_hash: Some(chain.prev_hash.clone()),
+            hash: Some(hash.clone()),
+            signature,
+        };
+        let Ok(line) = serde_json::to_string(&line) else {
+            return;
+        };
+        let written = writeln!(chain.file, "{}", line).and_then(|()| chain.file.flush());
+        // A line that didn't reach the file isn't part of the chain
+        if written.is_ok() {
+            chain.prev_hash = hash;
+            if signed {
+                chain.unsigned = 0;
+                chain.signed_at = Some(Instant::now());
+            } else {
+                chain.unsigned += 1;
+            }
+        }
+    }
+}
+
+/// What [`verify_audit_log`] found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditVerification {
+    /// Chained records checked
+    pub records: usize,
+    /// Lines written before the log was chained, at its start; they can't be verified
+    pub unchained_records: usize,
+    /// Chained records up to the last valid signature. Records after it hash correctly but could
+    /// have been written by anyone able to write the file.
+    pub signed_records: usize,
+    /// 1-based line where verification stopped: a hash that doesn't follow, or a bad signature
+    pub broken_at: Option<usize>,
+    pub problem: Option<String>,
+}
+
+impl AuditVerification {
+    pub fn is_intact(&self) -> bool {
+        self.broken_at.is_none()
+    }
+}
+
+/// Checks the hash chain of the log at `path` and its signatures under `key`, from
+/// [`audit_signing_key`]. Stops at the first line that doesn't verify.
+pub fn verify_audit_log(path: &Path, key: &[u8]) -> Result<AuditVerification, KeystoreError> {
+    let contents = fs::read_to_string(path)?;
+    let mut result = AuditVerification {
+        records: 0,
+        unchained_records: 0,
+        signed_records: 0,
+        broken_at: None,
+        problem: None,
+    };
+    let mut prev_hash: Option<String> = None;
+
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut broken = |problem: &str| {
+            result.broken_at = Some(index + 1);
+            result.problem = Some(problem.to_string());
+        };
+        let Ok(chained) = serde_json::from_str::<ChainedRecord>(line) else {
+            broken("not an audit record");
+            break;
+        };
+        let (Some(line_prev), Some(hash)) = (&chained.prev_hash, &chained.hash) else {
+            if prev_hash.is_none() {
+                result.unchained_records += 1;
+                continue;
+            }
+            broken("record is not chained");
+            break;
+        };
+        // A chain always starts at the genesis hash, even after unchained records, so a log with
+        // its first records cut off doesn't verify
+        let expected_prev = prev_hash.as_deref().unwrap_or(GENESIS_HASH);
+        if line_prev != expected_prev || *hash != chain_hash(line_prev, &chained.record)? {
+            broken("hash chain broken; a record was changed, removed or reordered");
+            break;
+        }
+        if let Some(signature) = &chained.signature {
+            let mut mac = signer(key)?;
+            mac.update(hash.as_bytes());
+            let valid =
+                hex::decode(signature).is_ok_and(|signature| mac.verify_slice(&signature).is_ok());
+            if !valid {
+                broken("invalid signature");
+                break;
+            }
+            result.signed_records = result.records + 1;
+        }
+        result.records += 1;
+        prev_hash = Some(hash.clone());
+    }
+    Ok(result)
+}
+
+/// The set of sinks records are delivered to, shared between the keystore and its owner
+#[derive(Default)]
+pub struct AuditLog {
+    sinks: RwLock<Vec<Arc<dyn AuditSink>>>,
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_sink(&self, sink: Arc<dyn AuditSink>) {
+        self.sinks.write().unwrap().push(sink);
+    }
+
+    pub fn record(&self, record: &AuditRecord) {
+        for sink in self.sinks.read().unwrap().iter() {
+            sink.record(record);
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub struct AuditingKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    log: Arc<AuditLog>,
+    backend: String,
+    scope: Option<String>,
+}
+
+impl AuditingKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>, log: Arc<AuditLog>) -> Self {
+        let backend = inner.backend_info().name;
+        Self {
+            inner,
+            log,
+            backend,
+            scope: None,
+        }
+    }
+
+    /// Records calls as made through the scope named `scope`
+    pub fn with_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = Some(scope.into());
+        self
+    }
+
+    /// Runs `op` and records it, for operations made around [`KeystoreOperations`] such as a
+    /// rename
+    pub fn audit<T>(
+        &self,
+        operation: &str,
+        key: &KeystoreKey,
+        op: impl FnOnce() -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        self.audited(operation, Some(&key.service), Some(&key.account), op())
+    }
+
+    fn audited<T>(
+        &self,
+        operation: &str,
+        service: Option<&str>,
+        account: Option<&str>,
+        result: Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        let outcome = match &result {
+            Ok(_) => "ok",
+            Err(e) => e.code(),
+        };
+        self.record(operation, service, account, outcome);
+        result
+    }
+
+    fn record(&self, operation: &str, service: Option<&str>, account: Option<&str>, outcome: &str) {
+        self.log.record(&AuditRecord {
+            timestamp: now_millis(),
+            operation: operation.to_string(),
+            service: service.map(str::to_string),
+            account: account.map(str::to_string),
+            outcome: outcome.to_string(),
+            backend: self.backend.clone(),
+            purpose: match operation {
+                "get" => current_access_context().purpose,
+                _ => None,
+            },
+            scope: self.scope.clone(),
+        });
+    }
+}
+
+impl KeystoreOperations for AuditingKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.audited(
+            "set",
+            Some(&entry.service),
+            Some(&entry.account),
+            self.inner.set_password(entry),
+        )
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.audited(
+            "get",
+            Some(service),
+            Some(account),
+            self.inner.get_password(service, account),
+        )
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.audited(
+            "delete",
+            Some(service),
+            Some(account),
+            self.inner.delete_password(service, account),
+        )
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.audited("list", None, None, self.inner.list_keys())
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.audited(
+            "list",
+            Some(service),
+            None,
+            self.inner.list_credentials(service),
+        )
+    }
+
+    /// Records one `get` per key, with `ERR_KEY_NOT_FOUND` for missing ones
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        let values = self.inner.get_many(keys);
+        for (index, key) in keys.iter().enumerate() {
+            let outcome = match &values {
+                Ok(values) if values[index].is_some() => "ok",
+                Ok(_) => "ERR_KEY_NOT_FOUND",
+                Err(e) => e.code(),
+            };
+            self.record("get", Some(&key.service), Some(&key.account), outcome);
+        }
+        values
+    }
+
+    /// Records one `set` per entry
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        let result = self.inner.set_many(entries);
+        let outcome = match &result {
+            Ok(()) => "ok",
+            Err(e) => e.code(),
+        };
+        for entry in entries {
+            self.record("set", Some(&entry.service), Some(&entry.account), outcome);
+        }
+        result
+    }
+
+    /// Records one `delete` per key, with `ERR_KEY_NOT_FOUND` for missing ones
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let removed = self.inner.delete_many(keys);
+        for key in keys {
+            let outcome = match &removed {
+                Ok(removed) if removed.binary_search(key).is_ok() => "ok",
+                Ok(_) => "ERR_KEY_NOT_FOUND",
+                Err(e) => e.code(),
+            };
+            self.record("delete", Some(&key.service), Some(&key.account), outcome);
+        }
+        removed
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.audited(
+            "delete_service",
+            Some(service),
+            None,
+            self.inner.delete_service(service),
+        )
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.audited("wipe_all", None, None, self.inner.wipe_all())
+    }
+
+    /// Records one `swap` per key
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        let result = self.inner.swap(a, b);
+        let outcome = match &result {
+            Ok(()) => "ok",
+            Err(e) => e.code(),
+        };
+        for key in [a, b] {
+            self.record("swap", Some(&key.service), Some(&key.account), outcome);
+        }
+        result
+    }
+
+    /// Not recorded, since no value is read
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        self.audited(
+            "get_or_create",
+            Some(service),
+            Some(account),
+            self.inner.get_or_create(service, account, length),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_records_reach_every_sink_without_values() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = Arc::new(AuditLog::new());
+        log.add_sink(Arc::new(FileSink::open(&path).unwrap()));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink_seen = Arc::clone(&seen);
+        log.add_sink(Arc::new(move |record: &AuditRecord| {
+            sink_seen.lock().unwrap().push(record.clone())
+        }));
+
+        let keystore = AuditingKeystore::new(Box::new(MemoryKeystore::default()), log);
+        keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "bot-oauth".to_string(),
+                value: "s3cret-token".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(keystore.get_password("twitch", "missing").is_err());
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].operation, "set");
+        assert_eq!(seen[0].account.as_deref(), Some("bot-oauth"));
+        assert_eq!(seen[1].outcome, "ERR_KEY_NOT_FOUND");
+        assert_eq!(seen[1].backend, "memory");
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<AuditRecord> = written
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines, *seen);
+        assert!(!written.contains("s3cret-token"));
+    }
+
+    #[test]
+    fn test_chained_log_detects_edits() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let backend = Arc::new(MemoryKeystore::default());
+        let key = audit_signing_key(backend.as_ref()).unwrap();
+        let record = |account: &str| AuditRecord {
+            timestamp: 1,
+            operation: "get".to_string(),
+            service: Some("twitch".to_string()),
+            account: Some(account.to_string()),
+            outcome: "ok".to_string(),
+            backend: "memory".to_string(),
+            purpose: None,
+            scope: None,
+        };
+
+        let key_backend = Arc::clone(&backend);
+        let sink = FileSink::open(&path)
+            .unwrap()
+            .signed(Box::new(move || audit_signing_key(key_backend.as_ref())));
+        sink.record(&record("bot"));
+        sink.record(&record("chat"));
+        drop(sink);
+        // Reopening continues the chain
+        FileSink::open(&path).unwrap().record(&record("obs"));
+
+        let report = verify_audit_log(&path, &key).unwrap();
+        assert!(report.is_intact());
+        assert_eq!((report.records, report.signed_records), (3, 1));
+
+        let written = fs::read_to_string(&path).unwrap();
+        fs::write(&path, written.replacen("\"chat\"", "\"other\"", 1)).unwrap();
+        let report = verify_audit_log(&path, &key).unwrap();
+        assert_eq!((report.records, report.broken_at), (1, Some(2)));
+
+        let lines: Vec<&str> = written.lines().collect();
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert_eq!(verify_audit_log(&path, &key).unwrap().broken_at, Some(2));
+
+        assert_eq!(
+            verify_audit_log(&path, b"another key")
+                .unwrap()
+                .problem
+                .as_deref(),
+            Some("invalid signature")
+        );
+
+        fs::write(&path, format!("{}\n{}\n", lines[1], lines[2])).unwrap();
+        assert_eq!(verify_audit_log(&path, &key).unwrap().broken_at, Some(1));
+    }
+}
@@ -0,0 +1,727 @@
+//! Passphrase-encrypted backups of the keystore.
+//!
+//! [`export_backup`] reads the entries the backend can enumerate into one blob encrypted with
+//! XChaCha20-Poly1305 under a key derived from the passphrase with Argon2id. [`read_backup`]
+//! decrypts a blob back into entries, which are restored through [`crate::import`] so the user sees
+//! the diff before anything is written. An [`ExportFilter`] narrows an export to some services,
+//! e.g. to move one platform's credentials to another machine, and [`export_redacted`] produces a
+//! plaintext support bundle with hashes in place of values. [`diff_snapshots`] compares two
+//! backups, e.g. last week's and today's, before one is restored.
+//!
+//! The payload is sealed under a random data key, which is wrapped under the passphrase and, for
+//! [`export_backup_with_escrow`], under each organization escrow key (see [`crate::escrow`]).
+//!
+//! Layout: `SEKB` magic, version byte, 16-byte Argon2 salt, the Argon2id parameters (memory in KiB,
+//! passes, lanes; u32 BE each), the passphrase-wrapped data key (nonce, key, tag), an escrow count
+//! byte and one stanza per escrow key, then the payload's nonce and sealed JSON. Everything before
+//! the payload is authenticated as its associated data. Version 1 backups, which have no
+//! parameters and used the Argon2 defaults, are still read.
+//!
+//! Version 3 is written only when a hybrid (X25519 + ML-KEM) escrow key is among the recipients:
+//! each stanza is preceded by a type byte, 1 for X25519 and 2 for hybrid, since the two differ in
+//! size. Backups without hybrid recipients stay version 2, so builds that predate ML-KEM can still
+//! read them.
+
+use crate::ephemeral::is_ephemeral;
+use crate::error::KeystoreError;
+use crate::escrow::{self, EscrowKey, EscrowPublicKey, HYBRID_STANZA_SIZE, STANZA_SIZE};
+use crate::generate::random_bytes;
+use crate::inventory::EntryKind;
+use crate::kdf::{KdfParams, PARAMS_SIZE};
+use crate::platform::KeystoreOperations;
+use crate::search::glob_match;
+use crate::{KeystoreEntry, KeystoreKey};
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+const MAGIC: &[u8; 4] = b"SEKB";
+const VERSION: u8 = 2;
+/// Without Argon2 parameters in the header
+const VERSION_DEFAULT_KDF: u8 = 1;
+/// With typed escrow stanzas, for hybrid recipients
+const VERSION_TYPED_ESCROW: u8 = 3;
+const STANZA_X25519: u8 = 1;
+const STANZA_HYBRID: u8 = 2;
+pub(crate) const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 24;
+/// Nonce, sealed 32-byte data key and tag
+const WRAPPED_KEY_SIZE: usize = NONCE_SIZE + 32 + 16;
+
+#[derive(Serialize, Deserialize)]
+struct BackupEntry {
+    service: String,
+    account: String,
+    value: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupPayload {
+    /// Milliseconds since the Unix epoch
+    created_at: u64,
+    entries: Vec<BackupEntry>,
+}
+
+/// Which entries an export includes. Patterns are `service` or `service/account` globs (`*`, `?`);
+/// a service-only pattern matches every account. An empty `include` selects everything.
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+fn pattern_matches(pattern: &str, key: &KeystoreKey) -> bool {
+    match pattern.split_once('/') {
+        Some((service, account)) => {
+            glob_match(service, &key.service) && glob_match(account, &key.account)
+        }
+        None => glob_match(pattern, &key.service),
+    }
+}
+
+impl ExportFilter {
+    pub fn matches(&self, key: &KeystoreKey) -> bool {
+        (self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| pattern_matches(pattern, key)))
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| pattern_matches(pattern, key))
+    }
+}
+
+/// Keys `filter` selects, sorted by service, then account. Ephemeral entries never leave the
+/// process, so they are left out whatever the filter says.
+fn selected_keys(
+    backend: &dyn KeystoreOperations,
+    filter: &ExportFilter,
+) -> Result<Vec<KeystoreKey>, KeystoreError> {
+    let mut keys: Vec<KeystoreKey> = backend
+        .list_keys()?
+        .into_iter()
+        .filter(|key| !is_ephemeral(&key.service) && filter.matches(key))
+        .collect();
+    keys.sort();
+    Ok(keys)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// An entry in a redacted export
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedactedEntry {
+    pub service: String,
+    pub account: String,
+    pub kind: EntryKind,
+    /// Bytes stored; `None` with `value_hash` when the entry could not be read
+    pub size: Option<usize>,
+    /// Hex SHA-256 of the export's salt followed by the value
+    pub value_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedactedExport {
+    /// Milliseconds since the Unix epoch
+    pub generated_at: u64,
+    pub backend: String,
+    /// Random per export, so hashes cannot be matched against precomputed tables or other exports
+    pub salt: String,
+    pub entries: Vec<RedactedEntry>,
+}
+
+impl RedactedExport {
+    pub fn to_json(&self) -> Result<String, KeystoreError> {
+        serde_json::to_string_pretty(self).map_err(|e| KeystoreError::Serialization(e.to_string()))
+    }
+}
+
+/// The entries `filter` selects with salted hashes in place of their values. Identical values
+/// within one export share a hash.
+pub fn export_redacted(
+    backend: &dyn KeystoreOperations,
+    filter: &ExportFilter,
+) -> Result<RedactedExport, KeystoreError> {
+    let salt = random_bytes(16)?;
+    let entries = selected_keys(backend, filter)?
+        .into_iter()
+        .map(|key| {
+            let value = backend
+                .get_password(&key.service, &key.account)
+                .map(Zeroizing::new)
+                .ok();
+            RedactedEntry {
+                kind: EntryKind::classify(&key.service, &key.account),
+                size: value.as_ref().map(|value| value.len()),
+                value_hash: value.map(|value| {
+                    hex::encode(
+                        Sha256::new()
+                            .chain_update(&salt)
+                            .chain_update(value.as_bytes())
+                            .finalize(),
+                    )
+                }),
+                service: key.service,
+                account: key.account,
+            }
+        })
+        .collect();
+    Ok(RedactedExport {
+        generated_at: now_millis(),
+        backend: backend.backend_info().name,
+        salt: hex::encode(&salt),
+        entries,
+    })
+}
+
+/// Argon2id key for `passphrase`
+pub(crate) fn passphrase_key(
+    passphrase: &str,
+    salt: &[u8],
+    params: &KdfParams,
+) -> Result<Zeroizing<[u8; 32]>, KeystoreError> {
+    if passphrase.is_empty() {
+        return Err(KeystoreError::InvalidInput(
+            "Backup passphrase must not be empty".to_string(),
+        ));
+    }
+    let mut key = Zeroizing::new([0u8; 32]);
+    params
+        .argon2()?
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|e| KeystoreError::Platform(format!("Failed to derive backup key: {}", e)))?;
+    Ok(key)
+}
+
+/// Encrypts `plaintext` as `nonce || ciphertext || tag`
+pub(crate) fn seal(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, KeystoreError> {
+    let nonce = random_bytes(NONCE_SIZE)?;
+    let ciphertext = XChaCha20Poly1305::new(key.into())
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| KeystoreError::Platform("Encryption failed".to_string()))?;
+    Ok([nonce, ciphertext].concat())
+}
+
+pub(crate) fn open(key: &[u8; 32], sealed: &[u8], aad: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+    let (nonce, ciphertext) = sealed.split_at_checked(NONCE_SIZE)?;
+    XChaCha20Poly1305::new(key.into())
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .ok()
+        .map(Zeroizing::new)
+}
+
+fn corrupt(message: &str) -> KeystoreError {
+    KeystoreError::Serialization(format!("Backup {}", message))
+}
+
+/// The parts of a backup blob
+struct Sections<'a> {
+    /// Magic, version, salt and parameters, authenticated with the wrapped key
+    prefix: &'a [u8],
+    salt: &'a [u8],
+    params: KdfParams,
+    wrapped_key: &'a [u8],
+    escrow_stanzas: Vec<&'a [u8]>,
+    /// Everything before the payload, authenticated with it
+    header: &'a [u8],
+    payload: &'a [u8],
+}
+
+fn sections(data: &[u8]) -> Result<Sections<'_>, KeystoreError> {
+    if data.len() < 5 || &data[..4] != MAGIC {
+        return Err(corrupt("is not a keystore backup"));
+    }
+    let version = data[4];
+    let params_size = match version {
+        VERSION | VERSION_TYPED_ESCROW => PARAMS_SIZE,
+        VERSION_DEFAULT_KDF => 0,
+        version if version > VERSION_TYPED_ESCROW => {
+            return Err(KeystoreError::FormatTooNew(format!(
+                "Backup has version {}, this build reads up to {}",
+                version, VERSION_TYPED_ESCROW
+            )))
+        }
+        version => return Err(corrupt(&format!("has unsupported version {}", version))),
+    };
+    let prefix_size = 5 + SALT_SIZE + params_size;
+    // Prefix, passphrase-wrapped data key and escrow key count
+    let fixed_header_size = prefix_size + WRAPPED_KEY_SIZE + 1;
+    if data.len() < fixed_header_size {
+        return Err(corrupt("is truncated"));
+    }
+    let params = match data[5 + SALT_SIZE..prefix_size].try_into() {
+        Ok(bytes) => KdfParams::from_bytes(bytes),
+        Err(_) => KdfParams::default(),
+    };
+    let escrow_count = data[fixed_header_size - 1] as usize;
+    let mut escrow_stanzas = Vec::with_capacity(escrow_count);
+    let mut header_size = fixed_header_size;
+    for _ in 0..escrow_count {
+        let size = if version == VERSION_TYPED_ESCROW {
+            let kind = *data
+                .get(header_size)
+                .ok_or_else(|| corrupt("is truncated"))?;
+            header_size += 1;
+            match kind {
+                STANZA_X25519 => STANZA_SIZE,
+                STANZA_HYBRID => HYBRID_STANZA_SIZE,
+                kind => return Err(corrupt(&format!("has unknown escrow type {}", kind))),
+            }
+        } else {
+            STANZA_SIZE
+        };
+        let stanza = data
+            .get(header_size..header_size + size)
+            .ok_or_else(|| corrupt("is truncated"))?;
+        escrow_stanzas.push(stanza);
+        header_size += size;
+    }
+    let (header, payload) = data.split_at(header_size);
+    Ok(Sections {
+        prefix: &data[..prefix_size],
+        salt: &data[5..5 + SALT_SIZE],
+        params,
+        wrapped_key: &data[prefix_size..fixed_header_size - 1],
+        escrow_stanzas,
+        header,
+        payload,
+    })
+}
+
+fn decode_payload(
+    sections: &Sections<'_>,
+    data_key: &[u8; 32],
+) -> Result<Vec<KeystoreEntry>, KeystoreError> {
+    let plaintext = open(data_key, sections.payload, sections.header)
+        .ok_or_else(|| corrupt("is damaged or was modified"))?;
+    let payload: BackupPayload =
+        serde_json::from_slice(&plaintext).map_err(|e| corrupt(&format!("payload: {}", e)))?;
+    Ok(payload
+        .entries
+        .into_iter()
+        .map(|entry| KeystoreEntry {
+            service: entry.service,
+            account: entry.account,
+            value: entry.value,
+            ..Default::default()
+        })
+        .collect())
+}
+
+/// Encrypts the entries `filter` selects under `passphrase`, derived with the Argon2 parameters
+/// tuned for this machine (see [`crate::kdf`])
+pub fn export_backup(
+    backend: &dyn KeystoreOperations,
+    passphrase: &str,
+    filter: &ExportFilter,
+) -> Result<Vec<u8>, KeystoreError> {
+    export_backup_with_escrow(backend, passphrase, filter, &[])
+}
+
+/// Like [`export_backup`], and also recoverable with the secret half of any of `escrow_keys`.
+/// Writes version 3 if any of them is hybrid, version 2 otherwise.
+pub fn export_backup_with_escrow(
+    backend: &dyn KeystoreOperations,
+    passphrase: &str,
+    filter: &ExportFilter,
+    escrow_keys: &[EscrowPublicKey],
+) -> Result<Vec<u8>, KeystoreError> {
+    export_keys_with_escrow(
+        backend,
+        passphrase,
+        &selected_keys(backend, filter)?,
+        escrow_keys,
+    )
+}
+
+/// Like [`export_backup_with_escrow`] for exactly `keys`, e.g. the members of a
+/// [`crate::bundle`]. Ephemeral entries are refused rather than left out.
+pub fn export_keys_with_escrow(
+    backend: &dyn KeystoreOperations,
+    passphrase: &str,
+    keys: &[KeystoreKey],
+    escrow_keys: &[EscrowPublicKey],
+) -> Result<Vec<u8>, KeystoreError> {
+    if let Some(key) = keys.iter().find(|key| is_ephemeral(&key.service)) {
+        return Err(KeystoreError::InvalidInput(format!(
+            "{}:{} is ephemeral and can't be exported",
+            key.service, key.account
+        )));
+    }
+    let escrow_count = u8::try_from(escrow_keys.len()).map_err(|_| {
+        KeystoreError::InvalidInput("A backup can have at most 255 escrow keys".to_string())
+    })?;
+    let entries = keys
+        .iter()
+        .map(|key| {
+            let value = backend.get_password(&key.service, &key.account)?;
+            Ok(BackupEntry {
+                service: key.service.clone(),
+                account: key.account.clone(),
+                value,
+            })
+        })
+        .collect::<Result<Vec<_>, KeystoreError>>()?;
+    let payload = BackupPayload {
+        created_at: now_millis(),
+        entries,
+    };
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(&payload).map_err(|e| KeystoreError::Serialization(e.to_string()))?,
+    );
+    for entry in payload.entries {
+        drop(Zeroizing::new(entry.value));
+    }
+
+    let mut data_key = Zeroizing::new([0u8; 32]);
+    data_key.copy_from_slice(&Zeroizing::new(random_bytes(32)?));
+    let salt = random_bytes(SALT_SIZE)?;
+    let params = KdfParams::load(backend)?;
+    let typed = escrow_keys.iter().any(EscrowPublicKey::is_hybrid);
+    let version = if typed { VERSION_TYPED_ESCROW } else { VERSION };
+    let prefix = [MAGIC.as_slice(), &[version], &salt, &params.to_bytes()].concat();
+    let wrapped_key = seal(
+        &*passphrase_key(passphrase, &salt, &params)?,
+        &*data_key,
+        &prefix,
+    )?;
+    let mut header = [prefix, wrapped_key, vec![escrow_count]].concat();
+    for escrow_key in escrow_keys {
+        if typed {
+            header.push(if escrow_key.is_hybrid() {
+                STANZA_HYBRID
+            } else {
+                STANZA_X25519
+            });
+        }
+        header.extend(escrow::wrap(escrow_key, &data_key)?);
+    }
+    let payload = seal(&data_key, &plaintext, &header)?;
+    Ok([header, payload].concat())
+}
+
+/// Decrypts a blob written by [`export_backup`] into its entries
+pub fn read_backup(data: &[u8], passphrase: &str) -> Result<Vec<KeystoreEntry>, KeystoreError> {
+    let sections = sections(data)?;
+    let key = passphrase_key(passphrase, sections.salt, &sections.params)?;
+    let data_key = open(&key, sections.wrapped_key, sections.prefix).ok_or_else(|| {
+        KeystoreError::AccessDenied(
+            "Backup passphrase is incorrect or the backup is damaged".to_string(),
+        )
+    })?;
+    let data_key: &[u8; 32] = data_key
+        .as_slice()
+        .try_into()
+        .map_err(|_| corrupt("has a malformed key"))?;
+    decode_payload(&sections, data_key)
+}
+
+/// Decrypts a backup with an escrow key it was exported to, without the passphrase
+pub fn recover_backup(
+    data: &[u8],
+    escrow_key: &EscrowKey,
+) -> Result<Vec<KeystoreEntry>, KeystoreError> {
+    let sections = sections(data)?;
+    let data_key = sections
+        .escrow_stanzas
+        .iter()
+        .find_map(|stanza| escrow_key.unwrap(stanza))
+        .ok_or_else(|| {
+            KeystoreError::AccessDenied("Backup was not escrowed to this key".to_string())
+        })?;
+    decode_payload(&sections, &data_key)
+}
+
+/// How two backups differ, each list sorted by service, then account
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    /// Only in the newer backup
+    pub added: Vec<KeystoreKey>,
+    /// Only in the older backup
+    pub removed: Vec<KeystoreKey>,
+    /// In both with different values
+    pub changed: Vec<KeystoreKey>,
+    pub unchanged: Vec<KeystoreKey>,
+}
+
+/// HMAC-SHA256 of each value under `key`, wiping the values as it goes
+fn keyed_digests(
+    entries: Vec<KeystoreEntry>,
+    key: &[u8],
+) -> Result<BTreeMap<KeystoreKey, [u8; 32]>, KeystoreError> {
+    let mut digests = BTreeMap::new();
+    for entry in entries {
+        let value = Zeroizing::new(entry.value);
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+            .map_err(|e| KeystoreError::Platform(format!("Invalid digest key: {}", e)))?;
+        mac.update(value.as_bytes());
+        digests.insert(
+            KeystoreKey {
+                service: entry.service,
+                account: entry.account,
+            },
+            mac.finalize().into_bytes().into(),
+        );
+    }
+    Ok(digests)
+}
+
+/// Compares two backups written by [`export_backup`], each with its own passphrase. Values are
+/// compared by HMAC under a random key that lives only for this call, so neither values nor
+/// digests that could be matched elsewhere leave it.
+pub fn diff_snapshots(
+    older: &[u8],
+    older_passphrase: &str,
+    newer: &[u8],
+    newer_passphrase: &str,
+) -> Result<SnapshotDiff, KeystoreError> {
+    let key = Zeroizing::new(random_bytes(32)?);
+    let older = keyed_digests(read_backup(older, older_passphrase)?, &key)?;
+    let mut newer = keyed_digests(read_backup(newer, newer_passphrase)?, &key)?;
+
+    let mut diff = SnapshotDiff::default();
+    for (entry, digest) in older {
+        match newer.remove(&entry) {
+            None => diff.removed.push(entry),
+            Some(newer_digest) if newer_digest == digest => diff.unchanged.push(entry),
+            Some(_) => diff.changed.push(entry),
+        }
+    }
+    diff.added = newer.into_keys().collect();
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::plan_import;
+    use crate::testing::MemoryKeystore;
+
+    #[test]
+    fn test_backup_round_trips_and_rejects_wrong_passphrase() {
+        let backend = MemoryKeystore::default();
+        backend
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "bot-oauth".to_string(),
+                value: "s3cret-token".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let blob = export_backup(&backend, "correct horse", &ExportFilter::default()).unwrap();
+        assert!(!String::from_utf8_lossy(&blob).contains("s3cret-token"));
+        assert!(matches!(
+            read_backup(&blob, "wrong"),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+
+        let entries = read_backup(&blob, "correct horse").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].value, "s3cret-token");
+        let diff = plan_import(&backend, &entries).unwrap();
+        assert_eq!(diff.unchanged.len(), 1);
+
+        let mut tampered = blob.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(read_backup(&tampered, "correct horse").is_err());
+    }
+
+    #[test]
+    fn test_backup_records_tuned_parameters_and_reads_version_1() {
+        let backend = MemoryKeystore::default();
+        backend
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "bot-oauth".to_string(),
+                value: "s3cret-token".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        let tuned = KdfParams {
+            iterations: 3,
+            ..KdfParams::default()
+        };
+        tuned.store(&backend).unwrap();
+        let blob = export_backup(&backend, "correct horse", &ExportFilter::default()).unwrap();
+        // Restoring elsewhere needs only the header, not this machine's tuning
+        KdfParams::default().store(&backend).unwrap();
+        assert_eq!(sections(&blob).unwrap().params, tuned);
+        assert_eq!(read_backup(&blob, "correct horse").unwrap().len(), 2);
+
+        // A version 1 backup of an empty keystore, written before parameters were recorded
+        let salt = [7u8; SALT_SIZE];
+        let data_key = [9u8; 32];
+        let prefix = [MAGIC.as_slice(), &[VERSION_DEFAULT_KDF], &salt].concat();
+        let key = passphrase_key("correct horse", &salt, &KdfParams::default()).unwrap();
+        let header = [
+            prefix.clone(),
+            seal(&key, &data_key, &prefix).unwrap(),
+            vec![0],
+        ]
+        .concat();
+        let payload = br#"{"created_at":0,"entries":[]}"#;
+        let v1 = [header.clone(), seal(&data_key, payload, &header).unwrap()].concat();
+        assert!(read_backup(&v1, "correct horse").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_escrowed_backup_recovers_without_passphrase() {
+        let backend = MemoryKeystore::default();
+        backend
+            .set_password(&KeystoreEntry {
+                service: "obs".to_string(),
+                account: "stream-key".to_string(),
+                value: "live_123".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        let admin = EscrowKey::generate().unwrap();
+        let stranger = EscrowKey::generate().unwrap();
+
+        let blob = export_backup_with_escrow(
+            &backend,
+            "operator passphrase",
+            &ExportFilter::default(),
+            &[stranger.public_key(), admin.public_key()],
+        )
+        .unwrap();
+        assert_eq!(
+            read_backup(&blob, "operator passphrase").unwrap()[0].value,
+            "live_123"
+        );
+        assert_eq!(recover_backup(&blob, &admin).unwrap()[0].value, "live_123");
+
+        assert_eq!(blob[4], VERSION);
+
+        let unescrowed =
+            export_backup(&backend, "operator passphrase", &ExportFilter::default()).unwrap();
+        assert!(matches!(
+            recover_backup(&unescrowed, &admin),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+
+        // A hybrid recipient switches to typed stanzas; classical ones still recover
+        let post_quantum = EscrowKey::generate_hybrid().unwrap();
+        let blob = export_backup_with_escrow(
+            &backend,
+            "operator passphrase",
+            &ExportFilter::default(),
+            &[admin.public_key(), post_quantum.public_key()],
+        )
+        .unwrap();
+        assert_eq!(blob[4], VERSION_TYPED_ESCROW);
+        assert_eq!(read_backup(&blob, "operator passphrase").unwrap().len(), 1);
+        assert_eq!(recover_backup(&blob, &admin).unwrap()[0].value, "live_123");
+        assert_eq!(
+            recover_backup(&blob, &post_quantum).unwrap()[0].value,
+            "live_123"
+        );
+        assert!(recover_backup(&blob, &stranger).is_err());
+    }
+
+    #[test]
+    fn test_filtered_and_redacted_exports() {
+        let backend = MemoryKeystore::default();
+        for (service, account) in [
+            ("twitch", "bot-oauth"),
+            ("twitch", "refresh"),
+            ("youtube", "api-key"),
+        ] {
+            backend
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    value: "s3cret-token".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let filter = ExportFilter {
+            include: vec!["twitch".to_string(), "you*/api-*".to_string()],
+            exclude: vec!["*/refresh".to_string()],
+        };
+
+        let blob = export_backup(&backend, "correct horse", &filter).unwrap();
+        let accounts: Vec<String> = read_backup(&blob, "correct horse")
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.account)
+            .collect();
+        assert_eq!(accounts, vec!["bot-oauth", "api-key"]);
+
+        let redacted = export_redacted(&backend, &filter).unwrap();
+        assert_eq!(redacted.entries.len(), 2);
+        assert_eq!(redacted.entries[0].kind, EntryKind::Credential);
+        assert_eq!(redacted.entries[0].size, Some(12));
+        // Same value, same salt, same hash
+        assert_eq!(
+            redacted.entries[0].value_hash,
+            redacted.entries[1].value_hash
+        );
+        assert!(!redacted.to_json().unwrap().contains("s3cret-token"));
+    }
+
+    #[test]
+    fn test_snapshot_diff_compares_values_without_revealing_them() {
+        let backend = MemoryKeystore::default();
+        let set = |account: &str, value: &str| {
+            backend
+                .set_password(&KeystoreEntry {
+                    service: "twitch".to_string(),
+                    account: account.to_string(),
+                    value: value.to_string(),
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+        set("bot-oauth", "one");
+        set("refresh", "two");
+        set("client-id", "three");
+        let older = export_backup(&backend, "first", &ExportFilter::default()).unwrap();
+
+        set("bot-oauth", "rotated");
+        backend.delete_password("twitch", "refresh").unwrap();
+        set("webhook", "four");
+        let newer = export_backup(&backend, "second", &ExportFilter::default()).unwrap();
+
+        let key = |account: &str| KeystoreKey {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+        };
+        let diff = diff_snapshots(&older, "first", &newer, "second").unwrap();
+        assert_eq!(diff.added, vec![key("webhook")]);
+        assert_eq!(diff.removed, vec![key("refresh")]);
+        assert_eq!(diff.changed, vec![key("bot-oauth")]);
+        assert_eq!(diff.unchanged, vec![key("client-id")]);
+        assert!(matches!(
+            diff_snapshots(&older, "first", &newer, "first"),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+    }
+}
@@ -0,0 +1,716 @@
+//! Values larger than a backend's per-entry size limit.
+//!
+//! A blob is stored as a small JSON envelope under the caller's service/account. When the value
+//! fits the backend's [`KeystoreOperations::max_value_size`] it is kept inline in the envelope;
+//! otherwise it is split into chunk entries under `<account>:chunk:<n>` and the envelope records
+//! the chunk count, length and SHA-256 of the whole value so a torn read is detected.
+//!
+//! Structured values ([`BlobStore::put_structured`]) are serialized in the [`StructuredFormat`]
+//! chosen for their service with [`set_structured_format`]: JSON by default, which stays readable
+//! in a keychain viewer, or CBOR or MessagePack, base64-encoded, for size. The envelope records the
+//! format, so reads decode whatever an entry was written in and a service can switch formats
+//! without rewriting what it stored.
+//!
+//! [`BlobWriter`] and [`BlobReader`] move a value of several megabytes a chunk at a time, so it is
+//! never held whole in one allocation. They store the same layout as [`BlobStore::put`], always
+//! chunked, and check the same length and digest.
+
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+use crate::KeystoreEntry;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+use zeroize::Zeroizing;
+
+const ENVELOPE_VERSION: u32 = 1;
+
+/// Written only for binary formats, so text and JSON blobs stay readable by older builds
+const ENVELOPE_VERSION_BINARY: u32 = 2;
+
+/// Wire format of a structured value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StructuredFormat {
+    #[default]
+    Json,
+    Cbor,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl StructuredFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            StructuredFormat::Json => "json",
+            StructuredFormat::Cbor => "cbor",
+            StructuredFormat::MessagePack => "msgpack",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, KeystoreError> {
+        match name {
+            "json" => Ok(StructuredFormat::Json),
+            "cbor" => Ok(StructuredFormat::Cbor),
+            "msgpack" => Ok(StructuredFormat::MessagePack),
+            _ => Err(KeystoreError::InvalidInput(format!(
+                "Unknown structured format '{}'; expected json, cbor or msgpack",
+                name
+            ))),
+        }
+    }
+
+    fn encode<T: Serialize + ?Sized>(self, value: &T) -> Result<String, KeystoreError> {
+        let error = |e: &dyn std::fmt::Display| KeystoreError::Serialization(e.to_string());
+        match self {
+            StructuredFormat::Json => serde_json::to_string(value).map_err(|e| error(&e)),
+            StructuredFormat::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes).map_err(|e| error(&e))?;
+                Ok(STANDARD.encode(bytes))
+            }
+            StructuredFormat::MessagePack => rmp_serde::to_vec_named(value)
+                .map(|bytes| STANDARD.encode(bytes))
+                .map_err(|e| error(&e)),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(self, data: &str) -> Result<T, KeystoreError> {
+        let error = |e: &dyn std::fmt::Display| KeystoreError::Serialization(e.to_string());
+        let bytes = || STANDARD.decode(data).map_err(|e| error(&e));
+        match self {
+            StructuredFormat::Json => serde_json::from_str(data).map_err(|e| error(&e)),
+            StructuredFormat::Cbor => {
+                ciborium::from_reader(bytes()?.as_slice()).map_err(|e| error(&e))
+            }
+            StructuredFormat::MessagePack => {
+                rmp_serde::from_slice(&bytes()?).map_err(|e| error(&e))
+            }
+        }
+    }
+}
+
+/// Formats chosen per service; every other service uses JSON
+static FORMATS: LazyLock<RwLock<HashMap<String, StructuredFormat>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Writes structured values under `service` in `format` from now on, process-wide. Values already
+/// stored keep their format until rewritten.
+pub fn set_structured_format(service: &str, format: StructuredFormat) {
+    let mut formats = FORMATS.write().unwrap();
+    if format == StructuredFormat::Json {
+        formats.remove(service);
+    } else {
+        formats.insert(service.to_string(), format);
+    }
+}
+
+pub fn structured_format(service: &str) -> StructuredFormat {
+    FORMATS
+        .read()
+        .unwrap()
+        .get(service)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Smallest chunk worth writing; also guarantees room for any UTF-8 character
+const MIN_CHUNK_SIZE: usize = 16;
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+    #[serde(default)]
+    chunks: usize,
+    len: usize,
+    sha256: String,
+    /// Set for structured values in a binary format; the data is then base64
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    format: Option<StructuredFormat>,
+}
+
+/// Stores values of any size on top of a backend
+pub struct BlobStore<'a> {
+    backend: &'a dyn KeystoreOperations,
+}
+
+impl<'a> BlobStore<'a> {
+    pub fn new(backend: &'a dyn KeystoreOperations) -> Self {
+        Self { backend }
+    }
+
+    fn chunk_account(account: &str, index: usize) -> String {
+        format!("{}:chunk:{}", account, index)
+    }
+
+    fn digest(value: &str) -> String {
+        format!("{:x}", Sha256::digest(value.as_bytes()))
+    }
+
+    fn read_envelope(&self, service: &str, account: &str) -> Result<Envelope, KeystoreError> {
+        let raw = self.backend.get_password(service, account)?;
+        serde_json::from_str(&raw).map_err(|e| KeystoreError::Serialization(e.to_string()))
+    }
+
+    fn write_envelope(
+        &self,
+        service: &str,
+        account: &str,
+        envelope: &Envelope,
+    ) -> Result<(), KeystoreError> {
+        let value = serde_json::to_string(envelope)
+            .map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+        self.backend.set_password(&KeystoreEntry {
+            service: service.to_string(),
+            account: account.to_string(),
+            value,
+            ..Default::default()
+        })
+    }
+
+    /// Removes chunk entries from `from` up to (excluding) `to`, ignoring ones already gone
+    fn delete_chunks(
+        &self,
+        service: &str,
+        account: &str,
+        from: usize,
+        to: usize,
+    ) -> Result<(), KeystoreError> {
+        for index in from..to {
+            match self
+                .backend
+                .delete_password(service, &Self::chunk_account(account, index))
+            {
+                Ok(()) | Err(KeystoreError::KeyNotFound(_)) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn put(&self, service: &str, account: &str, value: &str) -> Result<(), KeystoreError> {
+        self.put_as(service, account, value, None)
+    }
+
+    fn put_as(
+        &self,
+        service: &str,
+        account: &str,
+        value: &str,
+        format: Option<StructuredFormat>,
+    ) -> Result<(), KeystoreError> {
+        let previous_chunks = match self.read_envelope(service, account) {
+            Ok(envelope) => envelope.chunks,
+            Err(_) => 0,
+        };
+
+        let inline = Envelope {
+            version: match format {
+                Some(_) => ENVELOPE_VERSION_BINARY,
+                None => ENVELOPE_VERSION,
+            },
+            data: Some(value.to_string()),
+            chunks: 0,
+            len: value.len(),
+            sha256: Self::digest(value),
+            format,
+        };
+
+        let envelope = match self.backend.max_value_size() {
+            Some(max)
+                if serde_json::to_string(&inline)
+                    .map_err(|e| KeystoreError::Serialization(e.to_string()))?
+                    .len()
+                    > max =>
+            {
+                let chunks = split_chunks(value, max.max(MIN_CHUNK_SIZE));
+                for (index, chunk) in chunks.iter().enumerate() {
+                    self.backend.set_password(&KeystoreEntry {
+                        service: service.to_string(),
+                        account: Self::chunk_account(account, index),
+                        value: chunk.to_string(),
+                        ..Default::default()
+                    })?;
+                }
+                Envelope {
+                    data: None,
+                    chunks: chunks.len(),
+                    ..inline
+                }
+            }
+            _ => inline,
+        };
+
+        // Chunks are written before the envelope that points at them
+        self.write_envelope(service, account, &envelope)?;
+        self.delete_chunks(service, account, envelope.chunks, previous_chunks)
+    }
+
+    /// The stored text; base64 for a structured value in a binary format
+    pub fn get(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.get_with_format(service, account)
+            .map(|(value, _)| value)
+    }
+
+    fn get_with_format(
+        &self,
+        service: &str,
+        account: &str,
+    ) -> Result<(String, Option<StructuredFormat>), KeystoreError> {
+        let envelope = self.read_envelope(service, account)?;
+        if envelope.version > ENVELOPE_VERSION_BINARY {
+            return Err(KeystoreError::FormatTooNew(format!(
+                "Blob has version {}, this build reads up to {}",
+                envelope.version, ENVELOPE_VERSION_BINARY
+            )));
+        }
+
+        let value = match envelope.data {
+            Some(data) => data,
+            None => {
+                let mut value = String::with_capacity(envelope.len);
+                for index in 0..envelope.chunks {
+                    let chunk = self
+                        .backend
+                        .get_password(service, &Self::chunk_account(account, index))
+                        .map_err(|e| match e {
+                            KeystoreError::KeyNotFound(name) => {
+                                KeystoreError::Serialization(format!("Missing blob chunk {}", name))
+                            }
+                            e => e,
+                        })?;
+                    value.push_str(&chunk);
+                }
+                value
+            }
+        };
+
+        if value.len() != envelope.len || Self::digest(&value) != envelope.sha256 {
+            return Err(KeystoreError::Serialization(format!(
+                "Blob {}:{} failed its integrity check",
+                service, account
+            )));
+        }
+
+        Ok((value, envelope.format))
+    }
+
+    /// Decodes a value stored by [`Self::put_structured`] in whichever format it was written
+    pub fn get_structured<T: DeserializeOwned>(
+        &self,
+        service: &str,
+        account: &str,
+    ) -> Result<T, KeystoreError> {
+        let (data, format) = self.get_with_format(service, account)?;
+        format.unwrap_or_default().decode(&data)
+    }
+
+    /// Stores `value` in the format chosen for `service` (see [`set_structured_format`])
+    pub fn put_structured<T: Serialize + ?Sized>(
+        &self,
+        service: &str,
+        account: &str,
+        value: &T,
+    ) -> Result<(), KeystoreError> {
+        let format = structured_format(service);
+        let data = format.encode(value)?;
+        // JSON is recorded as plain text, as before formats existed
+        let recorded = (format != StructuredFormat::Json).then_some(format);
+        self.put_as(service, account, &data, recorded)
+    }
+
+    /// Chunk entries the blob at `service`/`account` is made of; 0 when stored inline
+    pub fn chunk_count(&self, service: &str, account: &str) -> Result<usize, KeystoreError> {
+        self.read_envelope(service, account)
+            .map(|envelope| envelope.chunks)
+    }
+
+    pub fn delete(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let chunks = match self.read_envelope(service, account) {
+            Ok(envelope) => envelope.chunks,
+            Err(KeystoreError::KeyNotFound(name)) => return Err(KeystoreError::KeyNotFound(name)),
+            Err(_) => 0,
+        };
+
+        self.backend.delete_password(service, account)?;
+        self.delete_chunks(service, account, 0, chunks)
+    }
+}
+
+/// Chunk size for streamed values on backends without a per-entry limit
+pub const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+fn stream_chunk_size(backend: &dyn KeystoreOperations) -> usize {
+    backend.max_value_size().map_or(STREAM_CHUNK_SIZE, |max| {
+        max.clamp(MIN_CHUNK_SIZE, STREAM_CHUNK_SIZE)
+    })
+}
+
+/// Writes a value piece by piece. Nothing replaces the stored value until [`BlobWriter::finish`];
+/// as with [`BlobStore::put`], chunks are written first, so a write abandoned halfway leaves the
+/// previous value failing its integrity check.
+pub struct BlobWriter {
+    backend: Arc<dyn KeystoreOperations>,
+    service: String,
+    account: String,
+    chunk_size: usize,
+    /// Bytes not yet written, at most a chunk plus the incoming piece
+    pending: Zeroizing<Vec<u8>>,
+    hasher: Sha256,
+    len: usize,
+    chunks: usize,
+}
+
+impl BlobWriter {
+    pub fn new(backend: Arc<dyn KeystoreOperations>, service: &str, account: &str) -> Self {
+        let chunk_size = stream_chunk_size(backend.as_ref());
+        Self {
+            backend,
+            service: service.to_string(),
+            account: account.to_string(),
+            chunk_size,
+            pending: Zeroizing::new(Vec::new()),
+            hasher: Sha256::new(),
+            len: 0,
+            chunks: 0,
+        }
+    }
+
+    /// Appends UTF-8 bytes; a character may be split across calls
+    pub fn write(&mut self, data: &[u8]) -> Result<(), KeystoreError> {
+        self.pending.extend_from_slice(data);
+        while self.pending.len() >= self.chunk_size {
+            let text = match std::str::from_utf8(&self.pending[..self.chunk_size]) {
+                Ok(text) => text,
+                // A character cut at the chunk boundary goes with the next chunk
+                Err(e) if e.error_len().is_none() && e.valid_up_to() > 0 => {
+                    std::str::from_utf8(&self.pending[..e.valid_up_to()]).unwrap_or_default()
+                }
+                Err(_) => {
+                    return Err(KeystoreError::InvalidInput(
+                        "Streamed value is not valid UTF-8".to_string(),
+                    ))
+                }
+            };
+            let written = text.len();
+            self.write_chunk(text)?;
+            self.record_chunk(written);
+            self.pending.drain(..written);
+        }
+        Ok(())
+    }
+
+    fn write_chunk(&self, text: &str) -> Result<(), KeystoreError> {
+        self.backend.set_password(&KeystoreEntry {
+            service: self.service.clone(),
+            account: BlobStore::chunk_account(&self.account, self.chunks),
+            value: text.to_string(),
+            ..Default::default()
+        })
+    }
+
+    fn record_chunk(&mut self, len: usize) {
+        self.hasher.update(&self.pending[..len]);
+        self.len += len;
+        self.chunks += 1;
+    }
+
+    /// Writes what remains and the envelope that makes the new value visible
+    pub fn finish(mut self) -> Result<(), KeystoreError> {
+        let rest = std::str::from_utf8(&self.pending)
+            .map_err(|_| {
+                KeystoreError::InvalidInput("Streamed value is not valid UTF-8".to_string())
+            })?
+            .len();
+        if rest > 0 {
+            let text = std::str::from_utf8(&self.pending[..rest]).unwrap_or_default();
+            self.write_chunk(text)?;
+            self.record_chunk(rest);
+        }
+        let store = BlobStore::new(self.backend.as_ref());
+        let previous_chunks = match store.read_envelope(&self.service, &self.account) {
+            Ok(envelope) => envelope.chunks,
+            Err(_) => 0,
+        };
+        store.write_envelope(
+            &self.service,
+            &self.account,
+            &Envelope {
+                version: ENVELOPE_VERSION,
+                data: None,
+                chunks: self.chunks,
+                len: self.len,
+                sha256: format!("{:x}", self.hasher.finalize_reset()),
+                format: None,
+            },
+        )?;
+        store.delete_chunks(&self.service, &self.account, self.chunks, previous_chunks)
+    }
+}
+
+/// Reads a value stored by [`BlobWriter`] or [`BlobStore::put`] a chunk at a time. The digest
+/// covers the whole value, so a damaged value is only reported after its last chunk.
+pub struct BlobReader {
+    backend: Arc<dyn KeystoreOperations>,
+    service: String,
+    account: String,
+    envelope: Envelope,
+    next: usize,
+    hasher: Sha256,
+    len: usize,
+}
+
+impl BlobReader {
+    pub fn open(
+        backend: Arc<dyn KeystoreOperations>,
+        service: &str,
+        account: &str,
+    ) -> Result<Self, KeystoreError> {
+        let envelope = BlobStore::new(backend.as_ref()).read_envelope(service, account)?;
+        if envelope.version > ENVELOPE_VERSION_BINARY {
+            return Err(KeystoreError::FormatTooNew(format!(
+                "Blob has version {}, this build reads up to {}",
+                envelope.version, ENVELOPE_VERSION_BINARY
+            )));
+        }
+        Ok(Self {
+            backend,
+            service: service.to_string(),
+            account: account.to_string(),
+            envelope,
+            next: 0,
+            hasher: Sha256::new(),
+            len: 0,
+        })
+    }
+
+    /// Length of the whole value in bytes
+    pub fn len(&self) -> usize {
+        self.envelope.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envelope.len == 0
+    }
+
+    /// The next piece, or `None` once the whole value has been read and verified
+    pub fn read(&mut self) -> Result<Option<Zeroizing<String>>, KeystoreError> {
+        let pieces = self.envelope.chunks.max(1);
+        if self.next > pieces {
+            return Ok(None);
+        }
+        if self.next == pieces {
+            self.next += 1;
+            let digest = format!("{:x}", self.hasher.finalize_reset());
+            if self.len != self.envelope.len || digest != self.envelope.sha256 {
+                return Err(KeystoreError::Serialization(format!(
+                    "Blob {}:{} failed its integrity check",
+                    self.service, self.account
+                )));
+            }
+            return Ok(None);
+        }
+
+        // Inline, or empty when written by a `BlobWriter` that got nothing
+        let piece = match self.envelope.chunks {
+            0 => Zeroizing::new(self.envelope.data.take().unwrap_or_default()),
+            _ => Zeroizing::new(
+                self.backend
+                    .get_password(
+                        &self.service,
+                        &BlobStore::chunk_account(&self.account, self.next),
+                    )
+                    .map_err(|e| match e {
+                        KeystoreError::KeyNotFound(name) => {
+                            KeystoreError::Serialization(format!("Missing blob chunk {}", name))
+                        }
+                        e => e,
+                    })?,
+            ),
+        };
+        self.next += 1;
+        self.hasher.update(piece.as_bytes());
+        self.len += piece.len();
+        Ok(Some(piece))
+    }
+}
+
+/// Splits `value` into pieces of at most `max` bytes without breaking UTF-8 characters
+fn split_chunks(value: &str, max: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = value;
+
+    while !rest.is_empty() {
+        let mut end = rest.len().min(max);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    fn limited(max: usize) -> MemoryKeystore {
+        MemoryKeystore {
+            max_value_size: Some(max),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_small_value_is_stored_inline() {
+        let backend = limited(2560);
+        let blobs = BlobStore::new(&backend);
+
+        blobs.put("svc", "acct", "short").unwrap();
+
+        assert_eq!(blobs.get("svc", "acct").unwrap(), "short");
+        assert_eq!(backend.entries.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_large_value_is_chunked_and_reassembled() {
+        let backend = limited(256);
+        let blobs = BlobStore::new(&backend);
+        let value = "Привет 🌍 ".repeat(500);
+
+        blobs.put("svc", "acct", &value).unwrap();
+
+        assert_eq!(blobs.get("svc", "acct").unwrap(), value);
+        assert!(backend.entries.lock().unwrap().len() > 10);
+    }
+
+    #[test]
+    fn test_shrinking_value_removes_stale_chunks() {
+        let backend = limited(256);
+        let blobs = BlobStore::new(&backend);
+
+        blobs.put("svc", "acct", &"a".repeat(2000)).unwrap();
+        blobs.put("svc", "acct", &"b".repeat(300)).unwrap();
+
+        assert_eq!(blobs.get("svc", "acct").unwrap(), "b".repeat(300));
+        assert_eq!(backend.entries.lock().unwrap().len(), 3);
+
+        blobs.delete("svc", "acct").unwrap();
+        assert!(backend.entries.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_missing_chunk_is_detected() {
+        let backend = limited(256);
+        let blobs = BlobStore::new(&backend);
+
+        blobs.put("svc", "acct", &"x".repeat(2000)).unwrap();
+        backend.delete_password("svc", "acct:chunk:2").unwrap();
+
+        match blobs.get("svc", "acct") {
+            Err(KeystoreError::Serialization(_)) => (),
+            other => panic!("Expected Serialization error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_structured_values_read_back_in_any_format() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Credential {
+            client_id: String,
+            scopes: Vec<String>,
+        }
+        let credential = Credential {
+            client_id: "abc123".to_string(),
+            scopes: vec!["chat:read".to_string(), "chat:edit".to_string()],
+        };
+        // A per-entry limit, as on Windows
+        let backend = limited(160);
+        let blobs = BlobStore::new(&backend);
+
+        blobs
+            .put_structured("test-formats", "json", &credential)
+            .unwrap();
+        set_structured_format("test-formats", StructuredFormat::Cbor);
+        blobs
+            .put_structured("test-formats", "cbor", &credential)
+            .unwrap();
+        set_structured_format("test-formats", StructuredFormat::MessagePack);
+        blobs
+            .put_structured("test-formats", "msgpack", &credential)
+            .unwrap();
+
+        for account in ["json", "cbor", "msgpack"] {
+            let read: Credential = blobs.get_structured("test-formats", account).unwrap();
+            assert_eq!(read, credential);
+        }
+        assert!(blobs.get("test-formats", "json").unwrap().starts_with('{'));
+        let envelope = backend.get_password("test-formats", "cbor").unwrap();
+        assert!(envelope.contains("\"format\":\"cbor\""));
+        set_structured_format("test-formats", StructuredFormat::Json);
+    }
+
+    #[test]
+    fn test_streamed_value_round_trips_in_pieces() {
+        let backend: Arc<dyn KeystoreOperations> = Arc::new(limited(256));
+        let value = "Привет 🌍 ".repeat(200);
+
+        let mut writer = BlobWriter::new(Arc::clone(&backend), "svc", "acct");
+        // Pieces that split characters
+        for piece in value.as_bytes().chunks(7) {
+            writer.write(piece).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(
+            BlobStore::new(backend.as_ref()).get("svc", "acct").unwrap(),
+            value
+        );
+
+        let mut reader = BlobReader::open(Arc::clone(&backend), "svc", "acct").unwrap();
+        assert_eq!(reader.len(), value.len());
+        let mut read = String::new();
+        let mut pieces = 0;
+        while let Some(piece) = reader.read().unwrap() {
+            assert!(piece.len() <= 256);
+            read.push_str(&piece);
+            pieces += 1;
+        }
+        assert_eq!(read, value);
+        assert!(pieces > 10);
+
+        backend
+            .set_password(&KeystoreEntry {
+                service: "svc".to_string(),
+                account: "acct:chunk:3".to_string(),
+                value: "tampered".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        let mut reader = BlobReader::open(Arc::clone(&backend), "svc", "acct").unwrap();
+        let outcome = std::iter::from_fn(|| reader.read().transpose()).find(Result::is_err);
+        assert!(matches!(
+            outcome,
+            Some(Err(KeystoreError::Serialization(_)))
+        ));
+    }
+
+    #[test]
+    fn test_split_chunks_respects_char_boundaries() {
+        let chunks = split_chunks("aé🌍b", 3);
+        assert_eq!(chunks, vec!["aé", "🌍", "b"]);
+    }
+}
@@ -0,0 +1,294 @@
+//! Named groups of entries.
+//!
+//! A Twitch setup is a bot token, a refresh token, a client secret and an EventSub signing secret;
+//! a bundle names them together so they can be exported, deleted or leased into the hot cache as
+//! one. Bundles are metadata only: a table under `streaming-enhancement:bundle-index` gives each
+//! member an id and records where that entry currently lives, and each bundle lists ids. Renaming
+//! an entry updates its location through [`Bundles::entry_renamed`], so bundles follow it.
+//! Members of deleted entries stay listed until [`crate::orphans::remove_orphans`] drops them.
+
+use crate::blob::BlobStore;
+use crate::error::KeystoreError;
+use crate::generate::hex_token;
+use crate::platform::KeystoreOperations;
+use crate::KeystoreKey;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, MutexGuard};
+
+pub const BUNDLE_SERVICE: &str = "streaming-enhancement:bundle-index";
+pub(crate) const BUNDLE_ACCOUNT: &str = "index";
+
+/// Serializes table read-modify-write cycles within the process
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct BundleTable {
+    /// Entry id -> where the entry is now
+    pub(crate) entries: BTreeMap<String, KeystoreKey>,
+    /// Bundle name -> member ids
+    pub(crate) bundles: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl BundleTable {
+    fn id_of(&self, key: &KeystoreKey) -> Option<&String> {
+        self.entries
+            .iter()
+            .find(|(_, entry)| *entry == key)
+            .map(|(id, _)| id)
+    }
+
+    /// The id of `key`, assigning one if it has none
+    fn assign(&mut self, key: &KeystoreKey) -> Result<String, KeystoreError> {
+        if let Some(id) = self.id_of(key) {
+            return Ok(id.clone());
+        }
+        let id = hex_token(8)?;
+        self.entries.insert(id.clone(), key.clone());
+        Ok(id)
+    }
+
+    /// Drops the ids no bundle lists any more
+    fn collect_garbage(&mut self) {
+        let used: BTreeSet<&String> = self.bundles.values().flatten().collect();
+        let unused: Vec<String> = self
+            .entries
+            .keys()
+            .filter(|id| !used.contains(id))
+            .cloned()
+            .collect();
+        for id in unused {
+            self.entries.remove(&id);
+        }
+    }
+
+    /// Removes the entries at `keys` from every bundle; whether any was a member
+    pub(crate) fn forget(&mut self, keys: &BTreeSet<&KeystoreKey>) -> bool {
+        let ids: BTreeSet<String> = self
+            .entries
+            .iter()
+            .filter(|(_, key)| keys.contains(key))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for members in self.bundles.values_mut() {
+            members.retain(|id| !ids.contains(id));
+        }
+        self.collect_garbage();
+        !ids.is_empty()
+    }
+}
+
+pub(crate) fn load_bundles(blobs: &BlobStore) -> Result<BundleTable, KeystoreError> {
+    match blobs.get_structured(BUNDLE_SERVICE, BUNDLE_ACCOUNT) {
+        Ok(table) => Ok(table),
+        Err(KeystoreError::KeyNotFound(_)) => Ok(BundleTable::default()),
+        Err(e) => Err(e),
+    }
+}
+
+pub(crate) fn lock_bundles() -> Result<MutexGuard<'static, ()>, KeystoreError> {
+    INDEX_LOCK
+        .lock()
+        .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))
+}
+
+fn not_found(name: &str) -> KeystoreError {
+    KeystoreError::KeyNotFound(format!("Bundle {}", name))
+}
+
+pub struct Bundles<'a> {
+    backend: &'a dyn KeystoreOperations,
+}
+
+impl<'a> Bundles<'a> {
+    pub fn new(backend: &'a dyn KeystoreOperations) -> Self {
+        Self { backend }
+    }
+
+    fn update<T>(
+        &self,
+        f: impl FnOnce(&mut BundleTable) -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        let _guard = lock_bundles()?;
+        let blobs = BlobStore::new(self.backend);
+        let mut table = load_bundles(&blobs)?;
+        let result = f(&mut table)?;
+        blobs.put_structured(BUNDLE_SERVICE, BUNDLE_ACCOUNT, &table)?;
+        Ok(result)
+    }
+
+    fn check_members(&self, members: &[KeystoreKey]) -> Result<(), KeystoreError> {
+        for key in members {
+            if !self.backend.has_password(&key.service, &key.account)? {
+                return Err(KeystoreError::KeyNotFound(format!(
+                    "{}:{}",
+                    key.service, key.account
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Names `members`, which must exist, as the bundle `name`. Fails with `InvalidInput` if the
+    /// name is empty or taken.
+    pub fn create(&self, name: &str, members: &[KeystoreKey]) -> Result<(), KeystoreError> {
+        if name.is_empty() {
+            return Err(KeystoreError::InvalidInput(
+                "Bundle name must not be empty".to_string(),
+            ));
+        }
+        self.check_members(members)?;
+        self.update(|table| {
+            if table.bundles.contains_key(name) {
+                return Err(KeystoreError::InvalidInput(format!(
+                    "Bundle {} already exists",
+                    name
+                )));
+            }
+            let ids = members
+                .iter()
+                .map(|key| table.assign(key))
+                .collect::<Result<BTreeSet<_>, _>>()?;
+            table.bundles.insert(name.to_string(), ids);
+            Ok(())
+        })
+    }
+
+    /// Adds `members`, which must exist, to the bundle `name`
+    pub fn add(&self, name: &str, members: &[KeystoreKey]) -> Result<(), KeystoreError> {
+        self.check_members(members)?;
+        self.update(|table| {
+            if !table.bundles.contains_key(name) {
+                return Err(not_found(name));
+            }
+            let ids = members
+                .iter()
+                .map(|key| table.assign(key))
+                .collect::<Result<Vec<_>, _>>()?;
+            table.bundles.get_mut(name).unwrap().extend(ids);
+            Ok(())
+        })
+    }
+
+    /// Takes `members` out of the bundle `name`, leaving the entries themselves alone
+    pub fn remove(&self, name: &str, members: &[KeystoreKey]) -> Result<(), KeystoreError> {
+        self.update(|table| {
+            let ids: BTreeSet<String> = members
+                .iter()
+                .filter_map(|key| table.id_of(key).cloned())
+                .collect();
+            let bundle = table.bundles.get_mut(name).ok_or_else(|| not_found(name))?;
+            bundle.retain(|id| !ids.contains(id));
+            table.collect_garbage();
+            Ok(())
+        })
+    }
+
+    /// Forgets the bundle `name`; its entries are kept. See [`Self::delete_entries`].
+    pub fn delete(&self, name: &str) -> Result<(), KeystoreError> {
+        self.update(|table| {
+            table.bundles.remove(name).ok_or_else(|| not_found(name))?;
+            table.collect_garbage();
+            Ok(())
+        })
+    }
+
+    /// Deletes the bundle's entries and then the bundle; returns the keys removed, sorted
+    pub fn delete_entries(&self, name: &str) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let members = self.members(name)?;
+        let removed = self.backend.delete_many(&members)?;
+        self.delete(name)?;
+        Ok(removed)
+    }
+
+    /// Bundle names, sorted
+    pub fn names(&self) -> Result<Vec<String>, KeystoreError> {
+        let table = load_bundles(&BlobStore::new(self.backend))?;
+        Ok(table.bundles.into_keys().collect())
+    }
+
+    /// Where the bundle's entries are now, sorted
+    pub fn members(&self, name: &str) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let table = load_bundles(&BlobStore::new(self.backend))?;
+        let ids = table.bundles.get(name).ok_or_else(|| not_found(name))?;
+        let mut members: Vec<KeystoreKey> = ids
+            .iter()
+            .filter_map(|id| table.entries.get(id).cloned())
+            .collect();
+        members.sort();
+        Ok(members)
+    }
+
+    /// Records that the entry at `from` now lives at `to`, e.g. after
+    /// [`crate::alias::AliasKeystore::rename`]. Nothing changes if it's in no bundle.
+    pub fn entry_renamed(&self, from: &KeystoreKey, to: &KeystoreKey) -> Result<(), KeystoreError> {
+        let _guard = lock_bundles()?;
+        let blobs = BlobStore::new(self.backend);
+        let mut table = load_bundles(&blobs)?;
+        let Some(id) = table.id_of(from).cloned() else {
+            return Ok(());
+        };
+        table.entries.insert(id, to.clone());
+        blobs.put_structured(BUNDLE_SERVICE, BUNDLE_ACCOUNT, &table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alias::AliasKeystore;
+    use crate::testing::MemoryKeystore;
+    use crate::KeystoreEntry;
+
+    fn twitch(account: &str) -> KeystoreKey {
+        KeystoreKey {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_bundles_follow_renames_and_delete_as_a_unit() {
+        let keystore = AliasKeystore::new(Box::new(MemoryKeystore::default()));
+        for account in ["bot-oauth", "refresh", "client-secret"] {
+            keystore
+                .set_password(&KeystoreEntry {
+                    service: "twitch".to_string(),
+                    account: account.to_string(),
+                    value: "secret".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let bundles = Bundles::new(&keystore);
+        bundles
+            .create("twitch-setup", &[twitch("bot-oauth"), twitch("refresh")])
+            .unwrap();
+        assert!(matches!(
+            bundles.create("twitch-setup", &[]),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            bundles.add("twitch-setup", &[twitch("missing")]),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+
+        let from = keystore
+            .rename(&twitch("bot-oauth"), &twitch("chat-bot"))
+            .unwrap();
+        bundles.entry_renamed(&from, &twitch("chat-bot")).unwrap();
+        assert_eq!(
+            bundles.members("twitch-setup").unwrap(),
+            [twitch("chat-bot"), twitch("refresh")]
+        );
+
+        assert_eq!(bundles.delete_entries("twitch-setup").unwrap().len(), 2);
+        assert!(bundles.names().unwrap().is_empty());
+        assert_eq!(
+            keystore.get_password("twitch", "client-secret").unwrap(),
+            "secret"
+        );
+        assert!(!keystore.has_password("twitch", "refresh").unwrap());
+    }
+}
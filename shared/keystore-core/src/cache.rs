@@ -0,0 +1,155 @@
+//! Byte-budgeted LRU cache of decrypted values.
+//!
+//! A daemon that runs for days on a small streaming PC shouldn't keep every value it ever read in
+//! memory. [`ValueCache`] counts the bytes of each cached service, account and value against a
+//! budget and evicts the least recently used entries once a write would exceed it. Values are
+//! held in [`Zeroizing`] buffers, so evicting, replacing or clearing one overwrites it before the
+//! memory is freed. A value larger than the whole budget is never cached.
+
+use lru::LruCache;
+use zeroize::Zeroizing;
+
+/// Budget of each cache unless the host sets another
+pub const DEFAULT_CACHE_BUDGET: usize = 256 * 1024;
+
+/// Bytes an entry counts against a budget
+pub fn entry_size(service: &str, account: &str, value: &str) -> usize {
+    service.len() + account.len() + value.len()
+}
+
+pub struct ValueCache {
+    entries: LruCache<(String, String), Zeroizing<String>>,
+    bytes: usize,
+    budget: usize,
+}
+
+impl Default for ValueCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_BUDGET)
+    }
+}
+
+impl ValueCache {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            bytes: 0,
+            budget,
+        }
+    }
+
+    /// Looks up a value and marks it most recently used
+    pub fn get(&mut self, service: &str, account: &str) -> Option<&Zeroizing<String>> {
+        self.entries
+            .get(&(service.to_string(), account.to_string()))
+    }
+
+    /// Caches a value, returning how many entries were evicted to make room
+    pub fn insert(&mut self, service: &str, account: &str, value: Zeroizing<String>) -> usize {
+        self.remove(service, account);
+        let size = entry_size(service, account, &value);
+        if size > self.budget {
+            return 0;
+        }
+        self.entries
+            .push((service.to_string(), account.to_string()), value);
+        self.bytes += size;
+        self.evict()
+    }
+
+    pub fn remove(&mut self, service: &str, account: &str) {
+        if let Some(((service, account), value)) = self
+            .entries
+            .pop_entry(&(service.to_string(), account.to_string()))
+        {
+            self.bytes -= entry_size(&service, &account, &value);
+        }
+    }
+
+    /// Drops every entry under `service`
+    pub fn remove_service(&mut self, service: &str) {
+        let accounts: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|((cached_service, _), _)| cached_service == service)
+            .map(|((_, account), _)| account.clone())
+            .collect();
+        for account in accounts {
+            self.remove(service, &account);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    /// Changes the budget, returning how many entries were evicted to fit it
+    pub fn set_budget(&mut self, budget: usize) -> usize {
+        self.budget = budget;
+        self.evict()
+    }
+
+    fn evict(&mut self) -> usize {
+        let mut evicted = 0;
+        while self.bytes > self.budget {
+            let Some(((service, account), value)) = self.entries.pop_lru() else {
+                break;
+            };
+            self.bytes -= entry_size(&service, &account, &value);
+            evicted += 1;
+        }
+        evicted
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Bytes currently counted against the budget
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_recently_used_entries_are_evicted_over_budget() {
+        // Each entry is 1 + 1 + 8 = 10 bytes
+        let mut cache = ValueCache::new(30);
+        for account in ["a", "b", "c"] {
+            assert_eq!(
+                cache.insert("s", account, Zeroizing::new("12345678".into())),
+                0
+            );
+        }
+        assert_eq!(cache.bytes(), 30);
+
+        cache.get("s", "a");
+        assert_eq!(cache.insert("s", "d", Zeroizing::new("12345678".into())), 1);
+        assert!(cache.get("s", "b").is_none());
+        assert!(cache.get("s", "a").is_some());
+        assert_eq!(cache.bytes(), 30);
+
+        // Too large to cache at all, and doesn't evict anything trying
+        assert_eq!(cache.insert("s", "e", Zeroizing::new("x".repeat(64))), 0);
+        assert_eq!(cache.len(), 3);
+
+        assert_eq!(cache.set_budget(15), 2);
+        assert_eq!(cache.bytes(), 10);
+        cache.remove_service("s");
+        assert!(cache.is_empty());
+        assert_eq!(cache.bytes(), 0);
+    }
+}
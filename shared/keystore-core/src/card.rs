@@ -0,0 +1,208 @@
+//! Single entries exported as passphrase-encrypted "credential cards".
+//!
+//! Handing one bot token to a moderator shouldn't mean exporting the whole vault. [`export_entry`]
+//! seals one entry, with its label, comment and expiry, into a short line of text that can be
+//! pasted into a message; [`read_card`] opens it again, and the entry is then imported through
+//! [`crate::import`] like a backup. The card is only as safe as the passphrase, which should travel
+//! separately.
+//!
+//! Text: `SEKC1:` followed by the card in unpadded URL-safe base64. Card: `SEKC` magic, version
+//! byte, 16-byte Argon2 salt and the Argon2id parameters, then the sealed JSON (XChaCha20-Poly1305
+//! nonce, ciphertext and tag) with everything before it as associated data.
+
+use crate::backup::{open, passphrase_key, seal, SALT_SIZE};
+use crate::ephemeral::is_ephemeral;
+use crate::error::KeystoreError;
+use crate::generate::random_bytes;
+use crate::kdf::{KdfParams, PARAMS_SIZE};
+use crate::platform::KeystoreOperations;
+use crate::KeystoreEntry;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+const PREFIX: &str = "SEKC1:";
+const MAGIC: &[u8; 4] = b"SEKC";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 5 + SALT_SIZE + PARAMS_SIZE;
+
+#[derive(Serialize, Deserialize)]
+struct CardPayload {
+    service: String,
+    account: String,
+    value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    /// Milliseconds since the Unix epoch
+    exported_at: u64,
+}
+
+/// An opened card
+#[derive(Debug, Clone)]
+pub struct CredentialCard {
+    pub entry: KeystoreEntry,
+    /// Milliseconds since the Unix epoch
+    pub exported_at: u64,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn invalid(message: &str) -> KeystoreError {
+    KeystoreError::InvalidInput(format!("Invalid credential card: {}", message))
+}
+
+/// The entry at `service`/`account`, with its metadata, as card text encrypted under `passphrase`
+pub fn export_entry(
+    backend: &dyn KeystoreOperations,
+    service: &str,
+    account: &str,
+    passphrase: &str,
+) -> Result<String, KeystoreError> {
+    if is_ephemeral(service) {
+        return Err(KeystoreError::InvalidInput(
+            "Ephemeral entries can't be exported".to_string(),
+        ));
+    }
+    let value = Zeroizing::new(backend.get_password(service, account)?);
+    // Metadata is a courtesy; a backend that keeps none still exports the value
+    let metadata = backend.get_metadata(service, account).unwrap_or_default();
+    let payload = CardPayload {
+        service: service.to_string(),
+        account: account.to_string(),
+        value: value.to_string(),
+        label: metadata.label,
+        comment: metadata.comment,
+        expires_at: metadata.expires_at,
+        exported_at: now_millis(),
+    };
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(&payload).map_err(|e| KeystoreError::Serialization(e.to_string()))?,
+    );
+    drop(Zeroizing::new(payload.value));
+
+    let salt = random_bytes(SALT_SIZE)?;
+    let params = KdfParams::load(backend)?;
+    let header = [MAGIC.as_slice(), &[VERSION], &salt, &params.to_bytes()].concat();
+    let sealed = seal(
+        &*passphrase_key(passphrase, &salt, &params)?,
+        &plaintext,
+        &header,
+    )?;
+    Ok(format!(
+        "{}{}",
+        PREFIX,
+        URL_SAFE_NO_PAD.encode([header, sealed].concat())
+    ))
+}
+
+/// Decrypts card text written by [`export_entry`]. Surrounding whitespace, e.g. from pasting, is
+/// ignored.
+pub fn read_card(card: &str, passphrase: &str) -> Result<CredentialCard, KeystoreError> {
+    let encoded = card
+        .trim()
+        .strip_prefix(PREFIX)
+        .ok_or_else(|| invalid("missing the SEKC1: prefix"))?;
+    let data = URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|_| invalid("not valid base64"))?;
+    if data.len() < HEADER_SIZE || &data[..4] != MAGIC {
+        return Err(invalid("truncated"));
+    }
+    if data[4] > VERSION {
+        return Err(KeystoreError::FormatTooNew(format!(
+            "Credential card has version {}, this build reads up to {}",
+            data[4], VERSION
+        )));
+    }
+    if data[4] != VERSION {
+        return Err(invalid(&format!("unsupported version {}", data[4])));
+    }
+    let (header, sealed) = data.split_at(HEADER_SIZE);
+    let salt = &header[5..5 + SALT_SIZE];
+    let params = KdfParams::from_bytes(
+        header[5 + SALT_SIZE..]
+            .try_into()
+            .map_err(|_| invalid("truncated"))?,
+    );
+    let key = passphrase_key(passphrase, salt, &params)?;
+    let plaintext = open(&key, sealed, header).ok_or_else(|| {
+        KeystoreError::AccessDenied(
+            "Card passphrase is incorrect or the card is damaged".to_string(),
+        )
+    })?;
+    let payload: CardPayload = serde_json::from_slice(&plaintext)
+        .map_err(|e| KeystoreError::Serialization(format!("Credential card payload: {}", e)))?;
+    Ok(CredentialCard {
+        entry: KeystoreEntry {
+            service: payload.service,
+            account: payload.account,
+            value: payload.value,
+            label: payload.label,
+            comment: payload.comment,
+            expires_at: payload.expires_at,
+        },
+        exported_at: payload.exported_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::apply_import;
+    use crate::testing::MemoryKeystore;
+
+    #[test]
+    fn test_cards_carry_one_entry_between_keystores() {
+        let streamer = MemoryKeystore::default();
+        for account in ["bot-oauth", "broadcaster-oauth"] {
+            streamer
+                .set_password(&KeystoreEntry {
+                    service: "twitch".to_string(),
+                    account: account.to_string(),
+                    value: format!("{}-token", account),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let card = export_entry(&streamer, "twitch", "bot-oauth", "hunter22").unwrap();
+        assert!(card.starts_with(PREFIX));
+
+        assert!(matches!(
+            read_card(&card, "wrong"),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        let mut data = URL_SAFE_NO_PAD.decode(&card[PREFIX.len()..]).unwrap();
+        for (version, too_new) in [(0, false), (VERSION + 1, true)] {
+            data[4] = version;
+            let altered = format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(&data));
+            match read_card(&altered, "hunter22") {
+                Err(KeystoreError::FormatTooNew(_)) => assert!(too_new),
+                Err(KeystoreError::InvalidInput(_)) => assert!(!too_new),
+                other => panic!("unexpected {:?}", other.map(|_| ())),
+            }
+        }
+        let opened = read_card(&format!("  {}\n", card), "hunter22").unwrap();
+        assert_eq!(opened.entry.value, "bot-oauth-token");
+
+        let moderator = MemoryKeystore::default();
+        let diff = apply_import(&moderator, &[opened.entry], false).unwrap();
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(
+            moderator.get_password("twitch", "bot-oauth").unwrap(),
+            "bot-oauth-token"
+        );
+        assert_eq!(moderator.list_credentials("twitch").unwrap(), ["bot-oauth"]);
+    }
+}
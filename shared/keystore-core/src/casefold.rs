@@ -0,0 +1,423 @@
+//! Opt-in case-insensitive account lookup.
+//!
+//! Chat platforms report the same login as `StreamerName` in one place and `streamername` in
+//! another. [`CaseInsensitiveKeystore`] keeps an index from each service's lowercased account names
+//! to the names actually stored, so a lookup in any casing resolves to the stored entry, and a write
+//! in a new casing updates that entry instead of creating a second one. Service names stay exact.
+//!
+//! The index is loaded on first use and seeded from `list_keys` where the backend supports it.
+//! Entries stored before the mode was enabled that differ only in case are collisions: a lookup
+//! that matches none of them exactly fails with `Ambiguous` rather than picking one.
+
+use crate::blob::BlobStore;
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+const INDEX_SERVICE: &str = "streaming-enhancement:case-index";
+const INDEX_ACCOUNT: &str = "index";
+
+/// service -> normalized account -> stored accounts
+type CaseIndex = BTreeMap<String, BTreeMap<String, BTreeSet<String>>>;
+
+pub fn normalize(account: &str) -> String {
+    account.to_lowercase()
+}
+
+pub struct CaseInsensitiveKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    index: Mutex<Option<CaseIndex>>,
+}
+
+impl CaseInsensitiveKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self {
+            inner,
+            index: Mutex::new(None),
+        }
+    }
+
+    /// Runs `f` on the index, loading it first if needed and merging in the entries the backend
+    /// can enumerate. The lock is held throughout so index updates don't interleave.
+    fn with_index<T>(
+        &self,
+        f: impl FnOnce(&mut CaseIndex) -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        let mut guard = self
+            .index
+            .lock()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))?;
+        if guard.is_none() {
+            let mut index: CaseIndex =
+                match self.blobs().get_structured(INDEX_SERVICE, INDEX_ACCOUNT) {
+                    Ok(index) => index,
+                    Err(KeystoreError::KeyNotFound(_)) => CaseIndex::new(),
+                    Err(e) => return Err(e),
+                };
+            match self.inner.list_keys() {
+                Ok(keys) => {
+                    for key in keys.into_iter().filter(|key| key.service != INDEX_SERVICE) {
+                        index
+                            .entry(key.service)
+                            .or_default()
+                            .entry(normalize(&key.account))
+                            .or_default()
+                            .insert(key.account);
+                    }
+                }
+                Err(KeystoreError::PlatformNotSupported) => {}
+                Err(e) => return Err(e),
+            }
+            *guard = Some(index);
+        }
+        f(guard.as_mut().unwrap())
+    }
+
+    fn blobs(&self) -> BlobStore<'_> {
+        BlobStore::new(self.inner.as_ref())
+    }
+
+    fn save(&self, index: &CaseIndex) -> Result<(), KeystoreError> {
+        self.blobs()
+            .put_structured(INDEX_SERVICE, INDEX_ACCOUNT, index)
+    }
+
+    /// The stored account `account` refers to, or `None` if the index has no match
+    fn resolve(
+        index: &CaseIndex,
+        service: &str,
+        account: &str,
+    ) -> Result<Option<String>, KeystoreError> {
+        let Some(stored) = index
+            .get(service)
+            .and_then(|accounts| accounts.get(&normalize(account)))
+        else {
+            return Ok(None);
+        };
+        if stored.contains(account) {
+            return Ok(Some(account.to_string()));
+        }
+        let mut names = stored.iter();
+        match (names.next(), names.next()) {
+            (Some(only), None) => Ok(Some(only.clone())),
+            (None, _) => Ok(None),
+            _ => Err(KeystoreError::Ambiguous(format!(
+                "{}:{} matches {}",
+                service,
+                account,
+                stored.iter().cloned().collect::<Vec<_>>().join(", ")
+            ))),
+        }
+    }
+
+    /// Accounts under each service that differ only in case, as (service, accounts)
+    pub fn collisions(&self) -> Result<Vec<(String, Vec<String>)>, KeystoreError> {
+        self.with_index(|index| {
+            Ok(index
+                .iter()
+                .flat_map(|(service, accounts)| {
+                    accounts
+                        .values()
+                        .filter(|stored| stored.len() > 1)
+                        .map(move |stored| (service.clone(), stored.iter().cloned().collect()))
+                })
+                .collect())
+        })
+    }
+}
+
+/// Drops `stored` from the index
+fn forget(index: &mut CaseIndex, service: &str, stored: &str) {
+    let normalized = normalize(stored);
+    if let Some(accounts) = index.get_mut(service) {
+        if let Some(names) = accounts.get_mut(&normalized) {
+            names.remove(stored);
+            if names.is_empty() {
+                accounts.remove(&normalized);
+            }
+        }
+        if accounts.is_empty() {
+            index.remove(service);
+        }
+    }
+}
+
+impl KeystoreOperations for CaseInsensitiveKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.with_index(|index| {
+            if let Some(stored) = Self::resolve(index, &entry.service, &entry.account)? {
+                return self.inner.set_password(&KeystoreEntry {
+                    account: stored,
+                    ..entry.clone()
+                });
+            }
+
+            self.inner.set_password(entry)?;
+            index
+                .entry(entry.service.clone())
+                .or_default()
+                .entry(normalize(&entry.account))
+                .or_default()
+                .insert(entry.account.clone());
+            self.save(index)
+        })
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        let stored = self.with_index(|index| Self::resolve(index, service, account))?;
+        self.inner
+            .get_password(service, stored.as_deref().unwrap_or(account))
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.with_index(|index| {
+            let stored =
+                Self::resolve(index, service, account)?.unwrap_or_else(|| account.to_string());
+            self.inner.delete_password(service, &stored)?;
+            forget(index, service, &stored);
+            self.save(index)
+        })
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner
+            .backend_info()
+            .with_detail("case_insensitive", true)
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.list_credentials(service)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        let stored = self.with_index(|index| {
+            keys.iter()
+                .map(|key| {
+                    Ok(KeystoreKey {
+                        service: key.service.clone(),
+                        account: Self::resolve(index, &key.service, &key.account)?
+                            .unwrap_or_else(|| key.account.clone()),
+                    })
+                })
+                .collect::<Result<Vec<_>, KeystoreError>>()
+        })?;
+        self.inner.get_many(&stored)
+    }
+
+    /// Entries in the batch that differ only in case resolve to the first of them, as if written
+    /// one after the other
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.with_index(|index| {
+            let mut updated = index.clone();
+            let mut stored = Vec::with_capacity(entries.len());
+            for entry in entries {
+                match Self::resolve(&updated, &entry.service, &entry.account)? {
+                    Some(account) => stored.push(KeystoreEntry {
+                        account,
+                        ..entry.clone()
+                    }),
+                    None => {
+                        updated
+                            .entry(entry.service.clone())
+                            .or_default()
+                            .entry(normalize(&entry.account))
+                            .or_default()
+                            .insert(entry.account.clone());
+                        stored.push(entry.clone());
+                    }
+                }
+            }
+            self.inner.set_many(&stored)?;
+            if updated != *index {
+                *index = updated;
+                self.save(index)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Returns the stored keys removed
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.with_index(|index| {
+            let stored = keys
+                .iter()
+                .map(|key| {
+                    Ok(KeystoreKey {
+                        service: key.service.clone(),
+                        account: Self::resolve(index, &key.service, &key.account)?
+                            .unwrap_or_else(|| key.account.clone()),
+                    })
+                })
+                .collect::<Result<Vec<_>, KeystoreError>>()?;
+            let removed = self.inner.delete_many(&stored)?;
+            for key in &removed {
+                forget(index, &key.service, &key.account);
+            }
+            if !removed.is_empty() {
+                self.save(index)?;
+            }
+            Ok(removed)
+        })
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.with_index(|index| {
+            let removed = self.inner.delete_service(service)?;
+            if index.remove(service).is_some() {
+                self.save(index)?;
+            }
+            Ok(removed)
+        })
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.with_index(|index| {
+            let removed = self.inner.wipe_all()?;
+            index.clear();
+            Ok(removed)
+        })
+    }
+
+    /// Both entries exist already, so the stored names don't change
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        let stored = self.with_index(|index| {
+            [a, b]
+                .map(|key| {
+                    Ok(KeystoreKey {
+                        service: key.service.clone(),
+                        account: Self::resolve(index, &key.service, &key.account)?
+                            .unwrap_or_else(|| key.account.clone()),
+                    })
+                })
+                .into_iter()
+                .collect::<Result<Vec<_>, KeystoreError>>()
+        })?;
+        self.inner.swap(&stored[0], &stored[1])
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        let stored = self.with_index(|index| Self::resolve(index, service, account))?;
+        self.inner
+            .get_metadata(service, stored.as_deref().unwrap_or(account))
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        let stored = self.with_index(|index| Self::resolve(index, service, account))?;
+        self.inner
+            .has_password(service, stored.as_deref().unwrap_or(account))
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        self.with_index(|index| {
+            if let Some(stored) = Self::resolve(index, service, account)? {
+                return self.inner.get_or_create(service, &stored, length);
+            }
+
+            let value = self.inner.get_or_create(service, account, length)?;
+            let spellings = index
+                .entry(service.to_string())
+                .or_default()
+                .entry(normalize(account))
+                .or_default();
+            if spellings.insert(account.to_string()) {
+                self.save(index)?;
+            }
+            Ok(value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    fn entry(account: &str, value: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_lookup_ignores_account_case() {
+        let keystore = CaseInsensitiveKeystore::new(Box::new(MemoryKeystore::default()));
+        keystore
+            .set_password(&entry("streamername", "one"))
+            .unwrap();
+
+        assert_eq!(
+            keystore.get_password("twitch", "StreamerName").unwrap(),
+            "one"
+        );
+        keystore
+            .set_password(&entry("STREAMERNAME", "two"))
+            .unwrap();
+        assert_eq!(
+            keystore.get_password("twitch", "streamername").unwrap(),
+            "two"
+        );
+        assert_eq!(
+            keystore
+                .list_keys()
+                .unwrap()
+                .iter()
+                .filter(|key| key.service == "twitch")
+                .count(),
+            1
+        );
+        assert!(keystore.get_password("Twitch", "streamername").is_err());
+
+        keystore.delete_password("twitch", "StreamerName").unwrap();
+        assert!(matches!(
+            keystore.get_password("twitch", "streamername"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_existing_case_variants_are_collisions() {
+        let backend = MemoryKeystore::default();
+        backend.set_password(&entry("Streamer", "upper")).unwrap();
+        backend.set_password(&entry("streamer", "lower")).unwrap();
+        let keystore = CaseInsensitiveKeystore::new(Box::new(backend));
+
+        assert_eq!(
+            keystore.collisions().unwrap(),
+            vec![(
+                "twitch".to_string(),
+                vec!["Streamer".to_string(), "streamer".to_string()]
+            )]
+        );
+        assert_eq!(
+            keystore.get_password("twitch", "Streamer").unwrap(),
+            "upper"
+        );
+        assert!(matches!(
+            keystore.get_password("twitch", "STREAMER"),
+            Err(KeystoreError::Ambiguous(_))
+        ));
+    }
+}
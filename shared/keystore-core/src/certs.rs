@@ -0,0 +1,576 @@
+//! Certificate entries: an X.509 chain plus an optional private key.
+//!
+//! Input is validated on write: every certificate must parse, and a private key must be PKCS#8
+//! and match the leaf certificate's public key. The chain and key are stored together as one blob
+//! under the `streaming-enhancement:certificates` service. An index of [`CertificateInfo`] rows
+//! lets callers read subjects, issuers and validity without touching the key.
+//!
+//! Stored keys never leave this module: renewal goes through [`CertificateStore::generate_csr`],
+//! which signs a PKCS#10 request with the key and returns only the request.
+
+use crate::blob::BlobStore;
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+
+use p12_keystore::{KeyStore, KeyStoreEntry};
+use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair, PublicKeyData};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use x509_parser::certificate::X509Certificate;
+use x509_parser::prelude::FromDer;
+
+/// Service under which certificates are stored, one account per name
+pub const CERTIFICATES_SERVICE: &str = "streaming-enhancement:certificates";
+
+pub(crate) const INDEX_SERVICE: &str = "streaming-enhancement:certificates-index";
+pub(crate) const INDEX_ACCOUNT: &str = "index";
+
+const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+const CERTIFICATE_TAG: &str = "CERTIFICATE";
+const PRIVATE_KEY_TAG: &str = "PRIVATE KEY";
+
+/// Serializes index read-modify-write cycles within the process
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Public description of a stored certificate; never includes the private key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CertificateInfo {
+    pub name: String,
+    pub subject: String,
+    pub issuer: String,
+    /// Colon-separated hex, as printed by OpenSSL
+    pub serial: String,
+    /// Milliseconds since the Unix epoch
+    pub not_before: i64,
+    pub not_after: i64,
+    /// SHA-256 of the leaf certificate's DER encoding, lowercase hex
+    pub fingerprint_sha256: String,
+    /// Number of certificates including the leaf
+    pub chain_length: usize,
+    pub has_private_key: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StoredCertificate {
+    /// DER certificates, leaf first, PEM encoded
+    chain: Vec<String>,
+    /// PKCS#8 PEM
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    private_key: Option<String>,
+}
+
+pub struct CertificateStore<'a> {
+    blobs: BlobStore<'a>,
+}
+
+fn invalid(message: impl Into<String>) -> KeystoreError {
+    KeystoreError::InvalidInput(message.into())
+}
+
+fn parse_certificate(der: &[u8]) -> Result<X509Certificate<'_>, KeystoreError> {
+    match X509Certificate::from_der(der) {
+        Ok(([], cert)) => Ok(cert),
+        Ok(_) => Err(invalid("Trailing data after certificate")),
+        Err(e) => Err(invalid(format!("Invalid certificate: {}", e))),
+    }
+}
+
+/// Checks a chain and key and builds the index row for them
+fn describe(
+    name: &str,
+    chain: &[Vec<u8>],
+    private_key: Option<&[u8]>,
+) -> Result<CertificateInfo, KeystoreError> {
+    let leaf_der = chain
+        .first()
+        .ok_or_else(|| invalid("No certificate found"))?;
+    let leaf = parse_certificate(leaf_der)?;
+    for der in &chain[1..] {
+        parse_certificate(der)?;
+    }
+
+    if let Some(key) = private_key {
+        let key_pair = KeyPair::try_from(key)
+            .map_err(|e| invalid(format!("Private key is not a supported PKCS#8 key: {}", e)))?;
+        if key_pair.subject_public_key_info() != leaf.public_key().raw {
+            return Err(invalid(
+                "Private key does not match the leaf certificate's public key",
+            ));
+        }
+    }
+
+    let validity = leaf.validity();
+    Ok(CertificateInfo {
+        name: name.to_string(),
+        subject: leaf.subject().to_string(),
+        issuer: leaf.issuer().to_string(),
+        serial: leaf.raw_serial_as_string(),
+        not_before: validity.not_before.timestamp() * 1000,
+        not_after: validity.not_after.timestamp() * 1000,
+        fingerprint_sha256: format!("{:x}", Sha256::digest(leaf_der)),
+        chain_length: chain.len(),
+        has_private_key: private_key.is_some(),
+    })
+}
+
+/// Parses an OpenSSL-style subject such as `CN=overlay.local, O=Streamer, C=US`
+fn parse_subject(subject: &str) -> Result<DistinguishedName, KeystoreError> {
+    let mut name = DistinguishedName::new();
+    for part in subject.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| invalid(format!("Invalid subject component '{}'", part)))?;
+        let dn_type = match key.trim().to_ascii_uppercase().as_str() {
+            "CN" => DnType::CommonName,
+            "O" => DnType::OrganizationName,
+            "OU" => DnType::OrganizationalUnitName,
+            "C" => DnType::CountryName,
+            "ST" => DnType::StateOrProvinceName,
+            "L" => DnType::LocalityName,
+            other => {
+                return Err(invalid(format!(
+                    "Unsupported subject attribute '{}'",
+                    other
+                )))
+            }
+        };
+        name.push(dn_type, value.trim());
+    }
+
+    if name.iter().next().is_none() {
+        return Err(invalid("Subject must not be empty"));
+    }
+    Ok(name)
+}
+
+fn encode_pem(tag: &str, der: &[u8]) -> String {
+    pem::encode(&pem::Pem::new(tag, der.to_vec()))
+}
+
+impl<'a> CertificateStore<'a> {
+    pub fn new(backend: &'a dyn KeystoreOperations) -> Self {
+        Self {
+            blobs: BlobStore::new(backend),
+        }
+    }
+
+    fn load_index(&self) -> Result<Vec<CertificateInfo>, KeystoreError> {
+        match self.blobs.get_structured(INDEX_SERVICE, INDEX_ACCOUNT) {
+            Ok(index) => Ok(index),
+            Err(KeystoreError::KeyNotFound(_)) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn lock_index(&self) -> Result<std::sync::MutexGuard<'static, ()>, KeystoreError> {
+        INDEX_LOCK
+            .lock()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))
+    }
+
+    fn store(
+        &self,
+        name: &str,
+        chain: Vec<Vec<u8>>,
+        private_key: Option<Vec<u8>>,
+    ) -> Result<CertificateInfo, KeystoreError> {
+        if name.trim().is_empty() {
+            return Err(invalid("Certificate name must not be empty"));
+        }
+
+        let info = describe(name, &chain, private_key.as_deref())?;
+        let stored = StoredCertificate {
+            chain: chain
+                .iter()
+                .map(|der| encode_pem(CERTIFICATE_TAG, der))
+                .collect(),
+            private_key: private_key.map(|der| encode_pem(PRIVATE_KEY_TAG, &der)),
+        };
+
+        let _guard = self.lock_index()?;
+        let mut index = self.load_index()?;
+
+        self.blobs
+            .put_structured(CERTIFICATES_SERVICE, name, &stored)?;
+
+        index.retain(|existing| existing.name != name);
+        index.push(info.clone());
+        self.blobs
+            .put_structured(INDEX_SERVICE, INDEX_ACCOUNT, &index)?;
+
+        Ok(info)
+    }
+
+    /// Stores a PEM bundle: one or more `CERTIFICATE` blocks, leaf first, and optionally a PKCS#8
+    /// `PRIVATE KEY` block
+    pub fn import_pem(&self, name: &str, pem_text: &str) -> Result<CertificateInfo, KeystoreError> {
+        let blocks =
+            pem::parse_many(pem_text).map_err(|e| invalid(format!("Invalid PEM: {}", e)))?;
+
+        let mut chain = Vec::new();
+        let mut private_key = None;
+        for block in blocks {
+            match block.tag() {
+                CERTIFICATE_TAG => chain.push(block.into_contents()),
+                PRIVATE_KEY_TAG if private_key.is_none() => {
+                    private_key = Some(block.into_contents())
+                }
+                PRIVATE_KEY_TAG => return Err(invalid("PEM contains more than one private key")),
+                "RSA PRIVATE KEY" | "EC PRIVATE KEY" => {
+                    return Err(invalid(format!(
+                        "{} keys are not supported; convert the key to PKCS#8",
+                        block.tag()
+                    )))
+                }
+                tag => return Err(invalid(format!("Unexpected PEM block {}", tag))),
+            }
+        }
+
+        self.store(name, chain, private_key)
+    }
+
+    /// Stores the key and chain from a PKCS#12 (PFX) archive
+    pub fn import_pkcs12(
+        &self,
+        name: &str,
+        der: &[u8],
+        password: &str,
+    ) -> Result<CertificateInfo, KeystoreError> {
+        let archive = KeyStore::from_pkcs12(der, password)
+            .map_err(|e| invalid(format!("Invalid PKCS#12 archive: {}", e)))?;
+
+        let (chain, private_key) = match archive.private_key_chain() {
+            Some((_, key_chain)) => (
+                key_chain
+                    .chain()
+                    .iter()
+                    .map(|cert| cert.as_der().to_vec())
+                    .collect(),
+                Some(key_chain.key().to_vec()),
+            ),
+            None => (
+                archive
+                    .entries()
+                    .filter_map(|(_, entry)| match entry {
+                        KeyStoreEntry::Certificate(cert) => Some(cert.as_der().to_vec()),
+                        KeyStoreEntry::PrivateKeyChain(_) => None,
+                    })
+                    .collect(),
+                None,
+            ),
+        };
+
+        self.store(name, chain, private_key)
+    }
+
+    /// Metadata of one certificate, without reading the stored key
+    pub fn info(&self, name: &str) -> Result<CertificateInfo, KeystoreError> {
+        self.load_index()?
+            .into_iter()
+            .find(|info| info.name == name)
+            .ok_or_else(|| KeystoreError::KeyNotFound(format!("{}:{}", CERTIFICATES_SERVICE, name)))
+    }
+
+    /// The certificate chain as PEM, leaf first; the private key is never included
+    pub fn chain_pem(&self, name: &str) -> Result<String, KeystoreError> {
+        let stored: StoredCertificate = self.blobs.get_structured(CERTIFICATES_SERVICE, name)?;
+        Ok(stored.chain.concat())
+    }
+
+    /// All certificates, sorted by name
+    pub fn list(&self) -> Result<Vec<CertificateInfo>, KeystoreError> {
+        let mut index = self.load_index()?;
+        index.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(index)
+    }
+
+    /// Certificates whose `not_after` falls within `days` from now, including ones that have
+    /// already expired, soonest first
+    pub fn list_expiring_certs(&self, days: u32) -> Result<Vec<CertificateInfo>, KeystoreError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        self.list_expiring_certs_at(now, days)
+    }
+
+    fn list_expiring_certs_at(
+        &self,
+        now: i64,
+        days: u32,
+    ) -> Result<Vec<CertificateInfo>, KeystoreError> {
+        let cutoff = now.saturating_add(i64::from(days) * MILLIS_PER_DAY);
+        let mut expiring: Vec<_> = self
+            .load_index()?
+            .into_iter()
+            .filter(|info| info.not_after <= cutoff)
+            .collect();
+        expiring.sort_by_key(|info| info.not_after);
+        Ok(expiring)
+    }
+
+    /// Builds a PKCS#10 certificate signing request for `subject` and `sans`, signed with the
+    /// private key stored under `key_name`. Only the CSR PEM is returned.
+    pub fn generate_csr(
+        &self,
+        key_name: &str,
+        subject: &str,
+        sans: &[String],
+    ) -> Result<String, KeystoreError> {
+        let distinguished_name = parse_subject(subject)?;
+
+        let stored: StoredCertificate =
+            self.blobs.get_structured(CERTIFICATES_SERVICE, key_name)?;
+        let key_pem = stored.private_key.ok_or_else(|| {
+            invalid(format!(
+                "Certificate '{}' has no stored private key",
+                key_name
+            ))
+        })?;
+        let key_pair = KeyPair::from_pem(&key_pem)
+            .map_err(|e| KeystoreError::Serialization(format!("Invalid stored key: {}", e)))?;
+
+        let mut params = CertificateParams::new(sans.to_vec())
+            .map_err(|e| invalid(format!("Invalid subject alternative name: {}", e)))?;
+        params.distinguished_name = distinguished_name;
+
+        params
+            .serialize_request(&key_pair)
+            .and_then(|csr| csr.pem())
+            .map_err(|e| KeystoreError::Platform(format!("Failed to create CSR: {}", e)))
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), KeystoreError> {
+        let _guard = self.lock_index()?;
+        let mut index = self.load_index()?;
+
+        let result = self.blobs.delete(CERTIFICATES_SERVICE, name);
+        let before = index.len();
+        index.retain(|info| info.name != name);
+        if index.len() != before {
+            self.blobs
+                .put_structured(INDEX_SERVICE, INDEX_ACCOUNT, &index)?;
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use p12_keystore::{Certificate, PrivateKeyChain};
+    use rcgen::{date_time_ymd, generate_simple_self_signed, CertificateParams, CertifiedKey};
+
+    fn self_signed() -> CertifiedKey<KeyPair> {
+        generate_simple_self_signed(vec!["overlay.local".to_string()]).unwrap()
+    }
+
+    #[test]
+    fn test_import_pem_with_key() {
+        let backend = MemoryKeystore::default();
+        let certs = CertificateStore::new(&backend);
+        let CertifiedKey { cert, signing_key } = self_signed();
+
+        let bundle = format!("{}{}", cert.pem(), signing_key.serialize_pem());
+        let info = certs.import_pem("overlay-tls", &bundle).unwrap();
+
+        assert_eq!(info.name, "overlay-tls");
+        assert!(info.subject.contains("rcgen self signed cert"));
+        assert!(info.has_private_key);
+        assert_eq!(info.chain_length, 1);
+        assert!(info.not_after > info.not_before);
+        assert_eq!(certs.info("overlay-tls").unwrap(), info);
+
+        let chain = certs.chain_pem("overlay-tls").unwrap();
+        assert!(chain.contains("BEGIN CERTIFICATE"));
+        assert!(!chain.contains("PRIVATE KEY"));
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        let backend = MemoryKeystore::default();
+        let certs = CertificateStore::new(&backend);
+
+        assert!(matches!(
+            certs.import_pem("bad", "not a certificate"),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+
+        let garbage = pem::encode(&pem::Pem::new(CERTIFICATE_TAG, vec![0x30, 0x03, 1, 2, 3]));
+        assert!(matches!(
+            certs.import_pem("bad", &garbage),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+        assert!(backend.entries.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_mismatched_key() {
+        let backend = MemoryKeystore::default();
+        let certs = CertificateStore::new(&backend);
+        let first = self_signed();
+        let second = self_signed();
+
+        let bundle = format!("{}{}", first.cert.pem(), second.signing_key.serialize_pem());
+        assert!(matches!(
+            certs.import_pem("mismatch", &bundle),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_import_pkcs12() {
+        let backend = MemoryKeystore::default();
+        let certs = CertificateStore::new(&backend);
+        let CertifiedKey { cert, signing_key } = self_signed();
+
+        let mut archive = KeyStore::new();
+        archive.add_entry(
+            "rtmps-client",
+            KeyStoreEntry::PrivateKeyChain(PrivateKeyChain::new(
+                signing_key.serialize_der(),
+                [1u8; 20],
+                [Certificate::from_der(cert.der()).unwrap()],
+            )),
+        );
+        let pfx = archive.writer("secret").write().unwrap();
+
+        let info = certs.import_pkcs12("rtmps", &pfx, "secret").unwrap();
+        assert!(info.has_private_key);
+        assert_eq!(
+            info.fingerprint_sha256,
+            format!("{:x}", Sha256::digest(cert.der()))
+        );
+
+        assert!(matches!(
+            certs.import_pkcs12("rtmps", &pfx, "wrong"),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_list_and_delete() {
+        let backend = MemoryKeystore {
+            max_value_size: Some(2560),
+            ..Default::default()
+        };
+        let certs = CertificateStore::new(&backend);
+
+        certs.import_pem("b", &self_signed().cert.pem()).unwrap();
+        certs.import_pem("a", &self_signed().cert.pem()).unwrap();
+
+        let names: Vec<_> = certs.list().unwrap().into_iter().map(|i| i.name).collect();
+        assert_eq!(names, vec!["a", "b"]);
+
+        certs.delete("a").unwrap();
+        assert_eq!(certs.list().unwrap().len(), 1);
+        assert!(matches!(
+            certs.info("a"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_generate_csr_with_stored_key() {
+        use x509_parser::certification_request::X509CertificationRequest;
+        use x509_parser::extensions::{GeneralName, ParsedExtension};
+
+        let backend = MemoryKeystore::default();
+        let certs = CertificateStore::new(&backend);
+        let CertifiedKey { cert, signing_key } = self_signed();
+        let bundle = format!("{}{}", cert.pem(), signing_key.serialize_pem());
+        certs.import_pem("overlay-tls", &bundle).unwrap();
+
+        let csr_pem = certs
+            .generate_csr(
+                "overlay-tls",
+                "CN=overlay.local, O=Streamer",
+                &["overlay.local".to_string(), "127.0.0.1".to_string()],
+            )
+            .unwrap();
+        assert!(!csr_pem.contains("PRIVATE KEY"));
+
+        let der = pem::parse(&csr_pem).unwrap().into_contents();
+        let (_, csr) = X509CertificationRequest::from_der(&der).unwrap();
+        let info = &csr.certification_request_info;
+        assert_eq!(info.subject.to_string(), "CN=overlay.local, O=Streamer");
+        assert_eq!(info.subject_pki.raw, signing_key.subject_public_key_info());
+
+        let sans: Vec<_> = csr
+            .requested_extensions()
+            .into_iter()
+            .flatten()
+            .filter_map(|ext| match ext {
+                ParsedExtension::SubjectAlternativeName(san) => Some(san),
+                _ => None,
+            })
+            .flat_map(|san| san.general_names.iter())
+            .map(|name| match name {
+                GeneralName::DNSName(dns) => dns.to_string(),
+                GeneralName::IPAddress(ip) => format!("{:?}", ip),
+                other => format!("{}", other),
+            })
+            .collect();
+        assert_eq!(sans, vec!["overlay.local", "[127, 0, 0, 1]"]);
+    }
+
+    #[test]
+    fn test_generate_csr_errors() {
+        let backend = MemoryKeystore::default();
+        let certs = CertificateStore::new(&backend);
+        certs
+            .import_pem("public-only", &self_signed().cert.pem())
+            .unwrap();
+
+        assert!(matches!(
+            certs.generate_csr("public-only", "CN=x", &[]),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            certs.generate_csr("missing", "CN=x", &[]),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+        assert!(matches!(
+            certs.generate_csr("public-only", "EMAIL=a@b", &[]),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+    }
+
+    fn expiring_on(year: i32, month: u8, day: u8) -> String {
+        let key_pair = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(vec!["rtmps.local".to_string()]).unwrap();
+        params.not_before = date_time_ymd(2020, 1, 1);
+        params.not_after = date_time_ymd(year, month, day);
+        params.self_signed(&key_pair).unwrap().pem()
+    }
+
+    #[test]
+    fn test_list_expiring_certs() {
+        let backend = MemoryKeystore::default();
+        let certs = CertificateStore::new(&backend);
+
+        certs
+            .import_pem("expired", &expiring_on(2024, 12, 1))
+            .unwrap();
+        certs.import_pem("soon", &expiring_on(2025, 1, 20)).unwrap();
+        certs.import_pem("later", &expiring_on(2026, 1, 1)).unwrap();
+
+        // 2025-01-01T00:00:00Z
+        let now = 1_735_689_600_000;
+        let names: Vec<_> = certs
+            .list_expiring_certs_at(now, 30)
+            .unwrap()
+            .into_iter()
+            .map(|info| info.name)
+            .collect();
+        assert_eq!(names, vec!["expired", "soon"]);
+
+        assert_eq!(certs.list_expiring_certs_at(now, 0).unwrap().len(), 1);
+        assert_eq!(certs.list_expiring_certs_at(now, 365).unwrap().len(), 3);
+    }
+}
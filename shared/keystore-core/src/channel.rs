@@ -0,0 +1,272 @@
+//! Encrypted daemon⇄overlay channel keyed by an X25519 handshake.
+//!
+//! The daemon holds a static X25519 key in the keystore and publishes its public half to the
+//! overlay out of band (for example in the browser-source URL). The overlay generates an ephemeral
+//! keypair per connection and sends its public key as the first WebSocket message. Both sides run
+//! Diffie-Hellman over one static and one ephemeral key and feed the result through HKDF-SHA256,
+//! bound to both public keys, to get a [`ChannelSession`] key. Messages are then sealed with
+//! XChaCha20-Poly1305 under a random 24-byte nonce prepended to the ciphertext.
+
+use crate::error::KeystoreError;
+use crate::generate::random_bytes;
+use crate::platform::KeystoreOperations;
+use crate::KeystoreEntry;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+/// Service under which static channel keys are stored, one account per key name
+pub const CHANNEL_SERVICE: &str = "streaming-enhancement:channel";
+
+pub const PUBLIC_KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 24;
+const SESSION_KEY_INFO: &[u8] = b"streaming-enhancement:channel:v1";
+
+fn random_secret() -> Result<StaticSecret, KeystoreError> {
+    let bytes = Zeroizing::new(random_bytes(32)?);
+    let mut secret = Zeroizing::new([0u8; 32]);
+    secret.copy_from_slice(&bytes);
+    Ok(StaticSecret::from(*secret))
+}
+
+fn public_key(bytes: &[u8]) -> Result<PublicKey, KeystoreError> {
+    let bytes: [u8; PUBLIC_KEY_SIZE] = bytes.try_into().map_err(|_| {
+        KeystoreError::InvalidInput(format!(
+            "X25519 public keys are {} bytes, got {}",
+            PUBLIC_KEY_SIZE,
+            bytes.len()
+        ))
+    })?;
+    Ok(PublicKey::from(bytes))
+}
+
+/// A keypair for one connection, generated by the initiating side
+pub struct EphemeralKeypair {
+    secret: StaticSecret,
+    public: PublicKey,
+}
+
+impl EphemeralKeypair {
+    pub fn generate() -> Result<Self, KeystoreError> {
+        let secret = random_secret()?;
+        let public = PublicKey::from(&secret);
+        Ok(Self { secret, public })
+    }
+
+    /// Restores a keypair from its 32-byte secret
+    pub fn from_secret_bytes(bytes: &[u8]) -> Result<Self, KeystoreError> {
+        let bytes: [u8; 32] = bytes.try_into().map_err(|_| {
+            KeystoreError::InvalidInput("X25519 secret keys are 32 bytes".to_string())
+        })?;
+        let secret = StaticSecret::from(bytes);
+        let public = PublicKey::from(&secret);
+        Ok(Self { secret, public })
+    }
+
+    pub fn public_key(&self) -> [u8; PUBLIC_KEY_SIZE] {
+        self.public.to_bytes()
+    }
+
+    pub fn secret_key(&self) -> Zeroizing<[u8; 32]> {
+        Zeroizing::new(self.secret.to_bytes())
+    }
+
+    /// Initiator side: combines this keypair with the responder's static public key
+    pub fn connect(&self, peer_static_public: &[u8]) -> Result<ChannelSession, KeystoreError> {
+        let peer = public_key(peer_static_public)?;
+        ChannelSession::derive(&self.secret, &peer, &self.public, &peer)
+    }
+}
+
+/// Symmetric state for one channel; both sides hold the same key
+pub struct ChannelSession {
+    cipher: XChaCha20Poly1305,
+}
+
+impl ChannelSession {
+    fn derive(
+        own_secret: &StaticSecret,
+        peer_public: &PublicKey,
+        ephemeral_public: &PublicKey,
+        static_public: &PublicKey,
+    ) -> Result<Self, KeystoreError> {
+        let shared = own_secret.diffie_hellman(peer_public);
+        // Low-order peer keys yield an all-zero secret an attacker could predict
+        if !shared.was_contributory() {
+            return Err(KeystoreError::InvalidInput(
+                "Peer public key is not a valid X25519 key".to_string(),
+            ));
+        }
+
+        let info = [
+            SESSION_KEY_INFO,
+            ephemeral_public.as_bytes(),
+            static_public.as_bytes(),
+        ]
+        .concat();
+        let mut key = Zeroizing::new([0u8; 32]);
+        Hkdf::<Sha256>::new(None, shared.as_bytes())
+            .expand(&info, key.as_mut())
+            .map_err(|e| KeystoreError::Platform(format!("Failed to derive session key: {}", e)))?;
+
+        Ok(Self::from_key(&key))
+    }
+
+    /// A session keyed by an already agreed 32-byte key
+    pub(crate) fn from_key(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(key.as_slice().into()),
+        }
+    }
+
+    /// Encrypts `plaintext` as `nonce || ciphertext || tag`
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, KeystoreError> {
+        let nonce = random_bytes(NONCE_SIZE)?;
+        let ciphertext = self
+            .cipher
+            .encrypt(XNonce::from_slice(&nonce), plaintext)
+            .map_err(|_| KeystoreError::Platform("Encryption failed".to_string()))?;
+        Ok([nonce, ciphertext].concat())
+    }
+
+    pub fn open(&self, message: &[u8]) -> Result<Vec<u8>, KeystoreError> {
+        if message.len() < NONCE_SIZE {
+            return Err(KeystoreError::Serialization(
+                "Channel message is too short".to_string(),
+            ));
+        }
+        let (nonce, ciphertext) = message.split_at(NONCE_SIZE);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                KeystoreError::Serialization("Channel message failed authentication".to_string())
+            })
+    }
+}
+
+/// The responder's static keys, held in the keystore
+pub struct ChannelKeys<'a> {
+    backend: &'a dyn KeystoreOperations,
+}
+
+impl<'a> ChannelKeys<'a> {
+    pub fn new(backend: &'a dyn KeystoreOperations) -> Self {
+        Self { backend }
+    }
+
+    fn load_or_create(&self, name: &str) -> Result<StaticSecret, KeystoreError> {
+        match self.backend.get_password(CHANNEL_SERVICE, name) {
+            Ok(encoded) => {
+                let encoded = Zeroizing::new(encoded);
+                let bytes = Zeroizing::new(STANDARD.decode(encoded.as_bytes()).map_err(|e| {
+                    KeystoreError::Serialization(format!("Invalid channel key: {}", e))
+                })?);
+                Ok(EphemeralKeypair::from_secret_bytes(&bytes)?.secret)
+            }
+            Err(KeystoreError::KeyNotFound(_)) => {
+                let secret = random_secret()?;
+                self.backend.set_password(&KeystoreEntry {
+                    service: CHANNEL_SERVICE.to_string(),
+                    account: name.to_string(),
+                    value: STANDARD.encode(secret.as_bytes()),
+                    ..Default::default()
+                })?;
+                Ok(secret)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Public half of the static key `name`, creating the key on first use
+    pub fn public_key(&self, name: &str) -> Result<[u8; PUBLIC_KEY_SIZE], KeystoreError> {
+        Ok(PublicKey::from(&self.load_or_create(name)?).to_bytes())
+    }
+
+    /// Responder side: combines the static key `name` with the initiator's ephemeral public key
+    pub fn accept(
+        &self,
+        name: &str,
+        peer_ephemeral_public: &[u8],
+    ) -> Result<ChannelSession, KeystoreError> {
+        let secret = self.load_or_create(name)?;
+        let peer = public_key(peer_ephemeral_public)?;
+        ChannelSession::derive(&secret, &peer, &peer, &PublicKey::from(&secret))
+    }
+
+    /// Replaces the static key; overlays must be given the new public key
+    pub fn rotate(&self, name: &str) -> Result<[u8; PUBLIC_KEY_SIZE], KeystoreError> {
+        match self.backend.delete_password(CHANNEL_SERVICE, name) {
+            Ok(()) | Err(KeystoreError::KeyNotFound(_)) => self.public_key(name),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    #[test]
+    fn test_handshake_and_round_trip() {
+        let backend = MemoryKeystore::default();
+        let daemon = ChannelKeys::new(&backend);
+        let daemon_public = daemon.public_key("overlay").unwrap();
+
+        let overlay_keys = EphemeralKeypair::generate().unwrap();
+        let overlay = overlay_keys.connect(&daemon_public).unwrap();
+        let server = daemon
+            .accept("overlay", &overlay_keys.public_key())
+            .unwrap();
+
+        let sealed = server.seal(b"{\"alertsKey\":\"s3cret\"}").unwrap();
+        assert!(!sealed.windows(6).any(|w| w == b"s3cret"));
+        assert_eq!(
+            overlay.open(&sealed).unwrap(),
+            b"{\"alertsKey\":\"s3cret\"}"
+        );
+        assert_eq!(server.open(&overlay.seal(b"ack").unwrap()).unwrap(), b"ack");
+    }
+
+    #[test]
+    fn test_rejects_tampering_and_wrong_keys() {
+        let backend = MemoryKeystore::default();
+        let daemon = ChannelKeys::new(&backend);
+        let overlay_keys = EphemeralKeypair::generate().unwrap();
+        let overlay = overlay_keys
+            .connect(&daemon.public_key("overlay").unwrap())
+            .unwrap();
+
+        let mut sealed = overlay.seal(b"payload").unwrap();
+        let server = daemon
+            .accept("overlay", &overlay_keys.public_key())
+            .unwrap();
+        sealed[NONCE_SIZE] ^= 1;
+        assert!(server.open(&sealed).is_err());
+
+        // A session for a different static key cannot read the traffic
+        let other = daemon.accept("other", &overlay_keys.public_key()).unwrap();
+        assert!(other.open(&overlay.seal(b"payload").unwrap()).is_err());
+
+        assert!(matches!(
+            daemon.accept("overlay", &[0u8; PUBLIC_KEY_SIZE]),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_static_key_is_persistent_until_rotated() {
+        let backend = MemoryKeystore::default();
+        let daemon = ChannelKeys::new(&backend);
+
+        let first = daemon.public_key("overlay").unwrap();
+        assert_eq!(daemon.public_key("overlay").unwrap(), first);
+        assert_ne!(daemon.rotate("overlay").unwrap(), first);
+    }
+}
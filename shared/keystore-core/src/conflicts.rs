@@ -0,0 +1,177 @@
+//! Conflicts between writers sharing one keystore file.
+//!
+//! People sync the encrypted-file keystore between machines with Dropbox and the like. When the
+//! file changed on disk since it was last read, the backend merges instead of overwriting: changes
+//! to different entries, or the same change on both sides, merge silently, and an entry deleted on
+//! one side but changed on the other keeps the change. Only an entry changed differently on both
+//! sides is a real conflict. The local write wins, and the version it displaced is kept under
+//! [`CONFLICTS_SERVICE`] until [`resolve_conflict`] settles it, so no value is lost either way.
+
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+use crate::KeystoreEntry;
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Service holding displaced versions, one entry per conflict
+pub const CONFLICTS_SERVICE: &str = "streaming-enhancement:conflicts";
+
+/// A conflict awaiting resolution; the displaced value is read with [`displaced_value`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncConflict {
+    pub id: String,
+    pub service: String,
+    pub account: String,
+    /// Revision of the displaced version, as counted by the writer that saved it
+    pub revision: u64,
+    /// Milliseconds since the Unix epoch
+    pub detected_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep the value the entry has now and drop the displaced one
+    KeepCurrent,
+    /// Write the displaced value back over the current one
+    UseDisplaced,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConflictRecord {
+    service: String,
+    account: String,
+    value: String,
+    revision: u64,
+    detected_at: u64,
+}
+
+/// The stored form of a conflict over `service`/`account`, for a backend to save under
+/// [`CONFLICTS_SERVICE`] and account `id`
+#[cfg(any(feature = "backend-file", test))]
+pub(crate) fn conflict_entry(
+    id: &str,
+    service: &str,
+    account: &str,
+    value: &str,
+    revision: u64,
+    detected_at: u64,
+) -> Result<KeystoreEntry, KeystoreError> {
+    let record = ConflictRecord {
+        service: service.to_string(),
+        account: account.to_string(),
+        value: value.to_string(),
+        revision,
+        detected_at,
+    };
+    let value =
+        serde_json::to_string(&record).map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+    Ok(KeystoreEntry {
+        service: CONFLICTS_SERVICE.to_string(),
+        account: id.to_string(),
+        value,
+        ..Default::default()
+    })
+}
+
+fn read_record(
+    backend: &dyn KeystoreOperations,
+    id: &str,
+) -> Result<ConflictRecord, KeystoreError> {
+    let json = Zeroizing::new(backend.get_password(CONFLICTS_SERVICE, id)?);
+    serde_json::from_str(&json)
+        .map_err(|e| KeystoreError::Serialization(format!("Invalid conflict record {}: {}", id, e)))
+}
+
+/// Unresolved conflicts, oldest first
+pub fn list_conflicts(
+    backend: &dyn KeystoreOperations,
+) -> Result<Vec<SyncConflict>, KeystoreError> {
+    let mut conflicts = Vec::new();
+    for key in backend.list_keys()? {
+        if key.service != CONFLICTS_SERVICE {
+            continue;
+        }
+        let record = read_record(backend, &key.account)?;
+        drop(Zeroizing::new(record.value));
+        conflicts.push(SyncConflict {
+            id: key.account,
+            service: record.service,
+            account: record.account,
+            revision: record.revision,
+            detected_at: record.detected_at,
+        });
+    }
+    conflicts.sort_by_key(|conflict| conflict.detected_at);
+    Ok(conflicts)
+}
+
+/// The value a conflict displaced, to show next to the current one
+pub fn displaced_value(
+    backend: &dyn KeystoreOperations,
+    id: &str,
+) -> Result<Zeroizing<String>, KeystoreError> {
+    Ok(Zeroizing::new(read_record(backend, id)?.value))
+}
+
+/// Settles a conflict and removes its record
+pub fn resolve_conflict(
+    backend: &dyn KeystoreOperations,
+    id: &str,
+    resolution: Resolution,
+) -> Result<(), KeystoreError> {
+    let record = read_record(backend, id)?;
+    if resolution == Resolution::UseDisplaced {
+        backend.set_password(&KeystoreEntry {
+            service: record.service,
+            account: record.account,
+            value: record.value,
+            ..Default::default()
+        })?;
+    } else {
+        drop(Zeroizing::new(record.value));
+    }
+    backend.delete_password(CONFLICTS_SERVICE, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    #[test]
+    fn test_resolving_conflicts() {
+        let backend = MemoryKeystore::default();
+        backend
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "bot".to_string(),
+                value: "current".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        for (id, value, at) in [("a", "displaced-a", 2), ("b", "displaced-b", 1)] {
+            let entry = conflict_entry(id, "twitch", "bot", value, 3, at).unwrap();
+            backend.set_password(&entry).unwrap();
+        }
+
+        let conflicts = list_conflicts(&backend).unwrap();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].id, "b");
+        assert_eq!(conflicts[0].revision, 3);
+        assert_eq!(*displaced_value(&backend, "a").unwrap(), "displaced-a");
+
+        resolve_conflict(&backend, "b", Resolution::KeepCurrent).unwrap();
+        assert_eq!(backend.get_password("twitch", "bot").unwrap(), "current");
+        resolve_conflict(&backend, "a", Resolution::UseDisplaced).unwrap();
+        assert_eq!(
+            backend.get_password("twitch", "bot").unwrap(),
+            "displaced-a"
+        );
+        assert!(list_conflicts(&backend).unwrap().is_empty());
+        assert!(matches!(
+            resolve_conflict(&backend, "a", Resolution::KeepCurrent),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+    }
+}
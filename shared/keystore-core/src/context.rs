@@ -0,0 +1,398 @@
+//! Detecting entries saved from another user context.
+//!
+//! A token saved from an elevated process (Run as administrator, `sudo`) or from a different
+//! account lands in that context's keystore and is invisible to the normal-user daemon, which
+//! then reports a bare `KeyNotFound`. [`ContextKeystore`] tags every entry it writes with the
+//! writing context in a machine-wide index, one file per context, and turns a missing entry that
+//! another context saved into [`KeystoreError::ContextMismatch`] naming both contexts. The index
+//! holds truncated SHA-256 digests of the keys, never names or values; maintaining it is best
+//! effort and never fails an operation.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The account and privilege level a process runs with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserContext {
+    pub user: String,
+    pub elevated: bool,
+}
+
+impl UserContext {
+    pub fn detect() -> Self {
+        let user = ["USER", "USERNAME", "LOGNAME"]
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|user| !user.is_empty()))
+            .unwrap_or_else(|| "unknown".to_string());
+        Self {
+            user,
+            elevated: is_elevated(),
+        }
+    }
+
+    /// File name of this context's index, e.g. `alice-elevated.json`
+    fn tag(&self) -> String {
+        let user: String = self
+            .user
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!(
+            "{}-{}",
+            user,
+            if self.elevated {
+                "elevated"
+            } else {
+                "standard"
+            }
+        )
+    }
+
+    pub fn describe(&self) -> String {
+        if self.elevated {
+            format!("{} (elevated)", self.user)
+        } else {
+            self.user.clone()
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_elevated() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(all(windows, feature = "backend-windows"))]
+fn is_elevated() -> bool {
+    crate::platform::windows::is_elevated()
+}
+
+#[cfg(not(any(unix, all(windows, feature = "backend-windows"))))]
+fn is_elevated() -> bool {
+    false
+}
+
+/// Machine-wide directory every context can write its index into
+pub fn default_index_dir() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        let data = std::env::var("ProgramData").unwrap_or_else(|_| r"C:\ProgramData".to_string());
+        PathBuf::from(data)
+            .join("streaming-enhancement")
+            .join("contexts")
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from("/Users/Shared/streaming-enhancement/contexts")
+    } else {
+        PathBuf::from("/var/tmp/streaming-enhancement-contexts")
+    }
+}
+
+fn key_digest(service: &str, account: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(service.as_bytes())
+        .chain_update([0])
+        .chain_update(account.as_bytes())
+        .finalize();
+    hex::encode(&digest[..16])
+}
+
+fn read_index(path: &Path) -> BTreeSet<String> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+pub struct ContextKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    context: UserContext,
+    index_dir: PathBuf,
+    /// Serializes rewrites of this context's index file
+    own_index: Mutex<()>,
+}
+
+impl ContextKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self::with_context(inner, UserContext::detect(), default_index_dir())
+    }
+
+    pub fn with_context(
+        inner: Box<dyn KeystoreOperations>,
+        context: UserContext,
+        index_dir: PathBuf,
+    ) -> Self {
+        Self {
+            inner,
+            context,
+            index_dir,
+            own_index: Mutex::new(()),
+        }
+    }
+
+    pub fn context(&self) -> &UserContext {
+        &self.context
+    }
+
+    fn own_index_path(&self) -> PathBuf {
+        self.index_dir.join(format!("{}.json", self.context.tag()))
+    }
+
+    /// Applies `f` to this context's set of digests and writes it back, ignoring failures
+    fn update_index(&self, f: impl FnOnce(&mut BTreeSet<String>)) {
+        let Ok(_guard) = self.own_index.lock() else {
+            return;
+        };
+        let path = self.own_index_path();
+        let mut digests = read_index(&path);
+        let before = digests.clone();
+        f(&mut digests);
+        if digests == before {
+            return;
+        }
+        if fs::create_dir_all(&self.index_dir).is_ok() {
+            // Every context, elevated or not, has to be able to add its own file
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let _ = fs::set_permissions(&self.index_dir, fs::Permissions::from_mode(0o1777));
+            }
+            if let Ok(json) = serde_json::to_string(&digests) {
+                let _ = fs::write(&path, json);
+            }
+        }
+    }
+
+    /// Another context that saved `service`/`account`, read from its index file name
+    fn saved_by_other(&self, service: &str, account: &str) -> Option<String> {
+        let digest = key_digest(service, account);
+        let own = self.own_index_path();
+        let mut others: Vec<PathBuf> = fs::read_dir(&self.index_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| *path != own && path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        others.sort();
+        others
+            .into_iter()
+            .find(|path| read_index(path).contains(&digest))
+            .and_then(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+    }
+
+    /// `KeyNotFound` becomes `ContextMismatch` when another context saved the key
+    fn explain(&self, service: &str, account: &str, err: KeystoreError) -> KeystoreError {
+        if !matches!(err, KeystoreError::KeyNotFound(_)) {
+            return err;
+        }
+        match self.saved_by_other(service, account) {
+            Some(other) => {
+                let (user, level) = other.rsplit_once('-').unwrap_or((&other, "standard"));
+                let saved_by = if level == "elevated" {
+                    format!("{} (elevated)", user)
+                } else {
+                    user.to_string()
+                };
+                KeystoreError::ContextMismatch(format!(
+                    "{}:{} was saved by {} and is not visible to {}. Run the app and the daemon \
+                     as the same user, either both elevated or neither, or save it again from \
+                     this context",
+                    service,
+                    account,
+                    saved_by,
+                    self.context.describe()
+                ))
+            }
+            None => err,
+        }
+    }
+}
+
+impl KeystoreOperations for ContextKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.inner.set_password(entry)?;
+        let digest = key_digest(&entry.service, &entry.account);
+        self.update_index(|digests| {
+            digests.insert(digest);
+        });
+        Ok(())
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.inner
+            .get_password(service, account)
+            .map_err(|e| self.explain(service, account, e))
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let result = self.inner.delete_password(service, account);
+        if matches!(result, Ok(()) | Err(KeystoreError::KeyNotFound(_))) {
+            let digest = key_digest(service, account);
+            self.update_index(|digests| {
+                digests.remove(&digest);
+            });
+        }
+        result.map_err(|e| self.explain(service, account, e))
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner
+            .backend_info()
+            .with_detail("user", &self.context.user)
+            .with_detail("elevated", self.context.elevated)
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.list_credentials(service)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        self.inner.get_many(keys)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.inner.set_many(entries)?;
+        self.update_index(|digests| {
+            for entry in entries {
+                digests.insert(key_digest(&entry.service, &entry.account));
+            }
+        });
+        Ok(())
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let removed = self.inner.delete_many(keys)?;
+        self.update_index(|digests| {
+            for key in &removed {
+                digests.remove(&key_digest(&key.service, &key.account));
+            }
+        });
+        Ok(removed)
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let removed = self.inner.delete_service(service)?;
+        self.update_index(|digests| {
+            for account in &removed {
+                digests.remove(&key_digest(service, account));
+            }
+        });
+        Ok(removed)
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let removed = self.inner.wipe_all()?;
+        self.update_index(|digests| {
+            for key in &removed {
+                digests.remove(&key_digest(&key.service, &key.account));
+            }
+        });
+        Ok(removed)
+    }
+
+    /// Both keys existed before and still do, so the index is unchanged
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.inner.swap(a, b)
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.inner
+            .get_metadata(service, account)
+            .map_err(|e| self.explain(service, account, e))
+    }
+
+    /// A missing entry is just `false` here, even one another context saved
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        let value = self
+            .inner
+            .get_or_create(service, account, length)
+            .map_err(|e| self.explain(service, account, e))?;
+        let digest = key_digest(service, account);
+        self.update_index(|digests| {
+            digests.insert(digest);
+        });
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use tempfile::TempDir;
+
+    fn keystore(dir: &TempDir, elevated: bool) -> ContextKeystore {
+        ContextKeystore::with_context(
+            Box::new(MemoryKeystore::default()),
+            UserContext {
+                user: "streamer".to_string(),
+                elevated,
+            },
+            dir.path().to_path_buf(),
+        )
+    }
+
+    #[test]
+    fn test_entry_saved_elevated_reports_a_context_mismatch() {
+        let dir = TempDir::new().unwrap();
+        let admin = keystore(&dir, true);
+        let daemon = keystore(&dir, false);
+        admin
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "bot".to_string(),
+                value: "token".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+
+        match daemon.get_password("twitch", "bot") {
+            Err(KeystoreError::ContextMismatch(message)) => {
+                assert!(message.contains("streamer (elevated)"));
+                assert!(!message.contains("token"));
+            }
+            other => panic!("Expected ContextMismatch, got {:?}", other),
+        }
+        assert!(matches!(
+            daemon.get_password("twitch", "other"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+
+        admin.delete_password("twitch", "bot").unwrap();
+        assert!(matches!(
+            daemon.get_password("twitch", "bot"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,207 @@
+//! AES-256-GCM for the encrypted-file backend, from a swappable provider.
+//!
+//! By default the cipher, keys and nonces come from the RustCrypto `aes-gcm` crate. With the
+//! `fips` feature they come from OpenSSL 3 with only its FIPS provider loaded, so every operation
+//! runs inside the validated module; if the provider is not installed and configured, the backend
+//! fails to open instead of quietly using unvalidated code. [`crate::version::version_info`]
+//! reports which provider is compiled in.
+//!
+//! Argon2id and XChaCha20-Poly1305, used by the lock passphrase, backups, file encryption and the
+//! overlay channel, are not FIPS-approved algorithms and have no FIPS implementation to swap in;
+//! they stay on RustCrypto in either build.
+
+use crate::error::KeystoreError;
+
+use zeroize::Zeroizing;
+
+pub const KEY_SIZE: usize = 32;
+pub const NONCE_SIZE: usize = 12;
+
+/// A 256-bit AES-GCM key, wiped on drop
+#[derive(Clone)]
+pub struct AesGcmKey(Zeroizing<[u8; KEY_SIZE]>);
+
+impl AesGcmKey {
+    pub fn generate() -> Result<Self, KeystoreError> {
+        let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+        provider::fill_random(key.as_mut())?;
+        Ok(Self(key))
+    }
+
+    /// `None` unless `bytes` is exactly [`KEY_SIZE`] long
+    pub fn from_slice(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != KEY_SIZE {
+            return None;
+        }
+        let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+        key.copy_from_slice(bytes);
+        Some(Self(key))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    /// Encrypts under a fresh random nonce, returning the nonce and `ciphertext || tag`
+    pub fn seal(&self, plaintext: &[u8]) -> Result<([u8; NONCE_SIZE], Vec<u8>), KeystoreError> {
+        let mut nonce = [0u8; NONCE_SIZE];
+        provider::fill_random(&mut nonce)?;
+        let ciphertext = provider::seal(&self.0, &nonce, plaintext)?;
+        Ok((nonce, ciphertext))
+    }
+
+    /// `None` if the ciphertext was not sealed under this key or was modified
+    pub fn open(&self, nonce: &[u8; NONCE_SIZE], ciphertext: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+        provider::open(&self.0, nonce, ciphertext).map(Zeroizing::new)
+    }
+}
+
+/// Name of the compiled-in provider
+pub fn provider_name() -> &'static str {
+    provider::NAME
+}
+
+/// Whether AES-GCM runs inside a FIPS-validated module; `false` if the `fips` build could not
+/// load the FIPS provider
+pub fn fips_active() -> bool {
+    provider::fips_active()
+}
+
+#[cfg(not(feature = "fips"))]
+mod provider {
+    use super::{KEY_SIZE, NONCE_SIZE};
+    use crate::error::KeystoreError;
+
+    use aes_gcm::aead::rand_core::RngCore;
+    use aes_gcm::aead::{Aead, KeyInit, OsRng};
+    use aes_gcm::{Aes256Gcm, Key, Nonce};
+
+    pub const NAME: &str = "rustcrypto";
+
+    pub fn fips_active() -> bool {
+        false
+    }
+
+    pub fn fill_random(buf: &mut [u8]) -> Result<(), KeystoreError> {
+        OsRng
+            .try_fill_bytes(buf)
+            .map_err(|e| KeystoreError::Platform(format!("Failed to generate random bytes: {}", e)))
+    }
+
+    pub fn seal(
+        key: &[u8; KEY_SIZE],
+        nonce: &[u8; NONCE_SIZE],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, KeystoreError> {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+            .encrypt(Nonce::from_slice(nonce), plaintext)
+            .map_err(|e| KeystoreError::Platform(format!("Encryption failed: {}", e)))
+    }
+
+    pub fn open(
+        key: &[u8; KEY_SIZE],
+        nonce: &[u8; NONCE_SIZE],
+        ciphertext: &[u8],
+    ) -> Option<Vec<u8>> {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .ok()
+    }
+}
+
+#[cfg(feature = "fips")]
+mod provider {
+    use super::{KEY_SIZE, NONCE_SIZE};
+    use crate::error::KeystoreError;
+
+    use openssl::provider::Provider;
+    use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+    use std::sync::OnceLock;
+
+    pub const NAME: &str = "openssl-fips";
+
+    const TAG_SIZE: usize = 16;
+
+    /// Loading the FIPS provider explicitly keeps OpenSSL from falling back to its default one
+    fn fips_provider() -> Result<(), KeystoreError> {
+        static PROVIDER: OnceLock<Result<Provider, String>> = OnceLock::new();
+        match PROVIDER.get_or_init(|| Provider::load(None, "fips").map_err(|e| e.to_string())) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(KeystoreError::Platform(format!(
+                "OpenSSL FIPS provider is not available: {}",
+                e
+            ))),
+        }
+    }
+
+    pub fn fips_active() -> bool {
+        fips_provider().is_ok()
+    }
+
+    fn failed(action: &str, e: openssl::error::ErrorStack) -> KeystoreError {
+        KeystoreError::Platform(format!("{} failed: {}", action, e))
+    }
+
+    pub fn fill_random(buf: &mut [u8]) -> Result<(), KeystoreError> {
+        fips_provider()?;
+        openssl::rand::rand_bytes(buf).map_err(|e| failed("Random generation", e))
+    }
+
+    pub fn seal(
+        key: &[u8; KEY_SIZE],
+        nonce: &[u8; NONCE_SIZE],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, KeystoreError> {
+        fips_provider()?;
+        let mut tag = [0u8; TAG_SIZE];
+        let mut ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            key,
+            Some(nonce),
+            &[],
+            plaintext,
+            &mut tag,
+        )
+        .map_err(|e| failed("Encryption", e))?;
+        ciphertext.extend_from_slice(&tag);
+        Ok(ciphertext)
+    }
+
+    pub fn open(
+        key: &[u8; KEY_SIZE],
+        nonce: &[u8; NONCE_SIZE],
+        ciphertext: &[u8],
+    ) -> Option<Vec<u8>> {
+        fips_provider().ok()?;
+        let split = ciphertext.len().checked_sub(TAG_SIZE)?;
+        let (ciphertext, tag) = ciphertext.split_at(split);
+        decrypt_aead(
+            Cipher::aes_256_gcm(),
+            key,
+            Some(nonce),
+            &[],
+            ciphertext,
+            tag,
+        )
+        .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open_round_trip() {
+        let key = AesGcmKey::generate().unwrap();
+        let (nonce, ciphertext) = key.seal(b"token").unwrap();
+        assert_eq!(key.open(&nonce, &ciphertext).unwrap().as_slice(), b"token");
+
+        let mut tampered = ciphertext.clone();
+        tampered[0] ^= 1;
+        assert!(key.open(&nonce, &tampered).is_none());
+        let other = AesGcmKey::from_slice(&[7u8; KEY_SIZE]).unwrap();
+        assert!(other.open(&nonce, &ciphertext).is_none());
+        assert!(AesGcmKey::from_slice(&[7u8; 16]).is_none());
+    }
+}
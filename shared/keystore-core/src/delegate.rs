@@ -0,0 +1,264 @@
+//! Short-lived delegate tokens for overlay and browser-source processes.
+//!
+//! The daemon mints a token naming a scope of `service/account` or `service/*` patterns and an
+//! expiry; the overlay presents it to the broker or REST interface, which can then read only the
+//! secrets inside that scope. Tokens are `<claims>.<signature>`, both base64url: the claims are
+//! JSON and the signature is HMAC-SHA256 under a key derived from the `delegation` master key, so
+//! tokens survive a daemon restart but the long-lived platform tokens never leave the keystore.
+
+use crate::error::KeystoreError;
+use crate::generate::{random_token, TokenEncoding};
+use crate::keys::{MasterKeys, KEYS_SERVICE};
+use crate::platform::KeystoreOperations;
+use crate::refs::{RefPolicy, SecretRef};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Master key the signing key is derived from
+pub const DELEGATION_KEY: &str = "delegation";
+
+/// Longest lifetime a delegate token may have
+pub const MAX_DELEGATE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const SIGNING_KEY_INFO: &[u8] = b"streaming-enhancement:delegate-token:v1";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DelegateClaims {
+    /// `service/account` or `service/*` patterns the bearer may read
+    pub scope: Vec<String>,
+    /// Milliseconds since the Unix epoch
+    pub issued_at: u64,
+    pub expires_at: u64,
+    /// Random token id, for logging and revocation lists
+    pub id: String,
+}
+
+impl DelegateClaims {
+    pub fn permits(&self, service: &str, account: &str) -> bool {
+        RefPolicy::allow(self.scope.iter().cloned()).permits(&SecretRef {
+            service: service.to_string(),
+            account: account.to_string(),
+        })
+    }
+}
+
+pub struct DelegateTokens<'a> {
+    backend: &'a dyn KeystoreOperations,
+    keys: MasterKeys<'a>,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn invalid_token(reason: &str) -> KeystoreError {
+    KeystoreError::AccessDenied(format!("Invalid delegate token: {}", reason))
+}
+
+impl<'a> DelegateTokens<'a> {
+    pub fn new(backend: &'a dyn KeystoreOperations) -> Self {
+        Self {
+            backend,
+            keys: MasterKeys::new(backend),
+        }
+    }
+
+    fn mac(&self) -> Result<Hmac<Sha256>, KeystoreError> {
+        self.keys.ensure(DELEGATION_KEY)?;
+        let key = self.keys.derive(DELEGATION_KEY, SIGNING_KEY_INFO, 32)?;
+        Hmac::<Sha256>::new_from_slice(&key)
+            .map_err(|e| KeystoreError::Platform(format!("Invalid signing key: {}", e)))
+    }
+
+    /// Signs a token granting read access to `scope` for `ttl`
+    pub fn mint(&self, scope: &[String], ttl: Duration) -> Result<String, KeystoreError> {
+        if scope.is_empty() {
+            return Err(KeystoreError::InvalidInput(
+                "Delegate token scope must not be empty".to_string(),
+            ));
+        }
+        if let Some(pattern) = scope.iter().find(|p| !p.contains('/')) {
+            return Err(KeystoreError::InvalidInput(format!(
+                "Scope entry '{}' must be service/account or service/*",
+                pattern
+            )));
+        }
+        if scope
+            .iter()
+            .any(|p| p.split_once('/').map(|(service, _)| service) == Some(KEYS_SERVICE))
+        {
+            return Err(KeystoreError::AccessDenied(
+                "Master keys cannot be delegated".to_string(),
+            ));
+        }
+        if ttl.is_zero() || ttl > MAX_DELEGATE_TTL {
+            return Err(KeystoreError::InvalidInput(format!(
+                "Delegate token lifetime must be between 1 ms and {} s",
+                MAX_DELEGATE_TTL.as_secs()
+            )));
+        }
+
+        let issued_at = now_millis();
+        let claims = DelegateClaims {
+            scope: scope.to_vec(),
+            issued_at,
+            expires_at: issued_at + ttl.as_millis() as u64,
+            id: random_token(TokenEncoding::Base64Url, 16)?,
+        };
+        let payload = URL_SAFE_NO_PAD.encode(
+            serde_json::to_vec(&claims).map_err(|e| KeystoreError::Serialization(e.to_string()))?,
+        );
+
+        let mut mac = self.mac()?;
+        mac.update(payload.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        Ok(format!("{}.{}", payload, signature))
+    }
+
+    /// Checks the signature and expiry of `token` and returns its claims
+    pub fn verify(&self, token: &str) -> Result<DelegateClaims, KeystoreError> {
+        self.verify_at(token, now_millis())
+    }
+
+    fn verify_at(&self, token: &str, now: u64) -> Result<DelegateClaims, KeystoreError> {
+        let (payload, signature) = token
+            .split_once('.')
+            .ok_or_else(|| invalid_token("malformed"))?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| invalid_token("malformed signature"))?;
+
+        let mut mac = self.mac()?;
+        mac.update(payload.as_bytes());
+        // verify_slice compares in constant time
+        mac.verify_slice(&signature)
+            .map_err(|_| invalid_token("bad signature"))?;
+
+        let claims: DelegateClaims = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| invalid_token("malformed claims"))?;
+        if now >= claims.expires_at {
+            return Err(invalid_token("expired"));
+        }
+        Ok(claims)
+    }
+
+    /// Reads `service`/`account` on behalf of the bearer of `token`, if it is inside the scope
+    pub fn get_password(
+        &self,
+        token: &str,
+        service: &str,
+        account: &str,
+    ) -> Result<String, KeystoreError> {
+        let claims = self.verify(token)?;
+        if !claims.permits(service, account) {
+            return Err(KeystoreError::AccessDenied(format!(
+                "{}:{} is outside the delegate token's scope",
+                service, account
+            )));
+        }
+        self.backend.get_password(service, account)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use crate::KeystoreEntry;
+
+    fn backend() -> MemoryKeystore {
+        let backend = MemoryKeystore::default();
+        for (service, account) in [("overlay", "alerts-key"), ("twitch", "oauth")] {
+            backend
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    value: format!("{}-secret", account),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        backend
+    }
+
+    #[test]
+    fn test_scoped_read() {
+        let backend = backend();
+        let tokens = DelegateTokens::new(&backend);
+        let token = tokens
+            .mint(&["overlay/*".to_string()], Duration::from_secs(300))
+            .unwrap();
+
+        assert_eq!(
+            tokens
+                .get_password(&token, "overlay", "alerts-key")
+                .unwrap(),
+            "alerts-key-secret"
+        );
+        assert!(matches!(
+            tokens.get_password(&token, "twitch", "oauth"),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+    }
+
+    #[test]
+    fn test_expired_and_tampered_tokens_are_rejected() {
+        let backend = backend();
+        let tokens = DelegateTokens::new(&backend);
+        let token = tokens
+            .mint(&["overlay/alerts-key".to_string()], Duration::from_secs(60))
+            .unwrap();
+        let claims = tokens.verify(&token).unwrap();
+
+        assert!(matches!(
+            tokens.verify_at(&token, claims.expires_at),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+
+        // Widen the scope without re-signing
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged_claims = DelegateClaims {
+            scope: vec!["twitch/*".to_string()],
+            ..claims
+        };
+        let forged = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged_claims).unwrap()),
+            signature
+        );
+        assert!(matches!(
+            tokens.verify(&forged),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+    }
+
+    #[test]
+    fn test_mint_validates_input() {
+        let backend = backend();
+        let tokens = DelegateTokens::new(&backend);
+
+        assert!(tokens.mint(&[], Duration::from_secs(60)).is_err());
+        assert!(tokens
+            .mint(&["overlay".to_string()], Duration::from_secs(60))
+            .is_err());
+        assert!(tokens
+            .mint(&["overlay/*".to_string()], MAX_DELEGATE_TTL * 2)
+            .is_err());
+        assert!(matches!(
+            tokens.mint(&[format!("{}/*", KEYS_SERVICE)], Duration::from_secs(60)),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+    }
+}
@@ -0,0 +1,275 @@
+//! Finding credentials other applications stored, to copy them into ours.
+//!
+//! Streamers often have a token saved already by another bot on the same machine. [`discover`]
+//! searches Secret Service on Linux, or Credential Manager on Windows, for items written in a
+//! [`ForeignSchema`] under the services it names, and reports them without reading their values.
+//! [`import_foreign`] then copies one into our namespace. Both take a [`Consent`], which callers
+//! create only once the user agreed to have other applications' credentials searched. Other
+//! platforms return `PlatformNotSupported`.
+
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+use crate::provenance::{with_provenance, Origin, ProvenanceContext};
+use crate::search::glob_match;
+use crate::{KeystoreEntry, KeystoreKey};
+
+use zeroize::Zeroizing;
+
+/// Services of the streaming platforms, which other bots keep their tokens under
+pub const STREAMING_SERVICES: &[&str] = &[
+    "*twitch*",
+    "*youtube*",
+    "*kick*",
+    "*trovo*",
+    "*streamlabs*",
+    "*streamelements*",
+];
+
+/// How another application names its items
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignSchema {
+    /// Shown to the user and recorded as the provenance component, e.g. `keytar`
+    pub app: String,
+    /// Secret Service attributes holding the service and the account
+    pub service_attribute: String,
+    pub account_attribute: String,
+    /// Between the service and the account in Credential Manager target names, whose user name is
+    /// the account
+    pub target_separator: String,
+    /// Glob patterns of the services searched, matched regardless of case
+    pub services: Vec<String>,
+}
+
+impl ForeignSchema {
+    /// Items written through keytar, as Electron apps do: `service` and `account` attributes, and
+    /// `service/account` targets
+    pub fn keytar(app: &str, services: &[&str]) -> Self {
+        Self {
+            app: app.to_string(),
+            service_attribute: "service".to_string(),
+            account_attribute: "account".to_string(),
+            target_separator: "/".to_string(),
+            services: services.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Whether `service` is one this schema is searched under
+    pub fn covers(&self, service: &str) -> bool {
+        let service = service.to_lowercase();
+        self.services
+            .iter()
+            .any(|pattern| glob_match(&pattern.to_lowercase(), &service))
+    }
+
+    /// Service of a Credential Manager target written in this schema for `user`
+    pub fn target_service<'a>(&self, target: &'a str, user: &str) -> Option<&'a str> {
+        let service = target
+            .strip_suffix(user)?
+            .strip_suffix(self.target_separator.as_str())?;
+        (!service.is_empty()).then_some(service)
+    }
+}
+
+/// Schemas [`discover`] is given by the napi binding: keytar items under a streaming service
+pub fn known_schemas() -> Vec<ForeignSchema> {
+    vec![ForeignSchema::keytar("keytar", STREAMING_SERVICES)]
+}
+
+/// The user agreed to have other applications' credentials searched and copied
+#[derive(Debug, Clone, Copy)]
+pub struct Consent(());
+
+impl Consent {
+    /// Create only after asking the user, e.g. in a dialog naming the applications searched
+    pub fn given() -> Self {
+        Consent(())
+    }
+}
+
+/// A credential another application stored, without its value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignCredential {
+    /// [`ForeignSchema::app`] of the schema it matched
+    pub app: String,
+    pub service: String,
+    pub account: String,
+    /// Secret Service item path, or Credential Manager target name
+    pub location: String,
+}
+
+/// Credentials written in any of `schemas`, sorted; values are not read
+pub fn discover(
+    schemas: &[ForeignSchema],
+    _consent: Consent,
+) -> Result<Vec<ForeignCredential>, KeystoreError> {
+    let mut found = find(schemas)?;
+    found.sort_by(|a, b| (&a.app, &a.service, &a.account).cmp(&(&b.app, &b.service, &b.account)));
+    found.dedup();
+    Ok(found)
+}
+
+/// Copies `credential` into `keystore` as `target`, recorded with the `import` origin and the
+/// application as component. Leaves an existing entry alone unless `overwrite` is set; returns
+/// whether it wrote.
+pub fn import_foreign(
+    keystore: &dyn KeystoreOperations,
+    schemas: &[ForeignSchema],
+    credential: &ForeignCredential,
+    target: &KeystoreKey,
+    overwrite: bool,
+    _consent: Consent,
+) -> Result<bool, KeystoreError> {
+    let schema = schemas
+        .iter()
+        .find(|schema| schema.app == credential.app && schema.covers(&credential.service))
+        .ok_or_else(|| {
+            KeystoreError::InvalidInput(format!(
+                "No schema of {} covers {}",
+                credential.app, credential.service
+            ))
+        })?;
+    if !overwrite {
+        match keystore.get_password(&target.service, &target.account) {
+            Ok(_) => return Ok(false),
+            Err(KeystoreError::KeyNotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let value = read(schema, credential)?;
+    let context = ProvenanceContext {
+        origin: Origin::Import,
+        component: Some(credential.app.clone()),
+    };
+    with_provenance(context, || {
+        keystore.set_password(&KeystoreEntry {
+            service: target.service.clone(),
+            account: target.account.clone(),
+            value: value.to_string(),
+            ..Default::default()
+        })
+    })?;
+    Ok(true)
+}
+
+#[cfg(all(target_os = "linux", feature = "backend-secret-service"))]
+fn find(schemas: &[ForeignSchema]) -> Result<Vec<ForeignCredential>, KeystoreError> {
+    use std::collections::HashMap;
+
+    let items = crate::platform::linux::foreign_items(HashMap::new())?;
+    let mut found = Vec::new();
+    for schema in schemas {
+        for (path, attributes) in &items {
+            let (Some(service), Some(account)) = (
+                attributes.get(&schema.service_attribute),
+                attributes.get(&schema.account_attribute),
+            ) else {
+                continue;
+            };
+            if schema.covers(service) {
+                found.push(ForeignCredential {
+                    app: schema.app.clone(),
+                    service: service.clone(),
+                    account: account.clone(),
+                    location: path.clone(),
+                });
+            }
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(all(target_os = "linux", feature = "backend-secret-service"))]
+fn read(
+    schema: &ForeignSchema,
+    credential: &ForeignCredential,
+) -> Result<Zeroizing<String>, KeystoreError> {
+    crate::platform::linux::read_foreign(
+        &credential.location,
+        &[
+            (
+                schema.service_attribute.as_str(),
+                credential.service.as_str(),
+            ),
+            (
+                schema.account_attribute.as_str(),
+                credential.account.as_str(),
+            ),
+        ],
+    )
+}
+
+#[cfg(all(windows, feature = "backend-windows"))]
+fn find(schemas: &[ForeignSchema]) -> Result<Vec<ForeignCredential>, KeystoreError> {
+    let credentials = crate::platform::windows::generic_credentials(None)?;
+    let mut found = Vec::new();
+    for schema in schemas {
+        for (target, user) in &credentials {
+            match schema.target_service(target, user) {
+                Some(service) if schema.covers(service) => found.push(ForeignCredential {
+                    app: schema.app.clone(),
+                    service: service.to_string(),
+                    account: user.clone(),
+                    location: target.clone(),
+                }),
+                _ => {}
+            }
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(all(windows, feature = "backend-windows"))]
+fn read(
+    schema: &ForeignSchema,
+    credential: &ForeignCredential,
+) -> Result<Zeroizing<String>, KeystoreError> {
+    let target = format!(
+        "{}{}{}",
+        credential.service, schema.target_separator, credential.account
+    );
+    // The target is rebuilt rather than taken from the caller
+    if target != credential.location {
+        return Err(KeystoreError::InvalidInput(format!(
+            "{} does not match the credential found",
+            credential.location
+        )));
+    }
+    crate::platform::windows::read_foreign(&target)
+}
+
+#[cfg(not(any(
+    all(target_os = "linux", feature = "backend-secret-service"),
+    all(windows, feature = "backend-windows")
+)))]
+fn find(_schemas: &[ForeignSchema]) -> Result<Vec<ForeignCredential>, KeystoreError> {
+    Err(KeystoreError::PlatformNotSupported)
+}
+
+#[cfg(not(any(
+    all(target_os = "linux", feature = "backend-secret-service"),
+    all(windows, feature = "backend-windows")
+)))]
+fn read(
+    _schema: &ForeignSchema,
+    _credential: &ForeignCredential,
+) -> Result<Zeroizing<String>, KeystoreError> {
+    Err(KeystoreError::PlatformNotSupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keytar_schema_matches_streaming_services() {
+        let schema = ForeignSchema::keytar("keytar", STREAMING_SERVICES);
+        assert!(schema.covers("Twitch Chat Bot"));
+        assert!(schema.covers("streamelements-overlay"));
+        assert!(!schema.covers("github"));
+
+        assert_eq!(schema.target_service("twitch/bot", "bot"), Some("twitch"));
+        assert_eq!(schema.target_service("twitch/bot", "other"), None);
+        assert_eq!(schema.target_service("/bot", "bot"), None);
+        assert_eq!(schema.target_service("twitch:bot", "bot"), None);
+    }
+}
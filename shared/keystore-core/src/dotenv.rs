@@ -0,0 +1,247 @@
+//! Importing tokens from dotenv (`.env`) files.
+//!
+//! Bots built by hand often keep their tokens in a `.env` file. [`read_env_file`] parses one and
+//! maps its variables to entries with [`EnvMappingRule`]s, leaving the variables no rule matches
+//! out; the entries then go through [`crate::import::plan_import`] and
+//! [`crate::import::apply_import`] like any other import. [`shred_file`] overwrites and removes
+//! the source afterwards.
+//!
+//! The parser takes `KEY=value` lines with an optional `export ` prefix, `#` comments, single
+//! quotes (literal) and double quotes (with `\n`, `\t`, `\"` and `\\` escapes, possibly spanning
+//! lines). Unquoted values end at ` #`. Variable expansion is not supported.
+
+use crate::error::KeystoreError;
+use crate::import::ImportDiff;
+use crate::search::glob_match;
+use crate::KeystoreEntry;
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Maps variables whose name matches `variable`, a glob such as `TWITCH_*`, to an entry.
+///
+/// `service` and `account` may contain `{name}`, the variable's name, and `{match}`, the part a
+/// single `*` in `variable` stood for (the whole name otherwise), lowercased.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvMappingRule {
+    pub variable: String,
+    pub service: String,
+    pub account: String,
+}
+
+impl EnvMappingRule {
+    /// Parses `VARIABLE=service/account`, e.g. `TWITCH_*=twitch/{match}`
+    pub fn parse(rule: &str) -> Result<Self, KeystoreError> {
+        let invalid = || {
+            KeystoreError::InvalidInput(format!(
+                "Invalid mapping rule {:?}; expected VARIABLE=service/account",
+                rule
+            ))
+        };
+        let (variable, target) = rule.split_once('=').ok_or_else(invalid)?;
+        let (service, account) = target.split_once('/').ok_or_else(invalid)?;
+        if variable.trim().is_empty() || service.is_empty() || account.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            variable: variable.trim().to_string(),
+            service: service.to_string(),
+            account: account.to_string(),
+        })
+    }
+
+    fn apply(&self, name: &str) -> Option<(String, String)> {
+        if !glob_match(&self.variable, name) {
+            return None;
+        }
+        let matched = match self.variable.split_once('*') {
+            Some((prefix, suffix)) if !suffix.contains('*') => {
+                &name[prefix.len()..name.len() - suffix.len()]
+            }
+            _ => name,
+        }
+        .to_lowercase();
+        let fill = |template: &str| {
+            template
+                .replace("{name}", name)
+                .replace("{match}", &matched)
+        };
+        Some((fill(&self.service), fill(&self.account)))
+    }
+}
+
+/// A parsed file mapped to entries
+#[derive(Default)]
+pub struct EnvImport {
+    pub entries: Vec<KeystoreEntry>,
+    /// Variables no rule matched, in file order
+    pub unmapped: Vec<String>,
+}
+
+impl EnvImport {
+    /// Whether applying `diff` (with `overwrite` as given) leaves every variable of the file in the
+    /// keystore, so shredding the file loses nothing
+    pub fn is_fully_imported(&self, diff: &ImportDiff, overwrite: bool) -> bool {
+        self.unmapped.is_empty()
+            && diff.conflicts.is_empty()
+            && (overwrite || diff.changed.is_empty())
+    }
+}
+
+fn invalid_line(line: usize, reason: &str) -> KeystoreError {
+    KeystoreError::InvalidInput(format!("Invalid .env file at line {}: {}", line, reason))
+}
+
+/// Variables in file order; a later assignment replaces an earlier one
+pub fn parse_dotenv(text: &str) -> Result<Vec<(String, Zeroizing<String>)>, KeystoreError> {
+    let mut variables: Vec<(String, Zeroizing<String>)> = Vec::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let number = index + 1;
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, rest) = line
+            .split_once('=')
+            .ok_or_else(|| invalid_line(number, "expected NAME=value"))?;
+        let name = name.trim();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            return Err(invalid_line(number, "invalid variable name"));
+        }
+        let rest = rest.trim_start();
+
+        let value = if let Some(quoted) = rest.strip_prefix('\'') {
+            let end = quoted
+                .find('\'')
+                .ok_or_else(|| invalid_line(number, "unterminated single quote"))?;
+            Zeroizing::new(quoted[..end].to_string())
+        } else if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = Zeroizing::new(String::new());
+            let mut current = Zeroizing::new(quoted.to_string());
+            'lines: loop {
+                let mut chars = current.chars();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break 'lines,
+                        '\\' => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(other) => value.push(other),
+                            None => value.push('\\'),
+                        },
+                        c => value.push(c),
+                    }
+                }
+                // The quote continues on the next line
+                let (_, next) = lines
+                    .next()
+                    .ok_or_else(|| invalid_line(number, "unterminated double quote"))?;
+                value.push('\n');
+                current = Zeroizing::new(next.to_string());
+            }
+            value
+        } else {
+            let end = rest.find(" #").unwrap_or(rest.len());
+            Zeroizing::new(rest[..end].trim_end().to_string())
+        };
+
+        variables.retain(|(existing, _)| existing != name);
+        variables.push((name.to_string(), value));
+    }
+    Ok(variables)
+}
+
+/// Maps parsed variables to entries; the first matching rule wins
+pub fn map_env(variables: Vec<(String, Zeroizing<String>)>, rules: &[EnvMappingRule]) -> EnvImport {
+    let mut import = EnvImport::default();
+    for (name, value) in variables {
+        match rules.iter().find_map(|rule| rule.apply(&name)) {
+            Some((service, account)) => import.entries.push(KeystoreEntry {
+                service,
+                account,
+                value: value.to_string(),
+                ..Default::default()
+            }),
+            None => import.unmapped.push(name),
+        }
+    }
+    import
+}
+
+pub fn read_env_file(path: &Path, rules: &[EnvMappingRule]) -> Result<EnvImport, KeystoreError> {
+    let text = Zeroizing::new(fs::read_to_string(path)?);
+    Ok(map_env(parse_dotenv(&text)?, rules))
+}
+
+/// Overwrites `path` with zeros, flushes it to disk and deletes it. On SSDs and copy-on-write
+/// filesystems the old blocks may survive anyway, so treat this as best effort.
+pub fn shred_file(path: &Path) -> Result<(), KeystoreError> {
+    let len = fs::metadata(path)?.len();
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; 4096];
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_env_file_maps_to_entries() {
+        let text = concat!(
+            "# bot settings\n",
+            "export TWITCH_BOT_TOKEN=oauth:abc # comment\n",
+            "TWITCH_CLIENT_SECRET='s3cr#t'\n",
+            "DISCORD_TOKEN=\"line\\none\n",
+            "two\"\n",
+            "LOG_LEVEL=debug\n",
+        );
+        let rules = [
+            EnvMappingRule::parse("TWITCH_*=twitch/{match}").unwrap(),
+            EnvMappingRule::parse("DISCORD_TOKEN=discord/bot").unwrap(),
+        ];
+        let import = map_env(parse_dotenv(text).unwrap(), &rules);
+
+        let found: Vec<(&str, &str, &str)> = import
+            .entries
+            .iter()
+            .map(|e| (e.service.as_str(), e.account.as_str(), e.value.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("twitch", "bot_token", "oauth:abc"),
+                ("twitch", "client_secret", "s3cr#t"),
+                ("discord", "bot", "line\none\ntwo"),
+            ]
+        );
+        assert_eq!(import.unmapped, ["LOG_LEVEL"]);
+        assert!(parse_dotenv("NOT A LINE").is_err());
+        assert!(EnvMappingRule::parse("TWITCH_*").is_err());
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(".env");
+        fs::write(&path, text).unwrap();
+        assert_eq!(read_env_file(&path, &rules).unwrap().entries.len(), 3);
+        shred_file(&path).unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,441 @@
+//! Runtime-only secrets under the `ephemeral:` service prefix.
+//!
+//! Session cookies and derived signing keys are needed while the process runs and must never reach
+//! the backend. [`EphemeralKeystore`] keeps entries whose service starts with [`EPHEMERAL_PREFIX`]
+//! in memory, as zeroizing strings, and passes everything else through, so callers use the same
+//! calls for both. Entries expire after their TTL ([`EphemeralKeystore::set_default_ttl`] for new
+//! ones, [`EphemeralKeystore::set_ttl`] per entry) and are wiped on [`EphemeralKeystore::clear`],
+//! which the napi layer calls when the keystore locks. A guard from
+//! [`EphemeralKeystore::guarded_by`] can refuse ephemeral calls, as while the keystore is locked.
+
+use crate::error::KeystoreError;
+use crate::generate::{generate_secret, SecretOptions};
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::resolution::{record_source, ReadSource};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Services starting with this are held in memory only
+pub const EPHEMERAL_PREFIX: &str = "ephemeral:";
+
+pub fn is_ephemeral(service: &str) -> bool {
+    service.starts_with(EPHEMERAL_PREFIX)
+}
+
+struct Slot {
+    value: Zeroizing<String>,
+    expires: Option<Instant>,
+}
+
+impl Slot {
+    fn is_live(&self, now: Instant) -> bool {
+        self.expires.is_none_or(|expires| now < expires)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    entries: BTreeMap<KeystoreKey, Slot>,
+    default_ttl: Option<Duration>,
+}
+
+impl State {
+    /// Drops expired entries; their values are zeroized as they go
+    fn purge(&mut self) {
+        let now = Instant::now();
+        self.entries.retain(|_, slot| slot.is_live(now));
+    }
+}
+
+type Guard = Box<dyn Fn() -> Result<(), KeystoreError> + Send + Sync>;
+
+pub struct EphemeralKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    state: Mutex<State>,
+    guard: Option<Guard>,
+}
+
+impl EphemeralKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self {
+            inner,
+            state: Mutex::new(State::default()),
+            guard: None,
+        }
+    }
+
+    /// Runs `guard` before every call on an ephemeral entry, failing the call with its error
+    pub fn guarded_by(
+        mut self,
+        guard: impl Fn() -> Result<(), KeystoreError> + Send + Sync + 'static,
+    ) -> Self {
+        self.guard = Some(Box::new(guard));
+        self
+    }
+
+    fn check(&self) -> Result<(), KeystoreError> {
+        self.guard.as_ref().map_or(Ok(()), |guard| guard())
+    }
+
+    /// TTL of ephemeral entries written from now on; `None`, the default, keeps them until
+    /// deleted, cleared or the process exits
+    pub fn set_default_ttl(&self, ttl: Option<Duration>) {
+        self.state.lock().unwrap().default_ttl = ttl;
+    }
+
+    /// Makes an existing ephemeral entry expire `ttl` from now, or never with `None`
+    pub fn set_ttl(
+        &self,
+        service: &str,
+        account: &str,
+        ttl: Option<Duration>,
+    ) -> Result<(), KeystoreError> {
+        self.check()?;
+        let mut state = self.state.lock().unwrap();
+        state.purge();
+        let slot = state
+            .entries
+            .get_mut(&key(service, account))
+            .ok_or_else(|| KeystoreError::KeyNotFound(format!("{}:{}", service, account)))?;
+        slot.expires = ttl.map(|ttl| Instant::now() + ttl);
+        Ok(())
+    }
+
+    /// Wipes every ephemeral entry
+    pub fn clear(&self) {
+        self.state.lock().unwrap().entries.clear();
+    }
+
+    fn keys(&self) -> Vec<KeystoreKey> {
+        let mut state = self.state.lock().unwrap();
+        state.purge();
+        state.entries.keys().cloned().collect()
+    }
+}
+
+fn key(service: &str, account: &str) -> KeystoreKey {
+    KeystoreKey {
+        service: service.to_string(),
+        account: account.to_string(),
+    }
+}
+
+impl KeystoreOperations for EphemeralKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        if !is_ephemeral(&entry.service) {
+            return self.inner.set_password(entry);
+        }
+        self.check()?;
+        let mut state = self.state.lock().unwrap();
+        let expires = state.default_ttl.map(|ttl| Instant::now() + ttl);
+        state.entries.insert(
+            key(&entry.service, &entry.account),
+            Slot {
+                value: Zeroizing::new(entry.value.clone()),
+                expires,
+            },
+        );
+        Ok(())
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        if !is_ephemeral(service) {
+            return self.inner.get_password(service, account);
+        }
+        self.check()?;
+        let mut state = self.state.lock().unwrap();
+        state.purge();
+        let value = state
+            .entries
+            .get(&key(service, account))
+            .map(|slot| slot.value.to_string())
+            .ok_or_else(|| KeystoreError::KeyNotFound(format!("{}:{}", service, account)))?;
+        record_source(ReadSource::Ephemeral);
+        Ok(value)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        if !is_ephemeral(service) {
+            return self.inner.delete_password(service, account);
+        }
+        self.check()?;
+        let mut state = self.state.lock().unwrap();
+        state.purge();
+        state
+            .entries
+            .remove(&key(service, account))
+            .map(|_| ())
+            .ok_or_else(|| KeystoreError::KeyNotFound(format!("{}:{}", service, account)))
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        let info = self.inner.backend_info();
+        match self.keys().len() {
+            0 => info,
+            entries => info.with_detail("ephemeral_entries", entries),
+        }
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut keys = self.inner.list_keys()?;
+        keys.extend(self.keys());
+        Ok(keys)
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        if !is_ephemeral(service) {
+            return self.inner.list_credentials(service);
+        }
+        let mut accounts: Vec<String> = self
+            .keys()
+            .into_iter()
+            .filter(|key| key.service == service)
+            .map(|key| key.account)
+            .collect();
+        accounts.sort();
+        Ok(accounts)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        if !keys.iter().any(|key| is_ephemeral(&key.service)) {
+            return self.inner.get_many(keys);
+        }
+        // Batch the stored ones and fill the ephemeral ones in around them
+        let stored: Vec<KeystoreKey> = keys
+            .iter()
+            .filter(|key| !is_ephemeral(&key.service))
+            .cloned()
+            .collect();
+        self.check()?;
+        let mut stored_values = self.inner.get_many(&stored)?.into_iter();
+        keys.iter()
+            .map(|key| {
+                if !is_ephemeral(&key.service) {
+                    return Ok(stored_values.next().flatten());
+                }
+                match self.get_password(&key.service, &key.account) {
+                    Ok(value) => Ok(Some(value)),
+                    Err(KeystoreError::KeyNotFound(_)) => Ok(None),
+                    Err(e) => Err(e),
+                }
+            })
+            .collect()
+    }
+
+    /// Stored entries go to the backend as one batch, then the ephemeral ones are kept
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        if !entries.iter().any(|entry| is_ephemeral(&entry.service)) {
+            return self.inner.set_many(entries);
+        }
+        let (ephemeral, stored): (Vec<&KeystoreEntry>, Vec<&KeystoreEntry>) = entries
+            .iter()
+            .partition(|entry| is_ephemeral(&entry.service));
+        self.check()?;
+        let stored: Vec<KeystoreEntry> = stored.into_iter().cloned().collect();
+        self.inner.set_many(&stored)?;
+        ephemeral
+            .into_iter()
+            .try_for_each(|entry| self.set_password(entry))
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        if !keys.iter().any(|key| is_ephemeral(&key.service)) {
+            return self.inner.delete_many(keys);
+        }
+        let (ephemeral, stored): (Vec<KeystoreKey>, Vec<KeystoreKey>) = keys
+            .iter()
+            .cloned()
+            .partition(|key| is_ephemeral(&key.service));
+        self.check()?;
+        let mut removed = self.inner.delete_many(&stored)?;
+        let mut state = self.state.lock().unwrap();
+        state.purge();
+        for key in ephemeral {
+            if state.entries.remove(&key).is_some() {
+                removed.push(key);
+            }
+        }
+        removed.sort();
+        Ok(removed)
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        if !is_ephemeral(service) {
+            return self.inner.delete_service(service);
+        }
+        self.check()?;
+        let mut state = self.state.lock().unwrap();
+        let keys: Vec<KeystoreKey> = state
+            .entries
+            .keys()
+            .filter(|key| key.service == service)
+            .cloned()
+            .collect();
+        for key in &keys {
+            state.entries.remove(key);
+        }
+        Ok(keys.into_iter().map(|key| key.account).collect())
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let ephemeral = self.keys();
+        self.clear();
+        let mut wiped = self.inner.wipe_all()?;
+        wiped.extend(ephemeral);
+        Ok(wiped)
+    }
+
+    /// Ephemeral entries swap in memory, keeping their expiry with the value. Refuses to swap one
+    /// with a stored entry, which would write it to the backend.
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        match (is_ephemeral(&a.service), is_ephemeral(&b.service)) {
+            (false, false) => return self.inner.swap(a, b),
+            (true, true) => {}
+            _ => {
+                return Err(KeystoreError::InvalidInput(format!(
+                    "Cannot swap {}:{} with {}:{}; only one of them is ephemeral",
+                    a.service, a.account, b.service, b.account
+                )))
+            }
+        }
+        self.check()?;
+        let mut state = self.state.lock().unwrap();
+        state.purge();
+        for key in [a, b] {
+            if !state.entries.contains_key(key) {
+                return Err(KeystoreError::KeyNotFound(format!(
+                    "{}:{}",
+                    key.service, key.account
+                )));
+            }
+        }
+        if a != b {
+            let first = state.entries.remove(a);
+            let second = state.entries.remove(b);
+            if let (Some(first), Some(second)) = (first, second) {
+                state.entries.insert(a.clone(), second);
+                state.entries.insert(b.clone(), first);
+            }
+        }
+        Ok(())
+    }
+
+    /// Ephemeral entries keep no metadata
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        if !is_ephemeral(service) {
+            return self.inner.get_metadata(service, account);
+        }
+        self.check()?;
+        let mut state = self.state.lock().unwrap();
+        state.purge();
+        if state.entries.contains_key(&key(service, account)) {
+            Ok(EntryMetadata::default())
+        } else {
+            Err(KeystoreError::KeyNotFound(format!(
+                "{}:{}",
+                service, account
+            )))
+        }
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        if !is_ephemeral(service) {
+            return self.inner.has_password(service, account);
+        }
+        self.check()?;
+        let mut state = self.state.lock().unwrap();
+        state.purge();
+        Ok(state.entries.contains_key(&key(service, account)))
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        if !is_ephemeral(service) {
+            return self.inner.get_or_create(service, account, length);
+        }
+        self.check()?;
+        let mut state = self.state.lock().unwrap();
+        state.purge();
+        if let Some(slot) = state.entries.get(&key(service, account)) {
+            record_source(ReadSource::Ephemeral);
+            return Ok(slot.value.to_string());
+        }
+        let value = generate_secret(&SecretOptions {
+            length,
+            ..Default::default()
+        })?;
+        let expires = state.default_ttl.map(|ttl| Instant::now() + ttl);
+        state.entries.insert(
+            key(service, account),
+            Slot {
+                value: Zeroizing::new(value.clone()),
+                expires,
+            },
+        );
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_ephemeral_entries_stay_in_memory_and_expire() {
+        let backend = Arc::new(MemoryKeystore::default());
+        let keystore = EphemeralKeystore::new(Box::new(Arc::clone(&backend)));
+        let entry = |service: &str| KeystoreEntry {
+            service: service.to_string(),
+            account: "session".to_string(),
+            value: "cookie".to_string(),
+            ..Default::default()
+        };
+        keystore.set_password(&entry("ephemeral:twitch")).unwrap();
+        keystore.set_password(&entry("twitch")).unwrap();
+
+        assert_eq!(backend.entries.lock().unwrap().len(), 1);
+        assert_eq!(
+            keystore
+                .get_password("ephemeral:twitch", "session")
+                .unwrap(),
+            "cookie"
+        );
+        assert_eq!(keystore.list_keys().unwrap().len(), 2);
+
+        keystore
+            .set_ttl(
+                "ephemeral:twitch",
+                "session",
+                Some(Duration::from_millis(10)),
+            )
+            .unwrap();
+        thread::sleep(Duration::from_millis(20));
+        assert!(matches!(
+            keystore.get_password("ephemeral:twitch", "session"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+        assert_eq!(
+            keystore.get_password("twitch", "session").unwrap(),
+            "cookie"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// What a caller can do about an error, grouping the codes of [`KeystoreError`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    /// The keyring is locked; retry once the user has unlocked it
+    Locked,
+    /// The OS wants to prompt the user; retry from a foreground process or after signing in
+    InteractionRequired,
+    /// Refused by the OS, policy or scope; retrying won't help
+    Denied,
+    NotFound,
+    Timeout,
+    /// May succeed if retried
+    Transient,
+    /// The stored data can't be read back
+    Corrupted,
+    /// This platform, backend or file version can't do it
+    Unsupported,
+    /// The arguments were rejected before reaching the keystore
+    Invalid,
+    /// Unclassified platform or I/O failures
+    Other,
+}
+
+impl ErrorKind {
+    /// The kind every error with `code` has, or `None` for codes this build doesn't know
+    pub fn from_code(code: &str) -> Option<Self> {
+        Some(match code {
+            "ERR_KEYRING_LOCKED" => ErrorKind::Locked,
+            "ERR_INTERACTION_REQUIRED" => ErrorKind::InteractionRequired,
+            "ERR_ACCESS_DENIED" | "ERR_CONTEXT_MISMATCH" => ErrorKind::Denied,
+            "ERR_KEY_NOT_FOUND" => ErrorKind::NotFound,
+            "ERR_TIMEOUT" => ErrorKind::Timeout,
+            "ERR_TRANSIENT" | "ERR_BUSY" => ErrorKind::Transient,
+            "ERR_CORRUPTED" | "ERR_SERIALIZATION" => ErrorKind::Corrupted,
+            "ERR_PLATFORM_NOT_SUPPORTED" | "ERR_FORMAT_TOO_NEW" => ErrorKind::Unsupported,
+            "ERR_INVALID_INPUT" | "ERR_AMBIGUOUS" => ErrorKind::Invalid,
+            "ERR_PLATFORM" | "ERR_IO" => ErrorKind::Other,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Locked => "locked",
+            ErrorKind::InteractionRequired => "interaction-required",
+            ErrorKind::Denied => "denied",
+            ErrorKind::NotFound => "not-found",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Transient => "transient",
+            ErrorKind::Corrupted => "corrupted",
+            ErrorKind::Unsupported => "unsupported",
+            ErrorKind::Invalid => "invalid",
+            ErrorKind::Other => "other",
+        }
+    }
+
+    /// Whether the same call may succeed later without the user doing anything
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorKind::Locked | ErrorKind::Timeout | ErrorKind::Transient
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum KeystoreError {
+    #[error("Platform not supported")]
+    PlatformNotSupported,
+
+    #[error("Key not found: {0}")]
+    KeyNotFound(String),
+
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+
+    #[error("Platform error: {0}")]
+    Platform(String),
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("Ambiguous key: {0}")]
+    Ambiguous(String),
+
+    #[error("Keystore busy: {0}")]
+    Busy(String),
+
+    #[error("Context mismatch: {0}")]
+    ContextMismatch(String),
+
+    /// Written by a newer build in a layout this one doesn't know
+    #[error("Format too new: {0}")]
+    FormatTooNew(String),
+
+    /// The OS keyring holding the entries hasn't been unlocked yet, as right after autologin
+    #[error("Keyring locked: {0}")]
+    KeyringLocked(String),
+
+    /// The OS needs the user to authenticate or confirm, which this caller can't show, as a
+    /// Keychain item read from a background process while the login keychain wants a password
+    #[error("Interaction required: {0}")]
+    InteractionRequired(String),
+
+    /// The backend didn't answer in time
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    /// A failure that may clear up on retry, such as a keyring daemon that isn't running yet or a
+    /// file another process has open
+    #[error("Temporarily unavailable: {0}")]
+    Transient(String),
+
+    /// Stored data that exists but can't be decoded or decrypted
+    #[error("Corrupted: {0}")]
+    Corrupted(String),
+
+    /// Too many operations are waiting on the backend; reported as `ERR_BUSY`
+    #[error(
+        "Keystore busy: {queued} operations queued, retry after {} ms",
+        retry_after.as_millis()
+    )]
+    Overloaded {
+        queued: usize,
+        retry_after: std::time::Duration,
+    },
+}
+
+impl KeystoreError {
+    /// Stable error code surfaced to JavaScript callers
+    pub fn code(&self) -> &'static str {
+        match self {
+            KeystoreError::PlatformNotSupported => "ERR_PLATFORM_NOT_SUPPORTED",
+            KeystoreError::KeyNotFound(_) => "ERR_KEY_NOT_FOUND",
+            KeystoreError::AccessDenied(_) => "ERR_ACCESS_DENIED",
+            KeystoreError::Io(_) => "ERR_IO",
+            KeystoreError::Serialization(_) => "ERR_SERIALIZATION",
+            KeystoreError::Platform(_) => "ERR_PLATFORM",
+            KeystoreError::InvalidInput(_) => "ERR_INVALID_INPUT",
+            KeystoreError::Ambiguous(_) => "ERR_AMBIGUOUS",
+            KeystoreError::Busy(_) => "ERR_BUSY",
+            KeystoreError::ContextMismatch(_) => "ERR_CONTEXT_MISMATCH",
+            KeystoreError::FormatTooNew(_) => "ERR_FORMAT_TOO_NEW",
+            KeystoreError::KeyringLocked(_) => "ERR_KEYRING_LOCKED",
+            KeystoreError::InteractionRequired(_) => "ERR_INTERACTION_REQUIRED",
+            KeystoreError::Timeout(_) => "ERR_TIMEOUT",
+            KeystoreError::Transient(_) => "ERR_TRANSIENT",
+            KeystoreError::Corrupted(_) => "ERR_CORRUPTED",
+            KeystoreError::Overloaded { .. } => "ERR_BUSY",
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::from_code(self.code()).unwrap_or(ErrorKind::Other)
+    }
+
+    /// How long to wait before retrying, when the error says
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            KeystoreError::Overloaded { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_code_has_a_kind() {
+        let errors = [
+            KeystoreError::PlatformNotSupported,
+            KeystoreError::KeyNotFound(String::new()),
+            KeystoreError::AccessDenied(String::new()),
+            KeystoreError::Io(std::io::Error::other("disk")),
+            KeystoreError::Serialization(String::new()),
+            KeystoreError::Platform(String::new()),
+            KeystoreError::InvalidInput(String::new()),
+            KeystoreError::Ambiguous(String::new()),
+            KeystoreError::Busy(String::new()),
+            KeystoreError::ContextMismatch(String::new()),
+            KeystoreError::FormatTooNew(String::new()),
+            KeystoreError::KeyringLocked(String::new()),
+            KeystoreError::InteractionRequired(String::new()),
+            KeystoreError::Timeout(String::new()),
+            KeystoreError::Transient(String::new()),
+            KeystoreError::Corrupted(String::new()),
+            KeystoreError::Overloaded {
+                queued: 1,
+                retry_after: std::time::Duration::from_millis(50),
+            },
+        ];
+        for error in &errors {
+            assert!(
+                ErrorKind::from_code(error.code()).is_some(),
+                "{}",
+                error.code()
+            );
+        }
+        assert_eq!(errors[12].kind(), ErrorKind::InteractionRequired);
+        assert!(errors[16].kind().is_retryable());
+        assert!(!errors[1].kind().is_retryable());
+        assert_eq!(ErrorKind::from_code("ERR_SOMETHING_ELSE"), None);
+    }
+}
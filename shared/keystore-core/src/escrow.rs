@@ -0,0 +1,344 @@
+//! Organization key escrow for backups.
+//!
+//! Studios can have every backup additionally encrypted to an admin's X25519 public key, so a
+//! streamer PC's credentials stay recoverable after the operator who knew the backup passphrase
+//! leaves. A backup is encrypted under a random data key, which [`crate::backup`] wraps once under
+//! the passphrase and once per escrow key. Wrapping runs X25519 between a fresh ephemeral key and
+//! the escrow key, derives a wrapping key with HKDF-SHA256 bound to both public keys, and seals the
+//! data key with XChaCha20-Poly1305. Only the public half ever needs to be on the streamer's
+//! machine; the admin recovers with [`crate::backup::recover_backup`] and the secret half.
+//!
+//! Backups live for years, so X25519 alone is open to harvest-now-decrypt-later by a future quantum
+//! computer. A hybrid key ([`EscrowKey::generate_hybrid`]) adds an ML-KEM-768 keypair: its stanza
+//! also carries an ML-KEM ciphertext and the wrapping key is derived from both shared secrets, so
+//! recovering the data key needs breaking X25519 and ML-KEM. Hybrid public keys are the X25519 key
+//! followed by the ML-KEM encapsulation key; hybrid secret keys are the X25519 secret followed by
+//! the 64-byte ML-KEM seed.
+
+use crate::error::KeystoreError;
+use crate::generate::random_bytes;
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use ml_kem::kem::{Decapsulate, DecapsulationKey, EncapsulationKey};
+use ml_kem::{EncapsulateDeterministic, EncodedSizeUser, KemCore, MlKem768, MlKem768Params, B32};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+/// Size of an X25519 public key, and of a classical escrow public key
+pub const PUBLIC_KEY_SIZE: usize = 32;
+/// ML-KEM-768 encapsulation key
+const ML_KEM_PUBLIC_KEY_SIZE: usize = 1184;
+const ML_KEM_CIPHERTEXT_SIZE: usize = 1088;
+/// ML-KEM key generation seed, `d` then `z`
+const ML_KEM_SEED_SIZE: usize = 64;
+/// X25519 key followed by the ML-KEM-768 encapsulation key
+pub const HYBRID_PUBLIC_KEY_SIZE: usize = PUBLIC_KEY_SIZE + ML_KEM_PUBLIC_KEY_SIZE;
+const NONCE_SIZE: usize = 24;
+const DATA_KEY_SIZE: usize = 32;
+/// Ephemeral public key, nonce, sealed data key and tag
+pub(crate) const STANZA_SIZE: usize = PUBLIC_KEY_SIZE + NONCE_SIZE + DATA_KEY_SIZE + 16;
+/// A classical stanza with the ML-KEM ciphertext after the ephemeral public key
+pub(crate) const HYBRID_STANZA_SIZE: usize = STANZA_SIZE + ML_KEM_CIPHERTEXT_SIZE;
+const WRAP_KEY_INFO: &[u8] = b"streaming-enhancement:backup-escrow:v1";
+const HYBRID_WRAP_KEY_INFO: &[u8] = b"streaming-enhancement:backup-escrow-hybrid:v1";
+
+type MlKemDecapsulationKey = DecapsulationKey<MlKem768Params>;
+type MlKemEncapsulationKey = EncapsulationKey<MlKem768Params>;
+
+/// The public half of an escrow key, classical or hybrid
+#[derive(Clone, PartialEq, Eq)]
+pub struct EscrowPublicKey {
+    x25519: [u8; PUBLIC_KEY_SIZE],
+    ml_kem: Option<Vec<u8>>,
+}
+
+impl EscrowPublicKey {
+    pub fn is_hybrid(&self) -> bool {
+        self.ml_kem.is_some()
+    }
+
+    /// [`PUBLIC_KEY_SIZE`] or [`HYBRID_PUBLIC_KEY_SIZE`] bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.x25519[..], self.ml_kem.as_deref().unwrap_or_default()].concat()
+    }
+}
+
+/// Validates an escrow public key, e.g. one read from policy or passed from JS: 32 bytes for
+/// X25519, or [`HYBRID_PUBLIC_KEY_SIZE`] for X25519 with ML-KEM-768
+pub fn public_key_from_bytes(bytes: &[u8]) -> Result<EscrowPublicKey, KeystoreError> {
+    if bytes.len() != PUBLIC_KEY_SIZE && bytes.len() != HYBRID_PUBLIC_KEY_SIZE {
+        return Err(KeystoreError::InvalidInput(format!(
+            "Escrow public keys are {} bytes, or {} with ML-KEM, got {}",
+            PUBLIC_KEY_SIZE,
+            HYBRID_PUBLIC_KEY_SIZE,
+            bytes.len()
+        )));
+    }
+    let (x25519, ml_kem) = bytes.split_at(PUBLIC_KEY_SIZE);
+    Ok(EscrowPublicKey {
+        x25519: x25519.try_into().expect("split at PUBLIC_KEY_SIZE"),
+        ml_kem: (!ml_kem.is_empty()).then(|| ml_kem.to_vec()),
+    })
+}
+
+struct MlKemKey {
+    seed: Zeroizing<[u8; ML_KEM_SEED_SIZE]>,
+    decapsulation: MlKemDecapsulationKey,
+    encapsulation: MlKemEncapsulationKey,
+}
+
+impl MlKemKey {
+    fn from_seed(seed: &[u8]) -> Self {
+        let mut stored = Zeroizing::new([0u8; ML_KEM_SEED_SIZE]);
+        stored.copy_from_slice(seed);
+        let (d, z) = seed.split_at(32);
+        let (decapsulation, encapsulation) = MlKem768::generate_deterministic(
+            &B32::try_from(d).unwrap(),
+            &B32::try_from(z).unwrap(),
+        );
+        Self {
+            seed: stored,
+            decapsulation,
+            encapsulation,
+        }
+    }
+}
+
+/// An escrow keypair, held by the organization's admin
+pub struct EscrowKey {
+    secret: StaticSecret,
+    public: PublicKey,
+    ml_kem: Option<MlKemKey>,
+}
+
+impl EscrowKey {
+    /// A classical X25519 key
+    pub fn generate() -> Result<Self, KeystoreError> {
+        Self::from_secret_bytes(&Zeroizing::new(random_bytes(32)?))
+    }
+
+    /// An X25519 key paired with an ML-KEM-768 key, for backups that must outlive X25519
+    pub fn generate_hybrid() -> Result<Self, KeystoreError> {
+        Self::from_secret_bytes(&Zeroizing::new(random_bytes(32 + ML_KEM_SEED_SIZE)?))
+    }
+
+    /// 32 bytes for a classical key, 96 for a hybrid one
+    pub fn from_secret_bytes(bytes: &[u8]) -> Result<Self, KeystoreError> {
+        if bytes.len() != 32 && bytes.len() != 32 + ML_KEM_SEED_SIZE {
+            return Err(KeystoreError::InvalidInput(format!(
+                "Escrow secret keys are 32 bytes, or {} with ML-KEM",
+                32 + ML_KEM_SEED_SIZE
+            )));
+        }
+        let (x25519, seed) = bytes.split_at(32);
+        let x25519: [u8; 32] = x25519.try_into().expect("split at 32");
+        let secret = StaticSecret::from(x25519);
+        let public = PublicKey::from(&secret);
+        Ok(Self {
+            secret,
+            public,
+            ml_kem: (!seed.is_empty()).then(|| MlKemKey::from_seed(seed)),
+        })
+    }
+
+    pub fn is_hybrid(&self) -> bool {
+        self.ml_kem.is_some()
+    }
+
+    pub fn public_key(&self) -> EscrowPublicKey {
+        EscrowPublicKey {
+            x25519: self.public.to_bytes(),
+            ml_kem: self
+                .ml_kem
+                .as_ref()
+                .map(|key| key.encapsulation.as_bytes().to_vec()),
+        }
+    }
+
+    pub fn secret_key(&self) -> Zeroizing<Vec<u8>> {
+        let mut bytes = Zeroizing::new(self.secret.to_bytes().to_vec());
+        if let Some(key) = &self.ml_kem {
+            bytes.extend_from_slice(key.seed.as_ref());
+        }
+        bytes
+    }
+
+    /// The data key from a stanza written by [`wrap`] for this key, or `None` if it was written
+    /// for another key
+    pub(crate) fn unwrap(&self, stanza: &[u8]) -> Option<Zeroizing<[u8; DATA_KEY_SIZE]>> {
+        let (header, sealed) = match (stanza.len(), &self.ml_kem) {
+            (STANZA_SIZE, _) => stanza.split_at(PUBLIC_KEY_SIZE),
+            (HYBRID_STANZA_SIZE, Some(_)) => {
+                stanza.split_at(PUBLIC_KEY_SIZE + ML_KEM_CIPHERTEXT_SIZE)
+            }
+            _ => return None,
+        };
+        let (ephemeral, ciphertext) = header.split_at(PUBLIC_KEY_SIZE);
+        let ephemeral = PublicKey::from(<[u8; PUBLIC_KEY_SIZE]>::try_from(ephemeral).ok()?);
+        let shared = Zeroizing::new(match &self.ml_kem {
+            Some(key) if !ciphertext.is_empty() => key
+                .decapsulation
+                .decapsulate(ciphertext.try_into().ok()?)
+                .ok()?
+                .to_vec(),
+            _ => Vec::new(),
+        });
+        let cipher = wrapping_cipher(
+            &self.secret,
+            &ephemeral,
+            &ephemeral,
+            &self.public_key(),
+            ciphertext,
+            &shared,
+        )
+        .ok()?;
+        let (nonce, sealed) = sealed.split_at(NONCE_SIZE);
+        let key = Zeroizing::new(
+            cipher
+                .decrypt(
+                    XNonce::from_slice(nonce),
+                    Payload {
+                        msg: sealed,
+                        aad: header,
+                    },
+                )
+                .ok()?,
+        );
+        let mut data_key = Zeroizing::new([0u8; DATA_KEY_SIZE]);
+        data_key.copy_from_slice(&key);
+        Some(data_key)
+    }
+}
+
+/// For a hybrid stanza `ml_kem_ciphertext` and `ml_kem_shared` are set and the wrapping key comes
+/// from both shared secrets, bound to the ML-KEM key and ciphertext as well
+fn wrapping_cipher(
+    own_secret: &StaticSecret,
+    peer_public: &PublicKey,
+    ephemeral_public: &PublicKey,
+    escrow_public: &EscrowPublicKey,
+    ml_kem_ciphertext: &[u8],
+    ml_kem_shared: &[u8],
+) -> Result<XChaCha20Poly1305, KeystoreError> {
+    let shared = own_secret.diffie_hellman(peer_public);
+    // Low-order keys yield an all-zero secret anyone could compute
+    if !shared.was_contributory() {
+        return Err(KeystoreError::InvalidInput(
+            "Escrow public key is not a valid X25519 key".to_string(),
+        ));
+    }
+    let info = match &escrow_public.ml_kem {
+        None => [
+            WRAP_KEY_INFO,
+            ephemeral_public.as_bytes(),
+            &escrow_public.x25519,
+        ]
+        .concat(),
+        Some(ml_kem) => [
+            HYBRID_WRAP_KEY_INFO,
+            ephemeral_public.as_bytes(),
+            &escrow_public.x25519,
+            &Sha256::digest(ml_kem),
+            &Sha256::digest(ml_kem_ciphertext),
+        ]
+        .concat(),
+    };
+    let secret = Zeroizing::new([shared.as_bytes().as_slice(), ml_kem_shared].concat());
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(None, &secret)
+        .expand(&info, key.as_mut())
+        .map_err(|e| KeystoreError::Platform(format!("Failed to derive escrow key: {}", e)))?;
+    Ok(XChaCha20Poly1305::new(key.as_slice().into()))
+}
+
+/// Seals `data_key` to `escrow_public` as one stanza of [`STANZA_SIZE`] bytes, or
+/// [`HYBRID_STANZA_SIZE`] for a hybrid key
+pub(crate) fn wrap(
+    escrow_public: &EscrowPublicKey,
+    data_key: &[u8; DATA_KEY_SIZE],
+) -> Result<Vec<u8>, KeystoreError> {
+    let ephemeral = EscrowKey::generate()?;
+    let (ciphertext, shared) = match &escrow_public.ml_kem {
+        Some(ml_kem) => {
+            let encoded = ml_kem.as_slice().try_into().map_err(|_| {
+                KeystoreError::InvalidInput("Malformed ML-KEM public key".to_string())
+            })?;
+            let m = Zeroizing::new(random_bytes(32)?);
+            let (ciphertext, shared) = MlKemEncapsulationKey::from_bytes(encoded)
+                .encapsulate_deterministic(&B32::try_from(m.as_slice()).unwrap())
+                .map_err(|_| KeystoreError::Platform("ML-KEM encapsulation failed".to_string()))?;
+            (ciphertext.to_vec(), Zeroizing::new(shared.to_vec()))
+        }
+        None => (Vec::new(), Zeroizing::new(Vec::new())),
+    };
+    let peer = PublicKey::from(escrow_public.x25519);
+    let cipher = wrapping_cipher(
+        &ephemeral.secret,
+        &peer,
+        &ephemeral.public,
+        escrow_public,
+        &ciphertext,
+        &shared,
+    )?;
+    let header = [ephemeral.public.as_bytes().as_slice(), &ciphertext].concat();
+    let nonce = random_bytes(NONCE_SIZE)?;
+    let sealed = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: data_key,
+                aad: &header,
+            },
+        )
+        .map_err(|_| KeystoreError::Platform("Encryption failed".to_string()))?;
+    Ok([header, nonce, sealed].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_escrow_key_unwraps() {
+        let admin = EscrowKey::generate().unwrap();
+        let other = EscrowKey::generate().unwrap();
+        let data_key = [7u8; DATA_KEY_SIZE];
+
+        let stanza = wrap(&admin.public_key(), &data_key).unwrap();
+        assert_eq!(stanza.len(), STANZA_SIZE);
+        assert_eq!(*admin.unwrap(&stanza).unwrap(), data_key);
+        assert!(other.unwrap(&stanza).is_none());
+        let low_order = public_key_from_bytes(&[0u8; PUBLIC_KEY_SIZE]).unwrap();
+        assert!(wrap(&low_order, &data_key).is_err());
+
+        let restored = EscrowKey::from_secret_bytes(admin.secret_key().as_ref()).unwrap();
+        assert!(restored.public_key() == admin.public_key());
+    }
+
+    #[test]
+    fn test_hybrid_stanzas_need_both_secrets() {
+        let admin = EscrowKey::generate_hybrid().unwrap();
+        let data_key = [7u8; DATA_KEY_SIZE];
+        let public = public_key_from_bytes(&admin.public_key().to_bytes()).unwrap();
+        assert!(public.is_hybrid());
+
+        let stanza = wrap(&public, &data_key).unwrap();
+        assert_eq!(stanza.len(), HYBRID_STANZA_SIZE);
+        assert_eq!(*admin.unwrap(&stanza).unwrap(), data_key);
+
+        // The same X25519 secret without the ML-KEM half can't unwrap it
+        let classical_only = EscrowKey::from_secret_bytes(&admin.secret_key()[..32]).unwrap();
+        assert!(classical_only.unwrap(&stanza).is_none());
+        // Nor can a different ML-KEM key with the right X25519 secret
+        let mut mismatched = admin.secret_key().to_vec();
+        mismatched[40] ^= 1;
+        let mismatched = EscrowKey::from_secret_bytes(&mismatched).unwrap();
+        assert!(mismatched.unwrap(&stanza).is_none());
+
+        let restored = EscrowKey::from_secret_bytes(&admin.secret_key()).unwrap();
+        assert!(restored.public_key() == admin.public_key());
+    }
+}
@@ -0,0 +1,499 @@
+//! Entries that expire.
+//!
+//! An OAuth device-flow code or a temporary webhook secret is only good for a while.
+//! [`ExpiringKeystore`] keeps the `expires_at` of every entry written with one in an index entry,
+//! `streaming-enhancement:expiry-index`, so it works on backends that can't store it themselves.
+//! Once that time has passed the entry reads as `KeyNotFound` and is left out of listings; reads
+//! only consult the index, and [`ExpiringKeystore::purge_expired`] deletes what has expired,
+//! except protected and write-once entries, which still take a confirmed delete. The index is read
+//! afresh on every call, so expiries set by another process or instance apply at once. Writing
+//! the entry again without an expiry keeps it for good.
+//!
+//! The encrypted file also stores the expiry with the entry's metadata, enforces it on its own and
+//! drops expired entries whenever it saves.
+
+use crate::blob::BlobStore;
+use crate::error::KeystoreError;
+use crate::platform::{get_or_create_by_writes, BackendInfo, KeystoreOperations};
+use crate::protect::{PROTECTED_ACCOUNT, PROTECTED_SERVICE, WRITE_ONCE_ACCOUNT};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const INDEX_SERVICE: &str = "streaming-enhancement:expiry-index";
+const INDEX_ACCOUNT: &str = "index";
+
+/// service -> account -> expiry, in milliseconds since the Unix epoch
+type ExpiryIndex = BTreeMap<String, BTreeMap<String, u64>>;
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn expiry(index: &ExpiryIndex, service: &str, account: &str) -> Option<u64> {
+    index.get(service)?.get(account).copied()
+}
+
+fn is_expired(index: &ExpiryIndex, service: &str, account: &str, now: u64) -> bool {
+    expiry(index, service, account).is_some_and(|at| at <= now)
+}
+
+/// Records `expires_at` for the entry, or drops its expiry; whether the index changed
+fn set_expiry(
+    index: &mut ExpiryIndex,
+    service: &str,
+    account: &str,
+    expires_at: Option<u64>,
+) -> bool {
+    match expires_at {
+        Some(at) => {
+            index
+                .entry(service.to_string())
+                .or_default()
+                .insert(account.to_string(), at)
+                != Some(at)
+        }
+        None => forget(index, service, account),
+    }
+}
+
+/// Drops the entry's expiry; whether it had one
+fn forget(index: &mut ExpiryIndex, service: &str, account: &str) -> bool {
+    let Some(accounts) = index.get_mut(service) else {
+        return false;
+    };
+    let removed = accounts.remove(account).is_some();
+    if accounts.is_empty() {
+        index.remove(service);
+    }
+    removed
+}
+
+fn not_found(service: &str, account: &str) -> KeystoreError {
+    KeystoreError::KeyNotFound(format!("{}:{} (expired)", service, account))
+}
+
+pub struct ExpiringKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    /// Serializes index updates; the index itself is never cached
+    index: Mutex<()>,
+}
+
+impl ExpiringKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self {
+            inner,
+            index: Mutex::new(()),
+        }
+    }
+
+    /// Runs `f` on the index as stored now. The lock is held throughout so index updates don't
+    /// interleave.
+    fn with_index<T>(
+        &self,
+        f: impl FnOnce(&mut ExpiryIndex) -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        let _guard = self
+            .index
+            .lock()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))?;
+        let mut index = match self.blobs().get_structured(INDEX_SERVICE, INDEX_ACCOUNT) {
+            Ok(index) => index,
+            Err(KeystoreError::KeyNotFound(_)) => ExpiryIndex::new(),
+            Err(e) => return Err(e),
+        };
+        f(&mut index)
+    }
+
+    fn blobs(&self) -> BlobStore<'_> {
+        BlobStore::new(self.inner.as_ref())
+    }
+
+    fn save(&self, index: &ExpiryIndex) -> Result<(), KeystoreError> {
+        self.blobs()
+            .put_structured(INDEX_SERVICE, INDEX_ACCOUNT, index)
+    }
+
+    /// Protected and write-once keys, read from the sets [`crate::protect::ProtectedKeystore`]
+    /// keeps, since this layer sits below it
+    fn guarded(&self) -> Result<BTreeSet<KeystoreKey>, KeystoreError> {
+        let mut guarded = BTreeSet::new();
+        for account in [PROTECTED_ACCOUNT, WRITE_ONCE_ACCOUNT] {
+            match self
+                .blobs()
+                .get_structured::<BTreeSet<KeystoreKey>>(PROTECTED_SERVICE, account)
+            {
+                Ok(keys) => guarded.extend(keys),
+                Err(KeystoreError::KeyNotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(guarded)
+    }
+
+    /// Deletes every entry that has expired and returns their keys, sorted. Protected and
+    /// write-once entries are left in place, still reading as expired.
+    pub fn purge_expired(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let now = now_millis();
+        let guarded = self.guarded()?;
+        self.with_index(|index| {
+            let expired: Vec<KeystoreKey> = index
+                .iter()
+                .flat_map(|(service, accounts)| {
+                    accounts
+                        .iter()
+                        .filter(|(_, &at)| at <= now)
+                        .map(|(account, _)| KeystoreKey {
+                            service: service.clone(),
+                            account: account.clone(),
+                        })
+                })
+                .filter(|key| !guarded.contains(key))
+                .collect();
+            if expired.is_empty() {
+                return Ok(expired);
+            }
+            self.inner.delete_many(&expired)?;
+            for key in &expired {
+                forget(index, &key.service, &key.account);
+            }
+            self.save(index)?;
+            Ok(expired)
+        })
+    }
+
+    /// `keys` that have expired, as a mask over `keys`. Only checks the index; nothing is deleted.
+    fn expired_of(&self, keys: &[KeystoreKey]) -> Result<Vec<bool>, KeystoreError> {
+        let now = now_millis();
+        self.with_index(|index| {
+            Ok(keys
+                .iter()
+                .map(|key| is_expired(index, &key.service, &key.account, now))
+                .collect())
+        })
+    }
+
+    fn is_live(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        let now = now_millis();
+        self.with_index(|index| Ok(!is_expired(index, service, account, now)))
+    }
+}
+
+impl KeystoreOperations for ExpiringKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.with_index(|index| {
+            self.inner.set_password(entry)?;
+            if set_expiry(index, &entry.service, &entry.account, entry.expires_at) {
+                self.save(index)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        if !self.is_live(service, account)? {
+            return Err(not_found(service, account));
+        }
+        self.inner.get_password(service, account)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.with_index(|index| {
+            self.inner.delete_password(service, account)?;
+            if forget(index, service, account) {
+                self.save(index)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    /// Without expired entries and the index
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let now = now_millis();
+        let mut keys = self.inner.list_keys()?;
+        self.with_index(|index| {
+            keys.retain(|key| {
+                key.service != INDEX_SERVICE && !is_expired(index, &key.service, &key.account, now)
+            });
+            Ok(())
+        })?;
+        Ok(keys)
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let now = now_millis();
+        let mut accounts = self.inner.list_credentials(service)?;
+        self.with_index(|index| {
+            accounts.retain(|account| !is_expired(index, service, account, now));
+            Ok(())
+        })?;
+        Ok(accounts)
+    }
+
+    /// Expired entries are `None` without being read
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        let expired = self.expired_of(keys)?;
+        let live: Vec<KeystoreKey> = keys
+            .iter()
+            .zip(&expired)
+            .filter(|(_, &expired)| !expired)
+            .map(|(key, _)| key.clone())
+            .collect();
+        let mut values = self.inner.get_many(&live)?.into_iter();
+        Ok(expired
+            .into_iter()
+            .map(|expired| {
+                if expired {
+                    None
+                } else {
+                    values.next().flatten()
+                }
+            })
+            .collect())
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.with_index(|index| {
+            self.inner.set_many(entries)?;
+            let mut changed = false;
+            for entry in entries {
+                changed |= set_expiry(index, &entry.service, &entry.account, entry.expires_at);
+            }
+            if changed {
+                self.save(index)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.with_index(|index| {
+            let removed = self.inner.delete_many(keys)?;
+            let mut changed = false;
+            for key in keys {
+                changed |= forget(index, &key.service, &key.account);
+            }
+            if changed {
+                self.save(index)?;
+            }
+            Ok(removed)
+        })
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.with_index(|index| {
+            let removed = self.inner.delete_service(service)?;
+            if index.remove(service).is_some() {
+                self.save(index)?;
+            }
+            Ok(removed)
+        })
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.with_index(|index| {
+            let removed = self.inner.wipe_all()?;
+            index.clear();
+            Ok(removed)
+        })
+    }
+
+    /// Each value keeps its expiry
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        for key in [a, b] {
+            if !self.is_live(&key.service, &key.account)? {
+                return Err(not_found(&key.service, &key.account));
+            }
+        }
+        self.with_index(|index| {
+            self.inner.swap(a, b)?;
+            let first = expiry(index, &a.service, &a.account);
+            let second = expiry(index, &b.service, &b.account);
+            let changed = set_expiry(index, &a.service, &a.account, second)
+                | set_expiry(index, &b.service, &b.account, first);
+            if changed {
+                self.save(index)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        if !self.is_live(service, account)? {
+            return Err(not_found(service, account));
+        }
+        let metadata = self.inner.get_metadata(service, account)?;
+        let expires_at = self.with_index(|index| Ok(expiry(index, service, account)))?;
+        Ok(EntryMetadata {
+            expires_at: expires_at.or(metadata.expires_at),
+            ..metadata
+        })
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        Ok(self.is_live(service, account)? && self.inner.has_password(service, account)?)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        // An expired entry counts as missing, so it's replaced by a new one without an expiry
+        if !self.is_live(service, account)? {
+            return get_or_create_by_writes(self, service, account, length);
+        }
+        self.inner.get_or_create(service, account, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_entries_read_as_missing_once_expired() {
+        let backend = Arc::new(MemoryKeystore::default());
+        let keystore = ExpiringKeystore::new(Box::new(Arc::clone(&backend)));
+        let now = now_millis();
+        for (account, expires_at) in [("device-code", now - 1), ("webhook", now + 60_000)] {
+            keystore
+                .set_password(&KeystoreEntry {
+                    service: "twitch".to_string(),
+                    account: account.to_string(),
+                    value: "secret".to_string(),
+                    expires_at: Some(expires_at),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+
+        assert!(matches!(
+            keystore.get_password("twitch", "device-code"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+        // Reads only check; the entry stays stored until purged
+        assert_eq!(
+            backend.get_password("twitch", "device-code").unwrap(),
+            "secret"
+        );
+        assert_eq!(
+            keystore.get_password("twitch", "webhook").unwrap(),
+            "secret"
+        );
+        assert_eq!(
+            keystore
+                .get_metadata("twitch", "webhook")
+                .unwrap()
+                .expires_at,
+            Some(now + 60_000)
+        );
+        assert_eq!(keystore.list_credentials("twitch").unwrap(), ["webhook"]);
+        assert_eq!(
+            keystore.purge_expired().unwrap(),
+            [KeystoreKey {
+                service: "twitch".to_string(),
+                account: "device-code".to_string(),
+            }]
+        );
+        assert!(matches!(
+            backend.get_password("twitch", "device-code"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+
+        // Written again without an expiry, it stays
+        keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "webhook".to_string(),
+                value: "secret".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            keystore
+                .get_metadata("twitch", "webhook")
+                .unwrap()
+                .expires_at,
+            None
+        );
+        assert!(keystore.purge_expired().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_purge_leaves_protected_entries_and_sees_other_instances() {
+        use crate::protect::ProtectedKeystore;
+
+        let backend = Arc::new(MemoryKeystore::default());
+        let expiring = Arc::new(ExpiringKeystore::new(Box::new(Arc::clone(&backend))));
+        let protected = ProtectedKeystore::new(Box::new(Arc::clone(&expiring)));
+        let other = ExpiringKeystore::new(Box::new(Arc::clone(&backend)));
+        for account in ["stream-key", "device-code"] {
+            protected
+                .set_password(&KeystoreEntry {
+                    service: "obs".to_string(),
+                    account: account.to_string(),
+                    value: "secret".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        protected
+            .protect(&KeystoreKey {
+                service: "obs".to_string(),
+                account: "stream-key".to_string(),
+            })
+            .unwrap();
+        assert_eq!(
+            expiring.get_password("obs", "stream-key").unwrap(),
+            "secret"
+        );
+
+        // Set through another instance, after this one last read the index
+        let past = now_millis() - 1;
+        for account in ["stream-key", "device-code"] {
+            other
+                .set_password(&KeystoreEntry {
+                    service: "obs".to_string(),
+                    account: account.to_string(),
+                    value: "secret".to_string(),
+                    expires_at: Some(past),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        assert!(matches!(
+            expiring.get_password("obs", "stream-key"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+
+        assert_eq!(
+            expiring.purge_expired().unwrap(),
+            [KeystoreKey {
+                service: "obs".to_string(),
+                account: "device-code".to_string(),
+            }]
+        );
+        assert_eq!(backend.get_password("obs", "stream-key").unwrap(), "secret");
+    }
+}
@@ -0,0 +1,282 @@
+//! Streaming file encryption with keystore-held master keys.
+//!
+//! Files are encrypted in 64 KiB chunks with XChaCha20-Poly1305 in the STREAM construction, so
+//! memory use is constant regardless of file size and truncation, reordering or a dropped final
+//! chunk is detected. Each file gets a random salt and its key is derived from the master key with
+//! HKDF, which keeps one master key usable for any number of files.
+//!
+//! Layout: `SEKF` magic, version byte, chunk size (u32 BE), 32-byte salt, 19-byte nonce prefix,
+//! then the sealed chunks. The header is authenticated as associated data of every chunk.
+
+use crate::error::KeystoreError;
+use crate::generate::random_bytes;
+use crate::keys::MasterKeys;
+use crate::platform::KeystoreOperations;
+
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{KeyInit, Payload};
+use chacha20poly1305::XChaCha20Poly1305;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"SEKF";
+const VERSION: u8 = 1;
+const SALT_SIZE: usize = 32;
+/// XChaCha20's 24-byte nonce minus STREAM's 4-byte counter and 1-byte last-chunk flag
+const NONCE_PREFIX_SIZE: usize = 19;
+const HEADER_SIZE: usize = 4 + 1 + 4 + SALT_SIZE + NONCE_PREFIX_SIZE;
+const TAG_SIZE: usize = 16;
+
+/// Plaintext bytes per chunk
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+const KEY_INFO_PREFIX: &[u8] = b"streaming-enhancement:file-encryption:v1:";
+
+pub struct FileCipher<'a> {
+    keys: MasterKeys<'a>,
+}
+
+/// Reads until `buf` is full or the reader is exhausted
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn corrupt(message: &str) -> KeystoreError {
+    KeystoreError::Serialization(format!("Encrypted file {}", message))
+}
+
+/// Writes to `<dst>.partial` and renames over `dst` only once `write` succeeds, so a failed or
+/// tampered decryption never leaves partial plaintext behind
+fn write_atomically(
+    dst: &Path,
+    write: impl FnOnce(&mut File) -> Result<(), KeystoreError>,
+) -> Result<(), KeystoreError> {
+    let mut partial = PathBuf::from(dst.as_os_str());
+    partial.as_mut_os_string().push(".partial");
+
+    let result = File::create(&partial)
+        .map_err(KeystoreError::from)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            file.sync_all()?;
+            Ok(())
+        })
+        .and_then(|()| fs::rename(&partial, dst).map_err(KeystoreError::from));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+impl<'a> FileCipher<'a> {
+    pub fn new(backend: &'a dyn KeystoreOperations) -> Self {
+        Self {
+            keys: MasterKeys::new(backend),
+        }
+    }
+
+    fn cipher(&self, key_name: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, KeystoreError> {
+        let info = [KEY_INFO_PREFIX, salt].concat();
+        let key = self.keys.derive(key_name, &info, 32)?;
+        XChaCha20Poly1305::new_from_slice(&key)
+            .map_err(|e| KeystoreError::Platform(format!("Invalid file key: {}", e)))
+    }
+
+    /// Encrypts `src` into `dst` with a key derived from the master key `key_name`
+    pub fn encrypt_file(
+        &self,
+        key_name: &str,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(), KeystoreError> {
+        let mut input = File::open(src)?;
+
+        let salt = random_bytes(SALT_SIZE)?;
+        let nonce_prefix = random_bytes(NONCE_PREFIX_SIZE)?;
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(MAGIC);
+        header.push(VERSION);
+        header.extend_from_slice(&(CHUNK_SIZE as u32).to_be_bytes());
+        header.extend_from_slice(&salt);
+        header.extend_from_slice(&nonce_prefix);
+
+        let mut encryptor = EncryptorBE32::from_aead(
+            self.cipher(key_name, &salt)?,
+            nonce_prefix.as_slice().into(),
+        );
+        let failed = |_| KeystoreError::Platform("Encryption failed".to_string());
+
+        write_atomically(dst, |output| {
+            output.write_all(&header)?;
+
+            let mut current = vec![0u8; CHUNK_SIZE];
+            let mut next = vec![0u8; CHUNK_SIZE];
+            let mut current_len = read_full(&mut input, &mut current)?;
+            loop {
+                // One chunk of lookahead tells us which chunk is the last
+                let next_len = read_full(&mut input, &mut next)?;
+                let payload = Payload {
+                    msg: &current[..current_len],
+                    aad: &header,
+                };
+                if next_len == 0 {
+                    output.write_all(&encryptor.encrypt_last(payload).map_err(failed)?)?;
+                    return Ok(());
+                }
+                output.write_all(&encryptor.encrypt_next(payload).map_err(failed)?)?;
+                std::mem::swap(&mut current, &mut next);
+                current_len = next_len;
+            }
+        })
+    }
+
+    /// Decrypts `src` into `dst`. `dst` is only created once every chunk has authenticated.
+    pub fn decrypt_file(
+        &self,
+        key_name: &str,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(), KeystoreError> {
+        let mut input = File::open(src)?;
+
+        let mut header = [0u8; HEADER_SIZE];
+        if read_full(&mut input, &mut header)? != HEADER_SIZE || &header[..4] != MAGIC {
+            return Err(corrupt("has an invalid header"));
+        }
+        if header[4] > VERSION {
+            return Err(KeystoreError::FormatTooNew(format!(
+                "Encrypted file has version {}, this build reads up to {}",
+                header[4], VERSION
+            )));
+        }
+        if header[4] != VERSION {
+            return Err(corrupt(&format!("has unsupported version {}", header[4])));
+        }
+        let chunk_size = u32::from_be_bytes([header[5], header[6], header[7], header[8]]) as usize;
+        if chunk_size == 0 || chunk_size > 16 * CHUNK_SIZE {
+            return Err(corrupt("has an invalid chunk size"));
+        }
+        let salt = &header[9..9 + SALT_SIZE];
+        let nonce_prefix = &header[9 + SALT_SIZE..];
+
+        let mut decryptor =
+            DecryptorBE32::from_aead(self.cipher(key_name, salt)?, nonce_prefix.into());
+        let failed = |_| corrupt("failed authentication; wrong key or tampered data");
+
+        write_atomically(dst, |output| {
+            let mut current = vec![0u8; chunk_size + TAG_SIZE];
+            let mut next = vec![0u8; chunk_size + TAG_SIZE];
+            let mut current_len = read_full(&mut input, &mut current)?;
+            loop {
+                let next_len = read_full(&mut input, &mut next)?;
+                let payload = Payload {
+                    msg: &current[..current_len],
+                    aad: &header,
+                };
+                if next_len == 0 {
+                    output.write_all(&decryptor.decrypt_last(payload).map_err(failed)?)?;
+                    return Ok(());
+                }
+                output.write_all(&decryptor.decrypt_next(payload).map_err(failed)?)?;
+                std::mem::swap(&mut current, &mut next);
+                current_len = next_len;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use tempfile::TempDir;
+
+    fn setup() -> (MemoryKeystore, TempDir) {
+        let backend = MemoryKeystore::default();
+        MasterKeys::new(&backend).create("archives").unwrap();
+        (backend, TempDir::new().unwrap())
+    }
+
+    #[test]
+    fn test_round_trip_multi_chunk() {
+        let (backend, dir) = setup();
+        let files = FileCipher::new(&backend);
+        let (plain, sealed, opened) = (
+            dir.path().join("chat.log"),
+            dir.path().join("chat.log.enc"),
+            dir.path().join("chat.out"),
+        );
+
+        for size in [0, 10, CHUNK_SIZE, CHUNK_SIZE * 2 + 123] {
+            let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            fs::write(&plain, &data).unwrap();
+
+            files.encrypt_file("archives", &plain, &sealed).unwrap();
+            let expected_chunks = size.div_ceil(CHUNK_SIZE).max(1);
+            assert_eq!(
+                fs::metadata(&sealed).unwrap().len() as usize,
+                HEADER_SIZE + size + expected_chunks * TAG_SIZE
+            );
+
+            files.decrypt_file("archives", &sealed, &opened).unwrap();
+            assert_eq!(fs::read(&opened).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_tampering_and_truncation_are_detected() {
+        let (backend, dir) = setup();
+        let files = FileCipher::new(&backend);
+        let (plain, sealed, opened) = (
+            dir.path().join("scenes.json"),
+            dir.path().join("scenes.enc"),
+            dir.path().join("scenes.out"),
+        );
+        fs::write(&plain, vec![7u8; CHUNK_SIZE * 3]).unwrap();
+        files.encrypt_file("archives", &plain, &sealed).unwrap();
+        let original = fs::read(&sealed).unwrap();
+
+        let mut flipped = original.clone();
+        flipped[HEADER_SIZE + 10] ^= 1;
+        let truncated = original[..HEADER_SIZE + CHUNK_SIZE + TAG_SIZE].to_vec();
+
+        for bad in [flipped, truncated] {
+            fs::write(&sealed, &bad).unwrap();
+            assert!(matches!(
+                files.decrypt_file("archives", &sealed, &opened),
+                Err(KeystoreError::Serialization(_))
+            ));
+            assert!(!opened.exists());
+            assert!(!dir.path().join("scenes.out.partial").exists());
+        }
+    }
+
+    #[test]
+    fn test_wrong_key_fails() {
+        let (backend, dir) = setup();
+        MasterKeys::new(&backend).create("other").unwrap();
+        let files = FileCipher::new(&backend);
+        let (plain, sealed) = (dir.path().join("a"), dir.path().join("a.enc"));
+        fs::write(&plain, b"backup").unwrap();
+
+        files.encrypt_file("archives", &plain, &sealed).unwrap();
+        assert!(files
+            .decrypt_file("other", &sealed, &dir.path().join("a.out"))
+            .is_err());
+        assert!(matches!(
+            files.encrypt_file("missing", &plain, &sealed),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+    }
+}
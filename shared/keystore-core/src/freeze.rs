@@ -0,0 +1,336 @@
+//! Maintenance mode that holds writes back instead of interleaving them.
+//!
+//! While a [`FreezableKeystore`] is frozen, reads pass through but every write (set, delete,
+//! `delete_service`, `wipe_all`) is queued and its caller waits. [`FreezableKeystore::thaw`]
+//! applies the queue in arrival order and hands each caller its own result. The queue is bounded
+//! and waits time out, both failing with `Busy`, so a stuck backup cannot hang callers forever.
+//! A thread that writes while it holds the freeze fails immediately rather than waiting on itself.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+/// Writes that may wait while frozen before further writes fail
+pub const DEFAULT_MAX_QUEUED: usize = 64;
+
+/// How long a write waits for `thaw` before failing
+pub const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+type Apply = Box<dyn FnOnce(&dyn KeystoreOperations) + Send>;
+
+struct Pending {
+    id: u64,
+    apply: Apply,
+}
+
+struct State {
+    /// Thread that called `freeze`, while frozen
+    frozen_by: Option<ThreadId>,
+    queue: VecDeque<Pending>,
+    next_id: u64,
+}
+
+pub struct FreezableKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    state: Mutex<State>,
+    max_queued: usize,
+    write_timeout: Duration,
+}
+
+impl FreezableKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self::with_limits(inner, DEFAULT_MAX_QUEUED, DEFAULT_WRITE_TIMEOUT)
+    }
+
+    pub fn with_limits(
+        inner: Box<dyn KeystoreOperations>,
+        max_queued: usize,
+        write_timeout: Duration,
+    ) -> Self {
+        Self {
+            inner,
+            state: Mutex::new(State {
+                frozen_by: None,
+                queue: VecDeque::new(),
+                next_id: 0,
+            }),
+            max_queued,
+            write_timeout,
+        }
+    }
+
+    fn lock_state(&self) -> Result<MutexGuard<'_, State>, KeystoreError> {
+        self.state
+            .lock()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))
+    }
+
+    /// Starts queueing writes. Fails if already frozen.
+    pub fn freeze(&self) -> Result<(), KeystoreError> {
+        let mut state = self.lock_state()?;
+        if state.frozen_by.is_some() {
+            return Err(KeystoreError::Busy(
+                "Keystore is already frozen for maintenance".to_string(),
+            ));
+        }
+        state.frozen_by = Some(thread::current().id());
+        Ok(())
+    }
+
+    /// Applies the queued writes in order, then resumes normal writes. Returns how many were
+    /// applied; each write's own result goes to its caller.
+    pub fn thaw(&self) -> Result<usize, KeystoreError> {
+        let mut state = self.lock_state()?;
+        // Drained under the lock so new writes cannot overtake queued ones
+        let applied = state.queue.len();
+        while let Some(pending) = state.queue.pop_front() {
+            (pending.apply)(self.inner.as_ref());
+        }
+        state.frozen_by = None;
+        Ok(applied)
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.state
+            .lock()
+            .map(|state| state.frozen_by.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Freezes, runs `f` with direct access to the backend (so a migration can still write), and
+    /// thaws even if `f` fails
+    pub fn maintenance<T>(
+        &self,
+        f: impl FnOnce(&dyn KeystoreOperations) -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        self.freeze()?;
+        let result = f(self.inner.as_ref());
+        self.thaw()?;
+        result
+    }
+
+    /// Runs `op` now, or queues it and waits for `thaw` while frozen. `op` gets the keystore
+    /// underneath; writes made around it, such as alias changes, go through here to wait as well.
+    pub fn write<T: Send + 'static>(
+        &self,
+        op: impl FnOnce(&dyn KeystoreOperations) -> Result<T, KeystoreError> + Send + 'static,
+    ) -> Result<T, KeystoreError> {
+        let mut state = self.lock_state()?;
+        match state.frozen_by {
+            None => {
+                drop(state);
+                return op(self.inner.as_ref());
+            }
+            Some(owner) if owner == thread::current().id() => {
+                return Err(KeystoreError::Busy(
+                    "Keystore is frozen by this thread; thaw it before writing".to_string(),
+                ));
+            }
+            Some(_) if state.queue.len() >= self.max_queued => {
+                return Err(KeystoreError::Busy(format!(
+                    "Keystore is frozen for maintenance and {} writes are already queued",
+                    self.max_queued
+                )));
+            }
+            Some(_) => {}
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.queue.push_back(Pending {
+            id,
+            apply: Box::new(move |inner| {
+                let _ = sender.send(op(inner));
+            }),
+        });
+        drop(state);
+
+        match receiver.recv_timeout(self.write_timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                let mut state = self.lock_state()?;
+                if let Some(position) = state.queue.iter().position(|pending| pending.id == id) {
+                    state.queue.remove(position);
+                    return Err(KeystoreError::Busy(
+                        "Timed out waiting for keystore maintenance to finish".to_string(),
+                    ));
+                }
+                drop(state);
+                // Applied by a thaw that ran between the timeout and taking the lock
+                receiver
+                    .recv()
+                    .map_err(|_| KeystoreError::Platform("Queued write was dropped".to_string()))?
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(KeystoreError::Platform(
+                "Queued write was dropped".to_string(),
+            )),
+        }
+    }
+}
+
+impl KeystoreOperations for FreezableKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        let entry = entry.clone();
+        self.write(move |inner| inner.set_password(&entry))
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.inner.get_password(service, account)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let (service, account) = (service.to_string(), account.to_string());
+        self.write(move |inner| inner.delete_password(&service, &account))
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner
+            .backend_info()
+            .with_detail("frozen", self.is_frozen())
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.list_credentials(service)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        self.inner.get_many(keys)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        let entries = entries.to_vec();
+        self.write(move |inner| inner.set_many(&entries))
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let keys = keys.to_vec();
+        self.write(move |inner| inner.delete_many(&keys))
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let service = service.to_string();
+        self.write(move |inner| inner.delete_service(&service))
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.write(|inner| inner.wipe_all())
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        let (a, b) = (a.clone(), b.clone());
+        self.write(move |inner| inner.swap(&a, &b))
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        let (service, account) = (service.to_string(), account.to_string());
+        self.write(move |inner| inner.get_or_create(&service, &account, length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::Arc;
+
+    fn entry(value: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: "twitch".to_string(),
+            account: "bot-oauth".to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_writes_wait_for_thaw_and_apply_in_order() {
+        let keystore = Arc::new(FreezableKeystore::new(Box::new(MemoryKeystore::default())));
+        keystore.set_password(&entry("before")).unwrap();
+        keystore.freeze().unwrap();
+        assert!(matches!(
+            keystore.set_password(&entry("own thread")),
+            Err(KeystoreError::Busy(_))
+        ));
+
+        let writers: Vec<_> = ["first", "second"]
+            .into_iter()
+            .enumerate()
+            .map(|(queued, value)| {
+                let writer = {
+                    let keystore = Arc::clone(&keystore);
+                    thread::spawn(move || keystore.set_password(&entry(value)))
+                };
+                // Let each writer enqueue before starting the next
+                while keystore.state.lock().unwrap().queue.len() <= queued {
+                    thread::yield_now();
+                }
+                writer
+            })
+            .collect();
+        assert_eq!(
+            keystore.get_password("twitch", "bot-oauth").unwrap(),
+            "before"
+        );
+
+        assert_eq!(keystore.thaw().unwrap(), 2);
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+        assert_eq!(
+            keystore.get_password("twitch", "bot-oauth").unwrap(),
+            "second"
+        );
+    }
+
+    #[test]
+    fn test_queued_write_times_out_and_is_dropped() {
+        let keystore = Arc::new(FreezableKeystore::with_limits(
+            Box::new(MemoryKeystore::default()),
+            1,
+            Duration::from_millis(50),
+        ));
+        keystore.freeze().unwrap();
+        let writer = {
+            let keystore = Arc::clone(&keystore);
+            thread::spawn(move || keystore.set_password(&entry("late")))
+        };
+        assert!(matches!(
+            writer.join().unwrap(),
+            Err(KeystoreError::Busy(_))
+        ));
+        assert_eq!(keystore.thaw().unwrap(), 0);
+        assert!(keystore.get_password("twitch", "bot-oauth").is_err());
+    }
+}
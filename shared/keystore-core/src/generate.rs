@@ -0,0 +1,288 @@
+//! Secret generation from the operating system's CSPRNG.
+//!
+//! Every value is drawn from [`OsRng`] with unbiased sampling, so webhook secrets, pairing tokens
+//! and generated passwords never depend on a userland PRNG seeded by the JS runtime.
+
+use crate::error::KeystoreError;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, TryRngCore};
+
+/// Longest secret [`generate_secret`] will produce, in characters
+pub const MAX_SECRET_LENGTH: usize = 4096;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!#$%&()*+,-./:;<=>?@[]^_{|}~";
+const HEX: &str = "0123456789abcdef";
+/// Alphanumerics without 0/O, 1/l/I, for codes read aloud or typed from a screen
+const UNAMBIGUOUS: &str = "23456789abcdefghjkmnpqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ";
+
+/// Characters a generated secret is drawn from
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Charset {
+    #[default]
+    Alphanumeric,
+    /// Alphanumerics plus ASCII punctuation
+    Symbols,
+    Hex,
+    Unambiguous,
+    Custom(String),
+}
+
+impl Charset {
+    /// Parses a preset name as used by the napi options object
+    pub fn from_name(name: &str) -> Result<Self, KeystoreError> {
+        match name {
+            "alphanumeric" => Ok(Charset::Alphanumeric),
+            "symbols" => Ok(Charset::Symbols),
+            "hex" => Ok(Charset::Hex),
+            "unambiguous" => Ok(Charset::Unambiguous),
+            other => Err(KeystoreError::InvalidInput(format!(
+                "Unknown charset '{}'",
+                other
+            ))),
+        }
+    }
+
+    fn chars(&self) -> Result<Vec<char>, KeystoreError> {
+        let mut chars: Vec<char> = match self {
+            Charset::Alphanumeric => [LOWERCASE, UPPERCASE, DIGITS].concat().chars().collect(),
+            Charset::Symbols => [LOWERCASE, UPPERCASE, DIGITS, SYMBOLS]
+                .concat()
+                .chars()
+                .collect(),
+            Charset::Hex => HEX.chars().collect(),
+            Charset::Unambiguous => UNAMBIGUOUS.chars().collect(),
+            Charset::Custom(custom) => custom.chars().collect(),
+        };
+        // Duplicates in a custom set would silently bias the output
+        chars.sort_unstable();
+        chars.dedup();
+
+        if chars.len() < 2 {
+            return Err(KeystoreError::InvalidInput(
+                "Charset must contain at least two distinct characters".to_string(),
+            ));
+        }
+        Ok(chars)
+    }
+}
+
+/// Text encoding for [`random_token`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenEncoding {
+    Hex,
+    /// Standard alphabet with padding
+    Base64,
+    /// URL-safe alphabet without padding, for OAuth `state`, session ids and CSRF tokens
+    Base64Url,
+}
+
+impl TokenEncoding {
+    pub fn from_name(name: &str) -> Result<Self, KeystoreError> {
+        match name {
+            "hex" => Ok(TokenEncoding::Hex),
+            "base64" => Ok(TokenEncoding::Base64),
+            "base64url" => Ok(TokenEncoding::Base64Url),
+            other => Err(KeystoreError::InvalidInput(format!(
+                "Unknown token encoding '{}'",
+                other
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretOptions {
+    /// Number of characters, excluding group separators
+    pub length: usize,
+    pub charset: Charset,
+    /// Splits the output into `-`-separated groups of this many characters
+    pub groups: Option<usize>,
+}
+
+impl Default for SecretOptions {
+    fn default() -> Self {
+        Self {
+            length: 32,
+            charset: Charset::default(),
+            groups: None,
+        }
+    }
+}
+
+/// `len` bytes from the OS CSPRNG
+pub fn random_bytes(len: usize) -> Result<Vec<u8>, KeystoreError> {
+    check_length(len, "Byte count")?;
+    let mut bytes = vec![0u8; len];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .map_err(|e| KeystoreError::Platform(format!("Failed to read OS randomness: {}", e)))?;
+    Ok(bytes)
+}
+
+fn check_length(length: usize, what: &str) -> Result<(), KeystoreError> {
+    if length == 0 || length > MAX_SECRET_LENGTH {
+        return Err(KeystoreError::InvalidInput(format!(
+            "{} must be between 1 and {}",
+            what, MAX_SECRET_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// Random string of `options.length` characters from `options.charset`
+pub fn generate_secret(options: &SecretOptions) -> Result<String, KeystoreError> {
+    check_length(options.length, "Secret length")?;
+    let chars = options.charset.chars()?;
+    if options.groups == Some(0) {
+        return Err(KeystoreError::InvalidInput(
+            "Group size must be at least 1".to_string(),
+        ));
+    }
+
+    let mut rng = OsRng.unwrap_err();
+    let mut secret = String::with_capacity(options.length * 2);
+    for i in 0..options.length {
+        if let Some(size) = options.groups {
+            if i > 0 && i % size == 0 {
+                secret.push('-');
+            }
+        }
+        secret.push(chars[rng.random_range(0..chars.len())]);
+    }
+    Ok(secret)
+}
+
+/// `bytes` random bytes as text in `encoding`
+pub fn random_token(encoding: TokenEncoding, bytes: usize) -> Result<String, KeystoreError> {
+    let raw = random_bytes(bytes)?;
+    Ok(match encoding {
+        TokenEncoding::Hex => hex::encode(raw),
+        TokenEncoding::Base64 => STANDARD.encode(raw),
+        TokenEncoding::Base64Url => URL_SAFE_NO_PAD.encode(raw),
+    })
+}
+
+/// `bytes` random bytes as lowercase hex, e.g. for webhook signing secrets
+pub fn hex_token(bytes: usize) -> Result<String, KeystoreError> {
+    random_token(TokenEncoding::Hex, bytes)
+}
+
+/// `bytes` random bytes as standard padded base64, e.g. for symmetric keys
+pub fn base64_key(bytes: usize) -> Result<String, KeystoreError> {
+    random_token(TokenEncoding::Base64, bytes)
+}
+
+/// `words` words from the BIP-39 English list (11 bits of entropy each) joined by `separator`
+pub fn passphrase(words: usize, separator: &str) -> Result<String, KeystoreError> {
+    if words == 0 || words > 64 {
+        return Err(KeystoreError::InvalidInput(
+            "Passphrase must have between 1 and 64 words".to_string(),
+        ));
+    }
+
+    let list = bip39::Language::English.word_list();
+    let mut rng = OsRng.unwrap_err();
+    let chosen: Vec<&str> = (0..words)
+        .map(|_| *list.choose(&mut rng).expect("word list is not empty"))
+        .collect();
+    Ok(chosen.join(separator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_secret_uses_charset_and_length() {
+        let secret = generate_secret(&SecretOptions {
+            length: 64,
+            charset: Charset::Hex,
+            groups: None,
+        })
+        .unwrap();
+
+        assert_eq!(secret.len(), 64);
+        assert!(secret.chars().all(|c| HEX.contains(c)));
+        assert_ne!(
+            secret,
+            generate_secret(&SecretOptions {
+                length: 64,
+                charset: Charset::Hex,
+                groups: None,
+            })
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_generate_secret_groups() {
+        let code = generate_secret(&SecretOptions {
+            length: 12,
+            charset: Charset::Unambiguous,
+            groups: Some(4),
+        })
+        .unwrap();
+
+        let groups: Vec<_> = code.split('-').collect();
+        assert_eq!(groups.len(), 3);
+        assert!(groups.iter().all(|g| g.len() == 4));
+    }
+
+    #[test]
+    fn test_rejects_invalid_options() {
+        let invalid = [
+            SecretOptions {
+                length: 0,
+                ..Default::default()
+            },
+            SecretOptions {
+                charset: Charset::Custom("aaaa".to_string()),
+                ..Default::default()
+            },
+            SecretOptions {
+                groups: Some(0),
+                ..Default::default()
+            },
+        ];
+        for options in &invalid {
+            assert!(matches!(
+                generate_secret(options),
+                Err(KeystoreError::InvalidInput(_))
+            ));
+        }
+        assert!(Charset::from_name("emoji").is_err());
+    }
+
+    #[test]
+    fn test_random_bytes_and_tokens() {
+        assert_eq!(random_bytes(16).unwrap().len(), 16);
+        assert_ne!(random_bytes(16).unwrap(), random_bytes(16).unwrap());
+        assert!(random_bytes(0).is_err());
+
+        let token = random_token(TokenEncoding::Base64Url, 32).unwrap();
+        assert_eq!(token.len(), 43);
+        assert!(token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert!(TokenEncoding::from_name("base32").is_err());
+    }
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(hex_token(32).unwrap().len(), 64);
+        assert_eq!(STANDARD.decode(base64_key(32).unwrap()).unwrap().len(), 32);
+
+        let phrase = passphrase(6, " ").unwrap();
+        let list = bip39::Language::English.word_list();
+        let words: Vec<_> = phrase.split(' ').collect();
+        assert_eq!(words.len(), 6);
+        assert!(words.iter().all(|w| list.contains(w)));
+    }
+}
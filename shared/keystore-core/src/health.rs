@@ -0,0 +1,201 @@
+//! Keystore health summary for the daemon's `/status` endpoint.
+//!
+//! [`doctor_report`] adds a full [`crate::integrity`] audit on top, for troubleshooting; it reads
+//! every entry, so keep it out of anything polled.
+
+use crate::certs::{CertificateInfo, CertificateStore};
+use crate::integrity::{verify_all, IntegrityReport};
+use crate::platform::{BackendInfo, KeystoreOperations};
+
+/// Days before `not_after` at which a certificate starts degrading the report by default
+pub const DEFAULT_CERT_WARNING_DAYS: u32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Healthy,
+    /// Usable, but running on the fallback backend or holding certificates close to expiry
+    Degraded,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HealthStatus::Healthy => "healthy",
+            HealthStatus::Degraded => "degraded",
+            HealthStatus::Unhealthy => "unhealthy",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub backend: BackendInfo,
+    pub available: bool,
+    pub is_fallback: bool,
+    /// Certificates expiring within the warning window, including expired ones, soonest first
+    pub expiring_certificates: Vec<CertificateInfo>,
+    /// Human-readable reasons for a non-healthy status
+    pub warnings: Vec<String>,
+    /// Set by [`doctor_report`]; `None` when the audit didn't run or couldn't
+    pub integrity: Option<IntegrityReport>,
+}
+
+pub fn health_report(backend: &dyn KeystoreOperations, cert_warning_days: u32) -> HealthReport {
+    let info = backend.backend_info();
+    let available = backend.is_available();
+    let is_fallback = info.name == "encrypted-file";
+    let mut warnings = Vec::new();
+
+    if !available {
+        warnings.push(format!("Keystore backend {} is unavailable", info.name));
+    }
+    if is_fallback {
+        match info.details.get("fallback_reason") {
+            Some(reason) => warnings.push(format!(
+                "Using the encrypted-file fallback keystore: {}",
+                reason
+            )),
+            None => warnings.push("Using the encrypted-file fallback keystore".to_string()),
+        }
+    }
+    if info
+        .details
+        .get("read_only")
+        .is_some_and(|flag| flag == "true")
+    {
+        warnings.push(format!(
+            "Keystore file is from a newer version (format {}) and is read-only until the app is \
+             updated",
+            info.details
+                .get("format_version")
+                .map(String::as_str)
+                .unwrap_or("unknown")
+        ));
+    }
+
+    if let Some(error) = info.details.get("legacy_migration_error") {
+        warnings.push(format!(
+            "Failed to import a keystore file an older version left behind: {}",
+            error
+        ));
+    }
+
+    let expiring_certificates = if available {
+        match CertificateStore::new(backend).list_expiring_certs(cert_warning_days) {
+            Ok(certs) => certs,
+            Err(e) => {
+                warnings.push(format!("Failed to check certificate expiry: {}", e));
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    for cert in &expiring_certificates {
+        warnings.push(format!(
+            "Certificate {} ({}) expires at {}",
+            cert.name, cert.subject, cert.not_after
+        ));
+    }
+
+    let status = if !available {
+        HealthStatus::Unhealthy
+    } else if warnings.is_empty() {
+        HealthStatus::Healthy
+    } else {
+        HealthStatus::Degraded
+    };
+
+    HealthReport {
+        status,
+        backend: info,
+        available,
+        is_fallback,
+        expiring_certificates,
+        warnings,
+        integrity: None,
+    }
+}
+
+/// [`health_report`] plus an integrity audit, whose issues degrade the status
+pub fn doctor_report(backend: &dyn KeystoreOperations, cert_warning_days: u32) -> HealthReport {
+    let mut report = health_report(backend, cert_warning_days);
+    if !report.available {
+        return report;
+    }
+    match verify_all(backend) {
+        Ok(integrity) => {
+            for issue in &integrity.issues {
+                report.warnings.push(match &issue.key {
+                    Some(key) => format!(
+                        "Integrity issue ({}) at {}:{}: {}",
+                        issue.kind.as_str(),
+                        key.service,
+                        key.account,
+                        issue.detail
+                    ),
+                    None => format!(
+                        "Integrity issue ({}): {}",
+                        issue.kind.as_str(),
+                        issue.detail
+                    ),
+                });
+            }
+            report.integrity = Some(integrity);
+        }
+        Err(e) => report
+            .warnings
+            .push(format!("Failed to audit keystore integrity: {}", e)),
+    }
+    if report.status == HealthStatus::Healthy && !report.warnings.is_empty() {
+        report.status = HealthStatus::Degraded;
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    #[test]
+    fn test_healthy_without_certificates() {
+        let backend = MemoryKeystore::default();
+
+        let report = health_report(&backend, DEFAULT_CERT_WARNING_DAYS);
+        assert_eq!(report.status, HealthStatus::Healthy);
+        assert!(report.available);
+        assert!(!report.is_fallback);
+        assert!(report.warnings.is_empty());
+        assert_eq!(report.integrity, None);
+
+        let report = doctor_report(&backend, DEFAULT_CERT_WARNING_DAYS);
+        assert_eq!(report.status, HealthStatus::Healthy);
+        assert!(report.integrity.unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_expiring_certificate_degrades_report() {
+        let backend = MemoryKeystore::default();
+        let pem = rcgen::generate_simple_self_signed(vec!["tls.local".to_string()])
+            .unwrap()
+            .cert
+            .pem();
+        CertificateStore::new(&backend)
+            .import_pem("overlay-tls", &pem)
+            .unwrap();
+
+        // rcgen's default validity ends in 4096, far outside a 30 day window
+        assert_eq!(
+            health_report(&backend, DEFAULT_CERT_WARNING_DAYS).status,
+            HealthStatus::Healthy
+        );
+
+        let report = health_report(&backend, 1_000_000);
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert_eq!(report.expiring_certificates[0].name, "overlay-tls");
+        assert_eq!(report.warnings.len(), 1);
+    }
+}
@@ -0,0 +1,456 @@
+//! Caller-supplied code run before and after keystore operations.
+//!
+//! [`HookedKeystore::add_hook`] registers a [`Hook`] for some or all operations, to run either
+//! before the call reaches the backend or after it returns. Hooks run synchronously on the calling
+//! thread, in registration order, and get the entries a call names but never their values. Each
+//! has a timeout and a [`FailurePolicy`]: under `Fail`, a before hook that refuses or runs out of
+//! time stops the call with `AccessDenied` or `Timeout`, and an after hook that fails turns a
+//! successful call into an error, although its change has been made. Under `Ignore` the call goes
+//! ahead as if the hook weren't there. Keystore calls a hook makes itself skip the hooks.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// How long a hook may take unless registered with another timeout
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookPhase {
+    Before,
+    After,
+}
+
+/// What a hook that fails or times out does to the call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailurePolicy {
+    /// The call fails with the hook's error
+    #[default]
+    Fail,
+    /// The call goes on as if the hook had succeeded
+    Ignore,
+}
+
+/// One operation, as a hook sees it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HookEvent {
+    /// The [`KeystoreOperations`] method, e.g. `set_password` or `delete_many`
+    pub operation: &'static str,
+    pub phase: HookPhase,
+    /// Entries the call names; empty for listings and `wipe_all`
+    pub keys: Vec<KeystoreKey>,
+    /// The service of `list_credentials` and `delete_service`
+    pub service: Option<String>,
+    /// For after hooks, the code of the call's error, `None` if it succeeded
+    pub error: Option<&'static str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookError {
+    /// The hook refused the call or failed, saying why
+    Failed(String),
+    TimedOut,
+}
+
+pub trait Hook: Send + Sync {
+    /// Runs for `event`. A hook that can stop waiting should give up after `timeout`; one that
+    /// returns later is treated as timed out anyway.
+    fn call(&self, event: &HookEvent, timeout: Duration) -> Result<(), HookError>;
+}
+
+impl<F> Hook for F
+where
+    F: Fn(&HookEvent) -> Result<(), String> + Send + Sync,
+{
+    fn call(&self, event: &HookEvent, _timeout: Duration) -> Result<(), HookError> {
+        self(event).map_err(HookError::Failed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookOptions {
+    pub phase: HookPhase,
+    /// Operations to run for, as in [`HookEvent::operation`]; empty for all of them
+    pub operations: Vec<String>,
+    pub timeout: Duration,
+    pub on_failure: FailurePolicy,
+}
+
+impl HookOptions {
+    pub fn new(phase: HookPhase) -> Self {
+        Self {
+            phase,
+            operations: Vec::new(),
+            timeout: DEFAULT_HOOK_TIMEOUT,
+            on_failure: FailurePolicy::Fail,
+        }
+    }
+
+    fn covers(&self, event: &HookEvent) -> bool {
+        self.phase == event.phase
+            && (self.operations.is_empty()
+                || self.operations.iter().any(|name| name == event.operation))
+    }
+}
+
+struct Registered {
+    id: u64,
+    options: HookOptions,
+    hook: Arc<dyn Hook>,
+}
+
+thread_local! {
+    /// Set while this thread runs a hook, so the keystore calls it makes don't run hooks again
+    static IN_HOOK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` as a hook, so keystore calls it makes skip the hooks. [`HookedKeystore`] does this
+/// itself; bindings whose hooks run on another thread than the call that triggered them wrap the
+/// hook in it there too.
+pub fn as_hook<T>(f: impl FnOnce() -> T) -> T {
+    /// Restores the flag even if `f` panics
+    struct Reset(bool);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            IN_HOOK.with(|flag| flag.set(self.0));
+        }
+    }
+
+    let _reset = Reset(IN_HOOK.with(|flag| flag.replace(true)));
+    f()
+}
+
+pub struct HookedKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    hooks: RwLock<(u64, Vec<Registered>)>,
+}
+
+fn keys_of(entries: &[KeystoreEntry]) -> Vec<KeystoreKey> {
+    entries
+        .iter()
+        .map(|entry| KeystoreKey {
+            service: entry.service.clone(),
+            account: entry.account.clone(),
+        })
+        .collect()
+}
+
+fn key(service: &str, account: &str) -> Vec<KeystoreKey> {
+    vec![KeystoreKey {
+        service: service.to_string(),
+        account: account.to_string(),
+    }]
+}
+
+impl HookedKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self {
+            inner,
+            hooks: RwLock::new((0, Vec::new())),
+        }
+    }
+
+    /// Registers `hook`, returning the id that removes it
+    pub fn add_hook(&self, options: HookOptions, hook: Arc<dyn Hook>) -> u64 {
+        let mut hooks = self.hooks.write().unwrap();
+        hooks.0 += 1;
+        let id = hooks.0;
+        hooks.1.push(Registered { id, options, hook });
+        id
+    }
+
+    /// Whether a hook with `id` was registered
+    pub fn remove_hook(&self, id: u64) -> bool {
+        let mut hooks = self.hooks.write().unwrap();
+        let before = hooks.1.len();
+        hooks.1.retain(|registered| registered.id != id);
+        hooks.1.len() != before
+    }
+
+    pub fn hook_count(&self) -> usize {
+        self.hooks.read().unwrap().1.len()
+    }
+
+    /// Runs the hooks `event` covers, stopping at the first failure that counts
+    fn run(&self, event: &HookEvent) -> Result<(), KeystoreError> {
+        let hooks: Vec<(u64, HookOptions, Arc<dyn Hook>)> = self
+            .hooks
+            .read()
+            .unwrap()
+            .1
+            .iter()
+            .filter(|registered| registered.options.covers(event))
+            .map(|registered| {
+                (
+                    registered.id,
+                    registered.options.clone(),
+                    Arc::clone(&registered.hook),
+                )
+            })
+            .collect();
+        for (id, options, hook) in hooks {
+            let started = Instant::now();
+            let mut outcome = as_hook(|| hook.call(event, options.timeout));
+            if outcome.is_ok() && started.elapsed() > options.timeout {
+                outcome = Err(HookError::TimedOut);
+            }
+            let error = match (outcome, options.on_failure) {
+                (Ok(()), _) | (Err(_), FailurePolicy::Ignore) => continue,
+                (Err(HookError::TimedOut), FailurePolicy::Fail) => KeystoreError::Timeout(format!(
+                    "Hook {} did not finish {} within {}ms",
+                    id,
+                    event.operation,
+                    options.timeout.as_millis()
+                )),
+                (Err(HookError::Failed(reason)), FailurePolicy::Fail) => match event.phase {
+                    HookPhase::Before => KeystoreError::AccessDenied(format!(
+                        "Hook {} refused {}: {}",
+                        id, event.operation, reason
+                    )),
+                    HookPhase::After => KeystoreError::Platform(format!(
+                        "Hook {} failed after {}: {}",
+                        id, event.operation, reason
+                    )),
+                },
+            };
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Runs `call` between its before and after hooks
+    fn hooked<T>(
+        &self,
+        operation: &'static str,
+        keys: Vec<KeystoreKey>,
+        service: Option<&str>,
+        call: impl FnOnce() -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        if IN_HOOK.with(Cell::get) || self.hooks.read().unwrap().1.is_empty() {
+            return call();
+        }
+        let mut event = HookEvent {
+            operation,
+            phase: HookPhase::Before,
+            keys,
+            service: service.map(str::to_string),
+            error: None,
+        };
+        self.run(&event)?;
+        let result = call();
+        event.phase = HookPhase::After;
+        event.error = result.as_ref().err().map(KeystoreError::code);
+        match (self.run(&event), result) {
+            (Err(hook_error), Ok(_)) => Err(hook_error),
+            (_, result) => result,
+        }
+    }
+}
+
+impl KeystoreOperations for HookedKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.hooked(
+            "set_password",
+            key(&entry.service, &entry.account),
+            None,
+            || self.inner.set_password(entry),
+        )
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.hooked("get_password", key(service, account), None, || {
+            self.inner.get_password(service, account)
+        })
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.hooked("delete_password", key(service, account), None, || {
+            self.inner.delete_password(service, account)
+        })
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        let info = self.inner.backend_info();
+        match self.hook_count() {
+            0 => info,
+            hooks => info.with_detail("hooks", hooks),
+        }
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.hooked("list_keys", Vec::new(), None, || self.inner.list_keys())
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.hooked("list_credentials", Vec::new(), Some(service), || {
+            self.inner.list_credentials(service)
+        })
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        self.hooked("get_many", keys.to_vec(), None, || {
+            self.inner.get_many(keys)
+        })
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.hooked("set_many", keys_of(entries), None, || {
+            self.inner.set_many(entries)
+        })
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.hooked("delete_many", keys.to_vec(), None, || {
+            self.inner.delete_many(keys)
+        })
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.hooked("delete_service", Vec::new(), Some(service), || {
+            self.inner.delete_service(service)
+        })
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.hooked("wipe_all", Vec::new(), None, || self.inner.wipe_all())
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.hooked("swap", vec![a.clone(), b.clone()], None, || {
+            self.inner.swap(a, b)
+        })
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.hooked("get_metadata", key(service, account), None, || {
+            self.inner.get_metadata(service, account)
+        })
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.hooked("has_password", key(service, account), None, || {
+            self.inner.has_password(service, account)
+        })
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        self.hooked("get_or_create", key(service, account), None, || {
+            self.inner.get_or_create(service, account, length)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::Mutex;
+
+    fn entry(account: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+            value: "token".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hooks_run_around_calls_with_their_failure_policy() {
+        let keystore = Arc::new(HookedKeystore::new(Box::new(MemoryKeystore::default())));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let reentrant = Arc::clone(&keystore);
+        keystore.add_hook(
+            HookOptions::new(HookPhase::After),
+            Arc::new(move |event: &HookEvent| {
+                // Calls from a hook don't run hooks again
+                let _ = reentrant.list_keys();
+                log.lock()
+                    .unwrap()
+                    .push((event.operation, event.keys.len(), event.error));
+                Ok(())
+            }),
+        );
+        let veto = keystore.add_hook(
+            HookOptions {
+                operations: vec!["set_password".to_string()],
+                ..HookOptions::new(HookPhase::Before)
+            },
+            Arc::new(|event: &HookEvent| match event.keys[0].account.as_str() {
+                "bot-oauth" => Err("bots are managed elsewhere".to_string()),
+                _ => Ok(()),
+            }),
+        );
+
+        keystore.set_password(&entry("broadcaster-oauth")).unwrap();
+        let refused = keystore.set_password(&entry("bot-oauth")).unwrap_err();
+        assert!(matches!(refused, KeystoreError::AccessDenied(_)));
+        assert!(keystore.get_password("twitch", "bot-oauth").is_err());
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                ("set_password", 1, None),
+                ("get_password", 1, Some("ERR_KEY_NOT_FOUND")),
+            ]
+        );
+
+        assert!(keystore.remove_hook(veto));
+        keystore.add_hook(
+            HookOptions {
+                timeout: Duration::from_millis(1),
+                on_failure: FailurePolicy::Ignore,
+                ..HookOptions::new(HookPhase::Before)
+            },
+            Arc::new(|_: &HookEvent| {
+                std::thread::sleep(Duration::from_millis(5));
+                Ok(())
+            }),
+        );
+        keystore.set_password(&entry("bot-oauth")).unwrap();
+    }
+
+    #[test]
+    fn test_a_panicking_hook_leaves_hooks_on() {
+        let keystore = HookedKeystore::new(Box::new(MemoryKeystore::default()));
+        let runs = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&runs);
+        keystore.add_hook(
+            HookOptions::new(HookPhase::Before),
+            Arc::new(move |_: &HookEvent| {
+                *counter.lock().unwrap() += 1;
+                if *counter.lock().unwrap() == 1 {
+                    panic!("hook bug");
+                }
+                Ok(())
+            }),
+        );
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            keystore.set_password(&entry("bot-oauth"))
+        }));
+        assert!(panicked.is_err());
+        keystore.set_password(&entry("bot-oauth")).unwrap();
+        assert_eq!(*runs.lock().unwrap(), 2);
+    }
+}
@@ -0,0 +1,489 @@
+//! Lock-free cache for secrets read on every message.
+//!
+//! The chat pipeline reads the same bot token for nearly every outbound message. Keys leased with
+//! [`HotCache::lease`] are kept in an immutable map behind an `ArcSwap`, so a hit through
+//! [`HotCacheKeystore`] is a pointer load and two hash lookups, with no lock and no backend call.
+//! Nothing is cached until leased. Writes and deletes through the wrapper replace or drop the
+//! cached value, and [`HotCache::clear`] empties the cache, e.g. when the keystore locks. Cached
+//! bytes are held to a budget: over it, the least recently read values are evicted first. A value
+//! is zeroized once no map still holds it.
+
+use crate::cache::{entry_size, DEFAULT_CACHE_BUDGET};
+use crate::error::KeystoreError;
+use crate::metrics::KeystoreMetrics;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::resolution::{caches_bypassed, record_source, ReadSource};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use arc_swap::ArcSwap;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use zeroize::Zeroizing;
+
+struct HotValue {
+    value: Zeroizing<String>,
+    size: usize,
+    /// Tick of the last read, for eviction; updated in place so reads stay lock-free
+    last_used: AtomicU64,
+}
+
+/// service -> account -> value, nested so lookups need no allocation
+type HotMap = HashMap<String, HashMap<String, Arc<HotValue>>>;
+
+fn bytes(values: &HotMap) -> usize {
+    values
+        .values()
+        .flat_map(HashMap::values)
+        .map(|value| value.size)
+        .sum()
+}
+
+/// Evicts the least recently read values until `values` fits `budget`; returns how many
+fn trim(values: &mut HotMap, budget: usize) -> usize {
+    let mut total = bytes(values);
+    let mut evicted = 0;
+    while total > budget {
+        let Some((service, account, size)) = values
+            .iter()
+            .flat_map(|(service, accounts)| {
+                accounts
+                    .iter()
+                    .map(move |(account, value)| (service, account, value))
+            })
+            .min_by_key(|(_, _, value)| value.last_used.load(Ordering::Relaxed))
+            .map(|(service, account, value)| (service.clone(), account.clone(), value.size))
+        else {
+            break;
+        };
+        if let Some(accounts) = values.get_mut(&service) {
+            accounts.remove(&account);
+            if accounts.is_empty() {
+                values.remove(&service);
+            }
+        }
+        total -= size;
+        evicted += 1;
+    }
+    evicted
+}
+
+fn key(service: &str, account: &str) -> KeystoreKey {
+    KeystoreKey {
+        service: service.to_string(),
+        account: account.to_string(),
+    }
+}
+
+pub struct HotCache {
+    values: ArcSwap<HotMap>,
+    /// Also held across filling and invalidating, so a slow fill cannot put back a value a
+    /// concurrent write replaced
+    leases: Mutex<HashSet<KeystoreKey>>,
+    clock: AtomicU64,
+    budget: AtomicUsize,
+    metrics: OnceLock<Arc<KeystoreMetrics>>,
+}
+
+impl Default for HotCache {
+    fn default() -> Self {
+        Self {
+            values: ArcSwap::default(),
+            leases: Mutex::new(HashSet::new()),
+            clock: AtomicU64::new(0),
+            budget: AtomicUsize::new(DEFAULT_CACHE_BUDGET),
+            metrics: OnceLock::new(),
+        }
+    }
+}
+
+impl HotCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports usage and evictions into `metrics`; only the first call has an effect
+    pub fn set_metrics(&self, metrics: Arc<KeystoreMetrics>) {
+        let _ = self.metrics.set(metrics);
+    }
+
+    fn report(&self, evicted: usize) {
+        if let Some(metrics) = self.metrics.get() {
+            let values = self.values.load();
+            let entries = values.values().map(HashMap::len).sum();
+            metrics.record_cache_usage("hot", bytes(&values), entries, evicted);
+        }
+    }
+
+    /// Caps the bytes of cached values, evicting the least recently read first
+    pub fn set_budget(&self, budget: usize) {
+        let _leases = self.leases.lock().unwrap_or_else(|e| e.into_inner());
+        self.budget.store(budget, Ordering::Relaxed);
+        let mut evicted = 0;
+        self.values.rcu(|values| {
+            let mut values = HotMap::clone(values);
+            evicted = trim(&mut values, budget);
+            values
+        });
+        self.report(evicted);
+    }
+
+    /// Bytes of cached values
+    pub fn bytes(&self) -> usize {
+        bytes(&self.values.load())
+    }
+
+    fn leases(&self) -> Result<MutexGuard<'_, HashSet<KeystoreKey>>, KeystoreError> {
+        self.leases
+            .lock()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))
+    }
+
+    /// Caches `service`/`account` from its next read on
+    pub fn lease(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.leases()?.insert(key(service, account));
+        Ok(())
+    }
+
+    /// Stops caching `service`/`account` and drops its cached value
+    pub fn release(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let mut leases = self.leases()?;
+        leases.remove(&key(service, account));
+        self.remove(service, account);
+        Ok(())
+    }
+
+    pub fn get(&self, service: &str, account: &str) -> Option<Zeroizing<String>> {
+        let values = self.values.load();
+        let cached = values.get(service)?.get(account)?;
+        cached.last_used.store(
+            self.clock.fetch_add(1, Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        Some(cached.value.clone())
+    }
+
+    /// Drops every cached value; leases stay, so values are cached again on their next read
+    pub fn clear(&self) {
+        let _leases = self.leases.lock().unwrap_or_else(|e| e.into_inner());
+        self.clear_values();
+    }
+
+    fn clear_values(&self) {
+        self.values.store(Arc::new(HotMap::new()));
+        self.report(0);
+    }
+
+    /// Number of cached values
+    pub fn len(&self) -> usize {
+        self.values.load().values().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn put(&self, service: &str, account: &str, value: &str) {
+        let budget = self.budget.load(Ordering::Relaxed);
+        let size = entry_size(service, account, value);
+        if size > budget {
+            self.remove(service, account);
+            return;
+        }
+        let value = Arc::new(HotValue {
+            value: Zeroizing::new(value.to_string()),
+            size,
+            last_used: AtomicU64::new(self.clock.fetch_add(1, Ordering::Relaxed)),
+        });
+        let mut evicted = 0;
+        self.values.rcu(|values| {
+            let mut values = HotMap::clone(values);
+            values
+                .entry(service.to_string())
+                .or_default()
+                .insert(account.to_string(), Arc::clone(&value));
+            evicted = trim(&mut values, budget);
+            values
+        });
+        self.report(evicted);
+    }
+
+    fn retain(&self, keep: impl Fn(&str, &str) -> bool) {
+        self.values.rcu(|values| {
+            let mut values = HotMap::clone(values);
+            for (service, accounts) in values.iter_mut() {
+                accounts.retain(|account, _| keep(service, account));
+            }
+            values.retain(|_, accounts| !accounts.is_empty());
+            values
+        });
+        self.report(0);
+    }
+
+    fn remove(&self, service: &str, account: &str) {
+        if self
+            .values
+            .load()
+            .get(service)
+            .is_some_and(|accounts| accounts.contains_key(account))
+        {
+            self.retain(|s, a| s != service || a != account);
+        }
+    }
+}
+
+/// Serves leased keys from a [`HotCache`] and keeps it current with writes made through it
+pub struct HotCacheKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    cache: Arc<HotCache>,
+}
+
+impl HotCacheKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>, cache: Arc<HotCache>) -> Self {
+        Self { inner, cache }
+    }
+}
+
+impl KeystoreOperations for HotCacheKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        let leases = self.cache.leases()?;
+        let result = self.inner.set_password(entry);
+        if result.is_ok() && leases.contains(&key(&entry.service, &entry.account)) {
+            self.cache.put(&entry.service, &entry.account, &entry.value);
+        } else {
+            self.cache.remove(&entry.service, &entry.account);
+        }
+        result
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        if !caches_bypassed() {
+            if let Some(value) = self.cache.get(service, account) {
+                record_source(ReadSource::HotCache);
+                return Ok(value.to_string());
+            }
+        }
+        let leases = self.cache.leases()?;
+        if !leases.contains(&key(service, account)) {
+            drop(leases);
+            return self.inner.get_password(service, account);
+        }
+        let value = self.inner.get_password(service, account)?;
+        self.cache.put(service, account, &value);
+        Ok(value)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let _leases = self.cache.leases()?;
+        let result = self.inner.delete_password(service, account);
+        self.cache.remove(service, account);
+        result
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.list_credentials(service)
+    }
+
+    /// Serves cached values and reads the rest from the backend in one batch
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        let bypass = caches_bypassed();
+        let mut values: Vec<Option<String>> = keys
+            .iter()
+            .map(|key| {
+                if bypass {
+                    return None;
+                }
+                self.cache
+                    .get(&key.service, &key.account)
+                    .map(|value| value.to_string())
+            })
+            .collect();
+        let misses: Vec<usize> = (0..keys.len()).filter(|&i| values[i].is_none()).collect();
+        if misses.is_empty() {
+            return Ok(values);
+        }
+
+        let missed: Vec<KeystoreKey> = misses.iter().map(|&index| keys[index].clone()).collect();
+        let leases = self.cache.leases()?;
+        if !missed.iter().any(|key| leases.contains(key)) {
+            drop(leases);
+            for (index, value) in misses.into_iter().zip(self.inner.get_many(&missed)?) {
+                values[index] = value;
+            }
+            return Ok(values);
+        }
+        // Held across the read, as for a single leased key, so a release can't be undone
+        for (index, value) in misses.into_iter().zip(self.inner.get_many(&missed)?) {
+            let key = &keys[index];
+            if let Some(value) = &value {
+                if leases.contains(key) {
+                    self.cache.put(&key.service, &key.account, value);
+                }
+            }
+            values[index] = value;
+        }
+        Ok(values)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        let leases = self.cache.leases()?;
+        let result = self.inner.set_many(entries);
+        for entry in entries {
+            if result.is_ok() && leases.contains(&key(&entry.service, &entry.account)) {
+                self.cache.put(&entry.service, &entry.account, &entry.value);
+            } else {
+                self.cache.remove(&entry.service, &entry.account);
+            }
+        }
+        result
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let _leases = self.cache.leases()?;
+        let result = self.inner.delete_many(keys);
+        for key in keys {
+            self.cache.remove(&key.service, &key.account);
+        }
+        result
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let _leases = self.cache.leases()?;
+        let result = self.inner.delete_service(service);
+        self.cache.retain(|s, _| s != service);
+        result
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let _leases = self.cache.leases()?;
+        let result = self.inner.wipe_all();
+        self.cache.clear_values();
+        result
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        let _leases = self.cache.leases()?;
+        let result = self.inner.swap(a, b);
+        for key in [a, b] {
+            self.cache.remove(&key.service, &key.account);
+        }
+        result
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        let leases = self.cache.leases()?;
+        let result = self.inner.get_or_create(service, account, length);
+        match &result {
+            Ok(value) if leases.contains(&key(service, account)) => {
+                self.cache.put(service, account, value)
+            }
+            _ => self.cache.remove(service, account),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    fn entry(value: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: "twitch".to_string(),
+            account: "bot".to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_leased_keys_are_served_from_the_cache_and_invalidated() {
+        let backend = Arc::new(MemoryKeystore::default());
+        let cache = Arc::new(HotCache::new());
+        let keystore = HotCacheKeystore::new(Box::new(Arc::clone(&backend)), Arc::clone(&cache));
+        keystore.set_password(&entry("one")).unwrap();
+
+        // Not leased, so not cached
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "one");
+        assert!(cache.is_empty());
+
+        cache.lease("twitch", "bot").unwrap();
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "one");
+        assert_eq!(cache.len(), 1);
+        // Served without reaching the backend
+        backend.entries.lock().unwrap().clear();
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "one");
+
+        keystore.set_password(&entry("two")).unwrap();
+        assert_eq!(cache.get("twitch", "bot").unwrap().as_str(), "two");
+        keystore.delete_password("twitch", "bot").unwrap();
+        assert!(cache.is_empty());
+        assert!(keystore.get_password("twitch", "bot").is_err());
+
+        keystore.set_password(&entry("three")).unwrap();
+        cache.clear();
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "three");
+        cache.release("twitch", "bot").unwrap();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_least_recently_read_values_are_evicted_over_budget() {
+        let cache = Arc::new(HotCache::new());
+        let keystore =
+            HotCacheKeystore::new(Box::new(MemoryKeystore::default()), Arc::clone(&cache));
+        // Each entry is 6 + 3 + 5 = 14 bytes
+        cache.set_budget(28);
+        for account in ["one", "two", "six"] {
+            cache.lease("twitch", account).unwrap();
+            keystore
+                .set_password(&KeystoreEntry {
+                    service: "twitch".to_string(),
+                    account: account.to_string(),
+                    value: "token".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+            if account == "two" {
+                // Read "one" so "two" is the least recently used
+                keystore.get_password("twitch", "one").unwrap();
+            }
+        }
+        assert_eq!(cache.bytes(), 28);
+        assert!(cache.get("twitch", "two").is_none());
+        assert!(cache.get("twitch", "one").is_some());
+        // Evicted values are read from the backend and cached again
+        assert_eq!(keystore.get_password("twitch", "two").unwrap(), "token");
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,180 @@
+//! Previewing and applying imports.
+//!
+//! Every import source decodes to a list of entries and goes through [`plan_import`] before
+//! anything is written, so the UI can show what an import will do: which entries are new, which
+//! would overwrite a different stored value, which are already identical, and which conflict.
+//! Incoming and stored values are compared by SHA-256 digest and never appear in the plan.
+//! [`apply_import`] writes the plan.
+
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+use crate::{KeystoreEntry, KeystoreKey};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use zeroize::Zeroizing;
+
+/// An entry the import cannot apply as-is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportConflict {
+    pub key: KeystoreKey,
+    pub reason: String,
+}
+
+/// What an import would do, each list sorted by service, then account
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportDiff {
+    /// Not stored yet
+    pub added: Vec<KeystoreKey>,
+    /// Stored with a different value
+    pub changed: Vec<KeystoreKey>,
+    /// Stored with the same value
+    pub unchanged: Vec<KeystoreKey>,
+    /// Listed twice with different values, or stored but unreadable (e.g. while locked)
+    pub conflicts: Vec<ImportConflict>,
+}
+
+impl ImportDiff {
+    /// Whether applying the import would write anything
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn digest(value: &str) -> [u8; 32] {
+    Sha256::digest(value.as_bytes()).into()
+}
+
+/// Compares `entries` with what `backend` holds without writing anything
+pub fn plan_import(
+    backend: &dyn KeystoreOperations,
+    entries: &[KeystoreEntry],
+) -> Result<ImportDiff, KeystoreError> {
+    // None marks a key listed more than once with different values
+    let mut incoming: BTreeMap<KeystoreKey, Option<[u8; 32]>> = BTreeMap::new();
+    for entry in entries {
+        let key = KeystoreKey {
+            service: entry.service.clone(),
+            account: entry.account.clone(),
+        };
+        let hash = digest(&entry.value);
+        match incoming.entry(key) {
+            Entry::Vacant(slot) => {
+                slot.insert(Some(hash));
+            }
+            Entry::Occupied(mut slot) => {
+                if *slot.get() != Some(hash) {
+                    slot.insert(None);
+                }
+            }
+        }
+    }
+
+    let mut diff = ImportDiff::default();
+    for (key, hash) in incoming {
+        let Some(hash) = hash else {
+            diff.conflicts.push(ImportConflict {
+                key,
+                reason: "Listed more than once with different values".to_string(),
+            });
+            continue;
+        };
+        match backend.get_password(&key.service, &key.account) {
+            Ok(current) => {
+                if digest(&Zeroizing::new(current)) == hash {
+                    diff.unchanged.push(key)
+                } else {
+                    diff.changed.push(key)
+                }
+            }
+            Err(KeystoreError::KeyNotFound(_)) => diff.added.push(key),
+            Err(e) => diff.conflicts.push(ImportConflict {
+                key,
+                reason: format!("Stored value could not be read: {}", e),
+            }),
+        }
+    }
+    Ok(diff)
+}
+
+/// Plans the import, then writes its added entries, and its changed ones when `overwrite` is set.
+/// Conflicts are skipped. Returns the plan that was applied.
+pub fn apply_import(
+    backend: &dyn KeystoreOperations,
+    entries: &[KeystoreEntry],
+    overwrite: bool,
+) -> Result<ImportDiff, KeystoreError> {
+    let diff = plan_import(backend, entries)?;
+    let writes: BTreeSet<&KeystoreKey> = diff
+        .added
+        .iter()
+        .chain(diff.changed.iter().filter(|_| overwrite))
+        .collect();
+    for entry in entries {
+        let key = KeystoreKey {
+            service: entry.service.clone(),
+            account: entry.account.clone(),
+        };
+        if writes.contains(&key) {
+            backend.set_password(entry)?;
+        }
+    }
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    fn entry(account: &str, value: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn key(account: &str) -> KeystoreKey {
+        KeystoreKey {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_plan_classifies_entries_and_apply_respects_overwrite() {
+        let backend = MemoryKeystore::default();
+        backend.set_password(&entry("same", "one")).unwrap();
+        backend.set_password(&entry("changed", "old")).unwrap();
+        let import = [
+            entry("same", "one"),
+            entry("changed", "new"),
+            entry("added", "fresh"),
+            entry("twice", "a"),
+            entry("twice", "b"),
+        ];
+
+        let diff = plan_import(&backend, &import).unwrap();
+        assert_eq!(diff.added, vec![key("added")]);
+        assert_eq!(diff.changed, vec![key("changed")]);
+        assert_eq!(diff.unchanged, vec![key("same")]);
+        assert_eq!(diff.conflicts.len(), 1);
+        assert_eq!(diff.conflicts[0].key, key("twice"));
+        assert!(!serde_json::to_string(&diff).unwrap().contains("fresh"));
+        // Planning writes nothing
+        assert!(backend.get_password("twitch", "added").is_err());
+
+        assert_eq!(apply_import(&backend, &import, false).unwrap(), diff);
+        assert_eq!(backend.get_password("twitch", "added").unwrap(), "fresh");
+        assert_eq!(backend.get_password("twitch", "changed").unwrap(), "old");
+        assert!(backend.get_password("twitch", "twice").is_err());
+
+        apply_import(&backend, &import, true).unwrap();
+        assert_eq!(backend.get_password("twitch", "changed").unwrap(), "new");
+        assert!(plan_import(&backend, &import[..3]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,318 @@
+//! Self-audit of everything the keystore holds, with a plan for what can be fixed.
+//!
+//! [`verify_all`] walks every entry and checks that it still decrypts, that blob envelopes (notes,
+//! certificates, indexes) pass their length and SHA-256 check, that entries of the application's
+//! own kinds decode into their schema, and that no chunk or alias is left pointing at nothing.
+//! Each problem is an [`IntegrityIssue`]. Only orphans, as found by [`crate::orphans`], have a
+//! safe fix; [`IntegrityReport::repair_plan`] collects them for [`repair`]. Damaged values are
+//! reported and left alone, as a backup or another device may still hold them.
+
+use crate::alias::ALIAS_SERVICE;
+use crate::blob::BlobStore;
+use crate::certs::{CertificateInfo, StoredCertificate, CERTIFICATES_SERVICE};
+use crate::error::KeystoreError;
+use crate::inventory::EntryKind;
+use crate::kdf::{KdfParams, KDF_SERVICE};
+use crate::notes::SecureNoteInfo;
+use crate::orphans::{find_orphans, remove_orphans, Orphan, OrphanKind};
+use crate::platform::KeystoreOperations;
+use crate::protect::PROTECTED_SERVICE;
+use crate::provenance::{EntryProvenance, PROVENANCE_SERVICE};
+use crate::KeystoreKey;
+
+use serde::de::IgnoredAny;
+use std::collections::BTreeSet;
+use zeroize::Zeroizing;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The backend failed to return the value
+    Unreadable,
+    /// Entries the backend holds but can no longer decrypt
+    Undecryptable,
+    /// A blob whose chunks are missing or fail the integrity check
+    Corrupt,
+    /// An entry of one of the application's kinds that doesn't decode into its schema
+    InvalidSchema,
+    /// A chunk no blob refers to
+    OrphanedChunk,
+    /// An alias whose target is gone
+    DanglingAlias,
+    /// An index row, protection mark or provenance record of a missing entry
+    OrphanedRecord,
+}
+
+impl IssueKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IssueKind::Unreadable => "unreadable",
+            IssueKind::Undecryptable => "undecryptable",
+            IssueKind::Corrupt => "corrupt",
+            IssueKind::InvalidSchema => "invalid-schema",
+            IssueKind::OrphanedChunk => "orphaned-chunk",
+            IssueKind::DanglingAlias => "dangling-alias",
+            IssueKind::OrphanedRecord => "orphaned-record",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityIssue {
+    /// The entry concerned; `None` for issues of the backend as a whole
+    pub key: Option<KeystoreKey>,
+    pub kind: IssueKind,
+    pub detail: String,
+    /// The orphan [`repair`] would remove to fix the issue
+    pub repair: Option<Orphan>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Entries looked at
+    pub checked: usize,
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// The repairs of every issue that has one
+    pub fn repair_plan(&self) -> Vec<Orphan> {
+        self.issues
+            .iter()
+            .filter_map(|issue| issue.repair.clone())
+            .collect()
+    }
+}
+
+fn entry_issue(key: &KeystoreKey, kind: IssueKind, detail: String) -> IntegrityIssue {
+    IntegrityIssue {
+        key: Some(key.clone()),
+        kind,
+        detail,
+        repair: None,
+    }
+}
+
+/// Errors that stop the audit instead of describing an entry: the keystore is locked, refuses
+/// access, wants to prompt, or is too busy or slow to answer, so nothing could be checked
+fn is_fatal(e: &KeystoreError) -> bool {
+    matches!(
+        e,
+        KeystoreError::AccessDenied(_)
+            | KeystoreError::KeyringLocked(_)
+            | KeystoreError::InteractionRequired(_)
+            | KeystoreError::Timeout(_)
+            | KeystoreError::Transient(_)
+            | KeystoreError::Busy(_)
+            | KeystoreError::Overloaded { .. }
+    )
+}
+
+/// Kinds stored as blob envelopes, whose length and digest can be checked
+fn is_blob(kind: EntryKind) -> bool {
+    matches!(
+        kind,
+        EntryKind::Note | EntryKind::Certificate | EntryKind::Index
+    )
+}
+
+/// Decodes the application's own entries into their schema; `Ok` for everything else
+fn check_schema(
+    backend: &dyn KeystoreOperations,
+    blobs: &BlobStore,
+    key: &KeystoreKey,
+) -> Result<(), KeystoreError> {
+    let (service, account) = (key.service.as_str(), key.account.as_str());
+    match service {
+        ALIAS_SERVICE => blobs
+            .get_structured::<Vec<(KeystoreKey, KeystoreKey)>>(service, account)
+            .map(drop),
+        PROTECTED_SERVICE => blobs
+            .get_structured::<BTreeSet<KeystoreKey>>(service, account)
+            .map(drop),
+        PROVENANCE_SERVICE => blobs
+            .get_structured::<Vec<EntryProvenance>>(service, account)
+            .map(drop),
+        crate::notes::INDEX_SERVICE => blobs
+            .get_structured::<Vec<SecureNoteInfo>>(service, account)
+            .map(drop),
+        crate::certs::INDEX_SERVICE => blobs
+            .get_structured::<Vec<CertificateInfo>>(service, account)
+            .map(drop),
+        CERTIFICATES_SERVICE => blobs
+            .get_structured::<StoredCertificate>(service, account)
+            .map(drop),
+        KDF_SERVICE => KdfParams::load(backend)?.argon2().map(drop),
+        // Other indexes have no schema worth spelling out here; they must still decode
+        _ if EntryKind::classify(service, account) == EntryKind::Index => blobs
+            .get_structured::<IgnoredAny>(service, account)
+            .map(drop),
+        _ => Ok(()),
+    }
+}
+
+/// Checks every entry in `backend`. Fails only if the keystore can't be audited at all, e.g. while
+/// locked; problems with entries are reported as issues.
+pub fn verify_all(backend: &dyn KeystoreOperations) -> Result<IntegrityReport, KeystoreError> {
+    let mut report = IntegrityReport::default();
+    if let Some(count) = backend.backend_info().details.get("undecryptable_entries") {
+        report.issues.push(IntegrityIssue {
+            key: None,
+            kind: IssueKind::Undecryptable,
+            detail: format!(
+                "{} entries in the keystore file no longer decrypt under this device's key",
+                count
+            ),
+            repair: None,
+        });
+    }
+
+    let mut keys = backend.list_keys()?;
+    keys.sort();
+    let blobs = BlobStore::new(backend);
+    for key in &keys {
+        report.checked += 1;
+
+        let kind = EntryKind::classify(&key.service, &key.account);
+        // Read as part of their blob; orphaned ones are found below
+        if kind == EntryKind::Chunk {
+            continue;
+        }
+
+        match backend.get_password(&key.service, &key.account) {
+            Ok(value) => drop(Zeroizing::new(value)),
+            // Deleted since it was listed
+            Err(KeystoreError::KeyNotFound(_)) => continue,
+            Err(e) if is_fatal(&e) => return Err(e),
+            Err(e) => {
+                report
+                    .issues
+                    .push(entry_issue(key, IssueKind::Unreadable, e.to_string()));
+                continue;
+            }
+        }
+
+        // The envelope and its chunks first, then what they decode into
+        let damaged = if is_blob(kind) {
+            blobs
+                .get(&key.service, &key.account)
+                .map(|value| drop(Zeroizing::new(value)))
+                .err()
+                .map(|e| (IssueKind::Corrupt, e))
+        } else {
+            None
+        };
+        match damaged.or_else(|| {
+            check_schema(backend, &blobs, key)
+                .err()
+                .map(|e| (IssueKind::InvalidSchema, e))
+        }) {
+            // Deleted meanwhile, or written by a newer build that knows its layout better
+            None
+            | Some((_, KeystoreError::KeyNotFound(_)))
+            | Some((_, KeystoreError::FormatTooNew(_))) => {}
+            Some((_, e)) if is_fatal(&e) => return Err(e),
+            Some((issue, e)) => report.issues.push(entry_issue(key, issue, e.to_string())),
+        }
+    }
+
+    match find_orphans(backend) {
+        Ok(orphans) => {
+            for orphan in orphans {
+                let kind = match orphan.kind {
+                    OrphanKind::Chunk => IssueKind::OrphanedChunk,
+                    OrphanKind::Alias => IssueKind::DanglingAlias,
+                    _ => IssueKind::OrphanedRecord,
+                };
+                report.issues.push(IntegrityIssue {
+                    key: Some(orphan.key.clone()),
+                    kind,
+                    detail: orphan.describe(),
+                    repair: Some(orphan),
+                });
+            }
+        }
+        Err(e) if is_fatal(&e) => return Err(e),
+        // A table that doesn't decode was reported above
+        Err(_) => {}
+    }
+
+    Ok(report)
+}
+
+/// Applies `plan`, typically [`IntegrityReport::repair_plan`], with
+/// [`crate::orphans::remove_orphans`], and returns how many repairs changed something
+pub fn repair(backend: &dyn KeystoreOperations, plan: &[Orphan]) -> Result<usize, KeystoreError> {
+    remove_orphans(backend, plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alias::AliasKeystore;
+    use crate::notes::SecureNotes;
+    use crate::testing::MemoryKeystore;
+    use crate::KeystoreEntry;
+    use std::sync::Arc;
+
+    fn set(backend: &dyn KeystoreOperations, service: &str, account: &str, value: &str) {
+        backend
+            .set_password(&KeystoreEntry {
+                service: service.to_string(),
+                account: account.to_string(),
+                value: value.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_verify_all_finds_orphans_and_damage_and_repairs_orphans() {
+        let backend = Arc::new(MemoryKeystore::default());
+        set(backend.as_ref(), "twitch", "bot", "token");
+        set(backend.as_ref(), "twitch", "old", "token");
+        let aliases = AliasKeystore::new(Box::new(Arc::clone(&backend)));
+        let key = |service: &str, account: &str| KeystoreKey {
+            service: service.to_string(),
+            account: account.to_string(),
+        };
+        aliases
+            .add_alias(&key("twitch", "renamed"), &key("twitch", "old"))
+            .unwrap();
+        SecureNotes::new(backend.as_ref())
+            .save("run of show", "hunter2")
+            .unwrap();
+        assert!(verify_all(backend.as_ref()).unwrap().is_clean());
+
+        backend.delete_password("twitch", "old").unwrap();
+        set(backend.as_ref(), "obs", "scene:chunk:3", "left over");
+        set(backend.as_ref(), KDF_SERVICE, "params", "{not json");
+        set(
+            backend.as_ref(),
+            crate::notes::NOTES_SERVICE,
+            "run of show",
+            r#"{"version":1,"data":"hunter3","len":7,"sha256":"00"}"#,
+        );
+
+        let report = verify_all(backend.as_ref()).unwrap();
+        let kinds: Vec<IssueKind> = report.issues.iter().map(|issue| issue.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                IssueKind::InvalidSchema,
+                IssueKind::Corrupt,
+                IssueKind::OrphanedChunk,
+                IssueKind::DanglingAlias,
+            ]
+        );
+        assert_eq!(report.repair_plan().len(), 2);
+
+        assert_eq!(repair(backend.as_ref(), &report.repair_plan()).unwrap(), 2);
+        aliases.reload();
+        assert!(aliases.aliases().unwrap().is_empty());
+        assert_eq!(verify_all(backend.as_ref()).unwrap().issues.len(), 2);
+    }
+}
@@ -0,0 +1,287 @@
+//! Values-free inventory of everything the keystore holds.
+//!
+//! [`inventory`] enumerates the backend through [`KeystoreOperations::list_keys`] and describes each
+//! entry by service, account, kind, stored size and backend, plus creation and update times where
+//! the application records them (secure notes), and the origin and component of the last write
+//! where [`crate::provenance`] recorded one. Values are read only to measure them and are dropped
+//! immediately. The report exports as JSON or CSV for privacy requests and user audits.
+
+use crate::alias::ALIAS_SERVICE;
+use crate::certs::CERTIFICATES_SERVICE;
+use crate::channel::CHANNEL_SERVICE;
+use crate::conflicts::CONFLICTS_SERVICE;
+use crate::error::KeystoreError;
+use crate::kdf::KDF_SERVICE;
+use crate::keys::KEYS_SERVICE;
+use crate::lock::LOCK_SERVICE;
+use crate::notes::{SecureNotes, NOTES_SERVICE};
+use crate::platform::KeystoreOperations;
+use crate::protect::PROTECTED_SERVICE;
+use crate::provenance::{load_provenance, Origin};
+use crate::reserved::RESERVED_PREFIX;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EntryKind {
+    /// Anything stored by a caller, such as platform tokens
+    Credential,
+    Note,
+    Certificate,
+    MasterKey,
+    ChannelKey,
+    LockPassphrase,
+    /// Argon2 parameters tuned for this machine
+    KdfParams,
+    /// A version displaced by a concurrent write to a synced keystore file
+    SyncConflict,
+    /// Listing metadata and lookup tables, such as the notes index and aliases
+    Index,
+    /// Part of a value split across entries by `blob::BlobStore`
+    Chunk,
+}
+
+impl EntryKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::Credential => "credential",
+            EntryKind::Note => "note",
+            EntryKind::Certificate => "certificate",
+            EntryKind::MasterKey => "master-key",
+            EntryKind::ChannelKey => "channel-key",
+            EntryKind::LockPassphrase => "lock-passphrase",
+            EntryKind::KdfParams => "kdf-params",
+            EntryKind::SyncConflict => "sync-conflict",
+            EntryKind::Index => "index",
+            EntryKind::Chunk => "chunk",
+        }
+    }
+
+    pub fn classify(service: &str, account: &str) -> Self {
+        if account.contains(":chunk:") {
+            return EntryKind::Chunk;
+        }
+        match service {
+            NOTES_SERVICE => EntryKind::Note,
+            CERTIFICATES_SERVICE => EntryKind::Certificate,
+            KEYS_SERVICE => EntryKind::MasterKey,
+            CHANNEL_SERVICE => EntryKind::ChannelKey,
+            LOCK_SERVICE => EntryKind::LockPassphrase,
+            KDF_SERVICE => EntryKind::KdfParams,
+            CONFLICTS_SERVICE => EntryKind::SyncConflict,
+            ALIAS_SERVICE | PROTECTED_SERVICE => EntryKind::Index,
+            s if s.starts_with(RESERVED_PREFIX) && s.ends_with("-index") => EntryKind::Index,
+            _ => EntryKind::Credential,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryItem {
+    pub service: String,
+    pub account: String,
+    pub kind: EntryKind,
+    /// Bytes stored in the entry; `None` when it could not be read, e.g. while locked
+    pub size: Option<usize>,
+    /// Milliseconds since the Unix epoch; only known for secure notes
+    pub created_at: Option<u64>,
+    pub updated_at: Option<u64>,
+    pub backend: String,
+    /// How the entry was last written; `None` if that wasn't recorded
+    #[serde(default)]
+    pub origin: Option<Origin>,
+    #[serde(default)]
+    pub component: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inventory {
+    /// Milliseconds since the Unix epoch
+    pub generated_at: u64,
+    pub backend: String,
+    /// Sorted by service, then account
+    pub items: Vec<InventoryItem>,
+}
+
+const CSV_HEADER: &str = "service,account,kind,size,created_at,updated_at,backend,origin,component";
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+impl Inventory {
+    pub fn to_json(&self) -> Result<String, KeystoreError> {
+        serde_json::to_string_pretty(self).map_err(|e| KeystoreError::Serialization(e.to_string()))
+    }
+
+    /// One row per entry under a header line; unknown sizes and timestamps are left empty
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for item in &self.items {
+            let row = [
+                csv_field(&item.service),
+                csv_field(&item.account),
+                item.kind.as_str().to_string(),
+                optional(item.size),
+                optional(item.created_at),
+                optional(item.updated_at),
+                csv_field(&item.backend),
+                optional(item.origin.map(Origin::as_str)),
+                csv_field(item.component.as_deref().unwrap_or_default()),
+            ];
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Keeps the entries last written with `origin`
+    pub fn filter_origin(mut self, origin: Origin) -> Self {
+        self.items.retain(|item| item.origin == Some(origin));
+        self
+    }
+}
+
+pub fn inventory(backend: &dyn KeystoreOperations) -> Result<Inventory, KeystoreError> {
+    let backend_name = backend.backend_info().name;
+    let mut keys = backend.list_keys()?;
+    keys.sort();
+
+    // Note timestamps live in the notes index; a missing or unreadable index just leaves them out
+    let note_times: HashMap<String, (u64, u64)> = SecureNotes::new(backend)
+        .list()
+        .map(|notes| {
+            notes
+                .into_iter()
+                .map(|note| (note.title, (note.created_at, note.updated_at)))
+                .collect()
+        })
+        .unwrap_or_default();
+    // Likewise for provenance records
+    let mut origins: BTreeMap<(String, String), (Origin, Option<String>)> =
+        load_provenance(backend)
+            .map(|records| {
+                records
+                    .into_iter()
+                    .map(|r| ((r.service, r.account), (r.origin, r.component)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+    let items = keys
+        .into_iter()
+        .map(|key| {
+            let kind = EntryKind::classify(&key.service, &key.account);
+            let size = backend
+                .get_password(&key.service, &key.account)
+                .map(|value| Zeroizing::new(value).len())
+                .ok();
+            let times = match kind {
+                EntryKind::Note => note_times.get(&key.account).copied(),
+                _ => None,
+            };
+            let (origin, component) = origins
+                .remove(&(key.service.clone(), key.account.clone()))
+                .unzip();
+            InventoryItem {
+                kind,
+                size,
+                created_at: times.map(|(created, _)| created),
+                updated_at: times.map(|(_, updated)| updated),
+                backend: backend_name.clone(),
+                origin,
+                component: component.flatten(),
+                service: key.service,
+                account: key.account,
+            }
+        })
+        .collect();
+
+    Ok(Inventory {
+        generated_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        backend: backend_name,
+        items,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provenance::{with_provenance, ProvenanceContext, ProvenanceKeystore};
+    use crate::testing::MemoryKeystore;
+    use crate::KeystoreEntry;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_inventory_describes_entries_without_values() {
+        let backend = Arc::new(MemoryKeystore::default());
+        with_provenance(
+            ProvenanceContext {
+                origin: Origin::Cli,
+                component: None,
+            },
+            || {
+                ProvenanceKeystore::new(Box::new(Arc::clone(&backend)))
+                    .set_password(&KeystoreEntry {
+                        service: "twitch".to_string(),
+                        account: "bot, oauth".to_string(),
+                        value: "s3cret-token".to_string(),
+                        ..Default::default()
+                    })
+                    .unwrap()
+            },
+        );
+        let note = SecureNotes::new(&backend)
+            .save("run of show", "stream key: hunter2")
+            .unwrap();
+
+        let report = inventory(&backend).unwrap();
+        assert_eq!(report.backend, "memory");
+        let kinds: Vec<(&str, EntryKind)> = report
+            .items
+            .iter()
+            .map(|item| (item.account.as_str(), item.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("run of show", EntryKind::Note),
+                ("index", EntryKind::Index),
+                ("index", EntryKind::Index),
+                ("bot, oauth", EntryKind::Credential),
+            ]
+        );
+        assert_eq!(report.items[0].created_at, Some(note.created_at));
+        assert_eq!(report.items[3].size, Some("s3cret-token".len()));
+        assert_eq!(report.items[3].created_at, None);
+        assert_eq!(report.items[3].origin, Some(Origin::Cli));
+        assert_eq!(report.items[0].origin, None);
+
+        let csv = report.to_csv();
+        assert!(csv.starts_with(CSV_HEADER));
+        assert!(csv.contains("twitch,\"bot, oauth\",credential,12,,,memory,cli,\n"));
+        let json = report.to_json().unwrap();
+        assert_eq!(serde_json::from_str::<Inventory>(&json).unwrap(), report);
+        assert_eq!(report.clone().filter_origin(Origin::Cli).items.len(), 1);
+        for dump in [csv, json] {
+            assert!(!dump.contains("s3cret-token"));
+            assert!(!dump.contains("hunter2"));
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Argon2id parameters tuned to the machine.
+//!
+//! The same memory and pass count that takes 200 ms on a desktop can take seconds on a small
+//! streaming PC. [`tune`] benchmarks Argon2id here and picks the largest memory cost, then the
+//! most passes, that fit a target time, never going below the Argon2 crate's defaults (19 MiB,
+//! two passes). [`KdfParams::store`] saves the result in the keystore, where the lock passphrase
+//! and backups pick it up, and each records the parameters it was derived with in its own header
+//! so a later re-tune never breaks unlocking or restoring. Tuning again replaces the stored
+//! parameters; the lock verifier is rehashed with them on the next successful unlock.
+
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+use crate::KeystoreEntry;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Service under which the tuned parameters are stored
+pub const KDF_SERVICE: &str = "streaming-enhancement:kdf";
+
+const PARAMS_ACCOUNT: &str = "params";
+
+/// Tuning never goes past 1 GiB, and headers asking for more are rejected
+pub const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+
+pub const MAX_ITERATIONS: u32 = 64;
+
+/// Size of [`KdfParams::to_bytes`]
+pub const PARAMS_SIZE: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl From<&Params> for KdfParams {
+    fn from(params: &Params) -> Self {
+        Self {
+            memory_kib: params.m_cost(),
+            iterations: params.t_cost(),
+            parallelism: params.p_cost(),
+        }
+    }
+}
+
+impl KdfParams {
+    /// An Argon2id instance with these parameters; refuses costs beyond the tuning limits, so a
+    /// crafted header can't make a read allocate gigabytes
+    pub fn argon2(&self) -> Result<Argon2<'static>, KeystoreError> {
+        if self.memory_kib > MAX_MEMORY_KIB || self.iterations > MAX_ITERATIONS {
+            return Err(KeystoreError::InvalidInput(format!(
+                "Argon2 parameters exceed the limits: {} KiB, {} passes",
+                self.memory_kib, self.iterations
+            )));
+        }
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| {
+                KeystoreError::InvalidInput(format!("Invalid Argon2 parameters: {}", e))
+            })?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+
+    /// Memory, passes and lanes as big-endian u32s, for binary headers
+    pub fn to_bytes(&self) -> [u8; PARAMS_SIZE] {
+        let mut bytes = [0u8; PARAMS_SIZE];
+        bytes[..4].copy_from_slice(&self.memory_kib.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.iterations.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.parallelism.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; PARAMS_SIZE]) -> Self {
+        let word =
+            |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        Self {
+            memory_kib: word(0),
+            iterations: word(4),
+            parallelism: word(8),
+        }
+    }
+
+    /// The tuned parameters stored in `backend`, or the defaults if it was never tuned
+    pub fn load(backend: &dyn KeystoreOperations) -> Result<Self, KeystoreError> {
+        match backend.get_password(KDF_SERVICE, PARAMS_ACCOUNT) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| {
+                KeystoreError::Serialization(format!("Invalid stored Argon2 parameters: {}", e))
+            }),
+            Err(KeystoreError::KeyNotFound(_)) => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn store(&self, backend: &dyn KeystoreOperations) -> Result<(), KeystoreError> {
+        self.argon2()?;
+        backend.set_password(&KeystoreEntry {
+            service: KDF_SERVICE.to_string(),
+            account: PARAMS_ACCOUNT.to_string(),
+            value: serde_json::to_string(self)
+                .map_err(|e| KeystoreError::Serialization(e.to_string()))?,
+            ..Default::default()
+        })
+    }
+}
+
+/// Time one derivation with `params`
+fn measure(params: &KdfParams) -> Result<Duration, KeystoreError> {
+    let argon2 = params.argon2()?;
+    let mut output = [0u8; 32];
+    let started = Instant::now();
+    argon2
+        .hash_password_into(b"benchmark", b"streaming-enhancement", &mut output)
+        .map_err(|e| KeystoreError::Platform(format!("Argon2 benchmark failed: {}", e)))?;
+    Ok(started.elapsed())
+}
+
+/// Benchmarks this machine and returns the strongest parameters that derive a key within
+/// `target`: memory doubles while a single pass fits, then passes are added to fill the rest.
+/// A machine too slow for `target` gets the defaults.
+pub fn tune(target: Duration) -> Result<KdfParams, KeystoreError> {
+    if target.is_zero() {
+        return Err(KeystoreError::InvalidInput(
+            "Target time must be positive".to_string(),
+        ));
+    }
+    let floor = KdfParams::default();
+    let mut params = KdfParams {
+        iterations: 1,
+        ..floor
+    };
+    let mut elapsed = measure(&params)?;
+    // Time grows about linearly with memory, so only try doubling when it should still fit
+    while params.memory_kib * 2 <= MAX_MEMORY_KIB && elapsed * 2 <= target {
+        let larger = KdfParams {
+            memory_kib: params.memory_kib * 2,
+            ..params
+        };
+        let larger_elapsed = measure(&larger)?;
+        if larger_elapsed > target {
+            break;
+        }
+        params = larger;
+        elapsed = larger_elapsed;
+    }
+    let passes = (target.as_secs_f64() / elapsed.as_secs_f64().max(f64::EPSILON)).floor();
+    params.iterations = (passes as u32).clamp(1, MAX_ITERATIONS);
+    if params.memory_kib == floor.memory_kib {
+        params.iterations = params.iterations.max(floor.iterations);
+    }
+    Ok(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    #[test]
+    fn test_tuned_parameters_round_trip_and_never_fall_below_defaults() {
+        let params = tune(Duration::from_millis(20)).unwrap();
+        assert!(params.memory_kib >= Params::DEFAULT_M_COST);
+        assert!(params.memory_kib <= MAX_MEMORY_KIB);
+        assert!(params.iterations >= 1);
+        assert_eq!(KdfParams::from_bytes(&params.to_bytes()), params);
+
+        let backend = MemoryKeystore::default();
+        assert_eq!(KdfParams::load(&backend).unwrap(), KdfParams::default());
+        params.store(&backend).unwrap();
+        assert_eq!(KdfParams::load(&backend).unwrap(), params);
+
+        let hostile = KdfParams {
+            memory_kib: u32::MAX,
+            ..params
+        };
+        assert!(matches!(
+            hostile.argon2(),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+    }
+}
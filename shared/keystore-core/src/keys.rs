@@ -0,0 +1,177 @@
+//! Master keys held in the keystore and HKDF-derived subkeys.
+//!
+//! A master key is 32 random bytes stored base64-encoded under the
+//! `streaming-enhancement:keys` service. Subsystems never read it directly; they ask for a key
+//! derived with HKDF-SHA256 and a purpose-specific `info` string, so compromising one derived key
+//! reveals nothing about the master or its siblings. Key material is returned in [`Zeroizing`]
+//! buffers that are wiped on drop.
+
+use crate::error::KeystoreError;
+use crate::generate::random_bytes;
+use crate::platform::KeystoreOperations;
+use crate::KeystoreEntry;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hkdf::Hkdf;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// Service under which master keys are stored, one account per key name
+pub const KEYS_SERVICE: &str = "streaming-enhancement:keys";
+
+/// Size of a generated master key in bytes
+pub const MASTER_KEY_SIZE: usize = 32;
+
+/// HKDF-SHA256 can expand to at most 255 hash blocks
+pub const MAX_DERIVED_KEY_SIZE: usize = 255 * 32;
+
+pub struct MasterKeys<'a> {
+    backend: &'a dyn KeystoreOperations,
+}
+
+impl<'a> MasterKeys<'a> {
+    pub fn new(backend: &'a dyn KeystoreOperations) -> Self {
+        Self { backend }
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, KeystoreError> {
+        match self.backend.get_password(KEYS_SERVICE, name) {
+            Ok(_) => Ok(true),
+            Err(KeystoreError::KeyNotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Generates and stores a new master key; fails if `name` is already taken
+    pub fn create(&self, name: &str) -> Result<(), KeystoreError> {
+        if name.trim().is_empty() {
+            return Err(KeystoreError::InvalidInput(
+                "Key name must not be empty".to_string(),
+            ));
+        }
+        if self.exists(name)? {
+            return Err(KeystoreError::InvalidInput(format!(
+                "Master key '{}' already exists",
+                name
+            )));
+        }
+
+        let key = Zeroizing::new(random_bytes(MASTER_KEY_SIZE)?);
+        self.backend.set_password(&KeystoreEntry {
+            service: KEYS_SERVICE.to_string(),
+            account: name.to_string(),
+            value: STANDARD.encode(key.as_slice()),
+            ..Default::default()
+        })
+    }
+
+    /// Creates the master key `name` unless it already exists
+    pub fn ensure(&self, name: &str) -> Result<(), KeystoreError> {
+        if self.exists(name)? {
+            return Ok(());
+        }
+        self.create(name)
+    }
+
+    pub(crate) fn load(&self, name: &str) -> Result<Zeroizing<Vec<u8>>, KeystoreError> {
+        let encoded = Zeroizing::new(self.backend.get_password(KEYS_SERVICE, name)?);
+        STANDARD
+            .decode(encoded.as_bytes())
+            .map(Zeroizing::new)
+            .map_err(|e| KeystoreError::Serialization(format!("Invalid master key: {}", e)))
+    }
+
+    /// Derives `length` bytes for the purpose described by `info` from the master key `name`.
+    /// The same inputs always yield the same key.
+    pub fn derive(
+        &self,
+        name: &str,
+        info: &[u8],
+        length: usize,
+    ) -> Result<Zeroizing<Vec<u8>>, KeystoreError> {
+        if length == 0 || length > MAX_DERIVED_KEY_SIZE {
+            return Err(KeystoreError::InvalidInput(format!(
+                "Derived key length must be between 1 and {}",
+                MAX_DERIVED_KEY_SIZE
+            )));
+        }
+
+        let master = self.load(name)?;
+        let mut derived = Zeroizing::new(vec![0u8; length]);
+        Hkdf::<Sha256>::new(None, &master)
+            .expand(info, &mut derived)
+            .map_err(|e| KeystoreError::Platform(format!("Failed to derive key: {}", e)))?;
+        Ok(derived)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), KeystoreError> {
+        self.backend.delete_password(KEYS_SERVICE, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    #[test]
+    fn test_derive_is_deterministic_per_purpose() {
+        let backend = MemoryKeystore::default();
+        let keys = MasterKeys::new(&backend);
+        keys.create("settings").unwrap();
+
+        let a = keys.derive("settings", b"overlay-encryption", 32).unwrap();
+        let b = keys.derive("settings", b"overlay-encryption", 32).unwrap();
+        let c = keys.derive("settings", b"settings-store", 32).unwrap();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a.as_slice(), keys.load("settings").unwrap().as_slice());
+    }
+
+    #[test]
+    fn test_known_answer() {
+        // RFC 5869 test case 3: no salt, no info
+        let backend = MemoryKeystore::default();
+        backend
+            .set_password(&KeystoreEntry {
+                service: KEYS_SERVICE.to_string(),
+                account: "rfc".to_string(),
+                value: STANDARD.encode([0x0b; 22]),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let okm = MasterKeys::new(&backend).derive("rfc", b"", 42).unwrap();
+        assert_eq!(
+            hex::encode(okm.as_slice()),
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d\
+             9d201395faa4b61a96c8"
+        );
+    }
+
+    #[test]
+    fn test_create_ensure_and_errors() {
+        let backend = MemoryKeystore::default();
+        let keys = MasterKeys::new(&backend);
+
+        keys.ensure("logs").unwrap();
+        let first = keys.load("logs").unwrap();
+        keys.ensure("logs").unwrap();
+        assert_eq!(keys.load("logs").unwrap(), first);
+
+        assert!(matches!(
+            keys.create("logs"),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            keys.derive("logs", b"x", 0),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            keys.derive("missing", b"x", 32),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+    }
+}
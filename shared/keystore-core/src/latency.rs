@@ -0,0 +1,388 @@
+//! Latency budgets, and reads taken off a backend that keeps exceeding its own.
+//!
+//! A slow keychain or an overloaded DBus can make every call take seconds.
+//! [`LatencyBudgetKeystore`] times each call to its backend against the [`BudgetConfig`] of a
+//! shared [`LatencyBudget`]. After [`BudgetConfig::degrade_after`] calls in a row over budget the
+//! backend is degraded: reads that got past the caches above are answered by the fallback set
+//! with [`LatencyBudget::set_fallback`], normally the encrypted-file mirror, or fail at once with
+//! `Transient` where there is none. Writes, deletes and listings still reach the backend, as does
+//! one trial read every [`TRIAL_INTERVAL`], and after [`BudgetConfig::recover_after`] calls in a
+//! row within budget it's restored. Listeners hear a [`DegradationEvent`] on each change.
+//!
+//! Budgets start off; [`LatencyBudget::set_config`] turns them on.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_DEGRADE_AFTER: u32 = 3;
+pub const DEFAULT_RECOVER_AFTER: u32 = 3;
+
+/// How often a degraded backend gets a read to show whether it has recovered
+pub const TRIAL_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetConfig {
+    /// Calls slower than this are over budget
+    pub budget: Duration,
+    /// Calls over budget in a row that degrade the backend
+    pub degrade_after: u32,
+    /// Calls within budget in a row that restore it
+    pub recover_after: u32,
+}
+
+impl BudgetConfig {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            degrade_after: DEFAULT_DEGRADE_AFTER,
+            recover_after: DEFAULT_RECOVER_AFTER,
+        }
+    }
+}
+
+/// A backend was degraded or restored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DegradationEvent {
+    pub backend: String,
+    pub degraded: bool,
+    /// The call that tipped it
+    pub latency: Duration,
+}
+
+/// Answers reads of a degraded backend
+pub type FallbackRead = Box<dyn Fn(&str, &str) -> Result<String, KeystoreError> + Send + Sync>;
+
+type Listener = Box<dyn Fn(&DegradationEvent) + Send + Sync>;
+
+#[derive(Default)]
+struct State {
+    config: Option<BudgetConfig>,
+    degraded: bool,
+    /// Calls in a row that count towards changing `degraded`
+    streak: u32,
+    last_trial: Option<Instant>,
+}
+
+#[derive(Default)]
+pub struct LatencyBudget {
+    state: Mutex<State>,
+    fallback: OnceLock<FallbackRead>,
+    listeners: Mutex<Vec<Listener>>,
+}
+
+impl LatencyBudget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `None` turns the budget off, restoring a degraded backend
+    pub fn set_config(&self, config: Option<BudgetConfig>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.config = config;
+        state.streak = 0;
+        if config.is_none() {
+            state.degraded = false;
+        }
+    }
+
+    pub fn config(&self) -> Option<BudgetConfig> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).config
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .degraded
+    }
+
+    /// Takes the first fallback only
+    pub fn set_fallback(
+        &self,
+        fallback: impl Fn(&str, &str) -> Result<String, KeystoreError> + Send + Sync + 'static,
+    ) {
+        let _ = self.fallback.set(Box::new(fallback));
+    }
+
+    pub fn on_change(&self, listener: impl Fn(&DegradationEvent) + Send + Sync + 'static) {
+        self.listeners
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Box::new(listener));
+    }
+
+    /// Counts a call to `backend` that took `latency`
+    fn record(&self, backend: impl FnOnce() -> String, latency: Duration) {
+        let changed = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(config) = state.config else {
+                return;
+            };
+            let over = latency > config.budget;
+            // Towards degrading while normal, towards restoring while degraded
+            if over != state.degraded {
+                state.streak += 1;
+            } else {
+                state.streak = 0;
+            }
+            let limit = if state.degraded {
+                config.recover_after
+            } else {
+                config.degrade_after
+            };
+            if state.streak < limit.max(1) {
+                return;
+            }
+            state.degraded = !state.degraded;
+            state.streak = 0;
+            state.last_trial = Some(Instant::now());
+            state.degraded
+        };
+        let event = DegradationEvent {
+            backend: backend(),
+            degraded: changed,
+            latency,
+        };
+        for listener in self
+            .listeners
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            listener(&event);
+        }
+    }
+
+    /// Whether a read should go to the backend: always unless degraded, then once a trial is due
+    fn admits_read(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if !state.degraded {
+            return true;
+        }
+        if state
+            .last_trial
+            .is_some_and(|at| at.elapsed() < TRIAL_INTERVAL)
+        {
+            return false;
+        }
+        state.last_trial = Some(Instant::now());
+        true
+    }
+}
+
+pub struct LatencyBudgetKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    budget: Arc<LatencyBudget>,
+}
+
+impl LatencyBudgetKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>, budget: Arc<LatencyBudget>) -> Self {
+        Self { inner, budget }
+    }
+
+    fn timed<T>(&self, f: impl FnOnce(&dyn KeystoreOperations) -> T) -> T {
+        let started = Instant::now();
+        let result = f(self.inner.as_ref());
+        self.budget
+            .record(|| self.inner.backend_info().name, started.elapsed());
+        result
+    }
+
+    /// Reads `service`/`account` from the fallback of a degraded backend
+    fn degraded_read(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        match self.budget.fallback.get() {
+            Some(fallback) => fallback(service, account),
+            None => Err(KeystoreError::Transient(format!(
+                "Backend {} is over its latency budget",
+                self.inner.backend_info().name
+            ))),
+        }
+    }
+}
+
+impl KeystoreOperations for LatencyBudgetKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.timed(|inner| inner.set_password(entry))
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        if !self.budget.admits_read() {
+            return self.degraded_read(service, account);
+        }
+        self.timed(|inner| inner.get_password(service, account))
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.timed(|inner| inner.delete_password(service, account))
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        let info = self.inner.backend_info();
+        match self.budget.config() {
+            Some(config) => info
+                .with_detail("latency_budget_ms", config.budget.as_millis())
+                .with_detail("degraded", self.budget.is_degraded()),
+            None => info,
+        }
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.timed(|inner| inner.list_keys())
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.timed(|inner| inner.list_credentials(service))
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        if !self.budget.admits_read() {
+            return keys
+                .iter()
+                .map(|key| match self.degraded_read(&key.service, &key.account) {
+                    Ok(value) => Ok(Some(value)),
+                    Err(KeystoreError::KeyNotFound(_)) => Ok(None),
+                    Err(e) => Err(e),
+                })
+                .collect();
+        }
+        self.timed(|inner| inner.get_many(keys))
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.timed(|inner| inner.set_many(entries))
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.timed(|inner| inner.delete_many(keys))
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.timed(|inner| inner.delete_service(service))
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.timed(|inner| inner.wipe_all())
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.timed(|inner| inner.swap(a, b))
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.timed(|inner| inner.get_metadata(service, account))
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.timed(|inner| inner.has_password(service, account))
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        self.timed(|inner| inner.get_or_create(service, account, length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Takes `delay` milliseconds over every call
+    struct SlowKeystore {
+        inner: MemoryKeystore,
+        delay: Arc<AtomicU64>,
+    }
+
+    impl SlowKeystore {
+        fn wait(&self) {
+            std::thread::sleep(Duration::from_millis(self.delay.load(Ordering::SeqCst)));
+        }
+    }
+
+    impl KeystoreOperations for SlowKeystore {
+        fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+            self.wait();
+            self.inner.set_password(entry)
+        }
+
+        fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+            self.wait();
+            self.inner.get_password(service, account)
+        }
+
+        fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+            self.wait();
+            self.inner.delete_password(service, account)
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn backend_info(&self) -> BackendInfo {
+            BackendInfo::new("slow")
+        }
+    }
+
+    #[test]
+    fn test_reads_leave_a_backend_over_budget_until_it_recovers() {
+        let delay = Arc::new(AtomicU64::new(30));
+        let budget = Arc::new(LatencyBudget::new());
+        budget.set_config(Some(BudgetConfig {
+            budget: Duration::from_millis(10),
+            degrade_after: 2,
+            recover_after: 1,
+        }));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&events);
+        budget.on_change(move |event| seen.lock().unwrap().push(event.degraded));
+        let keystore = LatencyBudgetKeystore::new(
+            Box::new(SlowKeystore {
+                inner: MemoryKeystore::default(),
+                delay: Arc::clone(&delay),
+            }),
+            Arc::clone(&budget),
+        );
+        let entry = KeystoreEntry {
+            service: "twitch".to_string(),
+            account: "bot".to_string(),
+            value: "token".to_string(),
+            ..Default::default()
+        };
+
+        keystore.set_password(&entry).unwrap();
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "token");
+        assert!(budget.is_degraded());
+        assert!(matches!(
+            keystore.get_password("twitch", "bot"),
+            Err(KeystoreError::Transient(_))
+        ));
+
+        budget.set_fallback(|_, _| Ok("mirrored".to_string()));
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "mirrored");
+
+        // Writes still reach the backend and count towards recovery
+        delay.store(0, Ordering::SeqCst);
+        keystore.set_password(&entry).unwrap();
+        assert!(!budget.is_degraded());
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "token");
+        assert_eq!(*events.lock().unwrap(), vec![true, false]);
+    }
+}
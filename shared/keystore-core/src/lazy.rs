@@ -0,0 +1,198 @@
+//! Deferring backend selection to the first call.
+//!
+//! Choosing a backend can be slow: on Linux it probes Secret Service, which takes up to
+//! `linux::PROBE_TIMEOUT` on a session bus with no keyring daemon. [`LazyKeystore`] takes the
+//! function that builds the backend and runs it on the first operation instead, or from
+//! [`LazyKeystore::initialize`] for callers that want to pay the cost at a moment of their
+//! choosing. Concurrent first calls build the backend once. A failed build is not kept: the call
+//! that ran it gets the error and the next call tries again.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::sync::{Mutex, OnceLock};
+
+type Factory = Box<dyn Fn() -> Result<Box<dyn KeystoreOperations>, KeystoreError> + Send + Sync>;
+type ReadyHook = Box<dyn FnOnce(&dyn KeystoreOperations) + Send>;
+
+pub struct LazyKeystore {
+    factory: Factory,
+    backend: OnceLock<Box<dyn KeystoreOperations>>,
+    /// Held while building, so concurrent first calls wait for one build; also holds the hooks
+    /// still to run
+    pending: Mutex<Vec<ReadyHook>>,
+}
+
+impl LazyKeystore {
+    pub fn new(
+        factory: impl Fn() -> Result<Box<dyn KeystoreOperations>, KeystoreError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            factory: Box::new(factory),
+            backend: OnceLock::new(),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Builds the backend now if no call has yet
+    pub fn initialize(&self) -> Result<(), KeystoreError> {
+        self.backend().map(|_| ())
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.backend.get().is_some()
+    }
+
+    /// Runs `f` with the backend once it's built, or right away if it already is
+    pub fn on_ready(&self, f: impl FnOnce(&dyn KeystoreOperations) + Send + 'static) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        match self.backend.get() {
+            Some(backend) => {
+                drop(pending);
+                f(backend.as_ref());
+            }
+            None => pending.push(Box::new(f)),
+        }
+    }
+
+    fn backend(&self) -> Result<&dyn KeystoreOperations, KeystoreError> {
+        if let Some(backend) = self.backend.get() {
+            return Ok(backend.as_ref());
+        }
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        // Another caller may have built it while this one waited
+        if let Some(backend) = self.backend.get() {
+            return Ok(backend.as_ref());
+        }
+        let built = (self.factory)()?;
+        let backend = self.backend.get_or_init(|| built).as_ref();
+        let hooks = std::mem::take(&mut *pending);
+        drop(pending);
+        for hook in hooks {
+            hook(backend);
+        }
+        Ok(backend)
+    }
+}
+
+impl KeystoreOperations for LazyKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.backend()?.set_password(entry)
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.backend()?.get_password(service, account)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.backend()?.delete_password(service, account)
+    }
+
+    fn is_available(&self) -> bool {
+        self.backend().is_ok_and(|backend| backend.is_available())
+    }
+
+    /// Builds the backend to describe it; a failed build is described by its error
+    fn backend_info(&self) -> BackendInfo {
+        match self.backend() {
+            Ok(backend) => backend.backend_info(),
+            Err(e) => BackendInfo::new("unavailable").with_detail("error", e.to_string()),
+        }
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.backend().ok()?.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.backend()?.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.backend()?.list_credentials(service)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        self.backend()?.get_many(keys)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.backend()?.set_many(entries)
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.backend()?.delete_many(keys)
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.backend()?.delete_service(service)
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.backend()?.wipe_all()
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.backend()?.swap(a, b)
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.backend()?.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.backend()?.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        self.backend()?.get_or_create(service, account, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_backend_is_built_once_on_first_use() {
+        let builds = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&builds);
+        let lazy = LazyKeystore::new(move || {
+            // The first build fails, as a probe timing out would
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(KeystoreError::Platform("probe failed".to_string()));
+            }
+            Ok(Box::new(MemoryKeystore::default()) as Box<dyn KeystoreOperations>)
+        });
+        let ready = Arc::new(AtomicUsize::new(0));
+        let hook_ready = Arc::clone(&ready);
+        lazy.on_ready(move |_| {
+            hook_ready.fetch_add(1, Ordering::SeqCst);
+        });
+        assert_eq!(builds.load(Ordering::SeqCst), 0);
+        assert!(!lazy.is_initialized());
+
+        assert!(lazy.get_password("svc", "acct").is_err());
+        assert_eq!(ready.load(Ordering::SeqCst), 0);
+        lazy.initialize().unwrap();
+        lazy.set_password(&KeystoreEntry {
+            service: "svc".to_string(),
+            account: "acct".to_string(),
+            value: "v".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(lazy.get_password("svc", "acct").unwrap(), "v");
+        assert_eq!(builds.load(Ordering::SeqCst), 2);
+        assert_eq!(ready.load(Ordering::SeqCst), 1);
+    }
+}
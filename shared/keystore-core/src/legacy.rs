@@ -0,0 +1,330 @@
+//! Importing encrypted files older versions left behind.
+//!
+//! Before the native binding, the server daemon kept its fallback secrets in `keystore.json`,
+//! encrypted under a hex `file.key` in a different directory. And on Linux, a `keystore.fallback`
+//! written before `XDG_CONFIG_HOME` was set stays under `~/.config`, where this build no longer
+//! looks. [`legacy_stores`] finds both; [`migrate`] copies their entries into the current keystore,
+//! keeping any value it already has, then renames the originals with [`MIGRATED_SUFFIX`] so they
+//! are only imported once. [`FallbackKeystore::new`] runs it when it opens.
+//!
+//! [`FallbackKeystore::new`]: crate::platform::fallback::FallbackKeystore::new
+
+use crate::crypto::{AesGcmKey, NONCE_SIZE};
+use crate::error::KeystoreError;
+use crate::platform::fallback::FallbackKeystore;
+use crate::platform::KeystoreOperations;
+use crate::{KeystoreEntry, KeystoreKey};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Appended to a legacy file's name once its entries are imported
+pub const MIGRATED_SUFFIX: &str = ".migrated";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyLayout {
+    /// The daemon's `keystore.json`: base64 `service:account` names mapped to hex AES-256-GCM
+    /// fields, under a hex-encoded `file.key`
+    DaemonJson,
+    /// A `keystore.fallback` and its `enc.key` in a directory this build no longer uses
+    Fallback,
+}
+
+impl LegacyLayout {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LegacyLayout::DaemonJson => "daemon-json",
+            LegacyLayout::Fallback => "fallback",
+        }
+    }
+}
+
+/// A legacy data file and the key it was encrypted under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyStore {
+    pub layout: LegacyLayout,
+    pub data_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+/// What importing one legacy store did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationOutcome {
+    pub source: PathBuf,
+    pub layout: LegacyLayout,
+    /// Entries written to the current keystore
+    pub imported: usize,
+    /// Entries the current keystore already held, which keep their current value
+    pub skipped: usize,
+    /// Why the store was left in place. Its files are renamed only when this is `None`, so a
+    /// failed migration is retried on the next start.
+    pub error: Option<String>,
+}
+
+/// One `keystore.json` record
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DaemonRecord {
+    iv: String,
+    ciphertext: String,
+    auth_tag: String,
+}
+
+fn home() -> PathBuf {
+    let var = if cfg!(target_os = "windows") {
+        "USERPROFILE"
+    } else {
+        "HOME"
+    };
+    PathBuf::from(std::env::var(var).unwrap_or_else(|_| ".".to_string()))
+}
+
+/// Where the daemon's TypeScript keystore kept its key and data, as (key, data) directories
+fn daemon_dirs() -> (PathBuf, PathBuf) {
+    let home = home();
+    if cfg!(target_os = "windows") {
+        let roaming = std::env::var("APPDATA")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| home.join("AppData/Roaming"));
+        let local = std::env::var("LOCALAPPDATA")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| home.join("AppData/Local"));
+        (
+            roaming.join("streaming-enhancement"),
+            local.join("streaming-enhancement"),
+        )
+    } else if cfg!(target_os = "macos") {
+        let dir = home.join("Library/Application Support/streaming-enhancement");
+        (dir.clone(), dir)
+    } else if cfg!(target_os = "linux") {
+        (
+            home.join(".config/streaming-enhancement"),
+            home.join(".local/share/streaming-enhancement"),
+        )
+    } else {
+        let dir = home.join(".streaming-enhancement");
+        (dir.clone(), dir)
+    }
+}
+
+/// The legacy stores present on this machine. `current` is the file in use, which is never one of
+/// them.
+pub fn legacy_stores(current: &Path) -> Vec<LegacyStore> {
+    let (key_dir, data_dir) = daemon_dirs();
+    let mut candidates = vec![LegacyStore {
+        layout: LegacyLayout::DaemonJson,
+        data_path: data_dir.join("keystore.json"),
+        key_path: key_dir.join("file.key"),
+    }];
+    if cfg!(target_os = "linux") {
+        let dir = home().join(".config/streaming-enhancement");
+        candidates.push(LegacyStore {
+            layout: LegacyLayout::Fallback,
+            data_path: dir.join("keystore.fallback"),
+            key_path: dir.join("enc.key"),
+        });
+    }
+    candidates
+        .into_iter()
+        .filter(|store| store.data_path != current && store.data_path.is_file())
+        .collect()
+}
+
+/// Reads every entry of `store`, failing if any of them can't be
+fn read_entries(store: &LegacyStore) -> Result<Vec<KeystoreEntry>, KeystoreError> {
+    let key_bytes = Zeroizing::new(fs::read(&store.key_path)?);
+    match store.layout {
+        LegacyLayout::DaemonJson => {
+            let key_hex = Zeroizing::new(String::from_utf8_lossy(&key_bytes).trim().to_string());
+            let key = hex::decode(key_hex.as_str())
+                .ok()
+                .map(Zeroizing::new)
+                .and_then(|bytes| AesGcmKey::from_slice(&bytes))
+                .ok_or_else(|| {
+                    KeystoreError::Platform(format!(
+                        "Invalid key file {}",
+                        store.key_path.display()
+                    ))
+                })?;
+            let records: BTreeMap<String, DaemonRecord> =
+                serde_json::from_slice(&fs::read(&store.data_path)?)
+                    .map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+            records
+                .iter()
+                .map(|(name, record)| {
+                    decode_daemon_record(&key, name, record).ok_or_else(|| {
+                        KeystoreError::Platform(format!("Failed to decrypt entry {}", name))
+                    })
+                })
+                .collect()
+        }
+        LegacyLayout::Fallback => {
+            let key = AesGcmKey::from_slice(&key_bytes).ok_or_else(|| {
+                KeystoreError::Platform(format!("Invalid key file {}", store.key_path.display()))
+            })?;
+            let legacy = FallbackKeystore::open(store.data_path.clone(), key)?;
+            if let Some(count) = legacy.backend_info().details.get("undecryptable_entries") {
+                return Err(KeystoreError::Platform(format!(
+                    "{} entries don't decrypt",
+                    count
+                )));
+            }
+            let keys = legacy.list_keys()?;
+            let values = legacy.get_many(&keys)?;
+            Ok(keys
+                .into_iter()
+                .zip(values)
+                .filter_map(|(key, value)| {
+                    Some(KeystoreEntry {
+                        service: key.service,
+                        account: key.account,
+                        value: value?,
+                        ..Default::default()
+                    })
+                })
+                .collect())
+        }
+    }
+}
+
+fn decode_daemon_record(
+    key: &AesGcmKey,
+    name: &str,
+    record: &DaemonRecord,
+) -> Option<KeystoreEntry> {
+    let (service, account) = name.split_once(':')?;
+    let decode = |part: &str| String::from_utf8(STANDARD.decode(part).ok()?).ok();
+    let nonce: [u8; NONCE_SIZE] = hex::decode(&record.iv).ok()?.try_into().ok()?;
+    let mut sealed = hex::decode(&record.ciphertext).ok()?;
+    sealed.extend(hex::decode(&record.auth_tag).ok()?);
+    let value = key.open(&nonce, &sealed)?;
+    Some(KeystoreEntry {
+        service: decode(service)?,
+        account: decode(account)?,
+        value: String::from_utf8(value.to_vec()).ok()?,
+        ..Default::default()
+    })
+}
+
+fn migrated_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(MIGRATED_SUFFIX);
+    path.with_file_name(name)
+}
+
+/// Imports `store` into `target` and renames its files. Nothing is renamed unless every entry was
+/// read and written.
+pub fn migrate(target: &dyn KeystoreOperations, store: &LegacyStore) -> MigrationOutcome {
+    let mut outcome = MigrationOutcome {
+        source: store.data_path.clone(),
+        layout: store.layout,
+        imported: 0,
+        skipped: 0,
+        error: None,
+    };
+    if let Err(e) = import_into(target, store, &mut outcome) {
+        outcome.error = Some(e.to_string());
+    }
+    outcome
+}
+
+fn import_into(
+    target: &dyn KeystoreOperations,
+    store: &LegacyStore,
+    outcome: &mut MigrationOutcome,
+) -> Result<(), KeystoreError> {
+    let entries = read_entries(store)?;
+    let keys: Vec<KeystoreKey> = entries
+        .iter()
+        .map(|entry| KeystoreKey {
+            service: entry.service.clone(),
+            account: entry.account.clone(),
+        })
+        .collect();
+    let current = target.get_many(&keys)?;
+    let mut missing = Vec::new();
+    for (mut entry, current) in entries.into_iter().zip(current) {
+        match current {
+            None => missing.push(entry),
+            Some(current) => {
+                drop(Zeroizing::new(current));
+                entry.value.zeroize();
+                outcome.skipped += 1;
+            }
+        }
+    }
+    let result = target.set_many(&missing);
+    missing.iter_mut().for_each(|entry| entry.value.zeroize());
+    result?;
+    outcome.imported = missing.len();
+    fs::rename(&store.data_path, migrated_path(&store.data_path))?;
+    fs::rename(&store.key_path, migrated_path(&store.key_path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use tempfile::TempDir;
+
+    fn daemon_record(key: &AesGcmKey, value: &str) -> serde_json::Value {
+        let (nonce, sealed) = key.seal(value.as_bytes()).unwrap();
+        let (ciphertext, tag) = sealed.split_at(sealed.len() - 16);
+        serde_json::json!({
+            "iv": hex::encode(nonce),
+            "ciphertext": hex::encode(ciphertext),
+            "authTag": hex::encode(tag),
+        })
+    }
+
+    #[test]
+    fn test_daemon_json_is_imported_and_renamed() {
+        let dir = TempDir::new().unwrap();
+        let key = AesGcmKey::generate().unwrap();
+        let store = LegacyStore {
+            layout: LegacyLayout::DaemonJson,
+            data_path: dir.path().join("keystore.json"),
+            key_path: dir.path().join("file.key"),
+        };
+        fs::write(&store.key_path, hex::encode(key.as_bytes())).unwrap();
+        let name = |service: &str, account: &str| {
+            format!("{}:{}", STANDARD.encode(service), STANDARD.encode(account))
+        };
+        let records = serde_json::json!({
+            name("twitch", "bot-oauth"): daemon_record(&key, "old-bot"),
+            name("youtube", "channel"): daemon_record(&key, "yt-token"),
+        });
+        fs::write(&store.data_path, records.to_string()).unwrap();
+
+        let target = MemoryKeystore::default();
+        target
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "bot-oauth".to_string(),
+                value: "current-bot".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let outcome = migrate(&target, &store);
+        assert_eq!(outcome.error, None);
+        assert_eq!((outcome.imported, outcome.skipped), (1, 1));
+        assert_eq!(
+            target.get_password("twitch", "bot-oauth").unwrap(),
+            "current-bot"
+        );
+        assert_eq!(
+            target.get_password("youtube", "channel").unwrap(),
+            "yt-token"
+        );
+        assert!(!store.data_path.exists());
+        assert!(dir.path().join("keystore.json.migrated").exists());
+        assert!(dir.path().join("file.key.migrated").exists());
+    }
+}
@@ -0,0 +1,141 @@
+//! Platform keystore access shared by the napi binding and other Rust tools.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default)]
+pub struct KeystoreEntry {
+    pub service: String,
+    pub account: String,
+    pub value: String,
+    /// Human-readable name for settings screens. `None` keeps the stored label; an empty one
+    /// clears it.
+    pub label: Option<String>,
+    /// Free-form note, kept and cleared like `label`
+    pub comment: Option<String>,
+    /// Milliseconds since the Unix epoch after which the entry reads as missing. `None` never
+    /// expires, even when writing over an entry that would have.
+    pub expires_at: Option<u64>,
+}
+
+/// What a backend keeps about an entry besides its value; see
+/// [`platform::KeystoreOperations::get_metadata`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Milliseconds since the Unix epoch; `None` where the backend doesn't record it, or for
+    /// entries written before it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl EntryMetadata {
+    /// The metadata after writing `entry` at `now` over an entry that had `previous`, or over
+    /// nothing
+    pub fn after_write(previous: Option<&EntryMetadata>, entry: &KeystoreEntry, now: u64) -> Self {
+        let pick = |new: &Option<String>, old: Option<&String>| match new {
+            Some(text) if text.is_empty() => None,
+            Some(text) => Some(text.clone()),
+            None => old.cloned(),
+        };
+        Self {
+            label: pick(&entry.label, previous.and_then(|p| p.label.as_ref())),
+            comment: pick(&entry.comment, previous.and_then(|p| p.comment.as_ref())),
+            created_at: previous.map_or(Some(now), |p| p.created_at),
+            updated_at: Some(now),
+            expires_at: entry.expires_at,
+        }
+    }
+
+    /// Whether the entry had expired by `now`
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// Identifies a stored entry without carrying its value
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct KeystoreKey {
+    pub service: String,
+    pub account: String,
+}
+
+pub mod access;
+pub mod alias;
+pub mod audit;
+pub mod backup;
+pub mod blob;
+pub mod bundle;
+pub mod cache;
+pub mod card;
+pub mod casefold;
+pub mod certs;
+pub mod channel;
+pub mod conflicts;
+pub mod context;
+#[cfg(feature = "backend-file")]
+pub mod crypto;
+pub mod delegate;
+pub mod discovery;
+pub mod dotenv;
+pub mod ephemeral;
+pub mod error;
+pub mod escrow;
+pub mod expiry;
+pub mod files;
+pub mod freeze;
+pub mod generate;
+pub mod health;
+pub mod hooks;
+pub mod hot;
+pub mod import;
+pub mod integrity;
+pub mod inventory;
+pub mod kdf;
+pub mod keys;
+pub mod latency;
+pub mod lazy;
+#[cfg(feature = "backend-file")]
+pub mod legacy;
+pub mod lock;
+pub mod metrics;
+pub mod mirror;
+pub mod namespace;
+pub mod notes;
+pub mod orphans;
+pub mod pairing;
+pub mod platform;
+pub mod policy;
+pub mod prefetch;
+pub mod previous;
+pub mod probe;
+pub mod protect;
+pub mod provenance;
+pub mod qr;
+pub mod queue;
+pub mod refresh;
+pub mod refs;
+pub mod reserved;
+pub mod resolution;
+#[cfg(feature = "async-runtime")]
+pub mod runtime;
+pub mod scope;
+pub mod search;
+pub mod strength;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+#[cfg(test)]
+mod testing;
+pub mod unlock_wait;
+pub mod verify;
+pub mod version;
+pub mod watch;
+
+pub use error::{ErrorKind, KeystoreError};
+pub use platform::{default_backend, BackendInfo, Keystore, KeystoreOperations};
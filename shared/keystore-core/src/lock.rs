@@ -0,0 +1,1041 @@
+//! Lockable keystore wrapper with an idle auto-lock timer.
+//!
+//! [`LockingKeystore`] sits in front of any backend and keeps a small cache of values read while it
+//! is unlocked, so repeated reads don't each go through the OS keystore. The cache is a
+//! [`ValueCache`] held to a memory budget, see [`LockingKeystore::set_cache_budget`].
+//! [`LockingKeystore::lock`] wipes that cache and every read, write or delete is refused with
+//! `AccessDenied` until [`LockingKeystore::unlock`] succeeds. An idle timeout locks it automatically after a period
+//! without keystore activity, and listeners are told about every state change so a UI can follow.
+//!
+//! Unlocking needs a passphrase once one has been set with [`LockingKeystore::set_passphrase`]; its
+//! Argon2id verifier lives in the wrapped backend under `streaming-enhancement:lock`. Without one
+//! the keystore is protected only by the OS session and `unlock` takes no secret. The verifier is
+//! hashed with the parameters [`LockingKeystore::tune_kdf`] picked for this machine, and rehashed
+//! on unlock after a re-tune. A marker stored with it records that a passphrase was enrolled, so
+//! if the verifier later goes missing `unlock` fails closed rather than falling back to the session
+//! or enrolling whatever passphrase comes next; a recovery code sets a new one. Biometric unlock
+//! is not implemented yet.
+//!
+//! A forgotten passphrase would otherwise lock the user out of every platform token, so
+//! [`LockingKeystore::generate_recovery_codes`] issues a set of one-time codes.
+//! [`LockingKeystore::recover`] consumes one in place of the passphrase to unlock and set a new
+//! one. The passphrase gates access rather than encrypting the entries, so a code doesn't wrap a
+//! key: like the passphrase, only a salted hash of each is stored, in the wrapped backend next to
+//! the verifier. Codes carry 80 random bits, too many to guess, so a fast hash is enough.
+
+use crate::cache::ValueCache;
+use crate::error::KeystoreError;
+use crate::generate::random_bytes;
+use crate::kdf::{self, KdfParams};
+use crate::metrics::KeystoreMetrics;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::resolution::{caches_bypassed, record_source, ReadSource};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Argon2, Params};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// Service under which the unlock passphrase verifier is stored
+pub const LOCK_SERVICE: &str = "streaming-enhancement:lock";
+
+const PASSPHRASE_ACCOUNT: &str = "passphrase";
+
+/// Present while a passphrase is set, whether or not its verifier still is
+const ENROLLED_ACCOUNT: &str = "enrolled";
+
+const RECOVERY_ACCOUNT: &str = "recovery-codes";
+
+/// Codes issued per set
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Crockford's base32: no I, L, O or U to misread
+const RECOVERY_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// 16 characters of 5 bits
+const RECOVERY_CODE_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockState {
+    Locked,
+    Unlocked,
+}
+
+/// What caused a state change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockReason {
+    /// `lock()` or `unlock()` was called
+    Manual,
+    /// The idle timeout elapsed
+    Idle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockEvent {
+    pub state: LockState,
+    pub reason: LockReason,
+}
+
+/// What `unlock` expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnlockMode {
+    /// No secret; the OS login session is the only protection
+    Session,
+    Passphrase,
+}
+
+impl UnlockMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnlockMode::Session => "session",
+            UnlockMode::Passphrase => "passphrase",
+        }
+    }
+}
+
+type Listener = Box<dyn Fn(LockEvent) + Send + Sync>;
+
+struct State {
+    locked: bool,
+    last_activity: Instant,
+    idle_timeout: Option<Duration>,
+    timer_started: bool,
+    closed: bool,
+    /// Set by managed policy: session-only unlock is not allowed
+    passphrase_required: bool,
+    cache: ValueCache,
+    /// Bumped by [`LockingKeystore::invalidate_cache`], so a read already in flight doesn't cache
+    /// the value it got
+    generation: u64,
+}
+
+struct Shared {
+    inner: Box<dyn KeystoreOperations>,
+    state: Mutex<State>,
+    wake: Condvar,
+    listeners: Mutex<Vec<Listener>>,
+    metrics: OnceLock<Arc<KeystoreMetrics>>,
+    /// Held while a recovery code is checked and consumed, so two recoveries can't both use it
+    recovery: Mutex<()>,
+}
+
+impl Shared {
+    fn emit(&self, event: LockEvent) {
+        for listener in self.listeners.lock().unwrap().iter() {
+            listener(event);
+        }
+    }
+
+    /// Locks and wipes the cache; returns false if already locked
+    fn lock(&self, state: &mut State) -> bool {
+        state.cache.clear();
+        self.report(state, 0);
+        !std::mem::replace(&mut state.locked, true)
+    }
+
+    fn report(&self, state: &State, evicted: usize) {
+        if let Some(metrics) = self.metrics.get() {
+            metrics.record_cache_usage("lock", state.cache.bytes(), state.cache.len(), evicted);
+        }
+    }
+
+    /// Applies `change` to the cache and reports its usage
+    fn update_cache(&self, change: impl FnOnce(&mut ValueCache) -> usize) {
+        let mut state = self.state.lock().unwrap();
+        let evicted = change(&mut state.cache);
+        self.report(&state, evicted);
+    }
+}
+
+pub struct LockingKeystore {
+    shared: Arc<Shared>,
+}
+
+/// The code's characters as issued, forgiving case, separators and the letters base32 leaves out
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        })
+        .collect()
+}
+
+fn hash_recovery_code(salt: &[u8], code: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(salt)
+        .chain_update(normalize_recovery_code(code).as_bytes())
+        .finalize();
+    format!("{}${}", hex::encode(salt), hex::encode(digest))
+}
+
+fn recovery_code_matches(stored: &str, code: &str) -> bool {
+    let Some((salt, _)) = stored.split_once('$') else {
+        return false;
+    };
+    let Ok(salt) = hex::decode(salt) else {
+        return false;
+    };
+    hash_recovery_code(&salt, code)
+        .as_bytes()
+        .ct_eq(stored.as_bytes())
+        .into()
+}
+
+fn locked_error() -> KeystoreError {
+    KeystoreError::AccessDenied("Keystore is locked".to_string())
+}
+
+fn idle_timer(shared: Arc<Shared>) {
+    let mut state = shared.state.lock().unwrap();
+    loop {
+        if state.closed {
+            return;
+        }
+        state = match (state.locked, state.idle_timeout) {
+            (false, Some(timeout)) => {
+                let idle = state.last_activity.elapsed();
+                if idle >= timeout {
+                    let changed = shared.lock(&mut state);
+                    drop(state);
+                    if changed {
+                        shared.emit(LockEvent {
+                            state: LockState::Locked,
+                            reason: LockReason::Idle,
+                        });
+                    }
+                    shared.state.lock().unwrap()
+                } else {
+                    shared.wake.wait_timeout(state, timeout - idle).unwrap().0
+                }
+            }
+            _ => shared.wake.wait(state).unwrap(),
+        };
+    }
+}
+
+impl LockingKeystore {
+    /// Wraps `inner`, starting unlocked with no idle timeout
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                inner,
+                state: Mutex::new(State {
+                    locked: false,
+                    last_activity: Instant::now(),
+                    idle_timeout: None,
+                    timer_started: false,
+                    closed: false,
+                    passphrase_required: false,
+                    cache: ValueCache::default(),
+                    generation: 0,
+                }),
+                wake: Condvar::new(),
+                listeners: Mutex::new(Vec::new()),
+                metrics: OnceLock::new(),
+                recovery: Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Refuses the call if locked, otherwise records activity for the idle timer
+    fn active(&self) -> Result<MutexGuard<'_, State>, KeystoreError> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.locked {
+            return Err(locked_error());
+        }
+        state.last_activity = Instant::now();
+        Ok(state)
+    }
+
+    pub fn state(&self) -> LockState {
+        if self.shared.state.lock().unwrap().locked {
+            LockState::Locked
+        } else {
+            LockState::Unlocked
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.state() == LockState::Locked
+    }
+
+    pub fn unlock_mode(&self) -> Result<UnlockMode, KeystoreError> {
+        Ok(match self.verifier()? {
+            Some(_) => UnlockMode::Passphrase,
+            None if self.passphrase_required() || self.enrolled()? => UnlockMode::Passphrase,
+            None => UnlockMode::Session,
+        })
+    }
+
+    fn passphrase_required(&self) -> bool {
+        self.shared.state.lock().unwrap().passphrase_required
+    }
+
+    /// Enforces a passphrase, as managed policy can require: locks now, and from then on `unlock`
+    /// needs one. Without a passphrase set yet, the first `unlock` passphrase becomes it. Removing
+    /// the passphrase is refused.
+    pub fn require_passphrase(&self) {
+        let changed = {
+            let mut state = self.shared.state.lock().unwrap();
+            state.passphrase_required = true;
+            self.shared.lock(&mut state)
+        };
+        if changed {
+            self.shared.emit(LockEvent {
+                state: LockState::Locked,
+                reason: LockReason::Manual,
+            });
+        }
+    }
+
+    fn verifier(&self) -> Result<Option<Zeroizing<String>>, KeystoreError> {
+        match self
+            .shared
+            .inner
+            .get_password(LOCK_SERVICE, PASSPHRASE_ACCOUNT)
+        {
+            Ok(hash) => Ok(Some(Zeroizing::new(hash))),
+            Err(KeystoreError::KeyNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn enrolled(&self) -> Result<bool, KeystoreError> {
+        self.shared
+            .inner
+            .has_password(LOCK_SERVICE, ENROLLED_ACCOUNT)
+    }
+
+    fn store_enrolled(&self) -> Result<(), KeystoreError> {
+        self.shared.inner.set_password(&KeystoreEntry {
+            service: LOCK_SERVICE.to_string(),
+            account: ENROLLED_ACCOUNT.to_string(),
+            value: "1".to_string(),
+            ..Default::default()
+        })
+    }
+
+    fn check_passphrase(&self, passphrase: Option<&str>) -> Result<(), KeystoreError> {
+        let Some(verifier) = self.verifier()? else {
+            if self.enrolled()? {
+                return Err(KeystoreError::AccessDenied(
+                    "The passphrase verifier is missing; unlock with a recovery code".to_string(),
+                ));
+            }
+            if !self.passphrase_required() {
+                return Ok(());
+            }
+            // Enrolls the first passphrase given
+            return match passphrase {
+                Some(passphrase) => self.store_verifier(passphrase),
+                None => Err(KeystoreError::AccessDenied(
+                    "Policy requires a passphrase; unlock with the one to set".to_string(),
+                )),
+            };
+        };
+        let passphrase = passphrase.ok_or_else(|| {
+            KeystoreError::AccessDenied("A passphrase is required to unlock".to_string())
+        })?;
+        let hash = PasswordHash::new(&verifier)
+            .map_err(|e| KeystoreError::Serialization(format!("Invalid lock verifier: {}", e)))?;
+        // The hash carries the parameters it was made with
+        Argon2::default()
+            .verify_password(passphrase.as_bytes(), &hash)
+            .map_err(|_| KeystoreError::AccessDenied("Incorrect passphrase".to_string()))?;
+        if !self.enrolled()? {
+            // Verifiers stored before the marker existed
+            self.store_enrolled()?;
+        }
+        let tuned = KdfParams::load(&*self.shared.inner)?;
+        if Params::try_from(&hash)
+            .ok()
+            .map(|params| KdfParams::from(&params))
+            != Some(tuned)
+        {
+            // Best effort: the old verifier still unlocks if this fails
+            let _ = self.store_verifier(passphrase);
+        }
+        Ok(())
+    }
+
+    /// Wipes cached values and refuses keystore access until `unlock`
+    pub fn lock(&self) {
+        let changed = self.shared.lock(&mut self.shared.state.lock().unwrap());
+        if changed {
+            self.shared.emit(LockEvent {
+                state: LockState::Locked,
+                reason: LockReason::Manual,
+            });
+        }
+    }
+
+    /// Unlocks the keystore. `passphrase` is required in [`UnlockMode::Passphrase`] and ignored
+    /// otherwise.
+    pub fn unlock(&self, passphrase: Option<&str>) -> Result<(), KeystoreError> {
+        self.check_passphrase(passphrase)?;
+
+        let changed = {
+            let mut state = self.shared.state.lock().unwrap();
+            state.last_activity = Instant::now();
+            std::mem::replace(&mut state.locked, false)
+        };
+        self.shared.wake.notify_all();
+        if changed {
+            self.shared.emit(LockEvent {
+                state: LockState::Unlocked,
+                reason: LockReason::Manual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Sets, changes or (with `new: None`) removes the unlock passphrase. Must be called while
+    /// unlocked, and `current` must match when a passphrase is already set.
+    pub fn set_passphrase(
+        &self,
+        current: Option<&str>,
+        new: Option<&str>,
+    ) -> Result<(), KeystoreError> {
+        drop(self.active()?);
+        self.check_passphrase(current)?;
+
+        let Some(new) = new else {
+            if self.passphrase_required() {
+                return Err(KeystoreError::AccessDenied(
+                    "Policy requires an unlock passphrase".to_string(),
+                ));
+            }
+            // Codes for a passphrase that no longer exists would only be a way in
+            self.delete_recovery_codes()?;
+            // Before the verifier, so a failure in between leaves the passphrase in place
+            match self
+                .shared
+                .inner
+                .delete_password(LOCK_SERVICE, ENROLLED_ACCOUNT)
+            {
+                Ok(()) | Err(KeystoreError::KeyNotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+            return match self
+                .shared
+                .inner
+                .delete_password(LOCK_SERVICE, PASSPHRASE_ACCOUNT)
+            {
+                Ok(()) | Err(KeystoreError::KeyNotFound(_)) => Ok(()),
+                Err(e) => Err(e),
+            };
+        };
+        self.store_verifier(new)
+    }
+
+    fn recovery_hashes(&self) -> Result<Vec<String>, KeystoreError> {
+        match self
+            .shared
+            .inner
+            .get_password(LOCK_SERVICE, RECOVERY_ACCOUNT)
+        {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| {
+                KeystoreError::Serialization(format!("Invalid recovery codes: {}", e))
+            }),
+            Err(KeystoreError::KeyNotFound(_)) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn store_recovery_hashes(&self, hashes: &[String]) -> Result<(), KeystoreError> {
+        if hashes.is_empty() {
+            return self.delete_recovery_codes();
+        }
+        self.shared.inner.set_password(&KeystoreEntry {
+            service: LOCK_SERVICE.to_string(),
+            account: RECOVERY_ACCOUNT.to_string(),
+            value: serde_json::to_string(hashes)
+                .map_err(|e| KeystoreError::Serialization(e.to_string()))?,
+            ..Default::default()
+        })
+    }
+
+    fn delete_recovery_codes(&self) -> Result<(), KeystoreError> {
+        match self
+            .shared
+            .inner
+            .delete_password(LOCK_SERVICE, RECOVERY_ACCOUNT)
+        {
+            Ok(()) | Err(KeystoreError::KeyNotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Issues [`RECOVERY_CODE_COUNT`] one-time recovery codes, replacing any earlier set, and
+    /// returns them as `XXXX-XXXX-XXXX-XXXX` to show the user once. Must be called while unlocked
+    /// with the current passphrase; without a passphrase there is nothing to recover.
+    pub fn generate_recovery_codes(
+        &self,
+        passphrase: &str,
+    ) -> Result<Vec<Zeroizing<String>>, KeystoreError> {
+        drop(self.active()?);
+        if self.verifier()?.is_none() {
+            return Err(KeystoreError::InvalidInput(
+                "Recovery codes need an unlock passphrase".to_string(),
+            ));
+        }
+        self.check_passphrase(Some(passphrase))?;
+
+        let _recovery = self.shared.recovery.lock().unwrap();
+        let mut codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+        let mut hashes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+        for _ in 0..RECOVERY_CODE_COUNT {
+            let bytes = Zeroizing::new(random_bytes(RECOVERY_CODE_LENGTH)?);
+            let mut code = Zeroizing::new(String::with_capacity(RECOVERY_CODE_LENGTH + 3));
+            for (i, byte) in bytes.iter().enumerate() {
+                if i > 0 && i % 4 == 0 {
+                    code.push('-');
+                }
+                code.push(char::from(RECOVERY_ALPHABET[usize::from(byte % 32)]));
+            }
+            hashes.push(hash_recovery_code(&random_bytes(16)?, &code));
+            codes.push(code);
+        }
+        self.store_recovery_hashes(&hashes)?;
+        Ok(codes)
+    }
+
+    /// Recovery codes not yet used
+    pub fn recovery_codes_remaining(&self) -> Result<usize, KeystoreError> {
+        Ok(self.recovery_hashes()?.len())
+    }
+
+    /// Unlocks with a recovery code instead of the forgotten passphrase, and sets `new_passphrase`.
+    /// The code is used up; returns how many remain. Works while locked.
+    pub fn recover(&self, code: &str, new_passphrase: &str) -> Result<usize, KeystoreError> {
+        let remaining = {
+            let _recovery = self.shared.recovery.lock().unwrap();
+            let mut hashes = self.recovery_hashes()?;
+            let used = hashes
+                .iter()
+                .position(|stored| recovery_code_matches(stored, code))
+                .ok_or_else(|| {
+                    KeystoreError::AccessDenied("Invalid or already used recovery code".to_string())
+                })?;
+            hashes.remove(used);
+            // Used up before anything else, so a failure below can't leave it reusable
+            self.store_recovery_hashes(&hashes)?;
+            hashes.len()
+        };
+        self.store_verifier(new_passphrase)?;
+        self.unlock(Some(new_passphrase))?;
+        Ok(remaining)
+    }
+
+    fn store_verifier(&self, new: &str) -> Result<(), KeystoreError> {
+        if new.is_empty() {
+            return Err(KeystoreError::InvalidInput(
+                "Passphrase must not be empty".to_string(),
+            ));
+        }
+
+        let salt = SaltString::encode_b64(&random_bytes(16)?)
+            .map_err(|e| KeystoreError::Platform(format!("Invalid salt: {}", e)))?;
+        let hash = KdfParams::load(&*self.shared.inner)?
+            .argon2()?
+            .hash_password(new.as_bytes(), &salt)
+            .map_err(|e| KeystoreError::Platform(format!("Failed to hash passphrase: {}", e)))?;
+        self.shared.inner.set_password(&KeystoreEntry {
+            service: LOCK_SERVICE.to_string(),
+            account: PASSPHRASE_ACCOUNT.to_string(),
+            value: hash.to_string(),
+            ..Default::default()
+        })?;
+        self.store_enrolled()
+    }
+
+    /// Benchmarks Argon2id on this machine, stores the parameters that take about `target` for
+    /// the verifier and backups to use, and returns them. Must be called while unlocked; the
+    /// verifier is rehashed with them on the next unlock.
+    pub fn tune_kdf(&self, target: Duration) -> Result<KdfParams, KeystoreError> {
+        drop(self.active()?);
+        let params = kdf::tune(target)?;
+        params.store(&*self.shared.inner)?;
+        Ok(params)
+    }
+
+    /// Locks automatically after `timeout` without keystore activity; `None` disables it
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.idle_timeout = timeout;
+        state.last_activity = Instant::now();
+        if timeout.is_some() && !state.timer_started {
+            state.timer_started = true;
+            let shared = Arc::clone(&self.shared);
+            thread::spawn(move || idle_timer(shared));
+        }
+        drop(state);
+        self.shared.wake.notify_all();
+    }
+
+    /// Caps the bytes of decrypted values cached, evicting the least recently read first
+    pub fn set_cache_budget(&self, bytes: usize) {
+        self.shared.update_cache(|cache| cache.set_budget(bytes));
+    }
+
+    /// Drops every cached value, e.g. when the platform keystore reports an entry changed outside
+    /// this process
+    pub fn invalidate_cache(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.cache.clear();
+        state.generation += 1;
+        self.shared.report(&state, 0);
+    }
+
+    /// Reports cache hits, misses and usage into `metrics`; only the first call has an effect
+    pub fn set_metrics(&self, metrics: Arc<KeystoreMetrics>) {
+        let _ = self.shared.metrics.set(metrics);
+    }
+
+    fn record_cache(&self, hit: bool) {
+        if let Some(metrics) = self.shared.metrics.get() {
+            metrics.record_cache(hit);
+        }
+    }
+
+    /// Registers a callback for every lock or unlock. Callbacks run on the thread that caused the
+    /// change, which for idle locks is the timer thread.
+    pub fn on_state_change(&self, listener: impl Fn(LockEvent) + Send + Sync + 'static) {
+        self.shared
+            .listeners
+            .lock()
+            .unwrap()
+            .push(Box::new(listener));
+    }
+}
+
+impl Drop for LockingKeystore {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.wake.notify_all();
+    }
+}
+
+impl KeystoreOperations for LockingKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        drop(self.active()?);
+        let result = self.shared.inner.set_password(entry);
+        self.shared.update_cache(|cache| {
+            cache.remove(&entry.service, &entry.account);
+            0
+        });
+        result
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        let generation = {
+            let mut state = self.active()?;
+            if !caches_bypassed() {
+                if let Some(value) = state.cache.get(service, account) {
+                    self.record_cache(true);
+                    record_source(ReadSource::LockCache);
+                    return Ok(value.to_string());
+                }
+            }
+            state.generation
+        };
+        self.record_cache(false);
+
+        let value = self.shared.inner.get_password(service, account)?;
+        let mut state = self.shared.state.lock().unwrap();
+        // The keystore may have been locked, or the cache invalidated, while the backend was read
+        if !state.locked && state.generation == generation {
+            let evicted = state
+                .cache
+                .insert(service, account, Zeroizing::new(value.clone()));
+            self.shared.report(&state, evicted);
+        }
+        Ok(value)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        drop(self.active()?);
+        let result = self.shared.inner.delete_password(service, account);
+        self.shared.update_cache(|cache| {
+            cache.remove(service, account);
+            0
+        });
+        result
+    }
+
+    fn is_available(&self) -> bool {
+        self.shared.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.shared
+            .inner
+            .backend_info()
+            .with_detail("locked", self.is_locked())
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.shared.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        drop(self.active()?);
+        self.shared.inner.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        drop(self.active()?);
+        self.shared.inner.list_credentials(service)
+    }
+
+    /// Serves cached values and reads the rest from the backend in one batch
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        let mut values = Vec::with_capacity(keys.len());
+        let mut misses = Vec::new();
+        let generation = {
+            let mut state = self.active()?;
+            let bypass = caches_bypassed();
+            for (index, key) in keys.iter().enumerate() {
+                let value = if bypass {
+                    None
+                } else {
+                    state
+                        .cache
+                        .get(&key.service, &key.account)
+                        .map(|value| value.to_string())
+                };
+                self.record_cache(value.is_some());
+                if value.is_none() {
+                    misses.push(index);
+                }
+                values.push(value);
+            }
+            state.generation
+        };
+        if misses.is_empty() {
+            return Ok(values);
+        }
+
+        let missed: Vec<KeystoreKey> = misses.iter().map(|&index| keys[index].clone()).collect();
+        let read = self.shared.inner.get_many(&missed)?;
+        let mut state = self.shared.state.lock().unwrap();
+        for (index, value) in misses.into_iter().zip(read) {
+            // The keystore may have been locked, or the cache invalidated, while the backend was read
+            if let (Some(value), false) = (&value, state.locked || state.generation != generation) {
+                let key = &keys[index];
+                let evicted =
+                    state
+                        .cache
+                        .insert(&key.service, &key.account, Zeroizing::new(value.clone()));
+                self.shared.report(&state, evicted);
+            }
+            values[index] = value;
+        }
+        Ok(values)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        drop(self.active()?);
+        let result = self.shared.inner.set_many(entries);
+        self.shared.update_cache(|cache| {
+            for entry in entries {
+                cache.remove(&entry.service, &entry.account);
+            }
+            0
+        });
+        result
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        drop(self.active()?);
+        let result = self.shared.inner.delete_many(keys);
+        self.shared.update_cache(|cache| {
+            for key in keys {
+                cache.remove(&key.service, &key.account);
+            }
+            0
+        });
+        result
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        drop(self.active()?);
+        let result = self.shared.inner.delete_service(service);
+        // Also on error, since some accounts may already be gone
+        self.shared.update_cache(|cache| {
+            cache.remove_service(service);
+            0
+        });
+        result
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        drop(self.active()?);
+        let result = self.shared.inner.wipe_all();
+        self.shared.update_cache(|cache| {
+            cache.clear();
+            0
+        });
+        result
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        drop(self.active()?);
+        let result = self.shared.inner.swap(a, b);
+        // Also on error, since a best-effort swap may have written one side
+        self.shared.update_cache(|cache| {
+            cache.remove(&a.service, &a.account);
+            cache.remove(&b.service, &b.account);
+            0
+        });
+        result
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        drop(self.active()?);
+        self.shared.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        drop(self.active()?);
+        self.shared.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        drop(self.active()?);
+        let result = self.shared.inner.get_or_create(service, account, length);
+        self.shared.update_cache(|cache| {
+            cache.remove(service, account);
+            0
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::mpsc;
+
+    fn keystore() -> LockingKeystore {
+        let keystore = LockingKeystore::new(Box::new(MemoryKeystore::default()));
+        keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "oauth".to_string(),
+                value: "token".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        keystore
+    }
+
+    #[test]
+    fn test_lock_refuses_access_and_clears_cache() {
+        let keystore = keystore();
+        assert_eq!(keystore.get_password("twitch", "oauth").unwrap(), "token");
+        assert_eq!(keystore.shared.state.lock().unwrap().cache.len(), 1);
+
+        keystore.lock();
+        assert!(keystore.is_locked());
+        assert!(keystore.shared.state.lock().unwrap().cache.is_empty());
+        assert!(matches!(
+            keystore.get_password("twitch", "oauth"),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        assert!(keystore.delete_password("twitch", "oauth").is_err());
+        assert_eq!(keystore.backend_info().details["locked"], "true");
+
+        assert_eq!(keystore.unlock_mode().unwrap(), UnlockMode::Session);
+        keystore.unlock(None).unwrap();
+        assert_eq!(keystore.get_password("twitch", "oauth").unwrap(), "token");
+    }
+
+    #[test]
+    fn test_get_many_caches_what_it_reads() {
+        let keystore = keystore();
+        let key = |account: &str| KeystoreKey {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+        };
+        let keys = [key("missing"), key("oauth")];
+
+        assert_eq!(
+            keystore.get_many(&keys).unwrap(),
+            [None, Some("token".to_string())]
+        );
+        assert_eq!(keystore.shared.state.lock().unwrap().cache.len(), 1);
+        keystore.invalidate_cache();
+        assert!(keystore.shared.state.lock().unwrap().cache.is_empty());
+
+        keystore.lock();
+        assert!(matches!(
+            keystore.get_many(&keys),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+    }
+
+    #[test]
+    fn test_passphrase_unlock() {
+        let keystore = keystore();
+        keystore
+            .set_passphrase(None, Some("correct horse"))
+            .unwrap();
+        assert_eq!(keystore.unlock_mode().unwrap(), UnlockMode::Passphrase);
+
+        keystore.lock();
+        assert!(keystore.unlock(None).is_err());
+        assert!(keystore.unlock(Some("wrong")).is_err());
+        assert!(keystore.is_locked());
+        keystore.unlock(Some("correct horse")).unwrap();
+
+        // Changing or removing it needs the current passphrase
+        assert!(keystore.set_passphrase(Some("wrong"), None).is_err());
+        keystore
+            .set_passphrase(Some("correct horse"), None)
+            .unwrap();
+        assert_eq!(keystore.unlock_mode().unwrap(), UnlockMode::Session);
+    }
+
+    #[test]
+    fn test_verifier_is_rehashed_after_retuning() {
+        let keystore = keystore();
+        keystore
+            .set_passphrase(None, Some("correct horse"))
+            .unwrap();
+        let tuned = keystore.tune_kdf(Duration::from_millis(20)).unwrap();
+        // Tuning never weakens the defaults, so force a change to observe the rehash
+        let retuned = KdfParams {
+            iterations: tuned.iterations + 1,
+            ..tuned
+        };
+        retuned.store(&*keystore.shared.inner).unwrap();
+
+        keystore.lock();
+        keystore.unlock(Some("correct horse")).unwrap();
+        let verifier = keystore.verifier().unwrap().unwrap();
+        let hash = PasswordHash::new(&verifier).unwrap();
+        assert_eq!(KdfParams::from(&Params::try_from(&hash).unwrap()), retuned);
+        keystore.lock();
+        keystore.unlock(Some("correct horse")).unwrap();
+    }
+
+    #[test]
+    fn test_required_passphrase_is_enrolled_on_first_unlock() {
+        let keystore = keystore();
+        keystore.require_passphrase();
+        assert!(keystore.is_locked());
+        assert_eq!(keystore.unlock_mode().unwrap(), UnlockMode::Passphrase);
+        assert!(keystore.unlock(None).is_err());
+
+        keystore.unlock(Some("correct horse")).unwrap();
+        keystore.lock();
+        assert!(keystore.unlock(Some("other")).is_err());
+        keystore.unlock(Some("correct horse")).unwrap();
+        assert!(keystore
+            .set_passphrase(Some("correct horse"), None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_recovery_codes_replace_a_forgotten_passphrase_once() {
+        let keystore = keystore();
+        assert!(keystore.generate_recovery_codes("anything").is_err());
+        keystore
+            .set_passphrase(None, Some("correct horse"))
+            .unwrap();
+        assert!(keystore.generate_recovery_codes("wrong").is_err());
+        let codes = keystore.generate_recovery_codes("correct horse").unwrap();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(codes[0].len(), 19);
+
+        keystore.lock();
+        assert!(keystore.recover("0000-0000-0000-0000", "new").is_err());
+        // Typed in lower case without separators
+        let typed = codes[3].replace('-', "").to_lowercase();
+        assert_eq!(keystore.recover(&typed, "battery staple").unwrap(), 9);
+        assert!(!keystore.is_locked());
+        assert_eq!(keystore.get_password("twitch", "oauth").unwrap(), "token");
+
+        keystore.lock();
+        assert!(keystore.unlock(Some("correct horse")).is_err());
+        keystore.unlock(Some("battery staple")).unwrap();
+        assert!(keystore.recover(&codes[3], "again").is_err());
+
+        // A new set replaces the old; removing the passphrase removes the codes
+        let fresh = keystore.generate_recovery_codes("battery staple").unwrap();
+        keystore.lock();
+        assert!(keystore.recover(&codes[0], "again").is_err());
+        keystore.recover(&fresh[0], "again").unwrap();
+        keystore.set_passphrase(Some("again"), None).unwrap();
+        assert_eq!(keystore.recovery_codes_remaining().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_missing_verifier_fails_closed() {
+        let keystore = keystore();
+        keystore
+            .set_passphrase(None, Some("correct horse"))
+            .unwrap();
+        let codes = keystore.generate_recovery_codes("correct horse").unwrap();
+        keystore
+            .shared
+            .inner
+            .delete_password(LOCK_SERVICE, PASSPHRASE_ACCOUNT)
+            .unwrap();
+
+        keystore.lock();
+        assert_eq!(keystore.unlock_mode().unwrap(), UnlockMode::Passphrase);
+        assert!(keystore.unlock(None).is_err());
+        // Policy would otherwise enrol the first passphrase given
+        keystore.require_passphrase();
+        assert!(matches!(
+            keystore.unlock(Some("attacker")),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        assert!(keystore.is_locked());
+
+        keystore.recover(&codes[0], "battery staple").unwrap();
+        keystore.lock();
+        assert!(keystore.unlock(Some("attacker")).is_err());
+        keystore.unlock(Some("battery staple")).unwrap();
+    }
+
+    #[test]
+    fn test_idle_auto_lock_emits_events() {
+        let keystore = keystore();
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        keystore.on_state_change(move |event| tx.lock().unwrap().send(event).unwrap());
+
+        keystore.set_idle_timeout(Some(Duration::from_millis(50)));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            LockEvent {
+                state: LockState::Locked,
+                reason: LockReason::Idle,
+            }
+        );
+        assert!(keystore.is_locked());
+
+        keystore.set_idle_timeout(None);
+        keystore.unlock(None).unwrap();
+        assert_eq!(rx.recv().unwrap().state, LockState::Unlocked);
+        thread::sleep(Duration::from_millis(100));
+        assert!(!keystore.is_locked());
+    }
+}
@@ -0,0 +1,511 @@
+//! Prometheus metrics for keystore operations.
+//!
+//! [`MetricsKeystore`] wraps a backend and records a counter by operation and outcome plus a
+//! latency histogram for every call. [`crate::lock::LockingKeystore`] reports cache hits and misses
+//! into the same [`KeystoreMetrics`], along with the bytes, entries and evictions of each value
+//! cache, and the host refreshes the availability gauge before
+//! scraping, or leaves that to [`crate::probe::HealthProber`]. [`KeystoreMetrics::render`] produces the Prometheus text format for a `/metrics`
+//! endpoint. Labels carry operation names, error codes and the backend name, never services or
+//! accounts.
+//!
+//! Failed backend calls are also kept for [`ERROR_HISTORY`], so [`KeystoreMetrics::error_stats`]
+//! can count them over a recent window (say, the last hour) without a Prometheus server to query.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Latency buckets in seconds, from an in-memory hit to a slow DBus round-trip
+const LATENCY_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// How long failed calls are kept for [`KeystoreMetrics::error_stats`]
+pub const ERROR_HISTORY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Failed calls kept at most, oldest dropped first, so an error storm can't grow memory unbounded
+pub const MAX_ERROR_EVENTS: usize = 10_000;
+
+struct ErrorEvent {
+    at: Instant,
+    code: &'static str,
+    backend: String,
+}
+
+/// Failed backend calls with one error code on one backend within a window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorCount {
+    pub code: String,
+    pub backend: String,
+    pub count: usize,
+}
+
+pub struct KeystoreMetrics {
+    registry: Registry,
+    operations: IntCounterVec,
+    latency: HistogramVec,
+    cache: IntCounterVec,
+    cache_bytes: IntGaugeVec,
+    cache_entries: IntGaugeVec,
+    cache_evictions: IntCounterVec,
+    queue_depth: IntGaugeVec,
+    queue_in_flight: IntGaugeVec,
+    queue_rejected: IntCounterVec,
+    available: IntGaugeVec,
+    health_transitions: IntCounterVec,
+    errors: Mutex<VecDeque<ErrorEvent>>,
+}
+
+fn registration_error(e: prometheus::Error) -> KeystoreError {
+    KeystoreError::Platform(format!("Failed to register metric: {}", e))
+}
+
+impl KeystoreMetrics {
+    pub fn new() -> Result<Self, KeystoreError> {
+        let operations = IntCounterVec::new(
+            Opts::new(
+                "keystore_operations_total",
+                "Keystore operations by outcome",
+            ),
+            &["operation", "outcome"],
+        )
+        .map_err(registration_error)?;
+        let latency = HistogramVec::new(
+            HistogramOpts::new(
+                "keystore_operation_duration_seconds",
+                "Keystore operation latency",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["operation"],
+        )
+        .map_err(registration_error)?;
+        let cache = IntCounterVec::new(
+            Opts::new("keystore_cache_requests_total", "Value cache lookups"),
+            &["result"],
+        )
+        .map_err(registration_error)?;
+        let cache_bytes = IntGaugeVec::new(
+            Opts::new(
+                "keystore_cache_bytes",
+                "Bytes of decrypted values held by each cache",
+            ),
+            &["cache"],
+        )
+        .map_err(registration_error)?;
+        let cache_entries = IntGaugeVec::new(
+            Opts::new("keystore_cache_entries", "Entries held by each cache"),
+            &["cache"],
+        )
+        .map_err(registration_error)?;
+        let cache_evictions = IntCounterVec::new(
+            Opts::new(
+                "keystore_cache_evictions_total",
+                "Entries evicted to stay within the cache memory budget",
+            ),
+            &["cache"],
+        )
+        .map_err(registration_error)?;
+        let queue_depth = IntGaugeVec::new(
+            Opts::new(
+                "keystore_queue_depth",
+                "Operations waiting for a backend slot",
+            ),
+            &["backend"],
+        )
+        .map_err(registration_error)?;
+        let queue_in_flight = IntGaugeVec::new(
+            Opts::new(
+                "keystore_queue_in_flight",
+                "Operations running on the backend",
+            ),
+            &["backend"],
+        )
+        .map_err(registration_error)?;
+        let queue_rejected = IntCounterVec::new(
+            Opts::new(
+                "keystore_queue_rejected_total",
+                "Operations refused because the queue was full",
+            ),
+            &["backend"],
+        )
+        .map_err(registration_error)?;
+        let available = IntGaugeVec::new(
+            Opts::new(
+                "keystore_backend_available",
+                "1 if the backend is reachable",
+            ),
+            &["backend"],
+        )
+        .map_err(registration_error)?;
+        let health_transitions = IntCounterVec::new(
+            Opts::new(
+                "keystore_backend_health_transitions_total",
+                "Changes of backend health seen by the background prober, by new status",
+            ),
+            &["backend", "status"],
+        )
+        .map_err(registration_error)?;
+
+        let registry = Registry::new();
+        registry
+            .register(Box::new(operations.clone()))
+            .map_err(registration_error)?;
+        registry
+            .register(Box::new(latency.clone()))
+            .map_err(registration_error)?;
+        registry
+            .register(Box::new(cache.clone()))
+            .map_err(registration_error)?;
+        registry
+            .register(Box::new(cache_bytes.clone()))
+            .map_err(registration_error)?;
+        registry
+            .register(Box::new(cache_entries.clone()))
+            .map_err(registration_error)?;
+        registry
+            .register(Box::new(cache_evictions.clone()))
+            .map_err(registration_error)?;
+        registry
+            .register(Box::new(queue_depth.clone()))
+            .map_err(registration_error)?;
+        registry
+            .register(Box::new(queue_in_flight.clone()))
+            .map_err(registration_error)?;
+        registry
+            .register(Box::new(queue_rejected.clone()))
+            .map_err(registration_error)?;
+        registry
+            .register(Box::new(available.clone()))
+            .map_err(registration_error)?;
+        registry
+            .register(Box::new(health_transitions.clone()))
+            .map_err(registration_error)?;
+
+        Ok(Self {
+            registry,
+            operations,
+            latency,
+            cache,
+            cache_bytes,
+            cache_entries,
+            cache_evictions,
+            queue_depth,
+            queue_in_flight,
+            queue_rejected,
+            available,
+            health_transitions,
+            errors: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    pub fn observe<T>(
+        &self,
+        operation: &str,
+        result: &Result<T, KeystoreError>,
+        elapsed: Duration,
+    ) {
+        let outcome = match result {
+            Ok(_) => "ok",
+            Err(e) => e.code(),
+        };
+        self.operations
+            .with_label_values(&[operation, outcome])
+            .inc();
+        self.latency
+            .with_label_values(&[operation])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Keeps a failed call on `backend` for [`Self::error_stats`]. Missing entries are an ordinary
+    /// answer rather than a fault, so `KeyNotFound` isn't kept.
+    pub fn record_error(&self, backend: &str, error: &KeystoreError) {
+        if matches!(error, KeystoreError::KeyNotFound(_)) {
+            return;
+        }
+        let now = Instant::now();
+        let mut errors = self.errors.lock().unwrap_or_else(|e| e.into_inner());
+        while errors.front().is_some_and(|event| {
+            errors.len() >= MAX_ERROR_EVENTS || now.duration_since(event.at) > ERROR_HISTORY
+        }) {
+            errors.pop_front();
+        }
+        errors.push_back(ErrorEvent {
+            at: now,
+            code: error.code(),
+            backend: backend.to_string(),
+        });
+    }
+
+    /// Failed calls within the last `window` (at most [`ERROR_HISTORY`]) by error code and
+    /// backend, most frequent first
+    pub fn error_stats(&self, window: Duration) -> Vec<ErrorCount> {
+        let now = Instant::now();
+        let errors = self.errors.lock().unwrap_or_else(|e| e.into_inner());
+        let mut counts: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+        for event in errors
+            .iter()
+            .rev()
+            .take_while(|event| now.duration_since(event.at) < window)
+        {
+            *counts.entry((event.code, &event.backend)).or_default() += 1;
+        }
+        let mut stats: Vec<ErrorCount> = counts
+            .into_iter()
+            .map(|((code, backend), count)| ErrorCount {
+                code: code.to_string(),
+                backend: backend.to_string(),
+                count,
+            })
+            .collect();
+        stats.sort_by_key(|stat| std::cmp::Reverse(stat.count));
+        stats
+    }
+
+    pub fn record_cache(&self, hit: bool) {
+        self.cache
+            .with_label_values(&[if hit { "hit" } else { "miss" }])
+            .inc();
+    }
+
+    /// Current usage of the cache named `cache`, plus entries it just evicted
+    pub fn record_cache_usage(&self, cache: &str, bytes: usize, entries: usize, evicted: usize) {
+        self.cache_bytes
+            .with_label_values(&[cache])
+            .set(bytes as i64);
+        self.cache_entries
+            .with_label_values(&[cache])
+            .set(entries as i64);
+        if evicted > 0 {
+            self.cache_evictions
+                .with_label_values(&[cache])
+                .inc_by(evicted as u64);
+        }
+    }
+
+    /// Operations running on and waiting for `backend`
+    pub fn record_queue(&self, backend: &str, in_flight: usize, waiting: usize) {
+        self.queue_in_flight
+            .with_label_values(&[backend])
+            .set(in_flight as i64);
+        self.queue_depth
+            .with_label_values(&[backend])
+            .set(waiting as i64);
+    }
+
+    pub fn record_queue_rejection(&self, backend: &str) {
+        self.queue_rejected.with_label_values(&[backend]).inc();
+    }
+
+    pub fn set_available(&self, backend: &str, available: bool) {
+        self.available
+            .with_label_values(&[backend])
+            .set(available as i64);
+    }
+
+    /// A backend changing to `status`, as found by [`crate::probe::HealthProber`]
+    pub fn record_health_transition(&self, backend: &str, status: &str) {
+        self.health_transitions
+            .with_label_values(&[backend, status])
+            .inc();
+    }
+
+    /// Prometheus text exposition format
+    pub fn render(&self) -> Result<String, KeystoreError> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| {
+                KeystoreError::Serialization(format!("Failed to encode metrics: {}", e))
+            })?;
+        String::from_utf8(buffer).map_err(|e| KeystoreError::Serialization(e.to_string()))
+    }
+}
+
+/// Records every operation on `inner` into shared [`KeystoreMetrics`]
+pub struct MetricsKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    metrics: Arc<KeystoreMetrics>,
+}
+
+impl MetricsKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>, metrics: Arc<KeystoreMetrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    fn timed<T>(
+        &self,
+        operation: &str,
+        call: impl FnOnce() -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        let started = Instant::now();
+        let result = call();
+        self.metrics.observe(operation, &result, started.elapsed());
+        if let Err(e) = &result {
+            self.metrics
+                .record_error(&self.inner.backend_info().name, e);
+        }
+        result
+    }
+}
+
+impl KeystoreOperations for MetricsKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.timed("set", || self.inner.set_password(entry))
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.timed("get", || self.inner.get_password(service, account))
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.timed("delete", || self.inner.delete_password(service, account))
+    }
+
+    fn is_available(&self) -> bool {
+        let available = self.inner.is_available();
+        self.metrics
+            .set_available(&self.inner.backend_info().name, available);
+        available
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.timed("list", || self.inner.list_keys())
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.timed("list", || self.inner.list_credentials(service))
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        self.timed("get_many", || self.inner.get_many(keys))
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.timed("set_many", || self.inner.set_many(entries))
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.timed("delete_many", || self.inner.delete_many(keys))
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.timed("delete_service", || self.inner.delete_service(service))
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.timed("wipe_all", || self.inner.wipe_all())
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.timed("swap", || self.inner.swap(a, b))
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.timed("get_metadata", || self.inner.get_metadata(service, account))
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.timed("has_password", || self.inner.has_password(service, account))
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        self.timed("get_or_create", || {
+            self.inner.get_or_create(service, account, length)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    #[test]
+    fn test_operations_are_counted_without_identifiers() {
+        let metrics = Arc::new(KeystoreMetrics::new().unwrap());
+        let keystore =
+            MetricsKeystore::new(Box::new(MemoryKeystore::default()), Arc::clone(&metrics));
+
+        keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "bot-oauth".to_string(),
+                value: "token".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        keystore.get_password("twitch", "bot-oauth").unwrap();
+        assert!(keystore.get_password("twitch", "missing").is_err());
+        assert!(keystore.is_available());
+        metrics.record_cache(true);
+        metrics.record_cache_usage("lock", 42, 2, 1);
+
+        let text = metrics.render().unwrap();
+        assert!(text.contains(r#"keystore_operations_total{operation="get",outcome="ok"} 1"#));
+        assert!(text.contains(
+            r#"keystore_operations_total{operation="get",outcome="ERR_KEY_NOT_FOUND"} 1"#
+        ));
+        assert!(text.contains(r#"keystore_operation_duration_seconds_count{operation="get"} 2"#));
+        assert!(text.contains(r#"keystore_cache_requests_total{result="hit"} 1"#));
+        assert!(text.contains(r#"keystore_cache_bytes{cache="lock"} 42"#));
+        assert!(text.contains(r#"keystore_cache_evictions_total{cache="lock"} 1"#));
+        assert!(text.contains(r#"keystore_backend_available{backend="memory"} 1"#));
+        assert!(!text.contains("twitch"));
+        assert!(!text.contains("bot-oauth"));
+    }
+
+    #[test]
+    fn test_error_stats_count_recent_failures_by_code() {
+        let metrics = KeystoreMetrics::new().unwrap();
+        let timeout = KeystoreError::Platform("DBus call timed out".to_string());
+        for _ in 0..3 {
+            metrics.record_error("secret-service", &timeout);
+        }
+        metrics.record_error(
+            "secret-service",
+            &KeystoreError::Busy("queue full".to_string()),
+        );
+        metrics.record_error(
+            "secret-service",
+            &KeystoreError::KeyNotFound("x".to_string()),
+        );
+
+        let stats = metrics.error_stats(Duration::from_secs(3600));
+        assert_eq!(
+            stats,
+            [
+                ErrorCount {
+                    code: "ERR_PLATFORM".to_string(),
+                    backend: "secret-service".to_string(),
+                    count: 3,
+                },
+                ErrorCount {
+                    code: "ERR_BUSY".to_string(),
+                    backend: "secret-service".to_string(),
+                    count: 1,
+                },
+            ]
+        );
+        assert!(metrics.error_stats(Duration::ZERO).is_empty());
+    }
+}
@@ -0,0 +1,404 @@
+//! Mirrored writes to a second backend.
+//!
+//! [`MirroredKeystore`] keeps every entry in two backends, normally the platform keystore and the
+//! encrypted file, so losing one keeps the secrets. Writes and deletes go to the primary, then to
+//! the secondary. Reads go to the primary and fall back to the secondary when it misses the entry
+//! or fails. A write the secondary failed leaves the two apart; [`MirroredKeystore::check`] reports
+//! such divergence and [`MirroredKeystore::reconcile`] repairs it, the primary winning where both
+//! hold a value.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::verify::constant_time_equals;
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::collections::BTreeSet;
+use zeroize::Zeroizing;
+
+/// Where the two backends of a mirror differ, without values
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MirrorReport {
+    /// Entries missing from the secondary
+    pub only_primary: Vec<KeystoreKey>,
+    /// Entries missing from the primary
+    pub only_secondary: Vec<KeystoreKey>,
+    /// Entries stored with a different value in each
+    pub differing: Vec<KeystoreKey>,
+}
+
+impl MirrorReport {
+    pub fn is_consistent(&self) -> bool {
+        self.only_primary.is_empty() && self.only_secondary.is_empty() && self.differing.is_empty()
+    }
+}
+
+pub struct MirroredKeystore {
+    primary: Box<dyn KeystoreOperations>,
+    secondary: Box<dyn KeystoreOperations>,
+}
+
+impl MirroredKeystore {
+    pub fn new(
+        primary: Box<dyn KeystoreOperations>,
+        secondary: Box<dyn KeystoreOperations>,
+    ) -> Self {
+        Self { primary, secondary }
+    }
+
+    /// Mirrors `primary` into the encrypted file. Fails with `InvalidInput` when `primary` is the
+    /// encrypted file already.
+    #[cfg(feature = "backend-file")]
+    pub fn mirror_to_file(primary: Box<dyn KeystoreOperations>) -> Result<Self, KeystoreError> {
+        if primary.backend_info().name == "encrypted-file" {
+            return Err(KeystoreError::InvalidInput(
+                "The encrypted file cannot mirror itself".to_string(),
+            ));
+        }
+        let secondary = crate::resolution::BackendSourceKeystore::new(Box::new(
+            crate::platform::fallback::FallbackKeystore::new()?,
+        ));
+        Ok(Self::new(primary, Box::new(secondary)))
+    }
+
+    /// The backend the mirror falls back to
+    pub fn secondary(&self) -> &dyn KeystoreOperations {
+        self.secondary.as_ref()
+    }
+
+    /// Compares the entries of both backends, reading the values of those stored in both
+    pub fn check(&self) -> Result<MirrorReport, KeystoreError> {
+        let primary: BTreeSet<KeystoreKey> = self.primary.list_keys()?.into_iter().collect();
+        let secondary: BTreeSet<KeystoreKey> = self.secondary.list_keys()?.into_iter().collect();
+        let common: Vec<KeystoreKey> = primary.intersection(&secondary).cloned().collect();
+        let values = |backend: &dyn KeystoreOperations| -> Result<Vec<_>, KeystoreError> {
+            Ok(backend
+                .get_many(&common)?
+                .into_iter()
+                .map(|value| value.map(Zeroizing::new))
+                .collect())
+        };
+        let ours = values(self.primary.as_ref())?;
+        let theirs = values(self.secondary.as_ref())?;
+        let differing = common
+            .iter()
+            .zip(ours.iter().zip(&theirs))
+            .filter(|(_, values)| match values {
+                (Some(ours), Some(theirs)) => {
+                    !constant_time_equals(ours.as_bytes(), theirs.as_bytes())
+                }
+                // Removed while checking
+                _ => false,
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        Ok(MirrorReport {
+            only_primary: primary.difference(&secondary).cloned().collect(),
+            only_secondary: secondary.difference(&primary).cloned().collect(),
+            differing,
+        })
+    }
+
+    /// Copies what one backend misses from the other, and the primary's value over the
+    /// secondary's where they differ. Returns what [`Self::check`] found before repairing it.
+    pub fn reconcile(&self) -> Result<MirrorReport, KeystoreError> {
+        let report = self.check()?;
+        let to_secondary = report.only_primary.iter().chain(&report.differing);
+        for key in to_secondary {
+            copy(self.primary.as_ref(), self.secondary.as_ref(), key)?;
+        }
+        for key in &report.only_secondary {
+            copy(self.secondary.as_ref(), self.primary.as_ref(), key)?;
+        }
+        Ok(report)
+    }
+}
+
+/// Copies `key` from `from` to `to` with its label, comment and expiry, skipping entries removed
+/// in between
+fn copy(
+    from: &dyn KeystoreOperations,
+    to: &dyn KeystoreOperations,
+    key: &KeystoreKey,
+) -> Result<(), KeystoreError> {
+    let value = match from.get_password(&key.service, &key.account) {
+        Ok(value) => Zeroizing::new(value),
+        Err(KeystoreError::KeyNotFound(_)) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    // Metadata is a courtesy; a backend that keeps none still gets the value
+    let metadata = from
+        .get_metadata(&key.service, &key.account)
+        .unwrap_or_default();
+    to.set_password(&KeystoreEntry {
+        service: key.service.clone(),
+        account: key.account.clone(),
+        value: value.to_string(),
+        label: metadata.label,
+        comment: metadata.comment,
+        expires_at: metadata.expires_at,
+    })
+}
+
+/// Errors after which a read is retried on the secondary
+fn falls_back(error: &KeystoreError) -> bool {
+    matches!(
+        error,
+        KeystoreError::KeyNotFound(_)
+            | KeystoreError::Platform(_)
+            | KeystoreError::KeyringLocked(_)
+            | KeystoreError::InteractionRequired(_)
+            | KeystoreError::Timeout(_)
+            | KeystoreError::Transient(_)
+    )
+}
+
+impl KeystoreOperations for MirroredKeystore {
+    /// Fails if either backend does; a failed mirror write leaves the value in the primary only
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.primary.set_password(entry)?;
+        self.secondary.set_password(entry).map_err(|e| {
+            KeystoreError::Platform(format!(
+                "Stored in {} but not in its mirror: {}",
+                self.primary.backend_info().name,
+                e
+            ))
+        })
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        match self.primary.get_password(service, account) {
+            Err(e) if falls_back(&e) => self.secondary.get_password(service, account).or(Err(e)),
+            result => result,
+        }
+    }
+
+    /// `KeyNotFound` only when neither backend held the entry
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let primary = self.primary.delete_password(service, account);
+        let secondary = self.secondary.delete_password(service, account);
+        match (primary, secondary) {
+            (Err(KeystoreError::KeyNotFound(_)), Ok(())) => Ok(()),
+            (Ok(()), Err(KeystoreError::KeyNotFound(_))) => Ok(()),
+            (Err(e), _) | (Ok(()), Err(e)) => Err(e),
+            (Ok(()), Ok(())) => Ok(()),
+        }
+    }
+
+    /// Available while either backend is
+    fn is_available(&self) -> bool {
+        self.primary.is_available() || self.secondary.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.primary
+            .backend_info()
+            .with_detail("mirror", self.secondary.backend_info().name)
+    }
+
+    /// The smaller limit, as every value goes to both
+    fn max_value_size(&self) -> Option<usize> {
+        match (
+            self.primary.max_value_size(),
+            self.secondary.max_value_size(),
+        ) {
+            (Some(ours), Some(theirs)) => Some(ours.min(theirs)),
+            (ours, theirs) => ours.or(theirs),
+        }
+    }
+
+    /// Entries of either backend
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut keys = self.primary.list_keys()?;
+        keys.extend(self.secondary.list_keys()?);
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let mut accounts = self.primary.list_credentials(service)?;
+        accounts.extend(self.secondary.list_credentials(service)?);
+        accounts.sort();
+        accounts.dedup();
+        Ok(accounts)
+    }
+
+    /// Fails if either backend does, like [`Self::set_password`]
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.primary.set_many(entries)?;
+        self.secondary.set_many(entries).map_err(|e| {
+            KeystoreError::Platform(format!(
+                "Stored in {} but not in its mirror: {}",
+                self.primary.backend_info().name,
+                e
+            ))
+        })
+    }
+
+    /// The keys either backend held
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut removed = self.primary.delete_many(keys)?;
+        removed.extend(self.secondary.delete_many(keys)?);
+        removed.sort();
+        removed.dedup();
+        Ok(removed)
+    }
+
+    /// Swapped in each backend on its own terms; a failure in the secondary leaves the two apart
+    /// like a failed mirror write
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.primary.swap(a, b)?;
+        self.secondary.swap(a, b).map_err(|e| {
+            KeystoreError::Platform(format!(
+                "Swapped in {} but not in its mirror: {}",
+                self.primary.backend_info().name,
+                e
+            ))
+        })
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        match self.primary.get_metadata(service, account) {
+            Err(e) if falls_back(&e) => self.secondary.get_metadata(service, account).or(Err(e)),
+            result => result,
+        }
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        match self.primary.has_password(service, account) {
+            Ok(false) => self.secondary.has_password(service, account).or(Ok(false)),
+            Err(e) if falls_back(&e) => self.secondary.has_password(service, account).or(Err(e)),
+            result => result,
+        }
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        let value = self.primary.get_or_create(service, account, length)?;
+        // Copied over unless the mirror already holds it, so a plain read doesn't rewrite the file
+        match self
+            .secondary
+            .get_password(service, account)
+            .map(Zeroizing::new)
+        {
+            Ok(mirrored) if constant_time_equals(mirrored.as_bytes(), value.as_bytes()) => {
+                Ok(value)
+            }
+            _ => {
+                self.secondary
+                    .set_password(&KeystoreEntry {
+                        service: service.to_string(),
+                        account: account.to_string(),
+                        value: value.clone(),
+                        ..Default::default()
+                    })
+                    .map_err(|e| {
+                        KeystoreError::Platform(format!(
+                            "Stored in {} but not in its mirror: {}",
+                            self.primary.backend_info().name,
+                            e
+                        ))
+                    })?;
+                Ok(value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::Arc;
+
+    fn entry(account: &str, value: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn key(account: &str) -> KeystoreKey {
+        KeystoreKey {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_reconcile_repairs_divergence() {
+        let primary = Arc::new(MemoryKeystore::default());
+        let secondary = Arc::new(MemoryKeystore::default());
+        let mirror = MirroredKeystore::new(
+            Box::new(Arc::clone(&primary)),
+            Box::new(Arc::clone(&secondary)),
+        );
+        mirror.set_password(&entry("bot", "one")).unwrap();
+        assert_eq!(secondary.get_password("twitch", "bot").unwrap(), "one");
+        assert!(mirror.check().unwrap().is_consistent());
+
+        primary.set_password(&entry("bot", "two")).unwrap();
+        primary
+            .set_password(&entry("broadcaster", "three"))
+            .unwrap();
+        secondary.set_password(&entry("editor", "four")).unwrap();
+        // Missing from the primary, read from the mirror
+        assert_eq!(mirror.get_password("twitch", "editor").unwrap(), "four");
+
+        let report = mirror.reconcile().unwrap();
+        assert_eq!(report.only_primary, [key("broadcaster")]);
+        assert_eq!(report.only_secondary, [key("editor")]);
+        assert_eq!(report.differing, [key("bot")]);
+        assert!(mirror.check().unwrap().is_consistent());
+        assert_eq!(secondary.get_password("twitch", "bot").unwrap(), "two");
+        assert_eq!(primary.get_password("twitch", "editor").unwrap(), "four");
+
+        mirror.delete_password("twitch", "bot").unwrap();
+        assert!(secondary.get_password("twitch", "bot").is_err());
+        assert!(matches!(
+            mirror.delete_password("twitch", "bot"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+    }
+
+    #[cfg(feature = "backend-file")]
+    #[test]
+    fn test_reconcile_keeps_metadata() {
+        use crate::crypto::AesGcmKey;
+        use crate::platform::fallback::FallbackKeystore;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let open = |name: &str| {
+            Arc::new(
+                FallbackKeystore::open(dir.path().join(name), AesGcmKey::generate().unwrap())
+                    .unwrap(),
+            )
+        };
+        let primary = open("primary.fallback");
+        let secondary = open("secondary.fallback");
+        let mirror = MirroredKeystore::new(
+            Box::new(Arc::clone(&primary)),
+            Box::new(Arc::clone(&secondary)),
+        );
+        primary
+            .set_password(&KeystoreEntry {
+                label: Some("Chat bot".to_string()),
+                comment: Some("Rotated weekly".to_string()),
+                expires_at: Some(u64::MAX),
+                ..entry("bot", "token")
+            })
+            .unwrap();
+
+        mirror.reconcile().unwrap();
+        let metadata = secondary.get_metadata("twitch", "bot").unwrap();
+        assert_eq!(metadata.label.as_deref(), Some("Chat bot"));
+        assert_eq!(metadata.comment.as_deref(), Some("Rotated weekly"));
+        assert_eq!(metadata.expires_at, Some(u64::MAX));
+    }
+}
@@ -0,0 +1,208 @@
+//! Service names prefixed with a namespace, so builds sharing a machine keep apart.
+//!
+//! A dev build and a production build of the same app store under the same service names and
+//! overwrite each other's credentials. [`NamespacedKeystore`] stores `twitch` as `dev::twitch`
+//! and strips the prefix again on the way out. Listings and `wipe_all` cover the namespace only;
+//! entries outside it can't be reached at all.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+pub const NAMESPACE_SEPARATOR: &str = "::";
+
+pub struct NamespacedKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    namespace: String,
+    /// `namespace` followed by the separator
+    prefix: String,
+}
+
+impl NamespacedKeystore {
+    /// Fails with `InvalidInput` for an empty namespace or one containing the separator
+    pub fn new(
+        inner: Box<dyn KeystoreOperations>,
+        namespace: impl Into<String>,
+    ) -> Result<Self, KeystoreError> {
+        let namespace = namespace.into();
+        if namespace.is_empty() || namespace.contains(NAMESPACE_SEPARATOR) {
+            return Err(KeystoreError::InvalidInput(format!(
+                "Invalid namespace {:?}: must be non-empty and not contain {}",
+                namespace, NAMESPACE_SEPARATOR
+            )));
+        }
+        let prefix = format!("{}{}", namespace, NAMESPACE_SEPARATOR);
+        Ok(Self {
+            inner,
+            namespace,
+            prefix,
+        })
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    fn service(&self, service: &str) -> String {
+        format!("{}{}", self.prefix, service)
+    }
+
+    fn key(&self, key: &KeystoreKey) -> KeystoreKey {
+        KeystoreKey {
+            service: self.service(&key.service),
+            account: key.account.clone(),
+        }
+    }
+
+    /// `key` as seen inside the namespace; `None` if it's outside
+    fn strip(&self, key: KeystoreKey) -> Option<KeystoreKey> {
+        let service = key.service.strip_prefix(&self.prefix)?.to_string();
+        Some(KeystoreKey {
+            service,
+            account: key.account,
+        })
+    }
+
+    fn entry(&self, entry: &KeystoreEntry) -> KeystoreEntry {
+        KeystoreEntry {
+            service: self.service(&entry.service),
+            ..entry.clone()
+        }
+    }
+}
+
+impl KeystoreOperations for NamespacedKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.inner.set_password(&self.entry(entry))
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.inner.get_password(&self.service(service), account)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.inner.delete_password(&self.service(service), account)
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner
+            .backend_info()
+            .with_detail("namespace", &self.namespace)
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        Ok(self
+            .inner
+            .list_keys()?
+            .into_iter()
+            .filter_map(|key| self.strip(key))
+            .collect())
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.list_credentials(&self.service(service))
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        let keys: Vec<_> = keys.iter().map(|key| self.key(key)).collect();
+        self.inner.get_many(&keys)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        let entries: Vec<_> = entries.iter().map(|entry| self.entry(entry)).collect();
+        self.inner.set_many(&entries)
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let keys: Vec<_> = keys.iter().map(|key| self.key(key)).collect();
+        Ok(self
+            .inner
+            .delete_many(&keys)?
+            .into_iter()
+            .filter_map(|key| self.strip(key))
+            .collect())
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.delete_service(&self.service(service))
+    }
+
+    /// Deletes the namespace's entries, leaving everything else alone
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let keys = self.list_keys()?;
+        self.delete_many(&keys)
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.inner.swap(&self.key(a), &self.key(b))
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.inner.get_metadata(&self.service(service), account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.inner.has_password(&self.service(service), account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        self.inner
+            .get_or_create(&self.service(service), account, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_namespaces_keep_apart() {
+        let backend = Arc::new(MemoryKeystore::default());
+        let dev = NamespacedKeystore::new(Box::new(Arc::clone(&backend)), "dev").unwrap();
+        let prod = NamespacedKeystore::new(Box::new(Arc::clone(&backend)), "prod").unwrap();
+        for (keystore, value) in [(&dev, "dev-token"), (&prod, "prod-token")] {
+            keystore
+                .set_password(&KeystoreEntry {
+                    service: "twitch".to_string(),
+                    account: "bot".to_string(),
+                    value: value.to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+
+        assert_eq!(dev.get_password("twitch", "bot").unwrap(), "dev-token");
+        assert_eq!(
+            backend.get_password("prod::twitch", "bot").unwrap(),
+            "prod-token"
+        );
+        assert_eq!(
+            dev.list_keys().unwrap(),
+            vec![KeystoreKey {
+                service: "twitch".to_string(),
+                account: "bot".to_string(),
+            }]
+        );
+        assert_eq!(dev.wipe_all().unwrap().len(), 1);
+        assert_eq!(prod.get_password("twitch", "bot").unwrap(), "prod-token");
+        assert!(matches!(
+            NamespacedKeystore::new(Box::new(Arc::clone(&backend)), "a::b"),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+    }
+}
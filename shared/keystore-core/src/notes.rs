@@ -0,0 +1,256 @@
+//! Secure notes: multi-kilobyte text such as run-of-show notes with embedded credentials.
+//!
+//! Note bodies are stored through [`BlobStore`], so they are split across entries on backends with
+//! a per-entry size limit (Windows Credential Manager). A separate index entry lists titles, sizes
+//! and timestamps so notes can be listed and searched by title without decrypting every body.
+
+use crate::blob::BlobStore;
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Service under which note bodies are stored, one account per title
+pub const NOTES_SERVICE: &str = "streaming-enhancement:notes";
+
+pub(crate) const INDEX_SERVICE: &str = "streaming-enhancement:notes-index";
+pub(crate) const INDEX_ACCOUNT: &str = "index";
+
+/// Upper bound on a single note body, in UTF-8 bytes
+pub const MAX_NOTE_SIZE: usize = 256 * 1024;
+
+/// Serializes index read-modify-write cycles within the process
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Listing entry for a note; never contains the body
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecureNoteInfo {
+    pub title: String,
+    /// Body length in UTF-8 bytes
+    pub size: usize,
+    /// Milliseconds since the Unix epoch
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+pub struct SecureNotes<'a> {
+    blobs: BlobStore<'a>,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl<'a> SecureNotes<'a> {
+    pub fn new(backend: &'a dyn KeystoreOperations) -> Self {
+        Self {
+            blobs: BlobStore::new(backend),
+        }
+    }
+
+    fn load_index(&self) -> Result<Vec<SecureNoteInfo>, KeystoreError> {
+        match self.blobs.get_structured(INDEX_SERVICE, INDEX_ACCOUNT) {
+            Ok(index) => Ok(index),
+            Err(KeystoreError::KeyNotFound(_)) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save_index(&self, index: &[SecureNoteInfo]) -> Result<(), KeystoreError> {
+        self.blobs
+            .put_structured(INDEX_SERVICE, INDEX_ACCOUNT, index)
+    }
+
+    fn lock_index(&self) -> Result<std::sync::MutexGuard<'static, ()>, KeystoreError> {
+        INDEX_LOCK
+            .lock()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))
+    }
+
+    /// Creates or replaces the note titled `title`
+    pub fn save(&self, title: &str, body: &str) -> Result<SecureNoteInfo, KeystoreError> {
+        if title.trim().is_empty() {
+            return Err(KeystoreError::InvalidInput(
+                "Note title must not be empty".to_string(),
+            ));
+        }
+        if body.len() > MAX_NOTE_SIZE {
+            return Err(KeystoreError::InvalidInput(format!(
+                "Note is {} bytes; the limit is {} bytes",
+                body.len(),
+                MAX_NOTE_SIZE
+            )));
+        }
+
+        let _guard = self.lock_index()?;
+        let mut index = self.load_index()?;
+
+        self.blobs.put(NOTES_SERVICE, title, body)?;
+
+        let now = now_millis();
+        let info = match index.iter_mut().find(|info| info.title == title) {
+            Some(info) => {
+                info.size = body.len();
+                info.updated_at = now;
+                info.clone()
+            }
+            None => {
+                let info = SecureNoteInfo {
+                    title: title.to_string(),
+                    size: body.len(),
+                    created_at: now,
+                    updated_at: now,
+                };
+                index.push(info.clone());
+                info
+            }
+        };
+
+        self.save_index(&index)?;
+        Ok(info)
+    }
+
+    pub fn get(&self, title: &str) -> Result<String, KeystoreError> {
+        self.blobs.get(NOTES_SERVICE, title)
+    }
+
+    pub fn delete(&self, title: &str) -> Result<(), KeystoreError> {
+        let _guard = self.lock_index()?;
+        let mut index = self.load_index()?;
+
+        let result = self.blobs.delete(NOTES_SERVICE, title);
+        // Drop stale index rows even when the body was already gone
+        let before = index.len();
+        index.retain(|info| info.title != title);
+        if index.len() != before {
+            self.save_index(&index)?;
+        }
+
+        result
+    }
+
+    /// All notes, sorted by title
+    pub fn list(&self) -> Result<Vec<SecureNoteInfo>, KeystoreError> {
+        let mut index = self.load_index()?;
+        index.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(index)
+    }
+
+    /// Notes whose title contains `query`, case-insensitively. With `include_body` the bodies are
+    /// decrypted and searched as well.
+    pub fn search(
+        &self,
+        query: &str,
+        include_body: bool,
+    ) -> Result<Vec<SecureNoteInfo>, KeystoreError> {
+        let query = query.to_lowercase();
+        let mut matches = Vec::new();
+
+        for info in self.list()? {
+            let matched = info.title.to_lowercase().contains(&query)
+                || (include_body
+                    && self
+                        .get(&info.title)
+                        .map(|body| body.to_lowercase().contains(&query))
+                        .unwrap_or(false));
+            if matched {
+                matches.push(info);
+            }
+        }
+
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    fn windows_like() -> MemoryKeystore {
+        MemoryKeystore {
+            max_value_size: Some(2560),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_save_and_get_multi_kilobyte_note() {
+        let backend = windows_like();
+        let notes = SecureNotes::new(&backend);
+        let body = "Scene 3: switch to BRB, OBS websocket pw = hunter2\n".repeat(200);
+
+        let info = notes.save("run of show", &body).unwrap();
+        assert_eq!(info.size, body.len());
+        assert_eq!(notes.get("run of show").unwrap(), body);
+    }
+
+    #[test]
+    fn test_list_and_search() {
+        let backend = windows_like();
+        let notes = SecureNotes::new(&backend);
+
+        notes.save("Sponsor reads", "code STREAM10").unwrap();
+        notes.save("Raid targets", "friendly channels").unwrap();
+
+        let titles: Vec<_> = notes.list().unwrap().into_iter().map(|i| i.title).collect();
+        assert_eq!(titles, vec!["Raid targets", "Sponsor reads"]);
+
+        assert_eq!(notes.search("sponsor", false).unwrap().len(), 1);
+        assert!(notes.search("stream10", false).unwrap().is_empty());
+        assert_eq!(
+            notes.search("stream10", true).unwrap()[0].title,
+            "Sponsor reads"
+        );
+    }
+
+    #[test]
+    fn test_update_keeps_created_at() {
+        let backend = MemoryKeystore::default();
+        let notes = SecureNotes::new(&backend);
+
+        let first = notes.save("todo", "one").unwrap();
+        let second = notes.save("todo", "one two").unwrap();
+
+        assert_eq!(second.created_at, first.created_at);
+        assert_eq!(second.size, 7);
+        assert_eq!(notes.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_delete_removes_body_and_index_row() {
+        let backend = windows_like();
+        let notes = SecureNotes::new(&backend);
+
+        notes.save("temp", &"x".repeat(10_000)).unwrap();
+        notes.delete("temp").unwrap();
+
+        assert!(notes.list().unwrap().is_empty());
+        match notes.get("temp") {
+            Err(KeystoreError::KeyNotFound(_)) => (),
+            other => panic!("Expected KeyNotFound error, got {:?}", other),
+        }
+        // Only the (now empty) index remains
+        assert_eq!(backend.entries.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_invalid_notes() {
+        let backend = MemoryKeystore::default();
+        let notes = SecureNotes::new(&backend);
+
+        assert!(matches!(
+            notes.save("  ", "body"),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            notes.save("huge", &"x".repeat(MAX_NOTE_SIZE + 1)),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+    }
+}
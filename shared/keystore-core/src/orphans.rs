@@ -0,0 +1,400 @@
+//! Records left behind with no entry to own them.
+//!
+//! A partial failure can leave debris: blob chunks (how large values are split across Windows
+//! credentials and other size-limited backends) whose envelope was never written or is gone,
+//! aliases of deleted entries, and rows of the notes and certificate indexes, protection marks,
+//! provenance records or bundle members describing entries that no longer exist.
+//! [`find_orphans`] lists them without changing anything, as a dry run; [`remove_orphans`] then
+//! deletes the chunks and drops the rows. Write-once marks are left out, as they may be set before
+//! their entry is written.
+//!
+//! The index tables are edited below any layer that caches them, so reload
+//! [`crate::alias::AliasKeystore`], [`crate::protect::ProtectedKeystore`] and
+//! [`crate::provenance::ProvenanceKeystore`] afterwards. A blob being written while the pass runs
+//! has its chunks stored before its envelope; run it when the keystore is idle.
+
+use crate::alias::{ALIAS_ACCOUNT, ALIAS_SERVICE};
+use crate::blob::BlobStore;
+use crate::bundle::{load_bundles, lock_bundles, BUNDLE_ACCOUNT, BUNDLE_SERVICE};
+use crate::certs::{self, CertificateInfo, CERTIFICATES_SERVICE};
+use crate::error::KeystoreError;
+use crate::inventory::EntryKind;
+use crate::notes::{self, SecureNoteInfo, NOTES_SERVICE};
+use crate::platform::KeystoreOperations;
+use crate::protect::{PROTECTED_ACCOUNT, PROTECTED_SERVICE};
+use crate::provenance::{EntryProvenance, PROVENANCE_ACCOUNT, PROVENANCE_SERVICE};
+use crate::KeystoreKey;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OrphanKind {
+    /// A blob chunk entry no envelope refers to
+    Chunk,
+    /// An alias whose target is gone
+    Alias,
+    NoteIndexRow,
+    CertificateIndexRow,
+    /// A protection mark on a missing entry
+    ProtectionMark,
+    ProvenanceRecord,
+    /// A bundle listing a missing entry
+    BundleMember,
+}
+
+impl OrphanKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrphanKind::Chunk => "chunk",
+            OrphanKind::Alias => "alias",
+            OrphanKind::NoteIndexRow => "note-index-row",
+            OrphanKind::CertificateIndexRow => "certificate-index-row",
+            OrphanKind::ProtectionMark => "protection-mark",
+            OrphanKind::ProvenanceRecord => "provenance-record",
+            OrphanKind::BundleMember => "bundle-member",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Result<Self, KeystoreError> {
+        [
+            OrphanKind::Chunk,
+            OrphanKind::Alias,
+            OrphanKind::NoteIndexRow,
+            OrphanKind::CertificateIndexRow,
+            OrphanKind::ProtectionMark,
+            OrphanKind::ProvenanceRecord,
+            OrphanKind::BundleMember,
+        ]
+        .into_iter()
+        .find(|k| k.as_str() == kind)
+        .ok_or_else(|| KeystoreError::InvalidInput(format!("Unknown orphan kind: {}", kind)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Orphan {
+    pub kind: OrphanKind,
+    /// The chunk entry or alias itself; for index rows, marks and records, the missing entry they
+    /// describe
+    pub key: KeystoreKey,
+    /// The entry that should have owned it
+    pub owner: KeystoreKey,
+}
+
+impl Orphan {
+    pub fn describe(&self) -> String {
+        match self.kind {
+            OrphanKind::Chunk => format!(
+                "Chunk {}:{} belongs to no blob at {}:{}",
+                self.key.service, self.key.account, self.owner.service, self.owner.account
+            ),
+            OrphanKind::Alias => format!(
+                "Alias {}:{} points at missing {}:{}",
+                self.key.service, self.key.account, self.owner.service, self.owner.account
+            ),
+            kind => format!(
+                "{} for missing {}:{}",
+                kind.as_str(),
+                self.key.service,
+                self.key.account
+            ),
+        }
+    }
+}
+
+fn key(service: &str, account: &str) -> KeystoreKey {
+    KeystoreKey {
+        service: service.to_string(),
+        account: account.to_string(),
+    }
+}
+
+/// A table stored with [`BlobStore::put_structured`], empty when there is none
+fn load<T: DeserializeOwned + Default>(
+    blobs: &BlobStore,
+    service: &str,
+    account: &str,
+) -> Result<T, KeystoreError> {
+    match blobs.get_structured(service, account) {
+        Ok(table) => Ok(table),
+        Err(KeystoreError::KeyNotFound(_)) => Ok(T::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Every orphan in `backend`, sorted by kind then key. Changes nothing.
+pub fn find_orphans(backend: &dyn KeystoreOperations) -> Result<Vec<Orphan>, KeystoreError> {
+    let existing: BTreeSet<KeystoreKey> = backend.list_keys()?.into_iter().collect();
+    let blobs = BlobStore::new(backend);
+    let mut orphans = Vec::new();
+
+    // Chunk counts of the envelopes chunks claim, read once per envelope
+    let mut chunk_counts: BTreeMap<KeystoreKey, usize> = BTreeMap::new();
+    for chunk in &existing {
+        if EntryKind::classify(&chunk.service, &chunk.account) != EntryKind::Chunk {
+            continue;
+        }
+        let Some((parent, index)) = chunk.account.rsplit_once(":chunk:") else {
+            continue;
+        };
+        let owner = key(&chunk.service, parent);
+        let chunks = match chunk_counts.get(&owner) {
+            Some(&chunks) => chunks,
+            None => {
+                let chunks = match blobs.chunk_count(&owner.service, &owner.account) {
+                    Ok(chunks) => chunks,
+                    // No envelope, or something that isn't one
+                    Err(KeystoreError::KeyNotFound(_)) | Err(KeystoreError::Serialization(_)) => 0,
+                    Err(e) => return Err(e),
+                };
+                chunk_counts.insert(owner.clone(), chunks);
+                chunks
+            }
+        };
+        if index.parse::<usize>().map_or(true, |index| index >= chunks) {
+            orphans.push(Orphan {
+                kind: OrphanKind::Chunk,
+                key: chunk.clone(),
+                owner,
+            });
+        }
+    }
+
+    let aliases: Vec<(KeystoreKey, KeystoreKey)> = load(&blobs, ALIAS_SERVICE, ALIAS_ACCOUNT)?;
+    for (alias, target) in aliases {
+        if !existing.contains(&target) {
+            orphans.push(Orphan {
+                kind: OrphanKind::Alias,
+                key: alias,
+                owner: target,
+            });
+        }
+    }
+
+    let mut missing = |kind: OrphanKind, keys: Vec<KeystoreKey>| {
+        for key in keys {
+            if !existing.contains(&key) {
+                orphans.push(Orphan {
+                    kind,
+                    owner: key.clone(),
+                    key,
+                });
+            }
+        }
+    };
+    let notes: Vec<SecureNoteInfo> = load(&blobs, notes::INDEX_SERVICE, notes::INDEX_ACCOUNT)?;
+    missing(
+        OrphanKind::NoteIndexRow,
+        notes
+            .into_iter()
+            .map(|note| key(NOTES_SERVICE, &note.title))
+            .collect(),
+    );
+    let certificates: Vec<CertificateInfo> =
+        load(&blobs, certs::INDEX_SERVICE, certs::INDEX_ACCOUNT)?;
+    missing(
+        OrphanKind::CertificateIndexRow,
+        certificates
+            .into_iter()
+            .map(|cert| key(CERTIFICATES_SERVICE, &cert.name))
+            .collect(),
+    );
+    let protected: BTreeSet<KeystoreKey> = load(&blobs, PROTECTED_SERVICE, PROTECTED_ACCOUNT)?;
+    missing(OrphanKind::ProtectionMark, protected.into_iter().collect());
+    let provenance: Vec<EntryProvenance> = load(&blobs, PROVENANCE_SERVICE, PROVENANCE_ACCOUNT)?;
+    missing(
+        OrphanKind::ProvenanceRecord,
+        provenance
+            .into_iter()
+            .map(|record| key(&record.service, &record.account))
+            .collect(),
+    );
+    let bundles = load_bundles(&blobs)?;
+    missing(
+        OrphanKind::BundleMember,
+        bundles.entries.into_values().collect(),
+    );
+
+    orphans.sort_by(|a, b| (a.kind, &a.key).cmp(&(b.kind, &b.key)));
+    Ok(orphans)
+}
+
+/// Loads the table at `service`/`account`, drops the items `remove` selects and saves it if any
+/// were; returns how many
+fn prune<T: Serialize + DeserializeOwned>(
+    blobs: &BlobStore,
+    service: &str,
+    account: &str,
+    mut remove: impl FnMut(&T) -> bool,
+) -> Result<usize, KeystoreError> {
+    let mut table: Vec<T> = load(blobs, service, account)?;
+    let before = table.len();
+    table.retain(|item| !remove(item));
+    let removed = before - table.len();
+    if removed > 0 {
+        blobs.put_structured(service, account, &table)?;
+    }
+    Ok(removed)
+}
+
+/// Removes `orphans`, as found by [`find_orphans`], and returns how many were still there. Each
+/// table is rewritten once.
+pub fn remove_orphans(
+    backend: &dyn KeystoreOperations,
+    orphans: &[Orphan],
+) -> Result<usize, KeystoreError> {
+    let blobs = BlobStore::new(backend);
+    let of = |kind: OrphanKind| -> BTreeSet<&KeystoreKey> {
+        orphans
+            .iter()
+            .filter(|orphan| orphan.kind == kind)
+            .map(|orphan| &orphan.key)
+            .collect()
+    };
+    let mut removed = 0;
+
+    for chunk in of(OrphanKind::Chunk) {
+        match backend.delete_password(&chunk.service, &chunk.account) {
+            Ok(()) => removed += 1,
+            Err(KeystoreError::KeyNotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let aliases = of(OrphanKind::Alias);
+    if !aliases.is_empty() {
+        removed += prune(
+            &blobs,
+            ALIAS_SERVICE,
+            ALIAS_ACCOUNT,
+            |(alias, _): &(KeystoreKey, KeystoreKey)| aliases.contains(alias),
+        )?;
+    }
+    let notes = of(OrphanKind::NoteIndexRow);
+    if !notes.is_empty() {
+        removed += prune(
+            &blobs,
+            notes::INDEX_SERVICE,
+            notes::INDEX_ACCOUNT,
+            |note: &SecureNoteInfo| notes.contains(&key(NOTES_SERVICE, &note.title)),
+        )?;
+    }
+    let certificates = of(OrphanKind::CertificateIndexRow);
+    if !certificates.is_empty() {
+        removed += prune(
+            &blobs,
+            certs::INDEX_SERVICE,
+            certs::INDEX_ACCOUNT,
+            |cert: &CertificateInfo| certificates.contains(&key(CERTIFICATES_SERVICE, &cert.name)),
+        )?;
+    }
+    let marks = of(OrphanKind::ProtectionMark);
+    if !marks.is_empty() {
+        removed += prune(
+            &blobs,
+            PROTECTED_SERVICE,
+            PROTECTED_ACCOUNT,
+            |protected: &KeystoreKey| marks.contains(protected),
+        )?;
+    }
+    let records = of(OrphanKind::ProvenanceRecord);
+    if !records.is_empty() {
+        removed += prune(
+            &blobs,
+            PROVENANCE_SERVICE,
+            PROVENANCE_ACCOUNT,
+            |record: &EntryProvenance| records.contains(&key(&record.service, &record.account)),
+        )?;
+    }
+    let members = of(OrphanKind::BundleMember);
+    if !members.is_empty() {
+        let _guard = lock_bundles()?;
+        let mut table = load_bundles(&blobs)?;
+        let before = table.entries.len();
+        if table.forget(&members) {
+            blobs.put_structured(BUNDLE_SERVICE, BUNDLE_ACCOUNT, &table)?;
+        }
+        removed += before - table.entries.len();
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alias::AliasKeystore;
+    use crate::blob::BlobStore;
+    use crate::notes::SecureNotes;
+    use crate::testing::MemoryKeystore;
+    use crate::KeystoreEntry;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_orphans_are_reported_then_removed() {
+        let backend = Arc::new(MemoryKeystore {
+            max_value_size: Some(200),
+            ..Default::default()
+        });
+        let set = |service: &str, account: &str| {
+            backend
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    value: "token".to_string(),
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+        set("twitch", "old");
+        AliasKeystore::new(Box::new(Arc::clone(&backend)))
+            .add_alias(&key("twitch", "renamed"), &key("twitch", "old"))
+            .unwrap();
+        let notes = SecureNotes::new(backend.as_ref());
+        notes
+            .save("run of show", &"stream key ".repeat(40))
+            .unwrap();
+        notes.save("sponsors", "none yet").unwrap();
+        assert!(find_orphans(backend.as_ref()).unwrap().is_empty());
+
+        // A delete that stopped halfway, and entries removed behind the tables' backs
+        backend
+            .delete_password(NOTES_SERVICE, "run of show")
+            .unwrap();
+        backend.delete_password(NOTES_SERVICE, "sponsors").unwrap();
+        backend.delete_password("twitch", "old").unwrap();
+        set("obs", "scene:chunk:0");
+
+        let orphans = find_orphans(backend.as_ref()).unwrap();
+        let kinds: Vec<OrphanKind> = orphans.iter().map(|orphan| orphan.kind).collect();
+        let chunks = kinds.iter().filter(|&&k| k == OrphanKind::Chunk).count();
+        assert!(
+            chunks > 1,
+            "expected the note's chunks and obs's, got {}",
+            chunks
+        );
+        assert_eq!(
+            &kinds[chunks..],
+            [
+                OrphanKind::Alias,
+                OrphanKind::NoteIndexRow,
+                OrphanKind::NoteIndexRow
+            ]
+        );
+        // The dry run changed nothing
+        assert_eq!(find_orphans(backend.as_ref()).unwrap(), orphans);
+
+        assert_eq!(
+            remove_orphans(backend.as_ref(), &orphans).unwrap(),
+            orphans.len()
+        );
+        assert!(find_orphans(backend.as_ref()).unwrap().is_empty());
+        assert!(notes.list().unwrap().is_empty());
+        assert!(BlobStore::new(backend.as_ref())
+            .get_structured::<Vec<(KeystoreKey, KeystoreKey)>>(ALIAS_SERVICE, ALIAS_ACCOUNT)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,446 @@
+//! Pairing with a companion device to hand it credentials over the LAN.
+//!
+//! The streaming PC (the sender) shows a short [`generate_pairing_code`]; the user types it on the
+//! mobile companion or a second PC (the receiver). Both run SPAKE2 over Ristretto255 keyed by the
+//! code, so a passive listener learns nothing and an active attacker gets a single guess per
+//! pairing attempt. Each side then checks the other's key confirmation, which only matches if both
+//! typed the same code, before anything secret is sent.
+//!
+//! Transfer takes three sealed messages. The sender offers the keys the user selected; the receiver
+//! shows them and accepts some or all, one by one; the sender then sends the values of accepted
+//! keys only, and the receiver writes only the entries it accepted. Values never appear in the
+//! offer. The module produces and consumes bytes; carrying them (a local WebSocket, a QR code for
+//! the first message) is up to the caller.
+
+use crate::channel::ChannelSession;
+use crate::error::KeystoreError;
+use crate::generate::random_bytes;
+use crate::platform::KeystoreOperations;
+use crate::{KeystoreEntry, KeystoreKey};
+
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// Digits in a pairing code: one online guess in 10^8 per attempt
+pub const PAIRING_CODE_DIGITS: usize = 8;
+
+/// Size of each side's handshake message and key confirmation
+pub const PAIRING_MESSAGE_SIZE: usize = 32;
+
+const PAIRING_INFO: &[u8] = b"streaming-enhancement:pairing:v1";
+
+/// Which end of the transfer this device is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairingRole {
+    /// Holds the entries and shows the code
+    Sender,
+    /// Types the code and receives the entries
+    Receiver,
+}
+
+/// A fresh code to show on the sender, as `1234-5678`
+pub fn generate_pairing_code() -> Result<String, KeystoreError> {
+    let mut digits = String::with_capacity(PAIRING_CODE_DIGITS + 1);
+    while digits.len() < PAIRING_CODE_DIGITS + 1 {
+        for byte in random_bytes(16)? {
+            // Rejecting 250..=255 keeps every digit equally likely
+            if byte < 250 && digits.len() < PAIRING_CODE_DIGITS + 1 {
+                if digits.len() == PAIRING_CODE_DIGITS / 2 {
+                    digits.push('-');
+                }
+                digits.push(char::from(b'0' + byte % 10));
+            }
+        }
+    }
+    Ok(digits)
+}
+
+/// The code's digits, ignoring the separators people type
+fn code_digits(code: &str) -> Result<Zeroizing<String>, KeystoreError> {
+    let digits: Zeroizing<String> = Zeroizing::new(
+        code.chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect(),
+    );
+    if digits.len() != PAIRING_CODE_DIGITS || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(KeystoreError::InvalidInput(format!(
+            "Pairing codes are {} digits",
+            PAIRING_CODE_DIGITS
+        )));
+    }
+    Ok(digits)
+}
+
+/// SPAKE2's blinding points, with no known discrete log
+fn blinding_point(role: PairingRole) -> RistrettoPoint {
+    let seed: &[u8] = match role {
+        PairingRole::Sender => b"streaming-enhancement:pairing:M",
+        PairingRole::Receiver => b"streaming-enhancement:pairing:N",
+    };
+    RistrettoPoint::from_uniform_bytes(&Sha512::digest(seed).into())
+}
+
+fn random_scalar() -> Result<Scalar, KeystoreError> {
+    let bytes = Zeroizing::new(random_bytes(64)?);
+    let mut wide = Zeroizing::new([0u8; 64]);
+    wide.copy_from_slice(&bytes);
+    Ok(Scalar::from_bytes_mod_order_wide(&wide))
+}
+
+fn derive(
+    ikm: &[u8],
+    transcript: &[u8],
+    label: &[u8],
+) -> Result<Zeroizing<[u8; 32]>, KeystoreError> {
+    let mut output = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(None, ikm)
+        .expand(&[transcript, label].concat(), output.as_mut())
+        .map_err(|e| KeystoreError::Platform(format!("Failed to derive pairing key: {}", e)))?;
+    Ok(output)
+}
+
+/// One side of the SPAKE2 exchange, before the peer's message arrives
+pub struct PairingHandshake {
+    role: PairingRole,
+    password: Scalar,
+    secret: Scalar,
+    message: [u8; PAIRING_MESSAGE_SIZE],
+}
+
+impl PairingHandshake {
+    pub fn start(role: PairingRole, code: &str) -> Result<Self, KeystoreError> {
+        let digits = code_digits(code)?;
+        let password = Scalar::from_bytes_mod_order_wide(
+            &Sha512::new()
+                .chain_update(PAIRING_INFO)
+                .chain_update(digits.as_bytes())
+                .finalize()
+                .into(),
+        );
+        let secret = random_scalar()?;
+        let point = RISTRETTO_BASEPOINT_POINT * secret + blinding_point(role) * password;
+        Ok(Self {
+            role,
+            password,
+            secret,
+            message: point.compress().to_bytes(),
+        })
+    }
+
+    /// Sent to the peer; either side may send first
+    pub fn message(&self) -> [u8; PAIRING_MESSAGE_SIZE] {
+        self.message
+    }
+
+    /// Combines the peer's message into a session, which must [`PairingSession::verify`] the
+    /// peer's confirmation before use
+    pub fn finish(self, peer_message: &[u8]) -> Result<PairingSession, KeystoreError> {
+        let invalid = || KeystoreError::InvalidInput("Invalid pairing message".to_string());
+        let peer_point = CompressedRistretto::from_slice(peer_message)
+            .map_err(|_| invalid())?
+            .decompress()
+            .ok_or_else(invalid)?;
+        let peer_role = match self.role {
+            PairingRole::Sender => PairingRole::Receiver,
+            PairingRole::Receiver => PairingRole::Sender,
+        };
+        let shared = (peer_point - blinding_point(peer_role) * self.password) * self.secret;
+        if shared == RistrettoPoint::identity() {
+            return Err(invalid());
+        }
+
+        let (sender_message, receiver_message) = match self.role {
+            PairingRole::Sender => (&self.message[..], peer_message),
+            PairingRole::Receiver => (peer_message, &self.message[..]),
+        };
+        let transcript = [PAIRING_INFO, sender_message, receiver_message].concat();
+        let ikm = Zeroizing::new(
+            [
+                shared.compress().as_bytes().as_slice(),
+                self.password.as_bytes(),
+            ]
+            .concat(),
+        );
+        let key = derive(&ikm, &transcript, b"session")?;
+        let sender_confirmation = *derive(&ikm, &transcript, b"sender-confirmation")?;
+        let receiver_confirmation = *derive(&ikm, &transcript, b"receiver-confirmation")?;
+        let (own, peer) = match self.role {
+            PairingRole::Sender => (sender_confirmation, receiver_confirmation),
+            PairingRole::Receiver => (receiver_confirmation, sender_confirmation),
+        };
+        Ok(PairingSession {
+            role: self.role,
+            channel: ChannelSession::from_key(&key),
+            own_confirmation: own,
+            peer_confirmation: peer,
+            verified: false,
+            offered: Vec::new(),
+            accepted: Vec::new(),
+        })
+    }
+}
+
+/// The transfer messages, sealed under the session key
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum TransferMessage {
+    Offer { keys: Vec<KeystoreKey> },
+    Acceptance { keys: Vec<KeystoreKey> },
+    Entries { entries: Vec<TransferEntry> },
+}
+
+#[derive(Serialize, Deserialize)]
+struct TransferEntry {
+    service: String,
+    account: String,
+    value: String,
+}
+
+/// A paired connection carrying one transfer
+pub struct PairingSession {
+    role: PairingRole,
+    channel: ChannelSession,
+    own_confirmation: [u8; PAIRING_MESSAGE_SIZE],
+    peer_confirmation: [u8; PAIRING_MESSAGE_SIZE],
+    verified: bool,
+    /// Keys offered, as sent or received
+    offered: Vec<KeystoreKey>,
+    /// Keys the receiver accepted, as sent or received
+    accepted: Vec<KeystoreKey>,
+}
+
+impl PairingSession {
+    /// Proves to the peer that this side typed the same code
+    pub fn confirmation(&self) -> [u8; PAIRING_MESSAGE_SIZE] {
+        self.own_confirmation
+    }
+
+    /// Checks the peer's confirmation. A mismatch means the codes differed, or someone in between
+    /// guessed: start over with a new code.
+    pub fn verify(&mut self, peer_confirmation: &[u8]) -> Result<(), KeystoreError> {
+        if !bool::from(peer_confirmation.ct_eq(&self.peer_confirmation)) {
+            return Err(KeystoreError::AccessDenied(
+                "Pairing code did not match".to_string(),
+            ));
+        }
+        self.verified = true;
+        Ok(())
+    }
+
+    fn require(&self, role: PairingRole) -> Result<(), KeystoreError> {
+        if !self.verified {
+            return Err(KeystoreError::AccessDenied(
+                "Pairing has not been confirmed".to_string(),
+            ));
+        }
+        if self.role != role {
+            return Err(KeystoreError::InvalidInput(format!(
+                "Only the {} does that",
+                match role {
+                    PairingRole::Sender => "sender",
+                    PairingRole::Receiver => "receiver",
+                }
+            )));
+        }
+        Ok(())
+    }
+
+    fn seal(&self, message: &TransferMessage) -> Result<Vec<u8>, KeystoreError> {
+        let json = Zeroizing::new(
+            serde_json::to_vec(message).map_err(|e| KeystoreError::Serialization(e.to_string()))?,
+        );
+        self.channel.seal(&json)
+    }
+
+    fn open(&self, sealed: &[u8]) -> Result<TransferMessage, KeystoreError> {
+        let json = Zeroizing::new(self.channel.open(sealed)?);
+        serde_json::from_slice(&json)
+            .map_err(|e| KeystoreError::Serialization(format!("Invalid pairing message: {}", e)))
+    }
+
+    /// Sender: offers `keys` to the receiver, without their values
+    pub fn offer(&mut self, keys: &[KeystoreKey]) -> Result<Vec<u8>, KeystoreError> {
+        self.require(PairingRole::Sender)?;
+        self.offered = keys.to_vec();
+        self.seal(&TransferMessage::Offer {
+            keys: self.offered.clone(),
+        })
+    }
+
+    /// Receiver: the keys the sender offers, to ask the user about one by one
+    pub fn read_offer(&mut self, sealed: &[u8]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.require(PairingRole::Receiver)?;
+        match self.open(sealed)? {
+            TransferMessage::Offer { keys } => {
+                self.offered = keys.clone();
+                Ok(keys)
+            }
+            _ => Err(KeystoreError::InvalidInput(
+                "Expected a pairing offer".to_string(),
+            )),
+        }
+    }
+
+    /// Receiver: accepts the offered `keys` the user agreed to, and no others
+    pub fn accept(&mut self, keys: &[KeystoreKey]) -> Result<Vec<u8>, KeystoreError> {
+        self.require(PairingRole::Receiver)?;
+        if let Some(key) = keys.iter().find(|key| !self.offered.contains(key)) {
+            return Err(KeystoreError::InvalidInput(format!(
+                "{}:{} was not offered",
+                key.service, key.account
+            )));
+        }
+        self.accepted = keys.to_vec();
+        self.seal(&TransferMessage::Acceptance {
+            keys: self.accepted.clone(),
+        })
+    }
+
+    /// Sender: reads the accepted entries from `backend` and seals them. Keys accepted that were
+    /// never offered are ignored.
+    pub fn send_entries(
+        &mut self,
+        backend: &dyn KeystoreOperations,
+        acceptance: &[u8],
+    ) -> Result<Vec<u8>, KeystoreError> {
+        self.require(PairingRole::Sender)?;
+        let TransferMessage::Acceptance { keys } = self.open(acceptance)? else {
+            return Err(KeystoreError::InvalidInput(
+                "Expected a pairing acceptance".to_string(),
+            ));
+        };
+        self.accepted = keys
+            .into_iter()
+            .filter(|key| self.offered.contains(key))
+            .collect();
+        let mut entries = Vec::with_capacity(self.accepted.len());
+        for key in &self.accepted {
+            entries.push(TransferEntry {
+                service: key.service.clone(),
+                account: key.account.clone(),
+                value: backend.get_password(&key.service, &key.account)?,
+            });
+        }
+        let message = TransferMessage::Entries { entries };
+        let sealed = self.seal(&message);
+        if let TransferMessage::Entries { entries } = message {
+            for entry in entries {
+                drop(Zeroizing::new(entry.value));
+            }
+        }
+        sealed
+    }
+
+    /// Receiver: writes the entries it accepted to `backend` and returns their keys; anything else
+    /// in the message is dropped
+    pub fn receive_entries(
+        &self,
+        backend: &dyn KeystoreOperations,
+        sealed: &[u8],
+    ) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.require(PairingRole::Receiver)?;
+        let TransferMessage::Entries { entries } = self.open(sealed)? else {
+            return Err(KeystoreError::InvalidInput(
+                "Expected pairing entries".to_string(),
+            ));
+        };
+        let mut written = Vec::new();
+        for entry in entries {
+            let key = KeystoreKey {
+                service: entry.service,
+                account: entry.account,
+            };
+            let value = Zeroizing::new(entry.value);
+            if self.accepted.contains(&key) {
+                backend.set_password(&KeystoreEntry {
+                    service: key.service.clone(),
+                    account: key.account.clone(),
+                    value: value.to_string(),
+                    ..Default::default()
+                })?;
+                written.push(key);
+            }
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    fn key(service: &str, account: &str) -> KeystoreKey {
+        KeystoreKey {
+            service: service.to_string(),
+            account: account.to_string(),
+        }
+    }
+
+    fn pair(sender_code: &str, receiver_code: &str) -> (PairingSession, PairingSession) {
+        let sender = PairingHandshake::start(PairingRole::Sender, sender_code).unwrap();
+        let receiver = PairingHandshake::start(PairingRole::Receiver, receiver_code).unwrap();
+        let (sender_message, receiver_message) = (sender.message(), receiver.message());
+        (
+            sender.finish(&receiver_message).unwrap(),
+            receiver.finish(&sender_message).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_pairing_transfers_only_accepted_entries() {
+        let code = generate_pairing_code().unwrap();
+        assert_eq!(code_digits(&code).unwrap().len(), PAIRING_CODE_DIGITS);
+        let (mut sender, mut receiver) = pair(&code, &code.replace('-', " "));
+        sender.verify(&receiver.confirmation()).unwrap();
+        receiver.verify(&sender.confirmation()).unwrap();
+
+        let pc = MemoryKeystore::default();
+        for account in ["bot", "chat"] {
+            pc.set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: account.to_string(),
+                value: format!("{}-token", account),
+                ..Default::default()
+            })
+            .unwrap();
+        }
+        let offer = sender
+            .offer(&[key("twitch", "bot"), key("twitch", "chat")])
+            .unwrap();
+        assert!(!offer.windows(5).any(|w| w == b"token"));
+        assert_eq!(receiver.read_offer(&offer).unwrap().len(), 2);
+        assert!(receiver.accept(&[key("obs", "ws")]).is_err());
+        let acceptance = receiver.accept(&[key("twitch", "chat")]).unwrap();
+
+        let phone = MemoryKeystore::default();
+        let entries = sender.send_entries(&pc, &acceptance).unwrap();
+        assert_eq!(
+            receiver.receive_entries(&phone, &entries).unwrap(),
+            [key("twitch", "chat")]
+        );
+        assert_eq!(phone.get_password("twitch", "chat").unwrap(), "chat-token");
+        assert!(phone.get_password("twitch", "bot").is_err());
+    }
+
+    #[test]
+    fn test_mismatched_codes_fail_confirmation() {
+        let (mut sender, receiver) = pair("1234-5678", "1234-5679");
+        assert!(matches!(
+            sender.verify(&receiver.confirmation()),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        assert!(matches!(
+            sender.offer(&[key("twitch", "bot")]),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        assert!(PairingHandshake::start(PairingRole::Sender, "1234").is_err());
+    }
+}
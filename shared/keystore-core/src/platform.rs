@@ -0,0 +1,495 @@
+use crate::error::KeystoreError;
+use crate::generate::{generate_secret, SecretOptions};
+use crate::policy::BackendPreference;
+use crate::refresh::with_refresh_lock;
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+#[cfg(all(windows, feature = "backend-windows"))]
+pub mod windows;
+
+#[cfg(all(target_os = "macos", feature = "backend-macos"))]
+pub mod macos;
+
+#[cfg(any(
+    all(target_os = "macos", feature = "backend-macos"),
+    all(target_os = "ios", feature = "backend-ios")
+))]
+pub mod keychain;
+
+#[cfg(all(target_os = "ios", feature = "backend-ios"))]
+pub mod ios;
+
+#[cfg(all(target_os = "linux", feature = "backend-secret-service"))]
+pub mod linux;
+
+#[cfg(target_os = "linux")]
+pub mod systemd;
+
+// Fallback is used on non-standard platforms, on Linux when Secret Service is unavailable, and
+// wherever the native backend has been compiled out
+#[cfg(feature = "backend-file")]
+pub mod fallback;
+
+#[cfg(not(any(
+    all(windows, feature = "backend-windows"),
+    all(target_os = "macos", feature = "backend-macos"),
+    all(target_os = "ios", feature = "backend-ios"),
+    all(target_os = "linux", feature = "backend-secret-service"),
+    feature = "backend-file"
+)))]
+compile_error!(
+    "no keystore backend enabled for this target: enable one of the `backend-windows`, \
+     `backend-macos`, `backend-ios`, `backend-secret-service` or `backend-file` features"
+);
+
+/// Describes a backend instance for diagnostics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendInfo {
+    /// Stable backend identifier, e.g. `windows-credential-manager`
+    pub name: String,
+    /// Backend settings and the decisions made when it was constructed
+    pub details: BTreeMap<String, String>,
+}
+
+impl BackendInfo {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            details: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_detail(mut self, key: &str, value: impl ToString) -> Self {
+        self.details.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+/// Operations every keystore backend provides.
+///
+/// Backends are `Send + Sync` and may be shared across threads behind an `Arc` or a
+/// `Box<dyn KeystoreOperations>`. Each operation is atomic with respect to other operations on
+/// the same backend instance: a concurrent `get_password` observes either the previous or the new
+/// value of a `set_password`, never a partial write. Native backends rely on the OS keystore for
+/// this; the fallback backend serializes writes behind an internal `RwLock`.
+pub trait KeystoreOperations: Send + Sync {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError>;
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError>;
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError>;
+    fn is_available(&self) -> bool;
+    fn backend_info(&self) -> BackendInfo;
+
+    /// Largest value, in UTF-8 bytes, a single entry can hold. `None` means no practical limit;
+    /// larger values go through [`crate::blob::BlobStore`].
+    fn max_value_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// Every stored entry, without values. Backends that cannot enumerate their store return
+    /// `PlatformNotSupported`.
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        Err(KeystoreError::PlatformNotSupported)
+    }
+
+    /// Accounts stored under `service`, sorted, without reading their values. Filtered from
+    /// `list_keys` unless the backend can search one service directly.
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let mut accounts: Vec<String> = self
+            .list_keys()?
+            .into_iter()
+            .filter(|key| key.service == service)
+            .map(|key| key.account)
+            .collect();
+        accounts.sort();
+        accounts.dedup();
+        Ok(accounts)
+    }
+
+    /// Reads several entries at once, returning values in the order of `keys` and `None` for
+    /// missing ones. Backends that can batch the reads override it to cut round-trips.
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        keys.iter()
+            .map(|key| match self.get_password(&key.service, &key.account) {
+                Ok(value) => Ok(Some(value)),
+                Err(KeystoreError::KeyNotFound(_)) => Ok(None),
+                Err(e) => Err(e),
+            })
+            .collect()
+    }
+
+    /// Writes several entries at once, in order. Backends that can write them together override
+    /// it; the default writes one at a time and stops at the first failure, leaving the entries
+    /// written before it.
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        entries
+            .iter()
+            .try_for_each(|entry| self.set_password(entry))
+    }
+
+    /// Deletes several entries at once and returns the keys it removed, sorted; missing ones are
+    /// skipped. The default deletes one at a time, so on a failure the entries removed before it
+    /// stay removed.
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut removed = Vec::new();
+        for key in keys {
+            match self.delete_password(&key.service, &key.account) {
+                Ok(()) => removed.push(key.clone()),
+                Err(KeystoreError::KeyNotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        removed.sort();
+        removed.dedup();
+        Ok(removed)
+    }
+
+    /// Deletes every entry under `service` and returns the removed accounts, sorted. Found through
+    /// `list_credentials`, so backends that cannot enumerate return `PlatformNotSupported` and
+    /// delete nothing. On a failed delete the accounts removed before it stay removed.
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let accounts = self.list_credentials(service)?;
+        for account in &accounts {
+            match self.delete_password(service, account) {
+                // Already removed by a concurrent caller
+                Ok(()) | Err(KeystoreError::KeyNotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(accounts)
+    }
+
+    /// Deletes every entry `list_keys` finds and returns their keys, sorted. Wrappers that cache
+    /// entries or keep index entries override it to reset their state.
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut keys = self.list_keys()?;
+        keys.sort();
+        for key in &keys {
+            match self.delete_password(&key.service, &key.account) {
+                Ok(()) | Err(KeystoreError::KeyNotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Exchanges the values of `a` and `b`, which must both exist. Backends that can write both in
+    /// one transaction override it; otherwise see [`swap_by_writes`].
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        swap_by_writes(self, a, b)
+    }
+
+    /// Label, comment and timestamps of an entry, `KeyNotFound` if there is none. The default, for
+    /// backends that keep no metadata, reads the value to check the entry exists and reports
+    /// nothing about it.
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        drop(Zeroizing::new(self.get_password(service, account)?));
+        Ok(EntryMetadata::default())
+    }
+
+    /// Whether the entry exists, without returning its value. The default asks
+    /// [`Self::get_metadata`], which backends keeping metadata apart answer without decrypting the
+    /// value; others override it with a lookup that skips the value where they can.
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        match self.get_metadata(service, account) {
+            Ok(_) => Ok(true),
+            Err(KeystoreError::KeyNotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The entry's value, or, if there is none, a new random one of `length` characters (see
+    /// [`crate::generate::generate_secret`]) stored and returned. Meant for keys generated on first
+    /// run, which the daemon and the UI may both reach for at once. Backends that can check and
+    /// write in one step override it; otherwise see [`get_or_create_by_writes`].
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        get_or_create_by_writes(self, service, account, length)
+    }
+}
+
+/// Swaps two entries with one write each: `a` first, then `b`. A reader in between sees the same
+/// value under both keys, but never neither. If writing `b` fails, `a` is restored.
+pub fn swap_by_writes<K: KeystoreOperations + ?Sized>(
+    keystore: &K,
+    a: &KeystoreKey,
+    b: &KeystoreKey,
+) -> Result<(), KeystoreError> {
+    if a == b {
+        keystore.get_password(&a.service, &a.account)?;
+        return Ok(());
+    }
+    let first = Zeroizing::new(keystore.get_password(&a.service, &a.account)?);
+    let second = Zeroizing::new(keystore.get_password(&b.service, &b.account)?);
+    let entry = |key: &KeystoreKey, value: &str| KeystoreEntry {
+        service: key.service.clone(),
+        account: key.account.clone(),
+        value: value.to_string(),
+        ..Default::default()
+    };
+    keystore.set_password(&entry(a, &second))?;
+    if let Err(e) = keystore.set_password(&entry(b, &first)) {
+        let _ = keystore.set_password(&entry(a, &first));
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Reads the entry and writes a new value if it's missing, holding the entry's cross-process lock
+/// (see [`crate::refresh`]) throughout, so callers of this in other processes wait and then read
+/// the value written rather than create their own.
+pub fn get_or_create_by_writes<K: KeystoreOperations + ?Sized>(
+    keystore: &K,
+    service: &str,
+    account: &str,
+    length: usize,
+) -> Result<String, KeystoreError> {
+    with_refresh_lock(service, account, || {
+        match keystore.get_password(service, account) {
+            Err(KeystoreError::KeyNotFound(_)) => {}
+            result => return result,
+        }
+        let value = generate_secret(&SecretOptions {
+            length,
+            ..Default::default()
+        })?;
+        keystore.set_password(&KeystoreEntry {
+            service: service.to_string(),
+            account: account.to_string(),
+            value: value.clone(),
+            ..Default::default()
+        })?;
+        Ok(value)
+    })
+}
+
+/// Lets a shared wrapper such as [`crate::lock::LockingKeystore`] sit inside another wrapper while
+/// its owner keeps a handle to it
+impl<T: KeystoreOperations + ?Sized> KeystoreOperations for std::sync::Arc<T> {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        (**self).set_password(entry)
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        (**self).get_password(service, account)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        (**self).delete_password(service, account)
+    }
+
+    fn is_available(&self) -> bool {
+        (**self).is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        (**self).backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        (**self).max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        (**self).list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        (**self).list_credentials(service)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        (**self).get_many(keys)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        (**self).set_many(entries)
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        (**self).delete_many(keys)
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        (**self).delete_service(service)
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        (**self).wipe_all()
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        (**self).swap(a, b)
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        (**self).get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        (**self).has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        (**self).get_or_create(service, account, length)
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(windows, feature = "backend-windows"))] {
+        pub use windows::WindowsKeystore as Keystore;
+    } else if #[cfg(all(target_os = "macos", feature = "backend-macos"))] {
+        pub use macos::MacOsKeystore as Keystore;
+    } else if #[cfg(all(target_os = "ios", feature = "backend-ios"))] {
+        pub use ios::IosKeystore as Keystore;
+    } else if #[cfg(all(target_os = "linux", feature = "backend-secret-service"))] {
+        pub use linux::LinuxKeystore as Keystore;
+    } else {
+        pub use fallback::FallbackKeystore as Keystore;
+    }
+}
+
+/// Selects the backend for the current platform from the enabled features
+pub fn default_backend() -> Result<Box<dyn KeystoreOperations>, KeystoreError> {
+    cfg_if::cfg_if! {
+        if #[cfg(all(windows, feature = "backend-windows"))] {
+            Ok(Box::new(windows::WindowsKeystore::new()?))
+        } else if #[cfg(all(target_os = "macos", feature = "backend-macos"))] {
+            Ok(Box::new(macos::MacOsKeystore::new()?))
+        } else if #[cfg(all(target_os = "ios", feature = "backend-ios"))] {
+            Ok(Box::new(ios::IosKeystore::new()?))
+        } else if #[cfg(all(target_os = "linux", feature = "backend-secret-service"))] {
+            // Try native Linux keystore first, fall back to encrypted file if unavailable. Obvious
+            // headless environments skip the probe, which can otherwise block on DBus.
+            let linux_keystore = linux::LinuxKeystore::new()?;
+            #[cfg(feature = "backend-file")]
+            if let Err(reason) = linux::headless_reason()
+                .map_or_else(|| linux_keystore.probe(linux::PROBE_TIMEOUT), Err)
+            {
+                return Ok(Box::new(
+                    fallback::FallbackKeystore::new()?.with_fallback_reason(reason),
+                ));
+            }
+            Ok(Box::new(linux_keystore))
+        } else {
+            Ok(Box::new(fallback::FallbackKeystore::new()?))
+        }
+    }
+}
+
+/// Like [`default_backend`], but honoring a backend forced by managed policy. `Native` never falls
+/// back to the encrypted file; `File` skips the platform keystore. On Linux, credentials systemd
+/// provides to the current service are layered ahead of the backend.
+pub fn backend_for(
+    preference: Option<BackendPreference>,
+) -> Result<Box<dyn KeystoreOperations>, KeystoreError> {
+    // Innermost, so reads the backend answers are told apart from the layers above
+    let backend: Box<dyn KeystoreOperations> = Box::new(
+        crate::resolution::BackendSourceKeystore::new(preferred_backend(preference)?),
+    );
+    Ok(layered(backend))
+}
+
+/// Like [`backend_for`], with every entry also kept in the encrypted file. The returned mirror
+/// checks and reconciles the two copies; systemd credentials stay out of it.
+#[cfg(feature = "backend-file")]
+pub fn mirrored_backend_for(
+    preference: Option<BackendPreference>,
+) -> Result<
+    (
+        Box<dyn KeystoreOperations>,
+        std::sync::Arc<crate::mirror::MirroredKeystore>,
+    ),
+    KeystoreError,
+> {
+    let primary = Box::new(crate::resolution::BackendSourceKeystore::new(
+        preferred_backend(preference)?,
+    ));
+    let mirror = std::sync::Arc::new(crate::mirror::MirroredKeystore::mirror_to_file(primary)?);
+    Ok((layered(Box::new(std::sync::Arc::clone(&mirror))), mirror))
+}
+
+/// Adds the layers every backend gets on this platform
+fn layered(backend: Box<dyn KeystoreOperations>) -> Box<dyn KeystoreOperations> {
+    #[cfg(target_os = "linux")]
+    let backend = systemd::SystemdCredentials::layer(backend);
+    backend
+}
+
+fn preferred_backend(
+    preference: Option<BackendPreference>,
+) -> Result<Box<dyn KeystoreOperations>, KeystoreError> {
+    match preference {
+        None => default_backend(),
+        Some(BackendPreference::File) => {
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "backend-file")] {
+                    Ok(Box::new(fallback::FallbackKeystore::new()?))
+                } else {
+                    Err(KeystoreError::PlatformNotSupported)
+                }
+            }
+        }
+        Some(BackendPreference::Native) => {
+            cfg_if::cfg_if! {
+                if #[cfg(all(target_os = "linux", feature = "backend-secret-service"))] {
+                    Ok(Box::new(linux::LinuxKeystore::new()?))
+                } else if #[cfg(any(
+                    all(windows, feature = "backend-windows"),
+                    all(target_os = "macos", feature = "backend-macos"),
+                    all(target_os = "ios", feature = "backend-ios"),
+                ))] {
+                    default_backend()
+                } else {
+                    Err(KeystoreError::PlatformNotSupported)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync + ?Sized>() {}
+
+    #[test]
+    fn test_backends_are_send_sync() {
+        assert_send_sync::<Keystore>();
+        assert_send_sync::<dyn KeystoreOperations>();
+        #[cfg(feature = "backend-file")]
+        assert_send_sync::<fallback::FallbackKeystore>();
+    }
+
+    #[test]
+    fn test_list_credentials_filters_the_listing() {
+        let keystore = crate::testing::MemoryKeystore::default();
+        for (service, account) in [("twitch", "bot"), ("youtube", "bot"), ("twitch", "alt")] {
+            keystore
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    value: "token".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+
+        assert_eq!(keystore.list_credentials("twitch").unwrap(), ["alt", "bot"]);
+        assert!(keystore.list_credentials("kick").unwrap().is_empty());
+        assert_eq!(keystore.delete_service("twitch").unwrap(), ["alt", "bot"]);
+        assert_eq!(keystore.list_credentials("youtube").unwrap(), ["bot"]);
+    }
+}
@@ -0,0 +1,2102 @@
+use super::{BackendInfo, KeystoreOperations};
+use crate::conflicts::conflict_entry;
+use crate::crypto::{AesGcmKey, KEY_SIZE, NONCE_SIZE};
+use crate::error::KeystoreError;
+use crate::generate::{generate_secret, SecretOptions};
+use crate::legacy::{legacy_stores, migrate, MigrationOutcome};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// Distinguishes temp files written by different instances within one process
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Version of the file layout this build writes. Files from before versioning have no
+/// `format_version` and count as version 0.
+///
+/// Opening an older file upgrades it in place. A file from a newer build is never rewritten. If
+/// its `min_reader_version` says this build can still parse it, it opens read-only so a
+/// rolled-back app can keep fetching tokens; otherwise it fails to open with
+/// [`KeystoreError::FormatTooNew`]. Either way it is left as it is, so going back to an older
+/// release for good means exporting a backup from the newer one first.
+///
+/// Version 2 added per-entry revisions, version 3 sealed entry metadata.
+pub const FORMAT_VERSION: u32 = 3;
+
+/// Oldest format version whose readers can parse what this build writes. A later layout that only
+/// adds fields older readers ignore keeps this; one that changes existing fields raises it.
+pub const MIN_READER_VERSION: u32 = 1;
+
+/// Read before the rest of the file, so a newer layout is reported as such rather than as a parse
+/// failure
+#[derive(Deserialize)]
+struct FormatHeader {
+    #[serde(default)]
+    format_version: u32,
+    /// Missing from files written before it existed, which newer builds never wrote
+    min_reader_version: Option<u32>,
+}
+
+/// How a file's version compares to this build's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileStatus {
+    Current,
+    Upgraded,
+    /// Written at this newer version, but readable here
+    ReadOnly(u32),
+}
+
+/// What opening a damaged file salvaged from it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Entries read intact
+    pub recovered: usize,
+    /// Entry records that couldn't be parsed: a truncated final record, or a layout this build
+    /// doesn't know
+    pub quarantined_entries: usize,
+    /// Bytes after the end of the file's JSON that were ignored
+    pub trailing_bytes: usize,
+    /// The file ended before its JSON did
+    pub truncated: bool,
+    /// Copy of the damaged file, taken before the intact part replaced it. `None` when the file
+    /// was opened read-only and left as it was.
+    pub quarantine_path: Option<PathBuf>,
+}
+
+/// What [`salvage`] could make of a file that doesn't parse as a whole
+struct Salvaged {
+    header: FormatHeader,
+    entries: Vec<EncryptedEntry>,
+    report: RecoveryReport,
+}
+
+/// Reads every intact entry record out of `json`, whatever follows or is missing after them.
+/// Never fails: with nothing intact, the result has no entries and a version-0 header.
+fn salvage(json: &str) -> Salvaged {
+    let mut report = RecoveryReport::default();
+    let mut header = FormatHeader {
+        format_version: 0,
+        min_reader_version: None,
+    };
+    let mut records = Vec::new();
+
+    let mut documents = serde_json::Deserializer::from_str(json).into_iter::<serde_json::Value>();
+    match documents.next() {
+        Some(Ok(document)) => {
+            report.trailing_bytes = json[documents.byte_offset()..].trim().len();
+            header = header_of(&document);
+            if let Some(entries) = document.get("entries").and_then(|e| e.as_array()) {
+                records.extend(entries.iter().cloned());
+            }
+        }
+        _ => {
+            report.truncated = true;
+            // Fields are written before the entries, so closing the array right after its opening
+            // bracket leaves a parseable header
+            if let Some(open) = json
+                .find("\"entries\"")
+                .and_then(|at| json[at..].find('[').map(|bracket| at + bracket))
+            {
+                if let Ok(prefix) =
+                    serde_json::from_str::<serde_json::Value>(&format!("{}[]}}", &json[..open]))
+                {
+                    header = header_of(&prefix);
+                }
+                let mut rest = &json[open + 1..];
+                loop {
+                    rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+                    if rest.is_empty() || rest.starts_with(']') {
+                        break;
+                    }
+                    let mut values =
+                        serde_json::Deserializer::from_str(rest).into_iter::<serde_json::Value>();
+                    match values.next() {
+                        Some(Ok(record)) => {
+                            records.push(record);
+                            rest = &rest[values.byte_offset()..];
+                        }
+                        _ => {
+                            report.quarantined_entries += 1;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    for record in records {
+        match serde_json::from_value::<EncryptedEntry>(record) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => report.quarantined_entries += 1,
+        }
+    }
+    report.recovered = entries.len();
+    Salvaged {
+        header,
+        entries,
+        report,
+    }
+}
+
+fn header_of(document: &serde_json::Value) -> FormatHeader {
+    let version = |field: &str| {
+        document
+            .get(field)
+            .and_then(|v| v.as_u64())
+            .and_then(|v| u32::try_from(v).ok())
+    };
+    FormatHeader {
+        format_version: version("format_version").unwrap_or(0),
+        min_reader_version: version("min_reader_version"),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct EncryptedEntry {
+    nonce: [u8; NONCE_SIZE],
+    ciphertext: Vec<u8>,
+    /// Bumped on every write of the entry; 0 in files from before revisions
+    #[serde(default)]
+    revision: u64,
+    /// The entry's [`EntryMetadata`], sealed like its value; missing from entries written before
+    /// format 3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<SealedMetadata>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SealedMetadata {
+    nonce: [u8; NONCE_SIZE],
+    ciphertext: Vec<u8>,
+}
+
+/// One version of an entry: every write picks a fresh nonce, so this changes whenever the entry
+/// does
+type EntryId = (u64, [u8; NONCE_SIZE]);
+
+impl EncryptedEntry {
+    fn id(&self) -> EntryId {
+        (self.revision, self.nonce)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct KeystoreData {
+    #[serde(default)]
+    format_version: u32,
+    #[serde(default)]
+    min_reader_version: u32,
+    entries: Vec<EncryptedEntry>,
+}
+
+impl KeystoreData {
+    fn empty() -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            min_reader_version: MIN_READER_VERSION,
+            entries: vec![],
+        }
+    }
+}
+
+/// The file's entries and which one holds each key, decrypted once when the file is opened
+#[derive(Clone)]
+struct State {
+    data: KeystoreData,
+    index: HashMap<KeystoreKey, usize>,
+    synced: Synced,
+}
+
+/// The file as this instance last read or wrote it, the base of a merge with other writers
+#[derive(Clone, Default)]
+struct Synced {
+    /// SHA-256 of the file's bytes, `None` if there was no file
+    digest: Option<[u8; 32]>,
+    base: HashMap<KeystoreKey, EntryId>,
+}
+
+/// Data and version of a file as opened, its digest, and what was salvaged if it was damaged
+type Loaded = (
+    KeystoreData,
+    FileStatus,
+    Option<[u8; 32]>,
+    Option<RecoveryReport>,
+);
+
+/// A decrypted entry: service, account, value
+type Plaintext = (String, String, String);
+
+fn decrypt_entry(key: &AesGcmKey, entry: &EncryptedEntry) -> Option<Plaintext> {
+    let decrypted = key.open(&entry.nonce, &entry.ciphertext)?;
+    serde_json::from_slice(&decrypted).ok()
+}
+
+/// Empty for entries without metadata, or whose metadata doesn't decrypt
+fn decrypt_metadata(key: &AesGcmKey, entry: &EncryptedEntry) -> EntryMetadata {
+    entry
+        .metadata
+        .as_ref()
+        .and_then(|sealed| key.open(&sealed.nonce, &sealed.ciphertext))
+        .and_then(|decrypted| serde_json::from_slice(&decrypted).ok())
+        .unwrap_or_default()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl State {
+    /// Entries that no longer decrypt are kept in the file but left out of the index; of
+    /// duplicate keys the first wins
+    fn new(data: KeystoreData, key: &AesGcmKey) -> Self {
+        let mut index = HashMap::new();
+        for (slot, entry) in data.entries.iter().enumerate() {
+            if let Some((service, account, _)) = decrypt_entry(key, entry) {
+                index
+                    .entry(KeystoreKey { service, account })
+                    .or_insert(slot);
+            }
+        }
+        Self {
+            data,
+            index,
+            synced: Synced::default(),
+        }
+    }
+
+    /// Marks the current entries as what the file with `digest` holds
+    fn mark_synced(&mut self, digest: Option<[u8; 32]>) {
+        let base = self
+            .index
+            .iter()
+            .map(|(key, &slot)| (key.clone(), self.data.entries[slot].id()))
+            .collect();
+        self.synced = Synced { digest, base };
+    }
+
+    fn entry(&self, key: &KeystoreKey) -> Option<&EncryptedEntry> {
+        self.index.get(key).map(|&slot| &self.data.entries[slot])
+    }
+
+    /// Like [`Self::entry`], but `None` once the entry has expired
+    fn live(&self, cipher: &AesGcmKey, key: &KeystoreKey) -> Option<&EncryptedEntry> {
+        let now = now_millis();
+        self.entry(key)
+            .filter(|entry| !decrypt_metadata(cipher, entry).is_expired(now))
+    }
+
+    /// Slots of the indexed entries that have expired
+    fn expired(&self, cipher: &AesGcmKey) -> BTreeSet<usize> {
+        let now = now_millis();
+        self.index
+            .values()
+            .copied()
+            .filter(|&slot| decrypt_metadata(cipher, &self.data.entries[slot]).is_expired(now))
+            .collect()
+    }
+
+    /// Writes `entry` at the next revision of its key
+    fn upsert(&mut self, cipher: &AesGcmKey, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        let plaintext = Zeroizing::new(
+            serde_json::to_string(&(&entry.service, &entry.account, &entry.value))
+                .map_err(|e| KeystoreError::Serialization(e.to_string()))?,
+        );
+        let (nonce, ciphertext) = cipher.seal(plaintext.as_bytes())?;
+        let key = KeystoreKey {
+            service: entry.service.clone(),
+            account: entry.account.clone(),
+        };
+        let previous = self.entry(&key);
+        let revision = previous.map_or(0, |previous| previous.revision) + 1;
+        let now = now_millis();
+        // An expired entry's label and creation time go with it
+        let metadata = EntryMetadata::after_write(
+            previous
+                .map(|previous| decrypt_metadata(cipher, previous))
+                .filter(|previous| !previous.is_expired(now))
+                .as_ref(),
+            entry,
+            now,
+        );
+        let sealed = serde_json::to_vec(&metadata)
+            .map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+        let (metadata_nonce, metadata_ciphertext) = cipher.seal(&sealed)?;
+        let encrypted_entry = EncryptedEntry {
+            nonce,
+            ciphertext,
+            revision,
+            metadata: Some(SealedMetadata {
+                nonce: metadata_nonce,
+                ciphertext: metadata_ciphertext,
+            }),
+        };
+        match self.index.get(&key) {
+            Some(&slot) => self.data.entries[slot] = encrypted_entry,
+            None => {
+                self.index.insert(key, self.data.entries.len());
+                self.data.entries.push(encrypted_entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// Entries the index leaves out: ones that no longer decrypt, and shadowed duplicates
+    fn unindexed(&self) -> usize {
+        self.data.entries.len() - self.index.len()
+    }
+
+    /// A copy with the entries in `removed` dropped and the index shifted to match
+    fn without(&self, removed: &BTreeSet<usize>) -> Self {
+        let entries = self
+            .data
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(slot, _)| !removed.contains(slot))
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        let index = self
+            .index
+            .iter()
+            .filter(|(_, slot)| !removed.contains(slot))
+            .map(|(key, &slot)| (key.clone(), slot - removed.range(..slot).count()))
+            .collect();
+        Self {
+            data: KeystoreData {
+                format_version: self.data.format_version,
+                min_reader_version: self.data.min_reader_version,
+                entries,
+            },
+            index,
+            synced: self.synced.clone(),
+        }
+    }
+}
+
+/// Encrypted file keystore.
+///
+/// The encrypted entries and an index of their keys live in memory behind an `Arc<RwLock>`,
+/// shared by clones of the keystore, so a read decrypts only the entry it asks for and never
+/// touches the file. Decrypted values are never cached. Reads share the lock; writes hold it
+/// exclusively across building the new state, saving it and swapping it in, so concurrent writers
+/// cannot lose each other's updates and a failed save leaves memory matching the file.
+#[derive(Clone)]
+pub struct FallbackKeystore {
+    file_path: PathBuf,
+    key: AesGcmKey,
+    state: Arc<RwLock<State>>,
+    /// Why the platform keystore was passed over, when this stands in for it
+    fallback_reason: Option<String>,
+    /// Format version of a newer file opened read-only
+    read_only_version: Option<u32>,
+    /// What was salvaged, if the file was damaged when opened
+    recovery: Option<RecoveryReport>,
+    /// Files older versions left elsewhere, imported when this was created
+    migrations: Vec<MigrationOutcome>,
+}
+
+impl FallbackKeystore {
+    /// Opens the file at its standard location, first importing the files older versions left at
+    /// theirs; see [`crate::legacy`]
+    pub fn new() -> Result<Self, KeystoreError> {
+        let mut keystore = Self::open(Self::get_file_path(), Self::get_or_create_key()?)?;
+        keystore.migrations = legacy_stores(&keystore.file_path)
+            .iter()
+            .map(|store| migrate(&keystore, store))
+            .collect();
+        Ok(keystore)
+    }
+
+    pub(crate) fn open(file_path: PathBuf, key: AesGcmKey) -> Result<Self, KeystoreError> {
+        let (data, status, digest, mut recovery) = Self::load(&file_path)?;
+        let read_only = matches!(status, FileStatus::ReadOnly(_));
+        if let Some(report) = recovery.as_mut().filter(|_| !read_only) {
+            report.quarantine_path = Some(Self::quarantine(&file_path)?);
+        }
+        let rewrite = status == FileStatus::Upgraded || (recovery.is_some() && !read_only);
+        let mut state = State::new(data, &key);
+        state.mark_synced(digest);
+        let keystore = Self {
+            file_path,
+            key,
+            state: Arc::new(RwLock::new(state)),
+            fallback_reason: None,
+            read_only_version: match status {
+                FileStatus::ReadOnly(version) => Some(version),
+                _ => None,
+            },
+            recovery,
+            migrations: Vec::new(),
+        };
+        if rewrite {
+            let mut state = keystore.write_state()?;
+            let digest = keystore.save_data(&state.data)?;
+            state.mark_synced(Some(digest));
+        }
+        Ok(keystore)
+    }
+
+    /// The file's data, at [`FORMAT_VERSION`] unless it is opened read-only, the digest of its
+    /// bytes, and what was salvaged if it doesn't parse as a whole
+    fn load(file_path: &Path) -> Result<Loaded, KeystoreError> {
+        if !file_path.exists() {
+            return Ok((KeystoreData::empty(), FileStatus::Current, None, None));
+        }
+        let bytes = fs::read(file_path).map_err(KeystoreError::Io)?;
+        let digest = Sha256::digest(&bytes).into();
+        // Invalid UTF-8 can only be damage, which salvaging skips over
+        let json = String::from_utf8_lossy(&bytes);
+        match Self::parse(file_path, &json) {
+            Ok((data, status)) => Ok((data, status, Some(digest), None)),
+            Err(KeystoreError::Serialization(_)) => {
+                let salvaged = salvage(&json);
+                let status = Self::status(file_path, &salvaged.header)?;
+                let mut data = KeystoreData {
+                    format_version: salvaged.header.format_version,
+                    min_reader_version: salvaged.header.min_reader_version.unwrap_or(0),
+                    entries: salvaged.entries,
+                };
+                if status == FileStatus::Current {
+                    data.min_reader_version = MIN_READER_VERSION;
+                } else if status == FileStatus::Upgraded {
+                    data.format_version = FORMAT_VERSION;
+                    data.min_reader_version = MIN_READER_VERSION;
+                }
+                Ok((data, status, Some(digest), Some(salvaged.report)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Copies the damaged file aside, next to it, before it is rewritten
+    fn quarantine(file_path: &Path) -> Result<PathBuf, KeystoreError> {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let name = file_path.file_name().unwrap().to_string_lossy();
+        let mut attempt = 0;
+        loop {
+            let suffix = match attempt {
+                0 => String::new(),
+                n => format!("-{}", n),
+            };
+            let path = file_path.with_file_name(format!("{}.corrupt-{}{}", name, stamp, suffix));
+            if !path.exists() {
+                // A copy keeps the original's permissions
+                fs::copy(file_path, &path)?;
+                return Ok(path);
+            }
+            attempt += 1;
+        }
+    }
+
+    fn status(file_path: &Path, header: &FormatHeader) -> Result<FileStatus, KeystoreError> {
+        if header.format_version <= FORMAT_VERSION {
+            // Older versions only lack fields, which default
+            if header.format_version < FORMAT_VERSION {
+                Ok(FileStatus::Upgraded)
+            } else {
+                Ok(FileStatus::Current)
+            }
+        } else if header
+            .min_reader_version
+            .is_some_and(|min| min <= FORMAT_VERSION)
+        {
+            Ok(FileStatus::ReadOnly(header.format_version))
+        } else {
+            Err(too_new(file_path, header.format_version))
+        }
+    }
+
+    fn parse(file_path: &Path, json: &str) -> Result<(KeystoreData, FileStatus), KeystoreError> {
+        let header: FormatHeader =
+            serde_json::from_str(json).map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+        let status = Self::status(file_path, &header)?;
+        let mut data: KeystoreData = serde_json::from_str(json).map_err(|e| match status {
+            // Promised to be readable but isn't; report the version rather than the parse error
+            FileStatus::ReadOnly(version) => too_new(file_path, version),
+            _ => KeystoreError::Serialization(e.to_string()),
+        })?;
+        if status == FileStatus::Upgraded {
+            data.format_version = FORMAT_VERSION;
+            data.min_reader_version = MIN_READER_VERSION;
+        }
+        Ok((data, status))
+    }
+
+    /// The format version of a file from a newer build that was opened read-only, or `None` if
+    /// the keystore is writable
+    pub fn read_only_version(&self) -> Option<u32> {
+        self.read_only_version
+    }
+
+    /// What was salvaged from the file if it was damaged when opened: trailing garbage, a
+    /// truncated end, or entry records this build can't parse. The damaged file is copied to the
+    /// report's `quarantine_path` before the intact entries replace it.
+    pub fn recovery_report(&self) -> Option<&RecoveryReport> {
+        self.recovery.as_ref()
+    }
+
+    /// What importing each legacy file found at startup did, including failures, which leave the
+    /// file in place
+    pub fn migration_report(&self) -> &[MigrationOutcome] {
+        &self.migrations
+    }
+
+    /// Records why the platform keystore was skipped, reported as `fallback_reason` in
+    /// [`BackendInfo`]
+    pub fn with_fallback_reason(mut self, reason: impl Into<String>) -> Self {
+        self.fallback_reason = Some(reason.into());
+        self
+    }
+
+    fn read_state(&self) -> Result<RwLockReadGuard<'_, State>, KeystoreError> {
+        self.state
+            .read()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))
+    }
+
+    fn write_state(&self) -> Result<RwLockWriteGuard<'_, State>, KeystoreError> {
+        if let Some(version) = self.read_only_version {
+            return Err(KeystoreError::FormatTooNew(format!(
+                "{} was written by a newer version (format {}) and is open read-only; update to \
+                 save changes",
+                self.file_path.display(),
+                version
+            )));
+        }
+        self.state
+            .write()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))
+    }
+
+    /// Saves `next` and swaps it in under the file lock, first merging in whatever another writer
+    /// saved to the file since this instance last read or wrote it. Expired entries are dropped
+    /// from what's saved; on failure the current state is untouched.
+    fn commit(&self, state: &mut State, next: State) -> Result<(), KeystoreError> {
+        let _lock = self.lock_file()?;
+        self.commit_locked(state, next)
+    }
+
+    /// [`Self::commit`] for a caller already holding the file lock
+    fn commit_locked(&self, state: &mut State, next: State) -> Result<(), KeystoreError> {
+        let mut next = match self.changed_on_disk(&state.synced)? {
+            Some(theirs) => self.merge(&state.synced.base, next, theirs)?,
+            None => next,
+        };
+        let expired = next.expired(&self.key);
+        if !expired.is_empty() {
+            next = next.without(&expired);
+        }
+        let digest = self.save_data(&next.data)?;
+        next.mark_synced(Some(digest));
+        *state = next;
+        Ok(())
+    }
+
+    /// Takes the OS lock on `<file>.lock`, which other processes take before merging and saving,
+    /// waiting for whoever holds it. Released when the returned file is closed.
+    fn lock_file(&self) -> Result<fs::File, KeystoreError> {
+        let parent_dir = self.file_path.parent().unwrap();
+        fs::create_dir_all(parent_dir)?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(parent_dir.join(format!(
+                "{}.lock",
+                self.file_path.file_name().unwrap().to_string_lossy()
+            )))?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    /// The file's state, if it no longer is what this instance last read or wrote
+    fn changed_on_disk(&self, synced: &Synced) -> Result<Option<State>, KeystoreError> {
+        let json = match fs::read_to_string(&self.file_path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(KeystoreError::Io(e)),
+        };
+        let digest: [u8; 32] = Sha256::digest(json.as_bytes()).into();
+        if synced.digest == Some(digest) {
+            return Ok(None);
+        }
+        match Self::parse(&self.file_path, &json)? {
+            // Saving would drop whatever the newer writer added
+            (_, FileStatus::ReadOnly(version)) => Err(too_new(&self.file_path, version)),
+            (data, _) => {
+                let mut theirs = State::new(data, &self.key);
+                theirs.mark_synced(Some(digest));
+                Ok(Some(theirs))
+            }
+        }
+    }
+
+    /// Three-way merge of `ours` and `theirs` against `base`. An entry only one side changed
+    /// takes that side's version; a change beats a delete; an entry both sides changed
+    /// differently keeps ours and records theirs as a conflict (see [`crate::conflicts`]).
+    /// Entries this instance can't decrypt are taken from `theirs`.
+    fn merge(
+        &self,
+        base: &HashMap<KeystoreKey, EntryId>,
+        ours: State,
+        theirs: State,
+    ) -> Result<State, KeystoreError> {
+        // Our entries in file order, then the ones only they have
+        let mut keys: Vec<(usize, &KeystoreKey)> =
+            ours.index.iter().map(|(key, &slot)| (slot, key)).collect();
+        keys.sort();
+        let mut theirs_only: Vec<(usize, &KeystoreKey)> = theirs
+            .index
+            .iter()
+            .filter(|(key, _)| !ours.index.contains_key(*key))
+            .map(|(key, &slot)| (slot, key))
+            .collect();
+        theirs_only.sort();
+        keys.extend(theirs_only);
+
+        let mut entries = Vec::new();
+        let mut displaced = Vec::new();
+        for (_, key) in keys {
+            let (mine, other) = (ours.entry(key), theirs.entry(key));
+            let mine_id = mine.map(EncryptedEntry::id);
+            let other_id = other.map(EncryptedEntry::id);
+            let base_id = base.get(key).copied();
+            let kept = if mine_id == other_id || other_id == base_id {
+                mine
+            } else if mine_id == base_id {
+                other
+            } else {
+                if let (Some(_), Some(other)) = (mine, other) {
+                    displaced.push((key, other));
+                }
+                mine.or(other)
+            };
+            entries.extend(kept.cloned());
+        }
+        let indexed: HashSet<usize> = theirs.index.values().copied().collect();
+        entries.extend(
+            theirs
+                .data
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(slot, _)| !indexed.contains(slot))
+                .map(|(_, entry)| entry.clone()),
+        );
+
+        let mut merged = State::new(
+            KeystoreData {
+                entries,
+                ..KeystoreData::empty()
+            },
+            &self.key,
+        );
+        let detected_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        for (key, other) in displaced {
+            let Some((_, _, value)) = decrypt_entry(&self.key, other) else {
+                continue;
+            };
+            let value = Zeroizing::new(value);
+            let record = conflict_entry(
+                &hex::encode(other.nonce),
+                &key.service,
+                &key.account,
+                &value,
+                other.revision,
+                detected_at,
+            )?;
+            merged.upsert(&self.key, &record)?;
+            drop(Zeroizing::new(record.value));
+        }
+        merged.synced = theirs.synced;
+        Ok(merged)
+    }
+
+    fn get_file_path() -> PathBuf {
+        let path = if cfg!(target_os = "windows") {
+            let appdata = std::env::var("LOCALAPPDATA").unwrap_or_else(|_| ".".to_string());
+            PathBuf::from(appdata).join("streaming-enhancement")
+        } else if cfg!(target_os = "macos") {
+            let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+            PathBuf::from(home).join("Library/Application Support/streaming-enhancement")
+        } else {
+            let config = std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| {
+                let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+                format!("{}/.config", home)
+            });
+            PathBuf::from(config).join("streaming-enhancement")
+        };
+
+        fs::create_dir_all(&path).ok();
+        path.join("keystore.fallback")
+    }
+
+    fn get_or_create_key() -> Result<AesGcmKey, KeystoreError> {
+        let key_file = if cfg!(target_os = "windows") {
+            let appdata = std::env::var("LOCALAPPDATA").unwrap_or_else(|_| ".".to_string());
+            PathBuf::from(appdata).join("streaming-enhancement/enc.key")
+        } else if cfg!(target_os = "macos") {
+            let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+            PathBuf::from(home).join("Library/Application Support/streaming-enhancement/enc.key")
+        } else {
+            let config = std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| {
+                let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+                format!("{}/.config", home)
+            });
+            PathBuf::from(config).join("streaming-enhancement/enc.key")
+        };
+
+        if let Ok(key_data) = fs::read(&key_file) {
+            return AesGcmKey::from_slice(&key_data).ok_or_else(|| {
+                KeystoreError::Corrupted(format!(
+                    "Invalid key file size: expected {} bytes, found {} bytes in {}",
+                    KEY_SIZE,
+                    key_data.len(),
+                    key_file.display()
+                ))
+            });
+        }
+
+        let key = AesGcmKey::generate()?;
+
+        let parent_dir = key_file.parent().unwrap();
+        fs::create_dir_all(parent_dir)?;
+
+        #[cfg(unix)]
+        {
+            use std::io::Write;
+            use std::os::unix::fs::OpenOptionsExt;
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&key_file)
+                .map_err(|e| {
+                    if e.kind() == std::io::ErrorKind::AlreadyExists {
+                        KeystoreError::Platform(format!(
+                            "Key file already exists but cannot be read: {}",
+                            key_file.display()
+                        ))
+                    } else {
+                        KeystoreError::Io(e)
+                    }
+                })?
+                .write_all(key.as_bytes())?;
+        }
+
+        #[cfg(not(unix))]
+        {
+            use std::io::Write;
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&key_file)
+                .map_err(|e| {
+                    if e.kind() == std::io::ErrorKind::AlreadyExists {
+                        KeystoreError::Platform(format!(
+                            "Key file already exists but cannot be read: {}",
+                            key_file.display()
+                        ))
+                    } else {
+                        KeystoreError::Io(e)
+                    }
+                })?
+                .write_all(key.as_bytes())?;
+        }
+
+        Ok(key)
+    }
+
+    /// Writes the file atomically, returning the digest of what was written
+    fn save_data(&self, data: &KeystoreData) -> Result<[u8; 32], KeystoreError> {
+        let json = serde_json::to_string_pretty(data)
+            .map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+
+        let parent_dir = self.file_path.parent().unwrap();
+        fs::create_dir_all(parent_dir)?;
+
+        let temp_path = parent_dir.join(format!(
+            ".{}-{:x}-{:x}.tmp",
+            self.file_path.file_name().unwrap().to_string_lossy(),
+            std::process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        #[cfg(unix)]
+        {
+            use std::io::Write;
+            use std::os::unix::fs::OpenOptionsExt;
+
+            let mut temp_file = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&temp_path)
+                .map_err(|e| {
+                    if e.kind() == std::io::ErrorKind::AlreadyExists {
+                        KeystoreError::Transient(format!(
+                            "Temp file already exists: {}",
+                            temp_path.display()
+                        ))
+                    } else {
+                        KeystoreError::Io(e)
+                    }
+                })?;
+
+            temp_file.write_all(json.as_bytes())?;
+            temp_file.flush()?;
+            temp_file.sync_all()?;
+        }
+
+        #[cfg(not(unix))]
+        {
+            use std::io::Write;
+
+            let mut temp_file = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&temp_path)
+                .map_err(|e| {
+                    if e.kind() == std::io::ErrorKind::AlreadyExists {
+                        KeystoreError::Transient(format!(
+                            "Temp file already exists: {}",
+                            temp_path.display()
+                        ))
+                    } else {
+                        KeystoreError::Io(e)
+                    }
+                })?;
+
+            temp_file.write_all(json.as_bytes())?;
+            temp_file.flush()?;
+            temp_file.sync_all()?;
+        }
+
+        fs::rename(&temp_path, &self.file_path)?;
+
+        Ok(Sha256::digest(json.as_bytes()).into())
+    }
+}
+
+fn too_new(file_path: &Path, version: u32) -> KeystoreError {
+    KeystoreError::FormatTooNew(format!(
+        "{} has format version {}, this build reads up to {}",
+        file_path.display(),
+        version,
+        FORMAT_VERSION
+    ))
+}
+
+impl KeystoreOperations for FallbackKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        let mut state = self.write_state()?;
+        let mut next = state.clone();
+        next.upsert(&self.key, entry)?;
+        self.commit(&mut state, next)
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        let state = self.read_state()?;
+        let key = KeystoreKey {
+            service: service.to_string(),
+            account: account.to_string(),
+        };
+        state
+            .live(&self.key, &key)
+            .and_then(|entry| decrypt_entry(&self.key, entry))
+            .map(|(_, _, value)| value)
+            .ok_or_else(|| KeystoreError::KeyNotFound(format!("{}:{}", service, account)))
+    }
+
+    /// Read from the entry's sealed metadata record, without decrypting its value
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        let state = self.read_state()?;
+        let key = KeystoreKey {
+            service: service.to_string(),
+            account: account.to_string(),
+        };
+        state
+            .live(&self.key, &key)
+            .map(|entry| decrypt_metadata(&self.key, entry))
+            .ok_or_else(|| KeystoreError::KeyNotFound(format!("{}:{}", service, account)))
+    }
+
+    /// A lookup in the index; only the entry's metadata is decrypted, for its expiry
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        let key = KeystoreKey {
+            service: service.to_string(),
+            account: account.to_string(),
+        };
+        Ok(self.read_state()?.live(&self.key, &key).is_some())
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let key = KeystoreKey {
+            service: service.to_string(),
+            account: account.to_string(),
+        };
+        let mut state = self.write_state()?;
+        let Some(&slot) = state.index.get(&key) else {
+            return Err(KeystoreError::KeyNotFound(format!(
+                "{}:{}",
+                service, account
+            )));
+        };
+        let next = state.without(&BTreeSet::from([slot]));
+        self.commit(&mut state, next)
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        let info = BackendInfo::new("encrypted-file")
+            .with_detail("path", self.file_path.display())
+            .with_detail(
+                "format_version",
+                self.read_only_version.unwrap_or(FORMAT_VERSION),
+            );
+        let info = match self.read_only_version {
+            Some(_) => info.with_detail("read_only", true),
+            None => info,
+        };
+        let info = match self.read_state().map(|state| state.unindexed()) {
+            Ok(0) | Err(_) => info,
+            Ok(undecryptable) => info.with_detail("undecryptable_entries", undecryptable),
+        };
+        let info = match &self.recovery {
+            Some(report) => {
+                let info = info
+                    .with_detail("recovered_entries", report.recovered)
+                    .with_detail("quarantined_entries", report.quarantined_entries);
+                match &report.quarantine_path {
+                    Some(path) => info.with_detail("quarantine_path", path.display()),
+                    None => info,
+                }
+            }
+            None => info,
+        };
+        let info = if self.migrations.is_empty() {
+            info
+        } else {
+            let sources: Vec<String> = self
+                .migrations
+                .iter()
+                .map(|outcome| outcome.source.display().to_string())
+                .collect();
+            let errors: Vec<String> = self
+                .migrations
+                .iter()
+                .filter_map(|outcome| {
+                    let error = outcome.error.as_ref()?;
+                    Some(format!("{}: {}", outcome.source.display(), error))
+                })
+                .collect();
+            let info = info
+                .with_detail("legacy_sources", sources.join(", "))
+                .with_detail(
+                    "legacy_imported_entries",
+                    self.migrations.iter().map(|o| o.imported).sum::<usize>(),
+                );
+            if errors.is_empty() {
+                info
+            } else {
+                info.with_detail("legacy_migration_error", errors.join("; "))
+            }
+        };
+        match &self.fallback_reason {
+            Some(reason) => info.with_detail("fallback_reason", reason),
+            None => info,
+        }
+    }
+
+    /// In file order; entries that no longer decrypt are skipped rather than failing the listing,
+    /// and expired ones left out
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let state = self.read_state()?;
+        let expired = state.expired(&self.key);
+        let mut keys: Vec<(&KeystoreKey, usize)> = state
+            .index
+            .iter()
+            .filter(|(_, slot)| !expired.contains(slot))
+            .map(|(key, &slot)| (key, slot))
+            .collect();
+        keys.sort_by_key(|&(_, slot)| slot);
+        Ok(keys.into_iter().map(|(key, _)| key.clone()).collect())
+    }
+
+    /// Under one read lock, so the values come from the same version of the file
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        let state = self.read_state()?;
+        Ok(keys
+            .iter()
+            .map(|key| {
+                state
+                    .live(&self.key, key)
+                    .and_then(|entry| decrypt_entry(&self.key, entry))
+                    .map(|(_, _, value)| value)
+            })
+            .collect())
+    }
+
+    /// One save for the batch; if it fails, none of the entries are written
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut state = self.write_state()?;
+        let mut next = state.clone();
+        for entry in entries {
+            next.upsert(&self.key, entry)?;
+        }
+        self.commit(&mut state, next)
+    }
+
+    /// One save for the batch; if it fails, none of the entries are removed
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut state = self.write_state()?;
+        let mut removed = Vec::new();
+        let mut slots = BTreeSet::new();
+        for key in keys {
+            if let Some(&slot) = state.index.get(key) {
+                if slots.insert(slot) {
+                    removed.push(key.clone());
+                }
+            }
+        }
+        if !slots.is_empty() {
+            let next = state.without(&slots);
+            self.commit(&mut state, next)?;
+        }
+        removed.sort();
+        Ok(removed)
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        // One save under the write lock, so the service disappears atomically
+        let mut state = self.write_state()?;
+        let mut accounts = Vec::new();
+        let mut removed = BTreeSet::new();
+        for (key, &slot) in &state.index {
+            if key.service == service {
+                accounts.push(key.account.clone());
+                removed.insert(slot);
+            }
+        }
+
+        if !removed.is_empty() {
+            let next = state.without(&removed);
+            self.commit(&mut state, next)?;
+        }
+        accounts.sort();
+        Ok(accounts)
+    }
+
+    /// Both entries are rewritten in one save under the write lock, so no reader or other writer
+    /// sees one swapped without the other
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        let mut state = self.write_state()?;
+        // Each value keeps its expiry
+        let value = |key: &KeystoreKey| {
+            let entry = state.live(&self.key, key);
+            entry
+                .and_then(|entry| decrypt_entry(&self.key, entry))
+                .map(|(_, _, value)| {
+                    let expires_at =
+                        entry.and_then(|entry| decrypt_metadata(&self.key, entry).expires_at);
+                    (Zeroizing::new(value), expires_at)
+                })
+                .ok_or_else(|| {
+                    KeystoreError::KeyNotFound(format!("{}:{}", key.service, key.account))
+                })
+        };
+        let (first, second) = (value(a)?, value(b)?);
+        if a == b {
+            return Ok(());
+        }
+        let mut next = state.clone();
+        for (key, (value, expires_at)) in [(a, &second), (b, &first)] {
+            next.upsert(
+                &self.key,
+                &KeystoreEntry {
+                    service: key.service.clone(),
+                    account: key.account.clone(),
+                    value: value.to_string(),
+                    expires_at: *expires_at,
+                    ..Default::default()
+                },
+            )?;
+        }
+        self.commit(&mut state, next)
+    }
+
+    /// Checks for the entry and writes it under the file lock, after taking in whatever other
+    /// processes saved, so two of them never both create it
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        let mut state = self.write_state()?;
+        let _lock = self.lock_file()?;
+        // Everything this instance wrote is saved, so the file is the newer state
+        if let Some(theirs) = self.changed_on_disk(&state.synced)? {
+            *state = theirs;
+        }
+        let key = KeystoreKey {
+            service: service.to_string(),
+            account: account.to_string(),
+        };
+        if let Some((_, _, value)) = state
+            .live(&self.key, &key)
+            .and_then(|entry| decrypt_entry(&self.key, entry))
+        {
+            return Ok(value);
+        }
+        let value = generate_secret(&SecretOptions {
+            length,
+            ..Default::default()
+        })?;
+        let mut next = state.clone();
+        next.upsert(
+            &self.key,
+            &KeystoreEntry {
+                service: key.service,
+                account: key.account,
+                value: value.clone(),
+                ..Default::default()
+            },
+        )?;
+        self.commit_locked(&mut state, next)?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread;
+    use tempfile::TempDir;
+
+    fn create_test_fallback(temp_dir: &TempDir) -> FallbackKeystore {
+        let file_path = temp_dir.path().join("keystore-test.fallback");
+        let key = AesGcmKey::generate().unwrap();
+        FallbackKeystore::open(file_path, key).unwrap()
+    }
+
+    fn create_test_entry(service: &str, account: &str, value: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: service.to_string(),
+            account: account.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_set_and_get_password() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let entry = create_test_entry("test-service", "test-account", "my-secret-password");
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("test-service", "test-account")
+            .unwrap();
+        assert_eq!(result, "my-secret-password");
+    }
+
+    #[test]
+    fn test_get_nonexistent_password() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let result = keystore.get_password("nonexistent-service", "nonexistent-account");
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            KeystoreError::KeyNotFound(_) => (),
+            _ => panic!("Expected KeyNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_has_password() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+        keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "bot".to_string(),
+                value: "token".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert!(keystore.has_password("twitch", "bot").unwrap());
+        assert!(!keystore.has_password("twitch", "alt").unwrap());
+        keystore.delete_password("twitch", "bot").unwrap();
+        assert!(!keystore.has_password("twitch", "bot").unwrap());
+    }
+
+    #[test]
+    fn test_expired_entries_are_dropped_on_save() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+        for (account, expires_at) in [
+            ("device-code", now_millis() - 1_000),
+            ("webhook", now_millis() + 3_600_000),
+        ] {
+            keystore
+                .set_password(&KeystoreEntry {
+                    expires_at: Some(expires_at),
+                    ..create_test_entry("twitch", account, "code")
+                })
+                .unwrap();
+        }
+        assert!(matches!(
+            keystore.get_password("twitch", "device-code"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+        assert_eq!(keystore.get_password("twitch", "webhook").unwrap(), "code");
+        assert_eq!(keystore.list_credentials("twitch").unwrap(), ["webhook"]);
+
+        keystore
+            .set_password(&create_test_entry("twitch", "bot", "token"))
+            .unwrap();
+        let reopened =
+            FallbackKeystore::open(keystore.file_path.clone(), keystore.key.clone()).unwrap();
+        assert_eq!(reopened.state.read().unwrap().index.len(), 2);
+    }
+
+    #[test]
+    fn test_delete_nonexistent_password() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let result = keystore.delete_password("nonexistent-service", "nonexistent-account");
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            KeystoreError::KeyNotFound(_) => (),
+            _ => panic!("Expected KeyNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_update_existing_password() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let entry1 = create_test_entry("update-service", "update-account", "old-password");
+        let entry2 = create_test_entry("update-service", "update-account", "new-password");
+
+        keystore.set_password(&entry1).unwrap();
+        keystore.set_password(&entry2).unwrap();
+
+        let result = keystore
+            .get_password("update-service", "update-account")
+            .unwrap();
+        assert_eq!(result, "new-password");
+
+        assert!(
+            keystore
+                .get_password("update-service", "update-account")
+                .unwrap()
+                == "new-password"
+        );
+    }
+
+    #[test]
+    fn test_empty_value() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let entry = create_test_entry("empty-service", "empty-account", "");
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("empty-service", "empty-account")
+            .unwrap();
+        assert_eq!(result, "");
+    }
+
+    #[test]
+    fn test_special_characters() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let special_value = "!@#$%^&*()_+-=[]{}|;':\",./<>?`~\n\t\r";
+        let entry = create_test_entry("special-service", "special-account", special_value);
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("special-service", "special-account")
+            .unwrap();
+        assert_eq!(result, special_value);
+    }
+
+    #[test]
+    fn test_long_value() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let long_value = "a".repeat(1000);
+        let entry = create_test_entry("long-service", "long-account", &long_value);
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("long-service", "long-account")
+            .unwrap();
+        assert_eq!(result, long_value);
+    }
+
+    #[test]
+    fn test_multiple_services() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let entries = vec![
+            create_test_entry("service1", "account1", "password1"),
+            create_test_entry("service1", "account2", "password2"),
+            create_test_entry("service2", "account1", "password3"),
+        ];
+
+        for entry in &entries {
+            keystore.set_password(entry).unwrap();
+        }
+
+        assert_eq!(
+            keystore.get_password("service1", "account1").unwrap(),
+            "password1"
+        );
+        assert_eq!(
+            keystore.get_password("service1", "account2").unwrap(),
+            "password2"
+        );
+        assert_eq!(
+            keystore.get_password("service2", "account1").unwrap(),
+            "password3"
+        );
+
+        let mut keys = keystore.list_keys().unwrap();
+        keys.sort();
+        assert_eq!(
+            keys,
+            entries
+                .iter()
+                .map(|e| KeystoreKey {
+                    service: e.service.clone(),
+                    account: e.account.clone(),
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_delete_service() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        for (service, account) in [
+            ("twitch", "bot-oauth"),
+            ("twitch", "broadcaster-oauth"),
+            ("youtube", "bot-oauth"),
+        ] {
+            keystore
+                .set_password(&create_test_entry(service, account, "token"))
+                .unwrap();
+        }
+
+        assert_eq!(
+            keystore.delete_service("twitch").unwrap(),
+            vec!["bot-oauth".to_string(), "broadcaster-oauth".to_string()]
+        );
+        assert!(keystore.delete_service("twitch").unwrap().is_empty());
+        assert!(keystore.get_password("twitch", "bot-oauth").is_err());
+        assert_eq!(
+            keystore.get_password("youtube", "bot-oauth").unwrap(),
+            "token"
+        );
+    }
+
+    #[test]
+    fn test_batches_are_saved_together() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+        let entries: Vec<KeystoreEntry> = ["bot-oauth", "broadcaster-oauth", "stream-key"]
+            .into_iter()
+            .map(|account| create_test_entry("twitch", account, account))
+            .collect();
+        keystore.set_many(&entries).unwrap();
+
+        let reopened =
+            FallbackKeystore::open(keystore.file_path.clone(), keystore.key.clone()).unwrap();
+        let key = |account: &str| KeystoreKey {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+        };
+        assert_eq!(
+            reopened
+                .get_many(&[key("stream-key"), key("missing"), key("bot-oauth")])
+                .unwrap(),
+            [
+                Some("stream-key".to_string()),
+                None,
+                Some("bot-oauth".to_string())
+            ]
+        );
+
+        assert_eq!(
+            keystore
+                .delete_many(&[key("stream-key"), key("missing"), key("bot-oauth")])
+                .unwrap(),
+            [key("bot-oauth"), key("stream-key")]
+        );
+        assert_eq!(keystore.list_keys().unwrap(), [key("broadcaster-oauth")]);
+    }
+
+    #[test]
+    fn test_swap() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+        let key = |account: &str| KeystoreKey {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+        };
+
+        keystore
+            .set_password(&create_test_entry("twitch", "stream-key", "blue"))
+            .unwrap();
+        keystore
+            .set_password(&create_test_entry("twitch", "stream-key-previous", "green"))
+            .unwrap();
+        keystore
+            .swap(&key("stream-key"), &key("stream-key-previous"))
+            .unwrap();
+        assert_eq!(
+            keystore.get_password("twitch", "stream-key").unwrap(),
+            "green"
+        );
+
+        // Saved together, so a reopened file holds both swapped
+        let reopened =
+            FallbackKeystore::open(keystore.file_path.clone(), keystore.key.clone()).unwrap();
+        assert_eq!(
+            reopened
+                .get_password("twitch", "stream-key-previous")
+                .unwrap(),
+            "blue"
+        );
+
+        assert!(matches!(
+            keystore.swap(&key("stream-key"), &key("missing")),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+        assert_eq!(
+            keystore.get_password("twitch", "stream-key").unwrap(),
+            "green"
+        );
+    }
+
+    #[test]
+    fn test_metadata_is_kept_across_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        keystore
+            .set_password(&KeystoreEntry {
+                label: Some("Bot account".to_string()),
+                comment: Some("Rotated by the refresh job".to_string()),
+                ..create_test_entry("twitch", "bot-oauth", "one")
+            })
+            .unwrap();
+        let first = keystore.get_metadata("twitch", "bot-oauth").unwrap();
+        assert_eq!(first.label.as_deref(), Some("Bot account"));
+        assert_eq!(first.created_at, first.updated_at);
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        keystore
+            .set_password(&KeystoreEntry {
+                comment: Some(String::new()),
+                ..create_test_entry("twitch", "bot-oauth", "two")
+            })
+            .unwrap();
+        let reopened =
+            FallbackKeystore::open(keystore.file_path.clone(), keystore.key.clone()).unwrap();
+        let second = reopened.get_metadata("twitch", "bot-oauth").unwrap();
+        assert_eq!(second.label.as_deref(), Some("Bot account"));
+        assert_eq!(second.comment, None);
+        assert_eq!(second.created_at, first.created_at);
+        assert!(second.updated_at > first.updated_at);
+
+        assert!(matches!(
+            keystore.get_metadata("twitch", "missing"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_utf8_values() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let utf8_value = "Hello 世界 🌍 Привет";
+        let entry = create_test_entry("utf8-service", "utf8-account", utf8_value);
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("utf8-service", "utf8-account")
+            .unwrap();
+        assert_eq!(result, utf8_value);
+    }
+
+    #[test]
+    fn test_delete_password() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let entry = create_test_entry("delete-service", "delete-account", "to-delete");
+
+        keystore.set_password(&entry).unwrap();
+        assert!(keystore
+            .get_password("delete-service", "delete-account")
+            .is_ok());
+
+        keystore
+            .delete_password("delete-service", "delete-account")
+            .unwrap();
+
+        let result = keystore.get_password("delete-service", "delete-account");
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            KeystoreError::KeyNotFound(_) => (),
+            _ => panic!("Expected KeyNotFound error after delete"),
+        }
+    }
+
+    #[test]
+    fn test_persistence() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("keystore-persist-test.fallback");
+        let key = AesGcmKey::generate().unwrap();
+
+        let entry = create_test_entry("persist-service", "persist-account", "persist-value");
+
+        {
+            let keystore1 = FallbackKeystore::open(file_path.clone(), key.clone()).unwrap();
+            keystore1.set_password(&entry).unwrap();
+        }
+
+        {
+            let keystore2 = FallbackKeystore::open(file_path.clone(), key).unwrap();
+            let result = keystore2
+                .get_password("persist-service", "persist-account")
+                .unwrap();
+            assert_eq!(result, "persist-value");
+        }
+    }
+
+    #[test]
+    fn test_colon_in_value() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let value_with_colons = "value:with:multiple:colons::";
+        let entry = create_test_entry("colon-service", "colon-account", value_with_colons);
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("colon-service", "colon-account")
+            .unwrap();
+        assert_eq!(result, value_with_colons);
+    }
+
+    #[test]
+    fn test_encryption_output_is_not_plaintext() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let entry = create_test_entry("encrypt-service", "encrypt-account", "plaintext-password");
+        keystore.set_password(&entry).unwrap();
+
+        let file_content = fs::read_to_string(&keystore.file_path).unwrap();
+
+        assert!(!file_content.contains("plaintext-password"));
+        assert!(!file_content.contains("encrypt-service"));
+        assert!(!file_content.contains("encrypt-account"));
+    }
+
+    #[test]
+    fn test_concurrent_writes_to_distinct_accounts() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = Arc::new(create_test_fallback(&temp_dir));
+
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let keystore = Arc::clone(&keystore);
+                thread::spawn(move || {
+                    for i in 0..10 {
+                        let entry = create_test_entry(
+                            "concurrent-service",
+                            &format!("account-{}-{}", t, i),
+                            &format!("value-{}-{}", t, i),
+                        );
+                        keystore.set_password(&entry).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Every write must be visible in memory and must have reached the file
+        let reopened =
+            FallbackKeystore::open(keystore.file_path.clone(), keystore.key.clone()).unwrap();
+
+        for t in 0..8 {
+            for i in 0..10 {
+                let account = format!("account-{}-{}", t, i);
+                let expected = format!("value-{}-{}", t, i);
+                assert_eq!(
+                    keystore
+                        .get_password("concurrent-service", &account)
+                        .unwrap(),
+                    expected
+                );
+                assert_eq!(
+                    reopened
+                        .get_password("concurrent-service", &account)
+                        .unwrap(),
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_concurrent_readers_and_writer() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = Arc::new(create_test_fallback(&temp_dir));
+
+        keystore
+            .set_password(&create_test_entry("rw-service", "rw-account", "value-0"))
+            .unwrap();
+
+        let writer = {
+            let keystore = Arc::clone(&keystore);
+            thread::spawn(move || {
+                for i in 1..=50 {
+                    let entry =
+                        create_test_entry("rw-service", "rw-account", &format!("value-{}", i));
+                    keystore.set_password(&entry).unwrap();
+                }
+            })
+        };
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let keystore = Arc::clone(&keystore);
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        let value = keystore.get_password("rw-service", "rw-account").unwrap();
+                        assert!(value.starts_with("value-"));
+                    }
+                })
+            })
+            .collect();
+
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert_eq!(
+            keystore.get_password("rw-service", "rw-account").unwrap(),
+            "value-50"
+        );
+    }
+
+    /// Writes, reads, deletes and listings of one worker's accounts; every other one is deleted
+    fn stress_worker(keystore: &FallbackKeystore, worker: &str) {
+        for i in 0..20 {
+            let account = format!("{}-{}", worker, i);
+            keystore
+                .set_password(&create_test_entry("stress", &account, &account))
+                .unwrap();
+            assert_eq!(keystore.get_password("stress", &account).unwrap(), account);
+            if i % 2 == 1 {
+                keystore.delete_password("stress", &account).unwrap();
+            }
+            assert!(keystore.list_keys().unwrap().len() <= 20 * 12);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_stress_across_threads_and_tasks() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+        let mut workers = Vec::new();
+
+        // Clones share one state, so each thread gets its own handle
+        let threads: Vec<_> = (0..6)
+            .map(|t| {
+                let keystore = keystore.clone();
+                let worker = format!("thread-{}", t);
+                workers.push(worker.clone());
+                thread::spawn(move || stress_worker(&keystore, &worker))
+            })
+            .collect();
+
+        #[cfg(feature = "async-runtime")]
+        {
+            let handle = crate::runtime::handle().unwrap();
+            let tasks: Vec<_> = (0..6)
+                .map(|t| {
+                    let keystore = keystore.clone();
+                    let worker = format!("task-{}", t);
+                    workers.push(worker.clone());
+                    handle.spawn(async move {
+                        tokio::task::yield_now().await;
+                        stress_worker(&keystore, &worker)
+                    })
+                })
+                .collect();
+            handle.block_on(async {
+                for task in tasks {
+                    task.await.unwrap();
+                }
+            });
+        }
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let mut expected: Vec<String> = workers
+            .iter()
+            .flat_map(|worker| (0..20).step_by(2).map(move |i| format!("{}-{}", worker, i)))
+            .collect();
+        expected.sort();
+        let reopened =
+            FallbackKeystore::open(keystore.file_path.clone(), keystore.key.clone()).unwrap();
+        for store in [&keystore, &reopened] {
+            let mut accounts: Vec<String> = store
+                .list_keys()
+                .unwrap()
+                .into_iter()
+                .map(|key| key.account)
+                .collect();
+            accounts.sort();
+            assert_eq!(accounts, expected);
+        }
+        assert_eq!(
+            reopened.get_password("stress", "thread-0-4").unwrap(),
+            "thread-0-4"
+        );
+    }
+
+    #[test]
+    fn test_unversioned_files_are_upgraded_and_newer_ones_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("keystore-test.fallback");
+        let key = AesGcmKey::generate().unwrap();
+        {
+            let keystore = FallbackKeystore::open(file_path.clone(), key.clone()).unwrap();
+            keystore
+                .set_password(&create_test_entry("svc", "acct", "value"))
+                .unwrap();
+        }
+
+        // Rewrite it the way builds before versioning did
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+        json.as_object_mut().unwrap().remove("format_version");
+        fs::write(&file_path, json.to_string()).unwrap();
+
+        let keystore = FallbackKeystore::open(file_path.clone(), key.clone()).unwrap();
+        assert_eq!(keystore.get_password("svc", "acct").unwrap(), "value");
+        let upgraded: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+        assert_eq!(upgraded["format_version"], FORMAT_VERSION);
+
+        // A future layout, even one this build can't parse, is reported as too new and kept
+        let future = format!(
+            r#"{{"format_version":{},"records":{{}}}}"#,
+            FORMAT_VERSION + 1
+        );
+        fs::write(&file_path, &future).unwrap();
+        assert!(matches!(
+            FallbackKeystore::open(file_path.clone(), key),
+            Err(KeystoreError::FormatTooNew(_))
+        ));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), future);
+    }
+
+    #[test]
+    fn test_newer_readable_files_open_read_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("keystore-test.fallback");
+        let key = AesGcmKey::generate().unwrap();
+        FallbackKeystore::open(file_path.clone(), key.clone())
+            .unwrap()
+            .set_password(&create_test_entry("twitch", "bot", "oauth:abc"))
+            .unwrap();
+
+        // A newer build that only added fields this one ignores
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+        json["format_version"] = (FORMAT_VERSION + 1).into();
+        json["sync_cursor"] = "abc".into();
+        let written = json.to_string();
+        fs::write(&file_path, &written).unwrap();
+
+        let keystore = FallbackKeystore::open(file_path.clone(), key).unwrap();
+        assert_eq!(keystore.read_only_version(), Some(FORMAT_VERSION + 1));
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "oauth:abc");
+        assert_eq!(keystore.list_keys().unwrap().len(), 1);
+        assert!(matches!(
+            keystore.set_password(&create_test_entry("twitch", "bot", "changed")),
+            Err(KeystoreError::FormatTooNew(_))
+        ));
+        assert!(keystore.delete_service("twitch").is_err());
+        assert_eq!(keystore.backend_info().details["read_only"], "true");
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), written);
+    }
+
+    #[test]
+    fn test_damaged_files_keep_intact_entries_and_quarantine_the_rest() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("keystore-test.fallback");
+        let key = AesGcmKey::generate().unwrap();
+        {
+            let keystore = FallbackKeystore::open(file_path.clone(), key.clone()).unwrap();
+            for account in ["a", "b", "c"] {
+                keystore
+                    .set_password(&create_test_entry("svc", account, "value"))
+                    .unwrap();
+            }
+        }
+        let intact = fs::read(&file_path).unwrap();
+
+        // Trailing garbage, including bytes that aren't UTF-8
+        let mut damaged = intact.clone();
+        damaged.extend_from_slice(b"\n\xff\xfe}garbage");
+        fs::write(&file_path, &damaged).unwrap();
+        let keystore = FallbackKeystore::open(file_path.clone(), key.clone()).unwrap();
+        let report = keystore.recovery_report().unwrap().clone();
+        assert_eq!(report.recovered, 3);
+        assert!(report.trailing_bytes > 0);
+        assert_eq!(fs::read(report.quarantine_path.unwrap()).unwrap(), damaged);
+        assert_eq!(keystore.get_password("svc", "c").unwrap(), "value");
+        // The intact entries were written back, so the next open is clean
+        drop(keystore);
+        let keystore = FallbackKeystore::open(file_path.clone(), key.clone()).unwrap();
+        assert!(keystore.recovery_report().is_none());
+
+        // Cut in the middle of the last record, with an older build's record beside it that
+        // predates revisions, and one in a layout this build doesn't know
+        let mut json: serde_json::Value = serde_json::from_slice(&intact).unwrap();
+        let entries = json["entries"].as_array_mut().unwrap();
+        entries[0].as_object_mut().unwrap().remove("revision");
+        entries.insert(1, serde_json::json!({ "sealed": "v9" }));
+        let text = serde_json::to_string_pretty(&json).unwrap();
+        let cut = text.rfind("\"ciphertext\"").unwrap() + 20;
+        fs::write(&file_path, &text[..cut]).unwrap();
+        let keystore = FallbackKeystore::open(file_path.clone(), key).unwrap();
+        let report = keystore.recovery_report().unwrap();
+        assert!(report.truncated);
+        assert_eq!(report.recovered, 2);
+        assert_eq!(report.quarantined_entries, 2);
+        assert_eq!(keystore.get_password("svc", "a").unwrap(), "value");
+        assert_eq!(keystore.get_password("svc", "b").unwrap(), "value");
+        assert!(keystore.get_password("svc", "c").is_err());
+        assert_eq!(keystore.backend_info().details["quarantined_entries"], "2");
+    }
+
+    mod salvage_properties {
+        use super::super::salvage;
+        use proptest::prelude::*;
+
+        /// A file as this build writes it, with `count` records of made-up ciphertext
+        fn written_file(count: usize) -> String {
+            let entries: Vec<serde_json::Value> = (0..count)
+                .map(|i| {
+                    serde_json::json!({
+                        "nonce": vec![i as u8; 12],
+                        "ciphertext": vec![i as u8; 20 + i],
+                        "revision": i,
+                    })
+                })
+                .collect();
+            serde_json::to_string_pretty(&serde_json::json!({
+                "format_version": 3,
+                "min_reader_version": 1,
+                "entries": entries,
+            }))
+            .unwrap()
+        }
+
+        proptest! {
+            #[test]
+            fn never_panics_on_arbitrary_input(input in "\\PC*") {
+                let salvaged = salvage(&input);
+                prop_assert_eq!(salvaged.report.recovered, salvaged.entries.len());
+            }
+
+            #[test]
+            fn prefixes_keep_every_complete_record(count in 0usize..6, cut in 0.0f64..1.0) {
+                let file = written_file(count);
+                let end = (file.len() as f64 * cut) as usize;
+                let salvaged = salvage(&file[..end]);
+                let complete = (0..count)
+                    .filter(|&i| {
+                        let record = format!("\"revision\": {}", i);
+                        file[..end].find(&record).is_some_and(|at| file[at..end].contains('}'))
+                    })
+                    .count();
+                prop_assert_eq!(salvaged.entries.len(), complete);
+                for (i, entry) in salvaged.entries.iter().enumerate() {
+                    prop_assert_eq!(entry.revision, i as u64);
+                }
+            }
+
+            #[test]
+            fn trailing_bytes_never_cost_an_entry(count in 0usize..6, tail in "\\PC{1,40}") {
+                let file = written_file(count);
+                let salvaged = salvage(&format!("{}{}", file, tail));
+                prop_assert_eq!(salvaged.entries.len(), count);
+                prop_assert_eq!(salvaged.header.format_version, 3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_or_create_creates_once_across_instances() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("keystore-test.fallback");
+        let key = AesGcmKey::generate().unwrap();
+        // Separate views of the file, as the daemon and the UI process would have
+        let instances: Vec<FallbackKeystore> = (0..4)
+            .map(|_| FallbackKeystore::open(file_path.clone(), key.clone()).unwrap())
+            .collect();
+
+        let values: Vec<String> = thread::scope(|scope| {
+            let handles: Vec<_> = instances
+                .iter()
+                .map(|keystore| {
+                    scope.spawn(move || keystore.get_or_create("overlay", "auth-key", 32).unwrap())
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(values[0].len(), 32);
+        assert!(values.iter().all(|value| *value == values[0]));
+
+        let reopened = FallbackKeystore::open(file_path, key).unwrap();
+        assert_eq!(
+            reopened.get_password("overlay", "auth-key").unwrap(),
+            values[0]
+        );
+        assert!(crate::conflicts::list_conflicts(&reopened)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_writers_sharing_a_file_merge_and_record_conflicts() {
+        use crate::conflicts::{displaced_value, list_conflicts};
+
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("keystore-test.fallback");
+        let key = AesGcmKey::generate().unwrap();
+        let laptop = FallbackKeystore::open(file_path.clone(), key.clone()).unwrap();
+        for account in ["bot", "refresh", "old"] {
+            laptop
+                .set_password(&create_test_entry("twitch", account, "v1"))
+                .unwrap();
+        }
+        // A second machine with its own view of the file, as a sync client would give it
+        let desktop = FallbackKeystore::open(file_path.clone(), key.clone()).unwrap();
+
+        laptop
+            .set_password(&create_test_entry("twitch", "bot", "laptop"))
+            .unwrap();
+        laptop.delete_password("twitch", "old").unwrap();
+        laptop
+            .set_password(&create_test_entry("youtube", "api", "laptop"))
+            .unwrap();
+        // Stale writes from the desktop: one conflicting, one independent
+        desktop
+            .set_password(&create_test_entry("twitch", "bot", "desktop"))
+            .unwrap();
+        desktop
+            .set_password(&create_test_entry("twitch", "refresh", "desktop"))
+            .unwrap();
+
+        let merged = FallbackKeystore::open(file_path, key).unwrap();
+        assert_eq!(merged.get_password("twitch", "bot").unwrap(), "desktop");
+        assert_eq!(merged.get_password("twitch", "refresh").unwrap(), "desktop");
+        assert_eq!(merged.get_password("youtube", "api").unwrap(), "laptop");
+        assert!(merged.get_password("twitch", "old").is_err());
+
+        let conflicts = list_conflicts(&merged).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            (conflicts[0].service.as_str(), conflicts[0].account.as_str()),
+            ("twitch", "bot")
+        );
+        assert_eq!(conflicts[0].revision, 2);
+        assert_eq!(
+            *displaced_value(&merged, &conflicts[0].id).unwrap(),
+            "laptop"
+        );
+    }
+
+    #[test]
+    fn test_backend_info() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = create_test_fallback(&temp_dir);
+
+        let info = keystore.backend_info();
+        assert_eq!(info.name, "encrypted-file");
+        assert_eq!(
+            info.details["path"],
+            temp_dir
+                .path()
+                .join("keystore-test.fallback")
+                .display()
+                .to_string()
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use super::keychain::{self, Accessibility};
+use super::{BackendInfo, KeystoreOperations};
+use crate::error::KeystoreError;
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+/// iOS Keychain backend storing generic passwords in the data-protection keychain
+pub struct IosKeystore {
+    accessibility: Accessibility,
+}
+
+impl IosKeystore {
+    pub fn new() -> Result<Self, KeystoreError> {
+        Ok(Self::with_accessibility(Accessibility::default()))
+    }
+
+    /// Uses `accessibility` as the protection class for every item this instance writes
+    pub fn with_accessibility(accessibility: Accessibility) -> Self {
+        Self { accessibility }
+    }
+
+    pub fn accessibility(&self) -> Accessibility {
+        self.accessibility
+    }
+}
+
+impl KeystoreOperations for IosKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        keychain::set_item(
+            &entry.service,
+            &entry.account,
+            entry.value.as_bytes(),
+            self.accessibility,
+            entry.label.as_deref(),
+            entry.comment.as_deref(),
+        )
+        .map_err(|e| keychain::keychain_error("set password", &entry.service, &entry.account, e))
+    }
+
+    /// The item's label, comment, creation and modification dates
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        keychain::get_attributes(service, account)
+            .map_err(|e| keychain::keychain_error("get metadata", service, account, e))
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        let bytes = keychain::get_item(service, account)
+            .map_err(|e| keychain::keychain_error("get password", service, account, e))?;
+        String::from_utf8(bytes).map_err(|e| KeystoreError::Serialization(e.to_string()))
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        keychain::delete_item(service, account)
+            .map_err(|e| keychain::keychain_error("delete password", service, account, e))
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut keys: Vec<KeystoreKey> = keychain::list_items(None, true)
+            .map_err(|e| keychain::keychain_error("list passwords", "*", "*", e))?
+            .into_iter()
+            .map(|(service, account)| KeystoreKey { service, account })
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Queries the keychain for `service` alone
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let mut accounts: Vec<String> = keychain::list_items(Some(service), true)
+            .map_err(|e| keychain::keychain_error("list passwords", service, "*", e))?
+            .into_iter()
+            .map(|(_, account)| account)
+            .collect();
+        accounts.sort();
+        Ok(accounts)
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        BackendInfo::new("ios-keychain")
+            .with_detail("accessibility", format!("{:?}", self.accessibility))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_entry(service: &str, account: &str, value: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: service.to_string(),
+            account: account.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn generate_unique_id() -> String {
+        uuid::Uuid::new_v4().simple().to_string()
+    }
+
+    #[test]
+    fn test_set_and_get_password() {
+        let keystore = IosKeystore::new().unwrap();
+        let id = generate_unique_id();
+        let service = format!("test-service-{}", id);
+        let account = format!("test-account-{}", id);
+
+        let entry = create_test_entry(&service, &account, "pairing-token");
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore.get_password(&service, &account).unwrap();
+        assert_eq!(result, "pairing-token");
+
+        keystore.delete_password(&service, &account).unwrap();
+    }
+
+    #[test]
+    fn test_get_nonexistent_password() {
+        let keystore = IosKeystore::new().unwrap();
+        let id = generate_unique_id();
+
+        let result = keystore.get_password(
+            &format!("nonexistent-service-{}", id),
+            &format!("nonexistent-account-{}", id),
+        );
+        match result.unwrap_err() {
+            KeystoreError::KeyNotFound(_) => (),
+            _ => panic!("Expected KeyNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_update_with_different_accessibility() {
+        let id = generate_unique_id();
+        let service = format!("update-service-{}", id);
+        let account = format!("update-account-{}", id);
+
+        let first = IosKeystore::with_accessibility(Accessibility::AfterFirstUnlock);
+        first
+            .set_password(&create_test_entry(&service, &account, "old-token"))
+            .unwrap();
+
+        let second = IosKeystore::with_accessibility(Accessibility::WhenUnlockedThisDeviceOnly);
+        second
+            .set_password(&create_test_entry(&service, &account, "new-token"))
+            .unwrap();
+
+        assert_eq!(first.get_password(&service, &account).unwrap(), "new-token");
+
+        second.delete_password(&service, &account).unwrap();
+    }
+}
@@ -0,0 +1,330 @@
+//! `SecItem` helpers for generic passwords in the data-protection keychain.
+//!
+//! `security_framework::passwords` only covers the default item attributes, so items that need a
+//! protection class are written here with the raw `SecItem` calls.
+
+use crate::error::KeystoreError;
+use crate::EntryMetadata;
+
+use core_foundation::array::{CFArray, CFArrayRef};
+use core_foundation::base::{CFType, CFTypeRef, TCFType};
+use core_foundation::boolean::CFBoolean;
+use core_foundation::data::CFData;
+use core_foundation::date::CFDate;
+use core_foundation::dictionary::CFDictionary;
+use core_foundation::string::CFString;
+use core_foundation_sys::base::OSStatus;
+use core_foundation_sys::data::CFDataRef;
+use core_foundation_sys::dictionary::CFDictionaryRef;
+use core_foundation_sys::string::CFStringRef;
+use security_framework::base::Error;
+use security_framework_sys::access_control::{
+    kSecAttrAccessibleAfterFirstUnlock, kSecAttrAccessibleAfterFirstUnlockThisDeviceOnly,
+    kSecAttrAccessibleWhenPasscodeSetThisDeviceOnly, kSecAttrAccessibleWhenUnlocked,
+    kSecAttrAccessibleWhenUnlockedThisDeviceOnly,
+};
+use security_framework_sys::base::{errSecDuplicateItem, errSecItemNotFound, errSecSuccess};
+use security_framework_sys::item::{
+    kSecAttrAccount, kSecAttrService, kSecClass, kSecClassGenericPassword, kSecMatchLimit,
+    kSecMatchLimitAll, kSecReturnAttributes, kSecReturnData, kSecUseDataProtectionKeychain,
+    kSecValueData,
+};
+use security_framework_sys::keychain_item::{
+    SecItemAdd, SecItemCopyMatching, SecItemDelete, SecItemUpdate,
+};
+
+extern "C" {
+    // Not exported by security-framework-sys
+    static kSecAttrAccessible: CFStringRef;
+    static kSecAttrLabel: CFStringRef;
+    static kSecAttrComment: CFStringRef;
+    static kSecAttrCreationDate: CFStringRef;
+    static kSecAttrModificationDate: CFStringRef;
+}
+
+/// Seconds from the Unix epoch to `CFAbsoluteTime`'s, 2001-01-01
+const ABSOLUTE_TIME_OFFSET: f64 = 978_307_200.0;
+
+/// `kSecAttrAccessible` protection class applied to newly written items
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Accessibility {
+    WhenUnlocked,
+    /// Readable by background work once the device has been unlocked after boot
+    #[default]
+    AfterFirstUnlock,
+    WhenUnlockedThisDeviceOnly,
+    AfterFirstUnlockThisDeviceOnly,
+    WhenPasscodeSetThisDeviceOnly,
+}
+
+impl Accessibility {
+    fn attribute(self) -> CFString {
+        unsafe {
+            CFString::wrap_under_get_rule(match self {
+                Accessibility::WhenUnlocked => kSecAttrAccessibleWhenUnlocked,
+                Accessibility::AfterFirstUnlock => kSecAttrAccessibleAfterFirstUnlock,
+                Accessibility::WhenUnlockedThisDeviceOnly => {
+                    kSecAttrAccessibleWhenUnlockedThisDeviceOnly
+                }
+                Accessibility::AfterFirstUnlockThisDeviceOnly => {
+                    kSecAttrAccessibleAfterFirstUnlockThisDeviceOnly
+                }
+                Accessibility::WhenPasscodeSetThisDeviceOnly => {
+                    kSecAttrAccessibleWhenPasscodeSetThisDeviceOnly
+                }
+            })
+        }
+    }
+}
+
+/// `OSStatus` codes the mapping below tells apart, from `SecBase.h`
+const ERR_SEC_USER_CANCELED: OSStatus = -128;
+const ERR_SEC_PARAM: OSStatus = -50;
+const ERR_SEC_NOT_AVAILABLE: OSStatus = -25291;
+const ERR_SEC_AUTH_FAILED: OSStatus = -25293;
+const ERR_SEC_INTERACTION_NOT_ALLOWED: OSStatus = -25308;
+const ERR_SEC_INTERACTION_REQUIRED: OSStatus = -25315;
+const ERR_SEC_DECODE: OSStatus = -26275;
+/// Returned by the data-protection keychain when the process lacks a keychain-access-groups
+/// entitlement, e.g. unsigned builds or a plain `node` binary
+pub const ERR_SEC_MISSING_ENTITLEMENT: OSStatus = -34018;
+
+/// Maps a failure to `action` the item `service`/`account`
+pub fn keychain_error(action: &str, service: &str, account: &str, e: Error) -> KeystoreError {
+    let message = format!("Failed to {}: {}", action, e);
+    match e.code() {
+        errSecItemNotFound => KeystoreError::KeyNotFound(format!("{}:{}", service, account)),
+        // On iOS the item's protection class is unavailable until the device is unlocked; on
+        // macOS the keychain would have to prompt, which this process isn't allowed to do
+        ERR_SEC_INTERACTION_NOT_ALLOWED if cfg!(target_os = "ios") => {
+            KeystoreError::KeyringLocked(message)
+        }
+        ERR_SEC_INTERACTION_NOT_ALLOWED | ERR_SEC_INTERACTION_REQUIRED => {
+            KeystoreError::InteractionRequired(message)
+        }
+        ERR_SEC_AUTH_FAILED | ERR_SEC_USER_CANCELED | ERR_SEC_MISSING_ENTITLEMENT => {
+            KeystoreError::AccessDenied(message)
+        }
+        ERR_SEC_NOT_AVAILABLE => KeystoreError::Transient(message),
+        ERR_SEC_DECODE => KeystoreError::Corrupted(message),
+        ERR_SEC_PARAM => KeystoreError::InvalidInput(message),
+        _ => KeystoreError::Platform(message),
+    }
+}
+
+fn base_query(service: &str, account: &str) -> Vec<(CFString, CFType)> {
+    unsafe {
+        vec![
+            (
+                CFString::wrap_under_get_rule(kSecClass),
+                CFString::wrap_under_get_rule(kSecClassGenericPassword).into_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecAttrService),
+                CFString::from(service).into_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecAttrAccount),
+                CFString::from(account).into_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecUseDataProtectionKeychain),
+                CFBoolean::true_value().into_CFType(),
+            ),
+        ]
+    }
+}
+
+fn cvt(status: OSStatus) -> Result<(), Error> {
+    if status == errSecSuccess {
+        Ok(())
+    } else {
+        Err(Error::from_code(status))
+    }
+}
+
+/// Creates or updates a generic password, (re)applying the protection class. A `label` or
+/// `comment` of `None` leaves the item's attribute as it is.
+pub fn set_item(
+    service: &str,
+    account: &str,
+    value: &[u8],
+    accessibility: Accessibility,
+    label: Option<&str>,
+    comment: Option<&str>,
+) -> Result<(), Error> {
+    let mut attributes = unsafe {
+        vec![
+            (
+                CFString::wrap_under_get_rule(kSecValueData),
+                CFData::from_buffer(value).into_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecAttrAccessible),
+                accessibility.attribute().into_CFType(),
+            ),
+        ]
+    };
+    for (attribute, text) in unsafe { [(kSecAttrLabel, label), (kSecAttrComment, comment)] } {
+        if let Some(text) = text {
+            attributes.push((
+                unsafe { CFString::wrap_under_get_rule(attribute) },
+                CFString::from(text).into_CFType(),
+            ));
+        }
+    }
+
+    let mut add = base_query(service, account);
+    add.extend(attributes.iter().cloned());
+    let add = CFDictionary::from_CFType_pairs(&add);
+
+    let mut result: CFTypeRef = std::ptr::null();
+    let status = unsafe { SecItemAdd(add.as_concrete_TypeRef(), &mut result) };
+    if status != errSecDuplicateItem {
+        return cvt(status);
+    }
+
+    let query = CFDictionary::from_CFType_pairs(&base_query(service, account));
+    let update = CFDictionary::from_CFType_pairs(&attributes);
+    cvt(unsafe { SecItemUpdate(query.as_concrete_TypeRef(), update.as_concrete_TypeRef()) })
+}
+
+pub fn get_item(service: &str, account: &str) -> Result<Vec<u8>, Error> {
+    let mut query = base_query(service, account);
+    query.push(unsafe {
+        (
+            CFString::wrap_under_get_rule(kSecReturnData),
+            CFBoolean::true_value().into_CFType(),
+        )
+    });
+    let query = CFDictionary::from_CFType_pairs(&query);
+
+    let mut result: CFTypeRef = std::ptr::null();
+    cvt(unsafe { SecItemCopyMatching(query.as_concrete_TypeRef(), &mut result) })?;
+    if result.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let data = unsafe { CFData::wrap_under_create_rule(result as CFDataRef) };
+    Ok(data.bytes().to_vec())
+}
+
+/// Label, comment and timestamps of a generic password, without reading its value
+pub fn get_attributes(service: &str, account: &str) -> Result<EntryMetadata, Error> {
+    let mut query = base_query(service, account);
+    query.push(unsafe {
+        (
+            CFString::wrap_under_get_rule(kSecReturnAttributes),
+            CFBoolean::true_value().into_CFType(),
+        )
+    });
+    let query = CFDictionary::from_CFType_pairs(&query);
+
+    let mut result: CFTypeRef = std::ptr::null();
+    cvt(unsafe { SecItemCopyMatching(query.as_concrete_TypeRef(), &mut result) })?;
+    if result.is_null() {
+        return Ok(EntryMetadata::default());
+    }
+
+    let item = unsafe {
+        CFDictionary::<CFString, CFType>::wrap_under_create_rule(result as CFDictionaryRef)
+    };
+    let text = |attribute: CFStringRef| {
+        item.find(&unsafe { CFString::wrap_under_get_rule(attribute) })
+            .and_then(|value| value.downcast::<CFString>())
+            .map(|value| value.to_string())
+            // Cleared by writing an empty string
+            .filter(|value| !value.is_empty())
+    };
+    let time = |attribute: CFStringRef| {
+        item.find(&unsafe { CFString::wrap_under_get_rule(attribute) })
+            .and_then(|value| value.downcast::<CFDate>())
+            .map(|date| ((date.abs_time() + ABSOLUTE_TIME_OFFSET) * 1000.0) as u64)
+    };
+    unsafe {
+        Ok(EntryMetadata {
+            label: text(kSecAttrLabel),
+            comment: text(kSecAttrComment),
+            created_at: time(kSecAttrCreationDate),
+            updated_at: time(kSecAttrModificationDate),
+            expires_at: None,
+        })
+    }
+}
+
+/// Service and account of every generic password, or of those under `service`, without reading
+/// the values. Searches the data-protection keychain, or the legacy file-based keychains when
+/// `data_protection` is false.
+pub fn list_items(
+    service: Option<&str>,
+    data_protection: bool,
+) -> Result<Vec<(String, String)>, Error> {
+    let mut query = unsafe {
+        vec![
+            (
+                CFString::wrap_under_get_rule(kSecClass),
+                CFString::wrap_under_get_rule(kSecClassGenericPassword).into_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecMatchLimit),
+                CFString::wrap_under_get_rule(kSecMatchLimitAll).into_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecReturnAttributes),
+                CFBoolean::true_value().into_CFType(),
+            ),
+        ]
+    };
+    if let Some(service) = service {
+        query.push(unsafe {
+            (
+                CFString::wrap_under_get_rule(kSecAttrService),
+                CFString::from(service).into_CFType(),
+            )
+        });
+    }
+    if data_protection {
+        query.push(unsafe {
+            (
+                CFString::wrap_under_get_rule(kSecUseDataProtectionKeychain),
+                CFBoolean::true_value().into_CFType(),
+            )
+        });
+    }
+    let query = CFDictionary::from_CFType_pairs(&query);
+
+    let mut result: CFTypeRef = std::ptr::null();
+    match unsafe { SecItemCopyMatching(query.as_concrete_TypeRef(), &mut result) } {
+        errSecItemNotFound => return Ok(Vec::new()),
+        status => cvt(status)?,
+    }
+    if result.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let items = unsafe {
+        CFArray::<CFDictionary<CFString, CFType>>::wrap_under_create_rule(result as CFArrayRef)
+    };
+    let (service_key, account_key) = unsafe {
+        (
+            CFString::wrap_under_get_rule(kSecAttrService),
+            CFString::wrap_under_get_rule(kSecAttrAccount),
+        )
+    };
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let text = |key: &CFString| {
+                item.find(key)
+                    .and_then(|value| value.downcast::<CFString>())
+                    .map(|value| value.to_string())
+            };
+            Some((text(&service_key)?, text(&account_key)?))
+        })
+        .collect())
+}
+
+pub fn delete_item(service: &str, account: &str) -> Result<(), Error> {
+    let query = CFDictionary::from_CFType_pairs(&base_query(service, account));
+    cvt(unsafe { SecItemDelete(query.as_concrete_TypeRef()) })
+}
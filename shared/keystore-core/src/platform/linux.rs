@@ -0,0 +1,911 @@
+use super::{BackendInfo, KeystoreOperations};
+use crate::error::KeystoreError;
+use crate::{KeystoreEntry, KeystoreKey};
+
+use dbus::arg::{RefArg, Variant};
+use dbus::blocking::Connection;
+use dbus::Message;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// How long [`LinuxKeystore::probe`] waits for Secret Service before giving up on it
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct LinuxKeystore;
+
+impl LinuxKeystore {
+    pub fn new() -> Result<Self, KeystoreError> {
+        Ok(Self)
+    }
+
+    /// Checks Secret Service answers within `timeout`, returning why not otherwise. A session bus
+    /// with no keyring daemon can block each call for DBus's own 25s timeout; the blocked probe
+    /// thread is left to finish in the background.
+    pub fn probe(&self, timeout: Duration) -> Result<(), String> {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(LinuxKeystore.is_available());
+        });
+        match rx.recv_timeout(timeout) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("Secret Service is unavailable".to_string()),
+            Err(_) => Err(format!(
+                "Secret Service did not respond within {}ms",
+                timeout.as_millis()
+            )),
+        }
+    }
+}
+
+/// Why this process obviously has no Secret Service to talk to (no session bus, a container, or
+/// CI), so backend selection can skip probing it
+pub fn headless_reason() -> Option<String> {
+    headless_reason_from(
+        |name| std::env::var(name).ok().filter(|value| !value.is_empty()),
+        |path| Path::new(path).exists(),
+    )
+}
+
+fn headless_reason_from(
+    var: impl Fn(&str) -> Option<String>,
+    exists: impl Fn(&str) -> bool,
+) -> Option<String> {
+    if exists("/.dockerenv") || exists("/run/.containerenv") {
+        return Some("running in a container".to_string());
+    }
+    if var("CI").is_some_and(|value| value != "false" && value != "0") {
+        return Some("running under CI".to_string());
+    }
+    // libdbus finds the systemd user bus without the variable
+    let user_bus = var("XDG_RUNTIME_DIR").is_some_and(|dir| exists(&format!("{}/bus", dir)));
+    if var("DBUS_SESSION_BUS_ADDRESS").is_none() && !user_bus {
+        return Some("no DBus session bus".to_string());
+    }
+    None
+}
+
+const SS_DEST: &str = "org.freedesktop.secrets";
+const SS_PATH: &str = "/org/freedesktop/secrets";
+const SS_SERVICE: &str = "org.freedesktop.Secret.Service";
+const SS_ITEM: &str = "org.freedesktop.Secret.Item";
+const SS_COLLECTION: &str = "org.freedesktop.Secret.Collection";
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+
+/// Per-call timeout, the same keyring's DBus client uses
+const CALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Collection keyring writes entries into, recorded in each item's `target` attribute
+const DEFAULT_TARGET: &str = "default";
+
+type ItemPath = dbus::Path<'static>;
+
+/// Items a search matched, unlocked then locked
+type SearchResult = (Vec<ItemPath>, Vec<ItemPath>);
+
+/// Session, parameters, value and content type, as `GetSecrets` returns each secret
+type Secret = (ItemPath, Vec<u8>, Vec<u8>, String);
+
+/// Path and attributes of an item
+type ItemAttributes = (String, HashMap<String, String>);
+
+fn dbus_error(e: dbus::Error) -> KeystoreError {
+    classify_dbus(&e, format!("Secret Service call failed: {}", e))
+}
+
+/// Sorts a DBus failure by its error name, keeping `message`
+fn classify_dbus(e: &dbus::Error, message: String) -> KeystoreError {
+    match e.name().unwrap_or_default() {
+        "org.freedesktop.DBus.Error.NoReply"
+        | "org.freedesktop.DBus.Error.Timeout"
+        | "org.freedesktop.DBus.Error.TimedOut" => KeystoreError::Timeout(message),
+        // No keyring daemon yet, as early in a session; it may be activated by the next call
+        "org.freedesktop.DBus.Error.ServiceUnknown"
+        | "org.freedesktop.DBus.Error.NameHasNoOwner"
+        | "org.freedesktop.DBus.Error.NoServer"
+        | "org.freedesktop.DBus.Error.Disconnected" => KeystoreError::Transient(message),
+        "org.freedesktop.DBus.Error.AccessDenied" => KeystoreError::AccessDenied(message),
+        "org.freedesktop.Secret.Error.IsLocked" => KeystoreError::KeyringLocked(message),
+        "org.freedesktop.Secret.Error.NoSuchObject" => KeystoreError::KeyNotFound(message),
+        _ => KeystoreError::Platform(message),
+    }
+}
+
+/// Maps a keyring failure to `action` the entry `service`/`account`
+fn keyring_error(e: keyring::Error, action: &str, service: &str, account: &str) -> KeystoreError {
+    let message = format!("Failed to {}: {}", action, e);
+    match &e {
+        keyring::Error::NoEntry => KeystoreError::KeyNotFound(format!("{}:{}", service, account)),
+        // A dismissed unlock prompt is the user saying no; otherwise the collection is locked
+        keyring::Error::NoStorageAccess(inner) if inner.to_string().contains("dismissed") => {
+            KeystoreError::AccessDenied(message)
+        }
+        keyring::Error::NoStorageAccess(_) => KeystoreError::KeyringLocked(message),
+        keyring::Error::PlatformFailure(inner) => {
+            match inner
+                .source()
+                .and_then(|source| source.downcast_ref::<dbus::Error>())
+            {
+                Some(dbus) => classify_dbus(dbus, message),
+                // dbus-secret-service's `Unavailable`
+                None if inner.to_string().starts_with("No DBus session") => {
+                    KeystoreError::Transient(message)
+                }
+                None => KeystoreError::Platform(message),
+            }
+        }
+        keyring::Error::BadEncoding(_) => KeystoreError::Corrupted(message),
+        keyring::Error::TooLong(..) | keyring::Error::Invalid(..) => {
+            KeystoreError::InvalidInput(message)
+        }
+        keyring::Error::Ambiguous(_) => KeystoreError::Ambiguous(message),
+        _ => KeystoreError::Platform(message),
+    }
+}
+
+/// Raw Secret Service calls for bulk work, where going through keyring would cost a DBus
+/// round-trip per item. Independent calls are pipelined: all are sent before any reply is read,
+/// so a batch takes one round-trip however many items it covers.
+struct SecretServiceBus {
+    connection: Connection,
+    /// Plain session; the session bus is private to the user, as for keyring's own calls
+    session: ItemPath,
+}
+
+impl SecretServiceBus {
+    fn connect() -> Result<Self, KeystoreError> {
+        let connection = Connection::new_session().map_err(dbus_error)?;
+        let (_, session): (Variant<Box<dyn RefArg>>, ItemPath) = connection
+            .with_proxy(SS_DEST, SS_PATH, CALL_TIMEOUT)
+            .method_call(
+                SS_SERVICE,
+                "OpenSession",
+                ("plain", Variant(Box::new(String::new()) as Box<dyn RefArg>)),
+            )
+            .map_err(dbus_error)?;
+        Ok(Self {
+            connection,
+            session,
+        })
+    }
+
+    /// Sends every message, then collects the replies in the order of `messages`
+    fn pipelined(
+        &self,
+        messages: Vec<Message>,
+    ) -> Result<Vec<Result<Message, dbus::Error>>, KeystoreError> {
+        let channel = self.connection.channel();
+        let mut pending = HashMap::new();
+        for (index, message) in messages.into_iter().enumerate() {
+            let serial = channel.send(message).map_err(|()| {
+                KeystoreError::Platform("Failed to send a Secret Service call".to_string())
+            })?;
+            pending.insert(serial, index);
+        }
+        channel.flush();
+
+        let mut replies: Vec<Option<Result<Message, dbus::Error>>> =
+            (0..pending.len()).map(|_| None).collect();
+        let deadline = Instant::now() + CALL_TIMEOUT;
+        while !pending.is_empty() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(KeystoreError::Timeout(format!(
+                    "Secret Service did not answer {} calls within {}ms",
+                    pending.len(),
+                    CALL_TIMEOUT.as_millis()
+                )));
+            }
+            let Some(mut reply) = channel
+                .blocking_pop_message(remaining)
+                .map_err(dbus_error)?
+            else {
+                continue;
+            };
+            // Signals and anything else that isn't one of our replies
+            let Some(index) = reply
+                .get_reply_serial()
+                .and_then(|serial| pending.remove(&serial))
+            else {
+                continue;
+            };
+            let failed = reply.as_result().err();
+            replies[index] = Some(match failed {
+                Some(e) => Err(e),
+                None => Ok(reply),
+            });
+        }
+        Ok(replies.into_iter().flatten().collect())
+    }
+
+    fn call(path: &ItemPath, interface: &str, method: &str) -> Result<Message, KeystoreError> {
+        Message::new_method_call(SS_DEST, path.clone(), interface, method)
+            .map_err(KeystoreError::Platform)
+    }
+
+    /// One `SearchItems` per attribute set, pipelined
+    fn search_each(
+        &self,
+        searches: &[HashMap<&str, &str>],
+    ) -> Result<Vec<SearchResult>, KeystoreError> {
+        let root = ItemPath::from(SS_PATH);
+        let messages = searches
+            .iter()
+            .map(|attributes| {
+                Ok(Self::call(&root, SS_SERVICE, "SearchItems")?.append1(attributes.clone()))
+            })
+            .collect::<Result<Vec<_>, KeystoreError>>()?;
+        self.pipelined(messages)?
+            .into_iter()
+            .map(|reply| {
+                reply
+                    .map_err(dbus_error)?
+                    .read_all::<SearchResult>()
+                    .map_err(dbus_error)
+            })
+            .collect()
+    }
+
+    /// Attributes of each item, `None` for items deleted since they were found
+    fn attributes(
+        &self,
+        items: &[ItemPath],
+    ) -> Result<Vec<Option<HashMap<String, String>>>, KeystoreError> {
+        let messages = items
+            .iter()
+            .map(|item| Ok(Self::call(item, PROPERTIES, "Get")?.append2(SS_ITEM, "Attributes")))
+            .collect::<Result<Vec<_>, KeystoreError>>()?;
+        Ok(self
+            .pipelined(messages)?
+            .into_iter()
+            .map(|reply| {
+                let (Variant(attributes),): (Variant<HashMap<String, String>>,) =
+                    reply.ok()?.read_all().ok()?;
+                Some(attributes)
+            })
+            .collect())
+    }
+
+    /// Secrets of unlocked items in one `GetSecrets` call; locked items are left out
+    fn secrets(
+        &self,
+        items: Vec<ItemPath>,
+    ) -> Result<HashMap<ItemPath, Zeroizing<Vec<u8>>>, KeystoreError> {
+        if items.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let (secrets,): (HashMap<ItemPath, Secret>,) = self
+            .connection
+            .with_proxy(SS_DEST, SS_PATH, CALL_TIMEOUT)
+            .method_call(SS_SERVICE, "GetSecrets", (items, self.session.clone()))
+            .map_err(dbus_error)?;
+        Ok(secrets
+            .into_iter()
+            .map(|(item, (_, _, value, _))| (item, Zeroizing::new(value)))
+            .collect())
+    }
+}
+
+/// Calls `notify` for every item created, changed or deleted in any collection, from a thread that
+/// listens on its own session bus connection for the rest of the process
+pub(crate) fn watch_changes(notify: fn()) -> Result<(), KeystoreError> {
+    let connection = Connection::new_session().map_err(dbus_error)?;
+    connection
+        .add_match_no_cb(&format!("type='signal',interface='{}'", SS_COLLECTION))
+        .map_err(dbus_error)?;
+    thread::Builder::new()
+        .name("keystore-watch".to_string())
+        .spawn(move || loop {
+            match connection
+                .channel()
+                .blocking_pop_message(Duration::from_secs(60))
+            {
+                Ok(Some(message)) => {
+                    if message
+                        .interface()
+                        .is_some_and(|interface| &*interface == SS_COLLECTION)
+                    {
+                        notify();
+                    }
+                }
+                Ok(None) => {}
+                // The bus went away; nothing will report changes again
+                Err(_) => return,
+            }
+        })?;
+    Ok(())
+}
+
+/// Keys of the items matching `attributes`, locked or not, sorted, from one search and one
+/// pipelined round of attribute reads
+fn search_keys(attributes: HashMap<&str, &str>) -> Result<Vec<KeystoreKey>, KeystoreError> {
+    let bus = SecretServiceBus::connect()?;
+    let (unlocked, locked) = bus.search_each(&[attributes])?.remove(0);
+    let items: Vec<ItemPath> = unlocked.into_iter().chain(locked).collect();
+    let mut keys: Vec<KeystoreKey> = bus
+        .attributes(&items)?
+        .into_iter()
+        .flatten()
+        .filter_map(|mut attributes| {
+            Some(KeystoreKey {
+                service: attributes.remove("service")?,
+                account: attributes.remove("username")?,
+            })
+        })
+        .collect();
+    keys.sort();
+    keys.dedup();
+    Ok(keys)
+}
+
+/// Items of any application matching `attributes`, locked or not, with the path and attributes
+/// of each
+pub(crate) fn foreign_items(
+    attributes: HashMap<&str, &str>,
+) -> Result<Vec<ItemAttributes>, KeystoreError> {
+    let bus = SecretServiceBus::connect()?;
+    let (unlocked, locked) = bus.search_each(&[attributes])?.remove(0);
+    let items: Vec<ItemPath> = unlocked.into_iter().chain(locked).collect();
+    let attributes = bus.attributes(&items)?;
+    Ok(items
+        .into_iter()
+        .zip(attributes)
+        .filter_map(|(item, attributes)| Some((item.to_string(), attributes?)))
+        .collect())
+}
+
+/// Value of the item at `path`, read only while its attributes still include `expected`, so a path
+/// passed back by the caller can't point at some other item
+pub(crate) fn read_foreign(
+    path: &str,
+    expected: &[(&str, &str)],
+) -> Result<Zeroizing<String>, KeystoreError> {
+    let item = ItemPath::new(path.to_string()).map_err(KeystoreError::InvalidInput)?;
+    let bus = SecretServiceBus::connect()?;
+    let attributes = bus
+        .attributes(std::slice::from_ref(&item))?
+        .remove(0)
+        .ok_or_else(|| KeystoreError::KeyNotFound(path.to_string()))?;
+    if expected
+        .iter()
+        .any(|(name, value)| attributes.get(*name).map(String::as_str) != Some(*value))
+    {
+        return Err(KeystoreError::InvalidInput(format!(
+            "{} no longer matches the credential found",
+            path
+        )));
+    }
+    let secret = bus
+        .secrets(vec![item.clone()])?
+        .remove(&item)
+        .ok_or_else(|| KeystoreError::KeyringLocked(format!("{} is locked", path)))?;
+    String::from_utf8(secret.to_vec())
+        .map(Zeroizing::new)
+        .map_err(|e| KeystoreError::Serialization(e.to_string()))
+}
+
+impl KeystoreOperations for LinuxKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        keyring::Entry::new(&entry.service, &entry.account)
+            .map_err(|e| keyring_error(e, "create entry", &entry.service, &entry.account))?
+            .set_password(&entry.value)
+            .map_err(|e| keyring_error(e, "set password", &entry.service, &entry.account))
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        let entry = keyring::Entry::new(service, account)
+            .map_err(|e| keyring_error(e, "create entry", service, account))?;
+
+        entry
+            .get_password()
+            .map_err(|e| keyring_error(e, "get password", service, account))
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let entry = keyring::Entry::new(service, account)
+            .map_err(|e| keyring_error(e, "create entry", service, account))?;
+
+        entry
+            .delete_credential()
+            .map_err(|e| keyring_error(e, "delete password", service, account))
+    }
+
+    fn is_available(&self) -> bool {
+        match keyring::Entry::new("keystore-availability-test", "test-availability") {
+            Ok(entry) => match entry.get_password() {
+                Ok(_) | Err(keyring::Error::NoEntry) => true,
+                Err(_) => false,
+            },
+            Err(_) => false,
+        }
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        BackendInfo::new("secret-service")
+    }
+
+    /// Every keyring entry in the default collection, including other applications' entries
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        search_keys(HashMap::from([("target", DEFAULT_TARGET)]))
+    }
+
+    /// One search on the `service` attribute instead of listing the whole collection
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        Ok(search_keys(HashMap::from([
+            ("target", DEFAULT_TARGET),
+            ("service", service),
+        ]))?
+        .into_iter()
+        .map(|key| key.account)
+        .collect())
+    }
+
+    /// One search, locked items included, so the secret is never read and a locked collection
+    /// doesn't prompt
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        let bus = SecretServiceBus::connect()?;
+        let (unlocked, locked) = bus
+            .search_each(&[HashMap::from([
+                ("target", DEFAULT_TARGET),
+                ("service", service),
+                ("username", account),
+            ])])?
+            .remove(0);
+        Ok(!unlocked.is_empty() || !locked.is_empty())
+    }
+
+    /// Finds every key with one pipelined round of searches and reads the unlocked matches with a
+    /// single `GetSecrets`, so the batch costs three round-trips in all. A key whose item is
+    /// locked, or that matches several items, is read on its own so the keyring can prompt for it
+    /// or report the ambiguity as usual.
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let bus = SecretServiceBus::connect()?;
+        let searches: Vec<HashMap<&str, &str>> = keys
+            .iter()
+            .map(|key| {
+                HashMap::from([
+                    ("target", DEFAULT_TARGET),
+                    ("service", key.service.as_str()),
+                    ("username", key.account.as_str()),
+                ])
+            })
+            .collect();
+        let found = bus.search_each(&searches)?;
+        let single: Vec<Option<ItemPath>> = found
+            .iter()
+            .map(|(unlocked, locked)| match unlocked.as_slice() {
+                [item] if locked.is_empty() => Some(item.clone()),
+                _ => None,
+            })
+            .collect();
+        let mut secrets = bus.secrets(single.iter().flatten().cloned().collect())?;
+
+        keys.iter()
+            .zip(found)
+            .zip(single)
+            .map(|((key, (unlocked, locked)), item)| {
+                if unlocked.is_empty() && locked.is_empty() {
+                    return Ok(None);
+                }
+                match item.and_then(|item| secrets.remove(&item)) {
+                    Some(secret) => String::from_utf8(secret.to_vec()).map(Some).map_err(|_| {
+                        KeystoreError::Serialization(format!(
+                            "{}:{} is not valid UTF-8",
+                            key.service, key.account
+                        ))
+                    }),
+                    None => match self.get_password(&key.service, &key.account) {
+                        Ok(value) => Ok(Some(value)),
+                        Err(KeystoreError::KeyNotFound(_)) => Ok(None),
+                        Err(e) => Err(e),
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headless_signals() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        let no_files = |_: &str| false;
+
+        let desktop = env(&[("DBUS_SESSION_BUS_ADDRESS", "unix:path=/run/user/1000/bus")]);
+        assert_eq!(headless_reason_from(desktop, no_files), None);
+        let systemd = env(&[("XDG_RUNTIME_DIR", "/run/user/1000")]);
+        assert_eq!(
+            headless_reason_from(systemd, |path: &str| path == "/run/user/1000/bus"),
+            None
+        );
+        assert_eq!(
+            headless_reason_from(systemd, no_files).as_deref(),
+            Some("no DBus session bus")
+        );
+        assert_eq!(
+            headless_reason_from(desktop, |path: &str| path == "/.dockerenv").as_deref(),
+            Some("running in a container")
+        );
+        let ci = env(&[
+            ("CI", "true"),
+            ("DBUS_SESSION_BUS_ADDRESS", "unix:path=/tmp/bus"),
+        ]);
+        assert_eq!(
+            headless_reason_from(ci, no_files).as_deref(),
+            Some("running under CI")
+        );
+    }
+
+    fn create_test_entry(service: &str, account: &str, value: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: service.to_string(),
+            account: account.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn check_keyring_available() -> bool {
+        let keystore = LinuxKeystore::new().unwrap();
+        let test_entry = create_test_entry("test-availability", "test-availability", "test");
+
+        let set_result = keystore.set_password(&test_entry);
+        if set_result.is_err() {
+            return false;
+        }
+
+        let get_result = keystore.get_password("test-availability", "test-availability");
+
+        let _ = keystore.delete_password("test-availability", "test-availability");
+
+        get_result.is_ok()
+    }
+
+    #[test]
+    fn test_set_and_get_password() {
+        if !check_keyring_available() {
+            eprintln!("Skipping Linux keyring tests: Secret Service not available");
+            return;
+        }
+
+        let keystore = LinuxKeystore::new().unwrap();
+
+        let entry = create_test_entry(
+            "test-service-rust-unit",
+            "test-account-rust-unit",
+            "my-secret-password",
+        );
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("test-service-rust-unit", "test-account-rust-unit")
+            .unwrap();
+        assert_eq!(result, "my-secret-password");
+
+        keystore
+            .delete_password("test-service-rust-unit", "test-account-rust-unit")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_get_nonexistent_password() {
+        if !check_keyring_available() {
+            eprintln!("Skipping Linux keyring tests: Secret Service not available");
+            return;
+        }
+
+        let keystore = LinuxKeystore::new().unwrap();
+
+        let result = keystore.get_password(
+            "nonexistent-service-rust-unit",
+            "nonexistent-account-rust-unit",
+        );
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            KeystoreError::KeyNotFound(_) => (),
+            _ => panic!("Expected KeyNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_delete_nonexistent_password() {
+        if !check_keyring_available() {
+            eprintln!("Skipping Linux keyring tests: Secret Service not available");
+            return;
+        }
+
+        let keystore = LinuxKeystore::new().unwrap();
+
+        let result = keystore.delete_password(
+            "nonexistent-service-rust-unit",
+            "nonexistent-account-rust-unit",
+        );
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            KeystoreError::KeyNotFound(_) => (),
+            _ => panic!("Expected KeyNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_update_existing_password() {
+        if !check_keyring_available() {
+            eprintln!("Skipping Linux keyring tests: Secret Service not available");
+            return;
+        }
+
+        let keystore = LinuxKeystore::new().unwrap();
+
+        let entry1 = create_test_entry(
+            "update-service-rust-unit",
+            "update-account-rust-unit",
+            "old-password",
+        );
+        let entry2 = create_test_entry(
+            "update-service-rust-unit",
+            "update-account-rust-unit",
+            "new-password",
+        );
+
+        keystore.set_password(&entry1).unwrap();
+        keystore.set_password(&entry2).unwrap();
+
+        let result = keystore
+            .get_password("update-service-rust-unit", "update-account-rust-unit")
+            .unwrap();
+        assert_eq!(result, "new-password");
+
+        keystore
+            .delete_password("update-service-rust-unit", "update-account-rust-unit")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_empty_value() {
+        if !check_keyring_available() {
+            eprintln!("Skipping Linux keyring tests: Secret Service not available");
+            return;
+        }
+
+        let keystore = LinuxKeystore::new().unwrap();
+
+        let entry = create_test_entry("empty-service-rust-unit", "empty-account-rust-unit", "");
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("empty-service-rust-unit", "empty-account-rust-unit")
+            .unwrap();
+        assert_eq!(result, "");
+
+        keystore
+            .delete_password("empty-service-rust-unit", "empty-account-rust-unit")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_special_characters() {
+        if !check_keyring_available() {
+            eprintln!("Skipping Linux keyring tests: Secret Service not available");
+            return;
+        }
+
+        let keystore = LinuxKeystore::new().unwrap();
+
+        let special_value = "!@#$%^&*()_+-=[]{}|;':\",./<>?`~\n\t\r";
+        let entry = create_test_entry(
+            "special-service-rust-unit",
+            "special-account-rust-unit",
+            special_value,
+        );
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("special-service-rust-unit", "special-account-rust-unit")
+            .unwrap();
+        assert_eq!(result, special_value);
+
+        keystore
+            .delete_password("special-service-rust-unit", "special-account-rust-unit")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_long_value() {
+        if !check_keyring_available() {
+            eprintln!("Skipping Linux keyring tests: Secret Service not available");
+            return;
+        }
+
+        let keystore = LinuxKeystore::new().unwrap();
+
+        let long_value = "a".repeat(1000);
+        let entry = create_test_entry(
+            "long-service-rust-unit",
+            "long-account-rust-unit",
+            &long_value,
+        );
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("long-service-rust-unit", "long-account-rust-unit")
+            .unwrap();
+        assert_eq!(result, long_value);
+
+        keystore
+            .delete_password("long-service-rust-unit", "long-account-rust-unit")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_multiple_services() {
+        if !check_keyring_available() {
+            eprintln!("Skipping Linux keyring tests: Secret Service not available");
+            return;
+        }
+
+        let keystore = LinuxKeystore::new().unwrap();
+
+        let entries = vec![
+            create_test_entry("service1-rust-unit", "account1-rust-unit", "password1"),
+            create_test_entry("service1-rust-unit", "account2-rust-unit", "password2"),
+            create_test_entry("service2-rust-unit", "account1-rust-unit", "password3"),
+        ];
+
+        for entry in &entries {
+            keystore.set_password(entry).unwrap();
+        }
+
+        assert_eq!(
+            keystore
+                .get_password("service1-rust-unit", "account1-rust-unit")
+                .unwrap(),
+            "password1"
+        );
+        assert_eq!(
+            keystore
+                .get_password("service1-rust-unit", "account2-rust-unit")
+                .unwrap(),
+            "password2"
+        );
+        assert_eq!(
+            keystore
+                .get_password("service2-rust-unit", "account1-rust-unit")
+                .unwrap(),
+            "password3"
+        );
+
+        keystore
+            .delete_password("service1-rust-unit", "account1-rust-unit")
+            .unwrap();
+        keystore
+            .delete_password("service1-rust-unit", "account2-rust-unit")
+            .unwrap();
+        keystore
+            .delete_password("service2-rust-unit", "account1-rust-unit")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_utf8_values() {
+        if !check_keyring_available() {
+            eprintln!("Skipping Linux keyring tests: Secret Service not available");
+            return;
+        }
+
+        let keystore = LinuxKeystore::new().unwrap();
+
+        let utf8_value = "Hello 世界 🌍 Привет";
+        let entry = create_test_entry(
+            "utf8-service-rust-unit",
+            "utf8-account-rust-unit",
+            utf8_value,
+        );
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("utf8-service-rust-unit", "utf8-account-rust-unit")
+            .unwrap();
+        assert_eq!(result, utf8_value);
+
+        keystore
+            .delete_password("utf8-service-rust-unit", "utf8-account-rust-unit")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_batched_listing_and_reads() {
+        if !check_keyring_available() {
+            eprintln!("Skipping Linux keyring tests: Secret Service not available");
+            return;
+        }
+
+        let keystore = LinuxKeystore::new().unwrap();
+        for account in ["batch-a", "batch-b"] {
+            keystore
+                .set_password(&create_test_entry(
+                    "batch-service-rust-unit",
+                    account,
+                    account,
+                ))
+                .unwrap();
+        }
+
+        let key = |account: &str| KeystoreKey {
+            service: "batch-service-rust-unit".to_string(),
+            account: account.to_string(),
+        };
+        let listed = keystore.list_keys().unwrap();
+        assert!(listed.contains(&key("batch-a")) && listed.contains(&key("batch-b")));
+        assert_eq!(
+            keystore
+                .get_many(&[key("batch-b"), key("batch-missing"), key("batch-a")])
+                .unwrap(),
+            [
+                Some("batch-b".to_string()),
+                None,
+                Some("batch-a".to_string())
+            ]
+        );
+
+        assert_eq!(
+            keystore.delete_service("batch-service-rust-unit").unwrap(),
+            ["batch-a", "batch-b"]
+        );
+    }
+
+    #[test]
+    fn test_concurrent_operations() {
+        if !check_keyring_available() {
+            eprintln!("Skipping Linux keyring tests: Secret Service not available");
+            return;
+        }
+
+        let keystore = std::sync::Arc::new(LinuxKeystore::new().unwrap());
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let keystore = std::sync::Arc::clone(&keystore);
+                std::thread::spawn(move || {
+                    let account = format!("concurrent-account-rust-unit-{}", t);
+                    let entry =
+                        create_test_entry("concurrent-service-rust-unit", &account, "password");
+
+                    keystore.set_password(&entry).unwrap();
+                    assert_eq!(
+                        keystore
+                            .get_password("concurrent-service-rust-unit", &account)
+                            .unwrap(),
+                        "password"
+                    );
+                    keystore
+                        .delete_password("concurrent-service-rust-unit", &account)
+                        .unwrap();
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,613 @@
+use super::keychain::{self, Accessibility, ERR_SEC_MISSING_ENTITLEMENT};
+use super::{BackendInfo, KeystoreOperations};
+use crate::error::KeystoreError;
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use core_foundation_sys::base::OSStatus;
+use core_foundation_sys::runloop::{
+    kCFRunLoopDefaultMode, kCFRunLoopRunFinished, CFRunLoopRunInMode,
+};
+use security_framework::base::Error;
+use security_framework::passwords::{
+    delete_generic_password, get_generic_password, set_generic_password,
+};
+use security_framework_sys::base::{errSecItemNotFound, errSecSuccess};
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, OnceLock};
+use std::thread;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// `kSecAddEventMask`, `kSecDeleteEventMask` and `kSecUpdateEventMask`
+const KEYCHAIN_CHANGE_EVENTS: u32 = (1 << 3) | (1 << 4) | (1 << 5);
+
+type SecKeychainCallback = extern "C" fn(u32, *mut c_void, *mut c_void) -> OSStatus;
+
+extern "C" {
+    // Legacy keychain only, and not exported by security-framework-sys
+    fn SecKeychainAddCallback(
+        callback: SecKeychainCallback,
+        event_mask: u32,
+        context: *mut c_void,
+    ) -> OSStatus;
+}
+
+static CHANGE_NOTIFY: OnceLock<fn()> = OnceLock::new();
+
+extern "C" fn keychain_event(_event: u32, _info: *mut c_void, _context: *mut c_void) -> OSStatus {
+    if let Some(notify) = CHANGE_NOTIFY.get() {
+        notify();
+    }
+    errSecSuccess
+}
+
+/// Calls `notify` for every item added, changed or deleted in the legacy keychain, by this process
+/// or another. Keychain events are delivered on the run loop of the registering thread, so a thread
+/// registers and then runs its run loop for the rest of the process.
+pub(crate) fn watch_changes(notify: fn()) -> Result<(), KeystoreError> {
+    let _ = CHANGE_NOTIFY.set(notify);
+    let (registered, status) = mpsc::channel();
+    thread::Builder::new()
+        .name("keystore-watch".to_string())
+        .spawn(move || {
+            let code = unsafe {
+                SecKeychainAddCallback(keychain_event, KEYCHAIN_CHANGE_EVENTS, std::ptr::null_mut())
+            };
+            let _ = registered.send(code);
+            if code != errSecSuccess {
+                return;
+            }
+            loop {
+                let outcome = unsafe { CFRunLoopRunInMode(kCFRunLoopDefaultMode, 3600.0, 0) };
+                // No source attached yet; don't spin
+                if outcome == kCFRunLoopRunFinished {
+                    thread::sleep(Duration::from_secs(1));
+                }
+            }
+        })?;
+    match status.recv() {
+        Ok(code) if code == errSecSuccess => Ok(()),
+        Ok(code) => Err(KeystoreError::Platform(format!(
+            "Failed to watch the keychain: {}",
+            Error::from_code(code)
+        ))),
+        Err(_) => Err(KeystoreError::Platform(
+            "Keychain watcher exited before registering".to_string(),
+        )),
+    }
+}
+
+/// Which macOS keychain implementation items are written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeychainMode {
+    /// iOS-style keychain with protection classes; legacy items are migrated on access
+    #[default]
+    DataProtection,
+    /// File-based login keychain used by earlier versions
+    Legacy,
+}
+
+/// macOS Keychain backend.
+///
+/// In [`KeychainMode::DataProtection`] items live in the data-protection keychain. An item still in
+/// the legacy file-based keychain is moved over the first time it is read or overwritten. Processes
+/// without the entitlement the data-protection keychain requires fall back to the legacy keychain
+/// for the lifetime of the instance, which `backend_info()` reports.
+pub struct MacOsKeystore {
+    mode: KeychainMode,
+    accessibility: Accessibility,
+    missing_entitlement: AtomicBool,
+}
+
+impl MacOsKeystore {
+    pub fn new() -> Result<Self, KeystoreError> {
+        Ok(Self::with_mode(KeychainMode::default()))
+    }
+
+    pub fn with_mode(mode: KeychainMode) -> Self {
+        Self {
+            mode,
+            accessibility: Accessibility::default(),
+            missing_entitlement: AtomicBool::new(false),
+        }
+    }
+
+    /// Protection class for items written to the data-protection keychain
+    pub fn with_accessibility(mut self, accessibility: Accessibility) -> Self {
+        self.accessibility = accessibility;
+        self
+    }
+
+    /// The keychain operations currently go to
+    pub fn effective_mode(&self) -> KeychainMode {
+        if self.missing_entitlement.load(Ordering::Relaxed) {
+            KeychainMode::Legacy
+        } else {
+            self.mode
+        }
+    }
+
+    /// Runs `op` against the data-protection keychain, returning `None` if the process turns out
+    /// not to be entitled to use it
+    fn data_protection<T>(
+        &self,
+        op: impl FnOnce() -> Result<T, Error>,
+    ) -> Option<Result<T, Error>> {
+        if self.effective_mode() == KeychainMode::Legacy {
+            return None;
+        }
+
+        match op() {
+            Err(e) if e.code() == ERR_SEC_MISSING_ENTITLEMENT => {
+                self.missing_entitlement.store(true, Ordering::Relaxed);
+                None
+            }
+            result => Some(result),
+        }
+    }
+
+    /// Moves a legacy item into the data-protection keychain. Failures leave the legacy item in
+    /// place so the value stays readable.
+    fn migrate_legacy_item(&self, service: &str, account: &str, value: &[u8]) {
+        if keychain::set_item(service, account, value, self.accessibility, None, None).is_ok() {
+            let _ = delete_generic_password(service, account);
+        }
+    }
+
+    /// Keys in the data-protection keychain and in the legacy one, sorted, without values
+    fn list(&self, service: Option<&str>) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let list_error =
+            |e: Error| keychain::keychain_error("list passwords", service.unwrap_or("*"), "*", e);
+        let mut items = match self.data_protection(|| keychain::list_items(service, true)) {
+            Some(items) => items.map_err(list_error)?,
+            None => Vec::new(),
+        };
+        // Items not yet migrated, or all of them without the entitlement
+        items.extend(keychain::list_items(service, false).map_err(list_error)?);
+        let mut keys: Vec<KeystoreKey> = items
+            .into_iter()
+            .map(|(service, account)| KeystoreKey { service, account })
+            .collect();
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+}
+
+fn not_found(service: &str, account: &str) -> KeystoreError {
+    KeystoreError::KeyNotFound(format!("{}:{}", service, account))
+}
+
+impl KeystoreOperations for MacOsKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        let value = entry.value.as_bytes();
+        let set_error =
+            |e: Error| keychain::keychain_error("set password", &entry.service, &entry.account, e);
+        let written = self.data_protection(|| {
+            keychain::set_item(
+                &entry.service,
+                &entry.account,
+                value,
+                self.accessibility,
+                entry.label.as_deref(),
+                entry.comment.as_deref(),
+            )
+        });
+
+        match written {
+            Some(Ok(())) => {
+                // Drop any legacy copy so it cannot shadow the new value after a mode change
+                let _ = delete_generic_password(&entry.service, &entry.account);
+                Ok(())
+            }
+            Some(Err(e)) => Err(set_error(e)),
+            None => set_generic_password(&entry.service, &entry.account, value).map_err(set_error),
+        }
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        let bytes = match self.data_protection(|| keychain::get_item(service, account)) {
+            Some(Ok(bytes)) => bytes,
+            Some(Err(e)) if e.code() != errSecItemNotFound => {
+                return Err(keychain::keychain_error(
+                    "get password",
+                    service,
+                    account,
+                    e,
+                ))
+            }
+            migrate_or_legacy => match get_generic_password(service, account) {
+                Ok(bytes) => {
+                    if migrate_or_legacy.is_some() {
+                        self.migrate_legacy_item(service, account, &bytes);
+                    }
+                    bytes
+                }
+                Err(e) if e.code() == errSecItemNotFound => {
+                    return Err(not_found(service, account))
+                }
+                Err(e) => {
+                    return Err(keychain::keychain_error(
+                        "get password",
+                        service,
+                        account,
+                        e,
+                    ))
+                }
+            },
+        };
+
+        String::from_utf8(bytes).map_err(|e| KeystoreError::Serialization(e.to_string()))
+    }
+
+    /// The item's label, comment, creation and modification dates. Items in the legacy keychain
+    /// report none.
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        match self.data_protection(|| keychain::get_attributes(service, account)) {
+            Some(Ok(metadata)) => Ok(metadata),
+            Some(Err(e)) if e.code() != errSecItemNotFound => Err(keychain::keychain_error(
+                "get metadata",
+                service,
+                account,
+                e,
+            )),
+            _ => {
+                // Confirms the legacy item exists, and migrates it
+                drop(Zeroizing::new(self.get_password(service, account)?));
+                Ok(EntryMetadata::default())
+            }
+        }
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let deleted = match self.data_protection(|| keychain::delete_item(service, account)) {
+            Some(Ok(())) => true,
+            Some(Err(e)) if e.code() != errSecItemNotFound => {
+                return Err(keychain::keychain_error(
+                    "delete password",
+                    service,
+                    account,
+                    e,
+                ))
+            }
+            _ => false,
+        };
+
+        // A legacy copy may exist alongside (or instead of) the data-protection item
+        match delete_generic_password(service, account) {
+            Ok(()) => Ok(()),
+            Err(e) if e.code() == errSecItemNotFound => {
+                if deleted {
+                    Ok(())
+                } else {
+                    Err(not_found(service, account))
+                }
+            }
+            Err(e) => Err(keychain::keychain_error(
+                "delete password",
+                service,
+                account,
+                e,
+            )),
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    /// Every generic password the process can see, including other applications' items
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.list(None)
+    }
+
+    /// Queries the keychains for `service` alone
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        Ok(self
+            .list(Some(service))?
+            .into_iter()
+            .map(|key| key.account)
+            .collect())
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        let mode = match self.effective_mode() {
+            KeychainMode::DataProtection => "data-protection",
+            KeychainMode::Legacy => "legacy",
+        };
+        let info = BackendInfo::new("macos-keychain").with_detail("keychain", mode);
+        if self.missing_entitlement.load(Ordering::Relaxed) {
+            info.with_detail(
+                "fallback_reason",
+                "missing keychain-access-groups entitlement",
+            )
+        } else {
+            info
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_entry(service: &str, account: &str, value: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: service.to_string(),
+            account: account.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn generate_unique_id() -> String {
+        format!(
+            "{}-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
+            uuid::Uuid::new_v4().simple()
+        )
+    }
+
+    struct TestGuard<'a> {
+        service: String,
+        account: String,
+        keystore: &'a MacOsKeystore,
+    }
+
+    impl<'a> TestGuard<'a> {
+        fn new(service: String, account: String, keystore: &'a MacOsKeystore) -> Self {
+            Self {
+                service,
+                account,
+                keystore,
+            }
+        }
+    }
+
+    impl<'a> Drop for TestGuard<'a> {
+        fn drop(&mut self) {
+            let _ = self.keystore.delete_password(&self.service, &self.account);
+        }
+    }
+
+    #[test]
+    fn test_set_and_get_password() {
+        let keystore = MacOsKeystore::new().unwrap();
+        let id = generate_unique_id();
+        let service = format!("test-service-{}", id);
+        let account = format!("test-account-{}", id);
+
+        let _guard = TestGuard::new(service.clone(), account.clone(), &keystore);
+        let entry = create_test_entry(&service, &account, "my-secret-password");
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore.get_password(&service, &account).unwrap();
+        assert_eq!(result, "my-secret-password");
+    }
+
+    #[test]
+    fn test_get_nonexistent_password() {
+        let keystore = MacOsKeystore::new().unwrap();
+        let id = generate_unique_id();
+        let service = format!("nonexistent-service-{}", id);
+        let account = format!("nonexistent-account-{}", id);
+
+        let result = keystore.get_password(&service, &account);
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            KeystoreError::KeyNotFound(_) => (),
+            _ => panic!("Expected KeyNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_delete_nonexistent_password() {
+        let keystore = MacOsKeystore::new().unwrap();
+        let id = generate_unique_id();
+        let service = format!("nonexistent-service-{}", id);
+        let account = format!("nonexistent-account-{}", id);
+
+        let result = keystore.delete_password(&service, &account);
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            KeystoreError::KeyNotFound(_) => (),
+            _ => panic!("Expected KeyNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_update_existing_password() {
+        let keystore = MacOsKeystore::new().unwrap();
+        let id = generate_unique_id();
+        let service = format!("update-service-{}", id);
+        let account = format!("update-account-{}", id);
+
+        let _guard = TestGuard::new(service.clone(), account.clone(), &keystore);
+        let entry1 = create_test_entry(&service, &account, "old-password");
+        let entry2 = create_test_entry(&service, &account, "new-password");
+
+        keystore.set_password(&entry1).unwrap();
+        keystore.set_password(&entry2).unwrap();
+
+        let result = keystore.get_password(&service, &account).unwrap();
+        assert_eq!(result, "new-password");
+    }
+
+    #[test]
+    fn test_empty_value() {
+        let keystore = MacOsKeystore::new().unwrap();
+        let id = generate_unique_id();
+        let service = format!("empty-service-{}", id);
+        let account = format!("empty-account-{}", id);
+
+        let _guard = TestGuard::new(service.clone(), account.clone(), &keystore);
+        let entry = create_test_entry(&service, &account, "");
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore.get_password(&service, &account).unwrap();
+        assert_eq!(result, "");
+    }
+
+    #[test]
+    fn test_special_characters() {
+        let keystore = MacOsKeystore::new().unwrap();
+        let id = generate_unique_id();
+        let service = format!("special-service-{}", id);
+        let account = format!("special-account-{}", id);
+
+        let _guard = TestGuard::new(service.clone(), account.clone(), &keystore);
+        let special_value = "!@#$%^&*()_+-=[]{}|;':\",./<>?`~\n\t\r";
+        let entry = create_test_entry(&service, &account, special_value);
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore.get_password(&service, &account).unwrap();
+        assert_eq!(result, special_value);
+    }
+
+    #[test]
+    fn test_long_value() {
+        let keystore = MacOsKeystore::new().unwrap();
+        let id = generate_unique_id();
+        let service = format!("long-service-{}", id);
+        let account = format!("long-account-{}", id);
+
+        let _guard = TestGuard::new(service.clone(), account.clone(), &keystore);
+        let long_value = "a".repeat(1000);
+        let entry = create_test_entry(&service, &account, &long_value);
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore.get_password(&service, &account).unwrap();
+        assert_eq!(result, long_value);
+    }
+
+    #[test]
+    fn test_multiple_services() {
+        let keystore = MacOsKeystore::new().unwrap();
+        let id = generate_unique_id();
+
+        let entries = vec![
+            create_test_entry(
+                &format!("service1-{}", id),
+                &format!("account1-{}", id),
+                "password1",
+            ),
+            create_test_entry(
+                &format!("service1-{}", id),
+                &format!("account2-{}", id),
+                "password2",
+            ),
+            create_test_entry(
+                &format!("service2-{}", id),
+                &format!("account1-{}", id),
+                "password3",
+            ),
+        ];
+
+        let guards: Vec<TestGuard> = entries
+            .iter()
+            .map(|e| TestGuard::new(e.service.clone(), e.account.clone(), &keystore))
+            .collect();
+
+        for entry in &entries {
+            keystore.set_password(entry).unwrap();
+        }
+
+        assert_eq!(
+            keystore
+                .get_password(&format!("service1-{}", id), &format!("account1-{}", id))
+                .unwrap(),
+            "password1"
+        );
+        assert_eq!(
+            keystore
+                .get_password(&format!("service1-{}", id), &format!("account2-{}", id))
+                .unwrap(),
+            "password2"
+        );
+        assert_eq!(
+            keystore
+                .get_password(&format!("service2-{}", id), &format!("account1-{}", id))
+                .unwrap(),
+            "password3"
+        );
+
+        drop(guards);
+    }
+
+    #[test]
+    fn test_utf8_values() {
+        let keystore = MacOsKeystore::new().unwrap();
+        let id = generate_unique_id();
+        let service = format!("utf8-service-{}", id);
+        let account = format!("utf8-account-{}", id);
+
+        let _guard = TestGuard::new(service.clone(), account.clone(), &keystore);
+        let utf8_value = "Hello 世界 🌍 Привет";
+        let entry = create_test_entry(&service, &account, utf8_value);
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore.get_password(&service, &account).unwrap();
+        assert_eq!(result, utf8_value);
+    }
+
+    #[test]
+    fn test_legacy_item_is_migrated_on_read() {
+        let id = generate_unique_id();
+        let service = format!("migrate-service-{}", id);
+        let account = format!("migrate-account-{}", id);
+
+        let legacy = MacOsKeystore::with_mode(KeychainMode::Legacy);
+        let keystore = MacOsKeystore::new().unwrap();
+        let _guard = TestGuard::new(service.clone(), account.clone(), &keystore);
+
+        legacy
+            .set_password(&create_test_entry(&service, &account, "legacy-token"))
+            .unwrap();
+
+        assert_eq!(
+            keystore.get_password(&service, &account).unwrap(),
+            "legacy-token"
+        );
+        if keystore.effective_mode() == KeychainMode::DataProtection {
+            // The legacy copy is gone once the item has moved
+            assert!(get_generic_password(&service, &account).is_err());
+        }
+        assert_eq!(
+            keystore.get_password(&service, &account).unwrap(),
+            "legacy-token"
+        );
+    }
+
+    #[test]
+    fn test_delete_removes_both_copies() {
+        let id = generate_unique_id();
+        let service = format!("both-service-{}", id);
+        let account = format!("both-account-{}", id);
+
+        let legacy = MacOsKeystore::with_mode(KeychainMode::Legacy);
+        let keystore = MacOsKeystore::new().unwrap();
+        let _guard = TestGuard::new(service.clone(), account.clone(), &keystore);
+
+        keystore
+            .set_password(&create_test_entry(&service, &account, "new"))
+            .unwrap();
+        legacy
+            .set_password(&create_test_entry(&service, &account, "old"))
+            .unwrap();
+
+        keystore.delete_password(&service, &account).unwrap();
+        match keystore.get_password(&service, &account) {
+            Err(KeystoreError::KeyNotFound(_)) => (),
+            other => panic!("Expected KeyNotFound error, got {:?}", other.map(|_| ())),
+        }
+    }
+}
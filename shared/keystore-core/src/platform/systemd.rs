@@ -0,0 +1,325 @@
+//! Secrets provisioned by systemd.
+//!
+//! A service started with `LoadCredential=` or `SetCredentialEncrypted=` finds each credential
+//! as a file in `$CREDENTIALS_DIRECTORY`, already decrypted. [`SystemdCredentials`] sits ahead of
+//! the real backend and answers reads from those files, so ops can inject tokens into a daemon
+//! without a keyring. The credential `twitch@bot` is the entry with service `twitch` and account
+//! `bot`; names split at the first `@`. Credentials are read-only: writing or deleting one is
+//! refused, and everything else goes to the backend underneath.
+
+use super::{BackendInfo, KeystoreOperations};
+use crate::error::KeystoreError;
+use crate::resolution::{record_source, ReadSource};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Set by systemd for services that have credentials
+pub const CREDENTIALS_DIRECTORY_ENV: &str = "CREDENTIALS_DIRECTORY";
+
+pub struct SystemdCredentials {
+    directory: PathBuf,
+    inner: Box<dyn KeystoreOperations>,
+}
+
+impl SystemdCredentials {
+    pub fn new(directory: PathBuf, inner: Box<dyn KeystoreOperations>) -> Self {
+        Self { directory, inner }
+    }
+
+    /// Layers the credentials of the current service over `inner`, or returns `inner` unchanged
+    /// outside a service with credentials
+    pub fn layer(inner: Box<dyn KeystoreOperations>) -> Box<dyn KeystoreOperations> {
+        match std::env::var_os(CREDENTIALS_DIRECTORY_ENV) {
+            Some(directory) if !directory.is_empty() => {
+                Box::new(Self::new(PathBuf::from(directory), inner))
+            }
+            _ => inner,
+        }
+    }
+
+    fn path(&self, service: &str, account: &str) -> Option<PathBuf> {
+        let name = format!("{}@{}", service, account);
+        // Names that can't be a single file in the directory are never credentials
+        if service.is_empty() || service.contains('@') || name.contains(['/', '\0']) {
+            return None;
+        }
+        Some(self.directory.join(name))
+    }
+
+    /// The credential's contents, without the trailing newline files written by `echo` carry
+    fn read(&self, service: &str, account: &str) -> Result<Option<String>, KeystoreError> {
+        let Some(path) = self.path(service, account) else {
+            return Ok(None);
+        };
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut value = String::from_utf8(bytes).map_err(|_| {
+            KeystoreError::Serialization(format!(
+                "Credential {} is not valid UTF-8",
+                path.display()
+            ))
+        })?;
+        if value.ends_with('\n') {
+            value.pop();
+        }
+        Ok(Some(value))
+    }
+
+    fn is_credential(&self, service: &str, account: &str) -> bool {
+        self.path(service, account)
+            .is_some_and(|path| path.is_file())
+    }
+
+    fn refuse_if_credential(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        if self.is_credential(service, account) {
+            return Err(KeystoreError::AccessDenied(format!(
+                "{}:{} is a systemd credential and is read-only",
+                service, account
+            )));
+        }
+        Ok(())
+    }
+
+    /// Every credential in the directory, sorted
+    pub fn credentials(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut keys = Vec::new();
+        for dir_entry in fs::read_dir(&self.directory)? {
+            let dir_entry = dir_entry?;
+            if !dir_entry.file_type()?.is_file() {
+                continue;
+            }
+            let name = dir_entry.file_name();
+            let Some((service, account)) = name.to_str().and_then(|name| name.split_once('@'))
+            else {
+                continue;
+            };
+            if !service.is_empty() {
+                keys.push(KeystoreKey {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                });
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+impl KeystoreOperations for SystemdCredentials {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.refuse_if_credential(&entry.service, &entry.account)?;
+        self.inner.set_password(entry)
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        match self.read(service, account)? {
+            Some(value) => {
+                record_source(ReadSource::SystemdCredential);
+                Ok(value)
+            }
+            None => self.inner.get_password(service, account),
+        }
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.refuse_if_credential(service, account)?;
+        self.inner.delete_password(service, account)
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner
+            .backend_info()
+            .with_detail("systemd_credentials", self.directory.display())
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    /// Credentials and the backend's entries; backends that can't enumerate contribute nothing
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut keys = self.credentials()?;
+        match self.inner.list_keys() {
+            Ok(stored) => keys.extend(stored),
+            Err(KeystoreError::PlatformNotSupported) => {}
+            Err(e) => return Err(e),
+        }
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    /// Credentials answer their keys; the rest are read from the backend in one batch
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        let mut values = keys
+            .iter()
+            .map(|key| self.read(&key.service, &key.account))
+            .collect::<Result<Vec<_>, _>>()?;
+        let misses: Vec<usize> = (0..keys.len()).filter(|&i| values[i].is_none()).collect();
+        if misses.is_empty() {
+            return Ok(values);
+        }
+        let missed: Vec<KeystoreKey> = misses.iter().map(|&index| keys[index].clone()).collect();
+        for (index, value) in misses.into_iter().zip(self.inner.get_many(&missed)?) {
+            values[index] = value;
+        }
+        Ok(values)
+    }
+
+    /// Refused, writing nothing, if any entry is a credential
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        for entry in entries {
+            self.refuse_if_credential(&entry.service, &entry.account)?;
+        }
+        self.inner.set_many(entries)
+    }
+
+    /// Refused, deleting nothing, if any key is a credential
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        for key in keys {
+            self.refuse_if_credential(&key.service, &key.account)?;
+        }
+        self.inner.delete_many(keys)
+    }
+
+    /// Clears the backend's entries under `service`; credentials stay
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.delete_service(service)
+    }
+
+    /// Clears the backend; credentials stay
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.wipe_all()
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.refuse_if_credential(&a.service, &a.account)?;
+        self.refuse_if_credential(&b.service, &b.account)?;
+        self.inner.swap(a, b)
+    }
+
+    /// Credentials carry no metadata
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        if self.is_credential(service, account) {
+            return Ok(EntryMetadata::default());
+        }
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        if self.is_credential(service, account) {
+            return Ok(true);
+        }
+        self.inner.has_password(service, account)
+    }
+
+    /// Credentials and the backend's accounts under `service`
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let mut accounts: Vec<String> = self
+            .credentials()?
+            .into_iter()
+            .filter(|key| key.service == service)
+            .map(|key| key.account)
+            .collect();
+        match self.inner.list_credentials(service) {
+            Ok(stored) => accounts.extend(stored),
+            Err(KeystoreError::PlatformNotSupported) => {}
+            Err(e) => return Err(e),
+        }
+        accounts.sort();
+        accounts.dedup();
+        Ok(accounts)
+    }
+
+    /// A credential is returned as it is; otherwise the backend checks and creates under its own
+    /// lock
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        match self.read(service, account)? {
+            Some(value) => {
+                record_source(ReadSource::SystemdCredential);
+                Ok(value)
+            }
+            None => self.inner.get_or_create(service, account, length),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_credentials_shadow_the_backend_and_are_read_only() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("twitch@bot"), "injected\n").unwrap();
+        fs::write(dir.path().join("not-a-credential"), "x").unwrap();
+        let keystore = SystemdCredentials::new(
+            dir.path().to_path_buf(),
+            Box::new(MemoryKeystore::default()),
+        );
+        let entry = |account: &str| KeystoreEntry {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+            value: "stored".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "injected");
+        assert!(matches!(
+            keystore.set_password(&entry("bot")),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        assert!(matches!(
+            keystore.delete_password("twitch", "bot"),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        // Names that would leave the directory fall through to the backend
+        assert!(matches!(
+            keystore.get_password("..", "/etc/passwd"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+
+        keystore.set_password(&entry("streamer")).unwrap();
+        assert_eq!(
+            keystore.get_password("twitch", "streamer").unwrap(),
+            "stored"
+        );
+        let accounts: Vec<String> = keystore
+            .list_keys()
+            .unwrap()
+            .into_iter()
+            .map(|key| key.account)
+            .collect();
+        assert_eq!(accounts, vec!["bot", "streamer"]);
+        assert_eq!(
+            keystore.list_credentials("twitch").unwrap(),
+            vec!["bot", "streamer"]
+        );
+        assert_eq!(
+            keystore.get_or_create("twitch", "bot", 32).unwrap(),
+            "injected"
+        );
+        let created = keystore.get_or_create("twitch", "webhook", 32).unwrap();
+        assert_eq!(keystore.get_password("twitch", "webhook").unwrap(), created);
+        keystore.delete_password("twitch", "webhook").unwrap();
+        assert_eq!(keystore.delete_service("twitch").unwrap(), vec!["streamer"]);
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "injected");
+    }
+}
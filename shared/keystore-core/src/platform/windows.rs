@@ -0,0 +1,1179 @@
+use super::{BackendInfo, KeystoreOperations};
+use crate::error::KeystoreError;
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use windows::core::{HSTRING, PCWSTR, PWSTR};
+use windows::Win32::Foundation::{
+    CloseHandle, ERROR_ACCESS_DENIED, ERROR_BAD_USERNAME, ERROR_BUSY, ERROR_CANCELLED,
+    ERROR_INSUFFICIENT_BUFFER, ERROR_INVALID_DATA, ERROR_INVALID_FLAGS, ERROR_INVALID_PARAMETER,
+    ERROR_NOT_FOUND, ERROR_NOT_SUPPORTED, ERROR_NO_SUCH_LOGON_SESSION, ERROR_SHARING_VIOLATION,
+    ERROR_TIMEOUT, FALSE, HANDLE, WAIT_OBJECT_0, WIN32_ERROR,
+};
+use windows::Win32::NetworkManagement::NetManagement::{
+    NetApiBufferFree, NetGetJoinInformation, NetSetupDomainName, NETSETUP_JOIN_STATUS,
+};
+use windows::Win32::Security::Credentials::*;
+use windows::Win32::Security::{
+    GetTokenInformation, IsWellKnownSid, TokenElevation, TokenUser, WinLocalSystemSid,
+    TOKEN_ELEVATION, TOKEN_INFORMATION_CLASS, TOKEN_QUERY, TOKEN_USER,
+};
+use windows::Win32::Storage::FileSystem::{
+    FindFirstChangeNotificationW, FindNextChangeNotification, FILE_NOTIFY_CHANGE_FILE_NAME,
+    FILE_NOTIFY_CHANGE_LAST_WRITE,
+};
+use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+use windows::Win32::System::Threading::{
+    GetCurrentProcess, GetCurrentProcessId, OpenProcessToken, WaitForMultipleObjects, INFINITE,
+};
+use windows::Win32::UI::Shell::GetProfileType;
+use zeroize::Zeroizing;
+
+/// Credential attribute marking a blob that was passed through `CredProtectW` before writing
+const PROTECTED_ATTRIBUTE: &str = "streaming-enhancement:cred-protected";
+
+/// Largest plaintext that still fits `CRED_MAX_CREDENTIAL_BLOB_SIZE` after `CredProtectW`
+const PROTECTED_VALUE_SIZE: usize = 256;
+
+/// `CRED_MAX_STRING_LENGTH`, the most UTF-16 units the `Comment` field holds
+const MAX_COMMENT_LENGTH: usize = 256;
+
+/// 100ns intervals from 1601 to the Unix epoch, in milliseconds
+const FILETIME_UNIX_OFFSET_MS: u64 = 11_644_473_600_000;
+
+/// `PT_ROAMING` and `PT_ROAMING_PREEXISTING` from userenv.h
+const PT_ROAMING: u32 = 0x2;
+const PT_ROAMING_PREEXISTING: u32 = 0x8;
+
+/// Where Credential Manager keeps a credential
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Persistence {
+    /// Stays on this machine for the current user
+    LocalMachine,
+    /// Additionally roams with the user's profile to every domain machine they log into
+    Enterprise,
+}
+
+impl Persistence {
+    fn flag(self) -> CRED_PERSIST {
+        match self {
+            Persistence::LocalMachine => CRED_PERSIST_LOCAL_MACHINE,
+            Persistence::Enterprise => CRED_PERSIST_ENTERPRISE,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Persistence::LocalMachine => "local-machine",
+            Persistence::Enterprise => "enterprise",
+        }
+    }
+}
+
+/// Signals that an `ENTERPRISE` credential would leave this machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RoamingEnvironment {
+    pub domain_joined: bool,
+    pub roaming_profile: bool,
+}
+
+impl RoamingEnvironment {
+    pub fn detect() -> Self {
+        Self {
+            domain_joined: is_domain_joined(),
+            roaming_profile: has_roaming_profile(),
+        }
+    }
+
+    /// Local-machine persistence whenever an enterprise credential could roam, so a stream key
+    /// saved on a studio PC is not copied to every machine the user logs into
+    pub fn default_persistence(&self) -> Persistence {
+        if self.domain_joined || self.roaming_profile {
+            Persistence::LocalMachine
+        } else {
+            Persistence::Enterprise
+        }
+    }
+}
+
+fn is_domain_joined() -> bool {
+    unsafe {
+        let mut name = PWSTR::null();
+        let mut status = NETSETUP_JOIN_STATUS::default();
+        let result = NetGetJoinInformation(PCWSTR::null(), &mut name, &mut status);
+        if !name.is_null() {
+            NetApiBufferFree(Some(name.as_ptr() as *const _));
+        }
+        result == 0 && status == NetSetupDomainName
+    }
+}
+
+fn has_roaming_profile() -> bool {
+    let mut flags = 0u32;
+    unsafe { GetProfileType(&mut flags) }.is_ok()
+        && flags & (PT_ROAMING | PT_ROAMING_PREEXISTING) != 0
+}
+
+/// Where this process runs. A service runs in session 0, usually as LocalSystem, where
+/// Credential Manager holds the service account's credentials rather than the streamer's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExecutionContext {
+    /// Remote Desktop session of the process, if it could be determined
+    pub session_id: Option<u32>,
+    pub local_system: bool,
+}
+
+impl ExecutionContext {
+    pub fn detect() -> Self {
+        Self {
+            session_id: session_id(),
+            local_system: is_local_system(),
+        }
+    }
+
+    /// Running as a Windows service rather than in a user's desktop session
+    pub fn is_service(&self) -> bool {
+        self.session_id == Some(0) || self.local_system
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        if self.is_service() {
+            "service"
+        } else {
+            "interactive"
+        }
+    }
+
+    /// Services always keep credentials local: LocalSystem has no profile to roam with, and a
+    /// service account's enterprise credentials would follow it to other servers
+    pub fn default_persistence(&self, environment: &RoamingEnvironment) -> Persistence {
+        if self.is_service() {
+            Persistence::LocalMachine
+        } else {
+            environment.default_persistence()
+        }
+    }
+}
+
+fn session_id() -> Option<u32> {
+    let mut session = 0u32;
+    unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session) }
+        .ok()
+        .map(|()| session)
+}
+
+/// Reads a token information class of the current process into a buffer aligned for the
+/// structure it holds
+fn process_token_information(class: TOKEN_INFORMATION_CLASS) -> Option<Vec<u64>> {
+    unsafe {
+        let mut token = HANDLE::default();
+        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).ok()?;
+        let mut size = 0u32;
+        let _ = GetTokenInformation(token, class, None, 0, &mut size);
+        let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+        let result = if size == 0 {
+            None
+        } else {
+            GetTokenInformation(
+                token,
+                class,
+                Some(buffer.as_mut_ptr().cast()),
+                size,
+                &mut size,
+            )
+            .ok()
+        };
+        let _ = CloseHandle(token);
+        result.map(|()| buffer)
+    }
+}
+
+fn is_local_system() -> bool {
+    process_token_information(TokenUser).is_some_and(|buffer| unsafe {
+        let user = &*(buffer.as_ptr() as *const TOKEN_USER);
+        IsWellKnownSid(user.User.Sid, WinLocalSystemSid).as_bool()
+    })
+}
+
+/// Whether the process runs with an elevated (administrator) token
+pub fn is_elevated() -> bool {
+    process_token_information(TokenElevation).is_some_and(|buffer| unsafe {
+        (*(buffer.as_ptr() as *const TOKEN_ELEVATION)).TokenIsElevated != 0
+    })
+}
+
+/// Maps a Credential Manager failure by its Win32 code, explaining the one services hit when their
+/// account has no loaded profile
+fn credential_error(action: &str, e: windows::core::Error) -> KeystoreError {
+    let is = |code: WIN32_ERROR| e.code() == code.to_hresult();
+    let message = format!("Failed to {} credential: {}", action, e);
+    if is(ERROR_NO_SUCH_LOGON_SESSION) {
+        KeystoreError::Platform(format!(
+            "Failed to {} credential: this account has no logon session. Run the service as \
+             LocalSystem or as a user account whose profile is loaded",
+            action
+        ))
+    } else if is(ERROR_ACCESS_DENIED) || is(ERROR_CANCELLED) {
+        KeystoreError::AccessDenied(message)
+    } else if is(ERROR_TIMEOUT) {
+        KeystoreError::Timeout(message)
+    } else if is(ERROR_BUSY) || is(ERROR_SHARING_VIOLATION) {
+        KeystoreError::Transient(message)
+    } else if is(ERROR_INVALID_DATA) {
+        KeystoreError::Corrupted(message)
+    } else if is(ERROR_INVALID_PARAMETER) || is(ERROR_BAD_USERNAME) || is(ERROR_INVALID_FLAGS) {
+        KeystoreError::InvalidInput(message)
+    } else if is(ERROR_NOT_SUPPORTED) {
+        KeystoreError::PlatformNotSupported
+    } else {
+        KeystoreError::Platform(message)
+    }
+}
+
+pub struct WindowsKeystore {
+    cred_protect: bool,
+    context: ExecutionContext,
+    environment: RoamingEnvironment,
+    persistence: Persistence,
+    persistence_configured: bool,
+}
+
+/// Attributes of a stored credential that can be read without decoding its value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CredentialMetadata {
+    /// The blob is additionally encrypted with `CredProtectW` for the user's logon session
+    pub protected: bool,
+    /// The `Comment` field, which holds the entry's [`EntryMetadata`] as JSON
+    pub comment: Option<String>,
+    /// `LastWritten`, in milliseconds since the Unix epoch
+    pub last_written: u64,
+}
+
+impl CredentialMetadata {
+    /// `updated_at` is when Credential Manager last wrote the credential; label, comment and
+    /// `created_at` come from the comment, and are missing from credentials written before it was
+    /// kept
+    fn entry_metadata(&self) -> EntryMetadata {
+        let mut metadata: EntryMetadata = self
+            .comment
+            .as_deref()
+            .and_then(|comment| serde_json::from_str(comment).ok())
+            .unwrap_or_default();
+        metadata.updated_at = Some(self.last_written);
+        metadata
+    }
+}
+
+impl WindowsKeystore {
+    /// Detects service execution, domain membership and roaming profiles to choose the
+    /// persistence level
+    pub fn new() -> Result<Self, KeystoreError> {
+        let context = ExecutionContext::detect();
+        let environment = RoamingEnvironment::detect();
+        Ok(Self {
+            cred_protect: false,
+            context,
+            environment,
+            persistence: context.default_persistence(&environment),
+            persistence_configured: false,
+        })
+    }
+
+    /// Overrides the detected persistence level for credentials written from now on
+    pub fn with_persistence(mut self, persistence: Persistence) -> Self {
+        self.persistence = persistence;
+        self.persistence_configured = true;
+        self
+    }
+
+    pub fn persistence(&self) -> Persistence {
+        self.persistence
+    }
+
+    /// Runs newly written blobs through `CredProtectW` so they can only be decrypted by the same
+    /// user. Existing credentials are read either way; protection is recorded per credential.
+    pub fn with_cred_protect(mut self, enabled: bool) -> Self {
+        self.cred_protect = enabled;
+        self
+    }
+
+    /// Builds a unique credential name by escaping colons in service/account names.
+    /// This prevents collision when service or account contains colons.
+    /// Uses backslash as escape character: ':' -> '\:', '\' -> '\\'
+    fn build_credential_name(service: &str, account: &str) -> String {
+        fn escape(s: &str) -> String {
+            s.replace('\\', "\\\\").replace(':', "\\:")
+        }
+        format!("{}:{}", escape(service), escape(account))
+    }
+
+    /// Reads a credential's raw blob and whether it carries the protected attribute
+    fn read_credential(
+        service: &str,
+        account: &str,
+    ) -> Result<(Vec<u8>, CredentialMetadata), KeystoreError> {
+        read_named(&Self::build_credential_name(service, account))
+    }
+
+    /// Generic credentials named like ours, all of them or those whose name starts with `prefix`,
+    /// sorted. Values are not decoded.
+    fn enumerate(prefix: Option<&str>) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        // Only a trailing asterisk is a wildcard; other names are filtered after parsing
+        let filter = prefix
+            .filter(|prefix| !prefix.contains('*'))
+            .map(|prefix| format!("{}*", prefix));
+        let mut keys: Vec<KeystoreKey> = generic_credentials(filter.as_deref())?
+            .into_iter()
+            // Ours always carry the account as the user name; other applications' rarely do
+            .filter_map(|(name, user)| {
+                parse_credential_name(&name).filter(|key| key.account == user)
+            })
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Returns the metadata of a stored credential without decrypting its value
+    pub fn metadata(
+        &self,
+        service: &str,
+        account: &str,
+    ) -> Result<CredentialMetadata, KeystoreError> {
+        Self::read_credential(service, account).map(|(_, metadata)| metadata)
+    }
+}
+
+/// Reads the raw blob of the generic credential named `credential_name` and whether it carries the
+/// protected attribute
+fn read_named(credential_name: &str) -> Result<(Vec<u8>, CredentialMetadata), KeystoreError> {
+    let credential_name_hstring = HSTRING::from(credential_name);
+
+    unsafe {
+        let mut credential_ptr: *mut CREDENTIALW = std::ptr::null_mut();
+
+        CredReadW(
+            PCWSTR(credential_name_hstring.as_ptr()),
+            CRED_TYPE_GENERIC,
+            0,
+            &mut credential_ptr as *mut *mut CREDENTIALW,
+        )
+        .map_err(|e| {
+            if e.code() == ERROR_NOT_FOUND.to_hresult() {
+                KeystoreError::KeyNotFound(credential_name.to_string())
+            } else {
+                credential_error("read", e)
+            }
+        })?;
+
+        if credential_ptr.is_null() {
+            return Err(KeystoreError::KeyNotFound(credential_name.to_string()));
+        }
+
+        let credential = &*credential_ptr;
+
+        let blob_len = credential.CredentialBlobSize as usize;
+        let blob_ptr = credential.CredentialBlob;
+
+        let blob_vec = if blob_len == 0 || blob_ptr.is_null() {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts(blob_ptr, blob_len).to_vec()
+        };
+
+        let attributes = if credential.AttributeCount == 0 || credential.Attributes.is_null() {
+            &[][..]
+        } else {
+            std::slice::from_raw_parts(credential.Attributes, credential.AttributeCount as usize)
+        };
+        let protected = attributes.iter().any(|attribute| {
+            attribute
+                .Keyword
+                .to_string()
+                .is_ok_and(|keyword| keyword == PROTECTED_ATTRIBUTE)
+        });
+        let comment = if credential.Comment.is_null() {
+            None
+        } else {
+            credential.Comment.to_string().ok()
+        };
+        let written = credential.LastWritten;
+        let last_written =
+            ((u64::from(written.dwHighDateTime) << 32) | u64::from(written.dwLowDateTime)) / 10_000;
+
+        CredFree(credential_ptr as *const _);
+
+        Ok((
+            blob_vec,
+            CredentialMetadata {
+                protected,
+                comment,
+                last_written: last_written.saturating_sub(FILETIME_UNIX_OFFSET_MS),
+            },
+        ))
+    }
+}
+
+/// Target and user names of the generic credentials of any application, all of them or those
+/// matching `filter`, where a trailing `*` is a wildcard
+pub(crate) fn generic_credentials(
+    filter: Option<&str>,
+) -> Result<Vec<(String, String)>, KeystoreError> {
+    let filter = filter.map(HSTRING::from);
+    unsafe {
+        let mut count = 0u32;
+        let mut credentials: *mut *mut CREDENTIALW = std::ptr::null_mut();
+        if let Err(e) = CredEnumerateW(
+            filter
+                .as_ref()
+                .map_or(PCWSTR::null(), |filter| PCWSTR(filter.as_ptr())),
+            CRED_ENUMERATE_FLAGS(0),
+            &mut count,
+            &mut credentials,
+        ) {
+            if e.code() == ERROR_NOT_FOUND.to_hresult() {
+                return Ok(Vec::new());
+            }
+            return Err(credential_error("enumerate", e));
+        }
+        if credentials.is_null() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = Vec::new();
+        for &credential_ptr in std::slice::from_raw_parts(credentials, count as usize) {
+            let credential = &*credential_ptr;
+            if credential.Type != CRED_TYPE_GENERIC
+                || credential.TargetName.is_null()
+                || credential.UserName.is_null()
+            {
+                continue;
+            }
+            if let (Ok(name), Ok(user)) = (
+                credential.TargetName.to_string(),
+                credential.UserName.to_string(),
+            ) {
+                names.push((name, user));
+            }
+        }
+        CredFree(credentials as *const _);
+        Ok(names)
+    }
+}
+
+/// Value of another application's generic credential named `target`: UTF-8 as keytar writes it,
+/// or UTF-16 as .NET and the Windows tools do
+pub(crate) fn read_foreign(target: &str) -> Result<Zeroizing<String>, KeystoreError> {
+    let (blob, _) = read_named(target)?;
+    let blob = Zeroizing::new(blob);
+    if let Ok(value) = std::str::from_utf8(&blob) {
+        return Ok(Zeroizing::new(value.to_string()));
+    }
+    let wide: Zeroizing<Vec<u16>> = Zeroizing::new(
+        blob.chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect(),
+    );
+    match String::from_utf16(&wide) {
+        Ok(value) if blob.len() % 2 == 0 => Ok(Zeroizing::new(value)),
+        _ => Err(KeystoreError::Serialization(format!(
+            "{} holds neither UTF-8 nor UTF-16 text",
+            target
+        ))),
+    }
+}
+
+/// Splits a name built by [`WindowsKeystore::build_credential_name`] back into its key; `None` for
+/// names of any other shape
+fn parse_credential_name(name: &str) -> Option<KeystoreKey> {
+    let mut parts = vec![String::new()];
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                escaped @ ('\\' | ':') => parts.last_mut()?.push(escaped),
+                _ => return None,
+            },
+            ':' => parts.push(String::new()),
+            c => parts.last_mut()?.push(c),
+        }
+    }
+    let [service, account] = <[String; 2]>::try_from(parts).ok()?;
+    Some(KeystoreKey { service, account })
+}
+
+/// Encrypts `value` with `CredProtectW` for the current user, returning the protected string
+/// without its terminating null
+fn cred_protect(value: &str) -> Result<Vec<u16>, KeystoreError> {
+    let mut plaintext: Vec<u16> = value.encode_utf16().chain(std::iter::once(0)).collect();
+    let mut protected = vec![0u16; plaintext.len() * 2 + 64];
+
+    let result = loop {
+        let mut chars = protected.len() as u32;
+        let result = unsafe {
+            CredProtectW(
+                FALSE,
+                &plaintext,
+                PWSTR(protected.as_mut_ptr()),
+                &mut chars,
+                None,
+            )
+        };
+        match result {
+            Err(e) if e.code() == ERROR_INSUFFICIENT_BUFFER.to_hresult() => {
+                protected.resize(chars as usize, 0);
+            }
+            Err(e) => break Err(e),
+            Ok(()) => {
+                protected.truncate(chars.saturating_sub(1) as usize);
+                break Ok(());
+            }
+        }
+    };
+
+    plaintext.fill(0);
+    result
+        .map(|()| protected)
+        .map_err(|e| KeystoreError::Platform(format!("Failed to protect credential: {}", e)))
+}
+
+/// Reverses [`cred_protect`] on a blob read back from the credential store
+fn cred_unprotect(blob: &[u8]) -> Result<String, KeystoreError> {
+    if blob.len() % 2 != 0 {
+        return Err(KeystoreError::Serialization(
+            "Protected credential has an odd byte length".to_string(),
+        ));
+    }
+
+    let protected: Vec<u16> = blob
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .chain(std::iter::once(0))
+        .collect();
+    let mut plaintext = vec![0u16; protected.len()];
+
+    loop {
+        let mut chars = plaintext.len() as u32;
+        let result =
+            unsafe { CredUnprotectW(FALSE, &protected, PWSTR(plaintext.as_mut_ptr()), &mut chars) };
+        match result {
+            Err(e) if e.code() == ERROR_INSUFFICIENT_BUFFER.to_hresult() => {
+                plaintext.resize(chars as usize, 0);
+            }
+            Err(e) => {
+                return Err(KeystoreError::Platform(format!(
+                    "Failed to unprotect credential: {}",
+                    e
+                )))
+            }
+            Ok(()) => {
+                let value = String::from_utf16(&plaintext[..chars.saturating_sub(1) as usize])
+                    .map_err(|e| KeystoreError::Serialization(e.to_string()));
+                plaintext.fill(0);
+                return value;
+            }
+        }
+    }
+}
+
+/// Calls `notify` whenever a credential file is written, added or removed. Credential Manager keeps
+/// each credential as a file under `Microsoft\Credentials` in the roaming and local app data
+/// folders; a thread waits on change notifications for both for the rest of the process.
+pub(crate) fn watch_changes(notify: fn()) -> Result<(), KeystoreError> {
+    let directories = ["APPDATA", "LOCALAPPDATA"]
+        .into_iter()
+        .filter_map(std::env::var_os)
+        .map(|base| {
+            std::path::PathBuf::from(base)
+                .join("Microsoft")
+                .join("Credentials")
+        })
+        .filter(|directory| directory.is_dir());
+    let mut handles = Vec::new();
+    for directory in directories {
+        let handle = unsafe {
+            FindFirstChangeNotificationW(
+                &HSTRING::from(directory.as_os_str()),
+                FALSE,
+                FILE_NOTIFY_CHANGE_FILE_NAME | FILE_NOTIFY_CHANGE_LAST_WRITE,
+            )
+        }
+        .map_err(|e| {
+            KeystoreError::Platform(format!("Failed to watch {}: {}", directory.display(), e))
+        })?;
+        // Raw handles aren't Send; they live as long as the process
+        handles.push(handle.0 as usize);
+    }
+    if handles.is_empty() {
+        return Err(KeystoreError::PlatformNotSupported);
+    }
+
+    std::thread::Builder::new()
+        .name("keystore-watch".to_string())
+        .spawn(move || {
+            let handles: Vec<HANDLE> = handles
+                .into_iter()
+                .map(|handle| HANDLE(handle as _))
+                .collect();
+            loop {
+                let event = unsafe { WaitForMultipleObjects(&handles, FALSE, INFINITE) };
+                let Some(&handle) = handles.get(event.0.wrapping_sub(WAIT_OBJECT_0.0) as usize)
+                else {
+                    return;
+                };
+                notify();
+                if unsafe { FindNextChangeNotification(handle) }.is_err() {
+                    return;
+                }
+            }
+        })?;
+    Ok(())
+}
+
+impl KeystoreOperations for WindowsKeystore {
+    /// Keeps the entry's label, comment and creation time as JSON in the `Comment` field
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        let credential_name = Self::build_credential_name(&entry.service, &entry.account);
+        let credential_name_hstring = HSTRING::from(credential_name.as_str());
+
+        let previous = match read_named(&credential_name) {
+            Ok((mut blob, metadata)) => {
+                blob.fill(0);
+                Some(metadata.entry_metadata())
+            }
+            Err(KeystoreError::KeyNotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let metadata = EntryMetadata {
+            // `LastWritten` records it
+            updated_at: None,
+            ..EntryMetadata::after_write(previous.as_ref(), entry, now)
+        };
+        let comment = serde_json::to_string(&metadata)
+            .map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+        if comment.encode_utf16().count() > MAX_COMMENT_LENGTH {
+            return Err(KeystoreError::InvalidInput(format!(
+                "Label and comment of {}:{} are too long for Credential Manager",
+                entry.service, entry.account
+            )));
+        }
+        let comment_hstring = HSTRING::from(comment.as_str());
+
+        let mut cred_blob = if self.cred_protect {
+            cred_protect(&entry.value)?
+                .iter()
+                .flat_map(|c| c.to_le_bytes())
+                .collect()
+        } else {
+            entry.value.as_bytes().to_vec()
+        };
+
+        let account_hstring = HSTRING::from(entry.account.as_str());
+
+        let mut keyword: Vec<u16> = PROTECTED_ATTRIBUTE
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .collect();
+        let mut protected_flag = [1u8];
+        let mut attributes = [CREDENTIAL_ATTRIBUTEW {
+            Keyword: PWSTR(keyword.as_mut_ptr()),
+            Flags: 0,
+            ValueSize: protected_flag.len() as u32,
+            Value: protected_flag.as_mut_ptr(),
+        }];
+
+        let credential = CREDENTIALW {
+            Flags: CRED_FLAGS(0),
+            Type: CRED_TYPE_GENERIC,
+            TargetName: PWSTR(credential_name_hstring.as_ptr() as *mut u16),
+            Comment: PWSTR(comment_hstring.as_ptr() as *mut u16),
+            LastWritten: windows::Win32::Foundation::FILETIME::default(),
+            CredentialBlobSize: cred_blob.len() as u32,
+            CredentialBlob: cred_blob.as_mut_ptr(),
+            Persist: self.persistence.flag(),
+            AttributeCount: u32::from(self.cred_protect),
+            Attributes: if self.cred_protect {
+                attributes.as_mut_ptr()
+            } else {
+                std::ptr::null_mut()
+            },
+            TargetAlias: PWSTR::null(),
+            UserName: PWSTR(account_hstring.as_ptr() as *mut u16),
+        };
+
+        let result =
+            unsafe { CredWriteW(&credential, 0).map_err(|e| credential_error("write", e)) };
+
+        cred_blob.fill(0);
+        result
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        let (blob_vec, metadata) = Self::read_credential(service, account)?;
+
+        if metadata.protected {
+            return cred_unprotect(&blob_vec);
+        }
+
+        let password =
+            String::from_utf8(blob_vec).map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+
+        Ok(password)
+    }
+
+    /// Read from the credential's `Comment` and `LastWritten` without decoding its value
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        let (mut blob, metadata) = Self::read_credential(service, account)?;
+        blob.fill(0);
+        Ok(metadata.entry_metadata())
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let credential_name = Self::build_credential_name(service, account);
+        let credential_name_hstring = HSTRING::from(credential_name.as_str());
+
+        unsafe {
+            CredDeleteW(
+                PCWSTR(credential_name_hstring.as_ptr()),
+                CRED_TYPE_GENERIC,
+                0,
+            )
+            .map_err(|e| {
+                if e.code() == ERROR_NOT_FOUND.to_hresult() {
+                    KeystoreError::KeyNotFound(credential_name.clone())
+                } else {
+                    credential_error("delete", e)
+                }
+            })
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    /// Every generic credential named and attributed the way this backend writes them, which can
+    /// include another application's that happens to follow the same scheme
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        Self::enumerate(None)
+    }
+
+    /// Enumerates only the credentials whose name starts with the escaped service
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        Ok(
+            Self::enumerate(Some(&Self::build_credential_name(service, "")))?
+                .into_iter()
+                .filter(|key| key.service == service)
+                .map(|key| key.account)
+                .collect(),
+        )
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        if self.cred_protect {
+            // UTF-16 expansion plus the DPAPI envelope and its text encoding
+            Some(PROTECTED_VALUE_SIZE)
+        } else {
+            Some(CRED_MAX_CREDENTIAL_BLOB_SIZE as usize)
+        }
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        BackendInfo::new("windows-credential-manager")
+            .with_detail("persistence", self.persistence.as_str())
+            .with_detail(
+                "persistence_source",
+                if self.persistence_configured {
+                    "configured"
+                } else {
+                    "detected"
+                },
+            )
+            .with_detail("execution_context", self.context.as_str())
+            .with_detail("local_system", self.context.local_system)
+            .with_detail(
+                "session_id",
+                self.context
+                    .session_id
+                    .map_or_else(|| "unknown".to_string(), |id| id.to_string()),
+            )
+            .with_detail("domain_joined", self.environment.domain_joined)
+            .with_detail("roaming_profile", self.environment.roaming_profile)
+            .with_detail("cred_protect", self.cred_protect)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_entry(service: &str, account: &str, value: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: service.to_string(),
+            account: account.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_set_and_get_password() {
+        let keystore = WindowsKeystore::new().unwrap();
+
+        let entry = create_test_entry("test-service", "test-account", "my-secret-password");
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("test-service", "test-account")
+            .unwrap();
+        assert_eq!(result, "my-secret-password");
+
+        keystore
+            .delete_password("test-service", "test-account")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_get_nonexistent_password() {
+        let keystore = WindowsKeystore::new().unwrap();
+
+        let result = keystore.get_password("nonexistent-service", "nonexistent-account");
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            KeystoreError::KeyNotFound(_) => (),
+            _ => panic!("Expected KeyNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_delete_nonexistent_password() {
+        let keystore = WindowsKeystore::new().unwrap();
+
+        let result = keystore.delete_password("nonexistent-service", "nonexistent-account");
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            KeystoreError::KeyNotFound(_) => (),
+            _ => panic!("Expected KeyNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_update_existing_password() {
+        let keystore = WindowsKeystore::new().unwrap();
+
+        let entry1 = create_test_entry("update-service", "update-account", "old-password");
+        let entry2 = create_test_entry("update-service", "update-account", "new-password");
+
+        keystore.set_password(&entry1).unwrap();
+        keystore.set_password(&entry2).unwrap();
+
+        let result = keystore
+            .get_password("update-service", "update-account")
+            .unwrap();
+        assert_eq!(result, "new-password");
+
+        keystore
+            .delete_password("update-service", "update-account")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_empty_value() {
+        let keystore = WindowsKeystore::new().unwrap();
+
+        let entry = create_test_entry("empty-service", "empty-account", "");
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("empty-service", "empty-account")
+            .unwrap();
+        assert_eq!(result, "");
+
+        keystore
+            .delete_password("empty-service", "empty-account")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_special_characters() {
+        let keystore = WindowsKeystore::new().unwrap();
+
+        let special_value = "!@#$%^&*()_+-=[]{}|;':\",./<>?`~\n\t\r";
+        let entry = create_test_entry("special-service", "special-account", special_value);
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("special-service", "special-account")
+            .unwrap();
+        assert_eq!(result, special_value);
+
+        keystore
+            .delete_password("special-service", "special-account")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_long_value() {
+        let keystore = WindowsKeystore::new().unwrap();
+
+        let long_value = "a".repeat(1000);
+        let entry = create_test_entry("long-service", "long-account", &long_value);
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("long-service", "long-account")
+            .unwrap();
+        assert_eq!(result, long_value);
+
+        keystore
+            .delete_password("long-service", "long-account")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_multiple_services() {
+        let keystore = WindowsKeystore::new().unwrap();
+
+        let entries = vec![
+            create_test_entry("service1", "account1", "password1"),
+            create_test_entry("service1", "account2", "password2"),
+            create_test_entry("service2", "account1", "password3"),
+        ];
+
+        for entry in &entries {
+            keystore.set_password(entry).unwrap();
+        }
+
+        assert_eq!(
+            keystore.get_password("service1", "account1").unwrap(),
+            "password1"
+        );
+        assert_eq!(
+            keystore.get_password("service1", "account2").unwrap(),
+            "password2"
+        );
+        assert_eq!(
+            keystore.get_password("service2", "account1").unwrap(),
+            "password3"
+        );
+
+        keystore.delete_password("service1", "account1").unwrap();
+        keystore.delete_password("service1", "account2").unwrap();
+        keystore.delete_password("service2", "account1").unwrap();
+    }
+
+    #[test]
+    fn test_utf8_values() {
+        let keystore = WindowsKeystore::new().unwrap();
+
+        let utf8_value = "Hello 世界 🌍 Привет";
+        let entry = create_test_entry("utf8-service", "utf8-account", utf8_value);
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("utf8-service", "utf8-account")
+            .unwrap();
+        assert_eq!(result, utf8_value);
+
+        keystore
+            .delete_password("utf8-service", "utf8-account")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_colon_in_service_account_no_collision() {
+        let keystore = WindowsKeystore::new().unwrap();
+
+        // These two entries would collide if we just used "service:account" format
+        // "foo:bar" + "baz" = "foo:bar:baz"
+        // "foo" + "bar:baz" = "foo:bar:baz"
+        let entry1 = create_test_entry("foo:bar", "baz", "password1");
+        let entry2 = create_test_entry("foo", "bar:baz", "password2");
+
+        keystore.set_password(&entry1).unwrap();
+        keystore.set_password(&entry2).unwrap();
+
+        // Both entries should be retrievable without collision
+        assert_eq!(
+            keystore.get_password("foo:bar", "baz").unwrap(),
+            "password1"
+        );
+        assert_eq!(
+            keystore.get_password("foo", "bar:baz").unwrap(),
+            "password2"
+        );
+
+        keystore.delete_password("foo:bar", "baz").unwrap();
+        keystore.delete_password("foo", "bar:baz").unwrap();
+    }
+
+    #[test]
+    fn test_backslash_in_service_account() {
+        let keystore = WindowsKeystore::new().unwrap();
+
+        let entry = create_test_entry("service\\with\\backslash", "account\\test", "password");
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("service\\with\\backslash", "account\\test")
+            .unwrap();
+        assert_eq!(result, "password");
+
+        keystore
+            .delete_password("service\\with\\backslash", "account\\test")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_list_credentials_matches_the_service_exactly() {
+        let keystore = WindowsKeystore::new().unwrap();
+        let service = "test-service-list:with\\escapes";
+        let entries = [
+            create_test_entry(service, "b", "2"),
+            create_test_entry(service, "a:1", "1"),
+            create_test_entry("test-service-list", "other", "3"),
+        ];
+        for entry in &entries {
+            keystore.set_password(entry).unwrap();
+        }
+
+        assert_eq!(keystore.list_credentials(service).unwrap(), ["a:1", "b"]);
+        assert!(keystore.list_keys().unwrap().contains(&KeystoreKey {
+            service: "test-service-list".to_string(),
+            account: "other".to_string(),
+        }));
+
+        for entry in &entries {
+            keystore
+                .delete_password(&entry.service, &entry.account)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_cred_protect_round_trip() {
+        let keystore = WindowsKeystore::new().unwrap().with_cred_protect(true);
+
+        let entry = create_test_entry("protect-service", "protect-account", "stream-key-123");
+
+        keystore.set_password(&entry).unwrap();
+
+        let result = keystore
+            .get_password("protect-service", "protect-account")
+            .unwrap();
+        assert_eq!(result, "stream-key-123");
+        assert!(
+            keystore
+                .metadata("protect-service", "protect-account")
+                .unwrap()
+                .protected
+        );
+
+        keystore
+            .delete_password("protect-service", "protect-account")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_protected_entry_readable_without_option() {
+        let protecting = WindowsKeystore::new().unwrap().with_cred_protect(true);
+        let plain = WindowsKeystore::new().unwrap();
+
+        let entry = create_test_entry("mixed-service", "mixed-account", "Hello 世界");
+        protecting.set_password(&entry).unwrap();
+        assert_eq!(
+            plain
+                .get_password("mixed-service", "mixed-account")
+                .unwrap(),
+            "Hello 世界"
+        );
+
+        // Overwriting without the option stores a plain blob again
+        plain.set_password(&entry).unwrap();
+        assert!(
+            !plain
+                .metadata("mixed-service", "mixed-account")
+                .unwrap()
+                .protected
+        );
+
+        plain
+            .delete_password("mixed-service", "mixed-account")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_default_persistence_avoids_roaming() {
+        let standalone = RoamingEnvironment::default();
+        assert_eq!(standalone.default_persistence(), Persistence::Enterprise);
+
+        let domain = RoamingEnvironment {
+            domain_joined: true,
+            roaming_profile: false,
+        };
+        assert_eq!(domain.default_persistence(), Persistence::LocalMachine);
+
+        let roaming = RoamingEnvironment {
+            domain_joined: false,
+            roaming_profile: true,
+        };
+        assert_eq!(roaming.default_persistence(), Persistence::LocalMachine);
+    }
+
+    #[test]
+    fn test_services_keep_credentials_local() {
+        let desktop = ExecutionContext {
+            session_id: Some(1),
+            local_system: false,
+        };
+        assert_eq!(desktop.as_str(), "interactive");
+        let standalone = RoamingEnvironment::default();
+        assert_eq!(
+            desktop.default_persistence(&standalone),
+            Persistence::Enterprise
+        );
+
+        let service = ExecutionContext {
+            session_id: Some(0),
+            local_system: true,
+        };
+        assert_eq!(service.as_str(), "service");
+        assert_eq!(
+            service.default_persistence(&standalone),
+            Persistence::LocalMachine
+        );
+    }
+
+    #[test]
+    fn test_backend_info_reports_persistence() {
+        let keystore = WindowsKeystore::new()
+            .unwrap()
+            .with_persistence(Persistence::LocalMachine);
+
+        let info = keystore.backend_info();
+        assert_eq!(info.name, "windows-credential-manager");
+        assert_eq!(info.details["persistence"], "local-machine");
+        assert_eq!(info.details["persistence_source"], "configured");
+
+        let entry = create_test_entry("persist-service", "persist-account", "value");
+        keystore.set_password(&entry).unwrap();
+        assert_eq!(
+            keystore
+                .get_password("persist-service", "persist-account")
+                .unwrap(),
+            "value"
+        );
+        keystore
+            .delete_password("persist-service", "persist-account")
+            .unwrap();
+    }
+}
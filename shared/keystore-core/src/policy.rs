@@ -0,0 +1,609 @@
+//! Managed configuration that administrators enforce over constructor options.
+//!
+//! Enterprise deployments pin settings with a policy: which backend to use, whether exports are
+//! allowed, whether an unlock passphrase is mandatory, where audit records go and which escrow keys
+//! backups are wrapped to. [`Policy::load`] reads the machine-wide policy file and then the
+//! platform's management channel, which wins where both set a field:
+//!
+//! | Platform | File | Management channel |
+//! | --- | --- | --- |
+//! | Windows | `%ProgramData%\streaming-enhancement\policy.json` | `HKLM\SOFTWARE\Policies\StreamingEnhancement` |
+//! | macOS | `/Library/Application Support/streaming-enhancement/policy.json` | Managed preferences for `com.streaming-enhancement.keystore` |
+//! | Other | `/etc/streaming-enhancement/policy.json` | |
+//!
+//! All sources use the field names of [`Policy`]; in the registry they are `REG_SZ` (backend),
+//! `REG_DWORD` (flags) and `REG_MULTI_SZ` (lists) values; `allowed_purposes` is a `REG_MULTI_SZ` of
+//! `service/account=purpose,purpose` lines. Both locations are writable only by administrators, so
+//! a user cannot relax the policy.
+//!
+//! `allowed_purposes` restricts what entries may be read for. [`PurposeCheckedKeystore`] refuses
+//! reads of a listed entry unless the reading thread declared one of its purposes (see
+//! [`crate::access::AccessContext`]).
+
+use crate::access::current_access_context;
+use crate::error::KeystoreError;
+use crate::escrow::{public_key_from_bytes, EscrowPublicKey};
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Backend a policy can force
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendPreference {
+    /// The platform keystore, never the encrypted file
+    Native,
+    /// The encrypted file, even where a platform keystore exists
+    File,
+}
+
+impl BackendPreference {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BackendPreference::Native => "native",
+            BackendPreference::File => "file",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, KeystoreError> {
+        match name {
+            "native" => Ok(BackendPreference::Native),
+            "file" => Ok(BackendPreference::File),
+            other => Err(KeystoreError::InvalidInput(format!(
+                "Unknown backend in policy: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Managed settings; unset fields leave the application's own choice alone
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    pub backend: Option<BackendPreference>,
+    /// Refuse backups and redacted exports
+    pub disable_exports: Option<bool>,
+    /// Require an unlock passphrase; see [`crate::lock::LockingKeystore::require_passphrase`]
+    pub require_passphrase: Option<bool>,
+    pub case_insensitive: Option<bool>,
+    /// Keep a copy of every entry in the encrypted file too; see [`crate::mirror`]
+    pub mirror_to_file: Option<bool>,
+    /// Files every audit record is appended to
+    pub audit_log_paths: Vec<PathBuf>,
+    /// Base64 X25519, or hybrid X25519 + ML-KEM, keys every backup is also wrapped to (see
+    /// [`crate::escrow`])
+    pub escrow_public_keys: Vec<String>,
+    /// Purposes each `service/account` or `service/*` may be read for; the exact entry wins over
+    /// its service's wildcard
+    pub allowed_purposes: BTreeMap<String, Vec<String>>,
+    /// Where the policy came from, for `backend_info`
+    #[serde(skip)]
+    pub sources: Vec<String>,
+}
+
+fn list<T>(theirs: Vec<T>, ours: Vec<T>) -> Vec<T> {
+    if theirs.is_empty() {
+        ours
+    } else {
+        theirs
+    }
+}
+
+impl Policy {
+    /// The machine policy, empty when none is configured
+    pub fn load() -> Result<Self, KeystoreError> {
+        let mut policy = Self::from_file(&policy_file_path())?.unwrap_or_default();
+        if let Some(managed) = managed_policy()? {
+            policy = policy.overridden_by(managed);
+        }
+        Ok(policy)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, KeystoreError> {
+        serde_json::from_str(json)
+            .map_err(|e| KeystoreError::Serialization(format!("Invalid policy: {}", e)))
+    }
+
+    /// `None` when `path` does not exist
+    pub fn from_file(path: &Path) -> Result<Option<Self>, KeystoreError> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut policy = Self::from_json(&json)?;
+        policy.sources = vec![path.display().to_string()];
+        Ok(Some(policy))
+    }
+
+    /// Fields `other` sets replace this policy's
+    pub fn overridden_by(self, other: Policy) -> Policy {
+        Policy {
+            backend: other.backend.or(self.backend),
+            disable_exports: other.disable_exports.or(self.disable_exports),
+            require_passphrase: other.require_passphrase.or(self.require_passphrase),
+            case_insensitive: other.case_insensitive.or(self.case_insensitive),
+            mirror_to_file: other.mirror_to_file.or(self.mirror_to_file),
+            audit_log_paths: list(other.audit_log_paths, self.audit_log_paths),
+            escrow_public_keys: list(other.escrow_public_keys, self.escrow_public_keys),
+            allowed_purposes: if other.allowed_purposes.is_empty() {
+                self.allowed_purposes
+            } else {
+                other.allowed_purposes
+            },
+            sources: [self.sources, other.sources].concat(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    pub fn exports_disabled(&self) -> bool {
+        self.disable_exports.unwrap_or(false)
+    }
+
+    pub fn passphrase_required(&self) -> bool {
+        self.require_passphrase.unwrap_or(false)
+    }
+
+    /// Fails with `AccessDenied` when the policy disables exports
+    pub fn check_export(&self) -> Result<(), KeystoreError> {
+        if self.exports_disabled() {
+            return Err(KeystoreError::AccessDenied(
+                "Exports are disabled by policy".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Purposes `service`/`account` may be read for, `None` when any will do
+    pub fn allowed_purposes(&self, service: &str, account: &str) -> Option<&[String]> {
+        self.allowed_purposes
+            .get(&format!("{}/{}", service, account))
+            .or_else(|| self.allowed_purposes.get(&format!("{}/*", service)))
+            .map(Vec::as_slice)
+    }
+
+    /// Fails with `AccessDenied` when the policy limits what `service`/`account` is read for and
+    /// `purpose` isn't one of those
+    pub fn check_purpose(
+        &self,
+        service: &str,
+        account: &str,
+        purpose: Option<&str>,
+    ) -> Result<(), KeystoreError> {
+        let Some(allowed) = self.allowed_purposes(service, account) else {
+            return Ok(());
+        };
+        match purpose {
+            Some(purpose) if allowed.iter().any(|p| p == purpose) => Ok(()),
+            Some(purpose) => Err(KeystoreError::AccessDenied(format!(
+                "Policy does not allow reading {}/{} for {}",
+                service, account, purpose
+            ))),
+            None => Err(KeystoreError::AccessDenied(format!(
+                "Policy requires a purpose to read {}/{}",
+                service, account
+            ))),
+        }
+    }
+
+    pub fn escrow_keys(&self) -> Result<Vec<EscrowPublicKey>, KeystoreError> {
+        self.escrow_public_keys
+            .iter()
+            .map(|encoded| {
+                let bytes = STANDARD.decode(encoded.trim()).map_err(|e| {
+                    KeystoreError::InvalidInput(format!("Invalid escrow key in policy: {}", e))
+                })?;
+                public_key_from_bytes(&bytes)
+            })
+            .collect()
+    }
+
+    /// Adds the enforced settings to `info` as `policy.*` details
+    pub fn annotate(&self, mut info: BackendInfo) -> BackendInfo {
+        if self.is_empty() {
+            return info;
+        }
+        info = info.with_detail("policy", self.sources.join(", "));
+        if let Some(backend) = self.backend {
+            info = info.with_detail("policy.backend", backend.as_str());
+        }
+        let flags = [
+            ("policy.disable_exports", self.disable_exports),
+            ("policy.require_passphrase", self.require_passphrase),
+            ("policy.case_insensitive", self.case_insensitive),
+            ("policy.mirror_to_file", self.mirror_to_file),
+        ];
+        for (key, value) in flags {
+            if let Some(value) = value {
+                info = info.with_detail(key, value);
+            }
+        }
+        if !self.audit_log_paths.is_empty() {
+            info = info.with_detail("policy.audit_log_paths", self.audit_log_paths.len());
+        }
+        if !self.escrow_public_keys.is_empty() {
+            info = info.with_detail("policy.escrow_keys", self.escrow_public_keys.len());
+        }
+        if !self.allowed_purposes.is_empty() {
+            info = info.with_detail("policy.allowed_purposes", self.allowed_purposes.len());
+        }
+        info
+    }
+}
+
+/// Enforces [`Policy::allowed_purposes`] on reads, using the purpose the calling thread declared.
+/// Put it below aliases and case folding, so rules apply to the names entries are stored under.
+pub struct PurposeCheckedKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    policy: Policy,
+}
+
+impl PurposeCheckedKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>, policy: Policy) -> Self {
+        Self { inner, policy }
+    }
+
+    fn check(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        if self.policy.allowed_purposes.is_empty() {
+            return Ok(());
+        }
+        let purpose = current_access_context().purpose;
+        self.policy
+            .check_purpose(service, account, purpose.as_deref())
+    }
+}
+
+impl KeystoreOperations for PurposeCheckedKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.inner.set_password(entry)
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.check(service, account)?;
+        self.inner.get_password(service, account)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.inner.delete_password(service, account)
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.list_credentials(service)
+    }
+
+    /// Refuses the whole batch if any entry may not be read for the declared purpose
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        for key in keys {
+            self.check(&key.service, &key.account)?;
+        }
+        self.inner.get_many(keys)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.inner.set_many(entries)
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.delete_many(keys)
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.delete_service(service)
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.wipe_all()
+    }
+
+    /// Each side ends up readable under the other's name, so both must be readable here
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.check(&a.service, &a.account)?;
+        self.check(&b.service, &b.account)?;
+        self.inner.swap(a, b)
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.check(service, account)?;
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.check(service, account)?;
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        self.check(service, account)?;
+        self.inner.get_or_create(service, account, length)
+    }
+}
+
+fn policy_file_path() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        let data = std::env::var("ProgramData").unwrap_or_else(|_| r"C:\ProgramData".to_string());
+        PathBuf::from(data)
+            .join("streaming-enhancement")
+            .join("policy.json")
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Application Support/streaming-enhancement/policy.json")
+    } else {
+        PathBuf::from("/etc/streaming-enhancement/policy.json")
+    }
+}
+
+#[cfg(all(windows, feature = "backend-windows"))]
+fn managed_policy() -> Result<Option<Policy>, KeystoreError> {
+    registry::read()
+}
+
+#[cfg(all(target_os = "macos", feature = "backend-macos"))]
+fn managed_policy() -> Result<Option<Policy>, KeystoreError> {
+    const MANAGED_PREFERENCES: &str =
+        "/Library/Managed Preferences/com.streaming-enhancement.keystore.plist";
+    let path = Path::new(MANAGED_PREFERENCES);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut policy: Policy = plist::from_file(path)
+        .map_err(|e| KeystoreError::Serialization(format!("Invalid managed preferences: {}", e)))?;
+    policy.sources = vec![MANAGED_PREFERENCES.to_string()];
+    Ok(Some(policy))
+}
+
+#[cfg(not(any(
+    all(windows, feature = "backend-windows"),
+    all(target_os = "macos", feature = "backend-macos")
+)))]
+fn managed_policy() -> Result<Option<Policy>, KeystoreError> {
+    Ok(None)
+}
+
+#[cfg(all(windows, feature = "backend-windows"))]
+mod registry {
+    use super::{BackendPreference, Policy};
+    use crate::error::KeystoreError;
+
+    use std::ffi::c_void;
+    use std::path::PathBuf;
+    use windows::core::{HSTRING, PCWSTR};
+    use windows::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_MORE_DATA, ERROR_SUCCESS};
+    use windows::Win32::System::Registry::{
+        RegGetValueW, HKEY_LOCAL_MACHINE, REG_ROUTINE_FLAGS, RRF_RT_REG_DWORD, RRF_RT_REG_MULTI_SZ,
+        RRF_RT_REG_SZ,
+    };
+
+    const POLICY_KEY: &str = r"SOFTWARE\Policies\StreamingEnhancement";
+
+    /// Raw bytes of a value, or `None` if it is not set
+    fn value(name: &str, flags: REG_ROUTINE_FLAGS) -> Result<Option<Vec<u8>>, KeystoreError> {
+        let key = HSTRING::from(POLICY_KEY);
+        let name = HSTRING::from(name);
+        let mut size = 0u32;
+        loop {
+            let mut data = vec![0u8; size as usize];
+            let status = unsafe {
+                RegGetValueW(
+                    HKEY_LOCAL_MACHINE,
+                    PCWSTR(key.as_ptr()),
+                    PCWSTR(name.as_ptr()),
+                    flags,
+                    None,
+                    (size > 0).then_some(data.as_mut_ptr().cast::<c_void>()),
+                    Some(&mut size as *mut u32),
+                )
+            };
+            match status {
+                ERROR_SUCCESS if !data.is_empty() || size == 0 => {
+                    data.truncate(size as usize);
+                    return Ok(Some(data));
+                }
+                // The first call only measured the value, or it grew in between
+                ERROR_SUCCESS | ERROR_MORE_DATA => continue,
+                ERROR_FILE_NOT_FOUND => return Ok(None),
+                status => {
+                    return Err(KeystoreError::Platform(format!(
+                        "Failed to read policy value: {:?}",
+                        status
+                    )))
+                }
+            }
+        }
+    }
+
+    fn strings(name: &str, flags: REG_ROUTINE_FLAGS) -> Result<Vec<String>, KeystoreError> {
+        let Some(data) = value(name, flags)? else {
+            return Ok(Vec::new());
+        };
+        let wide: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        Ok(wide
+            .split(|&c| c == 0)
+            .filter(|s| !s.is_empty())
+            .map(String::from_utf16_lossy)
+            .collect())
+    }
+
+    fn flag(name: &str) -> Result<Option<bool>, KeystoreError> {
+        Ok(value(name, RRF_RT_REG_DWORD)?
+            .and_then(|data| {
+                data.get(..4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            })
+            .map(|dword| dword != 0))
+    }
+
+    pub(super) fn read() -> Result<Option<Policy>, KeystoreError> {
+        let policy = Policy {
+            backend: strings("backend", RRF_RT_REG_SZ)?
+                .first()
+                .map(|name| BackendPreference::from_name(name))
+                .transpose()?,
+            disable_exports: flag("disable_exports")?,
+            require_passphrase: flag("require_passphrase")?,
+            case_insensitive: flag("case_insensitive")?,
+            mirror_to_file: flag("mirror_to_file")?,
+            audit_log_paths: strings("audit_log_paths", RRF_RT_REG_MULTI_SZ)?
+                .into_iter()
+                .map(PathBuf::from)
+                .collect(),
+            escrow_public_keys: strings("escrow_public_keys", RRF_RT_REG_MULTI_SZ)?,
+            allowed_purposes: strings("allowed_purposes", RRF_RT_REG_MULTI_SZ)?
+                .iter()
+                .filter_map(|line| line.split_once('='))
+                .map(|(entry, purposes)| {
+                    let purposes = purposes
+                        .split(',')
+                        .map(|p| p.trim().to_string())
+                        .filter(|p| !p.is_empty())
+                        .collect();
+                    (entry.trim().to_string(), purposes)
+                })
+                .collect(),
+            sources: vec![format!(r"HKLM\{}", POLICY_KEY)],
+        };
+        let unset = Policy {
+            sources: policy.sources.clone(),
+            ..Policy::default()
+        };
+        Ok((policy != unset).then_some(policy))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_managed_fields_override_the_file() {
+        let file = Policy {
+            sources: vec!["policy.json".to_string()],
+            ..Policy::from_json(r#"{"backend": "file", "disable_exports": true}"#).unwrap()
+        };
+        let managed = Policy {
+            sources: vec!["managed".to_string()],
+            ..Policy::from_json(r#"{"disable_exports": false, "require_passphrase": true}"#)
+                .unwrap()
+        };
+        let policy = file.overridden_by(managed);
+        assert_eq!(policy.backend, Some(BackendPreference::File));
+        assert!(!policy.exports_disabled());
+        assert!(policy.passphrase_required());
+        assert!(policy.check_export().is_ok());
+
+        let info = policy.annotate(BackendInfo::new("memory"));
+        assert_eq!(info.details["policy"], "policy.json, managed");
+        assert_eq!(info.details["policy.backend"], "file");
+        assert!(Policy::from_json(r#"{"unknown": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_reads_need_an_allowed_purpose() {
+        use crate::access::{with_access_context, AccessContext};
+        use crate::testing::MemoryKeystore;
+
+        let policy = Policy::from_json(
+            r#"{"allowed_purposes": {"twitch/*": ["irc-connect"], "twitch/webhook": []}}"#,
+        )
+        .unwrap();
+        let keystore = PurposeCheckedKeystore::new(Box::new(MemoryKeystore::default()), policy);
+        for account in ["bot", "webhook"] {
+            keystore
+                .set_password(&KeystoreEntry {
+                    service: "twitch".to_string(),
+                    account: account.to_string(),
+                    value: "token".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let purpose = |purpose: &str| AccessContext {
+            caller: None,
+            purpose: Some(purpose.to_string()),
+        };
+
+        assert!(matches!(
+            keystore.get_password("twitch", "bot"),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        with_access_context(purpose("irc-connect"), || {
+            assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "token");
+            // The exact entry's empty list overrides the wildcard
+            assert!(keystore.get_password("twitch", "webhook").is_err());
+        });
+        with_access_context(purpose("backup"), || {
+            assert!(keystore.get_password("twitch", "bot").is_err());
+        });
+    }
+
+    #[test]
+    fn test_swap_needs_an_allowed_purpose_for_both_sides() {
+        use crate::testing::MemoryKeystore;
+
+        let policy =
+            Policy::from_json(r#"{"allowed_purposes": {"twitch/webhook": ["verify"]}}"#).unwrap();
+        let keystore = PurposeCheckedKeystore::new(Box::new(MemoryKeystore::default()), policy);
+        for (service, account) in [("twitch", "webhook"), ("scratch", "slot")] {
+            keystore
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    value: format!("{}-secret", service),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let restricted = KeystoreKey {
+            service: "twitch".to_string(),
+            account: "webhook".to_string(),
+        };
+        let open = KeystoreKey {
+            service: "scratch".to_string(),
+            account: "slot".to_string(),
+        };
+
+        for (a, b) in [(&restricted, &open), (&open, &restricted)] {
+            assert!(matches!(
+                keystore.swap(a, b),
+                Err(KeystoreError::AccessDenied(_))
+            ));
+        }
+        assert_eq!(
+            keystore.get_password("scratch", "slot").unwrap(),
+            "scratch-secret"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Warming caches with the credentials a daemon needs at boot.
+//!
+//! Integrations starting one after another each wait on their own keystore read, and on macOS
+//! each first read may raise a Keychain prompt. [`prefetch`] reads every entry under the given
+//! services, plus any keys named outright, up front and spread over a few threads, so the caches
+//! in the stack hold them and any prompts appear together at startup. Values are dropped as soon
+//! as they're read; only the caches keep them.
+
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+use crate::KeystoreKey;
+
+use std::thread;
+use zeroize::Zeroizing;
+
+/// Threads reading at once; the operation queue, if any, bounds the backend further
+pub const PREFETCH_THREADS: usize = 4;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PrefetchReport {
+    /// Entries read
+    pub warmed: usize,
+    /// Named keys that don't exist
+    pub missing: Vec<KeystoreKey>,
+    /// Entries that couldn't be read, with the error
+    pub failed: Vec<(KeystoreKey, String)>,
+}
+
+/// Reads every entry under `services` and every key in `keys` through `backend`. Services are
+/// expanded with [`KeystoreOperations::list_keys`], so backends that can't enumerate only take
+/// `keys`.
+pub fn prefetch(
+    backend: &dyn KeystoreOperations,
+    services: &[String],
+    keys: &[KeystoreKey],
+) -> Result<PrefetchReport, KeystoreError> {
+    let mut wanted = keys.to_vec();
+    if !services.is_empty() {
+        wanted.extend(
+            backend
+                .list_keys()?
+                .into_iter()
+                .filter(|key| services.contains(&key.service)),
+        );
+    }
+    wanted.sort();
+    wanted.dedup();
+
+    let mut report = PrefetchReport::default();
+    if wanted.is_empty() {
+        return Ok(report);
+    }
+    let chunk_size = wanted.len().div_ceil(PREFETCH_THREADS);
+    let results: Vec<_> = thread::scope(|scope| {
+        let readers: Vec<_> = wanted
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || (chunk, backend.get_many(chunk))))
+            .collect();
+        readers
+            .into_iter()
+            .map(|reader| reader.join().expect("prefetch reader panicked"))
+            .collect()
+    });
+
+    for (chunk, values) in results {
+        match values {
+            Ok(values) => {
+                for (key, value) in chunk.iter().zip(values) {
+                    match value {
+                        Some(value) => {
+                            drop(Zeroizing::new(value));
+                            report.warmed += 1;
+                        }
+                        None => report.missing.push(key.clone()),
+                    }
+                }
+            }
+            Err(e) => report
+                .failed
+                .extend(chunk.iter().map(|key| (key.clone(), e.to_string()))),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use crate::KeystoreEntry;
+
+    #[test]
+    fn test_prefetch_reads_services_and_named_keys() {
+        let backend = MemoryKeystore::default();
+        for (service, account) in [("twitch", "bot"), ("twitch", "chat"), ("obs", "ws")] {
+            backend
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    value: "secret".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let key = |service: &str, account: &str| KeystoreKey {
+            service: service.to_string(),
+            account: account.to_string(),
+        };
+
+        let report = prefetch(
+            &backend,
+            &["twitch".to_string()],
+            &[key("twitch", "bot"), key("youtube", "api")],
+        )
+        .unwrap();
+        assert_eq!(report.warmed, 2);
+        assert_eq!(report.missing, [key("youtube", "api")]);
+        assert!(report.failed.is_empty());
+    }
+}
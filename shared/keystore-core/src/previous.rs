@@ -0,0 +1,468 @@
+//! Keeping the value an overwrite replaced.
+//!
+//! A token rotation that goes wrong mid-stream needs the old token back at once.
+//! [`PreviousValueKeystore`] copies the value each successful `set_password` or `swap` replaces to
+//! `<account>@previous` under the same service, with the time it was replaced, and reads of that
+//! name return it for as long as the retention window set with
+//! [`PreviousValueKeystore::set_retention`] lasts. Retention is off until a window is set. Previous
+//! values are left out of listings and deleted with their entry; expired ones are deleted when read
+//! or by [`PreviousValueKeystore::purge_expired`]. Previous-value names are read-only: writes,
+//! deletes and swaps of them fail with `InvalidInput`.
+
+use crate::blob::BlobStore;
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// Appended to an account to name its previous value
+pub const PREVIOUS_SUFFIX: &str = "@previous";
+
+/// Where the value `account` held before its last overwrite is kept
+pub fn previous_account(account: &str) -> String {
+    format!("{}{}", account, PREVIOUS_SUFFIX)
+}
+
+/// Whether `account` names a previous value, or a chunk of one
+pub fn is_previous(account: &str) -> bool {
+    let account = account
+        .rsplit_once(":chunk:")
+        .map_or(account, |(parent, _)| parent);
+    account.ends_with(PREVIOUS_SUFFIX)
+}
+
+#[derive(Serialize, Deserialize)]
+struct Retained {
+    value: String,
+    /// Milliseconds since the Unix epoch
+    replaced_at: u64,
+}
+
+impl Drop for Retained {
+    fn drop(&mut self) {
+        drop(Zeroizing::new(std::mem::take(&mut self.value)));
+    }
+}
+
+/// `InvalidInput` if `account` names a previous value, which only this layer writes
+fn check_not_previous(service: &str, account: &str) -> Result<(), KeystoreError> {
+    if is_previous(account) {
+        return Err(KeystoreError::InvalidInput(format!(
+            "{}:{} holds a previous value and is read-only",
+            service, account
+        )));
+    }
+    Ok(())
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub struct PreviousValueKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    retention: Mutex<Option<Duration>>,
+}
+
+impl PreviousValueKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self {
+            inner,
+            retention: Mutex::new(None),
+        }
+    }
+
+    /// How long a replaced value stays readable; `None`, the default, keeps none. Turning retention
+    /// off also hides the values kept so far.
+    pub fn set_retention(&self, window: Option<Duration>) {
+        *self.retention.lock().unwrap() = window;
+    }
+
+    pub fn retention(&self) -> Option<Duration> {
+        *self.retention.lock().unwrap()
+    }
+
+    fn blobs(&self) -> BlobStore<'_> {
+        BlobStore::new(self.inner.as_ref())
+    }
+
+    fn is_live(&self, retained: &Retained) -> bool {
+        self.retention().is_some_and(|window| {
+            now_millis().saturating_sub(retained.replaced_at) <= window.as_millis() as u64
+        })
+    }
+
+    /// Keeps `current`, which `value` has just replaced at `service`/`account`, if it differs
+    fn retain(
+        &self,
+        service: &str,
+        account: &str,
+        value: &str,
+        current: Option<&str>,
+    ) -> Result<(), KeystoreError> {
+        match current {
+            Some(current) if current != value => self.blobs().put_structured(
+                service,
+                &previous_account(account),
+                &Retained {
+                    value: current.to_string(),
+                    replaced_at: now_millis(),
+                },
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    fn current(
+        &self,
+        service: &str,
+        account: &str,
+    ) -> Result<Option<Zeroizing<String>>, KeystoreError> {
+        if self.retention().is_none() {
+            return Ok(None);
+        }
+        match self.inner.get_password(service, account) {
+            Ok(value) => Ok(Some(Zeroizing::new(value))),
+            Err(KeystoreError::KeyNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The value kept at `service`/`account`, a previous-value name; deleted once expired
+    fn read_previous(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        let retained: Retained = self.blobs().get_structured(service, account)?;
+        if self.is_live(&retained) {
+            return Ok(retained.value.clone());
+        }
+        if self.retention().is_some() {
+            self.forget(service, account)?;
+        }
+        Err(KeystoreError::KeyNotFound(format!(
+            "{}:{}",
+            service, account
+        )))
+    }
+
+    fn forget(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        match self.blobs().delete(service, account) {
+            Ok(()) | Err(KeystoreError::KeyNotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Deletes the previous values whose window has passed, returning the entries they belonged
+    /// to. Does nothing while retention is off.
+    pub fn purge_expired(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        if self.retention().is_none() {
+            return Ok(Vec::new());
+        }
+        let mut purged = Vec::new();
+        for key in self.inner.list_keys()? {
+            let Some(account) = key.account.strip_suffix(PREVIOUS_SUFFIX) else {
+                continue;
+            };
+            let retained: Retained = match self.blobs().get_structured(&key.service, &key.account) {
+                Ok(retained) => retained,
+                Err(KeystoreError::KeyNotFound(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            if !self.is_live(&retained) {
+                self.forget(&key.service, &key.account)?;
+                purged.push(KeystoreKey {
+                    service: key.service.clone(),
+                    account: account.to_string(),
+                });
+            }
+        }
+        purged.sort();
+        Ok(purged)
+    }
+}
+
+impl KeystoreOperations for PreviousValueKeystore {
+    /// Keeps the replaced value only once the write went through, so a failed write leaves no
+    /// previous value behind
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        check_not_previous(&entry.service, &entry.account)?;
+        let current = self.current(&entry.service, &entry.account)?;
+        self.inner.set_password(entry)?;
+        self.retain(
+            &entry.service,
+            &entry.account,
+            &entry.value,
+            current.as_ref().map(|value| value.as_str()),
+        )
+    }
+
+    /// Previous-value names return the value kept, not the stored record
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        if account.ends_with(PREVIOUS_SUFFIX) {
+            return self.read_previous(service, account);
+        }
+        self.inner.get_password(service, account)
+    }
+
+    /// Also deletes the entry's previous value
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        check_not_previous(service, account)?;
+        self.inner.delete_password(service, account)?;
+        self.forget(service, &previous_account(account))
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        let info = self.inner.backend_info();
+        match self.retention() {
+            Some(window) => info.with_detail("previous_retention_seconds", window.as_secs()),
+            None => info,
+        }
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut keys = self.inner.list_keys()?;
+        keys.retain(|key| !is_previous(&key.account));
+        Ok(keys)
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let mut accounts = self.inner.list_credentials(service)?;
+        accounts.retain(|account| !is_previous(account));
+        Ok(accounts)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        if !keys
+            .iter()
+            .any(|key| key.account.ends_with(PREVIOUS_SUFFIX))
+        {
+            return self.inner.get_many(keys);
+        }
+        keys.iter()
+            .map(|key| match self.get_password(&key.service, &key.account) {
+                Ok(value) => Ok(Some(value)),
+                Err(KeystoreError::KeyNotFound(_)) => Ok(None),
+                Err(e) => Err(e),
+            })
+            .collect()
+    }
+
+    /// Reads the values being replaced in one batch
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        for entry in entries {
+            check_not_previous(&entry.service, &entry.account)?;
+        }
+        if self.retention().is_none() {
+            return self.inner.set_many(entries);
+        }
+        let keys: Vec<KeystoreKey> = entries
+            .iter()
+            .map(|entry| KeystoreKey {
+                service: entry.service.clone(),
+                account: entry.account.clone(),
+            })
+            .collect();
+        let current: Vec<Option<Zeroizing<String>>> = self
+            .inner
+            .get_many(&keys)?
+            .into_iter()
+            .map(|value| value.map(Zeroizing::new))
+            .collect();
+        self.inner.set_many(entries)?;
+        for (entry, current) in entries.iter().zip(&current) {
+            self.retain(
+                &entry.service,
+                &entry.account,
+                &entry.value,
+                current.as_ref().map(|value| value.as_str()),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        for key in keys {
+            check_not_previous(&key.service, &key.account)?;
+        }
+        let removed = self.inner.delete_many(keys)?;
+        for key in &removed {
+            self.forget(&key.service, &previous_account(&key.account))?;
+        }
+        Ok(removed)
+    }
+
+    /// Previous values under `service` go too, but are not among the accounts returned
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let mut removed = self.inner.delete_service(service)?;
+        removed.retain(|account| !is_previous(account));
+        Ok(removed)
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut wiped = self.inner.wipe_all()?;
+        wiped.retain(|key| !is_previous(&key.account));
+        Ok(wiped)
+    }
+
+    /// Keeps each side's old value as its previous value, so a mistaken swap can be undone
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        check_not_previous(&a.service, &a.account)?;
+        check_not_previous(&b.service, &b.account)?;
+        let first = self.current(&a.service, &a.account)?;
+        let second = self.current(&b.service, &b.account)?;
+        self.inner.swap(a, b)?;
+        if let (Some(first), Some(second)) = (&first, &second) {
+            self.retain(&a.service, &a.account, second, Some(first))?;
+            self.retain(&b.service, &b.account, first, Some(second))?;
+        }
+        Ok(())
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        check_not_previous(service, account)?;
+        self.inner.get_or_create(service, account, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    fn set(keystore: &PreviousValueKeystore, account: &str, value: &str) {
+        keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: account.to_string(),
+                value: value.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_overwrites_keep_the_previous_value_for_the_window() {
+        let keystore = PreviousValueKeystore::new(Box::new(MemoryKeystore::default()));
+        set(&keystore, "stream-key", "one");
+        set(&keystore, "stream-key", "two");
+        // Retention was off
+        assert!(keystore
+            .get_password("twitch", "stream-key@previous")
+            .is_err());
+
+        keystore.set_retention(Some(Duration::from_secs(3600)));
+        set(&keystore, "stream-key", "three");
+        set(&keystore, "stream-key", "three");
+        assert_eq!(
+            keystore
+                .get_password("twitch", &previous_account("stream-key"))
+                .unwrap(),
+            "two"
+        );
+        assert_eq!(
+            keystore.list_credentials("twitch").unwrap(),
+            ["stream-key".to_string()]
+        );
+        // Only retention writes the previous-value name
+        assert!(matches!(
+            keystore.set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: previous_account("stream-key"),
+                value: "mine".to_string(),
+                ..Default::default()
+            }),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            keystore.delete_password("twitch", &previous_account("stream-key")),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+
+        keystore.set_retention(Some(Duration::ZERO));
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(
+            keystore.purge_expired().unwrap(),
+            [KeystoreKey {
+                service: "twitch".to_string(),
+                account: "stream-key".to_string(),
+            }]
+        );
+        assert_eq!(keystore.inner.list_keys().unwrap().len(), 1);
+
+        keystore.set_retention(Some(Duration::from_secs(3600)));
+        set(&keystore, "stream-key", "four");
+        keystore.delete_password("twitch", "stream-key").unwrap();
+        assert!(keystore.inner.list_keys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_failed_writes_keep_nothing_and_swaps_can_be_undone() {
+        use crate::protect::ProtectedKeystore;
+        use std::sync::Arc;
+
+        let protected = Arc::new(ProtectedKeystore::new(Box::new(MemoryKeystore::default())));
+        let keystore = PreviousValueKeystore::new(Box::new(Arc::clone(&protected)));
+        keystore.set_retention(Some(Duration::from_secs(3600)));
+        set(&keystore, "bot", "bot-token");
+        set(&keystore, "broadcaster", "broadcaster-token");
+
+        let key = |account: &str| KeystoreKey {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+        };
+        protected.make_write_once(&key("broadcaster")).unwrap();
+        assert!(keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "broadcaster".to_string(),
+                value: "wrong".to_string(),
+                ..Default::default()
+            })
+            .is_err());
+        assert!(matches!(
+            keystore.get_password("twitch", &previous_account("broadcaster")),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+
+        protected.clear_write_once(&key("broadcaster")).unwrap();
+        keystore.swap(&key("bot"), &key("broadcaster")).unwrap();
+        assert_eq!(
+            keystore
+                .get_password("twitch", &previous_account("bot"))
+                .unwrap(),
+            "bot-token"
+        );
+        assert_eq!(
+            keystore
+                .get_password("twitch", &previous_account("broadcaster"))
+                .unwrap(),
+            "broadcaster-token"
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! Background health probes of each backend.
+//!
+//! [`crate::health::health_report`] answers when asked; [`HealthProber`] asks on its own. Once
+//! started with an interval it checks every backend added with [`HealthProber::add_backend`] on a
+//! background thread, keeps the last [`MAX_SAMPLES`] results of each, updates the availability
+//! gauge of [`KeystoreMetrics`] and tells listeners of every change of status as a
+//! [`HealthTransition`]. A probe is an [`KeystoreOperations::is_available`] call: a backend that
+//! answers is healthy, one slower than [`SLOW_PROBE`] or running on the encrypted-file fallback is
+//! degraded, and one that doesn't answer is unhealthy.
+//!
+//! The backend is picked once, at startup, so nothing switches backends by itself yet; whatever
+//! decides to fail over reads [`HealthProber::status`] or listens for transitions.
+
+use crate::health::HealthStatus;
+use crate::metrics::KeystoreMetrics;
+use crate::platform::KeystoreOperations;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Probes kept per backend, oldest dropped first; an hour at the default interval
+pub const MAX_SAMPLES: usize = 120;
+
+/// Interval used when the caller doesn't pick one
+pub const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Probes answered slower than this degrade the backend
+pub const SLOW_PROBE: Duration = Duration::from_secs(1);
+
+/// One probe of one backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeSample {
+    /// Milliseconds since the Unix epoch
+    pub at: u64,
+    pub status: HealthStatus,
+    pub latency: Duration,
+}
+
+/// A backend whose status differs from its previous probe, or was probed for the first time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthTransition {
+    pub backend: String,
+    /// `None` on the first probe
+    pub from: Option<HealthStatus>,
+    pub to: HealthStatus,
+}
+
+type Listener = Box<dyn Fn(&HealthTransition) + Send + Sync>;
+
+#[derive(Default)]
+struct State {
+    /// `None` while stopped
+    interval: Option<Duration>,
+    running: bool,
+    closed: bool,
+    history: BTreeMap<String, VecDeque<ProbeSample>>,
+}
+
+struct Shared {
+    backends: Mutex<Vec<Box<dyn KeystoreOperations>>>,
+    state: Mutex<State>,
+    wake: Condvar,
+    listeners: Mutex<Vec<Listener>>,
+    metrics: OnceLock<Arc<KeystoreMetrics>>,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Probes every backend once, recording the samples and announcing transitions
+    fn probe_all(&self) {
+        let mut transitions = Vec::new();
+        for backend in self.backends.lock().unwrap().iter() {
+            let started = Instant::now();
+            let available = backend.is_available();
+            let latency = started.elapsed();
+            let name = backend.backend_info().name;
+            let status = if !available {
+                HealthStatus::Unhealthy
+            } else if latency > SLOW_PROBE || name == "encrypted-file" {
+                HealthStatus::Degraded
+            } else {
+                HealthStatus::Healthy
+            };
+            if let Some(metrics) = self.metrics.get() {
+                metrics.set_available(&name, available);
+            }
+
+            let mut state = self.state();
+            let samples = state.history.entry(name.clone()).or_default();
+            let from = samples.back().map(|sample| sample.status);
+            if samples.len() >= MAX_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(ProbeSample {
+                at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0),
+                status,
+                latency,
+            });
+            if from != Some(status) {
+                transitions.push(HealthTransition {
+                    backend: name,
+                    from,
+                    to: status,
+                });
+            }
+        }
+
+        for transition in &transitions {
+            if let Some(metrics) = self.metrics.get() {
+                metrics.record_health_transition(&transition.backend, transition.to.as_str());
+            }
+            for listener in self.listeners.lock().unwrap().iter() {
+                listener(transition);
+            }
+        }
+    }
+}
+
+fn prober(shared: Arc<Shared>) {
+    let mut state = shared.state();
+    loop {
+        if state.closed {
+            return;
+        }
+        match state.interval {
+            Some(interval) => {
+                drop(state);
+                shared.probe_all();
+                state = shared.state();
+                // Woken early by a new interval or by stop
+                state = shared.wake.wait_timeout(state, interval).unwrap().0;
+            }
+            None => {
+                state.running = false;
+                return;
+            }
+        }
+    }
+}
+
+pub struct HealthProber {
+    shared: Arc<Shared>,
+}
+
+impl Default for HealthProber {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthProber {
+    /// A stopped prober with no backends
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                backends: Mutex::new(Vec::new()),
+                state: Mutex::new(State::default()),
+                wake: Condvar::new(),
+                listeners: Mutex::new(Vec::new()),
+                metrics: OnceLock::new(),
+            }),
+        }
+    }
+
+    /// Probes `backend` too, under the name in its [`crate::platform::BackendInfo`]
+    pub fn add_backend(&self, backend: Box<dyn KeystoreOperations>) {
+        self.shared.backends.lock().unwrap().push(backend);
+    }
+
+    /// Reports availability and transitions into `metrics`; only the first call has an effect
+    pub fn set_metrics(&self, metrics: Arc<KeystoreMetrics>) {
+        let _ = self.shared.metrics.set(metrics);
+    }
+
+    /// Probes now and then every `interval` on a background thread; calling it again while
+    /// running changes the interval
+    pub fn start(&self, interval: Duration) {
+        let mut state = self.shared.state();
+        state.interval = Some(interval);
+        if !state.running {
+            state.running = true;
+            let shared = Arc::clone(&self.shared);
+            thread::spawn(move || prober(shared));
+        }
+        drop(state);
+        self.shared.wake.notify_all();
+    }
+
+    /// Stops probing after the probe in progress, if any; the history is kept
+    pub fn stop(&self) {
+        self.shared.state().interval = None;
+        self.shared.wake.notify_all();
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.shared.state().interval.is_some()
+    }
+
+    /// Probes every backend once on the calling thread, whether or not the prober is running
+    pub fn probe_now(&self) {
+        self.shared.probe_all();
+    }
+
+    /// Status of `backend` at its last probe; `None` if it wasn't probed yet
+    pub fn status(&self, backend: &str) -> Option<HealthStatus> {
+        self.shared
+            .state()
+            .history
+            .get(backend)
+            .and_then(|samples| samples.back())
+            .map(|sample| sample.status)
+    }
+
+    /// Recent probes of `backend`, oldest first
+    pub fn history(&self, backend: &str) -> Vec<ProbeSample> {
+        self.shared
+            .state()
+            .history
+            .get(backend)
+            .map(|samples| samples.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Names of the backends probed so far
+    pub fn backends(&self) -> Vec<String> {
+        self.shared.state().history.keys().cloned().collect()
+    }
+
+    /// Registers a callback for every transition. Callbacks run on the thread that probed, which
+    /// is the prober's own thread once started.
+    pub fn on_transition(&self, listener: impl Fn(&HealthTransition) + Send + Sync + 'static) {
+        self.shared
+            .listeners
+            .lock()
+            .unwrap()
+            .push(Box::new(listener));
+    }
+}
+
+impl Drop for HealthProber {
+    fn drop(&mut self) {
+        let mut state = self.shared.state();
+        state.closed = true;
+        state.interval = None;
+        drop(state);
+        self.shared.wake.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::KeystoreError;
+    use crate::platform::BackendInfo;
+    use crate::KeystoreEntry;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct Flaky(Arc<AtomicBool>);
+
+    impl KeystoreOperations for Flaky {
+        fn set_password(&self, _entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+            Ok(())
+        }
+
+        fn get_password(&self, service: &str, _account: &str) -> Result<String, KeystoreError> {
+            Err(KeystoreError::KeyNotFound(service.to_string()))
+        }
+
+        fn delete_password(&self, _service: &str, _account: &str) -> Result<(), KeystoreError> {
+            Ok(())
+        }
+
+        fn is_available(&self) -> bool {
+            self.0.load(Ordering::SeqCst)
+        }
+
+        fn backend_info(&self) -> BackendInfo {
+            BackendInfo::new("secret-service")
+        }
+    }
+
+    #[test]
+    fn test_transitions_are_announced_and_fed_into_metrics() {
+        let up = Arc::new(AtomicBool::new(true));
+        let prober = HealthProber::new();
+        prober.add_backend(Box::new(Flaky(Arc::clone(&up))));
+        let metrics = Arc::new(KeystoreMetrics::new().unwrap());
+        prober.set_metrics(Arc::clone(&metrics));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&seen);
+        prober.on_transition(move |transition| {
+            recorded.lock().unwrap().push(transition.clone());
+        });
+
+        prober.probe_now();
+        prober.probe_now();
+        up.store(false, Ordering::SeqCst);
+        prober.probe_now();
+
+        let transitions = seen.lock().unwrap().clone();
+        assert_eq!(
+            transitions
+                .iter()
+                .map(|t| (t.from, t.to))
+                .collect::<Vec<_>>(),
+            [
+                (None, HealthStatus::Healthy),
+                (Some(HealthStatus::Healthy), HealthStatus::Unhealthy),
+            ]
+        );
+        assert_eq!(prober.history("secret-service").len(), 3);
+        assert_eq!(
+            prober.status("secret-service"),
+            Some(HealthStatus::Unhealthy)
+        );
+        let text = metrics.render().unwrap();
+        assert!(text.contains(r#"keystore_backend_available{backend="secret-service"} 0"#));
+        assert!(text.contains(
+            r#"keystore_backend_health_transitions_total{backend="secret-service",status="unhealthy"} 1"#
+        ));
+
+        up.store(true, Ordering::SeqCst);
+        prober.start(Duration::from_millis(5));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while prober.status("secret-service") != Some(HealthStatus::Healthy) {
+            assert!(Instant::now() < deadline, "the prober never ran");
+            thread::sleep(Duration::from_millis(5));
+        }
+        prober.stop();
+        assert!(!prober.is_running());
+    }
+}
@@ -0,0 +1,528 @@
+//! Protected entries that ordinary deletes refuse to remove.
+//!
+//! Marking the broadcaster's stream key protected keeps a cleanup script or a stray click from
+//! deleting it. [`ProtectedKeystore`] fails `delete_password` on protected entries, and
+//! `delete_service` and `wipe_all` on any scope that contains one, with `AccessDenied`. Removing
+//! one anyway takes two steps: ask for a confirmation token, which is bound to the entry (or to the
+//! wipe) and expires after [`CONFIRMATION_TTL`], then pass it to `force_delete` or
+//! `authorize_wipe`. Tokens are single-use and only held in memory.
+//!
+//! Write-once entries, for provisioning flows, are guarded the same way against being replaced:
+//! while one exists, `set_password` on it fails with `AccessDenied`, so a buggy integration can't
+//! silently swap the broadcaster token for a bot token. Reads and deletes work as usual, and once
+//! deleted the entry can be written again, once. Replacing it takes a token from
+//! `confirm_overwrite`, passed to `force_set`.
+
+use crate::blob::BlobStore;
+use crate::error::KeystoreError;
+use crate::generate::hex_token;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// Service holding the set of protected keys
+pub const PROTECTED_SERVICE: &str = "streaming-enhancement:protected";
+pub(crate) const PROTECTED_ACCOUNT: &str = "index";
+pub(crate) const WRITE_ONCE_ACCOUNT: &str = "write-once";
+
+/// How long a confirmation token stays valid
+pub const CONFIRMATION_TTL: Duration = Duration::from_secs(60);
+
+/// What a confirmation token allows
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Scope {
+    Entry(KeystoreKey),
+    Overwrite(KeystoreKey),
+    WipeAll,
+}
+
+type KeySet = Mutex<Option<BTreeSet<KeystoreKey>>>;
+
+pub struct ProtectedKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    protected: KeySet,
+    write_once: KeySet,
+    confirmations: Mutex<HashMap<Scope, (String, Instant)>>,
+    /// Set by `authorize_wipe`, consumed by `wipe_all`
+    wipe_authorized: Mutex<Option<Instant>>,
+}
+
+fn protected_error(key: &KeystoreKey) -> KeystoreError {
+    KeystoreError::AccessDenied(format!(
+        "{}:{} is protected; confirm the deletion to remove it",
+        key.service, key.account
+    ))
+}
+
+fn write_once_error(key: &KeystoreKey) -> KeystoreError {
+    KeystoreError::AccessDenied(format!(
+        "{}:{} is write-once; confirm the overwrite to replace it",
+        key.service, key.account
+    ))
+}
+
+impl ProtectedKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self {
+            inner,
+            protected: Mutex::new(None),
+            write_once: Mutex::new(None),
+            confirmations: Mutex::new(HashMap::new()),
+            wipe_authorized: Mutex::new(None),
+        }
+    }
+
+    /// Drops the cached sets, so the next call reads the ones stored, e.g. after
+    /// [`crate::orphans::remove_orphans`] edited them below this layer
+    pub fn reload(&self) {
+        for set in [&self.protected, &self.write_once] {
+            if let Ok(mut set) = set.lock() {
+                *set = None;
+            }
+        }
+    }
+
+    /// Runs `f` on the set stored under `account`, loading it on first use
+    fn with_set<T>(
+        &self,
+        set: &KeySet,
+        account: &str,
+        f: impl FnOnce(&mut BTreeSet<KeystoreKey>) -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        let mut guard = set
+            .lock()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))?;
+        if guard.is_none() {
+            *guard = Some(
+                match BlobStore::new(self.inner.as_ref()).get_structured(PROTECTED_SERVICE, account)
+                {
+                    Ok(keys) => keys,
+                    Err(KeystoreError::KeyNotFound(_)) => BTreeSet::new(),
+                    Err(e) => return Err(e),
+                },
+            );
+        }
+        f(guard.as_mut().unwrap())
+    }
+
+    /// Runs `f` on the protected set
+    fn with_protected<T>(
+        &self,
+        f: impl FnOnce(&mut BTreeSet<KeystoreKey>) -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        self.with_set(&self.protected, PROTECTED_ACCOUNT, f)
+    }
+
+    /// Runs `f` on the write-once set
+    fn with_write_once<T>(
+        &self,
+        f: impl FnOnce(&mut BTreeSet<KeystoreKey>) -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        self.with_set(&self.write_once, WRITE_ONCE_ACCOUNT, f)
+    }
+
+    fn save(&self, protected: &BTreeSet<KeystoreKey>) -> Result<(), KeystoreError> {
+        BlobStore::new(self.inner.as_ref()).put_structured(
+            PROTECTED_SERVICE,
+            PROTECTED_ACCOUNT,
+            protected,
+        )
+    }
+
+    fn save_write_once(&self, write_once: &BTreeSet<KeystoreKey>) -> Result<(), KeystoreError> {
+        BlobStore::new(self.inner.as_ref()).put_structured(
+            PROTECTED_SERVICE,
+            WRITE_ONCE_ACCOUNT,
+            write_once,
+        )
+    }
+
+    fn exists(&self, key: &KeystoreKey) -> Result<bool, KeystoreError> {
+        match self.inner.get_password(&key.service, &key.account) {
+            Ok(value) => {
+                drop(Zeroizing::new(value));
+                Ok(true)
+            }
+            Err(KeystoreError::KeyNotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Marks an existing entry protected
+    pub fn protect(&self, key: &KeystoreKey) -> Result<(), KeystoreError> {
+        // Fails with KeyNotFound for a missing entry
+        drop(Zeroizing::new(
+            self.inner.get_password(&key.service, &key.account)?,
+        ));
+        self.with_protected(|protected| {
+            if protected.insert(key.clone()) {
+                self.save(protected)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn unprotect(&self, key: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.with_protected(|protected| {
+            if protected.remove(key) {
+                self.save(protected)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn is_protected(&self, key: &KeystoreKey) -> Result<bool, KeystoreError> {
+        self.with_protected(|protected| Ok(protected.contains(key)))
+    }
+
+    pub fn protected_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.with_protected(|protected| Ok(protected.iter().cloned().collect()))
+    }
+
+    /// Refuses writes to `key` while it exists; it need not exist yet
+    pub fn make_write_once(&self, key: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.with_write_once(|write_once| {
+            if write_once.insert(key.clone()) {
+                self.save_write_once(write_once)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn clear_write_once(&self, key: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.with_write_once(|write_once| {
+            if write_once.remove(key) {
+                self.save_write_once(write_once)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn is_write_once(&self, key: &KeystoreKey) -> Result<bool, KeystoreError> {
+        self.with_write_once(|write_once| Ok(write_once.contains(key)))
+    }
+
+    pub fn write_once_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.with_write_once(|write_once| Ok(write_once.iter().cloned().collect()))
+    }
+
+    fn issue(&self, scope: Scope) -> Result<String, KeystoreError> {
+        let token = hex_token(16)?;
+        let mut confirmations = self.confirmations.lock().unwrap();
+        confirmations.retain(|_, (_, issued)| issued.elapsed() < CONFIRMATION_TTL);
+        confirmations.insert(scope, (token.clone(), Instant::now()));
+        Ok(token)
+    }
+
+    /// Consumes the token for `scope`, failing if it is wrong, used or expired
+    fn redeem(&self, scope: &Scope, token: &str) -> Result<(), KeystoreError> {
+        let mut confirmations = self.confirmations.lock().unwrap();
+        let valid = match confirmations.get(scope) {
+            Some((expected, issued)) => {
+                issued.elapsed() < CONFIRMATION_TTL
+                    && bool::from(expected.as_bytes().ct_eq(token.as_bytes()))
+            }
+            None => false,
+        };
+        if !valid {
+            return Err(KeystoreError::AccessDenied(
+                "Invalid or expired confirmation token".to_string(),
+            ));
+        }
+        confirmations.remove(scope);
+        Ok(())
+    }
+
+    /// Token for one `force_delete` of `key`
+    pub fn confirm_delete(&self, key: &KeystoreKey) -> Result<String, KeystoreError> {
+        self.issue(Scope::Entry(key.clone()))
+    }
+
+    /// Token for one `force_set` of `key`
+    pub fn confirm_overwrite(&self, key: &KeystoreKey) -> Result<String, KeystoreError> {
+        self.issue(Scope::Overwrite(key.clone()))
+    }
+
+    /// Replaces a write-once entry; it stays write-once
+    pub fn force_set(&self, entry: &KeystoreEntry, token: &str) -> Result<(), KeystoreError> {
+        let key = KeystoreKey {
+            service: entry.service.clone(),
+            account: entry.account.clone(),
+        };
+        self.redeem(&Scope::Overwrite(key), token)?;
+        self.inner.set_password(entry)
+    }
+
+    /// Token for [`Self::authorize_wipe`]
+    pub fn confirm_wipe(&self) -> Result<String, KeystoreError> {
+        self.issue(Scope::WipeAll)
+    }
+
+    /// Deletes `key` even if it is protected, and drops its protection
+    pub fn force_delete(&self, key: &KeystoreKey, token: &str) -> Result<(), KeystoreError> {
+        self.redeem(&Scope::Entry(key.clone()), token)?;
+        self.with_protected(|protected| {
+            self.inner.delete_password(&key.service, &key.account)?;
+            if protected.remove(key) {
+                self.save(protected)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Lets the next `wipe_all`, within [`CONFIRMATION_TTL`], remove protected entries too.
+    /// `token` comes from [`Self::confirm_wipe`].
+    pub fn authorize_wipe(&self, token: &str) -> Result<(), KeystoreError> {
+        self.redeem(&Scope::WipeAll, token)?;
+        *self.wipe_authorized.lock().unwrap() = Some(Instant::now());
+        Ok(())
+    }
+}
+
+impl KeystoreOperations for ProtectedKeystore {
+    /// Refused if the entry is write-once and already exists
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        let key = KeystoreKey {
+            service: entry.service.clone(),
+            account: entry.account.clone(),
+        };
+        // Held across the check and the write, so two first writes can't both succeed
+        self.with_write_once(|write_once| {
+            if write_once.contains(&key) && self.exists(&key)? {
+                return Err(write_once_error(&key));
+            }
+            self.inner.set_password(entry)
+        })
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.inner.get_password(service, account)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let key = KeystoreKey {
+            service: service.to_string(),
+            account: account.to_string(),
+        };
+        self.with_protected(|protected| {
+            if protected.contains(&key) {
+                return Err(protected_error(&key));
+            }
+            self.inner.delete_password(service, account)
+        })
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.list_credentials(service)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        self.inner.get_many(keys)
+    }
+
+    /// Refused, writing nothing, if any entry is write-once and already exists or is written
+    /// twice in the batch
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.with_write_once(|write_once| {
+            let mut seen = BTreeSet::new();
+            for entry in entries {
+                let key = KeystoreKey {
+                    service: entry.service.clone(),
+                    account: entry.account.clone(),
+                };
+                if write_once.contains(&key) && (!seen.insert(key.clone()) || self.exists(&key)?) {
+                    return Err(write_once_error(&key));
+                }
+            }
+            self.inner.set_many(entries)
+        })
+    }
+
+    /// Refused, deleting nothing, if any key is protected
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.with_protected(|protected| {
+            if let Some(key) = keys.iter().find(|key| protected.contains(*key)) {
+                return Err(protected_error(key));
+            }
+            self.inner.delete_many(keys)
+        })
+    }
+
+    /// Refused, deleting nothing, if any entry under `service` is protected
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.with_protected(|protected| {
+            if let Some(key) = protected.iter().find(|key| key.service == service) {
+                return Err(protected_error(key));
+            }
+            self.inner.delete_service(service)
+        })
+    }
+
+    /// Refused, deleting nothing, while any entry is protected unless authorized with
+    /// [`ProtectedKeystore::authorize_wipe`]
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.with_protected(|protected| {
+            let authorized = self
+                .wipe_authorized
+                .lock()
+                .unwrap()
+                .take()
+                .is_some_and(|at| at.elapsed() < CONFIRMATION_TTL);
+            if let (false, Some(key)) = (authorized, protected.iter().next()) {
+                return Err(protected_error(key));
+            }
+            let removed = self.inner.wipe_all()?;
+            // The stored sets were among the entries removed
+            protected.clear();
+            *self.write_once.lock().unwrap() = None;
+            Ok(removed)
+        })
+    }
+
+    /// Refused if either entry is write-once, as both are overwritten
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.with_write_once(|write_once| {
+            if let Some(key) = [a, b].into_iter().find(|key| write_once.contains(*key)) {
+                return Err(write_once_error(key));
+            }
+            self.inner.swap(a, b)
+        })
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        // Never overwrites, so write-once entries need no check
+        self.inner.get_or_create(service, account, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    fn stream_key() -> KeystoreKey {
+        KeystoreKey {
+            service: "obs".to_string(),
+            account: "stream-key".to_string(),
+        }
+    }
+
+    fn keystore() -> ProtectedKeystore {
+        let keystore = ProtectedKeystore::new(Box::new(MemoryKeystore::default()));
+        for account in ["stream-key", "backup-key"] {
+            keystore
+                .set_password(&KeystoreEntry {
+                    service: "obs".to_string(),
+                    account: account.to_string(),
+                    value: "live_123".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        keystore.protect(&stream_key()).unwrap();
+        keystore
+    }
+
+    #[test]
+    fn test_protected_entry_needs_confirmed_force_delete() {
+        let keystore = keystore();
+        let key = stream_key();
+
+        assert!(matches!(
+            keystore.delete_password("obs", "stream-key"),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        assert!(keystore.delete_service("obs").is_err());
+        keystore.delete_password("obs", "backup-key").unwrap();
+
+        assert!(keystore.force_delete(&key, "guess").is_err());
+        let token = keystore.confirm_delete(&key).unwrap();
+        keystore.force_delete(&key, &token).unwrap();
+        assert!(keystore.get_password("obs", "stream-key").is_err());
+        assert!(!keystore.is_protected(&key).unwrap());
+        // Tokens are single-use
+        assert!(keystore.force_delete(&key, &token).is_err());
+    }
+
+    #[test]
+    fn test_write_once_entry_needs_confirmed_overwrite() {
+        let keystore = keystore();
+        let key = KeystoreKey {
+            service: "twitch".to_string(),
+            account: "broadcaster".to_string(),
+        };
+        let entry = |value: &str| KeystoreEntry {
+            service: key.service.clone(),
+            account: key.account.clone(),
+            value: value.to_string(),
+            ..Default::default()
+        };
+        keystore.make_write_once(&key).unwrap();
+        keystore.set_password(&entry("broadcaster-token")).unwrap();
+
+        assert!(matches!(
+            keystore.set_password(&entry("bot-token")),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        assert!(keystore.force_set(&entry("bot-token"), "guess").is_err());
+        let token = keystore.confirm_overwrite(&key).unwrap();
+        keystore.force_set(&entry("new-token"), &token).unwrap();
+        assert_eq!(
+            keystore.get_password("twitch", "broadcaster").unwrap(),
+            "new-token"
+        );
+
+        // Deleting is allowed, after which one write goes through again
+        keystore.delete_password("twitch", "broadcaster").unwrap();
+        keystore.set_password(&entry("reprovisioned")).unwrap();
+        assert!(keystore.set_password(&entry("again")).is_err());
+    }
+
+    #[test]
+    fn test_wipe_all_requires_confirmation_while_protected() {
+        let keystore = keystore();
+        assert!(keystore.wipe_all().is_err());
+        assert_eq!(keystore.list_keys().unwrap().len(), 3);
+
+        assert!(keystore.authorize_wipe("guess").is_err());
+        let token = keystore.confirm_wipe().unwrap();
+        keystore.authorize_wipe(&token).unwrap();
+        assert_eq!(keystore.wipe_all().unwrap().len(), 3);
+        assert!(keystore.list_keys().unwrap().is_empty());
+        assert!(keystore.protected_keys().unwrap().is_empty());
+    }
+}
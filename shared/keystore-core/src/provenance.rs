@@ -0,0 +1,427 @@
+//! Where each entry came from.
+//!
+//! A stale token is easier to chase down once you know what wrote it. [`ProvenanceKeystore`]
+//! records, for every write, the [`Origin`] and component declared by the calling thread with
+//! [`set_provenance`] or [`with_provenance`], the way [`crate::access`] takes the caller and
+//! purpose of reads. Records are kept in one structured entry under [`PROVENANCE_SERVICE`] and
+//! dropped with the entries they describe. Writes made without a declared origin are recorded as
+//! [`Origin::Manual`]. [`crate::inventory`] reports the records alongside each entry.
+
+use crate::blob::BlobStore;
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Service holding the provenance records
+pub const PROVENANCE_SERVICE: &str = "streaming-enhancement:provenance-index";
+pub(crate) const PROVENANCE_ACCOUNT: &str = "index";
+
+/// How an entry was written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Origin {
+    /// Stored at the end of an OAuth authorization
+    OauthFlow,
+    /// Entered by the user, or written without a declared origin
+    #[default]
+    Manual,
+    /// Migrated from the keytar store of an older release
+    ImportKeytar,
+    /// Restored from a backup or imported from a `.env` file
+    Import,
+    Cli,
+    /// Pulled from another device
+    Sync,
+}
+
+impl Origin {
+    pub const ALL: [Origin; 6] = [
+        Origin::OauthFlow,
+        Origin::Manual,
+        Origin::ImportKeytar,
+        Origin::Import,
+        Origin::Cli,
+        Origin::Sync,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Origin::OauthFlow => "oauth-flow",
+            Origin::Manual => "manual",
+            Origin::ImportKeytar => "import-keytar",
+            Origin::Import => "import",
+            Origin::Cli => "cli",
+            Origin::Sync => "sync",
+        }
+    }
+
+    pub fn parse(origin: &str) -> Result<Self, KeystoreError> {
+        Self::ALL
+            .into_iter()
+            .find(|o| o.as_str() == origin)
+            .ok_or_else(|| KeystoreError::InvalidInput(format!("Unknown origin: {}", origin)))
+    }
+}
+
+/// What writes made on this thread are recorded as
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProvenanceContext {
+    pub origin: Origin,
+    /// The part of the application writing, e.g. `twitch-auth`
+    pub component: Option<String>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<ProvenanceContext> = RefCell::new(ProvenanceContext::default());
+}
+
+/// Provenance of writes made on this thread
+pub fn current_provenance() -> ProvenanceContext {
+    CONTEXT.with(|context| context.borrow().clone())
+}
+
+/// Sets the provenance of writes made on this thread from now on
+pub fn set_provenance(context: ProvenanceContext) {
+    CONTEXT.with(|current| *current.borrow_mut() = context);
+}
+
+/// Runs `f` with this thread's writes recorded as `context`, restoring the previous context
+/// afterwards
+pub fn with_provenance<T>(context: ProvenanceContext, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<ProvenanceContext>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            if let Some(context) = self.0.take() {
+                set_provenance(context);
+            }
+        }
+    }
+
+    let _restore = Restore(Some(current_provenance()));
+    set_provenance(context);
+    f()
+}
+
+/// The write that last set an entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryProvenance {
+    pub service: String,
+    pub account: String,
+    pub origin: Origin,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component: Option<String>,
+    /// Milliseconds since the Unix epoch
+    pub written_at: u64,
+}
+
+type Records = BTreeMap<KeystoreKey, EntryProvenance>;
+
+/// Every record stored in `backend`, in key order; none if nothing was recorded yet
+pub fn load_provenance(
+    backend: &dyn KeystoreOperations,
+) -> Result<Vec<EntryProvenance>, KeystoreError> {
+    match BlobStore::new(backend).get_structured(PROVENANCE_SERVICE, PROVENANCE_ACCOUNT) {
+        Ok(records) => Ok(records),
+        Err(KeystoreError::KeyNotFound(_)) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+pub struct ProvenanceKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    records: Mutex<Option<Records>>,
+}
+
+impl ProvenanceKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self {
+            inner,
+            records: Mutex::new(None),
+        }
+    }
+
+    /// Drops the cached records, so the next call reads the ones stored, e.g. after
+    /// [`crate::orphans::remove_orphans`] edited them below this layer
+    pub fn reload(&self) {
+        if let Ok(mut records) = self.records.lock() {
+            *records = None;
+        }
+    }
+
+    /// Runs `f` on the records, loading them on first use and saving them if `f` returns true
+    fn update(&self, f: impl FnOnce(&mut Records) -> bool) -> Result<(), KeystoreError> {
+        let mut guard = self
+            .records
+            .lock()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))?;
+        if guard.is_none() {
+            *guard = Some(
+                load_provenance(self.inner.as_ref())?
+                    .into_iter()
+                    .map(|record| (key(&record.service, &record.account), record))
+                    .collect(),
+            );
+        }
+        let records = guard.as_mut().unwrap();
+        if f(records) {
+            let stored: Vec<&EntryProvenance> = records.values().collect();
+            BlobStore::new(self.inner.as_ref()).put_structured(
+                PROVENANCE_SERVICE,
+                PROVENANCE_ACCOUNT,
+                &stored,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Records that `service`/`account` was just written, by whoever the thread's context names
+    fn record_write(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let context = current_provenance();
+        let record = EntryProvenance {
+            service: service.to_string(),
+            account: account.to_string(),
+            origin: context.origin,
+            component: context.component,
+            written_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        };
+        self.update(|records| {
+            records.insert(key(service, account), record);
+            true
+        })
+    }
+
+    /// How `service`/`account` was last written, if that was recorded
+    pub fn provenance(
+        &self,
+        service: &str,
+        account: &str,
+    ) -> Result<Option<EntryProvenance>, KeystoreError> {
+        let mut found = None;
+        self.update(|records| {
+            found = records.get(&key(service, account)).cloned();
+            false
+        })?;
+        Ok(found)
+    }
+
+    /// Every record, in key order
+    pub fn records(&self) -> Result<Vec<EntryProvenance>, KeystoreError> {
+        let mut all = Vec::new();
+        self.update(|records| {
+            all = records.values().cloned().collect();
+            false
+        })?;
+        Ok(all)
+    }
+
+    fn forget(&self, keys: &[KeystoreKey]) -> Result<(), KeystoreError> {
+        self.update(|records| {
+            let before = records.len();
+            for key in keys {
+                records.remove(key);
+            }
+            records.len() != before
+        })
+    }
+}
+
+fn key(service: &str, account: &str) -> KeystoreKey {
+    KeystoreKey {
+        service: service.to_string(),
+        account: account.to_string(),
+    }
+}
+
+impl KeystoreOperations for ProvenanceKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.inner.set_password(entry)?;
+        self.record_write(&entry.service, &entry.account)
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.inner.get_password(service, account)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.inner.delete_password(service, account)?;
+        self.forget(&[key(service, account)])
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.list_credentials(service)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        self.inner.get_many(keys)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.inner.set_many(entries)?;
+        let context = current_provenance();
+        let written_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        self.update(|records| {
+            for entry in entries {
+                records.insert(
+                    key(&entry.service, &entry.account),
+                    EntryProvenance {
+                        service: entry.service.clone(),
+                        account: entry.account.clone(),
+                        origin: context.origin,
+                        component: context.component.clone(),
+                        written_at,
+                    },
+                );
+            }
+            !entries.is_empty()
+        })
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let removed = self.inner.delete_many(keys)?;
+        self.forget(&removed)?;
+        Ok(removed)
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let accounts = self.inner.delete_service(service)?;
+        let keys: Vec<KeystoreKey> = accounts
+            .iter()
+            .map(|account| key(service, account))
+            .collect();
+        self.forget(&keys)?;
+        Ok(accounts)
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let wiped = self.inner.wipe_all()?;
+        // The records went with everything else
+        *self
+            .records
+            .lock()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))? =
+            Some(Records::new());
+        Ok(wiped)
+    }
+
+    /// The records move with the values
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.inner.swap(a, b)?;
+        self.update(|records| {
+            let first = records.remove(a);
+            let second = records.remove(b);
+            for (key, record) in [(a, second), (b, first)] {
+                if let Some(record) = record {
+                    records.insert(
+                        key.clone(),
+                        EntryProvenance {
+                            service: key.service.clone(),
+                            account: key.account.clone(),
+                            ..record
+                        },
+                    );
+                }
+            }
+            true
+        })
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        // Checked first, so only the call that creates the entry is recorded; if another process
+        // creates it in between, the record names this one
+        let existed = self.inner.has_password(service, account)?;
+        let value = self.inner.get_or_create(service, account, length)?;
+        if !existed {
+            self.record_write(service, account)?;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_writes_are_recorded_with_the_thread_provenance() {
+        let backend = Arc::new(MemoryKeystore::default());
+        let keystore = ProvenanceKeystore::new(Box::new(Arc::clone(&backend)));
+        let entry = |account: &str| KeystoreEntry {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+            value: "token".to_string(),
+            ..Default::default()
+        };
+
+        keystore.set_password(&entry("bot")).unwrap();
+        with_provenance(
+            ProvenanceContext {
+                origin: Origin::OauthFlow,
+                component: Some("twitch-auth".to_string()),
+            },
+            || keystore.set_password(&entry("broadcaster")).unwrap(),
+        );
+        assert_eq!(current_provenance(), ProvenanceContext::default());
+
+        let bot = keystore.provenance("twitch", "bot").unwrap().unwrap();
+        assert_eq!((bot.origin, bot.component), (Origin::Manual, None));
+        let broadcaster = keystore
+            .provenance("twitch", "broadcaster")
+            .unwrap()
+            .unwrap();
+        assert_eq!(broadcaster.origin, Origin::OauthFlow);
+        assert_eq!(broadcaster.component.as_deref(), Some("twitch-auth"));
+
+        keystore.delete_password("twitch", "bot").unwrap();
+        let stored = load_provenance(backend.as_ref()).unwrap();
+        assert_eq!(stored, vec![broadcaster]);
+        assert_eq!(
+            Origin::parse("import-keytar").unwrap(),
+            Origin::ImportKeytar
+        );
+        assert!(Origin::parse("carrier-pigeon").is_err());
+    }
+}
@@ -0,0 +1,198 @@
+//! Passphrase-encrypted exports split across QR codes, for onboarding the phone app.
+//!
+//! [`export_qr`] writes an ordinary [`crate::backup`] blob for the chosen services and cuts it into
+//! text payloads small enough for a QR code a phone camera reads reliably. The desktop UI renders
+//! them one after another; the phone scans them in any order into a [`QrAssembler`], which decrypts
+//! the reassembled backup with the passphrase. The codes are only as safe as the passphrase, which
+//! should travel separately (typed, not shown beside the codes).
+//!
+//! Payload: `SEKQ1:<index>/<total>:<export id>:<chunk>`, the chunk in unpadded URL-safe base64 and
+//! the export id the first 8 hex digits of the blob's SHA-256, so codes from two exports never mix.
+
+use crate::backup::{export_backup_with_escrow, read_backup, ExportFilter};
+use crate::error::KeystoreError;
+use crate::escrow::EscrowPublicKey;
+use crate::platform::KeystoreOperations;
+use crate::KeystoreEntry;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+const PREFIX: &str = "SEKQ1";
+
+/// Backup bytes per code: 800 characters once encoded, which fits a version 20 code at medium error
+/// correction
+pub const QR_CHUNK_SIZE: usize = 600;
+
+/// More codes than anyone would scan; bounds what a hostile code can make the assembler allocate
+pub const MAX_QR_CODES: usize = 64;
+
+fn invalid(message: &str) -> KeystoreError {
+    KeystoreError::InvalidInput(format!("Invalid QR payload: {}", message))
+}
+
+/// The entries under `services` (every service if empty), encrypted under `passphrase` and split
+/// into QR payloads in scanning order
+pub fn export_qr(
+    backend: &dyn KeystoreOperations,
+    services: &[String],
+    passphrase: &str,
+) -> Result<Vec<String>, KeystoreError> {
+    export_qr_with_escrow(backend, services, passphrase, &[])
+}
+
+/// Like [`export_qr`], and also recoverable with any of `escrow_keys`, as managed policy may
+/// require of every export. Each key adds to the number of codes.
+pub fn export_qr_with_escrow(
+    backend: &dyn KeystoreOperations,
+    services: &[String],
+    passphrase: &str,
+    escrow_keys: &[EscrowPublicKey],
+) -> Result<Vec<String>, KeystoreError> {
+    let filter = ExportFilter {
+        include: services.to_vec(),
+        exclude: Vec::new(),
+    };
+    let blob = export_backup_with_escrow(backend, passphrase, &filter, escrow_keys)?;
+    let total = blob.len().div_ceil(QR_CHUNK_SIZE);
+    if total > MAX_QR_CODES {
+        return Err(KeystoreError::InvalidInput(format!(
+            "The export needs {} QR codes, more than {}; select fewer services",
+            total, MAX_QR_CODES
+        )));
+    }
+    let id = hex::encode(&Sha256::digest(&blob)[..4]);
+    Ok(blob
+        .chunks(QR_CHUNK_SIZE)
+        .enumerate()
+        .map(|(index, chunk)| {
+            format!(
+                "{}:{}/{}:{}:{}",
+                PREFIX,
+                index + 1,
+                total,
+                id,
+                URL_SAFE_NO_PAD.encode(chunk)
+            )
+        })
+        .collect())
+}
+
+/// Collects scanned payloads of one export, in any order and with repeats
+#[derive(Debug, Default)]
+pub struct QrAssembler {
+    id: Option<String>,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl QrAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a scanned payload, returning how many distinct codes of how many have been seen. A
+    /// code from another export is refused, so a stray scan can't corrupt this one.
+    pub fn add(&mut self, payload: &str) -> Result<(usize, usize), KeystoreError> {
+        let mut fields = payload.trim().splitn(4, ':');
+        if fields.next() != Some(PREFIX) {
+            return Err(invalid("not a keystore export code"));
+        }
+        let position = fields.next().ok_or_else(|| invalid("missing position"))?;
+        let (index, total) = position
+            .split_once('/')
+            .and_then(|(index, total)| Some((index.parse().ok()?, total.parse().ok()?)))
+            .filter(|&(index, total): &(usize, usize)| {
+                index >= 1 && index <= total && total <= MAX_QR_CODES
+            })
+            .ok_or_else(|| invalid("bad position"))?;
+        let id = fields.next().ok_or_else(|| invalid("missing export id"))?;
+        let chunk = URL_SAFE_NO_PAD
+            .decode(fields.next().ok_or_else(|| invalid("missing data"))?)
+            .map_err(|_| invalid("bad data"))?;
+
+        match &self.id {
+            Some(current) if current != id || self.chunks.len() != total => {
+                return Err(KeystoreError::InvalidInput(
+                    "This code belongs to a different export".to_string(),
+                ))
+            }
+            Some(_) => {}
+            None => {
+                self.id = Some(id.to_string());
+                self.chunks = vec![None; total];
+            }
+        }
+        self.chunks[index - 1] = Some(chunk);
+        Ok((self.received(), total))
+    }
+
+    fn received(&self) -> usize {
+        self.chunks.iter().filter(|chunk| chunk.is_some()).count()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        !self.chunks.is_empty() && self.received() == self.chunks.len()
+    }
+
+    /// Decrypts the reassembled export; `AccessDenied` if the passphrase is wrong
+    pub fn finish(&self, passphrase: &str) -> Result<Vec<KeystoreEntry>, KeystoreError> {
+        if !self.is_complete() {
+            return Err(KeystoreError::InvalidInput(format!(
+                "Scanned {} of {} codes",
+                self.received(),
+                self.chunks.len()
+            )));
+        }
+        let blob: Vec<u8> = self.chunks.iter().flatten().flatten().copied().collect();
+        if self.id.as_deref() != Some(hex::encode(&Sha256::digest(&blob)[..4]).as_str()) {
+            return Err(KeystoreError::Serialization(
+                "Scanned codes don't reassemble into their export".to_string(),
+            ));
+        }
+        read_backup(&blob, passphrase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    #[test]
+    fn test_codes_reassemble_in_any_order() {
+        let backend = MemoryKeystore::default();
+        for (service, account) in [("twitch", "bot"), ("twitch", "chat"), ("obs", "ws")] {
+            backend
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    // Long enough to need several codes
+                    value: format!("{}-{}", account, "x".repeat(700)),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+
+        let codes = export_qr(&backend, &["twitch".to_string()], "correct horse").unwrap();
+        assert!(codes.len() > 1);
+
+        let mut assembler = QrAssembler::new();
+        for code in codes.iter().rev().chain(codes.first()) {
+            assembler.add(code).unwrap();
+        }
+        assert!(assembler.is_complete());
+        assert!(matches!(
+            assembler.finish("wrong"),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        let mut entries = assembler.finish("correct horse").unwrap();
+        entries.sort_by(|a, b| a.account.cmp(&b.account));
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].value.starts_with("bot-"));
+
+        let other = export_qr(&backend, &["obs".to_string()], "correct horse").unwrap();
+        assert!(assembler.add(&other[0]).is_err());
+        assert!(QrAssembler::new().add("SEKQ1:3/2:abcd:AAAA").is_err());
+    }
+}
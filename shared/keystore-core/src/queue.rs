@@ -0,0 +1,469 @@
+//! Bounded concurrency in front of a backend.
+//!
+//! A broker serving many clients can see dozens of requests at once when OBS reconnects every
+//! source. [`QueuedKeystore`] lets a fixed number of operations reach the backend at a time and
+//! makes the rest wait in arrival order. Once the queue is full, further calls fail immediately
+//! with [`KeystoreError::Overloaded`] (`ERR_BUSY`) carrying a retry-after hint, instead of piling
+//! onto a backend that is already slow. The limits can be changed while running, and the number of
+//! operations in flight and waiting is reported through [`KeystoreMetrics`].
+//!
+//! Each waiting call has a [`Priority`], taken from the calling thread. Interactive calls, such as
+//! the read answering a chat command, take the next free slot ahead of any background work like a
+//! token refresh sweep; calls of the same priority keep their arrival order. Threads doing
+//! maintenance mark themselves with [`set_thread_priority`] or run it inside [`with_priority`].
+
+use crate::error::KeystoreError;
+use crate::metrics::KeystoreMetrics;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::cell::Cell;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+
+pub const DEFAULT_CONCURRENCY: usize = 8;
+pub const DEFAULT_MAX_QUEUED: usize = 256;
+pub const DEFAULT_RETRY_AFTER: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueLimits {
+    /// Operations running on the backend at once
+    pub concurrency: usize,
+    /// Operations waiting for a slot before further ones are refused
+    pub max_queued: usize,
+    /// Suggested wait before retrying a refused call, for one full round of the queue
+    pub retry_after: Duration,
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_CONCURRENCY,
+            max_queued: DEFAULT_MAX_QUEUED,
+            retry_after: DEFAULT_RETRY_AFTER,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Priority {
+    /// Someone is waiting on the result; served first
+    #[default]
+    Interactive,
+    /// Maintenance that can wait for interactive calls
+    Background,
+}
+
+impl Priority {
+    fn lane(self) -> usize {
+        self as usize
+    }
+}
+
+thread_local! {
+    static PRIORITY: Cell<Priority> = const { Cell::new(Priority::Interactive) };
+}
+
+/// Priority of queued calls made on this thread
+pub fn current_priority() -> Priority {
+    PRIORITY.with(Cell::get)
+}
+
+/// Sets the priority of calls made on this thread from now on
+pub fn set_thread_priority(priority: Priority) {
+    PRIORITY.with(|current| current.set(priority));
+}
+
+/// Runs `f` with this thread's calls at `priority`, restoring the previous priority afterwards
+pub fn with_priority<T>(priority: Priority, f: impl FnOnce() -> T) -> T {
+    struct Restore(Priority);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            set_thread_priority(self.0);
+        }
+    }
+
+    let _restore = Restore(current_priority());
+    set_thread_priority(priority);
+    f()
+}
+
+struct State {
+    limits: QueueLimits,
+    running: usize,
+    /// Calls waiting in each priority's lane
+    waiting: [usize; 2],
+    /// Tickets hand out slots in arrival order within a lane
+    next_ticket: [u64; 2],
+    serving: [u64; 2],
+}
+
+impl State {
+    fn total_waiting(&self) -> usize {
+        self.waiting.iter().sum()
+    }
+
+    /// Whether the head of `priority`'s lane may take a slot now
+    fn slot_free_for(&self, priority: Priority) -> bool {
+        self.running < self.limits.concurrency
+            && (priority == Priority::Interactive
+                || self.waiting[Priority::Interactive.lane()] == 0)
+    }
+}
+
+pub struct QueuedKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    /// Metrics label, looked up once rather than under the queue's lock
+    backend: String,
+    state: Mutex<State>,
+    slot_freed: Condvar,
+    metrics: OnceLock<Arc<KeystoreMetrics>>,
+}
+
+/// Frees its slot when the operation finishes
+struct Slot<'a> {
+    queue: &'a QueuedKeystore,
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.queue.state.lock() {
+            state.running -= 1;
+            self.queue.report(&state);
+        }
+        self.queue.slot_freed.notify_all();
+    }
+}
+
+impl QueuedKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self::with_limits(inner, QueueLimits::default())
+    }
+
+    pub fn with_limits(inner: Box<dyn KeystoreOperations>, limits: QueueLimits) -> Self {
+        Self {
+            backend: inner.backend_info().name,
+            inner,
+            state: Mutex::new(State {
+                limits,
+                running: 0,
+                waiting: [0; 2],
+                next_ticket: [0; 2],
+                serving: [0; 2],
+            }),
+            slot_freed: Condvar::new(),
+            metrics: OnceLock::new(),
+        }
+    }
+
+    /// Reports queue depth and in-flight operations to `metrics` from now on
+    pub fn set_metrics(&self, metrics: Arc<KeystoreMetrics>) {
+        let _ = self.metrics.set(metrics);
+    }
+
+    /// Applies to calls that arrive from now on; waiting calls get slots as the new concurrency
+    /// allows. A concurrency of 0 is treated as 1.
+    pub fn set_limits(&self, limits: QueueLimits) -> Result<(), KeystoreError> {
+        self.lock_state()?.limits = QueueLimits {
+            concurrency: limits.concurrency.max(1),
+            ..limits
+        };
+        self.slot_freed.notify_all();
+        Ok(())
+    }
+
+    pub fn limits(&self) -> Result<QueueLimits, KeystoreError> {
+        Ok(self.lock_state()?.limits)
+    }
+
+    /// Operations running and waiting
+    pub fn depth(&self) -> Result<(usize, usize), KeystoreError> {
+        let state = self.lock_state()?;
+        Ok((state.running, state.total_waiting()))
+    }
+
+    fn lock_state(&self) -> Result<MutexGuard<'_, State>, KeystoreError> {
+        self.state
+            .lock()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))
+    }
+
+    fn report(&self, state: &State) {
+        if let Some(metrics) = self.metrics.get() {
+            metrics.record_queue(&self.backend, state.running, state.total_waiting());
+        }
+    }
+
+    fn acquire(&self) -> Result<Slot<'_>, KeystoreError> {
+        let priority = current_priority();
+        let lane = priority.lane();
+        let mut state = self.lock_state()?;
+        if state.waiting[lane] == 0 && state.slot_free_for(priority) {
+            state.running += 1;
+            self.report(&state);
+            return Ok(Slot { queue: self });
+        }
+        let waiting = state.total_waiting();
+        if waiting >= state.limits.max_queued {
+            if let Some(metrics) = self.metrics.get() {
+                metrics.record_queue_rejection(&self.backend);
+            }
+            // Longer queues relative to the concurrency take proportionally longer to drain
+            let rounds = waiting / state.limits.concurrency + 1;
+            return Err(KeystoreError::Overloaded {
+                queued: waiting,
+                retry_after: state.limits.retry_after * rounds as u32,
+            });
+        }
+        let ticket = state.next_ticket[lane];
+        state.next_ticket[lane] += 1;
+        state.waiting[lane] += 1;
+        self.report(&state);
+        while state.serving[lane] != ticket || !state.slot_free_for(priority) {
+            state = self
+                .slot_freed
+                .wait(state)
+                .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))?;
+        }
+        state.serving[lane] += 1;
+        state.waiting[lane] -= 1;
+        state.running += 1;
+        self.report(&state);
+        drop(state);
+        // The next ticket, or background work once no interactive call waits, may start as well
+        self.slot_freed.notify_all();
+        Ok(Slot { queue: self })
+    }
+
+    fn run<T>(&self, op: impl FnOnce() -> Result<T, KeystoreError>) -> Result<T, KeystoreError> {
+        let _slot = self.acquire()?;
+        op()
+    }
+}
+
+impl KeystoreOperations for QueuedKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.run(|| self.inner.set_password(entry))
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.run(|| self.inner.get_password(service, account))
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.run(|| self.inner.delete_password(service, account))
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.run(|| self.inner.list_keys())
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.run(|| self.inner.list_credentials(service))
+    }
+
+    /// Takes one slot for the whole batch
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        self.run(|| self.inner.get_many(keys))
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.run(|| self.inner.set_many(entries))
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.run(|| self.inner.delete_many(keys))
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.run(|| self.inner.delete_service(service))
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.run(|| self.inner.wipe_all())
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.run(|| self.inner.swap(a, b))
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.run(|| self.inner.get_metadata(service, account))
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.run(|| self.inner.has_password(service, account))
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        self.run(|| self.inner.get_or_create(service, account, length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Blocks every read until the test lets it through, recording the accounts read in order
+    struct Gated {
+        inner: MemoryKeystore,
+        release: Mutex<mpsc::Receiver<()>>,
+        reads: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl KeystoreOperations for Gated {
+        fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+            self.inner.set_password(entry)
+        }
+
+        fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+            self.release.lock().unwrap().recv().unwrap();
+            self.reads.lock().unwrap().push(account.to_string());
+            self.inner.get_password(service, account)
+        }
+
+        fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+            self.inner.delete_password(service, account)
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn backend_info(&self) -> BackendInfo {
+            BackendInfo::new("gated")
+        }
+    }
+
+    fn gated(accounts: &[&str]) -> (Gated, mpsc::Sender<()>, Arc<Mutex<Vec<String>>>) {
+        let (release, gate) = mpsc::channel();
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let backend = Gated {
+            inner: MemoryKeystore::default(),
+            release: Mutex::new(gate),
+            reads: Arc::clone(&reads),
+        };
+        for account in accounts {
+            backend
+                .set_password(&KeystoreEntry {
+                    service: "obs".to_string(),
+                    account: account.to_string(),
+                    value: "pw".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        (backend, release, reads)
+    }
+
+    #[test]
+    fn test_full_queue_refuses_with_retry_after() {
+        let (backend, release, _) = gated(&["ws"]);
+        let metrics = Arc::new(KeystoreMetrics::new().unwrap());
+        let queue = Arc::new(QueuedKeystore::with_limits(
+            Box::new(backend),
+            QueueLimits {
+                concurrency: 1,
+                max_queued: 2,
+                retry_after: Duration::from_millis(40),
+            },
+        ));
+        queue.set_metrics(Arc::clone(&metrics));
+
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || queue.get_password("obs", "ws"))
+            })
+            .collect();
+        while queue.depth().unwrap() != (1, 2) {
+            thread::yield_now();
+        }
+
+        match queue.get_password("obs", "ws") {
+            Err(KeystoreError::Overloaded {
+                queued,
+                retry_after,
+            }) => {
+                assert_eq!(queued, 2);
+                assert_eq!(retry_after, Duration::from_millis(120));
+            }
+            other => panic!("Expected Overloaded, got {:?}", other),
+        }
+        let text = metrics.render().unwrap();
+        assert!(text.contains(r#"keystore_queue_depth{backend="gated"} 2"#));
+        assert!(text.contains(r#"keystore_queue_rejected_total{backend="gated"} 1"#));
+
+        for _ in 0..3 {
+            release.send(()).unwrap();
+        }
+        for reader in readers {
+            assert_eq!(reader.join().unwrap().unwrap(), "pw");
+        }
+        assert_eq!(queue.depth().unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn test_interactive_calls_overtake_background_work() {
+        let (backend, release, reads) = gated(&["chat", "sweep-1", "sweep-2", "startup"]);
+        let queue = Arc::new(QueuedKeystore::with_limits(
+            Box::new(backend),
+            QueueLimits {
+                concurrency: 1,
+                ..QueueLimits::default()
+            },
+        ));
+
+        let mut readers = Vec::new();
+        for (waiting, (account, priority)) in [
+            ("startup", Priority::Interactive),
+            ("sweep-1", Priority::Background),
+            ("sweep-2", Priority::Background),
+            ("chat", Priority::Interactive),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let reader = Arc::clone(&queue);
+            readers.push(thread::spawn(move || {
+                with_priority(priority, || reader.get_password("obs", account))
+            }));
+            while queue.depth().unwrap() != (1, waiting) {
+                thread::yield_now();
+            }
+        }
+
+        for _ in 0..4 {
+            release.send(()).unwrap();
+        }
+        for reader in readers {
+            reader.join().unwrap().unwrap();
+        }
+        assert_eq!(
+            *reads.lock().unwrap(),
+            ["startup", "chat", "sweep-1", "sweep-2"]
+        );
+        assert_eq!(current_priority(), Priority::Interactive);
+    }
+}
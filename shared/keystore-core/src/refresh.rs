@@ -0,0 +1,157 @@
+//! Named locks that serialize token refreshes across processes.
+//!
+//! When the daemon and the CLI both notice an expired OAuth token, both refresh it, and a provider
+//! that rotates refresh tokens (Twitch does) invalidates whichever new token was issued first.
+//! [`with_refresh_lock`] runs its callback holding an OS file lock named after the entry, so one
+//! refresher runs at a time on the machine; the next one to get the lock should re-read the entry
+//! and only refresh if it's still expired. Locks are advisory: they only exclude other callers of
+//! this module, in any process of the same user.
+//!
+//! [`crate::platform::get_or_create_by_writes`] takes the same lock while it creates a missing
+//! entry, so creating and refreshing an entry don't overlap either.
+
+use crate::error::KeystoreError;
+
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long [`with_refresh_lock`] waits for another refresher before failing with `Busy`
+pub const REFRESH_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Delay between attempts while another process holds the lock
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Held for the refresh of one entry; the lock is released on drop, or by the OS if the process
+/// dies while holding it
+pub struct RefreshLock {
+    file: File,
+}
+
+impl RefreshLock {
+    /// Waits up to `timeout` for the refresh lock of `service`/`account`
+    pub fn acquire(service: &str, account: &str, timeout: Duration) -> Result<Self, KeystoreError> {
+        Self::acquire_in(&lock_dir(), service, account, timeout)
+    }
+
+    fn acquire_in(
+        dir: &Path,
+        service: &str,
+        account: &str,
+        timeout: Duration,
+    ) -> Result<Self, KeystoreError> {
+        fs::create_dir_all(dir)?;
+        // Never removed: deleting a lock file another process has open would let a third lock a
+        // new file of the same name
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(lock_file_name(service, account)))?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { file }),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    thread::sleep(POLL_INTERVAL)
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(KeystoreError::Busy(format!(
+                        "Another process is refreshing {}:{}",
+                        service, account
+                    )))
+                }
+                Err(TryLockError::Error(e)) => return Err(KeystoreError::Io(e)),
+            }
+        }
+    }
+}
+
+impl Drop for RefreshLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Runs `refresh` while no other process refreshes `service`/`account`, waiting up to
+/// [`REFRESH_LOCK_TIMEOUT`] for one that is. The lock is released when `refresh` returns.
+pub fn with_refresh_lock<T>(
+    service: &str,
+    account: &str,
+    refresh: impl FnOnce() -> Result<T, KeystoreError>,
+) -> Result<T, KeystoreError> {
+    let _lock = RefreshLock::acquire(service, account, REFRESH_LOCK_TIMEOUT)?;
+    refresh()
+}
+
+/// Hashed, so names stay valid on every filesystem and don't reveal which accounts exist
+fn lock_file_name(service: &str, account: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(service.as_bytes())
+        .chain_update([0])
+        .chain_update(account.as_bytes())
+        .finalize();
+    format!("refresh-{}.lock", hex::encode(&digest[..16]))
+}
+
+fn lock_dir() -> PathBuf {
+    let base = if cfg!(target_os = "windows") {
+        let appdata = std::env::var("LOCALAPPDATA").unwrap_or_else(|_| ".".to_string());
+        PathBuf::from(appdata).join("streaming-enhancement")
+    } else if cfg!(target_os = "macos") {
+        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        PathBuf::from(home).join("Library/Application Support/streaming-enhancement")
+    } else {
+        let config = std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| {
+            let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+            format!("{}/.config", home)
+        });
+        PathBuf::from(config).join("streaming-enhancement")
+    };
+    base.join("locks")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_one_refresher_at_a_time() {
+        let temp_dir = TempDir::new().unwrap();
+        let running = Arc::new(AtomicUsize::new(0));
+        let refreshes = Arc::new(AtomicUsize::new(0));
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let _lock = RefreshLock::acquire_in(
+                        temp_dir.path(),
+                        "twitch",
+                        "bot",
+                        Duration::from_secs(10),
+                    )
+                    .unwrap();
+                    assert_eq!(running.fetch_add(1, Ordering::SeqCst), 0);
+                    thread::sleep(Duration::from_millis(20));
+                    refreshes.fetch_add(1, Ordering::SeqCst);
+                    running.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert_eq!(refreshes.load(Ordering::SeqCst), 4);
+
+        // Other entries have their own lock; a held one times out as Busy
+        let held = RefreshLock::acquire_in(temp_dir.path(), "twitch", "bot", Duration::ZERO);
+        let other = RefreshLock::acquire_in(temp_dir.path(), "twitch", "chat", Duration::ZERO);
+        assert!(held.is_ok() && other.is_ok());
+        assert!(matches!(
+            RefreshLock::acquire_in(temp_dir.path(), "twitch", "bot", Duration::ZERO),
+            Err(KeystoreError::Busy(_))
+        ));
+    }
+}
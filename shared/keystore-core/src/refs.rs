@@ -0,0 +1,412 @@
+//! `keystore://<service>/<account>` references in configuration.
+//!
+//! Integration configs and !command definitions hold references instead of plaintext secrets;
+//! [`resolve_refs`] substitutes the stored values just before use. Every reference is checked
+//! against a [`RefPolicy`] first, so a config can only reach the secrets its owner allowed.
+//!
+//! [`interpolate_template`] is the stricter mode for the sandboxed !commands executor: only
+//! `{{keystore://...}}` placeholders are substituted, only from an explicit whitelist, and every
+//! substitution produces an [`AuditEvent`].
+
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const REF_SCHEME: &str = "keystore://";
+
+/// A parsed `keystore://service/account` reference
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SecretRef {
+    pub service: String,
+    pub account: String,
+}
+
+impl SecretRef {
+    /// Parses a string consisting of exactly one reference
+    pub fn parse(uri: &str) -> Result<Self, KeystoreError> {
+        match segments(uri)?.as_slice() {
+            [Segment::Ref(secret)] => Ok(secret.clone()),
+            _ => Err(KeystoreError::InvalidInput(format!(
+                "'{}' is not a keystore:// reference",
+                uri
+            ))),
+        }
+    }
+
+    pub fn to_uri(&self) -> String {
+        format!("{}{}/{}", REF_SCHEME, self.service, self.account)
+    }
+}
+
+/// Which references may be resolved
+#[derive(Debug, Clone, Default)]
+pub struct RefPolicy {
+    /// `service/account` or `service/*` patterns; `None` allows everything
+    allowed: Option<Vec<String>>,
+}
+
+impl RefPolicy {
+    pub fn allow_all() -> Self {
+        Self { allowed: None }
+    }
+
+    /// Only references matching one of `patterns` (`service/account` or `service/*`)
+    pub fn allow(patterns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            allowed: Some(patterns.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    pub fn permits(&self, secret: &SecretRef) -> bool {
+        let Some(patterns) = &self.allowed else {
+            return true;
+        };
+        patterns
+            .iter()
+            .any(|pattern| match pattern.split_once('/') {
+                Some((service, "*")) => service == secret.service,
+                Some((service, account)) => service == secret.service && account == secret.account,
+                None => false,
+            })
+    }
+}
+
+fn is_ref_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '@')
+}
+
+/// Splits `text` into literal text and references, in order
+pub(crate) enum Segment<'t> {
+    Text(&'t str),
+    Ref(SecretRef),
+}
+
+pub(crate) fn segments(text: &str) -> Result<Vec<Segment<'_>>, KeystoreError> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find(REF_SCHEME) {
+        if start > 0 {
+            segments.push(Segment::Text(&rest[..start]));
+        }
+        let body = &rest[start + REF_SCHEME.len()..];
+        let service_len = body.find(|c| !is_ref_char(c)).unwrap_or(body.len());
+        let after_service = &body[service_len..];
+        // Trailing punctuation belongs to the surrounding sentence, not the account
+        let account = after_service
+            .strip_prefix('/')
+            .map(|account| {
+                let len = account.find(|c| !is_ref_char(c)).unwrap_or(account.len());
+                account[..len].trim_end_matches(['.', ':'])
+            })
+            .unwrap_or("");
+        if service_len == 0 || account.is_empty() {
+            let snippet: String = rest[start..].chars().take(40).collect();
+            return Err(KeystoreError::InvalidInput(format!(
+                "Malformed reference '{}'; expected keystore://<service>/<account>",
+                snippet
+            )));
+        }
+
+        let len = REF_SCHEME.len() + service_len + 1 + account.len();
+        segments.push(Segment::Ref(SecretRef {
+            service: body[..service_len].to_string(),
+            account: account.to_string(),
+        }));
+        rest = &rest[start + len..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+
+    Ok(segments)
+}
+
+/// All references in `text`, in order of appearance
+pub fn find_refs(text: &str) -> Result<Vec<SecretRef>, KeystoreError> {
+    Ok(segments(text)?
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Ref(secret) => Some(secret),
+            Segment::Text(_) => None,
+        })
+        .collect())
+}
+
+struct Resolver<'a> {
+    backend: &'a dyn KeystoreOperations,
+    policy: &'a RefPolicy,
+    cache: HashMap<SecretRef, String>,
+}
+
+impl Resolver<'_> {
+    fn lookup(&mut self, secret: &SecretRef) -> Result<String, KeystoreError> {
+        if !self.policy.permits(secret) {
+            return Err(KeystoreError::AccessDenied(format!(
+                "{} is not allowed by the resolution policy",
+                secret.to_uri()
+            )));
+        }
+        if let Some(value) = self.cache.get(secret) {
+            return Ok(value.clone());
+        }
+        let value = self
+            .backend
+            .get_password(&secret.service, &secret.account)?;
+        self.cache.insert(secret.clone(), value.clone());
+        Ok(value)
+    }
+
+    fn substitute(&mut self, text: &str) -> Result<String, KeystoreError> {
+        let mut out = String::with_capacity(text.len());
+        for segment in segments(text)? {
+            match segment {
+                Segment::Text(literal) => out.push_str(literal),
+                Segment::Ref(secret) => out.push_str(&self.lookup(&secret)?),
+            }
+        }
+        Ok(out)
+    }
+
+    fn substitute_json(&mut self, value: &mut serde_json::Value) -> Result<(), KeystoreError> {
+        match value {
+            serde_json::Value::String(text) => *text = self.substitute(text)?,
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.substitute_json(item)?;
+                }
+            }
+            serde_json::Value::Object(fields) => {
+                for field in fields.values_mut() {
+                    self.substitute_json(field)?;
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Replaces every reference in `input` with its stored value. When `input` is a JSON document the
+/// substitution happens inside string values, so secrets containing quotes or backslashes stay
+/// correctly escaped; anything else is treated as plain text. Fails without partial output if a
+/// reference is malformed, denied by `policy` or missing.
+pub fn resolve_refs(
+    backend: &dyn KeystoreOperations,
+    input: &str,
+    policy: &RefPolicy,
+) -> Result<String, KeystoreError> {
+    let mut resolver = Resolver {
+        backend,
+        policy,
+        cache: HashMap::new(),
+    };
+
+    match serde_json::from_str::<serde_json::Value>(input) {
+        Ok(mut json) if json.is_object() || json.is_array() => {
+            resolver.substitute_json(&mut json)?;
+            serde_json::to_string(&json).map_err(|e| KeystoreError::Serialization(e.to_string()))
+        }
+        _ => resolver.substitute(input),
+    }
+}
+
+/// One substitution made by [`interpolate_template`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditEvent {
+    pub reference: String,
+    /// Byte offset of the placeholder in the template
+    pub offset: usize,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Interpolation {
+    pub output: String,
+    pub audit: Vec<AuditEvent>,
+}
+
+/// Replaces `{{keystore://service/account}}` placeholders in `template`. Only references listed in
+/// `allowed` (as `keystore://` URIs) are resolved; anything else, including a malformed or
+/// unterminated placeholder, fails the whole call before any value is read.
+pub fn interpolate_template(
+    backend: &dyn KeystoreOperations,
+    template: &str,
+    allowed: &[String],
+) -> Result<Interpolation, KeystoreError> {
+    let allowed = allowed
+        .iter()
+        .map(|uri| SecretRef::parse(uri))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Parse and check every placeholder before reading any secret
+    let mut pieces = Vec::new();
+    let mut rest = template;
+    let mut offset = 0;
+    while let Some(open) = rest.find("{{") {
+        let close = rest[open..].find("}}").ok_or_else(|| {
+            KeystoreError::InvalidInput(format!(
+                "Unterminated placeholder at byte {}",
+                offset + open
+            ))
+        })? + open;
+        let secret = SecretRef::parse(rest[open + 2..close].trim())?;
+        if !allowed.contains(&secret) {
+            return Err(KeystoreError::AccessDenied(format!(
+                "{} is not whitelisted for this template",
+                secret.to_uri()
+            )));
+        }
+        pieces.push((&rest[..open], Some((secret, offset + open))));
+        offset += close + 2;
+        rest = &rest[close + 2..];
+    }
+    pieces.push((rest, None));
+
+    let policy = RefPolicy::allow_all();
+    let mut resolver = Resolver {
+        backend,
+        policy: &policy,
+        cache: HashMap::new(),
+    };
+    let mut output = String::with_capacity(template.len());
+    let mut audit = Vec::new();
+    for (literal, placeholder) in pieces {
+        output.push_str(literal);
+        if let Some((secret, offset)) = placeholder {
+            output.push_str(&resolver.lookup(&secret)?);
+            audit.push(AuditEvent {
+                reference: secret.to_uri(),
+                offset,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0),
+            });
+        }
+    }
+
+    Ok(Interpolation { output, audit })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use crate::KeystoreEntry;
+
+    fn backend() -> MemoryKeystore {
+        let backend = MemoryKeystore::default();
+        for (service, account, value) in [
+            ("twitch", "bot-token", "oauth:abc123"),
+            ("obs", "websocket", "pa\"ss\\word"),
+        ] {
+            backend
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    value: value.to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        backend
+    }
+
+    #[test]
+    fn test_find_refs() {
+        let refs =
+            find_refs("token=keystore://twitch/bot-token; ws=keystore://obs/websocket.").unwrap();
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs[0].to_uri(), "keystore://twitch/bot-token");
+        assert_eq!(refs[1].account, "websocket");
+
+        assert!(matches!(
+            find_refs("keystore://twitch"),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_text() {
+        let backend = backend();
+        let resolved = resolve_refs(
+            &backend,
+            "PASS keystore://twitch/bot-token",
+            &RefPolicy::allow_all(),
+        )
+        .unwrap();
+        assert_eq!(resolved, "PASS oauth:abc123");
+    }
+
+    #[test]
+    fn test_resolve_json_keeps_escaping() {
+        let backend = backend();
+        let config = r#"{"obs":{"password":"keystore://obs/websocket","port":4455}}"#;
+
+        let resolved = resolve_refs(&backend, config, &RefPolicy::allow(["obs/*"])).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&resolved).unwrap();
+        assert_eq!(json["obs"]["password"], "pa\"ss\\word");
+        assert_eq!(json["obs"]["port"], 4455);
+    }
+
+    #[test]
+    fn test_policy_and_missing_refs() {
+        let backend = backend();
+        let policy = RefPolicy::allow(["obs/websocket"]);
+
+        assert!(matches!(
+            resolve_refs(&backend, "keystore://twitch/bot-token", &policy),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        assert!(matches!(
+            resolve_refs(&backend, "keystore://obs/missing", &RefPolicy::allow_all()),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_interpolate_template_audits_each_substitution() {
+        let backend = backend();
+        let allowed = vec!["keystore://twitch/bot-token".to_string()];
+
+        let result = interpolate_template(
+            &backend,
+            "!so {{ keystore://twitch/bot-token }} and again {{keystore://twitch/bot-token}}",
+            &allowed,
+        )
+        .unwrap();
+
+        assert_eq!(result.output, "!so oauth:abc123 and again oauth:abc123");
+        assert_eq!(result.audit.len(), 2);
+        assert_eq!(result.audit[0].reference, "keystore://twitch/bot-token");
+        assert_eq!(result.audit[0].offset, 4);
+        assert_eq!(result.audit[1].offset, 48);
+    }
+
+    #[test]
+    fn test_interpolate_template_refuses_unlisted_refs() {
+        let backend = backend();
+        let allowed = vec!["keystore://twitch/bot-token".to_string()];
+
+        assert!(matches!(
+            interpolate_template(&backend, "{{keystore://obs/websocket}}", &allowed),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        assert!(matches!(
+            interpolate_template(&backend, "{{keystore://twitch/bot-token", &allowed),
+            Err(KeystoreError::InvalidInput(_))
+        ));
+        // Bare references outside placeholders are left alone
+        assert_eq!(
+            interpolate_template(&backend, "see keystore://obs/websocket", &allowed)
+                .unwrap()
+                .output,
+            "see keystore://obs/websocket"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! Services the keystore keeps its own state in.
+//!
+//! Aliases, protection marks, the lock's passphrase verifier, note and certificate indexes and the
+//! rest live in ordinary entries under `streaming-enhancement:*` services. Deleting
+//! `streaming-enhancement:protected` would drop every pin, and deleting the lock verifier would let
+//! the next unlock enrol any passphrase, so callers must not reach them directly.
+//! [`PublicKeystore`] is the keystore as callers see it: reads, writes and deletes of a reserved
+//! service fail with `AccessDenied`, listings leave them out, and `wipe_all` clears everything
+//! else. The features that own the state go around it.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+/// Prefix shared by the services the keystore manages itself
+pub const RESERVED_PREFIX: &str = "streaming-enhancement:";
+
+pub fn is_reserved(service: &str) -> bool {
+    service.starts_with(RESERVED_PREFIX)
+}
+
+/// `AccessDenied` if `service` is reserved
+pub fn check_not_reserved(service: &str) -> Result<(), KeystoreError> {
+    if is_reserved(service) {
+        return Err(KeystoreError::AccessDenied(format!(
+            "Service {} is reserved for the keystore's own state",
+            service
+        )));
+    }
+    Ok(())
+}
+
+pub struct PublicKeystore {
+    inner: Box<dyn KeystoreOperations>,
+}
+
+impl PublicKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self { inner }
+    }
+}
+
+impl KeystoreOperations for PublicKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        check_not_reserved(&entry.service)?;
+        self.inner.set_password(entry)
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        check_not_reserved(service)?;
+        self.inner.get_password(service, account)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        check_not_reserved(service)?;
+        self.inner.delete_password(service, account)
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut keys = self.inner.list_keys()?;
+        keys.retain(|key| !is_reserved(&key.service));
+        Ok(keys)
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        check_not_reserved(service)?;
+        self.inner.list_credentials(service)
+    }
+
+    /// Refuses the whole batch if any key is reserved
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        for key in keys {
+            check_not_reserved(&key.service)?;
+        }
+        self.inner.get_many(keys)
+    }
+
+    /// Refuses the whole batch if any entry is reserved
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        for entry in entries {
+            check_not_reserved(&entry.service)?;
+        }
+        self.inner.set_many(entries)
+    }
+
+    /// Refuses the whole batch if any key is reserved
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        for key in keys {
+            check_not_reserved(&key.service)?;
+        }
+        self.inner.delete_many(keys)
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        check_not_reserved(service)?;
+        self.inner.delete_service(service)
+    }
+
+    /// Deletes every entry outside the reserved services. Clearing those too takes a wipe of the
+    /// keystore underneath, e.g. after [`crate::protect::ProtectedKeystore::authorize_wipe`].
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let keys = self.list_keys()?;
+        self.inner.delete_many(&keys)
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        check_not_reserved(&a.service)?;
+        check_not_reserved(&b.service)?;
+        self.inner.swap(a, b)
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        check_not_reserved(service)?;
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        check_not_reserved(service)?;
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        check_not_reserved(service)?;
+        self.inner.get_or_create(service, account, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protect::{ProtectedKeystore, PROTECTED_SERVICE};
+    use crate::testing::MemoryKeystore;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_reserved_services_are_out_of_reach() {
+        let protected = Arc::new(ProtectedKeystore::new(Box::new(MemoryKeystore::default())));
+        let keystore = PublicKeystore::new(Box::new(Arc::clone(&protected)));
+        for account in ["stream-key", "backup-key"] {
+            keystore
+                .set_password(&KeystoreEntry {
+                    service: "obs".to_string(),
+                    account: account.to_string(),
+                    value: "live_123".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let stream_key = KeystoreKey {
+            service: "obs".to_string(),
+            account: "stream-key".to_string(),
+        };
+        protected.make_write_once(&stream_key).unwrap();
+
+        for result in [
+            keystore.delete_password(PROTECTED_SERVICE, "write-once"),
+            keystore
+                .get_password(PROTECTED_SERVICE, "write-once")
+                .map(drop),
+            keystore.delete_service(PROTECTED_SERVICE).map(drop),
+        ] {
+            assert!(matches!(result, Err(KeystoreError::AccessDenied(_))));
+        }
+        assert!(keystore
+            .list_keys()
+            .unwrap()
+            .iter()
+            .all(|key| key.service == "obs"));
+
+        assert_eq!(keystore.wipe_all().unwrap().len(), 2);
+        protected.reload();
+        assert!(protected.is_write_once(&stream_key).unwrap());
+    }
+
+    #[test]
+    fn test_backup_imports_skip_reserved_services() {
+        use crate::backup::{export_backup, read_backup, ExportFilter};
+        use crate::import::apply_import;
+
+        let source = MemoryKeystore::default();
+        for (service, account) in [("obs", "stream-key"), (PROTECTED_SERVICE, "index")] {
+            source
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    value: "[]".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let backup = export_backup(&source, "correct horse", &ExportFilter::default()).unwrap();
+        let entries = read_backup(&backup, "correct horse").unwrap();
+
+        let target = Arc::new(MemoryKeystore::default());
+        let keystore = PublicKeystore::new(Box::new(Arc::clone(&target)));
+        let diff = apply_import(&keystore, &entries, true).unwrap();
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.conflicts.len(), 1);
+        assert_eq!(diff.conflicts[0].key.service, PROTECTED_SERVICE);
+        assert!(matches!(
+            target.get_password(PROTECTED_SERVICE, "index"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+    }
+}
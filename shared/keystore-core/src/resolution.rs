@@ -0,0 +1,295 @@
+//! Where a read is answered from.
+//!
+//! A read passes the layers of the stack in a fixed order and the first that holds the value
+//! answers it:
+//!
+//! 1. ephemeral entries ([`crate::ephemeral`]), for `ephemeral:` services only
+//! 2. the hot cache of leased secrets ([`crate::hot`])
+//! 3. the value cache of the lock ([`crate::lock`])
+//! 4. writes queued behind a locked keyring ([`crate::unlock_wait`])
+//! 5. systemd credentials, on Linux ([`crate::platform::systemd`])
+//! 6. the backend: the platform keystore, or the encrypted-file fallback when it was unavailable
+//!    at startup
+//!
+//! Stacks built without some of these layers skip those steps. Each layer that answers notes
+//! itself on the calling thread, the way [`crate::access`] keeps the caller's context, and
+//! [`resolve`] returns the value with the [`ReadSource`] that answered it. A [`ReadPolicy`] set
+//! with [`with_read_policy`] changes the order for reads on the thread, e.g. to go past the caches
+//! for a value that must be current.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::cell::Cell;
+use std::sync::OnceLock;
+
+/// The step of the resolution order that answered a read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadSource {
+    Ephemeral,
+    HotCache,
+    LockCache,
+    /// A write still queued behind a locked keyring
+    PendingWrite,
+    SystemdCredential,
+    /// The platform keystore
+    Native,
+    /// The encrypted-file backend
+    Fallback,
+}
+
+impl ReadSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReadSource::Ephemeral => "ephemeral",
+            ReadSource::HotCache => "hot-cache",
+            ReadSource::LockCache => "lock-cache",
+            ReadSource::PendingWrite => "pending-write",
+            ReadSource::SystemdCredential => "systemd-credential",
+            ReadSource::Native => "native",
+            ReadSource::Fallback => "fallback",
+        }
+    }
+}
+
+/// How reads on this thread are resolved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadPolicy {
+    /// Skips the hot and lock caches; the value read still refreshes them
+    pub bypass_caches: bool,
+}
+
+thread_local! {
+    static POLICY: Cell<ReadPolicy> = const { Cell::new(ReadPolicy { bypass_caches: false }) };
+    static SOURCE: Cell<Option<ReadSource>> = const { Cell::new(None) };
+}
+
+/// Policy of reads made on this thread
+pub fn current_read_policy() -> ReadPolicy {
+    POLICY.with(Cell::get)
+}
+
+/// Sets the policy of reads made on this thread from now on
+pub fn set_read_policy(policy: ReadPolicy) {
+    POLICY.with(|current| current.set(policy));
+}
+
+/// Runs `f` with this thread's reads resolved under `policy`, restoring the previous policy
+/// afterwards
+pub fn with_read_policy<T>(policy: ReadPolicy, f: impl FnOnce() -> T) -> T {
+    struct Restore(ReadPolicy);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            set_read_policy(self.0);
+        }
+    }
+
+    let _restore = Restore(current_read_policy());
+    set_read_policy(policy);
+    f()
+}
+
+/// Whether the caches should be skipped by reads on this thread
+pub(crate) fn caches_bypassed() -> bool {
+    current_read_policy().bypass_caches
+}
+
+/// Notes that `source` answered the read in progress on this thread. The layer that answers
+/// calls this last, so it overrides anything noted by reads made along the way, such as an alias
+/// table lookup.
+pub(crate) fn record_source(source: ReadSource) {
+    SOURCE.with(|current| current.set(Some(source)));
+}
+
+/// A value and where it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub value: String,
+    pub source: ReadSource,
+}
+
+/// Reads `service`/`account` through `keystore` and reports which step answered. A backend not
+/// wrapped in [`BackendSourceKeystore`] is told apart by its name.
+pub fn resolve(
+    keystore: &dyn KeystoreOperations,
+    service: &str,
+    account: &str,
+) -> Result<Resolved, KeystoreError> {
+    SOURCE.with(|current| current.set(None));
+    let value = keystore.get_password(service, account)?;
+    let source = SOURCE
+        .with(Cell::take)
+        .unwrap_or_else(|| backend_source(&keystore.backend_info()));
+    Ok(Resolved { value, source })
+}
+
+fn backend_source(info: &BackendInfo) -> ReadSource {
+    if info.name == "encrypted-file" {
+        ReadSource::Fallback
+    } else {
+        ReadSource::Native
+    }
+}
+
+/// Marks reads that reach `inner` as answered by the backend. [`crate::platform::backend_for`]
+/// puts it directly on the backend it picked.
+pub struct BackendSourceKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    source: OnceLock<ReadSource>,
+}
+
+impl BackendSourceKeystore {
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self {
+            inner,
+            source: OnceLock::new(),
+        }
+    }
+}
+
+impl KeystoreOperations for BackendSourceKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.inner.set_password(entry)
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        let value = self.inner.get_password(service, account)?;
+        record_source(
+            *self
+                .source
+                .get_or_init(|| backend_source(&self.inner.backend_info())),
+        );
+        Ok(value)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.inner.delete_password(service, account)
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.list_keys()
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.list_credentials(service)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        self.inner.get_many(keys)
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.inner.set_many(entries)
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.delete_many(keys)
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.inner.delete_service(service)
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.inner.wipe_all()
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.inner.swap(a, b)
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        let value = self.inner.get_or_create(service, account, length)?;
+        record_source(
+            *self
+                .source
+                .get_or_init(|| backend_source(&self.inner.backend_info())),
+        );
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ephemeral::EphemeralKeystore;
+    use crate::hot::{HotCache, HotCacheKeystore};
+    use crate::lock::LockingKeystore;
+    use crate::testing::MemoryKeystore;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_each_read_reports_the_step_that_answered_it() {
+        let locking = Arc::new(LockingKeystore::new(Box::new(BackendSourceKeystore::new(
+            Box::new(MemoryKeystore::default()),
+        ))));
+        let hot = Arc::new(HotCache::new());
+        let keystore = EphemeralKeystore::new(Box::new(HotCacheKeystore::new(
+            Box::new(Arc::clone(&locking)),
+            Arc::clone(&hot),
+        )));
+        let set = |service: &str, account: &str| {
+            keystore
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    value: "token".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        };
+        set("twitch", "bot");
+        set("twitch", "broadcaster");
+        set("ephemeral:session", "nonce");
+        hot.lease("twitch", "broadcaster").unwrap();
+
+        let source = |account: &str| resolve(&keystore, "twitch", account).unwrap().source;
+        assert_eq!(source("bot"), ReadSource::Native);
+        assert_eq!(source("bot"), ReadSource::LockCache);
+        assert_eq!(source("broadcaster"), ReadSource::Native);
+        assert_eq!(source("broadcaster"), ReadSource::HotCache);
+        assert_eq!(
+            resolve(&keystore, "ephemeral:session", "nonce")
+                .unwrap()
+                .source,
+            ReadSource::Ephemeral
+        );
+
+        let fresh = ReadPolicy {
+            bypass_caches: true,
+        };
+        assert_eq!(
+            with_read_policy(fresh, || source("broadcaster")),
+            ReadSource::Native
+        );
+        assert_eq!(current_read_policy(), ReadPolicy::default());
+        assert_eq!(source("broadcaster"), ReadSource::HotCache);
+    }
+}
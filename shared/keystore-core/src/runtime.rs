@@ -0,0 +1,284 @@
+//! Shared tokio runtime for backends that need async I/O.
+//!
+//! Network-backed backends (and async DBus clients) implement [`AsyncKeystoreOperations`] and are
+//! wrapped in a [`BlockingAdapter`], which drives each call to completion on one lazily created,
+//! process-wide runtime. Calls borrow the caller's thread for the duration of the operation; no
+//! thread is spawned per call.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::KeystoreEntry;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::runtime::{Builder, Handle, Runtime, RuntimeFlavor};
+
+const WORKER_THREADS: usize = 2;
+
+static RUNTIME: Mutex<Option<Runtime>> = Mutex::new(None);
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Async counterpart of [`KeystoreOperations`] for backends built on async I/O
+pub trait AsyncKeystoreOperations: Send + Sync {
+    fn set_password<'a>(
+        &'a self,
+        entry: &'a KeystoreEntry,
+    ) -> BoxFuture<'a, Result<(), KeystoreError>>;
+    fn get_password<'a>(
+        &'a self,
+        service: &'a str,
+        account: &'a str,
+    ) -> BoxFuture<'a, Result<String, KeystoreError>>;
+    fn delete_password<'a>(
+        &'a self,
+        service: &'a str,
+        account: &'a str,
+    ) -> BoxFuture<'a, Result<(), KeystoreError>>;
+    fn is_available(&self) -> BoxFuture<'_, bool>;
+    fn backend_info(&self) -> BackendInfo;
+}
+
+/// Returns a handle to the shared runtime, starting it on first use or after [`shutdown`]
+pub fn handle() -> Result<Handle, KeystoreError> {
+    let mut runtime = RUNTIME
+        .lock()
+        .map_err(|e| KeystoreError::Platform(format!("Failed to acquire lock: {}", e)))?;
+
+    if runtime.is_none() {
+        *runtime = Some(
+            Builder::new_multi_thread()
+                .worker_threads(WORKER_THREADS)
+                .thread_name("keystore-runtime")
+                .enable_all()
+                .build()?,
+        );
+    }
+
+    Ok(runtime.as_ref().unwrap().handle().clone())
+}
+
+/// Runs `future` to completion on the shared runtime from synchronous code.
+///
+/// When the caller is already on a multi-threaded tokio worker (napi's runtime, for example) the
+/// worker is handed off with `block_in_place` first. Calling from a current-thread runtime is an
+/// error because that runtime has no other thread to make progress on.
+pub fn block_on<F: Future>(future: F) -> Result<F::Output, KeystoreError> {
+    let handle = handle()?;
+
+    match Handle::try_current() {
+        Err(_) => Ok(handle.block_on(future)),
+        Ok(current) if current.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            Ok(tokio::task::block_in_place(|| handle.block_on(future)))
+        }
+        Ok(_) => Err(KeystoreError::Platform(
+            "Cannot block on the keystore runtime from a current-thread runtime".to_string(),
+        )),
+    }
+}
+
+/// Stops the shared runtime, waiting up to `timeout` for in-flight operations.
+///
+/// Must be called from synchronous code, typically during process or addon teardown. A later
+/// operation transparently starts a fresh runtime.
+pub fn shutdown(timeout: Duration) {
+    let runtime = match RUNTIME.lock() {
+        Ok(mut runtime) => runtime.take(),
+        Err(poisoned) => poisoned.into_inner().take(),
+    };
+
+    if let Some(runtime) = runtime {
+        runtime.shutdown_timeout(timeout);
+    }
+}
+
+/// Exposes an async backend through the synchronous [`KeystoreOperations`] trait
+pub struct BlockingAdapter<B> {
+    inner: B,
+}
+
+impl<B: AsyncKeystoreOperations> BlockingAdapter<B> {
+    pub fn new(inner: B) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+}
+
+impl<B: AsyncKeystoreOperations> KeystoreOperations for BlockingAdapter<B> {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        block_on(self.inner.set_password(entry))?
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        block_on(self.inner.get_password(service, account))?
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        block_on(self.inner.delete_password(service, account))?
+    }
+
+    fn is_available(&self) -> bool {
+        block_on(self.inner.is_available()).unwrap_or(false)
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    /// In-memory async backend that yields to the runtime on every call
+    #[derive(Default)]
+    struct MemoryAsyncKeystore {
+        entries: tokio::sync::RwLock<HashMap<(String, String), String>>,
+    }
+
+    impl AsyncKeystoreOperations for MemoryAsyncKeystore {
+        fn set_password<'a>(
+            &'a self,
+            entry: &'a KeystoreEntry,
+        ) -> BoxFuture<'a, Result<(), KeystoreError>> {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                self.entries.write().await.insert(
+                    (entry.service.clone(), entry.account.clone()),
+                    entry.value.clone(),
+                );
+                Ok(())
+            })
+        }
+
+        fn get_password<'a>(
+            &'a self,
+            service: &'a str,
+            account: &'a str,
+        ) -> BoxFuture<'a, Result<String, KeystoreError>> {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                self.entries
+                    .read()
+                    .await
+                    .get(&(service.to_string(), account.to_string()))
+                    .cloned()
+                    .ok_or_else(|| KeystoreError::KeyNotFound(format!("{}:{}", service, account)))
+            })
+        }
+
+        fn delete_password<'a>(
+            &'a self,
+            service: &'a str,
+            account: &'a str,
+        ) -> BoxFuture<'a, Result<(), KeystoreError>> {
+            Box::pin(async move {
+                self.entries
+                    .write()
+                    .await
+                    .remove(&(service.to_string(), account.to_string()))
+                    .map(|_| ())
+                    .ok_or_else(|| KeystoreError::KeyNotFound(format!("{}:{}", service, account)))
+            })
+        }
+
+        fn is_available(&self) -> BoxFuture<'_, bool> {
+            Box::pin(async { true })
+        }
+
+        fn backend_info(&self) -> BackendInfo {
+            BackendInfo::new("memory")
+        }
+    }
+
+    fn create_test_entry(service: &str, account: &str, value: &str) -> KeystoreEntry {
+        KeystoreEntry {
+            service: service.to_string(),
+            account: account.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_blocking_adapter_round_trip() {
+        let keystore = BlockingAdapter::new(MemoryAsyncKeystore::default());
+
+        assert!(keystore.is_available());
+
+        keystore
+            .set_password(&create_test_entry(
+                "async-service",
+                "async-account",
+                "secret",
+            ))
+            .unwrap();
+        assert_eq!(
+            keystore
+                .get_password("async-service", "async-account")
+                .unwrap(),
+            "secret"
+        );
+
+        keystore
+            .delete_password("async-service", "async-account")
+            .unwrap();
+        match keystore.get_password("async-service", "async-account") {
+            Err(KeystoreError::KeyNotFound(_)) => (),
+            _ => panic!("Expected KeyNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_blocking_adapter_from_many_threads() {
+        let keystore = Arc::new(BlockingAdapter::new(MemoryAsyncKeystore::default()));
+
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let keystore = Arc::clone(&keystore);
+                std::thread::spawn(move || {
+                    let account = format!("account-{}", t);
+                    keystore
+                        .set_password(&create_test_entry("threads-service", &account, "value"))
+                        .unwrap();
+                    keystore.get_password("threads-service", &account).unwrap()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), "value");
+        }
+    }
+
+    #[test]
+    fn test_block_on_inside_multi_thread_runtime() {
+        let outer = Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let value = outer.block_on(async {
+            tokio::spawn(async { block_on(async { 42 }).unwrap() })
+                .await
+                .unwrap()
+        });
+        assert_eq!(value, 42);
+    }
+
+    #[test]
+    fn test_block_on_inside_current_thread_runtime_fails() {
+        let outer = Builder::new_current_thread().build().unwrap();
+
+        let result = outer.block_on(async { block_on(async { 42 }) });
+        assert!(matches!(result, Err(KeystoreError::Platform(_))));
+    }
+}
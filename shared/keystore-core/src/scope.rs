@@ -0,0 +1,195 @@
+//! Keystores restricted to a few services, for code that should see only what it was granted.
+//!
+//! A chat `!command` running in a plugin sandbox gets a [`ScopedKeystore`] limited to the services
+//! granted to that command. Calls on any other service fail with `AccessDenied` without reaching
+//! the backend, listings leave them out, and `wipe_all` clears the granted services only. Wrap the
+//! scoped keystore in an [`crate::audit::AuditingKeystore`] made
+//! [`with_scope`](crate::audit::AuditingKeystore::with_scope) to audit calls, refused ones
+//! included, under the scope's name.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::collections::BTreeSet;
+
+pub struct ScopedKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    services: BTreeSet<String>,
+}
+
+impl ScopedKeystore {
+    pub fn new(
+        inner: Box<dyn KeystoreOperations>,
+        services: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            inner,
+            services: services.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn services(&self) -> impl Iterator<Item = &str> {
+        self.services.iter().map(String::as_str)
+    }
+
+    pub fn allows(&self, service: &str) -> bool {
+        self.services.contains(service)
+    }
+
+    fn check(&self, service: &str) -> Result<(), KeystoreError> {
+        if self.allows(service) {
+            return Ok(());
+        }
+        Err(KeystoreError::AccessDenied(format!(
+            "Service {} is outside this keystore's scope",
+            service
+        )))
+    }
+}
+
+impl KeystoreOperations for ScopedKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.check(&entry.service)?;
+        self.inner.set_password(entry)
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.check(service)?;
+        self.inner.get_password(service, account)
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.check(service)?;
+        self.inner.delete_password(service, account)
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner
+            .backend_info()
+            .with_detail("scope_services", self.services.len())
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut keys = self.inner.list_keys()?;
+        keys.retain(|key| self.allows(&key.service));
+        Ok(keys)
+    }
+
+    /// Nothing for services outside the scope, as in [`Self::list_keys`]
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        if !self.allows(service) {
+            return Ok(Vec::new());
+        }
+        self.inner.list_credentials(service)
+    }
+
+    /// Refuses the whole batch if any key is out of scope
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        for key in keys {
+            self.check(&key.service)?;
+        }
+        self.inner.get_many(keys)
+    }
+
+    /// Refuses the whole batch if any entry is out of scope
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        for entry in entries {
+            self.check(&entry.service)?;
+        }
+        self.inner.set_many(entries)
+    }
+
+    /// Refuses the whole batch if any key is out of scope
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        for key in keys {
+            self.check(&key.service)?;
+        }
+        self.inner.delete_many(keys)
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.check(service)?;
+        self.inner.delete_service(service)
+    }
+
+    /// Deletes the granted services, leaving everything else alone
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut wiped = Vec::new();
+        for service in &self.services {
+            for account in self.inner.delete_service(service)? {
+                wiped.push(KeystoreKey {
+                    service: service.clone(),
+                    account,
+                });
+            }
+        }
+        Ok(wiped)
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.check(&a.service)?;
+        self.check(&b.service)?;
+        self.inner.swap(a, b)
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.check(service)?;
+        self.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.check(service)?;
+        self.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        self.check(service)?;
+        self.inner.get_or_create(service, account, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_only_granted_services_are_visible() {
+        let backend = Arc::new(MemoryKeystore::default());
+        for service in ["twitch", "obs"] {
+            backend
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: "bot".to_string(),
+                    value: "token".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let scoped = ScopedKeystore::new(Box::new(Arc::clone(&backend)), ["twitch"]);
+
+        assert_eq!(scoped.get_password("twitch", "bot").unwrap(), "token");
+        assert!(matches!(
+            scoped.get_password("obs", "bot"),
+            Err(KeystoreError::AccessDenied(_))
+        ));
+        assert_eq!(scoped.list_keys().unwrap().len(), 1);
+        assert_eq!(scoped.wipe_all().unwrap().len(), 1);
+        assert_eq!(backend.get_password("obs", "bot").unwrap(), "token");
+    }
+}
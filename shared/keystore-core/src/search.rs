@@ -0,0 +1,148 @@
+//! Glob search over stored entries.
+//!
+//! Patterns use `*` for any run of characters and `?` for exactly one, so `search(backend,
+//! "twitch:*", "bot-*")` finds every bot token across per-channel services without naming each
+//! one. Matching is case-sensitive and runs over [`KeystoreOperations::list_keys`]; no values are
+//! read. [`find_credentials`] is the keytar-style lookup of one service with its values.
+
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+use crate::{KeystoreEntry, KeystoreKey};
+
+/// Whether `text` matches `pattern` in full
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it is currently standing in for
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` absorb one more character and retry
+                Some((star, start)) => {
+                    p = star + 1;
+                    t = start + 1;
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Entries whose service and account both match, sorted by service then account
+pub fn search(
+    backend: &dyn KeystoreOperations,
+    service_pattern: &str,
+    account_pattern: &str,
+) -> Result<Vec<KeystoreKey>, KeystoreError> {
+    let mut keys: Vec<KeystoreKey> = backend
+        .list_keys()?
+        .into_iter()
+        .filter(|key| {
+            glob_match(service_pattern, &key.service) && glob_match(account_pattern, &key.account)
+        })
+        .collect();
+    keys.sort();
+    Ok(keys)
+}
+
+/// Every entry under `service` with its value, sorted by account, as keytar's `findCredentials`
+/// returns them. Found through [`KeystoreOperations::list_credentials`] and read in one batch;
+/// entries deleted in between are left out.
+pub fn find_credentials(
+    backend: &dyn KeystoreOperations,
+    service: &str,
+) -> Result<Vec<KeystoreEntry>, KeystoreError> {
+    let keys: Vec<KeystoreKey> = backend
+        .list_credentials(service)?
+        .into_iter()
+        .map(|account| KeystoreKey {
+            service: service.to_string(),
+            account,
+        })
+        .collect();
+    let values = backend.get_many(&keys)?;
+    Ok(keys
+        .into_iter()
+        .zip(values)
+        .filter_map(|(key, value)| {
+            Some(KeystoreEntry {
+                service: key.service,
+                account: key.account,
+                value: value?,
+                ..Default::default()
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("twitch:*", "twitch:channel-42"));
+        assert!(glob_match("twitch:*", "twitch:"));
+        assert!(!glob_match("twitch:*", "twitch"));
+        assert!(glob_match("*-oauth", "bot-oauth"));
+        assert!(glob_match("bot-?", "bot-1"));
+        assert!(!glob_match("bot-?", "bot-12"));
+        assert!(glob_match("*a*b*", "xaxxbx"));
+        assert!(!glob_match("*a*b", "xaxxbx"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("Bot-*", "bot-1"));
+    }
+
+    #[test]
+    fn test_search_filters_services_and_accounts() {
+        let backend = MemoryKeystore::default();
+        for (service, account) in [
+            ("twitch:alice", "bot-oauth"),
+            ("twitch:alice", "broadcaster-oauth"),
+            ("twitch:bob", "bot-oauth"),
+            ("youtube", "bot-oauth"),
+        ] {
+            backend
+                .set_password(&KeystoreEntry {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                    value: "token".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+
+        let found = search(&backend, "twitch:*", "bot-*").unwrap();
+        assert_eq!(
+            found
+                .iter()
+                .map(|key| key.service.as_str())
+                .collect::<Vec<_>>(),
+            vec!["twitch:alice", "twitch:bob"]
+        );
+        assert_eq!(search(&backend, "*", "*").unwrap().len(), 4);
+
+        let found = find_credentials(&backend, "twitch:alice").unwrap();
+        assert_eq!(
+            found
+                .iter()
+                .map(|entry| (entry.account.as_str(), entry.value.as_str()))
+                .collect::<Vec<_>>(),
+            [("bot-oauth", "token"), ("broadcaster-oauth", "token")]
+        );
+    }
+}
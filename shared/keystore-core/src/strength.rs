@@ -0,0 +1,361 @@
+//! Offline password strength estimation in the spirit of zxcvbn.
+//!
+//! The value is scanned left to right for guessable patterns — common passwords and words (also in
+//! l33t spelling), keyboard runs, alphabetic or numeric sequences, years and repeated characters —
+//! and every character outside a pattern is charged as brute force over the character classes
+//! present.
+//! The estimate never leaves the process, so users can check a secret before saving it.
+
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+
+/// Most common leaked passwords plus words that show up in streaming credentials, by rank
+const COMMON_PASSWORDS: &str = "\
+    password 123456 12345678 qwerty 123456789 12345 1234 111111 1234567 dragon 123123 \
+    baseball abc123 football monkey letmein shadow master 666666 qwertyuiop 123321 \
+    mustang 1234567890 michael 654321 superman 1qaz2wsx 7777777 121212 000000 qazwsx \
+    123qwe killer trustno1 jordan jennifer zxcvbnm asdfgh hunter buster soccer harley \
+    batman andrew tigger sunshine iloveyou 2000 charlie robert thomas hockey ranger \
+    daniel starwars klaster 112233 george computer michelle jessica pepper zxcvbn 555555 \
+    131313 freedom 777777 pass maggie 159753 aaaaaa ginger princess joshua cheese amanda \
+    summer love ashley nicole chelsea biteme matthew access yankees 987654321 dallas \
+    austin thunder taylor matrix admin welcome login secret changeme default guest root \
+    test twitch stream streamer streaming youtube obs obsstudio websocket discord gaming \
+    gamer minecraft fortnite valorant pokemon live chat mod subscriber follow overlay \
+    webhook token";
+
+const KEYBOARD_ROWS: &[&str] = &["qwertyuiop", "asdfghjkl", "zxcvbnm", "1qaz2wsx3edc4rfv"];
+
+const MIN_PATTERN_LEN: usize = 3;
+
+/// zxcvbn's score thresholds, in log10 guesses
+const SCORE_THRESHOLDS: [f64; 4] = [3.0, 6.0, 8.0, 10.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pattern {
+    Dictionary,
+    UserInput,
+    Keyboard,
+    Sequence,
+    Repeat,
+    Year,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrengthEstimate {
+    /// 0 (too guessable) to 4 (very unguessable), as in zxcvbn
+    pub score: u8,
+    /// Base-10 logarithm of the estimated number of guesses
+    pub guesses_log10: f64,
+    /// Main reason for a low score, if any
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+fn unleet(c: char) -> char {
+    match c {
+        '4' | '@' => 'a',
+        '8' => 'b',
+        '(' => 'c',
+        '3' => 'e',
+        '6' | '9' => 'g',
+        '1' | '!' | '|' => 'i',
+        '0' => 'o',
+        '$' | '5' => 's',
+        '7' | '+' => 't',
+        '2' => 'z',
+        c => c.to_ascii_lowercase(),
+    }
+}
+
+/// Size of the brute-force alphabet implied by the character classes in `value`
+fn cardinality(value: &[char]) -> f64 {
+    let mut size = 0;
+    if value.iter().any(|c| c.is_ascii_lowercase()) {
+        size += 26;
+    }
+    if value.iter().any(|c| c.is_ascii_uppercase()) {
+        size += 26;
+    }
+    if value.iter().any(|c| c.is_ascii_digit()) {
+        size += 10;
+    }
+    if value.iter().any(|c| c.is_ascii_punctuation() || *c == ' ') {
+        size += 33;
+    }
+    if value.iter().any(|c| !c.is_ascii()) {
+        size += 100;
+    }
+    f64::from(size.max(10))
+}
+
+/// Longest pattern starting at `start`, as (length, log10 guesses, kind)
+fn longest_match(
+    chars: &[char],
+    start: usize,
+    user_inputs: &[Vec<char>],
+) -> Option<(usize, f64, Pattern)> {
+    let rest = &chars[start..];
+    let lower: Vec<char> = rest.iter().map(|c| c.to_ascii_lowercase()).collect();
+    let unleeted: Vec<char> = rest.iter().map(|c| unleet(*c)).collect();
+    let mut best: Option<(usize, f64, Pattern)> = None;
+    let mut consider = |len: usize, guesses_log10: f64, pattern: Pattern| {
+        if len >= MIN_PATTERN_LEN && best.is_none_or(|(l, _, _)| len > l) {
+            best = Some((len, guesses_log10, pattern));
+        }
+    };
+
+    // Variations such as capitals or l33t substitutions roughly double the guesses
+    let variation = |len: usize| {
+        if rest[..len].iter().zip(&lower).any(|(a, b)| a != b) {
+            2f64.log10()
+        } else {
+            0.0
+        }
+    };
+
+    for input in user_inputs {
+        let len = input.len();
+        if len <= rest.len() && (lower[..len] == input[..] || unleeted[..len] == input[..]) {
+            consider(len, 0.0, Pattern::UserInput);
+        }
+    }
+
+    for (rank, word) in COMMON_PASSWORDS.split_whitespace().enumerate() {
+        let word: Vec<char> = word.chars().collect();
+        let len = word.len();
+        if len > rest.len() {
+            continue;
+        }
+        let exact = lower[..len] == word[..];
+        if exact || unleeted[..len] == word[..] {
+            let leet = if exact { 0.0 } else { 2f64.log10() };
+            consider(
+                len,
+                ((rank + 1) as f64).log10() + variation(len) + leet,
+                Pattern::Dictionary,
+            );
+        }
+    }
+
+    for row in KEYBOARD_ROWS {
+        let row: Vec<char> = row.chars().collect();
+        if let Some(offset) = row.iter().position(|c| *c == lower[0]) {
+            let len = row[offset..]
+                .iter()
+                .zip(&lower)
+                .take_while(|(a, b)| a == b)
+                .count();
+            consider(
+                len,
+                (row.len() as f64 * len as f64).log10(),
+                Pattern::Keyboard,
+            );
+        }
+    }
+
+    if rest.len() >= 2 {
+        let step = lower[1] as i32 - lower[0] as i32;
+        if (step == 1 || step == -1) && lower[0].is_ascii_alphanumeric() {
+            let len = 1 + lower
+                .windows(2)
+                .take_while(|w| w[1] as i32 - w[0] as i32 == step && w[1].is_ascii_alphanumeric())
+                .count();
+            let base: f64 = if lower[0].is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let descending = if step < 0 { 2.0 } else { 1.0 };
+            consider(
+                len,
+                (base * len as f64 * descending).log10(),
+                Pattern::Sequence,
+            );
+        }
+    }
+
+    if let Some(year) = rest.get(..4) {
+        let digits: String = year.iter().collect();
+        if let Ok(year) = digits.parse::<u32>() {
+            if (1900..2100).contains(&year) {
+                consider(4, 120f64.log10(), Pattern::Year);
+            }
+        }
+    }
+
+    let run = rest.iter().take_while(|c| **c == rest[0]).count();
+    consider(
+        run,
+        (cardinality(&rest[..1]) * run as f64).log10(),
+        Pattern::Repeat,
+    );
+
+    best
+}
+
+fn score_for(guesses_log10: f64) -> u8 {
+    SCORE_THRESHOLDS
+        .iter()
+        .take_while(|threshold| guesses_log10 >= **threshold)
+        .count() as u8
+}
+
+/// Estimates how guessable `value` is. `user_inputs` are strings the value should not resemble,
+/// such as the account name or other stored credentials.
+pub fn estimate_strength(value: &str, user_inputs: &[&str]) -> StrengthEstimate {
+    let chars: Vec<char> = value.chars().collect();
+    let user_inputs: Vec<Vec<char>> = user_inputs
+        .iter()
+        .filter(|input| input.chars().count() >= MIN_PATTERN_LEN)
+        .map(|input| input.chars().map(|c| c.to_ascii_lowercase()).collect())
+        .collect();
+
+    let per_char = cardinality(&chars).log10();
+    let mut guesses_log10 = 0.0;
+    let mut patterns = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match longest_match(&chars, i, &user_inputs) {
+            Some((len, cost, pattern)) => {
+                guesses_log10 += cost;
+                patterns.push((pattern, len));
+                i += len;
+            }
+            None => {
+                guesses_log10 += per_char;
+                i += 1;
+            }
+        }
+    }
+    // Each extra pattern multiplies the search by the number of ways to combine them
+    if patterns.len() > 1 {
+        guesses_log10 += (patterns.len() as f64).log10();
+    }
+
+    let covers_all = |kind: Pattern| patterns.len() == 1 && patterns[0] == (kind, chars.len());
+    let mut score = score_for(guesses_log10);
+    let warning = if patterns.iter().any(|(p, _)| *p == Pattern::UserInput) {
+        score = 0;
+        Some("This matches or contains another of your credentials".to_string())
+    } else if covers_all(Pattern::Dictionary) {
+        Some("This is a commonly used password".to_string())
+    } else if patterns.iter().any(|(p, _)| *p == Pattern::Keyboard) && score < 3 {
+        Some("Straight rows of keys are easy to guess".to_string())
+    } else if patterns.iter().any(|(p, _)| *p == Pattern::Sequence) && score < 3 {
+        Some("Sequences like abc or 6543 are easy to guess".to_string())
+    } else if patterns.iter().any(|(p, _)| *p == Pattern::Repeat) && score < 3 {
+        Some("Repeated characters like aaa are easy to guess".to_string())
+    } else if patterns.iter().any(|(p, _)| *p == Pattern::Year) && score < 3 {
+        Some("Years are easy to guess".to_string())
+    } else if patterns.iter().any(|(p, _)| *p == Pattern::Dictionary) && score < 3 {
+        Some("Common words are easy to guess".to_string())
+    } else {
+        None
+    };
+
+    let mut suggestions = Vec::new();
+    if score < 3 {
+        if chars.len() < 12 {
+            suggestions.push("Use at least 12 characters".to_string());
+        }
+        suggestions.push("Generate a random secret instead of choosing one".to_string());
+    }
+    if patterns.iter().any(|(p, _)| *p == Pattern::UserInput) {
+        suggestions.push("Use a different secret for each service".to_string());
+    }
+
+    StrengthEstimate {
+        score,
+        guesses_log10,
+        warning,
+        suggestions,
+    }
+}
+
+/// [`estimate_strength`] with the values stored at `related` as user inputs, so reuse of an
+/// existing credential is detected without the caller ever reading it. Entries that do not
+/// exist are skipped.
+pub fn estimate_strength_against(
+    backend: &dyn KeystoreOperations,
+    value: &str,
+    related: &[(&str, &str)],
+) -> Result<StrengthEstimate, KeystoreError> {
+    let mut stored = Vec::new();
+    for (service, account) in related {
+        match backend.get_password(service, account) {
+            Ok(existing) => stored.push(existing),
+            Err(KeystoreError::KeyNotFound(_)) => (),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let inputs: Vec<&str> = stored.iter().map(String::as_str).collect();
+    Ok(estimate_strength(value, &inputs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use crate::KeystoreEntry;
+
+    #[test]
+    fn test_weak_passwords_score_low() {
+        for weak in [
+            "password",
+            "P@ssw0rd",
+            "qwerty123",
+            "aaaaaaaaaaaa",
+            "abcdef123456",
+        ] {
+            let estimate = estimate_strength(weak, &[]);
+            assert!(estimate.score <= 1, "{} scored {}", weak, estimate.score);
+            assert!(estimate.warning.is_some(), "{} had no warning", weak);
+        }
+    }
+
+    #[test]
+    fn test_random_secrets_score_high() {
+        let estimate = estimate_strength("vQ7#pL2m!Xr9zK4w", &[]);
+        assert_eq!(estimate.score, 4);
+        assert!(estimate.warning.is_none());
+        assert!(estimate.suggestions.is_empty());
+    }
+
+    #[test]
+    fn test_words_with_padding_score_in_between() {
+        let estimate = estimate_strength("Summer2024!", &[]);
+        assert!((1..=3).contains(&estimate.score));
+    }
+
+    #[test]
+    fn test_user_input_reuse_scores_zero() {
+        let estimate = estimate_strength("MyStrongTw1tchPass!", &["mystrongtw1tchpass!"]);
+        assert_eq!(estimate.score, 0);
+        assert!(estimate
+            .warning
+            .unwrap()
+            .contains("another of your credentials"));
+    }
+
+    #[test]
+    fn test_estimate_against_stored_credentials() {
+        let backend = MemoryKeystore::default();
+        backend
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "login".to_string(),
+                value: "zk8Rw2mQ!vT4".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let related = [("twitch", "login"), ("missing", "entry")];
+        let reused = estimate_strength_against(&backend, "zk8Rw2mQ!vT4", &related).unwrap();
+        assert_eq!(reused.score, 0);
+
+        let fresh = estimate_strength_against(&backend, "pH3#xN7q@Lw2Ys9b", &related).unwrap();
+        assert_eq!(fresh.score, 4);
+    }
+}
@@ -0,0 +1,287 @@
+//! OpenTelemetry spans for keystore operations, exported over OTLP.
+//!
+//! [`TracingKeystore`] wraps a backend and starts one span per call named `keystore.<operation>`,
+//! with the backend, outcome and duration as attributes. The only identifier recorded is a
+//! truncated SHA-256 of the service name, which lets traces group calls without revealing which
+//! integrations exist. Spans go to the global tracer provider, so they cost almost nothing until
+//! [`init_otlp`] installs an exporter.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use opentelemetry::global::{self, BoxedTracer};
+use opentelemetry::trace::{Span, Status, Tracer};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+use std::time::Instant;
+
+const TRACER_NAME: &str = "keystore-core";
+
+/// Provider installed by `init_otlp`, kept so `shutdown_otlp` can flush it
+static PROVIDER: Mutex<Option<SdkTracerProvider>> = Mutex::new(None);
+
+/// First 16 hex digits of SHA-256 over the service name
+pub fn service_hash(service: &str) -> String {
+    hex::encode(&Sha256::digest(service.as_bytes())[..8])
+}
+
+/// Exports spans to the OTLP/HTTP collector at `endpoint` (e.g. `http://localhost:4318/v1/traces`)
+/// under the resource name `service_name`. Replaces any exporter installed earlier.
+pub fn init_otlp(endpoint: &str, service_name: &str) -> Result<(), KeystoreError> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| KeystoreError::Platform(format!("Failed to create OTLP exporter: {}", e)))?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        )
+        .build();
+
+    global::set_tracer_provider(provider.clone());
+    if let Some(previous) = PROVIDER.lock().unwrap().replace(provider) {
+        let _ = previous.shutdown();
+    }
+    Ok(())
+}
+
+/// Flushes pending spans and stops exporting
+pub fn shutdown_otlp() -> Result<(), KeystoreError> {
+    match PROVIDER.lock().unwrap().take() {
+        Some(provider) => provider
+            .shutdown()
+            .map_err(|e| KeystoreError::Platform(format!("Failed to flush spans: {}", e))),
+        None => Ok(()),
+    }
+}
+
+pub struct TracingKeystore {
+    inner: Box<dyn KeystoreOperations>,
+    tracer: BoxedTracer,
+    backend: String,
+}
+
+impl TracingKeystore {
+    /// Traces through the global tracer provider
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self::with_tracer(inner, global::tracer(TRACER_NAME))
+    }
+
+    pub fn with_tracer(inner: Box<dyn KeystoreOperations>, tracer: BoxedTracer) -> Self {
+        let backend = inner.backend_info().name;
+        Self {
+            inner,
+            tracer,
+            backend,
+        }
+    }
+
+    fn traced<T>(
+        &self,
+        operation: &'static str,
+        service: Option<&str>,
+        call: impl FnOnce() -> Result<T, KeystoreError>,
+    ) -> Result<T, KeystoreError> {
+        let mut span = self.tracer.start(format!("keystore.{}", operation));
+        span.set_attribute(KeyValue::new("keystore.operation", operation));
+        span.set_attribute(KeyValue::new("keystore.backend", self.backend.clone()));
+        if let Some(service) = service {
+            span.set_attribute(KeyValue::new(
+                "keystore.service_hash",
+                service_hash(service),
+            ));
+        }
+
+        let started = Instant::now();
+        let result = call();
+        span.set_attribute(KeyValue::new(
+            "keystore.duration_ms",
+            started.elapsed().as_secs_f64() * 1000.0,
+        ));
+        match &result {
+            Ok(_) => {
+                span.set_attribute(KeyValue::new("keystore.outcome", "ok"));
+                span.set_status(Status::Ok);
+            }
+            Err(e) => {
+                span.set_attribute(KeyValue::new("keystore.outcome", e.code()));
+                // The code only; messages can name the service and account
+                span.set_status(Status::error(e.code()));
+            }
+        }
+        span.end();
+        result
+    }
+}
+
+impl KeystoreOperations for TracingKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        self.traced("set", Some(&entry.service), || {
+            self.inner.set_password(entry)
+        })
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.traced("get", Some(service), || {
+            self.inner.get_password(service, account)
+        })
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.traced("delete", Some(service), || {
+            self.inner.delete_password(service, account)
+        })
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        self.inner.backend_info()
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.inner.max_value_size()
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.traced("list", None, || self.inner.list_keys())
+    }
+
+    fn list_credentials(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.traced("list", Some(service), || {
+            self.inner.list_credentials(service)
+        })
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        self.traced("get_many", None, || self.inner.get_many(keys))
+    }
+
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        self.traced("set_many", None, || self.inner.set_many(entries))
+    }
+
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.traced("delete_many", None, || self.inner.delete_many(keys))
+    }
+
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        self.traced("delete_service", Some(service), || {
+            self.inner.delete_service(service)
+        })
+    }
+
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        self.traced("wipe_all", None, || self.inner.wipe_all())
+    }
+
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        self.traced("swap", None, || self.inner.swap(a, b))
+    }
+
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        self.traced("get_metadata", Some(service), || {
+            self.inner.get_metadata(service, account)
+        })
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        self.traced("has_password", Some(service), || {
+            self.inner.has_password(service, account)
+        })
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        self.traced("get_or_create", Some(service), || {
+            self.inner.get_or_create(service, account, length)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry::Value;
+    use opentelemetry_sdk::error::OTelSdkResult;
+    use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+    use std::sync::Arc;
+
+    #[derive(Debug, Default, Clone)]
+    struct Collect(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for Collect {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            self.0.lock().unwrap().extend(batch);
+            Ok(())
+        }
+    }
+
+    fn attribute(span: &SpanData, key: &str) -> Option<Value> {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    }
+
+    #[test]
+    fn test_spans_carry_outcome_but_no_identifiers() {
+        let spans = Collect::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(spans.clone())
+            .build();
+        let keystore = TracingKeystore::with_tracer(
+            Box::new(MemoryKeystore::default()),
+            BoxedTracer::new(Box::new(provider.tracer("test"))),
+        );
+
+        keystore
+            .set_password(&KeystoreEntry {
+                service: "twitch".to_string(),
+                account: "bot-oauth".to_string(),
+                value: "token".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(keystore.get_password("twitch", "missing").is_err());
+
+        let spans = spans.0.lock().unwrap();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].name, "keystore.set");
+        assert_eq!(
+            attribute(&spans[0], "keystore.backend"),
+            Some(Value::from("memory"))
+        );
+        assert_eq!(
+            attribute(&spans[1], "keystore.outcome"),
+            Some(Value::from("ERR_KEY_NOT_FOUND"))
+        );
+        assert_eq!(
+            attribute(&spans[1], "keystore.service_hash"),
+            Some(Value::from(service_hash("twitch")))
+        );
+        assert!(attribute(&spans[1], "keystore.duration_ms").is_some());
+
+        let dump = format!("{:?}", *spans);
+        assert!(!dump.contains("twitch"));
+        assert!(!dump.contains("bot-oauth"));
+        assert!(!dump.contains("missing"));
+    }
+}
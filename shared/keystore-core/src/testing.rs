@@ -0,0 +1,73 @@
+//! Test doubles shared by the unit tests of several modules.
+
+use crate::error::KeystoreError;
+use crate::platform::{BackendInfo, KeystoreOperations};
+use crate::{KeystoreEntry, KeystoreKey};
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// In-memory backend with an optional per-entry size limit
+#[derive(Default)]
+pub struct MemoryKeystore {
+    pub entries: Mutex<HashMap<(String, String), String>>,
+    pub max_value_size: Option<usize>,
+}
+
+impl KeystoreOperations for MemoryKeystore {
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        if let Some(max) = self.max_value_size {
+            if entry.value.len() > max {
+                return Err(KeystoreError::Platform("Value too large".to_string()));
+            }
+        }
+        self.entries.lock().unwrap().insert(
+            (entry.service.clone(), entry.account.clone()),
+            entry.value.clone(),
+        );
+        Ok(())
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&(service.to_string(), account.to_string()))
+            .cloned()
+            .ok_or_else(|| KeystoreError::KeyNotFound(format!("{}:{}", service, account)))
+    }
+
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        self.entries
+            .lock()
+            .unwrap()
+            .remove(&(service.to_string(), account.to_string()))
+            .map(|_| ())
+            .ok_or_else(|| KeystoreError::KeyNotFound(format!("{}:{}", service, account)))
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        BackendInfo::new("memory")
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.max_value_size
+    }
+
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .keys()
+            .map(|(service, account)| KeystoreKey {
+                service: service.clone(),
+                account: account.clone(),
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,534 @@
+//! Deferring writes while the OS keyring is locked.
+//!
+//! Right after an autologin the Secret Service collection is often still locked, and every call
+//! fails with [`KeystoreError::KeyringLocked`] until the user unlocks it. With waiting enabled by
+//! [`UnlockWaitKeystore::set_max_wait`], writes and deletes that fail that way are queued instead,
+//! reads of queued keys are answered from the queue, and a background thread retries, backing off
+//! to [`MAX_RETRY_INTERVAL`]. Listeners hear [`UnlockWaitEvent::UnlockNeeded`] when the queue
+//! starts, so the UI can ask the user to unlock, and [`UnlockWaitEvent::Flushed`] once it is
+//! written. Reads of other keys still fail: a synchronous caller can't be made to wait on a person.
+//!
+//! The queue lives in memory. Operations still queued after the maximum wait are dropped and
+//! reported as [`UnlockWaitEvent::Expired`]; those queued when the process exits are lost.
+
+use crate::error::KeystoreError;
+use crate::platform::{get_or_create_by_writes, swap_by_writes, BackendInfo, KeystoreOperations};
+use crate::resolution::{record_source, ReadSource};
+use crate::{EntryMetadata, KeystoreEntry, KeystoreKey};
+
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Most operations held at once; further writes fail with `KeyringLocked`
+pub const MAX_QUEUED: usize = 256;
+
+/// Default bound on how long operations wait for an unlock
+pub const DEFAULT_MAX_WAIT: Duration = Duration::from_secs(5 * 60);
+
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+
+/// Retries back off to this interval; each may show the keyring's unlock prompt
+pub const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnlockWaitEvent {
+    /// The keyring is locked and operations are being queued
+    UnlockNeeded { queued: usize },
+    /// The keyring was unlocked and the queue written; `failed` operations hit another error
+    Flushed { written: usize, failed: usize },
+    /// The maximum wait passed before an unlock; the queued operations were dropped
+    Expired { dropped: usize },
+}
+
+type Listener = Box<dyn Fn(UnlockWaitEvent) + Send + Sync>;
+
+struct Operation {
+    seq: u64,
+    key: KeystoreKey,
+    /// `None` for a delete
+    value: Option<Zeroizing<String>>,
+}
+
+#[derive(Default)]
+struct Queue {
+    operations: Vec<Operation>,
+    next_seq: u64,
+    since: Option<Instant>,
+    /// `None` while waiting is off
+    max_wait: Option<Duration>,
+    retrying: bool,
+    written: usize,
+    failed: usize,
+}
+
+impl Queue {
+    fn find(&self, service: &str, account: &str) -> Option<&Operation> {
+        self.operations
+            .iter()
+            .find(|op| op.key.service == service && op.key.account == account)
+    }
+}
+
+struct Shared {
+    inner: Box<dyn KeystoreOperations>,
+    queue: Mutex<Queue>,
+    listeners: Mutex<Vec<Listener>>,
+}
+
+impl Shared {
+    fn emit(&self, event: UnlockWaitEvent) {
+        for listener in self.listeners.lock().unwrap().iter() {
+            listener(event);
+        }
+    }
+
+    /// Writes queued operations in order until the keyring refuses one. Returns the closing event
+    /// once the queue is empty or has expired, `None` while the keyring stays locked.
+    fn flush(&self) -> Option<UnlockWaitEvent> {
+        loop {
+            let (seq, key, value) = {
+                let mut queue = self.queue.lock().unwrap();
+                let max_wait = queue.max_wait.unwrap_or(DEFAULT_MAX_WAIT);
+                if queue.since.is_some_and(|since| since.elapsed() >= max_wait) {
+                    let dropped = queue.operations.len();
+                    queue.operations.clear();
+                    queue.since = None;
+                    queue.retrying = false;
+                    return Some(UnlockWaitEvent::Expired { dropped });
+                }
+                let Some(op) = queue.operations.first() else {
+                    queue.since = None;
+                    queue.retrying = false;
+                    return Some(UnlockWaitEvent::Flushed {
+                        written: queue.written,
+                        failed: queue.failed,
+                    });
+                };
+                (op.seq, op.key.clone(), op.value.clone())
+            };
+
+            let result = match &value {
+                Some(value) => self.inner.set_password(&KeystoreEntry {
+                    service: key.service.clone(),
+                    account: key.account.clone(),
+                    value: value.to_string(),
+                    ..Default::default()
+                }),
+                None => match self.inner.delete_password(&key.service, &key.account) {
+                    Err(KeystoreError::KeyNotFound(_)) => Ok(()),
+                    result => result,
+                },
+            };
+            let mut queue = self.queue.lock().unwrap();
+            match result {
+                Err(KeystoreError::KeyringLocked(_)) => return None,
+                Ok(()) => queue.written += 1,
+                Err(_) => queue.failed += 1,
+            }
+            // Unless a newer write to the same key replaced it meanwhile
+            queue.operations.retain(|op| op.seq != seq);
+        }
+    }
+}
+
+fn retry(shared: Weak<Shared>) {
+    let mut interval = FIRST_RETRY;
+    loop {
+        thread::sleep(interval);
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        if let Some(event) = shared.flush() {
+            shared.emit(event);
+            return;
+        }
+        interval = (interval * 2).min(MAX_RETRY_INTERVAL);
+    }
+}
+
+pub struct UnlockWaitKeystore {
+    shared: Arc<Shared>,
+}
+
+impl UnlockWaitKeystore {
+    /// Waiting starts off: until [`Self::set_max_wait`], a locked keyring fails calls as before
+    pub fn new(inner: Box<dyn KeystoreOperations>) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                inner,
+                queue: Mutex::new(Queue::default()),
+                listeners: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Queues operations for up to `max_wait` while the keyring is locked; `None` stops queueing
+    /// new ones. Operations already queued are still written, or expire, either way.
+    pub fn set_max_wait(&self, max_wait: Option<Duration>) {
+        self.shared.queue.lock().unwrap().max_wait = max_wait;
+    }
+
+    /// Operations waiting for the keyring to be unlocked
+    pub fn queued(&self) -> usize {
+        self.shared.queue.lock().unwrap().operations.len()
+    }
+
+    pub fn on_event(&self, listener: impl Fn(UnlockWaitEvent) + Send + Sync + 'static) {
+        self.shared
+            .listeners
+            .lock()
+            .unwrap()
+            .push(Box::new(listener));
+    }
+
+    /// Queues `value` (`None` to delete) for `key`, behind anything queued before it
+    fn enqueue(
+        &self,
+        key: KeystoreKey,
+        value: Option<Zeroizing<String>>,
+        reason: String,
+    ) -> Result<(), KeystoreError> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let replaces = queue.find(&key.service, &key.account).is_some();
+        if queue.operations.is_empty() && queue.max_wait.is_none() {
+            return Err(KeystoreError::KeyringLocked(reason));
+        }
+        if !replaces && queue.operations.len() >= MAX_QUEUED {
+            return Err(KeystoreError::KeyringLocked(format!(
+                "{} operations are already waiting for the keyring to be unlocked",
+                MAX_QUEUED
+            )));
+        }
+        queue
+            .operations
+            .retain(|op| op.key.service != key.service || op.key.account != key.account);
+        let seq = queue.next_seq;
+        queue.next_seq += 1;
+        queue.operations.push(Operation { seq, key, value });
+
+        let started = queue.since.is_none();
+        if started {
+            queue.since = Some(Instant::now());
+            queue.written = 0;
+            queue.failed = 0;
+        }
+        if !queue.retrying {
+            queue.retrying = true;
+            let shared = Arc::downgrade(&self.shared);
+            thread::Builder::new()
+                .name("keyring-unlock-wait".to_string())
+                .spawn(move || retry(shared))?;
+        }
+        drop(queue);
+        if started {
+            self.shared
+                .emit(UnlockWaitEvent::UnlockNeeded { queued: 1 });
+        }
+        Ok(())
+    }
+
+    fn is_queueing(&self) -> bool {
+        !self.shared.queue.lock().unwrap().operations.is_empty()
+    }
+
+    fn key(service: &str, account: &str) -> KeystoreKey {
+        KeystoreKey {
+            service: service.to_string(),
+            account: account.to_string(),
+        }
+    }
+}
+
+impl KeystoreOperations for UnlockWaitKeystore {
+    /// Queued while the keyring is locked, and also while anything else is queued, so writes land
+    /// in the order they were made
+    fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+        let key = Self::key(&entry.service, &entry.account);
+        let value = || Some(Zeroizing::new(entry.value.clone()));
+        if self.is_queueing() {
+            return self.enqueue(key, value(), "Keyring is locked".to_string());
+        }
+        match self.shared.inner.set_password(entry) {
+            Err(KeystoreError::KeyringLocked(reason)) => self.enqueue(key, value(), reason),
+            result => result,
+        }
+    }
+
+    fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+        if let Some(op) = self.shared.queue.lock().unwrap().find(service, account) {
+            return match &op.value {
+                Some(value) => {
+                    record_source(ReadSource::PendingWrite);
+                    Ok(value.to_string())
+                }
+                None => Err(KeystoreError::KeyNotFound(format!(
+                    "{}:{}",
+                    service, account
+                ))),
+            };
+        }
+        self.shared.inner.get_password(service, account)
+    }
+
+    /// Queued like [`Self::set_password`]; succeeds whether or not the entry exists
+    fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+        let key = Self::key(service, account);
+        if self.is_queueing() {
+            return self.enqueue(key, None, "Keyring is locked".to_string());
+        }
+        match self.shared.inner.delete_password(service, account) {
+            Err(KeystoreError::KeyringLocked(reason)) => self.enqueue(key, None, reason),
+            result => result,
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        self.shared.inner.is_available()
+    }
+
+    fn backend_info(&self) -> BackendInfo {
+        let info = self.shared.inner.backend_info();
+        match self.queued() {
+            0 => info,
+            queued => info.with_detail("queued_until_unlock", queued.to_string()),
+        }
+    }
+
+    fn max_value_size(&self) -> Option<usize> {
+        self.shared.inner.max_value_size()
+    }
+
+    /// The backend's keys as the queue will leave them
+    fn list_keys(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let mut keys = self.shared.inner.list_keys()?;
+        let queue = self.shared.queue.lock().unwrap();
+        keys.retain(|key| queue.find(&key.service, &key.account).is_none());
+        keys.extend(
+            queue
+                .operations
+                .iter()
+                .filter(|op| op.value.is_some())
+                .map(|op| op.key.clone()),
+        );
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn get_many(&self, keys: &[KeystoreKey]) -> Result<Vec<Option<String>>, KeystoreError> {
+        if !self.is_queueing() {
+            return self.shared.inner.get_many(keys);
+        }
+        keys.iter()
+            .map(|key| match self.get_password(&key.service, &key.account) {
+                Ok(value) => Ok(Some(value)),
+                Err(KeystoreError::KeyNotFound(_)) => Ok(None),
+                Err(e) => Err(e),
+            })
+            .collect()
+    }
+
+    /// Queued entry by entry, like [`Self::set_password`]. If the keyring locks partway through,
+    /// the whole batch is queued again; writing an entry twice leaves it the same.
+    fn set_many(&self, entries: &[KeystoreEntry]) -> Result<(), KeystoreError> {
+        let enqueue = |reason: String| {
+            entries.iter().try_for_each(|entry| {
+                self.enqueue(
+                    Self::key(&entry.service, &entry.account),
+                    Some(Zeroizing::new(entry.value.clone())),
+                    reason.clone(),
+                )
+            })
+        };
+        if self.is_queueing() {
+            return enqueue("Keyring is locked".to_string());
+        }
+        match self.shared.inner.set_many(entries) {
+            Err(KeystoreError::KeyringLocked(reason)) => enqueue(reason),
+            result => result,
+        }
+    }
+
+    /// Queued like [`Self::delete_password`]; the keys queued count as removed
+    fn delete_many(&self, keys: &[KeystoreKey]) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let enqueue = |reason: String| {
+            for key in keys {
+                self.enqueue(key.clone(), None, reason.clone())?;
+            }
+            let mut queued = keys.to_vec();
+            queued.sort();
+            queued.dedup();
+            Ok(queued)
+        };
+        if self.is_queueing() {
+            return enqueue("Keyring is locked".to_string());
+        }
+        match self.shared.inner.delete_many(keys) {
+            Err(KeystoreError::KeyringLocked(reason)) => enqueue(reason),
+            result => result,
+        }
+    }
+
+    /// Not queued; also drops the service's queued operations
+    fn delete_service(&self, service: &str) -> Result<Vec<String>, KeystoreError> {
+        let deleted = self.shared.inner.delete_service(service)?;
+        self.shared
+            .queue
+            .lock()
+            .unwrap()
+            .operations
+            .retain(|op| op.key.service != service);
+        Ok(deleted)
+    }
+
+    /// Not queued; also drops every queued operation
+    fn wipe_all(&self) -> Result<Vec<KeystoreKey>, KeystoreError> {
+        let wiped = self.shared.inner.wipe_all()?;
+        self.shared.queue.lock().unwrap().operations.clear();
+        Ok(wiped)
+    }
+
+    /// Queued as two writes while anything is queued, so it sees and keeps the queued values; a
+    /// locked keyring otherwise fails it, since its values can't be read
+    fn swap(&self, a: &KeystoreKey, b: &KeystoreKey) -> Result<(), KeystoreError> {
+        if self.is_queueing() {
+            return swap_by_writes(self, a, b);
+        }
+        self.shared.inner.swap(a, b)
+    }
+
+    /// A queued write reports no metadata until it reaches the backend
+    fn get_metadata(&self, service: &str, account: &str) -> Result<EntryMetadata, KeystoreError> {
+        if let Some(op) = self.shared.queue.lock().unwrap().find(service, account) {
+            return match op.value {
+                Some(_) => Ok(EntryMetadata::default()),
+                None => Err(KeystoreError::KeyNotFound(format!(
+                    "{}:{}",
+                    service, account
+                ))),
+            };
+        }
+        self.shared.inner.get_metadata(service, account)
+    }
+
+    fn has_password(&self, service: &str, account: &str) -> Result<bool, KeystoreError> {
+        if let Some(op) = self.shared.queue.lock().unwrap().find(service, account) {
+            return Ok(op.value.is_some());
+        }
+        self.shared.inner.has_password(service, account)
+    }
+
+    fn get_or_create(
+        &self,
+        service: &str,
+        account: &str,
+        length: usize,
+    ) -> Result<String, KeystoreError> {
+        if self.is_queueing() {
+            return get_or_create_by_writes(self, service, account, length);
+        }
+        self.shared.inner.get_or_create(service, account, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+
+    /// Refuses everything while `locked`, like a locked Secret Service collection
+    struct Keyring {
+        locked: Arc<AtomicBool>,
+        entries: MemoryKeystore,
+    }
+
+    impl Keyring {
+        fn check(&self) -> Result<(), KeystoreError> {
+            match self.locked.load(Ordering::SeqCst) {
+                true => Err(KeystoreError::KeyringLocked(
+                    "Collection is locked".to_string(),
+                )),
+                false => Ok(()),
+            }
+        }
+    }
+
+    impl KeystoreOperations for Keyring {
+        fn set_password(&self, entry: &KeystoreEntry) -> Result<(), KeystoreError> {
+            self.check()?;
+            self.entries.set_password(entry)
+        }
+
+        fn get_password(&self, service: &str, account: &str) -> Result<String, KeystoreError> {
+            self.check()?;
+            self.entries.get_password(service, account)
+        }
+
+        fn delete_password(&self, service: &str, account: &str) -> Result<(), KeystoreError> {
+            self.check()?;
+            self.entries.delete_password(service, account)
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn backend_info(&self) -> BackendInfo {
+            BackendInfo::new("keyring")
+        }
+    }
+
+    #[test]
+    fn test_writes_wait_for_the_keyring_to_be_unlocked() {
+        let locked = Arc::new(AtomicBool::new(true));
+        let keystore = UnlockWaitKeystore::new(Box::new(Keyring {
+            locked: Arc::clone(&locked),
+            entries: MemoryKeystore::default(),
+        }));
+        let entry = |account: &str, value: &str| KeystoreEntry {
+            service: "twitch".to_string(),
+            account: account.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        };
+        // Off by default
+        assert!(matches!(
+            keystore.set_password(&entry("bot", "one")),
+            Err(KeystoreError::KeyringLocked(_))
+        ));
+
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        keystore.on_event(move |event| tx.lock().unwrap().send(event).unwrap());
+        keystore.set_max_wait(Some(Duration::from_secs(60)));
+        keystore.set_password(&entry("bot", "one")).unwrap();
+        keystore.set_password(&entry("chat", "two")).unwrap();
+        keystore.set_password(&entry("bot", "three")).unwrap();
+        keystore.delete_password("twitch", "chat").unwrap();
+        assert_eq!(
+            rx.recv().unwrap(),
+            UnlockWaitEvent::UnlockNeeded { queued: 1 }
+        );
+        assert_eq!(keystore.queued(), 2);
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "three");
+        assert!(keystore.get_password("twitch", "chat").is_err());
+
+        locked.store(false, Ordering::SeqCst);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(10)).unwrap(),
+            UnlockWaitEvent::Flushed {
+                written: 2,
+                failed: 0
+            }
+        );
+        assert_eq!(keystore.queued(), 0);
+        assert_eq!(keystore.get_password("twitch", "bot").unwrap(), "three");
+        assert!(matches!(
+            keystore.get_password("twitch", "chat"),
+            Err(KeystoreError::KeyNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,67 @@
+//! Timing-safe comparison of secrets such as webhook signatures and pairing codes.
+//!
+//! Both sides are hashed with SHA-256 before a constant-time comparison, so neither the position
+//! of the first differing byte nor the length of the expected value leaks through timing.
+
+use crate::error::KeystoreError;
+use crate::platform::KeystoreOperations;
+
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+/// Compares `a` and `b` in time independent of their contents
+pub fn constant_time_equals(a: &[u8], b: &[u8]) -> bool {
+    let a = Sha256::digest(a);
+    let b = Sha256::digest(b);
+    a.ct_eq(&b).into()
+}
+
+/// Compares `candidate` with the value stored at `service`/`account` without returning it.
+/// A missing entry compares unequal.
+pub fn verify_against_stored(
+    backend: &dyn KeystoreOperations,
+    service: &str,
+    account: &str,
+    candidate: &str,
+) -> Result<bool, KeystoreError> {
+    match backend.get_password(service, account) {
+        Ok(stored) => Ok(constant_time_equals(
+            stored.as_bytes(),
+            candidate.as_bytes(),
+        )),
+        Err(KeystoreError::KeyNotFound(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryKeystore;
+    use crate::KeystoreEntry;
+
+    #[test]
+    fn test_constant_time_equals() {
+        assert!(constant_time_equals(b"sha256=abc123", b"sha256=abc123"));
+        assert!(!constant_time_equals(b"sha256=abc123", b"sha256=abc124"));
+        assert!(!constant_time_equals(b"sha256=abc123", b"sha256=abc12"));
+        assert!(constant_time_equals(b"", b""));
+    }
+
+    #[test]
+    fn test_verify_against_stored() {
+        let backend = MemoryKeystore::default();
+        backend
+            .set_password(&KeystoreEntry {
+                service: "pairing".to_string(),
+                account: "code".to_string(),
+                value: "483-921".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert!(verify_against_stored(&backend, "pairing", "code", "483-921").unwrap());
+        assert!(!verify_against_stored(&backend, "pairing", "code", "483-922").unwrap());
+        assert!(!verify_against_stored(&backend, "pairing", "missing", "483-921").unwrap());
+    }
+}
@@ -0,0 +1,44 @@
+//! Build information for support bundles and compliance checks.
+
+/// What this build of the crate is and which crypto provider it uses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    /// `rustcrypto` or `openssl-fips` for the encrypted-file backend, `none` without it
+    pub crypto_provider: &'static str,
+    /// Whether the FIPS provider is compiled in and loaded
+    pub fips: bool,
+    /// Cargo features enabled at build time
+    pub features: Vec<&'static str>,
+}
+
+pub fn version_info() -> VersionInfo {
+    #[cfg(feature = "backend-file")]
+    let (crypto_provider, fips) = (crate::crypto::provider_name(), crate::crypto::fips_active());
+    #[cfg(not(feature = "backend-file"))]
+    let (crypto_provider, fips) = ("none", false);
+
+    let features = [
+        ("backend-windows", cfg!(feature = "backend-windows")),
+        ("backend-macos", cfg!(feature = "backend-macos")),
+        ("backend-ios", cfg!(feature = "backend-ios")),
+        (
+            "backend-secret-service",
+            cfg!(feature = "backend-secret-service"),
+        ),
+        ("backend-file", cfg!(feature = "backend-file")),
+        ("fips", cfg!(feature = "fips")),
+        ("async-runtime", cfg!(feature = "async-runtime")),
+        ("otel", cfg!(feature = "otel")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect();
+
+    VersionInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        crypto_provider,
+        fips,
+        features,
+    }
+}
@@ -0,0 +1,138 @@
+//! Change notifications from the platform keystore.
+//!
+//! Caches in front of a native keystore save a Keychain ACL evaluation or a DBus call per read,
+//! but another process can change an entry behind them. [`subscribe`] runs a callback whenever the
+//! platform reports a change to any entry: Secret Service's collection signals on Linux, changes to
+//! the Credential Manager's credential files on Windows, and keychain events for the legacy macOS
+//! keychain. The data-protection keychain and the encrypted file report nothing, so
+//! [`subscribe`] refuses them with `PlatformNotSupported`. Notifications don't say which entry
+//! changed, so subscribers drop everything they cached.
+//!
+//! Each platform is watched by one thread per process, started by the first subscription. Dropping
+//! a [`ChangeSubscription`] unregisters its callback.
+
+use crate::error::KeystoreError;
+use crate::platform::BackendInfo;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+type Listener = Arc<dyn Fn() + Send + Sync>;
+
+static LISTENERS: Mutex<Vec<(u64, Listener)>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+/// Outcome of starting the platform's watcher thread, kept so it's only tried once
+static WATCHER: OnceLock<Result<(), String>> = OnceLock::new();
+
+/// Keeps a callback registered until dropped
+pub struct ChangeSubscription {
+    id: u64,
+}
+
+impl Drop for ChangeSubscription {
+    fn drop(&mut self) {
+        let mut listeners = LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+        listeners.retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// Called from the watcher thread for every change the platform reports
+#[cfg(any(
+    all(target_os = "linux", feature = "backend-secret-service"),
+    all(windows, feature = "backend-windows"),
+    all(target_os = "macos", feature = "backend-macos"),
+    test
+))]
+pub(crate) fn notify_listeners() {
+    let listeners: Vec<Listener> = LISTENERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(_, listener)| Arc::clone(listener))
+        .collect();
+    for listener in listeners {
+        listener();
+    }
+}
+
+fn register(on_change: impl Fn() + Send + Sync + 'static) -> ChangeSubscription {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    LISTENERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((id, Arc::new(on_change)));
+    ChangeSubscription { id }
+}
+
+/// Whether the platform reports changes to entries of the backend `info` describes
+pub fn supports(info: &BackendInfo) -> bool {
+    match info.name.as_str() {
+        "secret-service" => cfg!(all(target_os = "linux", feature = "backend-secret-service")),
+        "windows-credential-manager" => cfg!(all(windows, feature = "backend-windows")),
+        "macos-keychain" => {
+            cfg!(all(target_os = "macos", feature = "backend-macos"))
+                && info.details.get("keychain").map(String::as_str) == Some("legacy")
+        }
+        _ => false,
+    }
+}
+
+/// Runs `on_change` whenever an entry of the backend `info` describes changes, whichever process
+/// changed it. Writes through this process are reported too.
+pub fn subscribe(
+    info: &BackendInfo,
+    on_change: impl Fn() + Send + Sync + 'static,
+) -> Result<ChangeSubscription, KeystoreError> {
+    if !supports(info) {
+        return Err(KeystoreError::PlatformNotSupported);
+    }
+    WATCHER
+        .get_or_init(|| start_watcher().map_err(|e| e.to_string()))
+        .clone()
+        .map_err(|e| KeystoreError::Platform(format!("Failed to watch for changes: {}", e)))?;
+    Ok(register(on_change))
+}
+
+fn start_watcher() -> Result<(), KeystoreError> {
+    cfg_if::cfg_if! {
+        if #[cfg(all(target_os = "linux", feature = "backend-secret-service"))] {
+            crate::platform::linux::watch_changes(notify_listeners)
+        } else if #[cfg(all(windows, feature = "backend-windows"))] {
+            crate::platform::windows::watch_changes(notify_listeners)
+        } else if #[cfg(all(target_os = "macos", feature = "backend-macos"))] {
+            crate::platform::macos::watch_changes(notify_listeners)
+        } else {
+            Err(KeystoreError::PlatformNotSupported)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_listeners_run_until_unsubscribed() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let subscription = register(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        notify_listeners();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        drop(subscription);
+        notify_listeners();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert!(!supports(&BackendInfo::new("encrypted-file")));
+        assert!(!supports(
+            &BackendInfo::new("macos-keychain").with_detail("keychain", "data-protection")
+        ));
+        assert!(matches!(
+            subscribe(&BackendInfo::new("encrypted-file"), || {}),
+            Err(KeystoreError::PlatformNotSupported)
+        ));
+    }
+}
@@ -1,13 +1,960 @@
-export interface NapiKeystoreError {
-  code: string;
+export type KeystoreErrorKind =
+  | 'locked'
+  | 'interaction-required'
+  | 'denied'
+  | 'not-found'
+  | 'timeout'
+  | 'transient'
+  | 'corrupted'
+  | 'unsupported'
+  | 'invalid'
+  | 'other';
+
+/** What `parseKeystoreError` returns; narrow on `kind` to get the codes it covers */
+export type NapiKeystoreError = {
   message: string;
+  /** The same call may succeed later without the user doing anything */
+  retryable: boolean;
+} & (
+  | { kind: 'locked'; code: 'ERR_KEYRING_LOCKED' }
+  | { kind: 'interaction-required'; code: 'ERR_INTERACTION_REQUIRED' }
+  | { kind: 'denied'; code: 'ERR_ACCESS_DENIED' | 'ERR_CONTEXT_MISMATCH' }
+  | { kind: 'not-found'; code: 'ERR_KEY_NOT_FOUND' }
+  | { kind: 'timeout'; code: 'ERR_TIMEOUT' }
+  | { kind: 'transient'; code: 'ERR_TRANSIENT' | 'ERR_BUSY' }
+  | { kind: 'corrupted'; code: 'ERR_CORRUPTED' | 'ERR_SERIALIZATION' }
+  | { kind: 'unsupported'; code: 'ERR_PLATFORM_NOT_SUPPORTED' | 'ERR_FORMAT_TOO_NEW' }
+  | { kind: 'invalid'; code: 'ERR_INVALID_INPUT' | 'ERR_AMBIGUOUS' }
+  | { kind: 'other'; code: 'ERR_PLATFORM' | 'ERR_IO' }
+);
+
+/** Splits the message of an error this module threw into code, kind and text; `null` for other errors */
+export function parseKeystoreError(message: string): NapiKeystoreError | null;
+
+export interface EntryAlias {
+  alias: KeystoreKey;
+  target: KeystoreKey;
+}
+
+/** Managed policy, where configured, overrides these */
+export interface KeystoreOptions {
+  /** Resolve account names regardless of case; ambiguous lookups throw ERR_AMBIGUOUS */
+  caseInsensitive?: boolean;
+  /** Keep a copy of every entry in the encrypted file as well as the platform keystore */
+  mirrorToFile?: boolean;
+  /**
+   * Stores every service as `<namespace>::<service>`, e.g. `dev::twitch`, so a dev and a
+   * production build on one machine keep apart. Listings only show the namespace, unprefixed.
+   */
+  namespace?: string;
+}
+
+/** Returned by `findCredentials`, shaped like keytar's */
+export interface Credential {
+  account: string;
+  password: string;
+}
+
+export interface KeystoreKey {
+  service: string;
+  account: string;
+}
+
+export interface KeystoreEntry {
+  service: string;
+  account: string;
+  value: string;
+  /** Left out to keep the stored label, `''` to clear it */
+  label?: string;
+  /** Kept and cleared like `label` */
+  comment?: string;
+  /** Milliseconds since the Unix epoch after which the entry reads as missing */
+  expiresAt?: number;
+}
+
+export interface WriteOptions {
+  /** Name for settings screens; left out to keep the stored one, `''` to clear it */
+  label?: string;
+  /** Free-form note, kept and cleared like `label` */
+  comment?: string;
+  /** Milliseconds since the Unix epoch after which the entry reads as missing and is deleted; left out, it never expires */
+  expiresAt?: number;
+}
+
+export interface EntryMetadata {
+  label?: string;
+  comment?: string;
+  /** Milliseconds since the Unix epoch; absent where the backend doesn't record it */
+  createdAt?: number;
+  updatedAt?: number;
+  expiresAt?: number;
+}
+
+export interface PrefetchFailure {
+  key: KeystoreKey;
+  /** Why the entry couldn't be read */
+  error: string;
+}
+
+export interface PrefetchReport {
+  warmed: number;
+  /** Named keys that don't exist */
+  missing: KeystoreKey[];
+  failed: PrefetchFailure[];
+}
+
+export interface BackendInfo {
+  name: string;
+  details: Record<string, string>;
+}
+
+export interface SecureNoteInfo {
+  title: string;
+  size: number;
+  /** Milliseconds since the Unix epoch */
+  createdAt: number;
+  updatedAt: number;
+}
+
+export interface CertificateInfo {
+  name: string;
+  subject: string;
+  issuer: string;
+  serial: string;
+  /** Milliseconds since the Unix epoch */
+  notBefore: number;
+  notAfter: number;
+  fingerprintSha256: string;
+  chainLength: number;
+  hasPrivateKey: boolean;
+}
+
+/** `service` or `service/account` glob patterns (`*`, `?`) selecting entries to export */
+export interface ExportFilter {
+  /** Everything when omitted or empty */
+  include?: string[];
+  exclude?: string[];
+}
+
+export interface ImportConflict {
+  key: KeystoreKey;
+  reason: string;
+}
+
+/** A token another application stored, found by `discoverForeignCredentials` */
+export interface ForeignCredential {
+  /** Schema it matched, e.g. `keytar` */
+  app: string;
+  service: string;
+  account: string;
+  /** Secret Service item path or Credential Manager target name */
+  location: string;
+}
+
+/** Where the platform keystore and its encrypted-file mirror differ, without values */
+export interface MirrorReport {
+  /** Missing from the encrypted file */
+  onlyPrimary: KeystoreKey[];
+  /** Missing from the platform keystore */
+  onlySecondary: KeystoreKey[];
+  differing: KeystoreKey[];
+}
+
+/** What an import would do; values are compared by hash and never included */
+export interface ImportDiff {
+  added: KeystoreKey[];
+  /** Stored with a different value; only overwritten when `overwrite` is set */
+  changed: KeystoreKey[];
+  unchanged: KeystoreKey[];
+  /** Listed twice with different values, or stored but unreadable; never written */
+  conflicts: ImportConflict[];
+}
+
+export interface EnvImportReport {
+  diff: ImportDiff;
+  /** Variables no mapping rule matched */
+  unmapped: string[];
+  /** Whether the file was overwritten and deleted */
+  shredded: boolean;
+}
+
+/** An entry two writers of a synced keystore file changed differently */
+export interface SyncConflict {
+  id: string;
+  key: KeystoreKey;
+  /** Revision of the displaced version */
+  revision: number;
+  /** Milliseconds since the Unix epoch */
+  detectedAt: number;
+}
+
+export interface ErrorCount {
+  code: string;
+  backend: string;
+  count: number;
+}
+
+export interface HealthReport {
+  status: 'healthy' | 'degraded' | 'unhealthy';
+  backend: BackendInfo;
+  available: boolean;
+  isFallback: boolean;
+  expiringCertificates: CertificateInfo[];
+  warnings: string[];
+  /** Set by `doctorReport()` when the integrity audit ran */
+  integrity?: IntegrityReport;
+}
+
+export interface IntegrityIssue {
+  /** Unset for issues of the backend as a whole */
+  service?: string;
+  account?: string;
+  kind:
+    | 'unreadable'
+    | 'undecryptable'
+    | 'corrupt'
+    | 'invalid-schema'
+    | 'orphaned-chunk'
+    | 'dangling-alias'
+    | 'orphaned-record';
+  detail: string;
+  /** What `repair()` would remove to fix the issue */
+  orphan?: Orphan;
+}
+
+export interface Orphan {
+  kind:
+    | 'chunk'
+    | 'alias'
+    | 'note-index-row'
+    | 'certificate-index-row'
+    | 'protection-mark'
+    | 'provenance-record'
+    | 'bundle-member';
+  /** The chunk or alias itself; for index rows, marks and records, the missing entry */
+  key: KeystoreKey;
+  /** The entry that should have owned it */
+  owner: KeystoreKey;
+  detail: string;
+}
+
+export interface IntegrityReport {
+  /** Entries looked at */
+  checked: number;
+  issues: IntegrityIssue[];
+}
+
+export interface SecretOptions {
+  /** Defaults to 32 */
+  length?: number;
+  charset?: 'alphanumeric' | 'symbols' | 'hex' | 'unambiguous';
+  /** Explicit character set; takes precedence over `charset` */
+  customCharset?: string;
+  /** Dash-separated group size, e.g. 4 for `abcd-efgh-ijkl` */
+  groups?: number;
+}
+
+export function generateSecret(options?: SecretOptions): string;
+/** `bytes` (default 32) random bytes as lowercase hex */
+export function generateHexToken(bytes?: number): string;
+/** `bytes` (default 32) random bytes as padded base64 */
+export function generateBase64Key(bytes?: number): string;
+export function randomBytes(n: number): Buffer;
+/** `n` (default 32) random bytes; `base64url` is unpadded */
+export function randomToken(encoding: 'hex' | 'base64' | 'base64url', n?: number): string;
+/** `words` (default 6) BIP-39 English words joined by `separator` (default `-`) */
+export function generatePassphrase(words?: number, separator?: string): string;
+
+export interface StrengthEstimate {
+  /** 0 (too guessable) to 4 (very unguessable), as in zxcvbn */
+  score: number;
+  guessesLog10: number;
+  warning?: string;
+  suggestions: string[];
+}
+
+/** `userInputs` are strings the value should not resemble, such as the account name */
+export function estimateStrength(value: string, userInputs?: string[]): StrengthEstimate;
+
+/** Timing-safe equality for signatures, tokens and pairing codes */
+export function constantTimeEquals(a: string | Buffer, b: string | Buffer): boolean;
+
+export interface VersionInfo {
+  version: string;
+  /** `'rustcrypto'`, `'openssl-fips'` (built with `fips`) or `'none'` without the file backend */
+  cryptoProvider: string;
+  /** True only when built with `fips` and the OpenSSL FIPS provider loaded */
+  fips: boolean;
+  /** Cargo features the module was built with */
+  features: string[];
+}
+
+export function versionInfo(): VersionInfo;
+
+/**
+ * Wire format for structured values stored under `service` from now on, for this process. Values
+ * already stored keep theirs and are read either way.
+ */
+export function setStructuredFormat(service: string, format: 'json' | 'cbor' | 'msgpack'): void;
+
+export interface TemplateAuditEvent {
+  reference: string;
+  /** Byte offset of the placeholder in the template */
+  offset: number;
+  /** Milliseconds since the Unix epoch */
+  timestamp: number;
+}
+
+export interface TemplateInterpolation {
+  output: string;
+  audit: TemplateAuditEvent[];
+}
+
+export interface DelegateClaims {
+  /** `service/account` or `service/*` patterns the bearer may read */
+  scope: string[];
+  /** Milliseconds since the Unix epoch */
+  issuedAt: number;
+  expiresAt: number;
+  id: string;
+}
+
+export interface AuditRecord {
+  /** Milliseconds since the Unix epoch */
+  timestamp: number;
+  operation: 'get' | 'set' | 'delete' | 'delete_service' | 'wipe_all' | 'list';
+  service?: string;
+  account?: string;
+  /** `ok` or an error code */
+  outcome: string;
+  backend: string;
+  /** Why a `get` was made, when the caller said */
+  purpose?: string;
+  /** `viewId` of the scoped keystore the call was made through */
+  scope?: string;
+}
+
+export interface ReadOptions {
+  /** Why the value is read, e.g. `irc-connect`; audited and checked against the policy's `allowed_purposes` */
+  purpose?: string;
+  /** Reads past the hot and lock caches; the value read still refreshes them */
+  bypassCache?: boolean;
+}
+
+export type ReadSource =
+  | 'ephemeral'
+  | 'hot-cache'
+  | 'lock-cache'
+  | 'pending-write'
+  | 'systemd-credential'
+  | 'native'
+  | 'fallback';
+
+export interface ResolvedValue {
+  value: string;
+  source: ReadSource;
+}
+
+export interface AuditVerification {
+  intact: boolean;
+  records: number;
+  /** Lines from before the log was chained, which can't be verified */
+  unchainedRecords: number;
+  /** Records covered by a valid signature; later ones could have been appended by anyone */
+  signedRecords: number;
+  /** 1-based line where verification stopped */
+  brokenAt?: number;
+  problem?: string;
+}
+
+export interface KeystoreAuditEvent {
+  type: 'keystore.audit';
+  audit: AuditRecord;
+}
+
+export interface SecretAccess {
+  /** Milliseconds since the Unix epoch */
+  timestamp: number;
+  service: string;
+  account: string;
+  /** As set with `setAccessContext` on the reading thread */
+  caller?: string;
+  purpose?: string;
+  /** Reads of this entry since the process started, this one included */
+  count: number;
+}
+
+export interface KeystoreSecretAccessedEvent {
+  type: 'keystore.secret-accessed';
+  access: SecretAccess;
+}
+
+export type HealthStatus = 'healthy' | 'degraded' | 'unhealthy';
+
+export interface ProbeSample {
+  /** Milliseconds since the Unix epoch */
+  at: number;
+  status: HealthStatus;
+  latencyMs: number;
+}
+
+export interface BackendHealth {
+  backend: string;
+  /** Status at the last probe */
+  status: HealthStatus;
+  /** Recent probes, oldest first */
+  samples: ProbeSample[];
+}
+
+export interface HealthTransition {
+  backend: string;
+  /** Unset on the first probe */
+  from?: HealthStatus;
+  to: HealthStatus;
+}
+
+export interface KeystoreHealthChangedEvent {
+  type: 'keystore.health-changed';
+  health: HealthTransition;
+}
+
+export type KeystoreEvent =
+  | KeystoreAuditEvent
+  | KeystoreSecretAccessedEvent
+  | KeystoreHealthChangedEvent;
+
+export interface AccessCount {
+  service: string;
+  account: string;
+  count: number;
+}
+
+export type EntryOrigin = 'oauth-flow' | 'manual' | 'import-keytar' | 'import' | 'cli' | 'sync';
+
+export interface EntryProvenance {
+  service: string;
+  account: string;
+  origin: EntryOrigin;
+  component?: string;
+  /** Milliseconds since the Unix epoch */
+  writtenAt: number;
+}
+
+export interface QueueLimits {
+  /** Operations running on the backend at once; 8 by default */
+  concurrency?: number;
+  /** Operations waiting for a slot before further calls throw ERR_BUSY; 256 by default */
+  maxQueued?: number;
+  /** Suggested wait per round of the queue, in the ERR_BUSY message; 100 by default */
+  retryAfterMs?: number;
+}
+
+export interface KdfParams {
+  memoryKib: number;
+  iterations: number;
+  parallelism: number;
+}
+
+export interface LockEvent {
+  locked: boolean;
+  reason: 'manual' | 'idle';
+}
+
+export interface UnlockWaitEvent {
+  type: 'unlock-needed' | 'flushed' | 'expired';
+  /** Operations queued so far, written, or dropped, by `type` */
+  operations: number;
+  /** Queued operations that failed for another reason when written */
+  failed: number;
+}
+
+export interface LatencyBudgetOptions {
+  /** Backend calls slower than this are over budget */
+  budgetMs: number;
+  /** Calls over budget in a row that degrade the backend; 3 by default */
+  degradeAfter?: number;
+  /** Calls within budget in a row that restore it; 3 by default */
+  recoverAfter?: number;
+}
+
+export interface DegradationEvent {
+  backend: string;
+  degraded: boolean;
+  /** Duration of the call that tipped it */
+  latencyMs: number;
+}
+
+export interface HookOptions {
+  phase: 'before' | 'after';
+  /** Methods to run for, e.g. `setPassword` or `deleteMany`; all of them when omitted */
+  operations?: string[];
+  /** Defaults to 1000 */
+  timeoutMs?: number;
+  /** What a hook that throws, refuses or overruns does to the call; defaults to `fail` */
+  onFailure?: 'fail' | 'ignore';
+}
+
+/** Never carries values */
+export interface HookEvent {
+  operation: string;
+  phase: 'before' | 'after';
+  keys: KeystoreKey[];
+  /** The service of `listCredentials` and `deleteService` */
+  service?: string;
+  /** In after hooks, the code of the call's error */
+  error?: string;
+}
+
+/** Return `false` or a reason to refuse the call, or throw; promises are not awaited */
+export type KeystoreHook = (event: HookEvent) => void | boolean | string;
+
+export interface ChannelKeypair {
+  publicKey: Buffer;
+  secretKey: Buffer;
+}
+
+/** One end of an encrypted daemon/overlay channel */
+export class ChannelSession {
+  /** Encrypts as `nonce || ciphertext || tag` */
+  seal(plaintext: Uint8Array): Buffer;
+  open(message: Buffer): Buffer;
+}
+
+/** Ephemeral X25519 keypair for the initiating (overlay) side */
+export function generateChannelKeypair(): ChannelKeypair;
+export function connectChannel(secretKey: Buffer, peerStaticPublicKey: Buffer): ChannelSession;
+
+/** One side of the code-keyed handshake with a companion device */
+export class PairingHandshake {
+  /** Sent to the peer; either side may send first */
+  message(): Buffer;
+  /** Can only be called once */
+  finish(peerMessage: Buffer): PairingSession;
+}
+
+/** A paired connection carrying one transfer; `verify` the peer's confirmation before anything else */
+export class PairingSession {
+  confirmation(): Buffer;
+  /** Throws `ERR_ACCESS_DENIED` if the codes differed */
+  verify(peerConfirmation: Buffer): void;
+  /** Sender: offers keys, without their values */
+  offer(keys: KeystoreKey[]): Buffer;
+  /** Receiver: the offered keys, to ask the user about one by one */
+  readOffer(offer: Buffer): KeystoreKey[];
+  /** Receiver: accepts the offered keys the user agreed to */
+  accept(keys: KeystoreKey[]): Buffer;
+}
+
+/** A fresh code to show on the sending device, as `1234-5678` */
+export function generatePairingCode(): string;
+export function startPairing(role: 'sender' | 'receiver', code: string): PairingHandshake;
+
+/** How two backups differ; values are compared by keyed hash and never included */
+export interface SnapshotDiff {
+  /** Only in the newer backup */
+  added: KeystoreKey[];
+  /** Only in the older backup */
+  removed: KeystoreKey[];
+  changed: KeystoreKey[];
+  unchanged: KeystoreKey[];
+}
+
+/** Compares two `exportBackup` buffers; `newerPassphrase` defaults to `olderPassphrase` */
+export function diffSnapshots(
+  older: Buffer,
+  olderPassphrase: string,
+  newer: Buffer,
+  newerPassphrase?: string,
+): SnapshotDiff;
+
+/** Held while this process refreshes one entry's token */
+export class RefreshLock {
+  /** Lets the next refresher in; idempotent */
+  release(): void;
+}
+
+/**
+ * Waits up to `timeoutMs` (default 30000) for any other process refreshing the same entry,
+ * blocking the event loop meanwhile. Throws `ERR_BUSY` on timeout.
+ */
+export function acquireRefreshLock(service: string, account: string, timeoutMs?: number): RefreshLock;
+
+/**
+ * Exports a span per keystore operation to an OTLP/HTTP collector.
+ * Only present in builds with the `otel` feature.
+ */
+export function initTracing(endpoint: string, serviceName?: string): void;
+/** Flushes pending spans. Only present in builds with the `otel` feature. */
+export function shutdownTracing(): void;
+
+/** Writes one value in pieces; nothing replaces the stored value until `finish` */
+export class BlobWriteStream {
+  /** UTF-8 bytes; a character may be split between calls */
+  write(chunk: Uint8Array): void;
+  finish(): void;
+}
+
+/** Reads one value in pieces, verifying the whole after the last */
+export class BlobReadStream {
+  /** Bytes in the whole value */
+  readonly length: number;
+  /** The next piece, or `null` at the end; throws `ERR_SERIALIZATION` if the value is damaged */
+  read(): Buffer | null;
+}
+
+/** Sees only the services granted to one sandboxed view; other calls throw ERR_ACCESS_DENIED */
+export class ScopedKeystore {
+  readonly viewId: string;
+  setPassword(service: string, account: string, value: string | Uint8Array, options?: WriteOptions): void;
+  getPassword(service: string, account: string, options?: ReadOptions): string;
+  getMany(keys: KeystoreKey[], options?: ReadOptions): Array<string | null>;
+  getMetadata(service: string, account: string): EntryMetadata;
+  hasPassword(service: string, account: string): boolean;
+  getOrCreate(service: string, account: string, length?: number): string;
+  deletePassword(service: string, account: string): void;
+  /** Refused as a whole if any entry is outside the granted services */
+  setMany(entries: KeystoreEntry[]): void;
+  deleteMany(keys: KeystoreKey[]): KeystoreKey[];
+  /** Both entries must be in the granted services */
+  swap(serviceA: string, accountA: string, serviceB: string, accountB: string): void;
+  /** Entries of the granted services only */
+  listKeys(): KeystoreKey[];
+  /** Accounts under `service`, sorted; empty for services outside the scope */
+  listCredentials(service: string): string[];
+  /** Accounts under `service` with their values; empty for services outside the scope */
+  findCredentials(service: string, options?: ReadOptions): Credential[];
 }
 
 export class NapiKeystore {
-  constructor();
+  constructor(options?: KeystoreOptions);
+  /** Picks and connects to the backend now instead of on the first call */
+  initialize(): void;
   
-  setPassword(service: string, account: string, value: string): void;
-  getPassword(service: string, account: string): string;
+  /** A `Uint8Array` or `Buffer` value is UTF-8, read in place; wipe it afterwards with `fill(0)` */
+  setPassword(service: string, account: string, value: string | Uint8Array, options?: WriteOptions): void;
+  getPassword(service: string, account: string, options?: ReadOptions): string;
+  /**
+   * The entry's label, comment and created/updated times, without reading its value. Throws
+   * ERR_KEY_NOT_FOUND for a missing entry; fields the backend doesn't keep are absent.
+   */
+  getMetadata(service: string, account: string): EntryMetadata;
+  /** Whether the entry exists, without returning or, where the backend allows, decrypting it */
+  hasPassword(service: string, account: string): boolean;
+  /**
+   * The entry's value, or a new random one of `length` characters (default 32) stored and
+   * returned if there is none. Processes racing for the same entry all get the same value.
+   */
+  getOrCreate(service: string, account: string, length?: number): string;
+  /** `getPassword` plus the step of the resolution order that answered, for debugging */
+  getPasswordWithSource(service: string, account: string, options?: ReadOptions): ResolvedValue;
+  /** Values in the order of `keys`, `null` for missing entries; batched into a few DBus calls on Linux */
+  getMany(keys: KeystoreKey[], options?: ReadOptions): Array<string | null>;
+  /**
+   * Writes several entries in one call. The encrypted file saves them together, all or none;
+   * other backends write them in order and stop at the first failure.
+   */
+  setMany(entries: KeystoreEntry[]): void;
+  /** Reads everything under `services` plus `keys` up front to warm the caches; no values are returned */
+  prefetch(services: string[], keys?: KeystoreKey[]): PrefetchReport;
   deletePassword(service: string, account: string): void;
+  /** Deletes several entries in one call; returns the keys removed, sorted, skipping missing ones */
+  deleteMany(keys: KeystoreKey[]): KeystoreKey[];
+  /** Accounts stored under `service`, sorted; values are not read, use `getMany` for those */
+  listCredentials(service: string): string[];
+  /** Accounts under `service` with their values, sorted by account; replaces keytar's `findCredentials` */
+  findCredentials(service: string, options?: ReadOptions): Credential[];
+  /** Deletes every account under `service` and returns their names, sorted */
+  deleteService(service: string): string[];
+  /**
+   * Exchanges the values of two existing entries, e.g. to flip the current and previous stream
+   * keys. Throws ERR_KEY_NOT_FOUND, changing nothing, if either is missing. The encrypted file
+   * rewrites both in one save; other backends write one after the other, so a read in between
+   * finds the same value under both, never neither.
+   */
+  swap(serviceA: string, accountA: string, serviceB: string, accountB: string): void;
+  /**
+   * The same calls run on the libuv thread pool instead of the event loop. The caller's access
+   * context, provenance, thread priority and read options carry over to the worker.
+   */
+  initializeAsync(): Promise<void>;
+  /** The value is copied before this returns, so a buffer can be wiped right away */
+  setPasswordAsync(service: string, account: string, value: string | Uint8Array, options?: WriteOptions): Promise<void>;
+  getPasswordAsync(service: string, account: string, options?: ReadOptions): Promise<string>;
+  getManyAsync(keys: KeystoreKey[], options?: ReadOptions): Promise<Array<string | null>>;
+  setManyAsync(entries: KeystoreEntry[]): Promise<void>;
+  deleteManyAsync(keys: KeystoreKey[]): Promise<KeystoreKey[]>;
+  deletePasswordAsync(service: string, account: string): Promise<void>;
+  hasPasswordAsync(service: string, account: string): Promise<boolean>;
+  getOrCreateAsync(service: string, account: string, length?: number): Promise<string>;
+  listCredentialsAsync(service: string): Promise<string[]>;
+  findCredentialsAsync(service: string, options?: ReadOptions): Promise<Credential[]>;
+  /** Keeps the value each overwrite replaces for `retentionSeconds`; omitted turns retention off */
+  setPreviousRetention(retentionSeconds?: number): void;
+  /** The value before the last overwrite, while the retention window lasts */
+  getPrevious(service: string, account: string, options?: ReadOptions): string;
+  /** Writes the previous value back; the value it replaces becomes the previous one */
+  restorePrevious(service: string, account: string): void;
+  /** Deletes expired previous values and returns the entries they belonged to */
+  purgeExpiredPrevious(): KeystoreKey[];
+  /** Deletes the entries whose `expiresAt` has passed; returns their keys */
+  purgeExpired(): KeystoreKey[];
+  /** Lifetime of `ephemeral:` entries written from now on; omitted keeps them until deleted or locked */
+  setEphemeralDefaultTtl(ttlSeconds?: number): void;
+  /** Makes an `ephemeral:` entry expire `ttlSeconds` from now, or never when omitted */
+  setEphemeralTtl(service: string, account: string, ttlSeconds?: number): void;
+  /** Holds writes from other threads until `thaw()`; writes on this thread throw ERR_BUSY */
+  freeze(): void;
+  /** Applies the held writes in arrival order and returns how many there were */
+  thaw(): number;
+  isFrozen(): boolean;
+  /** Makes deletePassword, deleteService and wipeAll refuse to remove the entry */
+  protectEntry(key: KeystoreKey): void;
+  unprotectEntry(key: KeystoreKey): void;
+  listProtected(): KeystoreKey[];
+  /** Single-use token for `forceDelete(key, ...)`, valid for 60 seconds */
+  confirmDelete(key: KeystoreKey): string;
+  forceDelete(key: KeystoreKey, confirmation: string): void;
+  /** `setPassword` refuses to replace the entry while it exists; reads and deletes still work */
+  makeWriteOnce(key: KeystoreKey): void;
+  clearWriteOnce(key: KeystoreKey): void;
+  listWriteOnce(): KeystoreKey[];
+  /** Single-use token for `forceSetPassword(...)` on `key`, valid for 60 seconds */
+  confirmOverwrite(key: KeystoreKey): string;
+  forceSetPassword(service: string, account: string, value: string | Uint8Array, confirmation: string): void;
+  /** Single-use token for `wipeAll`, valid for 60 seconds */
+  confirmWipe(): string;
+  /**
+   * Deletes every entry outside the reserved `streaming-enhancement:*` services; throws
+   * ERR_ACCESS_DENIED while any entry is protected. Confirmed, it deletes everything, reserved
+   * services included.
+   */
+  wipeAll(confirmation?: string): KeystoreKey[];
+  /** Makes `alias` resolve to the existing entry `target` for reads, writes and deletes */
+  addAlias(alias: KeystoreKey, target: KeystoreKey): void;
+  removeAlias(alias: KeystoreKey): void;
+  listAliases(): EntryAlias[];
+  /** Moves an entry to a new key; its aliases and the bundles listing it follow */
+  renameEntry(from: KeystoreKey, to: KeystoreKey): void;
+  /** Names existing entries together, to export, delete or lease as one; throws if `name` is taken */
+  createBundle(name: string, members: KeystoreKey[]): void;
+  addToBundle(name: string, members: KeystoreKey[]): void;
+  /** Takes entries out of a bundle without deleting them */
+  removeFromBundle(name: string, members: KeystoreKey[]): void;
+  /** Forgets a bundle, keeping its entries */
+  deleteBundle(name: string): void;
+  /** Deletes a bundle's entries, then the bundle; returns the keys removed */
+  deleteBundleEntries(name: string): KeystoreKey[];
+  listBundles(): string[];
+  /** Where a bundle's entries are now, sorted */
+  bundleMembers(name: string): KeystoreKey[];
+  /** Entries whose service and account match the globs (`*`, `?`); values are not read */
+  search(servicePattern: string, accountPattern?: string): KeystoreKey[];
   isAvailable(): boolean;
+  backendInfo(): BackendInfo;
+  healthReport(certWarningDays?: number): HealthReport;
+  /**
+   * Probes the backend now and every `intervalSeconds` (30 by default) in the background,
+   * updating the availability metric and raising `keystore.health-changed` events
+   */
+  startHealthProbe(intervalSeconds?: number): void;
+  stopHealthProbe(): void;
+  /** Recent health of each backend probed by `startHealthProbe` */
+  backendHealth(): BackendHealth[];
+  /** `healthReport` plus an integrity audit of every entry; reads everything, so don't poll it */
+  doctorReport(certWarningDays?: number): HealthReport;
+  /** Checks that every entry decrypts and decodes, and that no chunk or alias is orphaned */
+  verifyAll(): IntegrityReport;
+  /** Applies the repairs of `issues` from `verifyAll`; returns how many changed something */
+  repair(issues: IntegrityIssue[]): number;
+  /** Records left without the entry that owned them; changes nothing */
+  findOrphans(): Orphan[];
+  /** Removes orphans from `findOrphans`; returns how many were still there */
+  removeOrphans(orphans: Orphan[]): number;
+  /** Compares the platform keystore with its mirror; throws unless opened with `mirrorToFile` */
+  checkMirror(): MirrorReport;
+  /** Repairs the divergence `checkMirror` reports, the platform keystore winning; returns it */
+  reconcileMirror(): MirrorReport;
+  /**
+   * Tokens other apps stored under a streaming service, without their values. Throws
+   * ERR_ACCESS_DENIED unless `consent` is true; ERR_PLATFORM_NOT_SUPPORTED outside Linux and Windows.
+   */
+  discoverForeignCredentials(consent: boolean): ForeignCredential[];
+  /** Copies a discovered token into `service`/`account`; returns false if that entry exists and `overwrite` isn't set */
+  importForeignCredential(credential: ForeignCredential, service: string, account: string, consent: boolean, overwrite?: boolean): boolean;
+  /**
+   * Service, account, kind, size, backend and provenance of every entry, never values. With
+   * `origin`, only entries last written with that origin.
+   */
+  exportInventory(format?: 'json' | 'csv', origin?: EntryOrigin): string;
+  /**
+   * Entries `filter` selects, encrypted under `passphrase` with Argon2id and XChaCha20-Poly1305.
+   * Each key in `escrowPublicKeys`, and in the managed policy, can also recover the backup: 32-byte
+   * X25519 keys, or 1216-byte hybrid X25519 + ML-KEM-768 keys. Throws ERR_ACCESS_DENIED when
+   * policy disables exports.
+   */
+  exportBackup(passphrase: string, filter?: ExportFilter, escrowPublicKeys?: Buffer[]): Buffer;
+  /** A bundle's entries as a backup `importBackup` restores; throws ERR_ACCESS_DENIED when policy disables exports */
+  exportBundle(name: string, passphrase: string, escrowPublicKeys?: Buffer[]): Buffer;
+  /** Encrypted export of `services` (all if empty) as QR payloads to show in order; the phone scans them in any order */
+  exportQr(services: string[], passphrase: string): string[];
+  /**
+   * JSON support bundle: service, account, kind, size and a salted SHA-256 `value_hash` per entry,
+   * never values. Throws ERR_ACCESS_DENIED when policy disables exports.
+   */
+  exportRedacted(filter?: ExportFilter): string;
+  /** What `importBackup` would do, without writing anything */
+  previewBackupImport(backup: Buffer, passphrase: string): ImportDiff;
+  /** Restores new entries, and changed ones too with `overwrite` */
+  importBackup(backup: Buffer, passphrase: string, overwrite?: boolean): ImportDiff;
+  /**
+   * One entry, with its label, comment and expiry, as `SEKC1:` text encrypted under `passphrase`.
+   * Throws ERR_ACCESS_DENIED when policy disables exports.
+   */
+  exportEntry(service: string, account: string, passphrase: string): string;
+  /** What `importEntry` would do, without writing anything */
+  previewEntryImport(card: string, passphrase: string): ImportDiff;
+  /** Stores the card's entry if it's new, or over a different stored value with `overwrite` */
+  importEntry(card: string, passphrase: string, overwrite?: boolean): ImportDiff;
+  /** What `importEnv` would do; `rules` are `VARIABLE=service/account`, e.g. `TWITCH_*=twitch/{match}` */
+  previewEnvImport(path: string, rules: string[]): EnvImportReport;
+  /** With `shred`, overwrites and deletes the file if every variable in it ended up in the keystore */
+  importEnv(path: string, rules: string[], overwrite?: boolean, shred?: boolean): EnvImportReport;
+  /** Entries two writers of a synced keystore file changed differently, oldest first */
+  listSyncConflicts(): SyncConflict[];
+  /** The value a conflict displaced; the current one is read with `getPassword` */
+  getSyncConflictValue(id: string): string;
+  /** Keeps the current value or restores the displaced one, then drops the conflict */
+  resolveSyncConflict(id: string, keep: 'current' | 'displaced'): void;
+  /** Prometheus text-format operation counters, latencies, cache hits and backend availability */
+  metrics(): string;
+  /** Failed backend calls in the last `windowSeconds` (default 3600, at most a day) by code and backend, most frequent first */
+  errorStats(windowSeconds?: number): ErrorCount[];
+  createMasterKey(name: string): void;
+  ensureMasterKey(name: string): void;
+  /** HKDF-SHA256 subkey of `baseKeyName` for the purpose named by `info` */
+  deriveKey(baseKeyName: string, info: string, length: number): Buffer;
+  deleteMasterKey(name: string): void;
+  /** Streams `src` through XChaCha20-Poly1305 into `dst` using a key derived from `keyName` */
+  encryptFile(keyName: string, src: string, dst: string): void;
+  /** `dst` is only written once every chunk has authenticated */
+  decryptFile(keyName: string, src: string, dst: string): void;
+  /**
+   * Substitutes `keystore://service/account` references in JSON or plain text.
+   * `allowed` limits resolution to `service/account` or `service/*` patterns.
+   */
+  resolveRefs(input: string, allowed?: string[]): string;
+  /** Fills `{{keystore://service/account}}` placeholders from the `allowed` URIs only */
+  interpolateTemplate(template: string, allowed: string[]): TemplateInterpolation;
+  /** Signed token letting an overlay read only `scope` for `ttlSeconds` (at most 24 h) */
+  mintDelegateToken(scope: string[], ttlSeconds: number): string;
+  verifyDelegateToken(token: string): DelegateClaims;
+  getPasswordWithDelegateToken(token: string, service: string, account: string): string;
+  /** Appends a hash-chained, signed JSON line per operation to `path`; values are never written */
+  addAuditLogFile(path: string): void;
+  verifyAuditLog(path: string): AuditVerification;
+  subscribe(callback: (err: Error | null, event: KeystoreEvent) => void): void;
+  /** Caller and purpose reported with reads made on this thread; omitted values clear them */
+  setAccessContext(caller?: string, purpose?: string): void;
+  /** Runs `hook` synchronously around the operations `options` names; returns an id for `removeHook` */
+  addHook(options: HookOptions, hook: KeystoreHook): number;
+  removeHook(id: number): boolean;
+  /** A keystore limited to `allowedServices`, audited with `viewId` as the scope */
+  createScoped(viewId: string, allowedServices: string[]): ScopedKeystore;
+  /** Reads of each entry read since the process started */
+  accessCounts(): AccessCount[];
+  /** Records writes made on this thread from now on as `origin` (default `manual`) and `component` */
+  setProvenance(origin?: EntryOrigin, component?: string): void;
+  /** How an entry was last written, if that was recorded */
+  getProvenance(service: string, account: string): EntryProvenance | null;
+  /** Wipes cached values; other calls fail with ERR_ACCESS_DENIED until `unlock` */
+  lock(): void;
+  /** `passphrase` is required once one has been set with `setLockPassphrase` */
+  unlock(passphrase?: string): void;
+  isLocked(): boolean;
+  unlockMode(): 'session' | 'passphrase';
+  /** Omitting `newPassphrase` removes it and returns to session mode */
+  setLockPassphrase(currentPassphrase?: string, newPassphrase?: string): void;
+  /** Ten one-time codes as `XXXX-XXXX-XXXX-XXXX`, replacing any earlier set; show them once */
+  generateRecoveryCodes(passphrase: string): string[];
+  recoveryCodesRemaining(): number;
+  /** Unlocks and sets `newPassphrase`, using up `code`; returns the codes left */
+  recoverWithCode(code: string, newPassphrase: string): number;
+  /** Locks after `seconds` without keystore activity; 0 or no argument disables auto-lock */
+  setIdleTimeout(seconds?: number): void;
+  /**
+   * Caps the bytes of decrypted values each in-memory cache holds (256 KiB by default). Over
+   * budget, the least recently read values are evicted and overwritten. Usage is reported by
+   * `metrics()` as `keystore_cache_bytes`, `keystore_cache_entries` and
+   * `keystore_cache_evictions_total`.
+   */
+  setCacheBudget(bytes: number): void;
+  /**
+   * Bounds how many operations reach the backend at once and how many may wait for a slot. Calls
+   * beyond that throw ERR_BUSY with `retry after <n> ms` in the message. Omitted fields keep their
+   * current values. Depth is reported by `metrics()` as `keystore_queue_depth`,
+   * `keystore_queue_in_flight` and `keystore_queue_rejected_total`.
+   */
+  setQueueLimits(limits: QueueLimits): void;
+  /**
+   * Marks calls made on this thread (the main thread or a worker) as `interactive`, the default, or
+   * `background`. When calls wait for the backend, interactive ones go first.
+   */
+  setPriority(priority: 'interactive' | 'background'): void;
+  /**
+   * Benchmarks Argon2id on this machine and stores the strongest parameters that derive a key in
+   * about `targetMs`, never weaker than the defaults. The lock passphrase (rehashed on the next
+   * unlock) and new backups use them; call again to re-tune. Blocks for a few times `targetMs`.
+   */
+  tuneKdf(targetMs: number): KdfParams;
+  /**
+   * Serves `service`/`account` from a lock-free in-memory cache from its next read on, for tokens
+   * read on every message. Writes through this keystore keep it current; locking clears it. Use
+   * the stored name, not an alias.
+   */
+  leaseHotSecret(service: string, account: string): void;
+  releaseHotSecret(service: string, account: string): void;
+  /** `leaseHotSecret` for each of a bundle's entries */
+  leaseBundle(name: string): void;
+  releaseBundle(name: string): void;
+  onLockStateChange(callback: (err: Error | null, event: LockEvent) => void): void;
+  /** Queues writes for up to `maxWaitSeconds` (300) while the OS keyring is locked */
+  setUnlockWait(enabled: boolean, maxWaitSeconds?: number): void;
+  onUnlockWait(callback: (err: Error | null, event: UnlockWaitEvent) => void): void;
+  /**
+   * While backend calls keep exceeding `budgetMs`, reads that miss the caches come from the
+   * encrypted-file mirror, or throw ERR_TRANSIENT without one; `null` turns it off
+   */
+  setLatencyBudget(options: LatencyBudgetOptions | null): void;
+  isDegraded(): boolean;
+  onDegradation(callback: (err: Error | null, event: DegradationEvent) => void): void;
+  /** Public half of the static channel key `name`, created on first use */
+  channelPublicKey(name: string): Buffer;
+  acceptChannel(name: string, peerPublicKey: Buffer): ChannelSession;
+  rotateChannelKey(name: string): Buffer;
+  /** Pairing sender: seals the accepted entries, read from this keystore */
+  sendPairedEntries(session: PairingSession, acceptance: Buffer): Buffer;
+  /** Pairing receiver: stores the accepted entries and returns their keys */
+  receivePairedEntries(session: PairingSession, entries: Buffer): KeystoreKey[];
+  /** Timing-safe check against the stored value, which never reaches JS; false if missing */
+  verifyAgainstStored(service: string, account: string, candidate: string): boolean;
+  /** Also flags reuse of the values stored at `related`; those values never reach JS */
+  estimateStrength(value: string, related: KeystoreKey[]): StrengthEstimate;
+
+  /** For values of several megabytes, stored in 64 KiB pieces (smaller where the platform limits) */
+  openWriteStream(service: string, account: string): BlobWriteStream;
+  openReadStream(service: string, account: string): BlobReadStream;
+  saveSecureNote(title: string, body: string | Uint8Array): SecureNoteInfo;
+  getSecureNote(title: string, options?: ReadOptions): string;
+  deleteSecureNote(title: string): void;
+  listSecureNotes(): SecureNoteInfo[];
+  searchSecureNotes(query: string, includeBody?: boolean): SecureNoteInfo[];
+
+  importCertificatePem(name: string, pem: string | Uint8Array): CertificateInfo;
+  importCertificatePkcs12(name: string, data: Uint8Array, password: string): CertificateInfo;
+  getCertificateInfo(name: string): CertificateInfo;
+  getCertificateChain(name: string): string;
+  generateCsr(keyName: string, subject: string, sans: string[]): string;
+  listCertificates(): CertificateInfo[];
+  listExpiringCerts(days: number): CertificateInfo[];
+  deleteCertificate(name: string): void;
 }
\ No newline at end of file
@@ -0,0 +1,71 @@
+use super::error::NapiKeystoreError;
+use super::input::checked_bytes;
+use keystore_core::channel::{self, EphemeralKeypair};
+use napi::bindgen_prelude::{Buffer, Uint8Array};
+use napi::Error;
+use napi_derive::napi;
+
+#[napi(object)]
+pub struct ChannelKeypair {
+    pub public_key: Buffer,
+    pub secret_key: Buffer,
+}
+
+/// One end of an encrypted daemon⇄overlay channel
+#[napi]
+pub struct ChannelSession {
+    inner: channel::ChannelSession,
+}
+
+impl From<channel::ChannelSession> for ChannelSession {
+    fn from(inner: channel::ChannelSession) -> Self {
+        Self { inner }
+    }
+}
+
+#[napi]
+impl ChannelSession {
+    /// Encrypts `plaintext` as `nonce || ciphertext || tag`
+    #[napi]
+    pub fn seal(&self, plaintext: Uint8Array) -> Result<Buffer, Error> {
+        let plaintext = checked_bytes(&plaintext, "Plaintext")?;
+        Ok(self
+            .inner
+            .seal(plaintext)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    #[napi]
+    pub fn open(&self, message: Buffer) -> Result<Buffer, Error> {
+        Ok(self
+            .inner
+            .open(&message)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+}
+
+/// Ephemeral X25519 keypair for the initiating side of a channel
+#[napi]
+pub fn generate_channel_keypair() -> Result<ChannelKeypair, Error> {
+    let keypair = EphemeralKeypair::generate().map_err(NapiKeystoreError::from)?;
+    Ok(ChannelKeypair {
+        public_key: keypair.public_key().to_vec().into(),
+        secret_key: keypair.secret_key().to_vec().into(),
+    })
+}
+
+/// Initiator side: session from an ephemeral secret key and the responder's static public key
+#[napi]
+pub fn connect_channel(
+    secret_key: Buffer,
+    peer_static_public_key: Buffer,
+) -> Result<ChannelSession, Error> {
+    let keypair =
+        EphemeralKeypair::from_secret_bytes(&secret_key).map_err(NapiKeystoreError::from)?;
+    Ok(keypair
+        .connect(&peer_static_public_key)
+        .map_err(NapiKeystoreError::from)?
+        .into())
+}
@@ -1,47 +1,48 @@
+use keystore_core::{ErrorKind, KeystoreError};
+use napi::Error;
 use napi_derive::napi;
-use thiserror::Error;
-
-#[derive(Error, Debug)]
-pub enum KeystoreError {
-    #[error("Platform not supported")]
-    PlatformNotSupported,
-
-    #[error("Key not found: {0}")]
-    KeyNotFound(String),
-
-    #[error("Access denied: {0}")]
-    AccessDenied(String),
-
-    #[error("IO error: {0}")]
-    Io(#[from] std::io::Error),
-
-    #[error("Serialization error: {0}")]
-    Serialization(String),
-
-    #[error("Platform error: {0}")]
-    Platform(String),
-}
 
 #[napi(object)]
 pub struct NapiKeystoreError {
     pub code: String,
+    /// `ErrorKind` of `code`, e.g. `locked` or `not-found`
+    pub kind: String,
     pub message: String,
+    /// Whether the same call may succeed later without the user doing anything
+    pub retryable: bool,
 }
 
 impl From<KeystoreError> for NapiKeystoreError {
     fn from(err: KeystoreError) -> Self {
-        let code = match err {
-            KeystoreError::PlatformNotSupported => "ERR_PLATFORM_NOT_SUPPORTED",
-            KeystoreError::KeyNotFound(_) => "ERR_KEY_NOT_FOUND",
-            KeystoreError::AccessDenied(_) => "ERR_ACCESS_DENIED",
-            KeystoreError::Io(_) => "ERR_IO",
-            KeystoreError::Serialization(_) => "ERR_SERIALIZATION",
-            KeystoreError::Platform(_) => "ERR_PLATFORM",
-        };
-
+        let kind = err.kind();
         NapiKeystoreError {
-            code: code.to_string(),
+            code: err.code().to_string(),
+            kind: kind.as_str().to_string(),
             message: err.to_string(),
+            retryable: kind.is_retryable(),
         }
     }
 }
+
+impl From<NapiKeystoreError> for Error {
+    fn from(err: NapiKeystoreError) -> Self {
+        Error::new(
+            napi::Status::GenericFailure,
+            format!("{}: {}", err.code, err.message),
+        )
+    }
+}
+
+/// Splits the message of an error thrown by this module back into its code, kind and text;
+/// `None` for other errors
+#[napi]
+pub fn parse_keystore_error(message: String) -> Option<NapiKeystoreError> {
+    let (code, text) = message.split_once(": ")?;
+    let kind = ErrorKind::from_code(code)?;
+    Some(NapiKeystoreError {
+        code: code.to_string(),
+        kind: kind.as_str().to_string(),
+        message: text.to_string(),
+        retryable: kind.is_retryable(),
+    })
+}
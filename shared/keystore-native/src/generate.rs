@@ -0,0 +1,81 @@
+use super::error::NapiKeystoreError;
+use keystore_core::generate::{self, Charset, TokenEncoding};
+use napi::bindgen_prelude::Buffer;
+use napi::Error;
+use napi_derive::napi;
+
+#[napi(object)]
+#[derive(Debug, Default)]
+pub struct SecretOptions {
+    /// Defaults to 32
+    pub length: Option<u32>,
+    /// `alphanumeric` (default), `symbols`, `hex` or `unambiguous`
+    pub charset: Option<String>,
+    /// Explicit character set; takes precedence over `charset`
+    pub custom_charset: Option<String>,
+    /// Dash-separated group size, e.g. 4 for `abcd-efgh-ijkl`
+    pub groups: Option<u32>,
+}
+
+impl TryFrom<SecretOptions> for generate::SecretOptions {
+    type Error = NapiKeystoreError;
+
+    fn try_from(options: SecretOptions) -> Result<Self, Self::Error> {
+        let defaults = generate::SecretOptions::default();
+        let charset = match (options.custom_charset, options.charset) {
+            (Some(custom), _) => Charset::Custom(custom),
+            (None, Some(name)) => Charset::from_name(&name)?,
+            (None, None) => defaults.charset,
+        };
+        Ok(generate::SecretOptions {
+            length: options.length.map_or(defaults.length, |l| l as usize),
+            charset,
+            groups: options.groups.map(|g| g as usize),
+        })
+    }
+}
+
+/// `length` of a secret generated for `getOrCreate`, 32 when omitted
+pub(crate) fn secret_length(length: Option<u32>) -> usize {
+    length.map_or(generate::SecretOptions::default().length, |l| l as usize)
+}
+
+#[napi]
+pub fn generate_secret(options: Option<SecretOptions>) -> Result<String, Error> {
+    let options = generate::SecretOptions::try_from(options.unwrap_or_default())?;
+    Ok(generate::generate_secret(&options).map_err(NapiKeystoreError::from)?)
+}
+
+#[napi]
+pub fn generate_hex_token(bytes: Option<u32>) -> Result<String, Error> {
+    Ok(generate::hex_token(bytes.unwrap_or(32) as usize).map_err(NapiKeystoreError::from)?)
+}
+
+#[napi]
+pub fn generate_base64_key(bytes: Option<u32>) -> Result<String, Error> {
+    Ok(generate::base64_key(bytes.unwrap_or(32) as usize).map_err(NapiKeystoreError::from)?)
+}
+
+#[napi]
+pub fn generate_passphrase(words: Option<u32>, separator: Option<String>) -> Result<String, Error> {
+    Ok(generate::passphrase(
+        words.unwrap_or(6) as usize,
+        separator.as_deref().unwrap_or("-"),
+    )
+    .map_err(NapiKeystoreError::from)?)
+}
+
+#[napi]
+pub fn random_bytes(n: u32) -> Result<Buffer, Error> {
+    Ok(generate::random_bytes(n as usize)
+        .map_err(NapiKeystoreError::from)?
+        .into())
+}
+
+/// `n` (default 32) random bytes encoded as `hex`, `base64` or `base64url`
+#[napi]
+pub fn random_token(encoding: String, n: Option<u32>) -> Result<String, Error> {
+    let encoding = TokenEncoding::from_name(&encoding).map_err(NapiKeystoreError::from)?;
+    Ok(generate::random_token(encoding, n.unwrap_or(32) as usize)
+        .map_err(NapiKeystoreError::from)?)
+}
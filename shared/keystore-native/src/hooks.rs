@@ -0,0 +1,342 @@
+//! JavaScript functions run as keystore hooks; see `NapiKeystore.addHook`.
+//!
+//! A hook has to run while the keystore call waits on it, on whichever thread made the call. On
+//! the JavaScript thread that means calling the function directly, since anything queued to that
+//! thread would only run once the call had returned. From the libuv pool it goes through a
+//! threadsafe function and the caller waits for the answer up to the hook's timeout. Both need the
+//! function's return value, so they use the N-API calls underneath napi-rs.
+
+use super::error::NapiKeystoreError;
+use super::KeystoreKey;
+use keystore_core::hooks::{self, FailurePolicy, Hook, HookError, HookPhase};
+use keystore_core::KeystoreError;
+use napi::bindgen_prelude::{FromNapiValue, ToNapiValue};
+use napi::{sys, Error};
+use napi_derive::napi;
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+#[napi(object)]
+pub struct HookOptions {
+    /// `before` or `after`
+    pub phase: String,
+    /// Methods to run for, e.g. `setPassword`; all of them when omitted
+    pub operations: Option<Vec<String>>,
+    pub timeout_ms: Option<u32>,
+    /// `fail` (the default) or `ignore`
+    pub on_failure: Option<String>,
+}
+
+/// Passed to a hook; never carries values
+#[napi(object)]
+pub struct HookEvent {
+    /// The keystore method, e.g. `setPassword` or `deleteMany`
+    pub operation: String,
+    /// `before` or `after`
+    pub phase: String,
+    pub keys: Vec<KeystoreKey>,
+    /// The service of `listCredentials` and `deleteService`
+    pub service: Option<String>,
+    /// In after hooks, the code of the call's error
+    pub error: Option<String>,
+}
+
+/// `set_password` as `setPassword`
+fn camel_case(name: &str) -> String {
+    let mut parts = name.split('_');
+    let mut camel = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+/// `setPassword` as `set_password`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+impl From<&hooks::HookEvent> for HookEvent {
+    fn from(event: &hooks::HookEvent) -> Self {
+        HookEvent {
+            operation: camel_case(event.operation),
+            phase: match event.phase {
+                HookPhase::Before => "before",
+                HookPhase::After => "after",
+            }
+            .to_string(),
+            keys: event.keys.iter().cloned().map(Into::into).collect(),
+            service: event.service.clone(),
+            error: event.error.map(str::to_string),
+        }
+    }
+}
+
+impl TryFrom<HookOptions> for hooks::HookOptions {
+    type Error = NapiKeystoreError;
+
+    fn try_from(options: HookOptions) -> Result<Self, Self::Error> {
+        let invalid = |what: &str, name: &str| {
+            NapiKeystoreError::from(KeystoreError::InvalidInput(format!(
+                "Unknown hook {}: {}",
+                what, name
+            )))
+        };
+        let phase = match options.phase.as_str() {
+            "before" => HookPhase::Before,
+            "after" => HookPhase::After,
+            other => return Err(invalid("phase", other)),
+        };
+        let on_failure = match options.on_failure.as_deref() {
+            None | Some("fail") => FailurePolicy::Fail,
+            Some("ignore") => FailurePolicy::Ignore,
+            Some(other) => return Err(invalid("failure policy", other)),
+        };
+        Ok(hooks::HookOptions {
+            phase,
+            operations: options
+                .operations
+                .unwrap_or_default()
+                .iter()
+                .map(|name| snake_case(name))
+                .collect(),
+            timeout: options
+                .timeout_ms
+                .map_or(hooks::DEFAULT_HOOK_TIMEOUT, |ms| {
+                    Duration::from_millis(ms.into())
+                }),
+            on_failure,
+        })
+    }
+}
+
+/// A JavaScript function argument, valid until the call that received it returns
+pub struct HookFunction {
+    env: sys::napi_env,
+    value: sys::napi_value,
+}
+
+impl FromNapiValue for HookFunction {
+    unsafe fn from_napi_value(env: sys::napi_env, value: sys::napi_value) -> napi::Result<Self> {
+        let mut kind = sys::ValueType::napi_undefined;
+        if sys::napi_typeof(env, value, &mut kind) != sys::Status::napi_ok
+            || kind != sys::ValueType::napi_function
+        {
+            return Err(Error::new(
+                napi::Status::FunctionExpected,
+                "Hook must be a function".to_string(),
+            ));
+        }
+        Ok(Self { env, value })
+    }
+}
+
+fn check(status: sys::napi_status) -> Result<(), String> {
+    if status == sys::Status::napi_ok {
+        Ok(())
+    } else {
+        Err(format!("N-API call failed with status {}", status))
+    }
+}
+
+/// Calls `function` with `event` on the JavaScript thread of `env`. Returning `false` or a
+/// string refuses the call, as does throwing; anything else lets it go ahead.
+///
+/// # Safety
+///
+/// Must run on the thread `env` belongs to, inside a handle scope.
+unsafe fn invoke(
+    env: sys::napi_env,
+    function: sys::napi_value,
+    event: HookEvent,
+) -> Result<(), String> {
+    let argument = HookEvent::to_napi_value(env, event).map_err(|e| e.to_string())?;
+    let mut receiver = ptr::null_mut();
+    check(sys::napi_get_undefined(env, &mut receiver))?;
+    let mut returned = ptr::null_mut();
+    let status = sys::napi_call_function(env, receiver, function, 1, &argument, &mut returned);
+    if status == sys::Status::napi_pending_exception {
+        let mut exception = ptr::null_mut();
+        check(sys::napi_get_and_clear_last_exception(env, &mut exception))?;
+        let mut text = ptr::null_mut();
+        check(sys::napi_coerce_to_string(env, exception, &mut text))?;
+        return Err(String::from_napi_value(env, text).map_err(|e| e.to_string())?);
+    }
+    check(status)?;
+    let mut kind = sys::ValueType::napi_undefined;
+    check(sys::napi_typeof(env, returned, &mut kind))?;
+    match kind {
+        sys::ValueType::napi_string => {
+            Err(String::from_napi_value(env, returned).map_err(|e| e.to_string())?)
+        }
+        sys::ValueType::napi_boolean
+            if !bool::from_napi_value(env, returned).map_err(|e| e.to_string())? =>
+        {
+            Err("Refused".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// An event queued to the JavaScript thread, and where its answer goes
+struct Pending {
+    event: HookEvent,
+    reply: mpsc::Sender<Result<(), String>>,
+}
+
+unsafe extern "C" fn call_queued(
+    env: sys::napi_env,
+    function: sys::napi_value,
+    _context: *mut c_void,
+    data: *mut c_void,
+) {
+    let Pending { event, reply } = *Box::from_raw(data.cast::<Pending>());
+    // Null while the environment shuts down; dropping `reply` tells the caller
+    if env.is_null() {
+        return;
+    }
+    let mut scope = ptr::null_mut();
+    if sys::napi_open_handle_scope(env, &mut scope) != sys::Status::napi_ok {
+        return;
+    }
+    // The caller waiting on the pool marked only its own thread as in a hook
+    let _ = reply.send(hooks::as_hook(|| invoke(env, function, event)));
+    sys::napi_close_handle_scope(env, scope);
+}
+
+/// Runs on the JavaScript thread once the threadsafe function is released
+unsafe extern "C" fn delete_reference(env: sys::napi_env, data: *mut c_void, _hint: *mut c_void) {
+    sys::napi_delete_reference(env, data.cast());
+}
+
+pub(crate) struct JsHook {
+    /// The environment of the thread that registered the hook
+    env: sys::napi_env,
+    thread: ThreadId,
+    function: sys::napi_ref,
+    /// Reaches the function from other threads
+    queued: sys::napi_threadsafe_function,
+}
+
+// SAFETY: `env` and `function` are only used on `thread`, checked on every call, and deleted
+// there by the threadsafe function's finalizer; `queued` may be used from any thread.
+unsafe impl Send for JsHook {}
+unsafe impl Sync for JsHook {}
+
+impl JsHook {
+    pub(crate) fn new(function: HookFunction) -> Result<Self, Error> {
+        let HookFunction { env, value } = function;
+        let failed = |e: String| Error::new(napi::Status::GenericFailure, e);
+        unsafe {
+            let mut reference = ptr::null_mut();
+            check(sys::napi_create_reference(env, value, 1, &mut reference)).map_err(failed)?;
+            let name = String::to_napi_value(env, "keystore-hook".to_string())?;
+            let mut queued = ptr::null_mut();
+            let created = check(sys::napi_create_threadsafe_function(
+                env,
+                value,
+                ptr::null_mut(),
+                name,
+                0,
+                1,
+                reference.cast(),
+                Some(delete_reference),
+                ptr::null_mut(),
+                Some(call_queued),
+                &mut queued,
+            ));
+            if let Err(e) = created {
+                sys::napi_delete_reference(env, reference);
+                return Err(failed(e));
+            }
+            // A registered hook shouldn't keep the process alive
+            sys::napi_unref_threadsafe_function(env, queued);
+            Ok(Self {
+                env,
+                thread: thread::current().id(),
+                function: reference,
+                queued,
+            })
+        }
+    }
+
+    fn call_here(&self, event: HookEvent) -> Result<(), String> {
+        unsafe {
+            let mut scope = ptr::null_mut();
+            check(sys::napi_open_handle_scope(self.env, &mut scope))?;
+            let mut function = ptr::null_mut();
+            let result = check(sys::napi_get_reference_value(
+                self.env,
+                self.function,
+                &mut function,
+            ))
+            .and_then(|()| hooks::as_hook(|| invoke(self.env, function, event)));
+            sys::napi_close_handle_scope(self.env, scope);
+            result
+        }
+    }
+
+    fn call_queued(&self, event: HookEvent, timeout: Duration) -> Result<(), HookError> {
+        let (reply, answer) = mpsc::channel();
+        let data = Box::into_raw(Box::new(Pending { event, reply }));
+        let status = unsafe {
+            sys::napi_call_threadsafe_function(
+                self.queued,
+                data.cast(),
+                sys::ThreadsafeFunctionCallMode::nonblocking,
+            )
+        };
+        if status != sys::Status::napi_ok {
+            drop(unsafe { Box::from_raw(data) });
+            return Err(HookError::Failed(format!(
+                "Failed to reach the JavaScript thread (status {})",
+                status
+            )));
+        }
+        match answer.recv_timeout(timeout) {
+            Ok(result) => result.map_err(HookError::Failed),
+            Err(RecvTimeoutError::Timeout) => Err(HookError::TimedOut),
+            Err(RecvTimeoutError::Disconnected) => Err(HookError::Failed(
+                "The JavaScript thread is shutting down".to_string(),
+            )),
+        }
+    }
+}
+
+impl Hook for JsHook {
+    fn call(&self, event: &hooks::HookEvent, timeout: Duration) -> Result<(), HookError> {
+        let event = HookEvent::from(event);
+        if thread::current().id() == self.thread {
+            self.call_here(event).map_err(HookError::Failed)
+        } else {
+            self.call_queued(event, timeout)
+        }
+    }
+}
+
+impl Drop for JsHook {
+    fn drop(&mut self) {
+        unsafe {
+            sys::napi_release_threadsafe_function(
+                self.queued,
+                sys::ThreadsafeFunctionReleaseMode::release,
+            );
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use super::error::NapiKeystoreError;
+use keystore_core::KeystoreError;
+use napi::bindgen_prelude::{Either, Uint8Array};
+
+/// Text from JS: a string, or UTF-8 in a `Uint8Array` or `Buffer`, which is read in place. Unlike
+/// a string, a byte array can be wiped by the caller once the call returns.
+pub type TextInput = Either<String, Uint8Array>;
+
+/// Most bytes one input may carry; checked before anything is copied or parsed
+pub const MAX_INPUT_SIZE: usize = 16 * 1024 * 1024;
+
+fn check_size(len: usize, what: &str) -> Result<(), NapiKeystoreError> {
+    if len > MAX_INPUT_SIZE {
+        return Err(KeystoreError::InvalidInput(format!(
+            "{} is {} bytes, more than the {} allowed",
+            what, len, MAX_INPUT_SIZE
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Checks the size of a byte array argument up front
+pub(crate) fn checked_bytes<'a>(
+    bytes: &'a Uint8Array,
+    what: &str,
+) -> Result<&'a [u8], NapiKeystoreError> {
+    check_size(bytes.len(), what)?;
+    Ok(bytes)
+}
+
+/// Borrows the input as text without copying it
+pub(crate) fn borrowed_text<'a>(
+    input: &'a TextInput,
+    what: &str,
+) -> Result<&'a str, NapiKeystoreError> {
+    match input {
+        Either::A(text) => {
+            check_size(text.len(), what)?;
+            Ok(text)
+        }
+        Either::B(bytes) => std::str::from_utf8(checked_bytes(bytes, what)?).map_err(|_| {
+            KeystoreError::InvalidInput(format!("{} is not valid UTF-8", what)).into()
+        }),
+    }
+}
+
+/// The input as an owned string, for APIs that take one; the caller zeroizes it after use
+pub(crate) fn owned_text(input: TextInput, what: &str) -> Result<String, NapiKeystoreError> {
+    match input {
+        Either::A(text) => {
+            check_size(text.len(), what)?;
+            Ok(text)
+        }
+        Either::B(_) => borrowed_text(&input, what).map(str::to_string),
+    }
+}
@@ -0,0 +1,2386 @@
+use super::channel::ChannelSession;
+use super::error::NapiKeystoreError;
+use super::generate::secret_length;
+use super::hooks::{HookFunction, HookOptions, JsHook};
+use super::input::{borrowed_text, checked_bytes, owned_text, TextInput};
+use super::pairing::PairingSession;
+use super::scoped::ScopedKeystore;
+use super::stream::{BlobReadStream, BlobWriteStream};
+use super::strength::StrengthEstimate;
+use super::task::KeystoreTask;
+use super::{
+    AccessCount, AuditVerification, BackendHealth, BackendInfo, CertificateInfo, Credential,
+    DegradationEvent, DelegateClaims, EntryAlias, EntryMetadata, EntryProvenance, EnvImportReport,
+    ErrorCount, ExportFilter, ForeignCredential, HealthReport, ImportDiff, IntegrityIssue,
+    IntegrityReport, KdfParams, KeystoreEvent, KeystoreKey, KeystoreOptions, LatencyBudgetOptions,
+    LockEvent, MirrorReport, Orphan, PrefetchReport, QueueLimits, ReadOptions, ResolvedValue,
+    SecureNoteInfo, SyncConflict, TemplateInterpolation, UnlockWaitEvent, WriteOptions,
+};
+use keystore_core::access::{
+    current_access_context, set_access_context, with_access_context, AccessContext, AccessCounters,
+    AccessCountingKeystore,
+};
+use keystore_core::alias::AliasKeystore;
+use keystore_core::audit::{
+    audit_signing_key, verify_audit_log, AuditKeySource, AuditLog, AuditingKeystore, FileSink,
+};
+use keystore_core::backup::{
+    export_backup_with_escrow, export_keys_with_escrow, export_redacted, read_backup,
+};
+use keystore_core::blob::{BlobReader, BlobWriter};
+use keystore_core::bundle::Bundles;
+use keystore_core::card::{export_entry, read_card};
+use keystore_core::casefold::CaseInsensitiveKeystore;
+use keystore_core::certs::CertificateStore;
+use keystore_core::channel::ChannelKeys;
+use keystore_core::conflicts::{displaced_value, list_conflicts, resolve_conflict, Resolution};
+use keystore_core::context::ContextKeystore;
+use keystore_core::delegate::DelegateTokens;
+use keystore_core::discovery::{discover, import_foreign, known_schemas, Consent};
+use keystore_core::dotenv::{read_env_file, shred_file, EnvImport, EnvMappingRule};
+use keystore_core::ephemeral::EphemeralKeystore;
+use keystore_core::escrow::public_key_from_bytes;
+use keystore_core::expiry::ExpiringKeystore;
+use keystore_core::files::FileCipher;
+use keystore_core::freeze::FreezableKeystore;
+use keystore_core::health::{doctor_report, health_report, DEFAULT_CERT_WARNING_DAYS};
+use keystore_core::hooks::HookedKeystore;
+use keystore_core::hot::{HotCache, HotCacheKeystore};
+use keystore_core::import::{apply_import, plan_import};
+use keystore_core::integrity::verify_all;
+use keystore_core::inventory::inventory;
+use keystore_core::keys::MasterKeys;
+use keystore_core::latency::{BudgetConfig, LatencyBudget, LatencyBudgetKeystore};
+use keystore_core::lazy::LazyKeystore;
+use keystore_core::lock::{LockState, LockingKeystore};
+use keystore_core::metrics::{KeystoreMetrics, MetricsKeystore};
+use keystore_core::mirror::MirroredKeystore;
+use keystore_core::namespace::NamespacedKeystore;
+use keystore_core::notes::SecureNotes;
+use keystore_core::orphans::{find_orphans, remove_orphans};
+use keystore_core::platform::backend_for;
+#[cfg(feature = "backend-file")]
+use keystore_core::platform::mirrored_backend_for;
+use keystore_core::policy::{BackendPreference, Policy, PurposeCheckedKeystore};
+use keystore_core::previous::{previous_account, PreviousValueKeystore};
+use keystore_core::probe::{HealthProber, DEFAULT_PROBE_INTERVAL};
+use keystore_core::protect::ProtectedKeystore;
+use keystore_core::provenance::{
+    current_provenance, set_provenance, with_provenance, Origin, ProvenanceContext,
+    ProvenanceKeystore,
+};
+use keystore_core::qr::export_qr_with_escrow;
+use keystore_core::queue::{set_thread_priority, Priority, QueuedKeystore};
+use keystore_core::refs::{interpolate_template, resolve_refs, RefPolicy};
+use keystore_core::reserved::{check_not_reserved, PublicKeystore};
+use keystore_core::resolution::{resolve, with_read_policy, ReadPolicy};
+use keystore_core::search::{find_credentials, search};
+use keystore_core::strength::estimate_strength_against;
+use keystore_core::unlock_wait::{UnlockWaitKeystore, DEFAULT_MAX_WAIT};
+use keystore_core::verify::verify_against_stored;
+use keystore_core::watch::{subscribe, ChangeSubscription};
+use keystore_core::{KeystoreEntry, KeystoreError, KeystoreOperations};
+use napi::bindgen_prelude::{AsyncTask, Buffer, Uint8Array};
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::Error;
+use napi_derive::napi;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use zeroize::{Zeroize, Zeroizing};
+
+#[napi]
+pub struct NapiKeystore {
+    inner: Arc<AuditingKeystore>,
+    /// `inner` without the reserved services, for everything keyed by the caller
+    public: Arc<PublicKeystore>,
+    locking: Arc<LockingKeystore>,
+    aliases: Arc<AliasKeystore>,
+    protected: Arc<ProtectedKeystore>,
+    provenance: Arc<ProvenanceKeystore>,
+    previous: Arc<PreviousValueKeystore>,
+    expiring: Arc<ExpiringKeystore>,
+    freezable: Arc<FreezableKeystore>,
+    ephemeral: Arc<EphemeralKeystore>,
+    hooked: Arc<HookedKeystore>,
+    hot: Arc<HotCache>,
+    queue: Arc<QueuedKeystore>,
+    unlock_wait: Arc<UnlockWaitKeystore>,
+    latency: Arc<LatencyBudget>,
+    metrics: Arc<KeystoreMetrics>,
+    prober: Arc<HealthProber>,
+    audit: Arc<AuditLog>,
+    access: Arc<AccessCounters>,
+    /// Everything below the audit layer, for scoped keystores to audit on their own
+    counted: Arc<AccessCountingKeystore>,
+    policy: Policy,
+    lazy: Arc<LazyKeystore>,
+    /// Set once the backend is built, if mirrored into the encrypted file
+    mirror: Arc<OnceLock<Arc<MirroredKeystore>>>,
+    /// Clears the caches when the platform reports an entry changed; `None` where it can't, or
+    /// until the backend is built
+    _changes: Arc<Mutex<Option<ChangeSubscription>>>,
+}
+
+#[napi]
+impl NapiKeystore {
+    #[napi(constructor)]
+    pub fn new(options: Option<KeystoreOptions>) -> Result<Self, Error> {
+        let options = options.unwrap_or_default();
+        // Managed policy overrides the options passed in
+        let policy = Policy::load().map_err(NapiKeystoreError::from)?;
+        // Picking the backend can mean probing Secret Service, so it waits for the first call
+        let preference = policy.backend;
+        let mirror_to_file = policy
+            .mirror_to_file
+            .or(options.mirror_to_file)
+            .unwrap_or(false);
+        let mirror = Arc::new(OnceLock::new());
+        let mirror_slot = Arc::clone(&mirror);
+        let latency = Arc::new(LatencyBudget::new());
+        let latency_slot = Arc::clone(&latency);
+        let lazy = Arc::new(LazyKeystore::new(move || {
+            let backend = if mirror_to_file {
+                mirrored_backend(preference, &mirror_slot)?
+            } else {
+                backend_for(preference)?
+            };
+            // A degraded backend's reads go to the file it mirrors into, if any
+            if let Some(mirror) = mirror_slot.get() {
+                let mirror = Arc::clone(mirror);
+                latency_slot.set_fallback(move |service, account| {
+                    mirror.secondary().get_password(service, account)
+                });
+            }
+            // Built here, so the backend's own startup isn't timed against the budget
+            let backend = Box::new(LatencyBudgetKeystore::new(
+                backend,
+                Arc::clone(&latency_slot),
+            ));
+            // Directly on the backend, so its misses are checked against other user contexts
+            Ok(Box::new(ContextKeystore::new(backend)) as Box<dyn KeystoreOperations>)
+        }));
+        let mut backend: Box<dyn KeystoreOperations> = Box::new(Arc::clone(&lazy));
+        if let Some(namespace) = options.namespace {
+            // Lowest, so the layers' own bookkeeping entries are namespaced too
+            backend = Box::new(
+                NamespacedKeystore::new(backend, namespace).map_err(NapiKeystoreError::from)?,
+            );
+        }
+        #[cfg(feature = "otel")]
+        let backend = Box::new(keystore_core::telemetry::TracingKeystore::new(backend));
+        // Below the metrics, so writes it queues aren't timed as backend calls
+        let unlock_wait = Arc::new(UnlockWaitKeystore::new(backend));
+        let metrics = Arc::new(KeystoreMetrics::new().map_err(NapiKeystoreError::from)?);
+        // Above the metrics, which then time the backend alone, and below the lock, whose cache
+        // hits never wait for a slot
+        let queue = Arc::new(QueuedKeystore::new(Box::new(MetricsKeystore::new(
+            Box::new(Arc::clone(&unlock_wait)),
+            Arc::clone(&metrics),
+        ))));
+        queue.set_metrics(Arc::clone(&metrics));
+        // Probes the backend past the queue, so a full queue doesn't read as an unhealthy backend
+        let prober = Arc::new(HealthProber::new());
+        prober.add_backend(Box::new(Arc::clone(&unlock_wait)));
+        prober.set_metrics(Arc::clone(&metrics));
+        let locking = Arc::new(LockingKeystore::new(Box::new(Arc::clone(&queue))));
+        locking.set_metrics(Arc::clone(&metrics));
+        // Outermost, so reads answered from the lock cache and refused while locked are audited too
+        let audit = Arc::new(AuditLog::new());
+        for path in &policy.audit_log_paths {
+            let sink = FileSink::open(path).map_err(NapiKeystoreError::from)?;
+            audit.add_sink(Arc::new(sink.signed(audit_key_source(&locking))));
+        }
+        if policy.passphrase_required() {
+            locking.require_passphrase();
+        }
+        // Above the lock, which clears it, so hot reads skip the lock's mutex as well
+        let hot = Arc::new(HotCache::new());
+        hot.set_metrics(Arc::clone(&metrics));
+        let hot_on_lock = Arc::clone(&hot);
+        locking.on_state_change(move |event| {
+            if event.state == LockState::Locked {
+                hot_on_lock.clear();
+            }
+        });
+        // Another process may change an entry behind both caches. Weak, as the stack holds the hook
+        // until the backend is built.
+        let changes = Arc::new(Mutex::new(None));
+        {
+            let changes = Arc::clone(&changes);
+            let locking = Arc::downgrade(&locking);
+            let hot = Arc::downgrade(&hot);
+            lazy.on_ready(move |backend| {
+                let subscription = subscribe(&backend.backend_info(), move || {
+                    if let (Some(locking), Some(hot)) = (locking.upgrade(), hot.upgrade()) {
+                        locking.invalidate_cache();
+                        hot.clear();
+                    }
+                });
+                *changes.lock().unwrap_or_else(|e| e.into_inner()) = subscription.ok();
+            });
+        }
+        let hot_layer = HotCacheKeystore::new(Box::new(Arc::clone(&locking)), Arc::clone(&hot));
+        // Below protection, so forced overwrites are recorded as well
+        let provenance = Arc::new(ProvenanceKeystore::new(Box::new(hot_layer)));
+        let previous = Arc::new(PreviousValueKeystore::new(Box::new(Arc::clone(
+            &provenance,
+        ))));
+        // Above the caches, so an expired entry isn't served from them, and below protection, so
+        // forced writes set or clear the expiry too
+        let expiring = Arc::new(ExpiringKeystore::new(Box::new(Arc::clone(&previous))));
+        // Below aliases and case folding, so protection applies to the entry a name resolves to
+        let protected = Arc::new(ProtectedKeystore::new(Box::new(Arc::clone(&expiring))));
+        let mut stack: Box<dyn KeystoreOperations> = Box::new(PurposeCheckedKeystore::new(
+            Box::new(Arc::clone(&protected)),
+            policy.clone(),
+        ));
+        if policy
+            .case_insensitive
+            .or(options.case_insensitive)
+            .unwrap_or(false)
+        {
+            // Above the lock cache, which is keyed by the account name as given
+            stack = Box::new(CaseInsensitiveKeystore::new(stack));
+        }
+        let aliases = Arc::new(AliasKeystore::new(stack));
+        let freezable = Arc::new(FreezableKeystore::new(Box::new(Arc::clone(&aliases))));
+        // Above the caches and the freeze, none of which ephemeral entries should touch, and wiped
+        // and refused while locked like the rest
+        let lock_guard = Arc::downgrade(&locking);
+        let ephemeral = Arc::new(
+            EphemeralKeystore::new(Box::new(Arc::clone(&freezable))).guarded_by(move || {
+                match lock_guard.upgrade() {
+                    Some(locking) if locking.is_locked() => Err(KeystoreError::AccessDenied(
+                        "Keystore is locked".to_string(),
+                    )),
+                    _ => Ok(()),
+                }
+            }),
+        );
+        let ephemeral_on_lock = Arc::downgrade(&ephemeral);
+        locking.on_state_change(move |event| {
+            if event.state == LockState::Locked {
+                if let Some(ephemeral) = ephemeral_on_lock.upgrade() {
+                    ephemeral.clear();
+                }
+            }
+        });
+        // Below the counters and the scopes, so scoped keystores run the hooks too
+        let hooked = Arc::new(HookedKeystore::new(Box::new(Arc::clone(&ephemeral))));
+        let access = Arc::new(AccessCounters::new());
+        // Counts reads by the name they were made with, alias or not
+        let counted = Arc::new(AccessCountingKeystore::new(
+            Box::new(Arc::clone(&hooked)),
+            Arc::clone(&access),
+        ));
+        let inner = Arc::new(AuditingKeystore::new(
+            Box::new(Arc::clone(&counted)),
+            Arc::clone(&audit),
+        ));
+        let public = Arc::new(PublicKeystore::new(Box::new(Arc::clone(&inner))));
+        Ok(Self {
+            inner,
+            public,
+            locking,
+            aliases,
+            protected,
+            provenance,
+            previous,
+            expiring,
+            freezable,
+            ephemeral,
+            hooked,
+            hot,
+            queue,
+            unlock_wait,
+            latency,
+            metrics,
+            prober,
+            audit,
+            access,
+            counted,
+            policy,
+            lazy,
+            mirror,
+            _changes: changes,
+        })
+    }
+
+    /// Picks and connects to the backend now rather than on the first call, which otherwise pays
+    /// for it (on Linux, up to 2s probing Secret Service). Safe to call more than once.
+    #[napi]
+    pub fn initialize(&self) -> Result<(), Error> {
+        Ok(self.lazy.initialize().map_err(NapiKeystoreError::from)?)
+    }
+
+    /// `value` may be a `Uint8Array` or `Buffer` of UTF-8, which the caller can wipe afterwards
+    #[napi]
+    pub fn set_password(
+        &self,
+        service: String,
+        account: String,
+        value: TextInput,
+        options: Option<WriteOptions>,
+    ) -> Result<(), Error> {
+        let options = options.unwrap_or_default();
+        let mut entry = KeystoreEntry {
+            service,
+            account,
+            value: owned_text(value, "Value")?,
+            label: options.label,
+            comment: options.comment,
+            expires_at: options.expires_at.map(|at| at.max(0) as u64),
+        };
+        let result = self.public.set_password(&entry);
+        entry.value.zeroize();
+        Ok(result.map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn get_password(
+        &self,
+        service: String,
+        account: String,
+        options: Option<ReadOptions>,
+    ) -> Result<String, Error> {
+        Ok(
+            with_read_options(options, || self.public.get_password(&service, &account))
+                .map_err(NapiKeystoreError::from)?,
+        )
+    }
+
+    /// Label, comment and creation and update times of an entry, without reading its value where
+    /// the backend keeps them apart: Credential Manager, the keychain and the encrypted file.
+    /// Elsewhere only existence is checked and every field is missing.
+    #[napi]
+    pub fn get_metadata(&self, service: String, account: String) -> Result<EntryMetadata, Error> {
+        Ok(self
+            .public
+            .get_metadata(&service, &account)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Whether the entry exists, without returning its value or, where the backend allows,
+    /// decrypting it
+    #[napi]
+    pub fn has_password(&self, service: String, account: String) -> Result<bool, Error> {
+        Ok(self
+            .public
+            .has_password(&service, &account)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// The entry's value, or a new random one of `length` characters (32 by default) stored and
+    /// returned if there is none. Safe to race from several processes: all of them get the same
+    /// value.
+    #[napi]
+    pub fn get_or_create(
+        &self,
+        service: String,
+        account: String,
+        length: Option<u32>,
+    ) -> Result<String, Error> {
+        Ok(self
+            .public
+            .get_or_create(&service, &account, secret_length(length))
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Like `getPassword`, also saying which step of the resolution order answered: ephemeral
+    /// entries, the hot cache, the lock cache, a write queued behind a locked keyring, systemd
+    /// credentials, then the backend, native or fallback. For debugging.
+    #[napi]
+    pub fn get_password_with_source(
+        &self,
+        service: String,
+        account: String,
+        options: Option<ReadOptions>,
+    ) -> Result<ResolvedValue, Error> {
+        let resolved = with_read_options(options, || resolve(&self.public, &service, &account))
+            .map_err(NapiKeystoreError::from)?;
+        Ok(ResolvedValue {
+            value: resolved.value,
+            source: resolved.source.as_str().to_string(),
+        })
+    }
+
+    /// Reads several entries at once, `null` for missing ones; on Linux in a fixed number of DBus
+    /// round-trips rather than one per entry
+    #[napi]
+    pub fn get_many(
+        &self,
+        keys: Vec<KeystoreKey>,
+        options: Option<ReadOptions>,
+    ) -> Result<Vec<Option<String>>, Error> {
+        let keys: Vec<keystore_core::KeystoreKey> = keys.into_iter().map(Into::into).collect();
+        Ok(with_read_options(options, || self.public.get_many(&keys))
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Writes several entries in one call; the encrypted file saves them together, all or none
+    #[napi]
+    pub fn set_many(&self, entries: Vec<crate::KeystoreEntry>) -> Result<(), Error> {
+        let mut entries: Vec<KeystoreEntry> = entries.into_iter().map(Into::into).collect();
+        let result = self.public.set_many(&entries);
+        entries.iter_mut().for_each(|entry| entry.value.zeroize());
+        Ok(result.map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Reads every entry under `services`, plus `keys`, a few at a time so the caches hold them
+    /// and any unlock prompts appear once at startup. Returns counts and keys only, no values.
+    #[napi]
+    pub fn prefetch(
+        &self,
+        services: Vec<String>,
+        keys: Option<Vec<KeystoreKey>>,
+    ) -> Result<PrefetchReport, Error> {
+        let keys: Vec<keystore_core::KeystoreKey> = keys
+            .unwrap_or_default()
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        Ok(
+            keystore_core::prefetch::prefetch(&self.public, &services, &keys)
+                .map_err(NapiKeystoreError::from)?
+                .into(),
+        )
+    }
+
+    #[napi]
+    pub fn delete_password(&self, service: String, account: String) -> Result<(), Error> {
+        Ok(self
+            .public
+            .delete_password(&service, &account)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Deletes several entries in one call and returns the keys removed, sorted; missing ones are
+    /// skipped
+    #[napi]
+    pub fn delete_many(&self, keys: Vec<KeystoreKey>) -> Result<Vec<KeystoreKey>, Error> {
+        let keys: Vec<keystore_core::KeystoreKey> = keys.into_iter().map(Into::into).collect();
+        Ok(self
+            .public
+            .delete_many(&keys)
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Accounts stored under `service`, sorted, without reading their values
+    #[napi]
+    pub fn list_credentials(&self, service: String) -> Result<Vec<String>, Error> {
+        Ok(self
+            .public
+            .list_credentials(&service)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Accounts under `service` with their values, sorted by account; keytar's `findCredentials`
+    #[napi]
+    pub fn find_credentials(
+        &self,
+        service: String,
+        options: Option<ReadOptions>,
+    ) -> Result<Vec<Credential>, Error> {
+        Ok(
+            with_read_options(options, || find_credentials(&self.public, &service))
+                .map_err(NapiKeystoreError::from)?
+                .into_iter()
+                .map(Credential::from)
+                .collect(),
+        )
+    }
+
+    /// Removes every account stored under `service`, e.g. when an integration is disconnected
+    #[napi]
+    pub fn delete_service(&self, service: String) -> Result<Vec<String>, Error> {
+        Ok(self
+            .public
+            .delete_service(&service)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Exchanges the values of two existing entries, e.g. to flip the current and previous stream
+    /// keys. One save in the encrypted file; elsewhere two writes, between which both entries hold
+    /// the same value.
+    #[napi]
+    pub fn swap(
+        &self,
+        service_a: String,
+        account_a: String,
+        service_b: String,
+        account_b: String,
+    ) -> Result<(), Error> {
+        let key = |service, account| keystore_core::KeystoreKey { service, account };
+        Ok(self
+            .public
+            .swap(&key(service_a, account_a), &key(service_b, account_b))
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// `initialize` on the thread pool
+    #[napi]
+    pub fn initialize_async(&self) -> AsyncTask<KeystoreTask<()>> {
+        let lazy = Arc::clone(&self.lazy);
+        AsyncTask::new(KeystoreTask::new(move || lazy.initialize()))
+    }
+
+    /// `setPassword` on the thread pool. The value is copied before the call returns, so a buffer
+    /// passed in can be wiped right away.
+    #[napi]
+    pub fn set_password_async(
+        &self,
+        service: String,
+        account: String,
+        value: TextInput,
+        options: Option<WriteOptions>,
+    ) -> Result<AsyncTask<KeystoreTask<()>>, Error> {
+        let options = options.unwrap_or_default();
+        let mut entry = KeystoreEntry {
+            service,
+            account,
+            value: owned_text(value, "Value")?,
+            label: options.label,
+            comment: options.comment,
+            expires_at: options.expires_at.map(|at| at.max(0) as u64),
+        };
+        let inner = Arc::clone(&self.public);
+        Ok(AsyncTask::new(KeystoreTask::new(move || {
+            let result = inner.set_password(&entry);
+            entry.value.zeroize();
+            result
+        })))
+    }
+
+    /// `getPassword` on the thread pool
+    #[napi]
+    pub fn get_password_async(
+        &self,
+        service: String,
+        account: String,
+        options: Option<ReadOptions>,
+    ) -> AsyncTask<KeystoreTask<String>> {
+        let inner = Arc::clone(&self.public);
+        AsyncTask::new(KeystoreTask::new(move || {
+            with_read_options(options, || inner.get_password(&service, &account))
+        }))
+    }
+
+    /// `getMany` on the thread pool
+    #[napi]
+    pub fn get_many_async(
+        &self,
+        keys: Vec<KeystoreKey>,
+        options: Option<ReadOptions>,
+    ) -> AsyncTask<KeystoreTask<Vec<Option<String>>>> {
+        let keys: Vec<keystore_core::KeystoreKey> = keys.into_iter().map(Into::into).collect();
+        let inner = Arc::clone(&self.public);
+        AsyncTask::new(KeystoreTask::new(move || {
+            with_read_options(options, || inner.get_many(&keys))
+        }))
+    }
+
+    /// `setMany` on the thread pool
+    #[napi]
+    pub fn set_many_async(
+        &self,
+        entries: Vec<crate::KeystoreEntry>,
+    ) -> AsyncTask<KeystoreTask<()>> {
+        let mut entries: Vec<KeystoreEntry> = entries.into_iter().map(Into::into).collect();
+        let inner = Arc::clone(&self.public);
+        AsyncTask::new(KeystoreTask::new(move || {
+            let result = inner.set_many(&entries);
+            entries.iter_mut().for_each(|entry| entry.value.zeroize());
+            result
+        }))
+    }
+
+    /// `deleteMany` on the thread pool
+    #[napi]
+    pub fn delete_many_async(
+        &self,
+        keys: Vec<KeystoreKey>,
+    ) -> AsyncTask<KeystoreTask<Vec<KeystoreKey>>> {
+        let keys: Vec<keystore_core::KeystoreKey> = keys.into_iter().map(Into::into).collect();
+        let inner = Arc::clone(&self.public);
+        AsyncTask::new(KeystoreTask::new(move || {
+            Ok(inner
+                .delete_many(&keys)?
+                .into_iter()
+                .map(Into::into)
+                .collect())
+        }))
+    }
+
+    /// `deletePassword` on the thread pool
+    #[napi]
+    pub fn delete_password_async(
+        &self,
+        service: String,
+        account: String,
+    ) -> AsyncTask<KeystoreTask<()>> {
+        let inner = Arc::clone(&self.public);
+        AsyncTask::new(KeystoreTask::new(move || {
+            inner.delete_password(&service, &account)
+        }))
+    }
+
+    /// `hasPassword` on the thread pool
+    #[napi]
+    pub fn has_password_async(
+        &self,
+        service: String,
+        account: String,
+    ) -> AsyncTask<KeystoreTask<bool>> {
+        let inner = Arc::clone(&self.public);
+        AsyncTask::new(KeystoreTask::new(move || {
+            inner.has_password(&service, &account)
+        }))
+    }
+
+    /// `getOrCreate` on the thread pool, which also keeps the event loop free while another
+    /// process holds the entry's lock
+    #[napi]
+    pub fn get_or_create_async(
+        &self,
+        service: String,
+        account: String,
+        length: Option<u32>,
+    ) -> AsyncTask<KeystoreTask<String>> {
+        let inner = Arc::clone(&self.public);
+        AsyncTask::new(KeystoreTask::new(move || {
+            inner.get_or_create(&service, &account, secret_length(length))
+        }))
+    }
+
+    /// `listCredentials` on the thread pool
+    #[napi]
+    pub fn list_credentials_async(&self, service: String) -> AsyncTask<KeystoreTask<Vec<String>>> {
+        let inner = Arc::clone(&self.public);
+        AsyncTask::new(KeystoreTask::new(move || inner.list_credentials(&service)))
+    }
+
+    /// `findCredentials` on the thread pool
+    #[napi]
+    pub fn find_credentials_async(
+        &self,
+        service: String,
+        options: Option<ReadOptions>,
+    ) -> AsyncTask<KeystoreTask<Vec<Credential>>> {
+        let inner = Arc::clone(&self.public);
+        AsyncTask::new(KeystoreTask::new(move || {
+            let entries = with_read_options(options, || find_credentials(&inner, &service))?;
+            Ok(entries.into_iter().map(Credential::from).collect())
+        }))
+    }
+
+    /// Keeps the value each overwrite replaces for `retentionSeconds`, readable with `getPrevious`;
+    /// omitted turns retention off and hides the values kept so far
+    #[napi]
+    pub fn set_previous_retention(&self, retention_seconds: Option<u32>) {
+        self.previous
+            .set_retention(retention_seconds.map(|s| Duration::from_secs(u64::from(s))));
+    }
+
+    /// The value `service`/`account` held before its last overwrite, while the retention window
+    /// lasts. Read through the same checks and audit as `getPassword`.
+    #[napi]
+    pub fn get_previous(
+        &self,
+        service: String,
+        account: String,
+        options: Option<ReadOptions>,
+    ) -> Result<String, Error> {
+        let account = previous_account(&account);
+        Ok(
+            with_read_options(options, || self.public.get_password(&service, &account))
+                .map_err(NapiKeystoreError::from)?,
+        )
+    }
+
+    /// Writes the previous value back, e.g. after a botched token rotation. The value replaced
+    /// becomes the previous one, so a restore can itself be undone.
+    #[napi]
+    pub fn restore_previous(&self, service: String, account: String) -> Result<(), Error> {
+        let value = Zeroizing::new(
+            self.public
+                .get_password(&service, &previous_account(&account))
+                .map_err(NapiKeystoreError::from)?,
+        );
+        let mut entry = KeystoreEntry {
+            service,
+            account,
+            value: value.to_string(),
+            ..Default::default()
+        };
+        let result = self.public.set_password(&entry);
+        entry.value.zeroize();
+        Ok(result.map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Deletes the previous values whose window has passed; returns the entries they belonged to
+    #[napi]
+    pub fn purge_expired_previous(&self) -> Result<Vec<KeystoreKey>, Error> {
+        Ok(self
+            .previous
+            .purge_expired()
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Deletes the entries whose `expiresAt` has passed; returns their keys
+    #[napi]
+    pub fn purge_expired(&self) -> Result<Vec<KeystoreKey>, Error> {
+        Ok(self
+            .expiring
+            .purge_expired()
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Lifetime of `ephemeral:` entries written from now on; omitted keeps them until deleted, the
+    /// keystore locks or the process exits
+    #[napi]
+    pub fn set_ephemeral_default_ttl(&self, ttl_seconds: Option<u32>) {
+        self.ephemeral
+            .set_default_ttl(ttl_seconds.map(|s| Duration::from_secs(u64::from(s))));
+    }
+
+    /// Makes the `ephemeral:` entry expire `ttlSeconds` from now, or not at all when omitted
+    #[napi]
+    pub fn set_ephemeral_ttl(
+        &self,
+        service: String,
+        account: String,
+        ttl_seconds: Option<u32>,
+    ) -> Result<(), Error> {
+        Ok(self
+            .ephemeral
+            .set_ttl(
+                &service,
+                &account,
+                ttl_seconds.map(|s| Duration::from_secs(u64::from(s))),
+            )
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Holds writes from other threads until `thaw`; writes from this thread fail with ERR_BUSY
+    #[napi]
+    pub fn freeze(&self) -> Result<(), Error> {
+        Ok(self.freezable.freeze().map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Applies the held writes in order and returns how many there were
+    #[napi]
+    pub fn thaw(&self) -> Result<u32, Error> {
+        Ok(self.freezable.thaw().map_err(NapiKeystoreError::from)? as u32)
+    }
+
+    #[napi]
+    pub fn is_frozen(&self) -> bool {
+        self.freezable.is_frozen()
+    }
+
+    /// Makes `deletePassword`, `deleteService` and `wipeAll` refuse to remove the entry
+    #[napi]
+    pub fn protect_entry(&self, key: KeystoreKey) -> Result<(), Error> {
+        Ok(self
+            .protected
+            .protect(&key.into())
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn unprotect_entry(&self, key: KeystoreKey) -> Result<(), Error> {
+        Ok(self
+            .protected
+            .unprotect(&key.into())
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn list_protected(&self) -> Result<Vec<KeystoreKey>, Error> {
+        Ok(self
+            .protected
+            .protected_keys()
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(KeystoreKey::from)
+            .collect())
+    }
+
+    /// Single-use token for `force_delete` of `key`, valid for a minute
+    #[napi]
+    pub fn confirm_delete(&self, key: KeystoreKey) -> Result<String, Error> {
+        Ok(self
+            .protected
+            .confirm_delete(&key.into())
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn force_delete(&self, key: KeystoreKey, confirmation: String) -> Result<(), Error> {
+        check_not_reserved(&key.service).map_err(NapiKeystoreError::from)?;
+        Ok(self
+            .protected
+            .force_delete(&key.into(), &confirmation)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Makes `setPassword` refuse to replace the entry while it exists; it need not exist yet
+    #[napi]
+    pub fn make_write_once(&self, key: KeystoreKey) -> Result<(), Error> {
+        Ok(self
+            .protected
+            .make_write_once(&key.into())
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn clear_write_once(&self, key: KeystoreKey) -> Result<(), Error> {
+        Ok(self
+            .protected
+            .clear_write_once(&key.into())
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn list_write_once(&self) -> Result<Vec<KeystoreKey>, Error> {
+        Ok(self
+            .protected
+            .write_once_keys()
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(KeystoreKey::from)
+            .collect())
+    }
+
+    /// Single-use token for `force_set_password` of `key`, valid for a minute
+    #[napi]
+    pub fn confirm_overwrite(&self, key: KeystoreKey) -> Result<String, Error> {
+        Ok(self
+            .protected
+            .confirm_overwrite(&key.into())
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Replaces a write-once entry; use the stored name, not an alias
+    #[napi]
+    pub fn force_set_password(
+        &self,
+        service: String,
+        account: String,
+        value: TextInput,
+        confirmation: String,
+    ) -> Result<(), Error> {
+        check_not_reserved(&service).map_err(NapiKeystoreError::from)?;
+        let mut entry = KeystoreEntry {
+            service,
+            account,
+            value: owned_text(value, "Value")?,
+            ..Default::default()
+        };
+        let result = self.protected.force_set(&entry, &confirmation);
+        entry.value.zeroize();
+        Ok(result.map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Single-use token letting `wipe_all` remove protected entries, valid for a minute
+    #[napi]
+    pub fn confirm_wipe(&self) -> Result<String, Error> {
+        Ok(self
+            .protected
+            .confirm_wipe()
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Deletes every entry outside the keystore's own `streaming-enhancement:*` services; refused
+    /// while any entry is protected. With `confirmation` the protected entries go too, and so does
+    /// the keystore's own state: aliases, protection marks, the lock's verifier.
+    #[napi]
+    pub fn wipe_all(&self, confirmation: Option<String>) -> Result<Vec<KeystoreKey>, Error> {
+        let wiped = match confirmation {
+            Some(confirmation) => {
+                self.protected
+                    .authorize_wipe(&confirmation)
+                    .map_err(NapiKeystoreError::from)?;
+                self.inner.wipe_all()
+            }
+            None => self.public.wipe_all(),
+        };
+        Ok(wiped
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(KeystoreKey::from)
+            .collect())
+    }
+
+    /// Makes `alias` resolve to the existing entry `target` for reads, writes and deletes
+    #[napi]
+    pub fn add_alias(&self, alias: KeystoreKey, target: KeystoreKey) -> Result<(), Error> {
+        check_not_reserved(&alias.service).map_err(NapiKeystoreError::from)?;
+        check_not_reserved(&target.service).map_err(NapiKeystoreError::from)?;
+        let target = target.into();
+        self.change_aliases("add_alias", alias.into(), move |aliases, alias| {
+            aliases.add_alias(alias, &target)
+        })
+    }
+
+    #[napi]
+    pub fn remove_alias(&self, alias: KeystoreKey) -> Result<(), Error> {
+        self.change_aliases("remove_alias", alias.into(), |aliases, alias| {
+            aliases.remove_alias(alias)
+        })
+    }
+
+    #[napi]
+    pub fn list_aliases(&self) -> Result<Vec<EntryAlias>, Error> {
+        Ok(self
+            .aliases
+            .aliases()
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(|(alias, target)| EntryAlias {
+                alias: alias.into(),
+                target: target.into(),
+            })
+            .collect())
+    }
+
+    /// Moves an entry to a new key; aliases of it and bundles listing it follow
+    #[napi]
+    pub fn rename_entry(&self, from: KeystoreKey, to: KeystoreKey) -> Result<(), Error> {
+        check_not_reserved(&from.service).map_err(NapiKeystoreError::from)?;
+        check_not_reserved(&to.service).map_err(NapiKeystoreError::from)?;
+        let to: keystore_core::KeystoreKey = to.into();
+        let from = {
+            let to = to.clone();
+            self.change_aliases("rename", from.into(), move |aliases, from| {
+                aliases.rename(from, &to)
+            })?
+        };
+        Ok(Bundles::new(&self.inner)
+            .entry_renamed(&from, &to)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Names existing entries together as `name`, to export, delete or lease as one
+    #[napi]
+    pub fn create_bundle(&self, name: String, members: Vec<KeystoreKey>) -> Result<(), Error> {
+        for member in &members {
+            check_not_reserved(&member.service).map_err(NapiKeystoreError::from)?;
+        }
+        let members: Vec<_> = members.into_iter().map(Into::into).collect();
+        Ok(Bundles::new(&self.inner)
+            .create(&name, &members)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn add_to_bundle(&self, name: String, members: Vec<KeystoreKey>) -> Result<(), Error> {
+        for member in &members {
+            check_not_reserved(&member.service).map_err(NapiKeystoreError::from)?;
+        }
+        let members: Vec<_> = members.into_iter().map(Into::into).collect();
+        Ok(Bundles::new(&self.inner)
+            .add(&name, &members)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Takes entries out of a bundle without deleting them
+    #[napi]
+    pub fn remove_from_bundle(&self, name: String, members: Vec<KeystoreKey>) -> Result<(), Error> {
+        for member in &members {
+            check_not_reserved(&member.service).map_err(NapiKeystoreError::from)?;
+        }
+        let members: Vec<_> = members.into_iter().map(Into::into).collect();
+        Ok(Bundles::new(&self.inner)
+            .remove(&name, &members)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Forgets a bundle, keeping its entries
+    #[napi]
+    pub fn delete_bundle(&self, name: String) -> Result<(), Error> {
+        Ok(Bundles::new(&self.inner)
+            .delete(&name)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Deletes a bundle's entries, then the bundle; returns the keys removed
+    #[napi]
+    pub fn delete_bundle_entries(&self, name: String) -> Result<Vec<KeystoreKey>, Error> {
+        let bundles = Bundles::new(&self.inner);
+        for member in bundles.members(&name).map_err(NapiKeystoreError::from)? {
+            check_not_reserved(&member.service).map_err(NapiKeystoreError::from)?;
+        }
+        Ok(bundles
+            .delete_entries(&name)
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    #[napi]
+    pub fn list_bundles(&self) -> Result<Vec<String>, Error> {
+        Ok(Bundles::new(&self.inner)
+            .names()
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Where a bundle's entries are now, sorted
+    #[napi]
+    pub fn bundle_members(&self, name: String) -> Result<Vec<KeystoreKey>, Error> {
+        Ok(Bundles::new(&self.inner)
+            .members(&name)
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Entries matching both glob patterns (`*` and `?`); `account_pattern` defaults to `*`
+    #[napi]
+    pub fn search(
+        &self,
+        service_pattern: String,
+        account_pattern: Option<String>,
+    ) -> Result<Vec<KeystoreKey>, Error> {
+        Ok(search(
+            &self.public,
+            &service_pattern,
+            account_pattern.as_deref().unwrap_or("*"),
+        )
+        .map_err(NapiKeystoreError::from)?
+        .into_iter()
+        .map(KeystoreKey::from)
+        .collect())
+    }
+
+    #[napi]
+    pub fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    #[napi]
+    pub fn backend_info(&self) -> BackendInfo {
+        self.policy.annotate(self.inner.backend_info()).into()
+    }
+
+    /// Prometheus text-format metrics for a `/metrics` endpoint; refreshes the availability gauge
+    #[napi]
+    pub fn metrics(&self) -> Result<String, Error> {
+        self.inner.is_available();
+        Ok(self.metrics.render().map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Failed backend calls in the last `window_seconds` (default an hour, at most a day) by error
+    /// code and backend, most frequent first. Missing entries aren't counted.
+    #[napi]
+    pub fn error_stats(&self, window_seconds: Option<u32>) -> Vec<ErrorCount> {
+        let window = Duration::from_secs(window_seconds.unwrap_or(3600).into());
+        self.metrics
+            .error_stats(window)
+            .into_iter()
+            .map(ErrorCount::from)
+            .collect()
+    }
+
+    #[napi]
+    pub fn health_report(&self, cert_warning_days: Option<u32>) -> HealthReport {
+        health_report(
+            &self.inner,
+            cert_warning_days.unwrap_or(DEFAULT_CERT_WARNING_DAYS),
+        )
+        .into()
+    }
+
+    /// Probes the backend now and every `intervalSeconds` (30 by default) on a background thread,
+    /// keeping its recent health, updating the availability metric and raising
+    /// `keystore.health-changed` events. Calling it again changes the interval.
+    #[napi]
+    pub fn start_health_probe(&self, interval_seconds: Option<u32>) {
+        self.prober
+            .start(interval_seconds.map_or(DEFAULT_PROBE_INTERVAL, |s| {
+                Duration::from_secs(s.max(1) as u64)
+            }));
+    }
+
+    /// Stops `startHealthProbe`; the health recorded so far is kept
+    #[napi]
+    pub fn stop_health_probe(&self) {
+        self.prober.stop();
+    }
+
+    /// Recent health of each backend probed by `startHealthProbe`
+    #[napi]
+    pub fn backend_health(&self) -> Vec<BackendHealth> {
+        self.prober
+            .backends()
+            .into_iter()
+            .filter_map(|backend| {
+                let status = self.prober.status(&backend)?;
+                Some(BackendHealth {
+                    samples: self
+                        .prober
+                        .history(&backend)
+                        .into_iter()
+                        .map(Into::into)
+                        .collect(),
+                    backend,
+                    status: status.as_str().to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// `health_report` plus an integrity audit of every entry. Reads everything, so don't poll it.
+    #[napi]
+    pub fn doctor_report(&self, cert_warning_days: Option<u32>) -> HealthReport {
+        doctor_report(
+            &self.inner,
+            cert_warning_days.unwrap_or(DEFAULT_CERT_WARNING_DAYS),
+        )
+        .into()
+    }
+
+    /// Checks that every entry decrypts and decodes, and that no chunk or alias is orphaned
+    #[napi]
+    pub fn verify_all(&self) -> Result<IntegrityReport, Error> {
+        Ok(verify_all(&self.inner)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Applies the repairs of `issues`, as returned by `verify_all`; returns how many changed
+    /// something
+    #[napi]
+    pub fn repair(&self, issues: Vec<IntegrityIssue>) -> Result<u32, Error> {
+        self.remove_orphans(
+            issues
+                .into_iter()
+                .filter_map(|issue| issue.orphan)
+                .collect(),
+        )
+    }
+
+    /// Chunks, aliases, index rows, protection marks and provenance records left without the entry
+    /// that owned them. Changes nothing.
+    #[napi]
+    pub fn find_orphans(&self) -> Result<Vec<Orphan>, Error> {
+        Ok(find_orphans(&self.inner)
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(Orphan::from)
+            .collect())
+    }
+
+    /// Removes `orphans` as returned by `find_orphans`; returns how many were still there
+    #[napi]
+    pub fn remove_orphans(&self, orphans: Vec<Orphan>) -> Result<u32, Error> {
+        let orphans = orphans
+            .into_iter()
+            .map(keystore_core::orphans::Orphan::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(NapiKeystoreError::from)?;
+        let removed = remove_orphans(&self.inner, &orphans);
+        // The tables were edited below the layers caching them
+        self.aliases.reload();
+        self.protected.reload();
+        self.provenance.reload();
+        Ok(removed.map_err(NapiKeystoreError::from)? as u32)
+    }
+
+    /// Compares the platform keystore with its encrypted-file mirror; changes nothing. Fails unless
+    /// the keystore was opened with `mirrorToFile`.
+    #[napi]
+    pub fn check_mirror(&self) -> Result<MirrorReport, Error> {
+        Ok(self
+            .mirror()?
+            .check()
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Copies what either side of the mirror misses from the other, and the platform keystore's
+    /// value where they differ; returns the divergence found
+    #[napi]
+    pub fn reconcile_mirror(&self) -> Result<MirrorReport, Error> {
+        let report = self.mirror()?.reconcile();
+        // Entries changed below the caches and the layers keeping tables
+        self.locking.invalidate_cache();
+        self.hot.clear();
+        self.aliases.reload();
+        self.protected.reload();
+        self.provenance.reload();
+        Ok(report.map_err(NapiKeystoreError::from)?.into())
+    }
+
+    /// Tokens other applications stored under a streaming service, found without reading their
+    /// values. `consent` must be `true`: ask the user before searching their other apps' secrets.
+    #[napi]
+    pub fn discover_foreign_credentials(
+        &self,
+        consent: bool,
+    ) -> Result<Vec<ForeignCredential>, Error> {
+        Ok(discover(&known_schemas(), consented(consent)?)
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(ForeignCredential::from)
+            .collect())
+    }
+
+    /// Copies a credential from `discoverForeignCredentials` into `service`/`account`, unless
+    /// that entry exists and `overwrite` isn't set; returns whether it was written
+    #[napi]
+    pub fn import_foreign_credential(
+        &self,
+        credential: ForeignCredential,
+        service: String,
+        account: String,
+        consent: bool,
+        overwrite: Option<bool>,
+    ) -> Result<bool, Error> {
+        let consent = consented(consent)?;
+        check_not_reserved(&service).map_err(NapiKeystoreError::from)?;
+        Ok(import_foreign(
+            &self.inner,
+            &known_schemas(),
+            &credential.into(),
+            &keystore_core::KeystoreKey { service, account },
+            overwrite.unwrap_or(false),
+            consent,
+        )
+        .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Every stored entry without its value, as `json` (default) or `csv`; with `origin`, only
+    /// those last written with that origin
+    #[napi]
+    pub fn export_inventory(
+        &self,
+        format: Option<String>,
+        origin: Option<String>,
+    ) -> Result<String, Error> {
+        let mut report = inventory(&self.inner).map_err(NapiKeystoreError::from)?;
+        if let Some(origin) = origin {
+            report = report.filter_origin(Origin::parse(&origin).map_err(NapiKeystoreError::from)?);
+        }
+        Ok(match format.as_deref().unwrap_or("json") {
+            "json" => report.to_json().map_err(NapiKeystoreError::from)?,
+            "csv" => report.to_csv(),
+            other => {
+                return Err(NapiKeystoreError::from(KeystoreError::InvalidInput(format!(
+                    "Unknown inventory format: {}",
+                    other
+                )))
+                .into())
+            }
+        })
+    }
+
+    /// The entries `filter` selects (all by default), encrypted under `passphrase` and
+    /// recoverable with any of `escrow_public_keys`
+    #[napi]
+    pub fn export_backup(
+        &self,
+        passphrase: String,
+        filter: Option<ExportFilter>,
+        escrow_public_keys: Option<Vec<Buffer>>,
+    ) -> Result<Buffer, Error> {
+        self.policy
+            .check_export()
+            .map_err(NapiKeystoreError::from)?;
+        let filter = filter.unwrap_or_default().into();
+        let mut escrow_keys = self.policy.escrow_keys().map_err(NapiKeystoreError::from)?;
+        for key in escrow_public_keys.unwrap_or_default() {
+            escrow_keys.push(public_key_from_bytes(&key).map_err(NapiKeystoreError::from)?);
+        }
+        Ok(
+            export_backup_with_escrow(&self.inner, &passphrase, &filter, &escrow_keys)
+                .map_err(NapiKeystoreError::from)?
+                .into(),
+        )
+    }
+
+    /// A bundle's entries as a backup encrypted under `passphrase`, which `importBackup` restores
+    /// on another machine
+    #[napi]
+    pub fn export_bundle(
+        &self,
+        name: String,
+        passphrase: String,
+        escrow_public_keys: Option<Vec<Buffer>>,
+    ) -> Result<Buffer, Error> {
+        self.policy
+            .check_export()
+            .map_err(NapiKeystoreError::from)?;
+        let members = Bundles::new(&self.inner)
+            .members(&name)
+            .map_err(NapiKeystoreError::from)?;
+        let mut escrow_keys = self.policy.escrow_keys().map_err(NapiKeystoreError::from)?;
+        for key in escrow_public_keys.unwrap_or_default() {
+            escrow_keys.push(public_key_from_bytes(&key).map_err(NapiKeystoreError::from)?);
+        }
+        Ok(
+            export_keys_with_escrow(&self.inner, &passphrase, &members, &escrow_keys)
+                .map_err(NapiKeystoreError::from)?
+                .into(),
+        )
+    }
+
+    /// The entries under `services` (all if empty), encrypted under `passphrase` and split into
+    /// QR code payloads, in the order to show them
+    #[napi]
+    pub fn export_qr(
+        &self,
+        services: Vec<String>,
+        passphrase: String,
+    ) -> Result<Vec<String>, Error> {
+        self.policy
+            .check_export()
+            .map_err(NapiKeystoreError::from)?;
+        let escrow_keys = self.policy.escrow_keys().map_err(NapiKeystoreError::from)?;
+        Ok(
+            export_qr_with_escrow(&self.inner, &services, &passphrase, &escrow_keys)
+                .map_err(NapiKeystoreError::from)?,
+        )
+    }
+
+    /// JSON support bundle of the entries `filter` selects, with salted hashes instead of values
+    #[napi]
+    pub fn export_redacted(&self, filter: Option<ExportFilter>) -> Result<String, Error> {
+        self.policy
+            .check_export()
+            .map_err(NapiKeystoreError::from)?;
+        let filter = filter.unwrap_or_default().into();
+        let export = export_redacted(&self.inner, &filter).map_err(NapiKeystoreError::from)?;
+        Ok(export.to_json().map_err(NapiKeystoreError::from)?)
+    }
+
+    /// What `import_backup` would do, without writing anything
+    #[napi]
+    pub fn preview_backup_import(
+        &self,
+        backup: Buffer,
+        passphrase: String,
+    ) -> Result<ImportDiff, Error> {
+        let entries = read_backup(&backup, &passphrase).map_err(NapiKeystoreError::from)?;
+        Ok(plan_import(&self.public, &entries)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Restores new entries from a backup, and changed ones too with `overwrite`. Entries under
+    /// reserved services are reported as conflicts and not written.
+    #[napi]
+    pub fn import_backup(
+        &self,
+        backup: Buffer,
+        passphrase: String,
+        overwrite: Option<bool>,
+    ) -> Result<ImportDiff, Error> {
+        let entries = read_backup(&backup, &passphrase).map_err(NapiKeystoreError::from)?;
+        Ok(
+            imported(|| apply_import(&self.public, &entries, overwrite.unwrap_or(false)))
+                .map_err(NapiKeystoreError::from)?
+                .into(),
+        )
+    }
+
+    /// One entry, with its label, comment and expiry, as a line of text encrypted under
+    /// `passphrase`, for handing a single token to another machine
+    #[napi]
+    pub fn export_entry(
+        &self,
+        service: String,
+        account: String,
+        passphrase: String,
+    ) -> Result<String, Error> {
+        self.policy
+            .check_export()
+            .map_err(NapiKeystoreError::from)?;
+        Ok(export_entry(&self.public, &service, &account, &passphrase)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// What `import_entry` would do with a card, without writing anything
+    #[napi]
+    pub fn preview_entry_import(
+        &self,
+        card: String,
+        passphrase: String,
+    ) -> Result<ImportDiff, Error> {
+        let card = read_card(&card, &passphrase).map_err(NapiKeystoreError::from)?;
+        Ok(plan_import(&self.public, &[card.entry])
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Stores the entry in a card from `export_entry` if it's new, or replaces a different stored
+    /// value with `overwrite`
+    #[napi]
+    pub fn import_entry(
+        &self,
+        card: String,
+        passphrase: String,
+        overwrite: Option<bool>,
+    ) -> Result<ImportDiff, Error> {
+        let card = read_card(&card, &passphrase).map_err(NapiKeystoreError::from)?;
+        Ok(
+            imported(|| apply_import(&self.public, &[card.entry], overwrite.unwrap_or(false)))
+                .map_err(NapiKeystoreError::from)?
+                .into(),
+        )
+    }
+
+    /// What `import_env` would do with the `.env` file at `path`, given `VARIABLE=service/account`
+    /// mapping rules such as `TWITCH_*=twitch/{match}`
+    #[napi]
+    pub fn preview_env_import(
+        &self,
+        path: String,
+        rules: Vec<String>,
+    ) -> Result<EnvImportReport, Error> {
+        let import = read_env_import(&path, &rules)?;
+        Ok(EnvImportReport {
+            diff: plan_import(&self.public, &import.entries)
+                .map_err(NapiKeystoreError::from)?
+                .into(),
+            unmapped: import.unmapped,
+            shredded: false,
+        })
+    }
+
+    /// Imports the mapped variables of a `.env` file, replacing changed entries with `overwrite`.
+    /// With `shred`, the file is then overwritten and deleted, but only if every variable in it
+    /// ended up in the keystore.
+    #[napi]
+    pub fn import_env(
+        &self,
+        path: String,
+        rules: Vec<String>,
+        overwrite: Option<bool>,
+        shred: Option<bool>,
+    ) -> Result<EnvImportReport, Error> {
+        let overwrite = overwrite.unwrap_or(false);
+        let import = read_env_import(&path, &rules)?;
+        let diff = imported(|| apply_import(&self.public, &import.entries, overwrite))
+            .map_err(NapiKeystoreError::from)?;
+        let shredded = shred.unwrap_or(false) && import.is_fully_imported(&diff, overwrite);
+        if shredded {
+            shred_file(Path::new(&path)).map_err(NapiKeystoreError::from)?;
+        }
+        Ok(EnvImportReport {
+            diff: diff.into(),
+            unmapped: import.unmapped,
+            shredded,
+        })
+    }
+
+    /// Entries two writers of a synced keystore file changed differently, oldest first
+    #[napi]
+    pub fn list_sync_conflicts(&self) -> Result<Vec<SyncConflict>, Error> {
+        Ok(list_conflicts(&self.inner)
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(SyncConflict::from)
+            .collect())
+    }
+
+    /// The value a conflict displaced
+    #[napi]
+    pub fn get_sync_conflict_value(&self, id: String) -> Result<String, Error> {
+        Ok(displaced_value(&self.inner, &id)
+            .map_err(NapiKeystoreError::from)?
+            .to_string())
+    }
+
+    /// Keeps the current value (`current`) or restores the displaced one (`displaced`)
+    #[napi]
+    pub fn resolve_sync_conflict(&self, id: String, keep: String) -> Result<(), Error> {
+        let resolution = match keep.as_str() {
+            "current" => Resolution::KeepCurrent,
+            "displaced" => Resolution::UseDisplaced,
+            other => {
+                return Err(NapiKeystoreError::from(KeystoreError::InvalidInput(format!(
+                    "Unknown conflict resolution: {}",
+                    other
+                )))
+                .into())
+            }
+        };
+        Ok(resolve_conflict(&self.inner, &id, resolution).map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn create_master_key(&self, name: String) -> Result<(), Error> {
+        Ok(MasterKeys::new(&self.inner)
+            .create(&name)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn ensure_master_key(&self, name: String) -> Result<(), Error> {
+        Ok(MasterKeys::new(&self.inner)
+            .ensure(&name)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// HKDF-SHA256 subkey of the master key `base_key_name` for the purpose named by `info`.
+    /// The native copy is wiped once it has been handed to JS.
+    #[napi]
+    pub fn derive_key(
+        &self,
+        base_key_name: String,
+        info: String,
+        length: u32,
+    ) -> Result<Buffer, Error> {
+        let derived = MasterKeys::new(&self.inner)
+            .derive(&base_key_name, info.as_bytes(), length as usize)
+            .map_err(NapiKeystoreError::from)?;
+        Ok(derived.as_slice().into())
+    }
+
+    #[napi]
+    pub fn delete_master_key(&self, name: String) -> Result<(), Error> {
+        Ok(MasterKeys::new(&self.inner)
+            .delete(&name)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Encrypts `src` into `dst` in constant memory with a key derived from the master key
+    /// `key_name`
+    #[napi]
+    pub fn encrypt_file(&self, key_name: String, src: String, dst: String) -> Result<(), Error> {
+        Ok(FileCipher::new(&self.inner)
+            .encrypt_file(&key_name, Path::new(&src), Path::new(&dst))
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn decrypt_file(&self, key_name: String, src: String, dst: String) -> Result<(), Error> {
+        Ok(FileCipher::new(&self.inner)
+            .decrypt_file(&key_name, Path::new(&src), Path::new(&dst))
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Substitutes `keystore://service/account` references in `input` (JSON or plain text).
+    /// `allowed` restricts resolution to `service/account` or `service/*` patterns.
+    #[napi]
+    pub fn resolve_refs(
+        &self,
+        input: String,
+        allowed: Option<Vec<String>>,
+    ) -> Result<String, Error> {
+        let policy = allowed.map_or_else(RefPolicy::allow_all, RefPolicy::allow);
+        Ok(resolve_refs(&self.public, &input, &policy).map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Fills `{{keystore://service/account}}` placeholders from the `allowed` references only,
+    /// returning one audit event per substitution
+    #[napi]
+    pub fn interpolate_template(
+        &self,
+        template: String,
+        allowed: Vec<String>,
+    ) -> Result<TemplateInterpolation, Error> {
+        Ok(interpolate_template(&self.public, &template, &allowed)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Signs a token that lets an overlay read only the secrets matching `scope` for
+    /// `ttl_seconds`
+    #[napi]
+    pub fn mint_delegate_token(
+        &self,
+        scope: Vec<String>,
+        ttl_seconds: u32,
+    ) -> Result<String, Error> {
+        Ok(DelegateTokens::new(&self.inner)
+            .mint(&scope, Duration::from_secs(u64::from(ttl_seconds)))
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn verify_delegate_token(&self, token: String) -> Result<DelegateClaims, Error> {
+        Ok(DelegateTokens::new(&self.inner)
+            .verify(&token)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Reads an entry on behalf of a delegate token's bearer, if it is inside the token's scope
+    #[napi]
+    pub fn get_password_with_delegate_token(
+        &self,
+        token: String,
+        service: String,
+        account: String,
+    ) -> Result<String, Error> {
+        Ok(DelegateTokens::new(&self.inner)
+            .get_password(&token, &service, &account)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Appends a hash-chained, signed JSON line per keystore operation to `path`; values are never
+    /// written
+    #[napi]
+    pub fn add_audit_log_file(&self, path: String) -> Result<(), Error> {
+        let sink = FileSink::open(Path::new(&path)).map_err(NapiKeystoreError::from)?;
+        self.audit
+            .add_sink(Arc::new(sink.signed(audit_key_source(&self.locking))));
+        Ok(())
+    }
+
+    /// Checks the hash chain and signatures of an audit log written by `addAuditLogFile`
+    #[napi]
+    pub fn verify_audit_log(&self, path: String) -> Result<AuditVerification, Error> {
+        let key = audit_signing_key(self.locking.as_ref()).map_err(NapiKeystoreError::from)?;
+        Ok(verify_audit_log(Path::new(&path), &key)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Delivers keystore events to `callback`: `keystore.audit` for every operation,
+    /// `keystore.secret-accessed` for every value handed out and `keystore.health-changed` when a
+    /// probe finds the backend's health changed
+    #[napi]
+    pub fn subscribe(&self, callback: ThreadsafeFunction<KeystoreEvent>) {
+        let callback = Arc::new(callback);
+        let audit_callback = Arc::clone(&callback);
+        self.audit.add_sink(Arc::new(
+            move |record: &keystore_core::audit::AuditRecord| {
+                audit_callback.call(
+                    Ok(KeystoreEvent::audit(record.clone())),
+                    ThreadsafeFunctionCallMode::NonBlocking,
+                );
+            },
+        ));
+        let access_callback = Arc::clone(&callback);
+        self.access.on_access(move |access| {
+            access_callback.call(
+                Ok(KeystoreEvent::secret_accessed(access.clone())),
+                ThreadsafeFunctionCallMode::NonBlocking,
+            );
+        });
+        self.prober.on_transition(move |transition| {
+            callback.call(
+                Ok(KeystoreEvent::health_changed(transition.clone())),
+                ThreadsafeFunctionCallMode::NonBlocking,
+            );
+        });
+    }
+
+    /// Runs `hook` before or after the operations `options` names, on the thread making the call,
+    /// and returns the id `removeHook` takes. See "Operation hooks" in the README.
+    #[napi]
+    pub fn add_hook(&self, options: HookOptions, hook: HookFunction) -> Result<u32, Error> {
+        let options = keystore_core::hooks::HookOptions::try_from(options)?;
+        let id = self.hooked.add_hook(options, Arc::new(JsHook::new(hook)?));
+        Ok(id as u32)
+    }
+
+    /// Whether a hook with `id` was registered
+    #[napi]
+    pub fn remove_hook(&self, id: u32) -> bool {
+        self.hooked.remove_hook(id.into())
+    }
+
+    /// A keystore that sees only `allowedServices`, for a plugin sandbox such as a `!command`.
+    /// Its calls, including refused ones, are audited with `viewId` as their scope.
+    #[napi]
+    pub fn create_scoped(
+        &self,
+        view_id: String,
+        allowed_services: Vec<String>,
+    ) -> Result<ScopedKeystore, Error> {
+        if view_id.is_empty() {
+            return Err(NapiKeystoreError::from(KeystoreError::InvalidInput(
+                "View id must not be empty".to_string(),
+            ))
+            .into());
+        }
+        let scoped = keystore_core::scope::ScopedKeystore::new(
+            Box::new(PublicKeystore::new(Box::new(Arc::clone(&self.counted)))),
+            allowed_services,
+        );
+        let audited = AuditingKeystore::new(Box::new(scoped), Arc::clone(&self.audit))
+            .with_scope(view_id.clone());
+        Ok(ScopedKeystore::new(view_id, Arc::new(audited)))
+    }
+
+    /// Names who reads secrets on this JavaScript thread, and why, in `keystore.secret-accessed`
+    /// events from now on. Omitted values clear them.
+    #[napi]
+    pub fn set_access_context(&self, caller: Option<String>, purpose: Option<String>) {
+        set_access_context(AccessContext { caller, purpose });
+    }
+
+    /// Reads of each entry read since the process started
+    #[napi]
+    pub fn access_counts(&self) -> Vec<AccessCount> {
+        self.access
+            .counts()
+            .into_iter()
+            .map(|(key, count)| AccessCount {
+                service: key.service,
+                account: key.account,
+                count: count as i64,
+            })
+            .collect()
+    }
+
+    /// Records writes made on this JavaScript thread from now on as coming from `origin`
+    /// (`oauth-flow`, `manual`, `import-keytar`, `import`, `cli` or `sync`) and `component`.
+    /// Omitted values reset them to `manual` and none.
+    #[napi]
+    pub fn set_provenance(
+        &self,
+        origin: Option<String>,
+        component: Option<String>,
+    ) -> Result<(), Error> {
+        let origin = match origin {
+            Some(origin) => Origin::parse(&origin).map_err(NapiKeystoreError::from)?,
+            None => Origin::default(),
+        };
+        set_provenance(ProvenanceContext { origin, component });
+        Ok(())
+    }
+
+    /// How an entry was last written, if that was recorded
+    #[napi]
+    pub fn get_provenance(
+        &self,
+        service: String,
+        account: String,
+    ) -> Result<Option<EntryProvenance>, Error> {
+        Ok(self
+            .provenance
+            .provenance(&service, &account)
+            .map_err(NapiKeystoreError::from)?
+            .map(EntryProvenance::from))
+    }
+
+    /// Wipes cached values and refuses keystore access until `unlock`
+    #[napi]
+    pub fn lock(&self) {
+        self.locking.lock()
+    }
+
+    /// `passphrase` is required once one has been set with `setLockPassphrase`
+    #[napi]
+    pub fn unlock(&self, passphrase: Option<String>) -> Result<(), Error> {
+        Ok(self
+            .locking
+            .unlock(passphrase.as_deref())
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn is_locked(&self) -> bool {
+        self.locking.is_locked()
+    }
+
+    /// `session` or `passphrase`
+    #[napi]
+    pub fn unlock_mode(&self) -> Result<String, Error> {
+        Ok(self
+            .locking
+            .unlock_mode()
+            .map_err(NapiKeystoreError::from)?
+            .name()
+            .to_string())
+    }
+
+    /// Sets, changes or (with no `newPassphrase`) removes the unlock passphrase
+    #[napi]
+    pub fn set_lock_passphrase(
+        &self,
+        current_passphrase: Option<String>,
+        new_passphrase: Option<String>,
+    ) -> Result<(), Error> {
+        Ok(self
+            .locking
+            .set_passphrase(current_passphrase.as_deref(), new_passphrase.as_deref())
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Issues ten one-time recovery codes, replacing any earlier set. Needs the current passphrase.
+    #[napi]
+    pub fn generate_recovery_codes(&self, passphrase: String) -> Result<Vec<String>, Error> {
+        Ok(self
+            .locking
+            .generate_recovery_codes(&passphrase)
+            .map_err(NapiKeystoreError::from)?
+            .iter()
+            .map(|code| code.to_string())
+            .collect())
+    }
+
+    #[napi]
+    pub fn recovery_codes_remaining(&self) -> Result<u32, Error> {
+        Ok(self
+            .locking
+            .recovery_codes_remaining()
+            .map_err(NapiKeystoreError::from)? as u32)
+    }
+
+    /// Unlocks with a recovery code in place of a forgotten passphrase and sets `newPassphrase`.
+    /// Returns how many codes remain.
+    #[napi]
+    pub fn recover_with_code(&self, code: String, new_passphrase: String) -> Result<u32, Error> {
+        Ok(self
+            .locking
+            .recover(&code, &new_passphrase)
+            .map_err(NapiKeystoreError::from)? as u32)
+    }
+
+    /// Locks after `seconds` without keystore activity; 0 or no argument disables auto-lock
+    #[napi]
+    pub fn set_idle_timeout(&self, seconds: Option<u32>) {
+        self.locking.set_idle_timeout(
+            seconds
+                .filter(|&s| s > 0)
+                .map(|s| Duration::from_secs(s as u64)),
+        )
+    }
+
+    /// Benchmarks Argon2id for about `target_ms` per derivation, stores the parameters for the
+    /// lock passphrase and backups, and returns them. Blocks for a few times `target_ms`.
+    #[napi]
+    pub fn tune_kdf(&self, target_ms: u32) -> Result<KdfParams, Error> {
+        Ok(self
+            .locking
+            .tune_kdf(Duration::from_millis(target_ms as u64))
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Caps the bytes of decrypted values each in-memory cache holds, evicting the least recently
+    /// read first; 256 KiB by default
+    #[napi]
+    pub fn set_cache_budget(&self, bytes: u32) {
+        self.locking.set_cache_budget(bytes as usize);
+        self.hot.set_budget(bytes as usize);
+    }
+
+    /// Bounds how many operations reach the backend at once and how many may wait; calls beyond
+    /// that throw ERR_BUSY with a retry-after hint. Omitted fields keep their current values.
+    #[napi]
+    pub fn set_queue_limits(&self, limits: QueueLimits) -> Result<(), Error> {
+        let current = self.queue.limits().map_err(NapiKeystoreError::from)?;
+        self.queue
+            .set_limits(keystore_core::queue::QueueLimits {
+                concurrency: limits
+                    .concurrency
+                    .map_or(current.concurrency, |n| n as usize),
+                max_queued: limits.max_queued.map_or(current.max_queued, |n| n as usize),
+                retry_after: limits
+                    .retry_after_ms
+                    .map_or(current.retry_after, |ms| Duration::from_millis(ms as u64)),
+            })
+            .map_err(NapiKeystoreError::from)?;
+        Ok(())
+    }
+
+    /// Marks calls made on this JavaScript thread as `interactive` (the default) or `background`.
+    /// Waiting interactive calls take the next free backend slot ahead of background ones.
+    #[napi]
+    pub fn set_priority(&self, priority: String) -> Result<(), Error> {
+        let priority = match priority.as_str() {
+            "interactive" => Priority::Interactive,
+            "background" => Priority::Background,
+            other => {
+                return Err(NapiKeystoreError::from(KeystoreError::InvalidInput(format!(
+                    "Unknown priority: {}",
+                    other
+                )))
+                .into())
+            }
+        };
+        set_thread_priority(priority);
+        Ok(())
+    }
+
+    /// Keeps `service`/`account` in the lock-free hot cache from its next read on, for secrets read
+    /// on every message. Use the stored name, not an alias.
+    #[napi]
+    pub fn lease_hot_secret(&self, service: String, account: String) -> Result<(), Error> {
+        check_not_reserved(&service).map_err(NapiKeystoreError::from)?;
+        Ok(self
+            .hot
+            .lease(&service, &account)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn release_hot_secret(&self, service: String, account: String) -> Result<(), Error> {
+        Ok(self
+            .hot
+            .release(&service, &account)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// `leaseHotSecret` for each of a bundle's entries
+    #[napi]
+    pub fn lease_bundle(&self, name: String) -> Result<(), Error> {
+        for key in Bundles::new(&self.inner)
+            .members(&name)
+            .map_err(NapiKeystoreError::from)?
+        {
+            self.hot
+                .lease(&key.service, &key.account)
+                .map_err(NapiKeystoreError::from)?;
+        }
+        Ok(())
+    }
+
+    #[napi]
+    pub fn release_bundle(&self, name: String) -> Result<(), Error> {
+        for key in Bundles::new(&self.inner)
+            .members(&name)
+            .map_err(NapiKeystoreError::from)?
+        {
+            self.hot
+                .release(&key.service, &key.account)
+                .map_err(NapiKeystoreError::from)?;
+        }
+        Ok(())
+    }
+
+    /// Calls `callback` on every lock and unlock, including idle auto-locks
+    #[napi]
+    pub fn on_lock_state_change(&self, callback: ThreadsafeFunction<LockEvent>) {
+        self.locking.on_state_change(move |event| {
+            callback.call(Ok(event.into()), ThreadsafeFunctionCallMode::NonBlocking);
+        });
+    }
+
+    /// Instead of throwing ERR_KEYRING_LOCKED while the OS keyring is locked, as right after
+    /// autologin, queues writes and deletes for up to `maxWaitSeconds` (300 by default) and writes
+    /// them once it is unlocked. `false` stops queueing new ones.
+    #[napi]
+    pub fn set_unlock_wait(&self, enabled: bool, max_wait_seconds: Option<u32>) {
+        self.unlock_wait.set_max_wait(
+            enabled.then(|| {
+                max_wait_seconds.map_or(DEFAULT_MAX_WAIT, |s| Duration::from_secs(s as u64))
+            }),
+        );
+    }
+
+    /// Calls `callback` with `unlock-needed` when operations start queueing behind a locked
+    /// keyring, then `flushed` or `expired`
+    #[napi]
+    pub fn on_unlock_wait(&self, callback: ThreadsafeFunction<UnlockWaitEvent>) {
+        self.unlock_wait.on_event(move |event| {
+            callback.call(Ok(event.into()), ThreadsafeFunctionCallMode::NonBlocking);
+        });
+    }
+
+    /// Serves reads that miss the caches from the encrypted-file mirror, or fails them with
+    /// `ERR_TRANSIENT`, while backend calls keep taking longer than `options.budgetMs`; `null`
+    /// turns the budget off
+    #[napi]
+    pub fn set_latency_budget(&self, options: Option<LatencyBudgetOptions>) {
+        self.latency.set_config(options.map(|options| {
+            let defaults = BudgetConfig::new(Duration::from_millis(options.budget_ms.into()));
+            BudgetConfig {
+                degrade_after: options.degrade_after.unwrap_or(defaults.degrade_after),
+                recover_after: options.recover_after.unwrap_or(defaults.recover_after),
+                ..defaults
+            }
+        }));
+    }
+
+    /// Whether the backend is over its latency budget and reads are kept off it
+    #[napi]
+    pub fn is_degraded(&self) -> bool {
+        self.latency.is_degraded()
+    }
+
+    /// Calls `callback` when the backend is degraded and when it recovers
+    #[napi]
+    pub fn on_degradation(&self, callback: ThreadsafeFunction<DegradationEvent>) {
+        self.latency.on_change(move |event| {
+            callback.call(Ok(event.into()), ThreadsafeFunctionCallMode::NonBlocking);
+        });
+    }
+
+    /// Public half of the daemon's static channel key `name`, created on first use
+    #[napi]
+    pub fn channel_public_key(&self, name: String) -> Result<Buffer, Error> {
+        Ok(ChannelKeys::new(&self.inner)
+            .public_key(&name)
+            .map_err(NapiKeystoreError::from)?
+            .to_vec()
+            .into())
+    }
+
+    /// Responder side: session from the static key `name` and the overlay's ephemeral public key
+    #[napi]
+    pub fn accept_channel(
+        &self,
+        name: String,
+        peer_public_key: Buffer,
+    ) -> Result<ChannelSession, Error> {
+        Ok(ChannelKeys::new(&self.inner)
+            .accept(&name, &peer_public_key)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Sender side of pairing: seals the entries the receiver accepted, read from this keystore
+    #[napi]
+    pub fn send_paired_entries(
+        &self,
+        session: &mut PairingSession,
+        acceptance: Buffer,
+    ) -> Result<Buffer, Error> {
+        Ok(session
+            .inner
+            .send_entries(&self.public, &acceptance)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Receiver side of pairing: stores the accepted entries and returns their keys
+    #[napi]
+    pub fn receive_paired_entries(
+        &self,
+        session: &PairingSession,
+        entries: Buffer,
+    ) -> Result<Vec<KeystoreKey>, Error> {
+        Ok(session
+            .inner
+            .receive_entries(&self.public, &entries)
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(KeystoreKey::from)
+            .collect())
+    }
+
+    #[napi]
+    pub fn rotate_channel_key(&self, name: String) -> Result<Buffer, Error> {
+        Ok(ChannelKeys::new(&self.inner)
+            .rotate(&name)
+            .map_err(NapiKeystoreError::from)?
+            .to_vec()
+            .into())
+    }
+
+    /// Timing-safe check of `candidate` against the stored value, which is never returned
+    #[napi]
+    pub fn verify_against_stored(
+        &self,
+        service: String,
+        account: String,
+        candidate: String,
+    ) -> Result<bool, Error> {
+        Ok(
+            verify_against_stored(&self.public, &service, &account, &candidate)
+                .map_err(NapiKeystoreError::from)?,
+        )
+    }
+
+    /// Strength check that also flags reuse of the values stored at `related`, which never
+    /// leave native code
+    #[napi]
+    pub fn estimate_strength(
+        &self,
+        value: String,
+        related: Vec<KeystoreKey>,
+    ) -> Result<StrengthEstimate, Error> {
+        let related: Vec<(&str, &str)> = related
+            .iter()
+            .map(|key| (key.service.as_str(), key.account.as_str()))
+            .collect();
+        Ok(estimate_strength_against(&self.public, &value, &related)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Starts writing a value of several megabytes in pieces; see `BlobWriteStream`
+    #[napi]
+    pub fn open_write_stream(&self, service: String, account: String) -> BlobWriteStream {
+        BlobWriteStream::new(BlobWriter::new(
+            Arc::clone(&self.public) as Arc<dyn KeystoreOperations>,
+            &service,
+            &account,
+        ))
+    }
+
+    /// Reads a value written with `openWriteStream` a piece at a time
+    #[napi]
+    pub fn open_read_stream(
+        &self,
+        service: String,
+        account: String,
+    ) -> Result<BlobReadStream, Error> {
+        Ok(BlobReadStream::new(
+            BlobReader::open(
+                Arc::clone(&self.public) as Arc<dyn KeystoreOperations>,
+                &service,
+                &account,
+            )
+            .map_err(NapiKeystoreError::from)?,
+        ))
+    }
+
+    #[napi]
+    pub fn save_secure_note(
+        &self,
+        title: String,
+        body: TextInput,
+    ) -> Result<SecureNoteInfo, Error> {
+        let body = borrowed_text(&body, "Note body")?;
+        Ok(SecureNotes::new(&self.inner)
+            .save(&title, body)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    #[napi]
+    pub fn get_secure_note(
+        &self,
+        title: String,
+        options: Option<ReadOptions>,
+    ) -> Result<String, Error> {
+        Ok(
+            with_read_options(options, || SecureNotes::new(&self.inner).get(&title))
+                .map_err(NapiKeystoreError::from)?,
+        )
+    }
+
+    #[napi]
+    pub fn delete_secure_note(&self, title: String) -> Result<(), Error> {
+        Ok(SecureNotes::new(&self.inner)
+            .delete(&title)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn list_secure_notes(&self) -> Result<Vec<SecureNoteInfo>, Error> {
+        Ok(SecureNotes::new(&self.inner)
+            .list()
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(SecureNoteInfo::from)
+            .collect())
+    }
+
+    #[napi]
+    pub fn search_secure_notes(
+        &self,
+        query: String,
+        include_body: Option<bool>,
+    ) -> Result<Vec<SecureNoteInfo>, Error> {
+        Ok(SecureNotes::new(&self.inner)
+            .search(&query, include_body.unwrap_or(false))
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(SecureNoteInfo::from)
+            .collect())
+    }
+
+    #[napi]
+    pub fn import_certificate_pem(
+        &self,
+        name: String,
+        pem: TextInput,
+    ) -> Result<CertificateInfo, Error> {
+        let pem = borrowed_text(&pem, "PEM input")?;
+        Ok(CertificateStore::new(&self.inner)
+            .import_pem(&name, pem)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    #[napi]
+    pub fn import_certificate_pkcs12(
+        &self,
+        name: String,
+        data: Uint8Array,
+        password: String,
+    ) -> Result<CertificateInfo, Error> {
+        let password = Zeroizing::new(password);
+        let data = checked_bytes(&data, "PKCS#12 input")?;
+        Ok(CertificateStore::new(&self.inner)
+            .import_pkcs12(&name, data, &password)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    #[napi]
+    pub fn get_certificate_info(&self, name: String) -> Result<CertificateInfo, Error> {
+        Ok(CertificateStore::new(&self.inner)
+            .info(&name)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    #[napi]
+    pub fn get_certificate_chain(&self, name: String) -> Result<String, Error> {
+        Ok(CertificateStore::new(&self.inner)
+            .chain_pem(&name)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn generate_csr(
+        &self,
+        key_name: String,
+        subject: String,
+        sans: Vec<String>,
+    ) -> Result<String, Error> {
+        Ok(CertificateStore::new(&self.inner)
+            .generate_csr(&key_name, &subject, &sans)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn list_certificates(&self) -> Result<Vec<CertificateInfo>, Error> {
+        Ok(CertificateStore::new(&self.inner)
+            .list()
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(CertificateInfo::from)
+            .collect())
+    }
+
+    #[napi]
+    pub fn list_expiring_certs(&self, days: u32) -> Result<Vec<CertificateInfo>, Error> {
+        Ok(CertificateStore::new(&self.inner)
+            .list_expiring_certs(days)
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(CertificateInfo::from)
+            .collect())
+    }
+
+    #[napi]
+    pub fn delete_certificate(&self, name: String) -> Result<(), Error> {
+        Ok(CertificateStore::new(&self.inner)
+            .delete(&name)
+            .map_err(NapiKeystoreError::from)?)
+    }
+}
+
+impl NapiKeystore {
+    /// The mirror, building the backend first if needed
+    fn mirror(&self) -> Result<Arc<MirroredKeystore>, Error> {
+        self.lazy.initialize().map_err(NapiKeystoreError::from)?;
+        Ok(self.mirror.get().cloned().ok_or_else(|| {
+            NapiKeystoreError::from(KeystoreError::InvalidInput(
+                "The keystore is not mirrored; open it with mirrorToFile".to_string(),
+            ))
+        })?)
+    }
+
+    /// Alias changes go to `aliases` directly, below the freeze and the audit, so this waits for
+    /// `thaw` like any write and audits the change under `key`
+    fn change_aliases<T: Send + 'static>(
+        &self,
+        operation: &str,
+        key: keystore_core::KeystoreKey,
+        change: impl FnOnce(&AliasKeystore, &keystore_core::KeystoreKey) -> Result<T, KeystoreError>
+            + Send
+            + 'static,
+    ) -> Result<T, Error> {
+        let aliases = Arc::clone(&self.aliases);
+        let audited = key.clone();
+        Ok(self
+            .inner
+            .audit(operation, &audited, || {
+                self.freezable.write(move |_| change(&aliases, &key))
+            })
+            .map_err(NapiKeystoreError::from)?)
+    }
+}
+
+/// Refuses with `ERR_ACCESS_DENIED` unless the caller says the user agreed
+fn consented(consent: bool) -> Result<Consent, Error> {
+    if !consent {
+        return Err(NapiKeystoreError::from(KeystoreError::AccessDenied(
+            "Searching other applications' credentials needs the user's consent".to_string(),
+        ))
+        .into());
+    }
+    Ok(Consent::given())
+}
+
+/// Picks the backend like `backend_for`, keeping the mirror it adds in `slot`
+#[cfg(feature = "backend-file")]
+fn mirrored_backend(
+    preference: Option<BackendPreference>,
+    slot: &OnceLock<Arc<MirroredKeystore>>,
+) -> Result<Box<dyn KeystoreOperations>, KeystoreError> {
+    let (backend, mirror) = mirrored_backend_for(preference)?;
+    let _ = slot.set(mirror);
+    Ok(backend)
+}
+
+#[cfg(not(feature = "backend-file"))]
+fn mirrored_backend(
+    _preference: Option<BackendPreference>,
+    _slot: &OnceLock<Arc<MirroredKeystore>>,
+) -> Result<Box<dyn KeystoreOperations>, KeystoreError> {
+    Err(KeystoreError::PlatformNotSupported)
+}
+
+/// Reads the audit signing key from below the auditing layer, which it would otherwise audit
+fn audit_key_source(locking: &Arc<LockingKeystore>) -> AuditKeySource {
+    let locking = Arc::clone(locking);
+    Box::new(move || audit_signing_key(locking.as_ref()))
+}
+
+/// Runs `f` with the purpose and cache bypass from `options`, where given, in place of the
+/// thread's own
+pub(crate) fn with_read_options<T>(options: Option<ReadOptions>, f: impl FnOnce() -> T) -> T {
+    let ReadOptions {
+        purpose,
+        bypass_cache,
+    } = options.unwrap_or_default();
+    let f = || match bypass_cache {
+        Some(bypass_caches) => with_read_policy(ReadPolicy { bypass_caches }, f),
+        None => f(),
+    };
+    match purpose {
+        Some(purpose) => with_access_context(
+            AccessContext {
+                purpose: Some(purpose),
+                ..current_access_context()
+            },
+            f,
+        ),
+        None => f(),
+    }
+}
+
+fn read_env_import(path: &str, rules: &[String]) -> Result<EnvImport, Error> {
+    let rules = rules
+        .iter()
+        .map(|rule| EnvMappingRule::parse(rule))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(NapiKeystoreError::from)?;
+    Ok(read_env_file(Path::new(path), &rules).map_err(NapiKeystoreError::from)?)
+}
+
+/// Runs an import with its writes recorded as `import`, keeping the thread's component
+fn imported<T>(f: impl FnOnce() -> T) -> T {
+    let context = ProvenanceContext {
+        origin: Origin::Import,
+        component: current_provenance().component,
+    };
+    with_provenance(context, f)
+}
@@ -1,4 +1,5 @@
 use napi_derive::napi;
+use std::collections::HashMap;
 
 #[napi(object)]
 #[derive(Debug)]
@@ -6,9 +7,974 @@ pub struct KeystoreEntry {
     pub service: String,
     pub account: String,
     pub value: String,
+    /// Omitted keeps the stored label; an empty one clears it
+    pub label: Option<String>,
+    /// Omitted keeps the stored comment; an empty one clears it
+    pub comment: Option<String>,
+    /// Milliseconds since the Unix epoch after which the entry reads as missing; omitted never
+    /// expires
+    pub expires_at: Option<i64>,
 }
 
+impl From<KeystoreEntry> for keystore_core::KeystoreEntry {
+    fn from(entry: KeystoreEntry) -> Self {
+        keystore_core::KeystoreEntry {
+            service: entry.service,
+            account: entry.account,
+            value: entry.value,
+            label: entry.label,
+            comment: entry.comment,
+            expires_at: entry.expires_at.map(|at| at.max(0) as u64),
+        }
+    }
+}
+
+/// Options accepted by `setPassword`
+#[napi(object)]
+#[derive(Debug, Default)]
+pub struct WriteOptions {
+    /// Human-readable name for the settings UI. Omitted keeps the stored label; an empty one
+    /// clears it.
+    pub label: Option<String>,
+    /// Free-form note, kept and cleared like `label`
+    pub comment: Option<String>,
+    /// Milliseconds since the Unix epoch after which the entry reads as missing and is deleted.
+    /// Omitted never expires, even when overwriting an entry that would have.
+    pub expires_at: Option<i64>,
+}
+
+/// Returned by `getMetadata`
+#[napi(object)]
+#[derive(Debug)]
+pub struct EntryMetadata {
+    pub label: Option<String>,
+    pub comment: Option<String>,
+    /// Milliseconds since the Unix epoch; missing where the backend doesn't record it
+    pub created_at: Option<i64>,
+    pub updated_at: Option<i64>,
+    pub expires_at: Option<i64>,
+}
+
+impl From<keystore_core::EntryMetadata> for EntryMetadata {
+    fn from(metadata: keystore_core::EntryMetadata) -> Self {
+        EntryMetadata {
+            label: metadata.label,
+            comment: metadata.comment,
+            created_at: metadata.created_at.map(|at| at as i64),
+            updated_at: metadata.updated_at.map(|at| at as i64),
+            expires_at: metadata.expires_at.map(|at| at as i64),
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug, Default)]
+pub struct KeystoreOptions {
+    /// Resolve account names regardless of case; off by default
+    pub case_insensitive: Option<bool>,
+    /// Keep a copy of every entry in the encrypted file as well as the platform keystore; off by
+    /// default
+    pub mirror_to_file: Option<bool>,
+    /// Stores every service under `<namespace>::<service>`, e.g. `dev::twitch`, so builds sharing
+    /// a machine don't overwrite each other's credentials; listings only show the namespace
+    pub namespace: Option<String>,
+}
+
+/// Options accepted by the read methods
+#[napi(object)]
+#[derive(Debug, Default)]
+pub struct ReadOptions {
+    /// Why the value is read, e.g. `irc-connect`; recorded in audit records and checked against
+    /// the policy's `allowed_purposes`. Defaults to the thread's `setAccessContext` purpose.
+    pub purpose: Option<String>,
+    /// Reads past the hot and lock caches, for a value that must be current; the value read still
+    /// refreshes them
+    pub bypass_cache: Option<bool>,
+}
+
+/// Returned by `getPasswordWithSource`
+#[napi(object)]
+#[derive(Debug)]
+pub struct ResolvedValue {
+    pub value: String,
+    /// `ephemeral`, `hot-cache`, `lock-cache`, `pending-write`, `systemd-credential`, `native` or
+    /// `fallback`
+    pub source: String,
+}
+
+/// Identifies a stored entry without carrying its value
+#[napi(object)]
+#[derive(Debug)]
+pub struct KeystoreKey {
+    pub service: String,
+    pub account: String,
+}
+
+impl From<keystore_core::KeystoreKey> for KeystoreKey {
+    fn from(key: keystore_core::KeystoreKey) -> Self {
+        KeystoreKey {
+            service: key.service,
+            account: key.account,
+        }
+    }
+}
+
+/// An account and its value, as keytar's `findCredentials` returns them
+#[napi(object)]
+#[derive(Debug)]
+pub struct Credential {
+    pub account: String,
+    pub password: String,
+}
+
+impl From<keystore_core::KeystoreEntry> for Credential {
+    fn from(entry: keystore_core::KeystoreEntry) -> Self {
+        Credential {
+            account: entry.account,
+            password: entry.value,
+        }
+    }
+}
+
+impl From<KeystoreKey> for keystore_core::KeystoreKey {
+    fn from(key: KeystoreKey) -> Self {
+        keystore_core::KeystoreKey {
+            service: key.service,
+            account: key.account,
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct EntryAlias {
+    pub alias: KeystoreKey,
+    pub target: KeystoreKey,
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct BackendInfo {
+    pub name: String,
+    pub details: HashMap<String, String>,
+}
+
+impl From<keystore_core::BackendInfo> for BackendInfo {
+    fn from(info: keystore_core::BackendInfo) -> Self {
+        BackendInfo {
+            name: info.name,
+            details: info.details.into_iter().collect(),
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct SecureNoteInfo {
+    pub title: String,
+    pub size: u32,
+    /// Milliseconds since the Unix epoch
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl From<keystore_core::notes::SecureNoteInfo> for SecureNoteInfo {
+    fn from(info: keystore_core::notes::SecureNoteInfo) -> Self {
+        SecureNoteInfo {
+            title: info.title,
+            size: info.size as u32,
+            created_at: info.created_at as i64,
+            updated_at: info.updated_at as i64,
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct CertificateInfo {
+    pub name: String,
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    /// Milliseconds since the Unix epoch
+    pub not_before: i64,
+    pub not_after: i64,
+    pub fingerprint_sha256: String,
+    pub chain_length: u32,
+    pub has_private_key: bool,
+}
+
+impl From<keystore_core::certs::CertificateInfo> for CertificateInfo {
+    fn from(info: keystore_core::certs::CertificateInfo) -> Self {
+        CertificateInfo {
+            name: info.name,
+            subject: info.subject,
+            issuer: info.issuer,
+            serial: info.serial,
+            not_before: info.not_before,
+            not_after: info.not_after,
+            fingerprint_sha256: info.fingerprint_sha256,
+            chain_length: info.chain_length as u32,
+            has_private_key: info.has_private_key,
+        }
+    }
+}
+
+/// Failed backend calls with one error code on one backend
+#[napi(object)]
+#[derive(Debug)]
+pub struct ErrorCount {
+    pub code: String,
+    pub backend: String,
+    pub count: u32,
+}
+
+impl From<keystore_core::metrics::ErrorCount> for ErrorCount {
+    fn from(count: keystore_core::metrics::ErrorCount) -> Self {
+        ErrorCount {
+            code: count.code,
+            backend: count.backend,
+            count: count.count as u32,
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct HealthReport {
+    /// `healthy`, `degraded` or `unhealthy`
+    pub status: String,
+    pub backend: BackendInfo,
+    pub available: bool,
+    pub is_fallback: bool,
+    pub expiring_certificates: Vec<CertificateInfo>,
+    pub warnings: Vec<String>,
+    /// Set by `doctorReport()` when the integrity audit ran
+    pub integrity: Option<IntegrityReport>,
+}
+
+impl From<keystore_core::health::HealthReport> for HealthReport {
+    fn from(report: keystore_core::health::HealthReport) -> Self {
+        HealthReport {
+            status: report.status.as_str().to_string(),
+            backend: report.backend.into(),
+            available: report.available,
+            is_fallback: report.is_fallback,
+            expiring_certificates: report
+                .expiring_certificates
+                .into_iter()
+                .map(CertificateInfo::from)
+                .collect(),
+            warnings: report.warnings,
+            integrity: report.integrity.map(IntegrityReport::from),
+        }
+    }
+}
+
+/// `service` or `service/account` glob patterns (`*`, `?`) selecting entries to export
+#[napi(object)]
+#[derive(Debug, Default)]
+pub struct ExportFilter {
+    /// Everything when omitted or empty
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+}
+
+impl From<ExportFilter> for keystore_core::backup::ExportFilter {
+    fn from(filter: ExportFilter) -> Self {
+        keystore_core::backup::ExportFilter {
+            include: filter.include.unwrap_or_default(),
+            exclude: filter.exclude.unwrap_or_default(),
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct ImportConflict {
+    pub key: KeystoreKey,
+    pub reason: String,
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct PrefetchFailure {
+    pub key: KeystoreKey,
+    /// Why the entry couldn't be read
+    pub error: String,
+}
+
+/// What `prefetch` read; values are never included
+#[napi(object)]
+#[derive(Debug)]
+pub struct PrefetchReport {
+    pub warmed: u32,
+    pub missing: Vec<KeystoreKey>,
+    pub failed: Vec<PrefetchFailure>,
+}
+
+impl From<keystore_core::prefetch::PrefetchReport> for PrefetchReport {
+    fn from(report: keystore_core::prefetch::PrefetchReport) -> Self {
+        PrefetchReport {
+            warmed: report.warmed as u32,
+            missing: report.missing.into_iter().map(KeystoreKey::from).collect(),
+            failed: report
+                .failed
+                .into_iter()
+                .map(|(key, error)| PrefetchFailure {
+                    key: key.into(),
+                    error,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What an import would do; values are compared by hash and never included
+#[napi(object)]
+#[derive(Debug)]
+pub struct ImportDiff {
+    pub added: Vec<KeystoreKey>,
+    pub changed: Vec<KeystoreKey>,
+    pub unchanged: Vec<KeystoreKey>,
+    pub conflicts: Vec<ImportConflict>,
+}
+
+/// Returned by `previewEnvImport` and `importEnv`
+#[napi(object)]
+#[derive(Debug)]
+pub struct EnvImportReport {
+    pub diff: ImportDiff,
+    /// Variables no mapping rule matched
+    pub unmapped: Vec<String>,
+    /// Whether the file was overwritten and deleted
+    pub shredded: bool,
+}
+
+impl From<keystore_core::import::ImportDiff> for ImportDiff {
+    fn from(diff: keystore_core::import::ImportDiff) -> Self {
+        let keys = |keys: Vec<keystore_core::KeystoreKey>| {
+            keys.into_iter().map(KeystoreKey::from).collect()
+        };
+        ImportDiff {
+            added: keys(diff.added),
+            changed: keys(diff.changed),
+            unchanged: keys(diff.unchanged),
+            conflicts: diff
+                .conflicts
+                .into_iter()
+                .map(|conflict| ImportConflict {
+                    key: conflict.key.into(),
+                    reason: conflict.reason,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Where the platform keystore and its encrypted-file mirror differ, without values
+#[napi(object)]
+#[derive(Debug)]
+pub struct MirrorReport {
+    /// Missing from the encrypted file
+    pub only_primary: Vec<KeystoreKey>,
+    /// Missing from the platform keystore
+    pub only_secondary: Vec<KeystoreKey>,
+    pub differing: Vec<KeystoreKey>,
+}
+
+impl From<keystore_core::mirror::MirrorReport> for MirrorReport {
+    fn from(report: keystore_core::mirror::MirrorReport) -> Self {
+        let keys = |keys: Vec<keystore_core::KeystoreKey>| {
+            keys.into_iter().map(KeystoreKey::from).collect()
+        };
+        MirrorReport {
+            only_primary: keys(report.only_primary),
+            only_secondary: keys(report.only_secondary),
+            differing: keys(report.differing),
+        }
+    }
+}
+
+/// A token another application stored, returned by `discoverForeignCredentials`
+#[napi(object)]
+#[derive(Debug)]
+pub struct ForeignCredential {
+    /// Schema it matched, e.g. `keytar`
+    pub app: String,
+    pub service: String,
+    pub account: String,
+    /// Secret Service item path or Credential Manager target name
+    pub location: String,
+}
+
+impl From<keystore_core::discovery::ForeignCredential> for ForeignCredential {
+    fn from(credential: keystore_core::discovery::ForeignCredential) -> Self {
+        Self {
+            app: credential.app,
+            service: credential.service,
+            account: credential.account,
+            location: credential.location,
+        }
+    }
+}
+
+impl From<ForeignCredential> for keystore_core::discovery::ForeignCredential {
+    fn from(credential: ForeignCredential) -> Self {
+        Self {
+            app: credential.app,
+            service: credential.service,
+            account: credential.account,
+            location: credential.location,
+        }
+    }
+}
+
+/// An entry two writers of a synced keystore file changed differently
+#[napi(object)]
+#[derive(Debug)]
+pub struct SyncConflict {
+    pub id: String,
+    pub key: KeystoreKey,
+    /// Revision of the displaced version
+    pub revision: i64,
+    /// Milliseconds since the Unix epoch
+    pub detected_at: i64,
+}
+
+impl From<keystore_core::conflicts::SyncConflict> for SyncConflict {
+    fn from(conflict: keystore_core::conflicts::SyncConflict) -> Self {
+        SyncConflict {
+            id: conflict.id,
+            key: KeystoreKey {
+                service: conflict.service,
+                account: conflict.account,
+            },
+            revision: conflict.revision as i64,
+            detected_at: conflict.detected_at as i64,
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct TemplateAuditEvent {
+    pub reference: String,
+    /// Byte offset of the placeholder in the template
+    pub offset: u32,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct TemplateInterpolation {
+    pub output: String,
+    pub audit: Vec<TemplateAuditEvent>,
+}
+
+impl From<keystore_core::refs::Interpolation> for TemplateInterpolation {
+    fn from(result: keystore_core::refs::Interpolation) -> Self {
+        TemplateInterpolation {
+            output: result.output,
+            audit: result
+                .audit
+                .into_iter()
+                .map(|event| TemplateAuditEvent {
+                    reference: event.reference,
+                    offset: event.offset as u32,
+                    timestamp: event.timestamp as i64,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct DelegateClaims {
+    pub scope: Vec<String>,
+    /// Milliseconds since the Unix epoch
+    pub issued_at: i64,
+    pub expires_at: i64,
+    pub id: String,
+}
+
+impl From<keystore_core::delegate::DelegateClaims> for DelegateClaims {
+    fn from(claims: keystore_core::delegate::DelegateClaims) -> Self {
+        DelegateClaims {
+            scope: claims.scope,
+            issued_at: claims.issued_at as i64,
+            expires_at: claims.expires_at as i64,
+            id: claims.id,
+        }
+    }
+}
+
+/// Argon2id parameters chosen by `tuneKdf`
+#[napi(object)]
+#[derive(Debug)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl From<keystore_core::kdf::KdfParams> for KdfParams {
+    fn from(params: keystore_core::kdf::KdfParams) -> Self {
+        KdfParams {
+            memory_kib: params.memory_kib,
+            iterations: params.iterations,
+            parallelism: params.parallelism,
+        }
+    }
+}
+
+/// Passed to `setQueueLimits`
+#[napi(object)]
+#[derive(Debug, Default)]
+pub struct QueueLimits {
+    /// Operations running on the backend at once; 8 by default
+    pub concurrency: Option<u32>,
+    /// Operations waiting for a slot before further calls throw ERR_BUSY; 256 by default
+    pub max_queued: Option<u32>,
+    /// Suggested wait per round of the queue in the ERR_BUSY message; 100 by default
+    pub retry_after_ms: Option<u32>,
+}
+
+/// Passed to `onLockStateChange` listeners
+#[napi(object)]
+#[derive(Debug)]
+pub struct LockEvent {
+    pub locked: bool,
+    /// `manual` or `idle`
+    pub reason: String,
+}
+
+impl From<keystore_core::lock::LockEvent> for LockEvent {
+    fn from(event: keystore_core::lock::LockEvent) -> Self {
+        use keystore_core::lock::{LockReason, LockState};
+
+        LockEvent {
+            locked: event.state == LockState::Locked,
+            reason: match event.reason {
+                LockReason::Manual => "manual",
+                LockReason::Idle => "idle",
+            }
+            .to_string(),
+        }
+    }
+}
+
+/// Accepted by `setLatencyBudget`
+#[napi(object)]
+#[derive(Debug)]
+pub struct LatencyBudgetOptions {
+    /// Backend calls slower than this are over budget
+    pub budget_ms: u32,
+    /// Calls over budget in a row that degrade the backend; 3 by default
+    pub degrade_after: Option<u32>,
+    /// Calls within budget in a row that restore it; 3 by default
+    pub recover_after: Option<u32>,
+}
+
+/// Passed to `onDegradation` listeners
+#[napi(object)]
+#[derive(Debug)]
+pub struct DegradationEvent {
+    pub backend: String,
+    pub degraded: bool,
+    /// Duration of the call that tipped it
+    pub latency_ms: f64,
+}
+
+impl From<&keystore_core::latency::DegradationEvent> for DegradationEvent {
+    fn from(event: &keystore_core::latency::DegradationEvent) -> Self {
+        DegradationEvent {
+            backend: event.backend.clone(),
+            degraded: event.degraded,
+            latency_ms: event.latency.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+/// Passed to `onUnlockWait` listeners
+#[napi(object)]
+#[derive(Debug)]
+pub struct UnlockWaitEvent {
+    /// `unlock-needed`, `flushed` or `expired`
+    #[napi(js_name = "type")]
+    pub event_type: String,
+    /// Operations queued so far, written, or dropped, by `type`
+    pub operations: u32,
+    /// Queued operations that failed for another reason when written
+    pub failed: u32,
+}
+
+impl From<keystore_core::unlock_wait::UnlockWaitEvent> for UnlockWaitEvent {
+    fn from(event: keystore_core::unlock_wait::UnlockWaitEvent) -> Self {
+        use keystore_core::unlock_wait::UnlockWaitEvent as Event;
+
+        let (event_type, operations, failed) = match event {
+            Event::UnlockNeeded { queued } => ("unlock-needed", queued, 0),
+            Event::Flushed { written, failed } => ("flushed", written, failed),
+            Event::Expired { dropped } => ("expired", dropped, 0),
+        };
+        UnlockWaitEvent {
+            event_type: event_type.to_string(),
+            operations: operations as u32,
+            failed: failed as u32,
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct AuditRecord {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+    pub operation: String,
+    pub service: Option<String>,
+    pub account: Option<String>,
+    /// `ok` or an error code
+    pub outcome: String,
+    pub backend: String,
+    /// Why a `get` was made, when the caller said
+    pub purpose: Option<String>,
+    /// `viewId` of the scoped keystore the call was made through
+    pub scope: Option<String>,
+}
+
+impl From<keystore_core::audit::AuditRecord> for AuditRecord {
+    fn from(record: keystore_core::audit::AuditRecord) -> Self {
+        AuditRecord {
+            timestamp: record.timestamp as i64,
+            operation: record.operation,
+            service: record.service,
+            account: record.account,
+            outcome: record.outcome,
+            backend: record.backend,
+            purpose: record.purpose,
+            scope: record.scope,
+        }
+    }
+}
+
+/// Returned by `verifyAuditLog`
+#[napi(object)]
+#[derive(Debug)]
+pub struct AuditVerification {
+    pub intact: bool,
+    pub records: u32,
+    /// Lines from before the log was chained, which can't be verified
+    pub unchained_records: u32,
+    /// Records covered by a valid signature
+    pub signed_records: u32,
+    /// 1-based line where verification stopped
+    pub broken_at: Option<u32>,
+    pub problem: Option<String>,
+}
+
+impl From<keystore_core::audit::AuditVerification> for AuditVerification {
+    fn from(report: keystore_core::audit::AuditVerification) -> Self {
+        AuditVerification {
+            intact: report.is_intact(),
+            records: report.records as u32,
+            unchained_records: report.unchained_records as u32,
+            signed_records: report.signed_records as u32,
+            broken_at: report.broken_at.map(|line| line as u32),
+            problem: report.problem,
+        }
+    }
+}
+
+/// One probe by `startHealthProbe`
+#[napi(object)]
+#[derive(Debug)]
+pub struct ProbeSample {
+    /// Milliseconds since the Unix epoch
+    pub at: i64,
+    /// `healthy`, `degraded` or `unhealthy`
+    pub status: String,
+    pub latency_ms: f64,
+}
+
+impl From<keystore_core::probe::ProbeSample> for ProbeSample {
+    fn from(sample: keystore_core::probe::ProbeSample) -> Self {
+        ProbeSample {
+            at: sample.at as i64,
+            status: sample.status.as_str().to_string(),
+            latency_ms: sample.latency.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+/// Returned by `backendHealth()`
+#[napi(object)]
+#[derive(Debug)]
+pub struct BackendHealth {
+    pub backend: String,
+    /// Status at the last probe
+    pub status: String,
+    /// Recent probes, oldest first
+    pub samples: Vec<ProbeSample>,
+}
+
+/// A backend's health changing, as in `keystore.health-changed` events
+#[napi(object)]
+#[derive(Debug)]
+pub struct HealthTransition {
+    pub backend: String,
+    /// Unset on the first probe
+    pub from: Option<String>,
+    pub to: String,
+}
+
+impl From<keystore_core::probe::HealthTransition> for HealthTransition {
+    fn from(transition: keystore_core::probe::HealthTransition) -> Self {
+        HealthTransition {
+            backend: transition.backend,
+            from: transition.from.map(|status| status.as_str().to_string()),
+            to: transition.to.as_str().to_string(),
+        }
+    }
+}
+
+/// Passed to `subscribe` listeners; the field matching `type` is set
+#[napi(object)]
+#[derive(Debug)]
+pub struct KeystoreEvent {
+    #[napi(js_name = "type")]
+    pub event_type: String,
+    pub audit: Option<AuditRecord>,
+    pub access: Option<SecretAccess>,
+    pub health: Option<HealthTransition>,
+}
+
+impl KeystoreEvent {
+    pub fn audit(record: keystore_core::audit::AuditRecord) -> Self {
+        KeystoreEvent {
+            event_type: "keystore.audit".to_string(),
+            audit: Some(record.into()),
+            access: None,
+            health: None,
+        }
+    }
+
+    pub fn secret_accessed(access: keystore_core::access::SecretAccess) -> Self {
+        KeystoreEvent {
+            event_type: "keystore.secret-accessed".to_string(),
+            audit: None,
+            access: Some(access.into()),
+            health: None,
+        }
+    }
+
+    pub fn health_changed(transition: keystore_core::probe::HealthTransition) -> Self {
+        KeystoreEvent {
+            event_type: "keystore.health-changed".to_string(),
+            audit: None,
+            access: None,
+            health: Some(transition.into()),
+        }
+    }
+}
+
+/// A value handed out, as in `keystore.secret-accessed` events
+#[napi(object)]
+#[derive(Debug)]
+pub struct SecretAccess {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+    pub service: String,
+    pub account: String,
+    /// As set with `setAccessContext` on the reading thread
+    pub caller: Option<String>,
+    pub purpose: Option<String>,
+    /// Reads of this entry since the process started, this one included
+    pub count: i64,
+}
+
+impl From<keystore_core::access::SecretAccess> for SecretAccess {
+    fn from(access: keystore_core::access::SecretAccess) -> Self {
+        SecretAccess {
+            timestamp: access.timestamp as i64,
+            service: access.service,
+            account: access.account,
+            caller: access.caller,
+            purpose: access.purpose,
+            count: access.count as i64,
+        }
+    }
+}
+
+/// Returned by `accessCounts()`
+#[napi(object)]
+#[derive(Debug)]
+pub struct AccessCount {
+    pub service: String,
+    pub account: String,
+    pub count: i64,
+}
+
+/// Returned by `verifyAll()`
+#[napi(object)]
+#[derive(Debug)]
+pub struct IntegrityReport {
+    pub checked: u32,
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl From<keystore_core::integrity::IntegrityReport> for IntegrityReport {
+    fn from(report: keystore_core::integrity::IntegrityReport) -> Self {
+        Self {
+            checked: report.checked as u32,
+            issues: report
+                .issues
+                .into_iter()
+                .map(IntegrityIssue::from)
+                .collect(),
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct IntegrityIssue {
+    /// Unset for issues of the backend as a whole
+    pub service: Option<String>,
+    pub account: Option<String>,
+    /// `unreadable`, `undecryptable`, `corrupt`, `invalid-schema`, `orphaned-chunk`,
+    /// `dangling-alias` or `orphaned-record`
+    pub kind: String,
+    pub detail: String,
+    /// What `repair()` would remove to fix the issue
+    pub orphan: Option<Orphan>,
+}
+
+impl From<keystore_core::integrity::IntegrityIssue> for IntegrityIssue {
+    fn from(issue: keystore_core::integrity::IntegrityIssue) -> Self {
+        Self {
+            service: issue.key.as_ref().map(|key| key.service.clone()),
+            account: issue.key.map(|key| key.account),
+            kind: issue.kind.as_str().to_string(),
+            detail: issue.detail,
+            orphan: issue.repair.map(Orphan::from),
+        }
+    }
+}
+
+/// Returned by `findOrphans()`
+#[napi(object)]
+#[derive(Debug)]
+pub struct Orphan {
+    /// `chunk`, `alias`, `note-index-row`, `certificate-index-row`, `protection-mark` or
+    /// `provenance-record`
+    pub kind: String,
+    /// The chunk or alias itself; for index rows, marks and records, the missing entry
+    pub key: KeystoreKey,
+    /// The entry that should have owned it
+    pub owner: KeystoreKey,
+    pub detail: String,
+}
+
+impl From<keystore_core::orphans::Orphan> for Orphan {
+    fn from(orphan: keystore_core::orphans::Orphan) -> Self {
+        Self {
+            kind: orphan.kind.as_str().to_string(),
+            detail: orphan.describe(),
+            key: orphan.key.into(),
+            owner: orphan.owner.into(),
+        }
+    }
+}
+
+impl TryFrom<Orphan> for keystore_core::orphans::Orphan {
+    type Error = keystore_core::KeystoreError;
+
+    fn try_from(orphan: Orphan) -> Result<Self, Self::Error> {
+        Ok(Self {
+            kind: keystore_core::orphans::OrphanKind::parse(&orphan.kind)?,
+            key: orphan.key.into(),
+            owner: orphan.owner.into(),
+        })
+    }
+}
+
+/// Returned by `getProvenance()`
+#[napi(object)]
+#[derive(Debug)]
+pub struct EntryProvenance {
+    pub service: String,
+    pub account: String,
+    /// `oauth-flow`, `manual`, `import-keytar`, `import`, `cli` or `sync`
+    pub origin: String,
+    pub component: Option<String>,
+    /// Milliseconds since the Unix epoch
+    pub written_at: i64,
+}
+
+impl From<keystore_core::provenance::EntryProvenance> for EntryProvenance {
+    fn from(record: keystore_core::provenance::EntryProvenance) -> Self {
+        Self {
+            service: record.service,
+            account: record.account,
+            origin: record.origin.as_str().to_string(),
+            component: record.component,
+            written_at: record.written_at as i64,
+        }
+    }
+}
+
+/// Returned by `versionInfo()`
+#[napi(object)]
+#[derive(Debug)]
+pub struct VersionInfo {
+    pub version: String,
+    /// `rustcrypto`, `openssl-fips` or `none`
+    pub crypto_provider: String,
+    pub fips: bool,
+    pub features: Vec<String>,
+}
+
+/// Build version, crypto provider and enabled features of the native module
+#[napi]
+pub fn version_info() -> VersionInfo {
+    let info = keystore_core::version::version_info();
+    VersionInfo {
+        version: info.version.to_string(),
+        crypto_provider: info.crypto_provider.to_string(),
+        fips: info.fips,
+        features: info.features.into_iter().map(str::to_string).collect(),
+    }
+}
+
+/// Serializes structured values under `service` (secure-note and certificate indexes, stored
+/// certificates) as `json`, `cbor` or `msgpack` from now on; values already stored keep their
+/// format and are read either way
+#[napi]
+pub fn set_structured_format(service: String, format: String) -> Result<(), napi::Error> {
+    let format = keystore_core::blob::StructuredFormat::from_name(&format)
+        .map_err(error::NapiKeystoreError::from)?;
+    keystore_core::blob::set_structured_format(&service, format);
+    Ok(())
+}
+
+pub mod channel;
 pub mod error;
-pub mod platform;
+pub mod generate;
+pub mod hooks;
+pub mod input;
+pub mod keystore;
+pub mod pairing;
+pub mod refresh;
+pub mod scoped;
+pub mod snapshot;
+pub mod stream;
+pub mod strength;
+pub mod task;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+pub mod verify;
 
-pub use platform::Keystore;
+pub use keystore::NapiKeystore;
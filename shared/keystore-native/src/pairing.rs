@@ -0,0 +1,128 @@
+use super::error::NapiKeystoreError;
+use super::KeystoreKey;
+use keystore_core::pairing::{self, PairingRole};
+use keystore_core::reserved::check_not_reserved;
+use keystore_core::KeystoreError;
+use napi::bindgen_prelude::Buffer;
+use napi::Error;
+use napi_derive::napi;
+
+/// One side of the code-keyed handshake with a companion device
+#[napi]
+pub struct PairingHandshake {
+    inner: Option<pairing::PairingHandshake>,
+    message: Vec<u8>,
+}
+
+#[napi]
+impl PairingHandshake {
+    /// Sent to the peer; either side may send first
+    #[napi]
+    pub fn message(&self) -> Buffer {
+        self.message.clone().into()
+    }
+
+    /// Combines the peer's message into a session; can only be called once
+    #[napi]
+    pub fn finish(&mut self, peer_message: Buffer) -> Result<PairingSession, Error> {
+        let handshake = self.inner.take().ok_or_else(|| {
+            NapiKeystoreError::from(KeystoreError::InvalidInput(
+                "Pairing handshake already finished".to_string(),
+            ))
+        })?;
+        Ok(PairingSession {
+            inner: handshake
+                .finish(&peer_message)
+                .map_err(NapiKeystoreError::from)?,
+        })
+    }
+}
+
+/// A paired connection carrying one transfer of entries
+#[napi]
+pub struct PairingSession {
+    pub(crate) inner: pairing::PairingSession,
+}
+
+#[napi]
+impl PairingSession {
+    /// Proves to the peer that this side typed the same code
+    #[napi]
+    pub fn confirmation(&self) -> Buffer {
+        self.inner.confirmation().to_vec().into()
+    }
+
+    /// Throws `ERR_ACCESS_DENIED` if the codes differed; start over with a new code
+    #[napi]
+    pub fn verify(&mut self, peer_confirmation: Buffer) -> Result<(), Error> {
+        Ok(self
+            .inner
+            .verify(&peer_confirmation)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Sender: offers `keys`, without their values
+    #[napi]
+    pub fn offer(&mut self, keys: Vec<KeystoreKey>) -> Result<Buffer, Error> {
+        let keys: Vec<keystore_core::KeystoreKey> = keys.into_iter().map(Into::into).collect();
+        Ok(self
+            .inner
+            .offer(&keys)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    /// Receiver: the offered keys, to ask the user about one by one
+    #[napi]
+    pub fn read_offer(&mut self, offer: Buffer) -> Result<Vec<KeystoreKey>, Error> {
+        Ok(self
+            .inner
+            .read_offer(&offer)
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(KeystoreKey::from)
+            .collect())
+    }
+
+    /// Receiver: accepts the offered keys the user agreed to
+    #[napi]
+    pub fn accept(&mut self, keys: Vec<KeystoreKey>) -> Result<Buffer, Error> {
+        for key in &keys {
+            check_not_reserved(&key.service).map_err(NapiKeystoreError::from)?;
+        }
+        let keys: Vec<keystore_core::KeystoreKey> = keys.into_iter().map(Into::into).collect();
+        Ok(self
+            .inner
+            .accept(&keys)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+}
+
+/// A fresh code to show on the sending device, as `1234-5678`
+#[napi]
+pub fn generate_pairing_code() -> Result<String, Error> {
+    Ok(pairing::generate_pairing_code().map_err(NapiKeystoreError::from)?)
+}
+
+/// Starts pairing as `'sender'` (holds the entries, shows the code) or `'receiver'`
+#[napi]
+pub fn start_pairing(role: String, code: String) -> Result<PairingHandshake, Error> {
+    let role = match role.as_str() {
+        "sender" => PairingRole::Sender,
+        "receiver" => PairingRole::Receiver,
+        _ => {
+            return Err(NapiKeystoreError::from(KeystoreError::InvalidInput(format!(
+                "Unknown pairing role: {}",
+                role
+            )))
+            .into())
+        }
+    };
+    let handshake =
+        pairing::PairingHandshake::start(role, &code).map_err(NapiKeystoreError::from)?;
+    Ok(PairingHandshake {
+        message: handshake.message().to_vec(),
+        inner: Some(handshake),
+    })
+}
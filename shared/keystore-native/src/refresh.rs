@@ -0,0 +1,37 @@
+use super::error::NapiKeystoreError;
+use keystore_core::refresh::{self, REFRESH_LOCK_TIMEOUT};
+use napi::Error;
+use napi_derive::napi;
+use std::time::Duration;
+
+/// Excludes other processes from refreshing one entry until released
+#[napi]
+pub struct RefreshLock {
+    inner: Option<refresh::RefreshLock>,
+}
+
+#[napi]
+impl RefreshLock {
+    /// Lets the next refresher in; also happens when the object is garbage collected or the
+    /// process exits
+    #[napi]
+    pub fn release(&mut self) {
+        self.inner = None;
+    }
+}
+
+/// Waits up to `timeout_ms` (default 30s) for other processes refreshing `service`/`account` to
+/// finish, blocking the calling thread. Throws `ERR_BUSY` on timeout.
+#[napi]
+pub fn acquire_refresh_lock(
+    service: String,
+    account: String,
+    timeout_ms: Option<u32>,
+) -> Result<RefreshLock, Error> {
+    let timeout = timeout_ms
+        .map(|ms| Duration::from_millis(ms.into()))
+        .unwrap_or(REFRESH_LOCK_TIMEOUT);
+    let lock = refresh::RefreshLock::acquire(&service, &account, timeout)
+        .map_err(NapiKeystoreError::from)?;
+    Ok(RefreshLock { inner: Some(lock) })
+}
@@ -0,0 +1,193 @@
+use super::error::NapiKeystoreError;
+use super::generate::secret_length;
+use super::input::{owned_text, TextInput};
+use super::keystore::with_read_options;
+use super::{Credential, EntryMetadata, KeystoreKey, ReadOptions, WriteOptions};
+use keystore_core::audit::AuditingKeystore;
+use keystore_core::search::find_credentials;
+use keystore_core::{KeystoreEntry, KeystoreOperations};
+use napi::Error;
+use napi_derive::napi;
+use std::sync::Arc;
+use zeroize::Zeroize;
+
+/// A keystore limited to the services granted to one sandboxed view; see
+/// `NapiKeystore.createScoped`. Calls outside them throw ERR_ACCESS_DENIED.
+#[napi]
+pub struct ScopedKeystore {
+    view_id: String,
+    inner: Arc<AuditingKeystore>,
+}
+
+impl ScopedKeystore {
+    pub(crate) fn new(view_id: String, inner: Arc<AuditingKeystore>) -> Self {
+        Self { view_id, inner }
+    }
+}
+
+#[napi]
+impl ScopedKeystore {
+    #[napi(getter)]
+    pub fn view_id(&self) -> String {
+        self.view_id.clone()
+    }
+
+    #[napi]
+    pub fn set_password(
+        &self,
+        service: String,
+        account: String,
+        value: TextInput,
+        options: Option<WriteOptions>,
+    ) -> Result<(), Error> {
+        let options = options.unwrap_or_default();
+        let mut entry = KeystoreEntry {
+            service,
+            account,
+            value: owned_text(value, "Value")?,
+            label: options.label,
+            comment: options.comment,
+            expires_at: options.expires_at.map(|at| at.max(0) as u64),
+        };
+        let result = self.inner.set_password(&entry);
+        entry.value.zeroize();
+        Ok(result.map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn get_password(
+        &self,
+        service: String,
+        account: String,
+        options: Option<ReadOptions>,
+    ) -> Result<String, Error> {
+        Ok(
+            with_read_options(options, || self.inner.get_password(&service, &account))
+                .map_err(NapiKeystoreError::from)?,
+        )
+    }
+
+    #[napi]
+    pub fn get_many(
+        &self,
+        keys: Vec<KeystoreKey>,
+        options: Option<ReadOptions>,
+    ) -> Result<Vec<Option<String>>, Error> {
+        let keys: Vec<keystore_core::KeystoreKey> = keys.into_iter().map(Into::into).collect();
+        Ok(with_read_options(options, || self.inner.get_many(&keys))
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn get_metadata(&self, service: String, account: String) -> Result<EntryMetadata, Error> {
+        Ok(self
+            .inner
+            .get_metadata(&service, &account)
+            .map_err(NapiKeystoreError::from)?
+            .into())
+    }
+
+    #[napi]
+    pub fn has_password(&self, service: String, account: String) -> Result<bool, Error> {
+        Ok(self
+            .inner
+            .has_password(&service, &account)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn get_or_create(
+        &self,
+        service: String,
+        account: String,
+        length: Option<u32>,
+    ) -> Result<String, Error> {
+        Ok(self
+            .inner
+            .get_or_create(&service, &account, secret_length(length))
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    #[napi]
+    pub fn delete_password(&self, service: String, account: String) -> Result<(), Error> {
+        Ok(self
+            .inner
+            .delete_password(&service, &account)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Refused as a whole if any entry is outside the granted services
+    #[napi]
+    pub fn set_many(&self, entries: Vec<crate::KeystoreEntry>) -> Result<(), Error> {
+        let mut entries: Vec<KeystoreEntry> = entries.into_iter().map(Into::into).collect();
+        let result = self.inner.set_many(&entries);
+        entries.iter_mut().for_each(|entry| entry.value.zeroize());
+        Ok(result.map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Refused as a whole if any key is outside the granted services
+    #[napi]
+    pub fn delete_many(&self, keys: Vec<KeystoreKey>) -> Result<Vec<KeystoreKey>, Error> {
+        let keys: Vec<keystore_core::KeystoreKey> = keys.into_iter().map(Into::into).collect();
+        Ok(self
+            .inner
+            .delete_many(&keys)
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Both entries must be in the granted services
+    #[napi]
+    pub fn swap(
+        &self,
+        service_a: String,
+        account_a: String,
+        service_b: String,
+        account_b: String,
+    ) -> Result<(), Error> {
+        let key = |service, account| keystore_core::KeystoreKey { service, account };
+        Ok(self
+            .inner
+            .swap(&key(service_a, account_a), &key(service_b, account_b))
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Entries of the granted services only
+    #[napi]
+    pub fn list_keys(&self) -> Result<Vec<KeystoreKey>, Error> {
+        Ok(self
+            .inner
+            .list_keys()
+            .map_err(NapiKeystoreError::from)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Accounts under `service`; empty outside the granted services
+    #[napi]
+    pub fn list_credentials(&self, service: String) -> Result<Vec<String>, Error> {
+        Ok(self
+            .inner
+            .list_credentials(&service)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Accounts under `service` with their values; empty outside the granted services
+    #[napi]
+    pub fn find_credentials(
+        &self,
+        service: String,
+        options: Option<ReadOptions>,
+    ) -> Result<Vec<Credential>, Error> {
+        Ok(
+            with_read_options(options, || find_credentials(self.inner.as_ref(), &service))
+                .map_err(NapiKeystoreError::from)?
+                .into_iter()
+                .map(Credential::from)
+                .collect(),
+        )
+    }
+}
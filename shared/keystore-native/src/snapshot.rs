@@ -0,0 +1,49 @@
+use super::error::NapiKeystoreError;
+use super::KeystoreKey;
+use keystore_core::backup;
+use napi::bindgen_prelude::Buffer;
+use napi::Error;
+use napi_derive::napi;
+
+/// How two backups differ; values are compared by keyed hash and never included
+#[napi(object)]
+#[derive(Debug)]
+pub struct SnapshotDiff {
+    pub added: Vec<KeystoreKey>,
+    pub removed: Vec<KeystoreKey>,
+    pub changed: Vec<KeystoreKey>,
+    pub unchanged: Vec<KeystoreKey>,
+}
+
+impl From<backup::SnapshotDiff> for SnapshotDiff {
+    fn from(diff: backup::SnapshotDiff) -> Self {
+        let keys = |keys: Vec<keystore_core::KeystoreKey>| {
+            keys.into_iter().map(KeystoreKey::from).collect()
+        };
+        SnapshotDiff {
+            added: keys(diff.added),
+            removed: keys(diff.removed),
+            changed: keys(diff.changed),
+            unchanged: keys(diff.unchanged),
+        }
+    }
+}
+
+/// Compares two backups from `exportBackup`. `newerPassphrase` defaults to `olderPassphrase`.
+/// Throws `ERR_ACCESS_DENIED` if either passphrase is wrong.
+#[napi]
+pub fn diff_snapshots(
+    older: Buffer,
+    older_passphrase: String,
+    newer: Buffer,
+    newer_passphrase: Option<String>,
+) -> Result<SnapshotDiff, Error> {
+    Ok(backup::diff_snapshots(
+        &older,
+        &older_passphrase,
+        &newer,
+        newer_passphrase.as_deref().unwrap_or(&older_passphrase),
+    )
+    .map_err(NapiKeystoreError::from)?
+    .into())
+}
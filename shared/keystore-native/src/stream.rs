@@ -0,0 +1,80 @@
+use super::error::NapiKeystoreError;
+use super::input::checked_bytes;
+use keystore_core::blob::{BlobReader, BlobWriter};
+use keystore_core::KeystoreError;
+use napi::bindgen_prelude::{Buffer, Uint8Array};
+use napi::Error;
+use napi_derive::napi;
+
+/// Writes one value in pieces; nothing replaces the stored value until `finish`
+#[napi]
+pub struct BlobWriteStream {
+    inner: Option<BlobWriter>,
+}
+
+impl BlobWriteStream {
+    pub(crate) fn new(inner: BlobWriter) -> Self {
+        Self { inner: Some(inner) }
+    }
+}
+
+fn finished() -> NapiKeystoreError {
+    KeystoreError::InvalidInput("The write stream is already finished".to_string()).into()
+}
+
+#[napi]
+impl BlobWriteStream {
+    /// Appends UTF-8 bytes; a character may be split between calls
+    #[napi]
+    pub fn write(&mut self, chunk: Uint8Array) -> Result<(), Error> {
+        let chunk = checked_bytes(&chunk, "Chunk")?;
+        Ok(self
+            .inner
+            .as_mut()
+            .ok_or_else(finished)?
+            .write(chunk)
+            .map_err(NapiKeystoreError::from)?)
+    }
+
+    /// Stores the rest and makes the new value visible
+    #[napi]
+    pub fn finish(&mut self) -> Result<(), Error> {
+        Ok(self
+            .inner
+            .take()
+            .ok_or_else(finished)?
+            .finish()
+            .map_err(NapiKeystoreError::from)?)
+    }
+}
+
+/// Reads one value in pieces, verifying it after the last
+#[napi]
+pub struct BlobReadStream {
+    inner: BlobReader,
+}
+
+impl BlobReadStream {
+    pub(crate) fn new(inner: BlobReader) -> Self {
+        Self { inner }
+    }
+}
+
+#[napi]
+impl BlobReadStream {
+    /// Length of the whole value in bytes
+    #[napi(getter)]
+    pub fn length(&self) -> u32 {
+        self.inner.len() as u32
+    }
+
+    /// The next piece as UTF-8, or `null` once the whole value has been read and verified
+    #[napi]
+    pub fn read(&mut self) -> Result<Option<Buffer>, Error> {
+        Ok(self
+            .inner
+            .read()
+            .map_err(NapiKeystoreError::from)?
+            .map(|piece| Buffer::from(piece.as_bytes())))
+    }
+}
@@ -0,0 +1,31 @@
+use keystore_core::strength;
+use napi_derive::napi;
+
+#[napi(object)]
+#[derive(Debug)]
+pub struct StrengthEstimate {
+    /// 0 (too guessable) to 4 (very unguessable)
+    pub score: u32,
+    pub guesses_log10: f64,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+impl From<strength::StrengthEstimate> for StrengthEstimate {
+    fn from(estimate: strength::StrengthEstimate) -> Self {
+        StrengthEstimate {
+            score: u32::from(estimate.score),
+            guesses_log10: estimate.guesses_log10,
+            warning: estimate.warning,
+            suggestions: estimate.suggestions,
+        }
+    }
+}
+
+/// Plain strength check; `user_inputs` are strings the value should not resemble
+#[napi]
+pub fn estimate_strength(value: String, user_inputs: Option<Vec<String>>) -> StrengthEstimate {
+    let inputs = user_inputs.unwrap_or_default();
+    let inputs: Vec<&str> = inputs.iter().map(String::as_str).collect();
+    strength::estimate_strength(&value, &inputs).into()
+}
@@ -0,0 +1,72 @@
+use super::error::NapiKeystoreError;
+use keystore_core::access::{current_access_context, with_access_context, AccessContext};
+use keystore_core::provenance::{current_provenance, with_provenance, ProvenanceContext};
+use keystore_core::queue::{current_priority, with_priority, Priority};
+use keystore_core::resolution::{current_read_policy, with_read_policy, ReadPolicy};
+use keystore_core::KeystoreError;
+use napi::bindgen_prelude::{ToNapiValue, TypeName};
+use napi::{Env, Error, Task};
+
+type Call<T> = Box<dyn FnOnce() -> Result<T, KeystoreError> + Send>;
+
+/// Settings the calling thread made with `setAccessContext`, `setProvenance`, `setPriority` and
+/// read options, which the worker thread wouldn't otherwise see
+struct CallerContext {
+    access: AccessContext,
+    provenance: ProvenanceContext,
+    priority: Priority,
+    read_policy: ReadPolicy,
+}
+
+impl CallerContext {
+    fn capture() -> Self {
+        Self {
+            access: current_access_context(),
+            provenance: current_provenance(),
+            priority: current_priority(),
+            read_policy: current_read_policy(),
+        }
+    }
+
+    fn apply<T>(self, f: impl FnOnce() -> T) -> T {
+        with_access_context(self.access, || {
+            with_provenance(self.provenance, || {
+                with_priority(self.priority, || with_read_policy(self.read_policy, f))
+            })
+        })
+    }
+}
+
+/// One keystore call run on the libuv thread pool, so a slow backend (a Secret Service round-trip,
+/// an unlock prompt) doesn't block the event loop. Backs the `*Async` methods, whose promises
+/// settle with the call's result.
+pub struct KeystoreTask<T> {
+    call: Option<Call<T>>,
+    caller: Option<CallerContext>,
+}
+
+impl<T> KeystoreTask<T> {
+    /// Captures the calling thread's context now, to run `call` under it later
+    pub(crate) fn new(call: impl FnOnce() -> Result<T, KeystoreError> + Send + 'static) -> Self {
+        Self {
+            call: Some(Box::new(call)),
+            caller: Some(CallerContext::capture()),
+        }
+    }
+}
+
+impl<T: ToNapiValue + TypeName + Send + 'static> Task for KeystoreTask<T> {
+    type Output = T;
+    type JsValue = T;
+
+    fn compute(&mut self) -> Result<T, Error> {
+        let (Some(call), Some(caller)) = (self.call.take(), self.caller.take()) else {
+            return Err(Error::from_reason("Keystore task already ran"));
+        };
+        Ok(caller.apply(call).map_err(NapiKeystoreError::from)?)
+    }
+
+    fn resolve(&mut self, _env: Env, output: T) -> Result<T, Error> {
+        Ok(output)
+    }
+}
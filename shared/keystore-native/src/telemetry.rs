@@ -0,0 +1,22 @@
+use super::error::NapiKeystoreError;
+use keystore_core::telemetry;
+use napi::Error;
+use napi_derive::napi;
+
+/// Exports keystore spans to an OTLP/HTTP collector, e.g. `http://localhost:4318/v1/traces`
+#[napi]
+pub fn init_tracing(endpoint: String, service_name: Option<String>) -> Result<(), Error> {
+    Ok(telemetry::init_otlp(
+        &endpoint,
+        service_name
+            .as_deref()
+            .unwrap_or("streaming-enhancement-keystore"),
+    )
+    .map_err(NapiKeystoreError::from)?)
+}
+
+/// Flushes pending spans; call before the process exits
+#[napi]
+pub fn shutdown_tracing() -> Result<(), Error> {
+    Ok(telemetry::shutdown_otlp().map_err(NapiKeystoreError::from)?)
+}
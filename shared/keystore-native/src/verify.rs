@@ -0,0 +1,17 @@
+use keystore_core::verify;
+use napi::bindgen_prelude::Buffer;
+use napi::bindgen_prelude::Either;
+use napi_derive::napi;
+
+fn as_bytes(value: &Either<String, Buffer>) -> &[u8] {
+    match value {
+        Either::A(text) => text.as_bytes(),
+        Either::B(buffer) => buffer.as_ref(),
+    }
+}
+
+/// Timing-safe equality for signatures, tokens and pairing codes
+#[napi]
+pub fn constant_time_equals(a: Either<String, Buffer>, b: Either<String, Buffer>) -> bool {
+    verify::constant_time_equals(as_bytes(&a), as_bytes(&b))
+}
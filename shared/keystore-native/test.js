@@ -64,6 +64,28 @@ try {
     throw err;
   }
   
+  const expiredAccount = 'expired-account';
+  store.setPassword(testService, expiredAccount, testValue, { expiresAt: Date.now() - 1000 });
+  try {
+    store.getPassword(testService, expiredAccount);
+    throw new Error('getPassword() should have thrown for an expired entry');
+  } catch (err) {
+    if (err.code !== 'ERR_KEY_NOT_FOUND' && !(err.message && err.message.includes('ERR_KEY_NOT_FOUND'))) {
+      throw err;
+    }
+    console.log('getPassword() throws error for expired entry');
+  }
+  
+  try {
+    store.deletePassword('streaming-enhancement:protected', 'keys');
+    throw new Error('deletePassword() should have refused a reserved service');
+  } catch (err) {
+    if (err.code !== 'ERR_ACCESS_DENIED' && !(err.message && err.message.includes('ERR_ACCESS_DENIED'))) {
+      throw err;
+    }
+    console.log('deletePassword() refuses reserved services');
+  }
+  
   console.log('\nAll tests passed!');
 } catch (err) {
   console.error(`\nTest failed: ${err.message}`);